@@ -28,7 +28,7 @@
 //! let language = SourceLanguage::new(Language::Ink, Version::new(2, 1, 0));
 //! let compiler = SourceCompiler::new(Compiler::RustC, Version::parse("1.46.0-nightly").unwrap());
 //! let wasm = SourceWasm::new(vec![0u8]);
-//! let source = Source::new(Some(wasm), CodeHash([0u8; 32]), language, compiler);
+//! let source = Source::new(Some(wasm), CodeHash([0u8; 32]), language, compiler, None);
 //! let contract = Contract::builder()
 //!     .name("incrementer".to_string())
 //!     .version(Version::new(2, 1, 0))
@@ -94,8 +94,10 @@ impl ContractMetadata {
         }
     }
 
+    /// Removes the code of the contract from the `source`, both the Wasm and the PolkaVM program.
     pub fn remove_source_wasm_attribute(&mut self) {
         self.source.wasm = None;
+        self.source.polkavm = None;
     }
 }
 
@@ -119,6 +121,14 @@ pub struct Source {
     compiler: SourceCompiler,
     #[serde(skip_serializing_if = "Option::is_none")]
     wasm: Option<SourceWasm>,
+    /// The PolkaVM program of a contract compiled for RISC-V, instead of the `wasm`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    polkavm: Option<SourceWasm>,
+    /// Extra information about the environment in which the contract was built.
+    ///
+    /// Useful for reproducing and verifying the build.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    build_info: Option<Map<String, Value>>,
 }
 
 impl Source {
@@ -128,14 +138,24 @@ impl Source {
         hash: CodeHash,
         language: SourceLanguage,
         compiler: SourceCompiler,
+        build_info: Option<Map<String, Value>>,
     ) -> Self {
         Source {
             hash,
             language,
             compiler,
             wasm,
+            polkavm: None,
+            build_info,
         }
     }
+
+    /// Moves the code to the `polkavm` attribute, for a contract compiled to a PolkaVM program
+    /// instead of Wasm.
+    pub fn into_polkavm(mut self) -> Self {
+        self.polkavm = self.wasm.take();
+        self
+    }
 }
 
 /// The bytes of the compiled Wasm smart contract.
@@ -521,7 +541,22 @@ mod tests {
         let compiler =
             SourceCompiler::new(Compiler::RustC, Version::parse("1.46.0-nightly").unwrap());
         let wasm = SourceWasm::new(vec![0u8, 1u8, 2u8]);
-        let source = Source::new(Some(wasm), CodeHash([0u8; 32]), language, compiler);
+        let build_info = json! {
+            {
+                "example_compiler_version": 42,
+                "example_settings": []
+            }
+        }
+        .as_object()
+        .unwrap()
+        .clone();
+        let source = Source::new(
+            Some(wasm),
+            CodeHash([0u8; 32]),
+            language,
+            compiler,
+            Some(build_info),
+        );
         let contract = Contract::builder()
             .name("incrementer".to_string())
             .version(Version::new(2, 1, 0))
@@ -566,7 +601,11 @@ mod tests {
                     "hash": "0x0000000000000000000000000000000000000000000000000000000000000000",
                     "language": "ink! 2.1.0",
                     "compiler": "rustc 1.46.0-nightly",
-                    "wasm": "0x000102"
+                    "wasm": "0x000102",
+                    "build_info": {
+                        "example_compiler_version": 42,
+                        "example_settings": []
+                    }
                 },
                 "contract": {
                     "name": "incrementer",
@@ -603,7 +642,7 @@ mod tests {
         let language = SourceLanguage::new(Language::Ink, Version::new(2, 1, 0));
         let compiler =
             SourceCompiler::new(Compiler::RustC, Version::parse("1.46.0-nightly").unwrap());
-        let source = Source::new(None, CodeHash([0u8; 32]), language, compiler);
+        let source = Source::new(None, CodeHash([0u8; 32]), language, compiler, None);
         let contract = Contract::builder()
             .name("incrementer".to_string())
             .version(Version::new(2, 1, 0))
@@ -648,4 +687,26 @@ mod tests {
 
         assert_eq!(json, expected);
     }
+
+    #[test]
+    fn json_of_a_polkavm_program() {
+        let language = SourceLanguage::new(Language::Ink, Version::new(2, 1, 0));
+        let compiler =
+            SourceCompiler::new(Compiler::RustC, Version::parse("1.46.0-nightly").unwrap());
+        let program = SourceWasm::new(vec![0x50, 0x56, 0x4d, 0x00]);
+        let source = Source::new(Some(program), CodeHash([0u8; 32]), language, compiler, None)
+            .into_polkavm();
+        let json = serde_json::to_value(&source).unwrap();
+
+        let expected = json! {
+            {
+                "hash": "0x0000000000000000000000000000000000000000000000000000000000000000",
+                "language": "ink! 2.1.0",
+                "compiler": "rustc 1.46.0-nightly",
+                "polkavm": "0x50564d00"
+            }
+        };
+
+        assert_eq!(json, expected);
+    }
 }
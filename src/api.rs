@@ -0,0 +1,293 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use sp_core::{
+    crypto::{AccountId32, Ss58AddressFormat},
+    H256,
+};
+
+use crate::{
+    cmd::{self, Address, BalanceVariant, DisplayEvent, ExtrinsicDetails, GasLimit, GasMargin},
+    progress::Reporter,
+    Error, ExtrinsicOpts, OutputType, ProgressHook, SignerOpts, SubmitOpts, WaitFor,
+};
+
+pub use crate::cmd::signer::Scheme;
+
+/// A node to submit extrinsics to, and the key signing them, for uploading, instantiating and
+/// calling contracts with the same logic as `cargo contract`.
+///
+/// Each operation connects to the node, and awaits the inclusion of its extrinsic. Unlike the
+/// commands, the deployments are only recorded in the `deployments.json` of the contract project
+/// in the current directory with [`Client::record_deployments`].
+pub struct Client {
+    opts: ExtrinsicOpts,
+}
+
+impl Client {
+    /// The node at the `url`, and the key pair of the secret key URI, e.g. `//Alice`, an sr25519
+    /// key pair unless another [`Client::scheme`] is selected.
+    pub fn new(url: url::Url, suri: &str) -> Self {
+        Self::with_signer(url, Some(suri.to_string()), None)
+    }
+
+    /// The node at the `url`, and the key pair of the JSON keystore file, as exported by
+    /// polkadot.js.
+    pub fn from_keyfile(url: url::Url, keyfile: PathBuf) -> Self {
+        Self::with_signer(url, None, Some(keyfile))
+    }
+
+    fn with_signer(url: url::Url, suri: Option<String>, keyfile: Option<PathBuf>) -> Self {
+        let submit = SubmitOpts {
+            urls: vec![url],
+            network: None,
+            network_profile: None,
+            output: OutputType::Json,
+            wait_for: WaitFor::InBlock,
+            tls_ca_cert: None,
+            raw: false,
+            record_deployments: false,
+            progress: Default::default(),
+        };
+        let signer_opts = SignerOpts {
+            suri,
+            keyfile,
+            password: None,
+            password_file: None,
+            scheme: Scheme::Sr25519,
+        };
+        let opts = ExtrinsicOpts {
+            submit,
+            signer_opts,
+            remote_signer: None,
+            remote_account: None,
+            qr_signer: None,
+            nonce: None,
+            lifetime: None,
+            immortal: false,
+            tip: Default::default(),
+            fee_asset: None,
+            sudo: false,
+            proxy: None,
+            multisig: Default::default(),
+            offline: Default::default(),
+            // There is nobody to confirm extrinsics transferring value.
+            yes: true,
+        };
+        Client { opts }
+    }
+
+    /// The signature scheme of the key pair derived from the secret key URI.
+    pub fn scheme(mut self, scheme: Scheme) -> Self {
+        self.opts.signer_opts.scheme = scheme;
+        self
+    }
+
+    /// The password of the secret key URI, or for decrypting the keystore file.
+    pub fn password(mut self, password: &str) -> Self {
+        self.opts.signer_opts.password = Some(password.to_string());
+        self
+    }
+
+    /// Record the uploads and instantiations in the `deployments.json` of the contract project in
+    /// the current directory, like the commands.
+    pub fn record_deployments(mut self) -> Self {
+        self.opts.submit.record_deployments = true;
+        self
+    }
+
+    /// Report the statuses of the extrinsics to the hook, e.g. to show their progress.
+    pub fn progress_hook(mut self, hook: impl ProgressHook + 'static) -> Self {
+        self.opts.submit.progress = Reporter::new(hook);
+        self
+    }
+
+    /// Await the finalization of the blocks including the extrinsics.
+    pub fn finalized(mut self) -> Self {
+        self.opts.submit.wait_for = WaitFor::Finalized;
+        self
+    }
+}
+
+/// A runtime event emitted by an extrinsic.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Event {
+    /// The name of the module which emitted the event.
+    pub module: String,
+    /// The name of the event.
+    pub variant: String,
+    /// The SCALE encoded data of the event.
+    pub data: Vec<u8>,
+}
+
+/// An extrinsic included in a block.
+#[derive(Clone, Debug)]
+pub struct Extrinsic {
+    pub hash: H256,
+    pub block: H256,
+    /// The weight consumed by the extrinsic.
+    pub weight: Option<u64>,
+    /// All events emitted while executing the extrinsic.
+    pub events: Vec<Event>,
+}
+
+impl Extrinsic {
+    fn new(details: ExtrinsicDetails) -> Result<Self> {
+        let block = details
+            .block
+            .ok_or_else(|| anyhow::anyhow!("The extrinsic was not included in a block"))?;
+        let events = details
+            .events
+            .into_iter()
+            .map(|event: DisplayEvent| Event {
+                module: event.module,
+                variant: event.variant,
+                data: event.bytes,
+            })
+            .collect();
+        Ok(Extrinsic {
+            hash: details.extrinsic,
+            block,
+            weight: details.weight,
+            events,
+        })
+    }
+}
+
+/// Result of [`upload`].
+#[derive(Clone, Debug)]
+pub struct Uploaded {
+    pub code_hash: H256,
+    /// The extrinsic uploading the code, `None` if it was already stored on the chain.
+    pub extrinsic: Option<Extrinsic>,
+}
+
+/// Result of [`instantiate`].
+#[derive(Clone, Debug)]
+pub struct Instantiated {
+    /// The address of the instantiated contract.
+    pub contract: AccountId32,
+    pub extrinsic: Extrinsic,
+}
+
+/// Result of [`call`].
+#[derive(Clone, Debug)]
+pub struct Called {
+    /// The gas limit the call was submitted with.
+    pub gas_limit: u64,
+    pub extrinsic: Extrinsic,
+}
+
+/// Uploads the Wasm code, unless it is already stored on the chain.
+pub async fn upload(client: &Client, code: &[u8]) -> Result<Uploaded, Error> {
+    let signer = client.opts.signer()?;
+    let result = cmd::upload_code(&client.opts, &signer, code).await?;
+    Ok(Uploaded {
+        code_hash: result.code_hash,
+        extrinsic: result.extrinsic.map(Extrinsic::new).transpose()?,
+    })
+}
+
+/// Instantiates a contract of the uploaded code, calling its constructor with the `data`, i.e.
+/// the selector of the constructor followed by its SCALE encoded arguments.
+pub async fn instantiate(
+    client: &Client,
+    code_hash: H256,
+    data: &[u8],
+    endowment: u128,
+    gas_limit: u64,
+) -> Result<Instantiated, Error> {
+    let signer = client.opts.signer()?;
+    let endowment = BalanceVariant::Default(endowment);
+    let result =
+        cmd::instantiate_contract(&client.opts, &signer, endowment, gas_limit, code_hash, data)
+            .await?;
+    Ok(Instantiated {
+        contract: result
+            .contract
+            .ok_or_else(|| anyhow::anyhow!("Failed to find Instantiated event"))?,
+        extrinsic: Extrinsic::new(result.extrinsic)?,
+    })
+}
+
+/// Calls the contract with the `data`, i.e. the selector of the message followed by its SCALE
+/// encoded arguments, and the gas limit estimated by a dry run unless one is supplied.
+pub async fn call(
+    client: &Client,
+    contract: &AccountId32,
+    data: &[u8],
+    value: u128,
+    gas_limit: Option<u64>,
+) -> Result<Called, Error> {
+    let contract = Address {
+        account: contract.clone(),
+        format: Ss58AddressFormat::default(),
+    };
+    let gas_limit = gas_limit.map_or(GasLimit::Auto, GasLimit::Limit);
+    let signer = client.opts.signer()?;
+    let result = cmd::call_contract(
+        &client.opts,
+        &signer,
+        &contract,
+        BalanceVariant::Default(value),
+        gas_limit,
+        GasMargin::default(),
+        false,
+        data,
+    )
+    .await?;
+    Ok(Called {
+        gas_limit: result.gas_limit,
+        extrinsic: Extrinsic::new(result.extrinsic)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_the_details_of_the_extrinsic() {
+        let details = ExtrinsicDetails {
+            block: Some(H256::repeat_byte(1)),
+            extrinsic: H256::repeat_byte(2),
+            weight: Some(10),
+            events: vec![DisplayEvent {
+                module: "Contracts".to_string(),
+                variant: "CodeStored".to_string(),
+                data: "0x0102".to_string(),
+                bytes: vec![1, 2],
+            }],
+            storage_deposits: Vec::new(),
+            multisig: None,
+            explorer: None,
+            balance_format: Default::default(),
+        };
+        let extrinsic = Extrinsic::new(details).unwrap();
+        assert_eq!(extrinsic.block, H256::repeat_byte(1));
+        assert_eq!(
+            extrinsic.events,
+            vec![Event {
+                module: "Contracts".to_string(),
+                variant: "CodeStored".to_string(),
+                data: vec![1, 2],
+            }]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::Result;
+use serde::Serialize;
+use sp_core::crypto::{AccountId32, Ss58Codec};
+
+use super::{address::address_format, balance::BalanceFormat, extrinsic::Connection};
+use crate::SubmitOpts;
+
+/// Result of a successful `account`.
+#[derive(Debug, Serialize)]
+pub(crate) struct AccountResult {
+    /// The address of the account, encoded with the address format of the chain.
+    pub account: AccountId32,
+    /// Hex encoded account id.
+    pub account_id: String,
+    /// The address re-encoded with the SS58 prefix supplied with `--ss58-prefix`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reencoded: Option<Reencoded>,
+    /// The free balance of the account, unless `--offline`.
+    pub free: Option<u128>,
+    /// The reserved balance of the account, unless `--offline`.
+    pub reserved: Option<u128>,
+    /// How the balances are printed in the human readable output.
+    #[serde(skip)]
+    pub balance_format: BalanceFormat,
+}
+
+/// The address of an account encoded with another SS58 prefix.
+#[derive(Debug, Serialize)]
+pub(crate) struct Reencoded {
+    pub ss58_prefix: u8,
+    pub address: String,
+}
+
+impl AccountResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let mut output = format!(
+            "Account: {}\n\tAccount id: {}",
+            self.account, self.account_id
+        );
+        if let Some(ref reencoded) = self.reencoded {
+            output.push_str(&format!(
+                "\n\tSS58 prefix {}: {}",
+                reencoded.ss58_prefix, reencoded.address
+            ));
+        }
+        if let (Some(free), Some(reserved)) = (self.free, self.reserved) {
+            output.push_str(&format!(
+                "\n\tFree balance: {}\n\tReserved balance: {}",
+                self.balance_format.display(free),
+                self.balance_format.display(reserved)
+            ));
+        }
+        output
+    }
+}
+
+/// Shows the address of the account, re-encoded with the `ss58_prefix` if supplied, and its
+/// balance on the chain unless `offline`.
+pub(crate) fn execute_account(
+    submit_opts: &SubmitOpts,
+    account: &AccountId32,
+    ss58_prefix: Option<u8>,
+    offline: bool,
+) -> Result<AccountResult> {
+    let mut result = AccountResult {
+        account: account.clone(),
+        account_id: format!("0x{}", hex::encode(account)),
+        reencoded: ss58_prefix.map(|ss58_prefix| Reencoded {
+            ss58_prefix,
+            address: account.to_ss58check_with_version(address_format(ss58_prefix)),
+        }),
+        free: None,
+        reserved: None,
+        balance_format: BalanceFormat::default(),
+    };
+    if offline {
+        return Ok(result);
+    }
+    async_std::task::block_on(async move {
+        let connection = Connection::new(submit_opts).await?;
+        let data = connection.account_data(account, None).await?;
+        result.free = Some(data.free);
+        result.reserved = Some(data.reserved);
+        result.balance_format = connection.balance_format();
+        Ok(result)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    #[test]
+    fn reencodes_the_address() {
+        let alice = AccountId32::from_ss58check("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY")
+            .unwrap();
+        let submit_opts = SubmitOpts::from_iter(&["account"]);
+        let result = execute_account(&submit_opts, &alice, Some(0), true).unwrap();
+        let reencoded = result.reencoded.unwrap();
+        assert_eq!(
+            reencoded.address,
+            "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5"
+        );
+        assert_eq!(
+            result.account_id,
+            "0xd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d"
+        );
+        assert_eq!(result.free, None);
+    }
+}
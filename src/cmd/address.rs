@@ -0,0 +1,98 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    convert::TryFrom,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::Result;
+use sp_core::crypto::{self, AccountId32, Ss58AddressFormat, Ss58Codec};
+
+/// Whether the address format of the chain is known, from the chain itself or the network.
+static FORMAT_KNOWN: AtomicBool = AtomicBool::new(false);
+
+/// An SS58 encoded account address, together with the address format it was encoded with.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Address {
+    pub account: AccountId32,
+    pub format: Ss58AddressFormat,
+}
+
+impl FromStr for Address {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let (account, format) = AccountId32::from_ss58check_with_version(input)
+            .map_err(|err| anyhow::anyhow!("Invalid account address {}: {:?}", input, err))?;
+        Ok(Address { account, format })
+    }
+}
+
+impl Address {
+    /// Warns if the address was encoded for another network than the chain's, which hints at
+    /// the address being mixed up.
+    pub fn check_format(&self) {
+        let chain_format = Ss58AddressFormat::default();
+        if FORMAT_KNOWN.load(Ordering::Relaxed) && self.format != chain_format {
+            log::warn!(
+                "The address {} is encoded for {}, while the chain uses {}",
+                self.account.to_ss58check_with_version(self.format),
+                self.format,
+                chain_format
+            );
+        }
+    }
+}
+
+/// Parses an SS58 encoded account address, of any address format.
+pub(crate) fn parse_address(input: &str) -> Result<Address> {
+    input.parse()
+}
+
+/// Returns the address format with the SS58 prefix.
+pub(crate) fn address_format(ss58_prefix: u8) -> Ss58AddressFormat {
+    Ss58AddressFormat::try_from(ss58_prefix).unwrap_or(Ss58AddressFormat::Custom(ss58_prefix))
+}
+
+/// Sets the address format of the chain, which the account addresses in the output are encoded
+/// with.
+pub(crate) fn set_address_format(ss58_prefix: u8) {
+    crypto::set_default_ss58_version(address_format(ss58_prefix));
+    FORMAT_KNOWN.store(true, Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_addresses_of_any_format() {
+        let alice = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+        let substrate = parse_address(alice).unwrap();
+        assert_eq!(substrate.format, Ss58AddressFormat::SubstrateAccount);
+
+        let polkadot = substrate
+            .account
+            .to_ss58check_with_version(Ss58AddressFormat::PolkadotAccount);
+        let parsed = parse_address(&polkadot).unwrap();
+        assert_eq!(parsed.account, substrate.account);
+        assert_eq!(parsed.format, Ss58AddressFormat::PolkadotAccount);
+
+        assert!(parse_address("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQZ").is_err());
+    }
+}
@@ -0,0 +1,272 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::Ss58Codec;
+use structopt::StructOpt;
+
+use super::{
+    address::{parse_address, Address},
+    network::section,
+};
+use crate::SubmitOpts;
+
+/// Name of the address book file in the home directory of the user.
+pub(crate) const ADDRESS_BOOK_FILE: &str = ".cargo-contract-addresses.toml";
+
+/// A contract supplied either by its address or by its alias in the address book.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ContractRef {
+    Address(Address),
+    Alias(String),
+}
+
+impl FromStr for ContractRef {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input.parse() {
+            Ok(address) => Ok(ContractRef::Address(address)),
+            Err(_) if is_alias(input) => Ok(ContractRef::Alias(input.to_string())),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl ContractRef {
+    /// Returns the address of the contract, looking up an alias in the address book of the
+    /// network the options connect to.
+    pub fn resolve(&self, submit_opts: &SubmitOpts) -> Result<Address> {
+        match self {
+            ContractRef::Address(address) => Ok(address.clone()),
+            ContractRef::Alias(alias) => {
+                let book = AddressBook::load(&AddressBook::path()?)?;
+                book.lookup(&section(submit_opts), alias)
+            }
+        }
+    }
+}
+
+/// Aliases consist of ASCII letters, digits, `-` and `_`.
+fn is_alias(input: &str) -> bool {
+    !input.is_empty()
+        && input
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// The contract addresses by alias, for each network.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub(crate) struct AddressBook {
+    networks: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl AddressBook {
+    /// The path of the address book in the home directory of the user.
+    pub fn path() -> Result<PathBuf> {
+        let home = env::var_os("HOME")
+            .ok_or_else(|| anyhow::anyhow!("The address book requires the HOME directory"))?;
+        Ok(PathBuf::from(home).join(ADDRESS_BOOK_FILE))
+    }
+
+    /// Reads the address book, which is empty if the file does not exist yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(AddressBook::default());
+        }
+        let toml =
+            fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+        toml::from_str(&toml).context(format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let toml = toml::to_string(self)?;
+        fs::write(path, toml).context(format!("Failed to write {}", path.display()))
+    }
+
+    /// Adds the alias of the contract, returning the address it replaced.
+    pub fn add(&mut self, network: &str, alias: &str, address: &Address) -> Option<String> {
+        let address = address.account.to_ss58check_with_version(address.format);
+        self.networks
+            .entry(network.to_string())
+            .or_default()
+            .insert(alias.to_string(), address)
+    }
+
+    /// Removes the alias, returning the address of its contract.
+    pub fn remove(&mut self, network: &str, alias: &str) -> Result<String> {
+        let contracts = self.networks.get_mut(network);
+        let address = contracts.and_then(|contracts| contracts.remove(alias));
+        if self.contracts(network).is_empty() {
+            self.networks.remove(network);
+        }
+        address.ok_or_else(|| anyhow::anyhow!("No contract `{}` on {}", alias, network))
+    }
+
+    /// Returns the address of the contract with the alias.
+    pub fn lookup(&self, network: &str, alias: &str) -> Result<Address> {
+        let address = self
+            .networks
+            .get(network)
+            .and_then(|contracts| contracts.get(alias))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No contract `{}` on {} in the address book, add it with `cargo contract \
+                     addressbook add {} <address>`",
+                    alias,
+                    network,
+                    alias
+                )
+            })?;
+        parse_address(address).context(format!("Invalid address of `{}`", alias))
+    }
+
+    /// The contract addresses by alias of the network.
+    pub fn contracts(&self, network: &str) -> BTreeMap<String, String> {
+        self.networks.get(network).cloned().unwrap_or_default()
+    }
+}
+
+/// Manage the aliases of the contracts of the network in the address book.
+#[derive(Debug, StructOpt)]
+pub(crate) enum AddressBookCommand {
+    /// Add an alias for a contract, replacing an existing one
+    #[structopt(name = "add")]
+    Add {
+        /// The alias, consisting of letters, digits, `-` and `_`
+        #[structopt(parse(try_from_str = parse_alias))]
+        alias: String,
+        /// The address of the contract
+        #[structopt(parse(try_from_str = parse_address))]
+        address: Address,
+    },
+    /// Remove the alias of a contract
+    #[structopt(name = "remove")]
+    Remove {
+        /// The alias to remove
+        alias: String,
+    },
+    /// List the aliases of the contracts
+    #[structopt(name = "list")]
+    List,
+}
+
+fn parse_alias(input: &str) -> Result<String> {
+    if !is_alias(input) {
+        anyhow::bail!("Aliases consist of letters, digits, `-` and `_`")
+    }
+    if input.parse::<Address>().is_ok() {
+        anyhow::bail!("The alias {} is a valid address itself", input)
+    }
+    Ok(input.to_string())
+}
+
+/// Result of an `addressbook` command.
+#[derive(Debug, Serialize)]
+pub(crate) struct AddressBookResult {
+    /// The section of the address book of the network.
+    pub network: String,
+    /// The contract addresses by alias of the network, after the change.
+    pub contracts: BTreeMap<String, String>,
+    #[serde(skip)]
+    summary: String,
+}
+
+impl AddressBookResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let mut output = self.summary.clone();
+        for (alias, address) in &self.contracts {
+            output.push_str(&format!("\n\t{}: {}", alias, address));
+        }
+        output
+    }
+}
+
+impl AddressBookCommand {
+    pub fn exec(&self, submit_opts: &SubmitOpts) -> Result<AddressBookResult> {
+        let path = AddressBook::path()?;
+        let network = section(submit_opts);
+        let mut book = AddressBook::load(&path)?;
+        let summary = match self {
+            AddressBookCommand::Add { alias, address } => {
+                address.check_format();
+                if let Some(replaced) = book.add(&network, alias, address) {
+                    log::warn!("Replaced the contract {} of `{}`", replaced, alias);
+                }
+                book.save(&path)?;
+                format!("Added `{}` to the address book of {}", alias, network)
+            }
+            AddressBookCommand::Remove { alias } => {
+                book.remove(&network, alias)?;
+                book.save(&path)?;
+                format!("Removed `{}` from the address book of {}", alias, network)
+            }
+            AddressBookCommand::List => format!("Address book of {}", network),
+        };
+        Ok(AddressBookResult {
+            contracts: book.contracts(&network),
+            network,
+            summary,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+
+    #[test]
+    fn parses_addresses_and_aliases() {
+        assert!(matches!(ALICE.parse(), Ok(ContractRef::Address(_))));
+        assert_eq!(
+            "dex".parse::<ContractRef>().unwrap(),
+            ContractRef::Alias("dex".to_string())
+        );
+        assert!("no alias".parse::<ContractRef>().is_err());
+        assert!(parse_alias(ALICE).is_err());
+    }
+
+    #[test]
+    fn stores_aliases_per_network() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            let path = dir.join(ADDRESS_BOOK_FILE);
+            let alice = parse_address(ALICE)?;
+            let mut book = AddressBook::load(&path)?;
+            assert_eq!(book.add("local", "dex", &alice), None);
+            book.save(&path)?;
+
+            let mut book = AddressBook::load(&path)?;
+            assert_eq!(book.lookup("local", "dex")?, alice);
+            assert!(book.lookup("shibuya", "dex").is_err());
+            assert_eq!(book.remove("local", "dex")?, ALICE);
+            assert!(book.remove("local", "dex").is_err());
+            assert!(book.networks.is_empty());
+            Ok(())
+        })
+    }
+}
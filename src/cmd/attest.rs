@@ -0,0 +1,277 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sp_core::crypto::AccountId32;
+use structopt::StructOpt;
+use subxt::sp_runtime::{traits::Verify, MultiSignature};
+
+use super::{
+    metadata::{blake2_hash, to_hex},
+    signer::ExtrinsicSigner,
+};
+use crate::{crate_metadata::CrateMetadata, util, SignerOpts};
+
+/// The extension of the attestation written next to the artifacts of a verifiable build.
+const ATTESTATION_EXTENSION: &str = "attestation.json";
+
+/// Options for signing the attestation of a verifiable build.
+#[derive(Debug, StructOpt)]
+pub(crate) struct AttestOpts {
+    /// Write a signed `<name>.attestation.json` after the verifiable build, stating the code hash
+    /// built from the sources of the commit, with the key of `--suri` or `--keyfile`
+    #[structopt(long)]
+    attest: bool,
+    #[structopt(flatten)]
+    signer_opts: SignerOpts,
+}
+
+impl AttestOpts {
+    /// Whether an attestation is requested.
+    pub fn enabled(&self) -> bool {
+        self.attest
+    }
+
+    fn signer(&self) -> Result<ExtrinsicSigner> {
+        self.signer_opts
+            .key_pair()?
+            .ok_or_else(|| anyhow::anyhow!("Signing the attestation requires --suri or --keyfile"))
+    }
+
+    /// Signs the statement of the verifiable build, and writes the attestation to the target
+    /// directory of the build.
+    pub fn attest(
+        &self,
+        crate_metadata: &CrateMetadata,
+        target_directory: &Path,
+        image: &str,
+        code_hash: &str,
+    ) -> Result<PathBuf> {
+        let statement = Statement::new(crate_metadata, target_directory, image, code_hash)?;
+        let attestation = Attestation::sign(statement, &self.signer()?)?;
+        let path = target_directory.join(format!(
+            "{}.{}",
+            crate_metadata.package_name, ATTESTATION_EXTENSION
+        ));
+        fs::write(&path, serde_json::to_string_pretty(&attestation)?)
+            .context(format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// What the signer of an attestation states about the build.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct Statement {
+    pub contract: String,
+    pub version: String,
+    /// The git commit of the sources, if the project is in a git repository.
+    pub commit: Option<String>,
+    /// Whether the sources had changes not committed yet.
+    pub dirty: bool,
+    /// Hex encoded blake2 hash of the files of the project and the `Cargo.lock`.
+    pub source_hash: String,
+    /// Hex encoded blake2 hash of the built Wasm.
+    pub code_hash: String,
+    /// The Docker image pinning the toolchain of the build.
+    pub image: String,
+    /// The versions of the toolchain, from the `build_info` of the metadata of the build.
+    pub build_info: Option<Value>,
+}
+
+impl Statement {
+    fn new(
+        crate_metadata: &CrateMetadata,
+        target_directory: &Path,
+        image: &str,
+        code_hash: &str,
+    ) -> Result<Self> {
+        let project = crate_metadata
+            .root_package
+            .manifest_path
+            .parent()
+            .expect("the manifest is in a directory");
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args(args)
+                .current_dir(project)
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+        let commit = git(&["rev-parse", "HEAD"]);
+        let dirty = commit.is_some() && git(&["status", "--porcelain"]) != Some(String::new());
+        let build_info = fs::read(target_directory.join("metadata.json"))
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Value>(&json).ok())
+            .and_then(|metadata| metadata["source"].get("build_info").cloned());
+        Ok(Statement {
+            contract: crate_metadata.package_name.clone(),
+            version: crate_metadata.root_package.version.to_string(),
+            commit,
+            dirty,
+            source_hash: source_hash(crate_metadata, project)?,
+            code_hash: code_hash.to_string(),
+            image: image.to_string(),
+            build_info,
+        })
+    }
+}
+
+/// The hash of the files of the project, skipping the `target` directory and hidden files, and
+/// of the `Cargo.lock` of its workspace.
+fn source_hash(crate_metadata: &CrateMetadata, project: &Path) -> Result<String> {
+    let mut sources = Vec::new();
+    for path in util::project_files(project)? {
+        sources.extend(path.strip_prefix(project)?.to_string_lossy().as_bytes());
+        sources.extend(fs::read(&path)?);
+    }
+    let cargo_lock = crate_metadata.cargo_meta.workspace_root.join("Cargo.lock");
+    if let Ok(cargo_lock) = fs::read(cargo_lock) {
+        sources.extend(cargo_lock);
+    }
+    Ok(to_hex(&blake2_hash(&sources).0))
+}
+
+/// The statement, signed by the account of the signer.
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Attestation {
+    pub statement: Statement,
+    pub signer: AccountId32,
+    /// Hex encoded, SCALE encoded `MultiSignature` of the JSON of the statement.
+    pub signature: String,
+}
+
+impl Attestation {
+    fn sign(statement: Statement, signer: &ExtrinsicSigner) -> Result<Self> {
+        let message = serde_json::to_vec(&statement)?;
+        let signature = async_std::task::block_on(signer.sign(&message))?;
+        Ok(Attestation {
+            statement,
+            signer: signer.account_id().clone(),
+            signature: format!("0x{}", hex::encode(signature.encode())),
+        })
+    }
+
+    /// Reads the attestation, failing unless it is signed by its signer.
+    pub fn verify(path: &Path) -> Result<Self> {
+        let json = fs::read(path).context(format!("Failed to read {}", path.display()))?;
+        let attestation: Attestation = serde_json::from_slice(&json).context(format!(
+            "Failed to parse {} as an attestation",
+            path.display()
+        ))?;
+        let bytes = hex::decode(attestation.signature.trim_start_matches("0x"))
+            .context("The signature of the attestation is not hex encoded")?;
+        let signature = MultiSignature::decode(&mut &bytes[..])
+            .context("The attestation has an invalid signature")?;
+        let message = serde_json::to_vec(&attestation.statement)?;
+        if !signature.verify(&message[..], &attestation.signer) {
+            anyhow::bail!(
+                "The attestation is not signed by its signer {}",
+                attestation.signer
+            )
+        }
+        Ok(attestation)
+    }
+
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let statement = &self.statement;
+        let mut output = format!(
+            "Signed by {}: {} {} builds to the code hash {}\n\tSource hash: {}\n\tImage: {}",
+            self.signer,
+            statement.contract,
+            statement.version,
+            statement.code_hash,
+            statement.source_hash,
+            statement.image
+        );
+        if let Some(ref commit) = statement.commit {
+            let dirty = if statement.dirty {
+                " with uncommitted changes"
+            } else {
+                ""
+            };
+            output.push_str(&format!("\n\tCommit: {}{}", commit, dirty));
+        }
+        output
+    }
+}
+
+/// Verifies the signature of the attestation, and that the Wasm hashes to its code hash.
+pub(crate) fn execute_verify_attestation(path: &Path, wasm: Option<&Path>) -> Result<Attestation> {
+    let attestation = Attestation::verify(path)?;
+    if let Some(wasm) = wasm {
+        let code = fs::read(wasm).context(format!("Failed to read {}", wasm.display()))?;
+        let code_hash = to_hex(&blake2_hash(&code).0);
+        if code_hash != attestation.statement.code_hash {
+            anyhow::bail!(
+                "{} hashes to {}, not to the attested code hash {}",
+                wasm.display(),
+                code_hash,
+                attestation.statement.code_hash
+            )
+        }
+    }
+    Ok(attestation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Scheme;
+
+    #[test]
+    fn verifies_the_signature() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            let statement = Statement {
+                contract: "flipper".to_string(),
+                version: "0.1.0".to_string(),
+                commit: Some("4f6af17".to_string()),
+                dirty: false,
+                source_hash: to_hex(&[1; 32]),
+                code_hash: to_hex(&blake2_hash(b"\0asm").0),
+                image: "paritytech/contracts-verifiable:0.8.0".to_string(),
+                build_info: Some(serde_json::json!({ "rust_toolchain": "1.49.0" })),
+            };
+            let signer = ExtrinsicSigner::from_suri(Scheme::Sr25519, "//Alice", None)?;
+            let attestation = Attestation::sign(statement, &signer)?;
+            let path = dir.join("flipper.attestation.json");
+            fs::write(&path, serde_json::to_string_pretty(&attestation)?)?;
+            let wasm = dir.join("flipper.wasm");
+            fs::write(&wasm, b"\0asm")?;
+
+            let verified = execute_verify_attestation(&path, Some(&wasm))?;
+            assert_eq!(verified.statement, attestation.statement);
+            fs::write(&wasm, b"\0asm\x01")?;
+            assert!(execute_verify_attestation(&path, Some(&wasm)).is_err());
+
+            let forged = serde_json::to_string(&attestation)?.replace("4f6af17", "0000000");
+            fs::write(&path, forged)?;
+            assert!(Attestation::verify(&path).is_err());
+            Ok(())
+        })
+    }
+}
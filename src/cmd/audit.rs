@@ -0,0 +1,521 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::Result;
+use parity_wasm::elements::{External, Instruction, Module, Type};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use super::inspect::load;
+use crate::OutputType;
+
+/// The host function calling the chain extensions of the runtime.
+const CHAIN_EXTENSION: &str = "seal_call_chain_extension";
+
+/// The host functions with side effects beyond the storage of the contract, and what they do.
+const SIDE_EFFECTS: [(&str, &str); 7] = [
+    (
+        "seal_terminate",
+        "removes the contract and transfers its balance to a beneficiary",
+    ),
+    ("seal_set_code_hash", "replaces the code of the contract"),
+    (
+        "seal_delegate_call",
+        "executes the code of another contract on the storage and balance of this one",
+    ),
+    ("seal_transfer", "transfers balance of the contract"),
+    (
+        "seal_call",
+        "calls another contract, which may transfer balance and call back",
+    ),
+    (
+        "seal_instantiate",
+        "instantiates contracts, which may transfer balance",
+    ),
+    (
+        "seal_restore_to",
+        "restores an evicted contract, removing this one",
+    ),
+];
+
+/// The compiler intrinsics of 128 bit integer arithmetic, which wraps on overflow.
+const INTRINSICS: [&str; 8] = [
+    "__multi3",
+    "__divti3",
+    "__udivti3",
+    "__modti3",
+    "__umodti3",
+    "__ashlti3",
+    "__ashrti3",
+    "__lshrti3",
+];
+
+/// The number of instructions after an arithmetic instruction searched for its overflow check.
+const CHECK_DISTANCE: usize = 4;
+
+/// Report the chain extensions, the host functions with side effects and the unchecked
+/// arithmetic the Wasm of a contract uses, as a risk profile for reviewing it
+#[derive(Debug, StructOpt)]
+#[structopt(name = "audit")]
+pub(crate) struct AuditCommand {
+    /// Path to the Wasm or the `.contract` bundle, by default the Wasm built for the contract
+    /// project in the current directory
+    #[structopt(parse(from_os_str))]
+    artifact: Option<PathBuf>,
+    /// Export the report in JSON format
+    #[structopt(
+        long,
+        default_value = "human-readable",
+        value_name = "human-readable | json | json-pjs"
+    )]
+    output: OutputType,
+}
+
+impl AuditCommand {
+    pub fn exec(&self) -> Result<String> {
+        let module = load(self.artifact.as_ref())?;
+        let report = AuditReport::new(&module);
+        self.output.format(&report, |report| report.display())
+    }
+}
+
+/// A function of the contract, with its name if the Wasm keeps a name section.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct Function {
+    pub index: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl Function {
+    fn display(&self) -> String {
+        match self.name {
+            Some(ref name) => format!("{} ({})", self.index, name),
+            None => self.index.to_string(),
+        }
+    }
+}
+
+/// A call of a chain extension.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct ChainExtensionCall {
+    /// The id of the function of the chain extension, unless it is computed at runtime.
+    pub func_id: Option<u32>,
+    pub caller: Function,
+}
+
+/// An imported host function with side effects.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct SideEffect {
+    pub host_function: String,
+    pub effect: &'static str,
+    /// The functions calling the host function, empty if it is imported but never called.
+    pub callers: Vec<Function>,
+}
+
+/// The 64 bit integer additions, subtractions and multiplications of the contract, which wrap
+/// on overflow unless their result is checked.
+///
+/// 32 bit arithmetic is not counted, as it is mostly the computation of memory addresses.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub(crate) struct Arithmetic {
+    /// The instructions followed by a comparison of their result, as overflow checks are.
+    pub checked: usize,
+    pub unchecked: usize,
+    /// The functions with unchecked instructions.
+    pub functions: Vec<Function>,
+    /// The compiler intrinsics of 128 bit arithmetic, found by the name section.
+    pub intrinsics: Vec<Function>,
+}
+
+/// Result of a successful `audit`.
+#[derive(Debug, Serialize)]
+pub(crate) struct AuditReport {
+    pub chain_extensions: Vec<ChainExtensionCall>,
+    pub side_effects: Vec<SideEffect>,
+    pub arithmetic: Arithmetic,
+    /// Whether the Wasm keeps the names of its functions, which the report refers to.
+    pub names: bool,
+}
+
+impl AuditReport {
+    /// Statically analyses the code of the module.
+    pub fn new(module: &Module) -> Self {
+        let code = Code::new(module);
+        let mut report = AuditReport {
+            chain_extensions: Vec::new(),
+            side_effects: Vec::new(),
+            arithmetic: Arithmetic::default(),
+            names: !code.names.is_empty(),
+        };
+        for (index, (module, name)) in &code.imports {
+            if let Some((_, effect)) = SIDE_EFFECTS.iter().find(|(host, _)| host == name) {
+                report.side_effects.push(SideEffect {
+                    host_function: format!("{}::{}", module, name),
+                    effect,
+                    callers: code.callers(*index),
+                });
+            }
+        }
+        for (index, body) in code.bodies() {
+            let mut unchecked = 0;
+            for (position, instruction) in body.iter().enumerate() {
+                match instruction {
+                    Instruction::Call(function) if code.is_import(*function, CHAIN_EXTENSION) => {
+                        report.chain_extensions.push(ChainExtensionCall {
+                            func_id: code.constant_arg(body, position, 0).map(|id| id as u32),
+                            caller: code.function(index),
+                        })
+                    }
+                    Instruction::I64Add | Instruction::I64Sub | Instruction::I64Mul => {
+                        let following = &body[position + 1..];
+                        let checked = following.iter().take(CHECK_DISTANCE).any(|instruction| {
+                            matches!(
+                                instruction,
+                                Instruction::I64LtU
+                                    | Instruction::I64GtU
+                                    | Instruction::I64LtS
+                                    | Instruction::I64GtS
+                            )
+                        });
+                        match checked {
+                            true => report.arithmetic.checked += 1,
+                            false => unchecked += 1,
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            if unchecked > 0 {
+                report.arithmetic.unchecked += unchecked;
+                report.arithmetic.functions.push(code.function(index));
+            }
+        }
+        for (index, name) in &code.names {
+            if INTRINSICS.contains(&name.as_str()) && !code.imports.contains_key(index) {
+                report.arithmetic.intrinsics.push(code.function(*index));
+            }
+        }
+        report
+    }
+
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let list = |functions: &[Function]| {
+            functions
+                .iter()
+                .map(Function::display)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut output = "Chain extensions:".to_string();
+        for call in &self.chain_extensions {
+            let func_id = match call.func_id {
+                Some(func_id) => format!("function {:#010x}", func_id),
+                None => "a function computed at runtime".to_string(),
+            };
+            output.push_str(&format!(
+                "\n\t{} called by function {}",
+                func_id,
+                call.caller.display()
+            ));
+        }
+        if self.chain_extensions.is_empty() {
+            output.push_str("\n\tNone");
+        }
+        output.push_str("\nHost functions with side effects:");
+        for side_effect in &self.side_effects {
+            let callers = match side_effect.callers.len() {
+                0 => "imported but never called".to_string(),
+                _ => format!("called by functions {}", list(&side_effect.callers)),
+            };
+            output.push_str(&format!(
+                "\n\t{}: {}, {}",
+                side_effect.host_function, side_effect.effect, callers
+            ));
+        }
+        if self.side_effects.is_empty() {
+            output.push_str("\n\tNone");
+        }
+        let arithmetic = &self.arithmetic;
+        output.push_str(&format!(
+            "\nUnchecked arithmetic:\n\t{} of {} 64 bit additions, subtractions and \
+             multiplications are not followed by an overflow check",
+            arithmetic.unchecked,
+            arithmetic.checked + arithmetic.unchecked
+        ));
+        if !arithmetic.functions.is_empty() {
+            output.push_str(&format!(
+                "\n\tin the functions {}",
+                list(&arithmetic.functions)
+            ));
+        }
+        if !arithmetic.intrinsics.is_empty() {
+            output.push_str(&format!(
+                "\n\t128 bit arithmetic wrapping on overflow in {}",
+                list(&arithmetic.intrinsics)
+            ));
+        }
+        if !self.names {
+            output.push_str(
+                "\nThe Wasm has no name section, so functions are only referred to by their \
+                 index and the intrinsics of 128 bit arithmetic are not detected",
+            );
+        }
+        output
+    }
+}
+
+/// The functions of a module, indexed as in its function index space.
+struct Code<'a> {
+    module: &'a Module,
+    /// The parameter and result counts of the imported functions followed by the defined ones.
+    arities: Vec<(usize, usize)>,
+    /// The module and name of the imported functions.
+    imports: BTreeMap<u32, (String, String)>,
+    names: BTreeMap<u32, String>,
+}
+
+impl<'a> Code<'a> {
+    fn new(module: &'a Module) -> Self {
+        let types = module.type_section().map_or(&[][..], |s| s.types());
+        let arity = |ty: u32| match types.get(ty as usize) {
+            Some(Type::Function(ty)) => (ty.params().len(), ty.results().len()),
+            None => (0, 0),
+        };
+        let mut arities = Vec::new();
+        let mut imports = BTreeMap::new();
+        for import in module.import_section().map_or(&[][..], |s| s.entries()) {
+            if let External::Function(ty) = import.external() {
+                let names = (import.module().to_string(), import.field().to_string());
+                imports.insert(arities.len() as u32, names);
+                arities.push(arity(*ty));
+            }
+        }
+        if let Some(section) = module.function_section() {
+            arities.extend(section.entries().iter().map(|func| arity(func.type_ref())));
+        }
+        let mut names = BTreeMap::new();
+        if let Some(subsection) = module.names_section().and_then(|names| names.functions()) {
+            for (index, name) in subsection.names().iter() {
+                names.insert(index, name.clone());
+            }
+        }
+        Code {
+            module,
+            arities,
+            imports,
+            names,
+        }
+    }
+
+    /// The instructions of the defined functions, by their index.
+    fn bodies(&self) -> impl Iterator<Item = (u32, &'a [Instruction])> {
+        let imported = self.imports.len() as u32;
+        let bodies = self.module.code_section().map_or(&[][..], |s| s.bodies());
+        bodies
+            .iter()
+            .enumerate()
+            .map(move |(index, body)| (imported + index as u32, body.code().elements()))
+    }
+
+    fn function(&self, index: u32) -> Function {
+        Function {
+            index,
+            name: self.names.get(&index).cloned(),
+        }
+    }
+
+    fn is_import(&self, index: u32, name: &str) -> bool {
+        self.imports.get(&index).map(|(_, field)| field.as_str()) == Some(name)
+    }
+
+    /// The defined functions calling the function.
+    fn callers(&self, function: u32) -> Vec<Function> {
+        self.bodies()
+            .filter(|(_, body)| body.contains(&Instruction::Call(function)))
+            .map(|(index, _)| self.function(index))
+            .collect()
+    }
+
+    /// The value of the `arg` of the call at the `position`, if it is an `i32.const`.
+    ///
+    /// Walks back from the call through the instructions pushing and popping values, until the
+    /// one pushing the argument, giving up on control flow.
+    fn constant_arg(&self, body: &[Instruction], position: usize, arg: usize) -> Option<i32> {
+        let params = match body.get(position)? {
+            Instruction::Call(function) => self.arities.get(*function as usize)?.0,
+            _ => return None,
+        };
+        // The depth of the argument below the top of the stack.
+        let mut depth = params.checked_sub(arg + 1)?;
+        for instruction in body[..position].iter().rev() {
+            let (pops, pushes) = self.stack_effect(instruction)?;
+            if depth < pushes {
+                return match instruction {
+                    Instruction::I32Const(value) if pushes == 1 => Some(*value),
+                    _ => None,
+                };
+            }
+            depth = depth - pushes + pops;
+        }
+        None
+    }
+
+    /// The number of values the instruction pops and pushes, for instructions without control
+    /// flow operating on integers.
+    fn stack_effect(&self, instruction: &Instruction) -> Option<(usize, usize)> {
+        use Instruction::*;
+        let effect = match instruction {
+            Nop => (0, 0),
+            I32Const(_) | I64Const(_) | GetLocal(_) | GetGlobal(_) | CurrentMemory(_) => (0, 1),
+            SetLocal(_) | SetGlobal(_) | Drop => (1, 0),
+            TeeLocal(_) | GrowMemory(_) => (1, 1),
+            I32Load(..) | I64Load(..) | I32Load8S(..) | I32Load8U(..) | I32Load16S(..)
+            | I32Load16U(..) | I64Load8S(..) | I64Load8U(..) | I64Load16S(..) | I64Load16U(..)
+            | I64Load32S(..) | I64Load32U(..) => (1, 1),
+            I32Store(..) | I64Store(..) | I32Store8(..) | I32Store16(..) | I64Store8(..)
+            | I64Store16(..) | I64Store32(..) => (2, 0),
+            I32Eqz | I64Eqz | I32Clz | I32Ctz | I32Popcnt | I64Clz | I64Ctz | I64Popcnt
+            | I32WrapI64 | I64ExtendSI32 | I64ExtendUI32 => (1, 1),
+            I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS
+            | I32GeU | I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU
+            | I64GeS | I64GeU | I32Add | I32Sub | I32Mul | I32DivS | I32DivU | I32RemS
+            | I32RemU | I32And | I32Or | I32Xor | I32Shl | I32ShrS | I32ShrU | I32Rotl
+            | I32Rotr | I64Add | I64Sub | I64Mul | I64DivS | I64DivU | I64RemS | I64RemU
+            | I64And | I64Or | I64Xor | I64Shl | I64ShrS | I64ShrU | I64Rotl | I64Rotr => (2, 1),
+            Select => (3, 1),
+            Call(function) => *self.arities.get(*function as usize)?,
+            _ => return None,
+        };
+        Some(effect)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parity_wasm::{
+        builder,
+        elements::{Instruction::*, Instructions, ValueType},
+    };
+
+    fn contract() -> Module {
+        let mut module = builder::module();
+        let chain_extension = module.push_signature(
+            builder::signature()
+                .with_params(vec![ValueType::I32; 5])
+                .with_result(ValueType::I32)
+                .build_sig(),
+        );
+        let two = module.push_signature(
+            builder::signature()
+                .with_params(vec![ValueType::I32; 2])
+                .build_sig(),
+        );
+        let body = |instructions: Vec<Instruction>| {
+            builder::function()
+                .signature()
+                .build()
+                .body()
+                .with_instructions(Instructions::new(instructions))
+                .build()
+                .build()
+        };
+        let args = vec![I32Const(0); 4];
+        let call = [
+            vec![I32Const(0x0001_0001)],
+            args.clone(),
+            vec![Call(0), Drop, I32Const(0), I32Const(32), Call(1)],
+            vec![I64Const(1), I64Const(2), I64Add, I64Const(1), I64LtU, Drop],
+            vec![I64Const(3), I64Const(4), I64Mul, Drop, End],
+        ]
+        .concat();
+        let computed = [
+            vec![I32Const(1), I32Const(2), I32Add],
+            args,
+            vec![Call(0), Drop, End],
+        ]
+        .concat();
+        module.push_function(body(call));
+        module.push_function(body(computed));
+        module
+            .import()
+            .module("seal0")
+            .field(CHAIN_EXTENSION)
+            .external()
+            .func(chain_extension)
+            .build()
+            .import()
+            .module("seal0")
+            .field("seal_terminate")
+            .external()
+            .func(two)
+            .build()
+            .import()
+            .module("seal0")
+            .field("seal_transfer")
+            .external()
+            .func(chain_extension)
+            .build()
+            .build()
+    }
+
+    #[test]
+    fn reports_the_capabilities_of_the_contract() {
+        let report = AuditReport::new(&contract());
+        let function = |index| Function { index, name: None };
+        assert_eq!(
+            report.chain_extensions,
+            vec![
+                ChainExtensionCall {
+                    func_id: Some(0x0001_0001),
+                    caller: function(3),
+                },
+                ChainExtensionCall {
+                    func_id: None,
+                    caller: function(4),
+                },
+            ]
+        );
+        let side_effects: Vec<_> = report
+            .side_effects
+            .iter()
+            .map(|side_effect| (side_effect.host_function.as_str(), &side_effect.callers[..]))
+            .collect();
+        assert_eq!(
+            side_effects,
+            vec![
+                ("seal0::seal_terminate", &[function(3)][..]),
+                ("seal0::seal_transfer", &[][..]),
+            ]
+        );
+        assert_eq!(
+            report.arithmetic,
+            Arithmetic {
+                checked: 1,
+                unchecked: 1,
+                functions: vec![function(3)],
+                intrinsics: Vec::new(),
+            }
+        );
+        assert!(report
+            .display()
+            .contains("function 0x00010001 called by function 3"));
+    }
+}
@@ -0,0 +1,268 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+
+/// A balance supplied on the command line.
+///
+/// Either a plain integer in the smallest unit of the chain's token, e.g. `1000000`, or an
+/// amount denominated in the token, e.g. `1.5DOT` or `250 mUNIT`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum BalanceVariant {
+    /// An amount of the smallest unit.
+    Default(u128),
+    /// An amount denominated in the chain's token.
+    Denominated {
+        /// The decimal amount, e.g. `1.5`.
+        amount: String,
+        /// The unit including an optional metric prefix, e.g. `mUNIT`.
+        unit: String,
+    },
+}
+
+impl Default for BalanceVariant {
+    fn default() -> Self {
+        BalanceVariant::Default(0)
+    }
+}
+
+impl FromStr for BalanceVariant {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let input = input.trim().replace('_', "");
+        let split = input
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(input.len());
+        let (amount, unit) = input.split_at(split);
+        if amount.is_empty() {
+            anyhow::bail!("Balance '{}' should start with a number", input)
+        }
+        let unit = unit.trim();
+        if unit.is_empty() {
+            let balance = amount.parse().context(format!(
+                "Balance '{}' without a token denomination must be an integer",
+                input
+            ))?;
+            return Ok(BalanceVariant::Default(balance));
+        }
+        Ok(BalanceVariant::Denominated {
+            amount: amount.to_string(),
+            unit: unit.to_string(),
+        })
+    }
+}
+
+impl BalanceVariant {
+    /// Returns the balance in the smallest unit of the token with the given symbol and number of
+    /// decimals.
+    pub fn denominate(&self, token_symbol: &str, token_decimals: u8) -> Result<u128> {
+        let (amount, unit) = match self {
+            BalanceVariant::Default(balance) => return Ok(*balance),
+            BalanceVariant::Denominated { amount, unit } => (amount, unit),
+        };
+        let exponent = i32::from(token_decimals) + unit_exponent(unit, token_symbol)?;
+
+        let (integer, fraction) = match amount.find('.') {
+            Some(point) => (&amount[..point], &amount[point + 1..]),
+            None => (&amount[..], ""),
+        };
+        let fraction = fraction.trim_end_matches('0');
+        if exponent < 0 || fraction.len() > exponent as usize {
+            anyhow::bail!(
+                "{}{} is smaller than the smallest unit of {}",
+                amount,
+                unit,
+                token_symbol
+            )
+        }
+        let digits = format!(
+            "{}{}{}",
+            integer,
+            fraction,
+            "0".repeat(exponent as usize - fraction.len())
+        );
+        digits
+            .parse::<u128>()
+            .context(format!("Invalid balance {}{}", amount, unit))
+    }
+
+    /// Whether the balance is zero, in any denomination.
+    pub fn is_zero(&self) -> bool {
+        match self {
+            BalanceVariant::Default(balance) => *balance == 0,
+            BalanceVariant::Denominated { amount, .. } => {
+                amount.chars().all(|c| c == '0' || c == '.')
+            }
+        }
+    }
+
+    /// Returns the balance in the smallest unit, which requires the balance not to be
+    /// denominated in a token.
+    pub fn without_denomination(&self) -> Result<u128> {
+        match self {
+            BalanceVariant::Default(balance) => Ok(*balance),
+            BalanceVariant::Denominated { amount, unit } => Err(anyhow::anyhow!(
+                "The token denomination of {}{} can not be resolved without a connection to the \
+                 node, specify the balance in the smallest unit instead",
+                amount,
+                unit
+            )),
+        }
+    }
+}
+
+/// Formats the balance in the smallest unit as an amount of the token, e.g. `1.5 UNIT`.
+pub(crate) fn display_balance(balance: u128, token_symbol: &str, token_decimals: u8) -> String {
+    let digits = format!(
+        "{:0>width$}",
+        balance,
+        width = usize::from(token_decimals) + 1
+    );
+    let (integer, fraction) = digits.split_at(digits.len() - usize::from(token_decimals));
+    let fraction = fraction.trim_end_matches('0');
+    let amount = if fraction.is_empty() {
+        integer.to_string()
+    } else {
+        format!("{}.{}", integer, fraction)
+    };
+    format!("{} {}", amount, token_symbol)
+        .trim_end()
+        .to_string()
+}
+
+/// How balances are printed in the human readable output.
+///
+/// Denominated in the chain's token, unless the node reports no token decimals or `--raw`
+/// requests the smallest unit.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BalanceFormat {
+    /// The symbol and number of decimals of the token, `None` for the smallest unit.
+    token: Option<(String, u8)>,
+}
+
+impl BalanceFormat {
+    /// Denominates balances in the token with the given symbol and number of decimals.
+    pub fn new(token_symbol: &str, token_decimals: u8) -> Self {
+        BalanceFormat {
+            token: Some((token_symbol.to_string(), token_decimals)),
+        }
+    }
+
+    /// Formats the balance in the smallest unit.
+    pub fn display(&self, balance: u128) -> String {
+        match self.token {
+            Some((ref token_symbol, token_decimals)) => {
+                display_balance(balance, token_symbol, token_decimals)
+            }
+            None => balance.to_string(),
+        }
+    }
+
+    /// Formats a change of balance, negative for a refund.
+    pub fn display_signed(&self, balance: i128) -> String {
+        let amount = self.display(balance.wrapping_abs() as u128);
+        if balance < 0 {
+            format!("-{}", amount)
+        } else {
+            amount
+        }
+    }
+}
+
+/// Returns the exponent of the metric prefix of the unit.
+fn unit_exponent(unit: &str, token_symbol: &str) -> Result<i32> {
+    if unit.eq_ignore_ascii_case(token_symbol) {
+        return Ok(0);
+    }
+    let mut chars = unit.chars();
+    let prefix = chars.next();
+    if !token_symbol.is_empty() && chars.as_str().eq_ignore_ascii_case(token_symbol) {
+        match prefix {
+            Some('G') => return Ok(9),
+            Some('M') => return Ok(6),
+            Some('k') => return Ok(3),
+            Some('m') => return Ok(-3),
+            Some('u') | Some('µ') | Some('μ') => return Ok(-6),
+            Some('n') => return Ok(-9),
+            _ => (),
+        }
+    }
+    anyhow::bail!(
+        "Unknown unit '{}', expected a denomination of {}",
+        unit,
+        token_symbol
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn denominate(input: &str) -> Result<u128> {
+        input.parse::<BalanceVariant>()?.denominate("UNIT", 12)
+    }
+
+    #[test]
+    fn parses_plain_balances() {
+        assert_eq!(
+            "1000".parse::<BalanceVariant>().unwrap(),
+            BalanceVariant::Default(1000)
+        );
+        assert_eq!(denominate("1_000").unwrap(), 1000);
+        assert!(denominate("1.5").is_err());
+    }
+
+    #[test]
+    fn denominates_balances() {
+        assert_eq!(denominate("1UNIT").unwrap(), 1_000_000_000_000);
+        assert_eq!(denominate("1.5 unit").unwrap(), 1_500_000_000_000);
+        assert_eq!(denominate("250mUNIT").unwrap(), 250_000_000_000);
+        assert_eq!(denominate("0.000001uUNIT").unwrap(), 1);
+        assert_eq!(denominate("2kUNIT").unwrap(), 2_000_000_000_000_000);
+    }
+
+    #[test]
+    fn rejects_invalid_denominations() {
+        assert!(denominate("1DOT").is_err());
+        assert!(denominate("0.0000001uUNIT").is_err());
+        assert!(denominate("UNIT").is_err());
+        assert!("1UNIT"
+            .parse::<BalanceVariant>()
+            .unwrap()
+            .without_denomination()
+            .is_err());
+    }
+
+    #[test]
+    fn displays_balances_in_the_token() {
+        assert_eq!(display_balance(1_500_000_000_000, "UNIT", 12), "1.5 UNIT");
+        assert_eq!(display_balance(1, "UNIT", 12), "0.000000000001 UNIT");
+        assert_eq!(display_balance(2_000, "UNIT", 0), "2000 UNIT");
+        assert_eq!(display_balance(0, "", 3), "0");
+    }
+
+    #[test]
+    fn formats_balances_raw_or_denominated() {
+        let format = BalanceFormat::new("UNIT", 12);
+        assert_eq!(format.display(2_500_000_000_000), "2.5 UNIT");
+        assert_eq!(format.display_signed(-1_000_000_000), "-0.001 UNIT");
+        assert_eq!(BalanceFormat::default().display(2_500), "2500");
+        assert_eq!(BalanceFormat::default().display_signed(-16), "-16");
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use codec::{Encode, Output};
+use serde::{Deserialize, Serialize};
+use subxt::{
+    contracts::{CallCall, ContractsEventsDecoder},
+    Call, DefaultNodeRuntime, Encoded, EventsDecoder, Metadata,
+};
+
+use super::{
+    address::Address,
+    balance::BalanceVariant,
+    events::ExtrinsicDetails,
+    extrinsic::Connection,
+    sign::{self, OfflineParams, SignedExtrinsic},
+};
+use crate::{ExtrinsicOpts, HexData};
+
+/// The contract calls of a batch, as read from the calls file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchFile {
+    call: Vec<BatchCall>,
+}
+
+/// A single contract call of a batch.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchCall {
+    /// SS58 address of the contract to call.
+    contract: String,
+    /// Hex encoded data to call the contract with.
+    data: String,
+    /// Balance transferred to the contract, defaults to nothing.
+    value: Option<String>,
+    /// Maximum amount of gas to be used for this call.
+    #[serde(default = "default_gas_limit")]
+    gas_limit: u64,
+}
+
+fn default_gas_limit() -> u64 {
+    500_000_000
+}
+
+/// The `Utility::batch_all` call, which dispatches either all of the calls or none of them.
+struct BatchAllCall {
+    calls: Vec<Encoded>,
+}
+
+impl Encode for BatchAllCall {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.calls.encode_to(dest)
+    }
+}
+
+impl Call<DefaultNodeRuntime> for BatchAllCall {
+    const MODULE: &'static str = "Utility";
+    const FUNCTION: &'static str = "batch_all";
+
+    fn events_decoder(decoder: &mut EventsDecoder<DefaultNodeRuntime>) {
+        decoder.with_contracts();
+    }
+}
+
+/// Result of a successful `batch`.
+#[derive(Debug, Serialize)]
+pub(crate) struct BatchResult {
+    /// The number of contract calls in the batch.
+    pub calls: usize,
+    #[serde(flatten)]
+    pub extrinsic: ExtrinsicDetails,
+}
+
+/// Load the contract calls from a TOML file with a `[[call]]` table per call.
+fn load_calls(path: &Path) -> Result<Vec<BatchCall>> {
+    let toml = fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    let file: BatchFile =
+        toml::from_str(&toml).context(format!("Failed to parse {}", path.display()))?;
+    if file.call.is_empty() {
+        anyhow::bail!("No calls found in {}", path.display())
+    }
+    Ok(file.call)
+}
+
+/// Encode the contract calls and wrap them in a `Utility::batch_all` call.
+fn batch_all_call<F>(
+    calls: &[BatchCall],
+    metadata: &Metadata,
+    denominate: F,
+) -> Result<BatchAllCall>
+where
+    F: Fn(&BalanceVariant) -> Result<u128>,
+{
+    let calls = calls
+        .iter()
+        .enumerate()
+        .map(|(index, call)| {
+            let encode = || -> Result<Encoded> {
+                let contract: Address = call.contract.parse()?;
+                contract.check_format();
+                let dest = contract.account.into();
+                let value = match call.value {
+                    Some(ref value) => denominate(&value.parse()?)?,
+                    None => 0,
+                };
+                let data: HexData = call.data.parse().context("Invalid hex data")?;
+                sign::encode_call(
+                    metadata,
+                    CallCall::<DefaultNodeRuntime> {
+                        dest: &dest,
+                        value,
+                        gas_limit: call.gas_limit,
+                        data: &data.0,
+                    },
+                )
+            };
+            encode().context(format!("Invalid call #{}", index + 1))
+        })
+        .collect::<Result<_>>()?;
+    Ok(BatchAllCall { calls })
+}
+
+/// Dispatch several contract calls atomically, in a single `Utility::batch_all` extrinsic.
+///
+/// The calls are read from a TOML file, for example:
+///
+/// ```toml
+/// [[call]]
+/// contract = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"
+/// data = "0x..."
+/// value = "1UNIT"
+/// gas_limit = 500000000
+/// ```
+pub(crate) fn execute_batch(extrinsic_opts: &ExtrinsicOpts, path: &Path) -> Result<BatchResult> {
+    let calls = load_calls(path)?;
+    let signer = extrinsic_opts.signer()?;
+
+    async_std::task::block_on(async move {
+        let mut connection = Connection::new(&extrinsic_opts.submit).await?;
+
+        let call = batch_all_call(&calls, connection.metadata(), |balance| {
+            connection.denominate(balance)
+        })?;
+        let mut value = 0u128;
+        for call in &calls {
+            if let Some(ref balance) = call.value {
+                value = value.saturating_add(connection.denominate(&balance.parse()?)?);
+            }
+        }
+        let submitted = connection
+            .submit_transferring(call, &signer, extrinsic_opts, value)
+            .await?;
+
+        Ok(BatchResult {
+            calls: calls.len(),
+            extrinsic: ExtrinsicDetails::new(&submitted)?,
+        })
+    })
+}
+
+/// Sign the `Utility::batch_all` extrinsic for the contract calls without submitting it.
+pub(crate) fn sign_batch(
+    extrinsic_opts: &ExtrinsicOpts,
+    params: &OfflineParams,
+    path: &Path,
+) -> Result<SignedExtrinsic> {
+    let calls = load_calls(path)?;
+    let call = batch_all_call(
+        &calls,
+        params.metadata(),
+        BalanceVariant::without_denomination,
+    )?;
+    sign::sign_offline(call, extrinsic_opts, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_calls_file() {
+        crate::util::tests::with_tmp_dir(|path| {
+            let calls_path = path.join("calls.toml");
+            fs::write(
+                &calls_path,
+                r#"
+[[call]]
+contract = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"
+data = "0x00"
+
+[[call]]
+contract = "5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty"
+data = "0x01"
+value = "1UNIT"
+gas_limit = 1000
+"#,
+            )?;
+            let calls = load_calls(&calls_path)?;
+
+            assert_eq!(calls.len(), 2);
+            assert_eq!(calls[0].gas_limit, default_gas_limit());
+            assert_eq!(calls[0].value, None);
+            assert_eq!(calls[1].gas_limit, 1000);
+            assert_eq!(calls[1].value.as_deref(), Some("1UNIT"));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn rejects_empty_calls_file() {
+        crate::util::tests::with_tmp_dir(|path| {
+            let calls_path = path.join("calls.toml");
+            fs::write(&calls_path, "call = []")?;
+            assert!(load_calls(&calls_path).is_err());
+            Ok(())
+        })
+    }
+}
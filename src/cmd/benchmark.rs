@@ -0,0 +1,475 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use sp_core::crypto::AccountId32;
+use structopt::StructOpt;
+
+use super::{
+    call::{self, DryRunRequest},
+    deployments::resolve_contract,
+    extrinsic::Connection,
+    fuzz::generate_args,
+    gas::DRY_RUN_GAS_LIMIT,
+    sandbox::{Sandbox, SandboxOpts},
+    transcode::{InkMetadata, Message},
+    BalanceVariant,
+};
+use crate::SubmitOpts;
+
+/// Dry run the messages of the contract several times, reporting the minimum, median and maximum
+/// gas, proof size and storage deposit of each
+#[derive(Debug, StructOpt)]
+#[structopt(name = "benchmark")]
+pub(crate) struct BenchmarkCommand {
+    /// The label of a message to benchmark, by default every message of the contract
+    #[structopt(long)]
+    message: Vec<String>,
+    /// The arguments of a message as a JSON array, e.g. `transfer=["5Grw...", 100]`, used for
+    /// each of its runs. The arguments of the other messages are generated for each run
+    #[structopt(long)]
+    args: Vec<MessageArgs>,
+    /// The number of dry runs of each message
+    #[structopt(long, default_value = "10")]
+    runs: u64,
+    /// The seed the generated arguments of the first run are derived from. Each following run
+    /// uses the next seed
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+    /// The balance transferred to the contract with each message
+    #[structopt(long, default_value = "0")]
+    value: BalanceVariant,
+    /// Dry run the messages in the in-memory sandbox instead of on the node
+    #[structopt(long)]
+    sandbox: bool,
+    #[structopt(flatten)]
+    sandbox_opts: SandboxOpts,
+    #[structopt(flatten)]
+    pub submit_opts: SubmitOpts,
+}
+
+/// The arguments supplied for a message, as `<message>=<JSON array>`.
+#[derive(Debug)]
+struct MessageArgs {
+    message: String,
+    args: Vec<String>,
+}
+
+impl FromStr for MessageArgs {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let (message, args) = input
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Expected <message>=<JSON array of the arguments>"))?;
+        let args: Vec<Value> = serde_json::from_str(args)
+            .context(format!("The arguments of `{}` are no JSON array", message))?;
+        Ok(MessageArgs {
+            message: message.to_string(),
+            args: args.iter().map(Value::to_string).collect(),
+        })
+    }
+}
+
+/// The resources consumed by a dry run which did not fail, though it may have reverted.
+///
+/// The node reports the weight and storage deposit charged, the sandbox the instructions executed
+/// and the bytes of storage read and added instead.
+#[derive(Default)]
+struct Sample {
+    ref_time: Option<u64>,
+    proof_size: Option<u64>,
+    storage_deposit: Option<i128>,
+    instructions: Option<u64>,
+    storage_read: Option<u64>,
+    storage_added: Option<u64>,
+}
+
+/// Executes the dry runs, on the node or in the sandbox.
+enum Backend {
+    Node {
+        connection: Box<Connection>,
+        origin: AccountId32,
+        contract: AccountId32,
+    },
+    Sandbox {
+        deployed: Sandbox,
+        caller: AccountId32,
+    },
+}
+
+impl Backend {
+    /// Dry runs the message with the `input`, `None` if it failed, e.g. trapped.
+    ///
+    /// In the sandbox, no storage is added if the message reverted.
+    fn dry_run(&self, value: u128, input: &[u8]) -> Result<Option<Sample>> {
+        match self {
+            Backend::Node {
+                connection,
+                origin,
+                contract,
+            } => {
+                let request = DryRunRequest {
+                    origin,
+                    dest: contract,
+                    value,
+                    data: input,
+                    at: None,
+                };
+                let result = async_std::task::block_on(call::dry_run(
+                    connection,
+                    request,
+                    DRY_RUN_GAS_LIMIT,
+                ))?;
+                if result.error.is_some() {
+                    return Ok(None);
+                }
+                Ok(Some(Sample {
+                    ref_time: Some(result.gas_consumed),
+                    proof_size: result.proof_size,
+                    storage_deposit: result.storage_deposit,
+                    ..Default::default()
+                }))
+            }
+            Backend::Sandbox { deployed, caller } => {
+                let execution = deployed.clone().call(caller, value, input.to_vec())?;
+                if execution.trap.is_some() {
+                    return Ok(None);
+                }
+                let storage_added = match execution.reverted {
+                    true => 0,
+                    false => execution.storage_added,
+                };
+                Ok(Some(Sample {
+                    instructions: Some(execution.instructions),
+                    storage_read: Some(execution.storage_read),
+                    storage_added: Some(storage_added),
+                    ..Default::default()
+                }))
+            }
+        }
+    }
+}
+
+/// The minimum, median and maximum of the measurements of the runs which did not fail.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct Stats<T> {
+    pub min: T,
+    pub median: T,
+    pub max: T,
+}
+
+impl<T: Copy + Ord> Stats<T> {
+    /// The statistics of the values, `None` if there are none.
+    fn new(mut values: Vec<T>) -> Option<Self> {
+        values.sort_unstable();
+        Some(Stats {
+            min: *values.first()?,
+            median: values[values.len() / 2],
+            max: *values.last()?,
+        })
+    }
+
+    /// The statistics of a measurement of the samples, `None` unless every sample has it.
+    fn of(samples: &[Sample], measurement: impl Fn(&Sample) -> Option<T>) -> Option<Self> {
+        samples
+            .iter()
+            .map(measurement)
+            .collect::<Option<_>>()
+            .and_then(Stats::new)
+    }
+}
+
+impl<T: std::fmt::Display> Stats<T> {
+    fn display(stats: &Option<Self>) -> String {
+        match stats {
+            Some(stats) => format!("{} / {} / {}", stats.min, stats.median, stats.max),
+            None => "-".to_string(),
+        }
+    }
+}
+
+/// The measurements of a message, of the runs which did not fail.
+#[derive(Debug, Serialize)]
+pub(crate) struct MessageBenchmark {
+    pub message: String,
+    /// The number of runs which failed, e.g. trapped, and are not measured.
+    pub failed: u64,
+    pub ref_time: Option<Stats<u64>>,
+    pub proof_size: Option<Stats<u64>>,
+    pub storage_deposit: Option<Stats<i128>>,
+    /// The number of instructions executed in the sandbox.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<Stats<u64>>,
+    /// The bytes of storage read in the sandbox.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_read: Option<Stats<u64>>,
+    /// The bytes of storage added in the sandbox.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_added: Option<Stats<u64>>,
+}
+
+/// Result of a `benchmark`.
+#[derive(Debug, Serialize)]
+pub(crate) struct BenchmarkResult {
+    /// The url of the node, or `sandbox`.
+    pub backend: String,
+    /// Whether the messages were dry run in the sandbox, which measures other resources.
+    #[serde(skip)]
+    pub sandbox: bool,
+    pub runs: u64,
+    pub messages: Vec<MessageBenchmark>,
+}
+
+impl BenchmarkResult {
+    /// The human readable output, a table of the minimum, median and maximum of each message.
+    pub fn display(&self) -> String {
+        let header = if self.sandbox {
+            [
+                "Message",
+                "Failed",
+                "Instructions",
+                "Storage read (bytes)",
+                "Storage added (bytes)",
+            ]
+        } else {
+            [
+                "Message",
+                "Failed",
+                "Ref time",
+                "Proof size",
+                "Storage deposit",
+            ]
+        };
+        let rows = self
+            .messages
+            .iter()
+            .map(|benchmark| {
+                let measurements = if self.sandbox {
+                    [
+                        Stats::display(&benchmark.instructions),
+                        Stats::display(&benchmark.storage_read),
+                        Stats::display(&benchmark.storage_added),
+                    ]
+                } else {
+                    [
+                        Stats::display(&benchmark.ref_time),
+                        Stats::display(&benchmark.proof_size),
+                        Stats::display(&benchmark.storage_deposit),
+                    ]
+                };
+                let [first, second, third] = measurements;
+                [
+                    benchmark.message.clone(),
+                    benchmark.failed.to_string(),
+                    first,
+                    second,
+                    third,
+                ]
+            })
+            .collect::<Vec<_>>();
+        let widths = (0..header.len())
+            .map(|column| {
+                let cells = rows.iter().map(|row| row[column].len());
+                cells.chain(Some(header[column].len())).max().unwrap_or(0)
+            })
+            .collect::<Vec<_>>();
+        let line = |cells: Vec<&str>| {
+            let cells = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width));
+            format!("\n\t{}", cells.collect::<Vec<_>>().join("  ").trim_end())
+        };
+        let mut output = format!(
+            "Dry ran each message {} times on {}, min / median / max:\n",
+            self.runs, self.backend
+        );
+        output.push_str(&line(header.to_vec()));
+        for row in &rows {
+            output.push_str(&line(row.iter().map(String::as_str).collect()));
+        }
+        output
+    }
+}
+
+impl BenchmarkCommand {
+    /// Dry runs each message `runs` times, with the supplied arguments or ones generated from
+    /// the seed of the run.
+    pub fn exec(&self) -> Result<String> {
+        let output = self.submit_opts.output;
+        let metadata = self.sandbox_opts.metadata(output)?;
+        let messages = match self.message.is_empty() {
+            true => metadata.messages()?,
+            false => self
+                .message
+                .iter()
+                .map(|label| metadata.find_message(Some(label), ""))
+                .collect::<Result<_>>()?,
+        };
+        if let Some(args) = self
+            .args
+            .iter()
+            .find(|args| !messages.iter().any(|message| message.label == args.message))
+        {
+            anyhow::bail!("No message `{}` is benchmarked", args.message)
+        }
+
+        let caller = self.sandbox_opts.caller.account.clone();
+        let (backend, name, value) = if self.sandbox {
+            let deployed = self.sandbox_opts.deploy(&metadata)?;
+            let value = self.value.without_denomination()?;
+            let backend = Backend::Sandbox { deployed, caller };
+            (backend, "the sandbox".to_string(), value)
+        } else {
+            if self.sandbox_opts.fork.is_some() {
+                anyhow::bail!("--fork dry runs the messages in the sandbox, pass --sandbox")
+            }
+            let contract =
+                resolve_contract(self.sandbox_opts.contract.as_ref(), &self.submit_opts)?;
+            contract.check_format();
+            let connection = async_std::task::block_on(Connection::new(&self.submit_opts))?;
+            let value = connection.denominate(&self.value)?;
+            let name = self.submit_opts.urls[0].to_string();
+            let backend = Backend::Node {
+                connection: Box::new(connection),
+                origin: caller,
+                contract: contract.account,
+            };
+            (backend, name, value)
+        };
+
+        let mut result = BenchmarkResult {
+            backend: name,
+            sandbox: self.sandbox,
+            runs: self.runs,
+            messages: Vec::new(),
+        };
+        for message in &messages {
+            let args = self.args.iter().find(|args| args.message == message.label);
+            result
+                .messages
+                .push(self.benchmark(&metadata, message, args, &backend, value)?);
+        }
+        output.format(&result, |result| result.display())
+    }
+
+    /// Dry runs the message `runs` times, collecting the measurements of the runs which did not
+    /// fail.
+    fn benchmark(
+        &self,
+        metadata: &InkMetadata,
+        message: &Message,
+        args: Option<&MessageArgs>,
+        backend: &Backend,
+        value: u128,
+    ) -> Result<MessageBenchmark> {
+        let accounts = match backend {
+            Backend::Node {
+                origin, contract, ..
+            } => [origin.clone(), contract.clone()],
+            Backend::Sandbox { deployed, caller } => [caller.clone(), deployed.contract.clone()],
+        };
+        let accounts = [
+            &accounts[..],
+            &[AccountId32::from([0; 32]), AccountId32::from([1; 32])],
+        ]
+        .concat();
+        let mut samples = Vec::new();
+        for run in 0..self.runs {
+            let args = match args {
+                Some(args) => args.args.clone(),
+                None => {
+                    let seed = self.seed.wrapping_add(run);
+                    let args = generate_args(metadata, message, &accounts, seed)?;
+                    args.iter().map(Value::to_string).collect()
+                }
+            };
+            let input = metadata.encode_call(message, &args)?;
+            if let Some(sample) = backend.dry_run(value, &input)? {
+                samples.push(sample);
+            }
+        }
+        Ok(MessageBenchmark {
+            message: message.label.clone(),
+            failed: self.runs - samples.len() as u64,
+            ref_time: Stats::of(&samples, |sample| sample.ref_time),
+            proof_size: Stats::of(&samples, |sample| sample.proof_size),
+            storage_deposit: Stats::of(&samples, |sample| sample.storage_deposit),
+            instructions: Stats::of(&samples, |sample| sample.instructions),
+            storage_read: Stats::of(&samples, |sample| sample.storage_read),
+            storage_added: Stats::of(&samples, |sample| sample.storage_added),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::sandbox::tests::contract;
+
+    #[test]
+    fn computes_the_stats_of_the_samples() {
+        assert_eq!(Stats::<u64>::new(vec![]), None);
+        let stats = Stats::new(vec![5, 1, 3, 9]).unwrap();
+        assert_eq!(
+            stats,
+            Stats {
+                min: 1,
+                median: 5,
+                max: 9
+            }
+        );
+    }
+
+    #[test]
+    fn measures_dry_runs_in_the_sandbox() {
+        let caller = AccountId32::from([1; 32]);
+        let mut deployed = Sandbox::new(contract(), 1_000);
+        assert!(deployed.deploy(&caller, 0, vec![]).unwrap().succeeded());
+        let backend = Backend::Sandbox { deployed, caller };
+        let sample = backend.dry_run(0, &[1, 2]).unwrap().unwrap();
+        assert!(sample.instructions.unwrap() > 0);
+        assert_eq!(sample.storage_added, Some(0));
+        assert_eq!((sample.ref_time, sample.storage_deposit), (None, None));
+        assert!(backend.dry_run(0, &[1, 0xff]).unwrap().is_none());
+
+        let result = BenchmarkResult {
+            backend: "the sandbox".to_string(),
+            sandbox: true,
+            runs: 1,
+            messages: vec![MessageBenchmark {
+                message: "get".to_string(),
+                failed: 0,
+                ref_time: None,
+                proof_size: None,
+                storage_deposit: None,
+                instructions: Stats::of(&[sample], |sample| sample.instructions),
+                storage_read: None,
+                storage_added: None,
+            }],
+        };
+        assert!(result.display().contains("Instructions"));
+        assert!(!result.display().contains("Ref time"));
+        let json = serde_json::to_value(&result).unwrap();
+        assert!(json["messages"][0]["instructions"]["min"].as_u64().unwrap() > 0);
+        assert!(json["messages"][0]["ref_time"].is_null());
+    }
+}
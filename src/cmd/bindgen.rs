@@ -0,0 +1,327 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{fmt, fs, path::PathBuf, str::FromStr};
+
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use structopt::StructOpt;
+
+use super::metadata_format::{read, take_latest, Registry};
+
+/// The languages to generate bindings in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Language {
+    /// TypeScript types for polkadot.js.
+    TypeScript,
+    /// A Rust client module calling the contract through a transport, e.g. implemented with
+    /// subxt.
+    Rust,
+    /// A best-effort Solidity ABI JSON, for tools expecting one.
+    SolidityAbi,
+}
+
+impl FromStr for Language {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input {
+            "ts" | "typescript" => Ok(Language::TypeScript),
+            "rust" | "rs" => Ok(Language::Rust),
+            "sol-abi" | "solidity-abi" => Ok(Language::SolidityAbi),
+            _ => anyhow::bail!(
+                "Bindings can be generated in `ts`, `rust` or `sol-abi`, not in `{}`",
+                input
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Language::TypeScript => write!(f, "ts"),
+            Language::Rust => write!(f, "rust"),
+            Language::SolidityAbi => write!(f, "sol-abi"),
+        }
+    }
+}
+
+/// Generates typed bindings for the contract from its metadata.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "bindgen")]
+pub(crate) struct BindgenCommand {
+    /// Path to the `metadata.json` or `.contract` bundle
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+    /// The language of the bindings
+    #[structopt(long, value_name = "ts | rust | sol-abi")]
+    lang: Language,
+    /// The file to write the bindings to, instead of printing them
+    #[structopt(long, short, parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+impl BindgenCommand {
+    pub fn exec(&self) -> Result<String> {
+        let mut metadata = read(&self.path)?;
+        let abi = take_latest(&mut metadata).context(format!(
+            "Failed to read the metadata {}",
+            self.path.display()
+        ))?;
+        let contract = Contract::new(&metadata, &abi);
+        let bindings = match self.lang {
+            Language::TypeScript => super::bindgen_ts::generate(&contract),
+            Language::Rust => super::bindgen_rust::generate(&contract),
+            Language::SolidityAbi => super::bindgen_abi::generate(&contract),
+        };
+        match self.output {
+            Some(ref output) => {
+                fs::write(output, bindings)
+                    .context(format!("Failed to write {}", output.display()))?;
+                Ok(format!(
+                    "Generated the {} bindings of {} to {}",
+                    self.lang,
+                    contract.name,
+                    output.display()
+                ))
+            }
+            None => Ok(bindings),
+        }
+    }
+}
+
+/// The ABI of the contract to generate bindings for.
+pub(crate) struct Contract<'a> {
+    pub name: String,
+    pub version: String,
+    pub docs: Vec<String>,
+    pub registry: Registry<'a>,
+    pub constructors: Vec<Function>,
+    pub messages: Vec<Function>,
+    pub events: Vec<Function>,
+}
+
+/// A constructor, message or event.
+pub(crate) struct Function {
+    pub label: String,
+    /// `None` for events.
+    pub selector: Option<String>,
+    pub docs: Vec<String>,
+    pub args: Vec<Arg>,
+    /// The id of the return type, `None` for constructors, events and messages returning `()`.
+    pub returns: Option<u64>,
+    pub mutates: bool,
+    pub payable: bool,
+}
+
+/// An argument of a constructor or message, or a field of an event.
+pub(crate) struct Arg {
+    pub label: String,
+    pub ty: u64,
+    pub indexed: bool,
+}
+
+impl<'a> Contract<'a> {
+    pub fn new(metadata: &Map<String, Value>, abi: &'a Map<String, Value>) -> Self {
+        let section = |section: &str| {
+            abi.get("spec")
+                .and_then(|spec| spec[section].as_array())
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+                .iter()
+                .map(Function::new)
+                .collect()
+        };
+        let contract = metadata.get("contract").unwrap_or(&Value::Null);
+        let string = |value: &Value| value.as_str().unwrap_or_default().to_string();
+        Contract {
+            name: string(&contract["name"]),
+            version: string(&contract["version"]),
+            docs: abi
+                .get("spec")
+                .map(|spec| strings(&spec["docs"]))
+                .unwrap_or_default(),
+            registry: Registry::new(abi),
+            constructors: section("constructors"),
+            messages: section("messages"),
+            events: section("events"),
+        }
+    }
+}
+
+impl Function {
+    fn new(item: &Value) -> Self {
+        let args = item["args"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .map(|arg| Arg {
+                label: arg["label"].as_str().unwrap_or_default().to_string(),
+                ty: arg["type"]["type"].as_u64().unwrap_or_default(),
+                indexed: arg["indexed"].as_bool() == Some(true),
+            })
+            .collect();
+        Function {
+            label: item["label"].as_str().unwrap_or_default().to_string(),
+            selector: item["selector"].as_str().map(str::to_string),
+            docs: strings(&item["docs"]),
+            args,
+            returns: item["returnType"]["type"].as_u64(),
+            mutates: item["mutates"].as_bool() == Some(true),
+            payable: item["payable"].as_bool() == Some(true),
+        }
+    }
+}
+
+/// The strings of the array, e.g. of the lines of a doc comment.
+fn strings(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect()
+}
+
+/// The words of a label, e.g. `get`, `total` and `supply` of `Erc20::get_total_supply`.
+fn words(label: &str) -> impl Iterator<Item = &str> {
+    label
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+}
+
+/// The label in `camelCase`, the way polkadot.js names messages and arguments.
+pub(crate) fn camel_case(label: &str) -> String {
+    let pascal = pascal_case(label);
+    let mut chars = pascal.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// The label in `PascalCase`.
+pub(crate) fn pascal_case(label: &str) -> String {
+    words(label)
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// The label in `snake_case`, e.g. `erc20_transfer` for `Erc20::transfer`.
+pub(crate) fn snake_case(label: &str) -> String {
+    words(label)
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde_json::{json, Map, Value};
+
+    /// The contract section of the metadata and its ABI, as read from a metadata file.
+    pub fn contract(metadata: Value, abi: Value) -> (Map<String, Value>, Map<String, Value>) {
+        (
+            serde_json::from_value(metadata).unwrap(),
+            serde_json::from_value(abi).unwrap(),
+        )
+    }
+
+    /// The metadata of an ERC-20 contract, with a documented constructor, a mutating message
+    /// returning a `Result`, a read-only message and an event with an indexed `Option`.
+    pub fn erc20() -> (Map<String, Value>, Map<String, Value>) {
+        let metadata = json!({ "contract": { "name": "erc20", "version": "0.1.0" } });
+        let abi = json!({
+            "spec": {
+                "constructors": [{
+                    "label": "new",
+                    "selector": "0x9bae9d5e",
+                    "args": [{ "label": "initial_supply", "type": { "type": 1 } }],
+                    "docs": ["Creates a new ERC-20 contract."]
+                }],
+                "messages": [{
+                    "label": "Erc20::transfer",
+                    "selector": "0x84a15da1",
+                    "mutates": true,
+                    "args": [
+                        { "label": "to", "type": { "type": 2 } },
+                        { "label": "value", "type": { "type": 1 } }
+                    ],
+                    "returnType": { "type": 4 }
+                }, {
+                    "label": "total_supply",
+                    "selector": "0xdb6375a8",
+                    "args": [],
+                    "returnType": { "type": 1 }
+                }],
+                "events": [{
+                    "label": "Transfer",
+                    "args": [
+                        { "label": "from", "type": { "type": 5 }, "indexed": true },
+                        { "label": "value", "type": { "type": 1 }, "indexed": false }
+                    ]
+                }]
+            },
+            "types": [
+                { "id": 0, "type": { "def": { "primitive": "u8" } } },
+                { "id": 1, "type": { "def": { "primitive": "u128" } } },
+                {
+                    "id": 2,
+                    "type": {
+                        "path": ["ink_env", "types", "AccountId"],
+                        "def": { "composite": { "fields": [{ "type": 3 }] } }
+                    }
+                },
+                { "id": 3, "type": { "def": { "array": { "len": 32, "type": 0 } } } },
+                {
+                    "id": 4,
+                    "type": {
+                        "path": ["Result"],
+                        "params": [{ "name": "T", "type": 6 }, { "name": "E", "type": 7 }],
+                        "def": { "variant": {} }
+                    }
+                },
+                {
+                    "id": 5,
+                    "type": {
+                        "path": ["Option"],
+                        "params": [{ "name": "T", "type": 2 }],
+                        "def": { "variant": {} }
+                    }
+                },
+                { "id": 6, "type": { "def": { "tuple": [] } } },
+                {
+                    "id": 7,
+                    "type": {
+                        "path": ["erc20", "Error"],
+                        "def": { "variant": { "variants": [{ "name": "InsufficientBalance" }] } }
+                    }
+                }
+            ]
+        });
+        contract(metadata, abi)
+    }
+}
@@ -0,0 +1,347 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{
+    bindgen::{camel_case, Contract, Function},
+    metadata_format::Registry,
+};
+
+/// An entry of a Solidity ABI: a constructor, function or event.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    inputs: Vec<Param>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outputs: Option<Vec<Param>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_mutability: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    anonymous: Option<bool>,
+}
+
+/// An input or output of a function, a field of an event or a component of a tuple.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Param {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    /// The Rust name of the type, as Solidity tools name struct and enum types.
+    internal_type: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    components: Vec<Param>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    indexed: Option<bool>,
+}
+
+/// Maps the types of the registry to the closest Solidity types.
+struct Types<'a> {
+    registry: &'a Registry<'a>,
+    /// The types being mapped, to detect their recursion.
+    mapping: BTreeSet<u64>,
+}
+
+impl Param {
+    fn new(name: &str, ty: &str, internal_type: String) -> Self {
+        Param {
+            name: name.to_string(),
+            ty: ty.to_string(),
+            internal_type,
+            components: Vec::new(),
+            indexed: None,
+        }
+    }
+}
+
+impl<'a> Types<'a> {
+    fn param(&mut self, name: &str, id: u64) -> Param {
+        let mut param = Param::new(name, "bytes", self.registry.name(id));
+        let ty = match self.registry.get(id) {
+            // Recursive types are passed as their SCALE encoding.
+            Some(ty) if self.mapping.insert(id) => ty,
+            _ => return param,
+        };
+        let def = &ty["def"];
+        let path = ty["path"].as_array().and_then(|path| path.last());
+        let params = Registry::params(ty);
+        let reference = |key: &str| def[key]["type"].as_u64();
+        match path.and_then(Value::as_str) {
+            Some("Option") if params.len() == 1 => {
+                param.ty = "tuple".to_string();
+                param.components = vec![
+                    Param::new("isSome", "bool", "bool".to_string()),
+                    self.param("value", params[0]),
+                ];
+            }
+            _ => {
+                if let Some(primitive) = def["primitive"].as_str() {
+                    param.ty = primitive_type(primitive).to_string();
+                } else if let Some(element) = reference("sequence") {
+                    param.ty = match self.is_u8(element) {
+                        true => "bytes".to_string(),
+                        false => format!("{}[]", self.element(&mut param, element)),
+                    };
+                } else if let Some(element) = reference("array") {
+                    let len = def["array"]["len"].as_u64().unwrap_or_default();
+                    param.ty = match self.is_u8(element) && (1..=32).contains(&len) {
+                        true => format!("bytes{}", len),
+                        false => format!("{}[{}]", self.element(&mut param, element), len),
+                    };
+                } else if let Some(inner) = reference("compact") {
+                    param = Param {
+                        name: param.name,
+                        internal_type: param.internal_type,
+                        ..self.param("", inner)
+                    };
+                } else if let Some(Value::Array(ids)) = def.get("tuple") {
+                    param.ty = "tuple".to_string();
+                    param.components = ids
+                        .iter()
+                        .enumerate()
+                        .map(|(index, id)| {
+                            let id = id.as_u64().unwrap_or(u64::MAX);
+                            self.param(&format!("_{}", index), id)
+                        })
+                        .collect();
+                } else if let Some(composite) = def.get("composite") {
+                    let fields = self.fields(&composite["fields"]);
+                    match fields.as_slice() {
+                        // Newtypes, e.g. `AccountId`, are encoded as their field.
+                        [_] if composite["fields"][0]["name"].is_null() => {
+                            let field = fields.into_iter().next().expect("one field");
+                            param.ty = field.ty;
+                            param.components = field.components;
+                        }
+                        _ => {
+                            param.ty = "tuple".to_string();
+                            param.internal_type = format!("struct {}", param.internal_type);
+                            param.components = fields;
+                        }
+                    }
+                } else if let Some(variant) = def.get("variant") {
+                    let variants = variant["variants"]
+                        .as_array()
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
+                    let basic = variants.iter().all(|variant| {
+                        let fields = variant["fields"].as_array().map(Vec::as_slice);
+                        fields.unwrap_or_default().is_empty()
+                    });
+                    param.ty = "uint8".to_string();
+                    if basic {
+                        param.internal_type = format!("enum {}", param.internal_type);
+                    } else {
+                        // The index of the variant, and the fields of each variant.
+                        let mut components =
+                            vec![Param::new("variant", "uint8", "uint8".to_string())];
+                        for variant in variants {
+                            let name = variant["name"].as_str().unwrap_or_default();
+                            let mut fields =
+                                Param::new(&camel_case(name), "tuple", name.to_string());
+                            fields.components = self.fields(&variant["fields"]);
+                            components.push(fields);
+                        }
+                        param.ty = "tuple".to_string();
+                        param.components = components;
+                    }
+                }
+            }
+        }
+        self.mapping.remove(&id);
+        param
+    }
+
+    /// The element type of a sequence or array, taking over its components.
+    fn element(&mut self, param: &mut Param, element: u64) -> String {
+        let element = self.param("", element);
+        param.components = element.components;
+        element.ty
+    }
+
+    fn fields(&mut self, fields: &Value) -> Vec<Param> {
+        let fields = fields.as_array().map(Vec::as_slice).unwrap_or_default();
+        fields
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                let name = match field["name"].as_str() {
+                    Some(name) => camel_case(name),
+                    None => format!("_{}", index),
+                };
+                self.param(&name, field["type"].as_u64().unwrap_or(u64::MAX))
+            })
+            .collect()
+    }
+
+    fn is_u8(&self, id: u64) -> bool {
+        let element = self.registry.get(id);
+        element.map(|element| &element["def"]["primitive"]) == Some(&Value::from("u8"))
+    }
+}
+
+/// The Solidity type of the primitive.
+fn primitive_type(primitive: &str) -> &'static str {
+    match primitive {
+        "bool" => "bool",
+        "char" | "str" => "string",
+        "u8" => "uint8",
+        "u16" => "uint16",
+        "u32" => "uint32",
+        "u64" => "uint64",
+        "u128" => "uint128",
+        "u256" => "uint256",
+        "i8" => "int8",
+        "i16" => "int16",
+        "i32" => "int32",
+        "i64" => "int64",
+        "i128" => "int128",
+        "i256" => "int256",
+        _ => "bytes",
+    }
+}
+
+/// The name of the message in Solidity, `camelCase` without the trait, e.g. `transfer` for
+/// `Erc20::transfer`.
+fn function_name(function: &Function) -> String {
+    let name = function.label.rsplit("::").next().unwrap_or_default();
+    camel_case(name)
+}
+
+/// Generates a best-effort Solidity ABI of the contract, for tools expecting one, e.g. SDK
+/// generators of EVM frontends. The selectors are not those of Solidity, and types without a
+/// Solidity counterpart are mapped to the closest one.
+pub(crate) fn generate(contract: &Contract) -> String {
+    let mut types = Types {
+        registry: &contract.registry,
+        mapping: BTreeSet::new(),
+    };
+    let inputs = |function: &Function, types: &mut Types| {
+        let args = function.args.iter();
+        args.map(|arg| types.param(&camel_case(&arg.label), arg.ty))
+            .collect::<Vec<_>>()
+    };
+    let mut entries = Vec::new();
+    // Solidity contracts have a single constructor.
+    let constructor = contract
+        .constructors
+        .iter()
+        .find(|constructor| constructor.label == "new")
+        .or_else(|| contract.constructors.first());
+    if let Some(constructor) = constructor {
+        if contract.constructors.len() > 1 {
+            log::warn!(
+                "Only the constructor `{}` is exported, Solidity ABIs have a single one",
+                constructor.label
+            );
+        }
+        entries.push(Entry {
+            kind: "constructor",
+            name: None,
+            inputs: inputs(constructor, &mut types),
+            outputs: None,
+            state_mutability: Some(match constructor.payable {
+                true => "payable",
+                false => "nonpayable",
+            }),
+            anonymous: None,
+        });
+    }
+    for message in &contract.messages {
+        // Errors revert in Solidity, so a `Result` returns its `Ok` value.
+        let returns = message.returns.map(|id| match contract.registry.get(id) {
+            Some(ty) if ty["path"] == Value::from(vec!["Result"]) => {
+                Registry::params(ty).first().copied().unwrap_or(id)
+            }
+            _ => id,
+        });
+        let outputs = returns
+            .map(|id| types.param("", id))
+            // `()` returns nothing.
+            .filter(|output| !(output.ty == "tuple" && output.components.is_empty()))
+            .into_iter()
+            .collect();
+        entries.push(Entry {
+            kind: "function",
+            name: Some(function_name(message)),
+            inputs: inputs(message, &mut types),
+            outputs: Some(outputs),
+            state_mutability: Some(match (message.mutates, message.payable) {
+                (_, true) => "payable",
+                (true, false) => "nonpayable",
+                (false, false) => "view",
+            }),
+            anonymous: None,
+        });
+    }
+    for event in &contract.events {
+        let inputs = event.args.iter().map(|arg| Param {
+            indexed: Some(arg.indexed),
+            ..types.param(&camel_case(&arg.label), arg.ty)
+        });
+        entries.push(Entry {
+            kind: "event",
+            name: Some(event.label.clone()),
+            inputs: inputs.collect(),
+            outputs: None,
+            state_mutability: None,
+            anonymous: Some(false),
+        });
+    }
+    let mut abi = serde_json::to_string_pretty(&entries).expect("the ABI is serializable");
+    abi.push('\n');
+    abi
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::bindgen::tests::erc20;
+    use serde_json::json;
+
+    #[test]
+    fn maps_the_messages_and_events_to_solidity() {
+        let (metadata, abi) = erc20();
+        let contract = Contract::new(&metadata, &abi);
+        let abi: Value = serde_json::from_str(&generate(&contract)).unwrap();
+
+        assert_eq!(abi[0]["type"], "constructor");
+        assert_eq!(abi[0]["inputs"][0]["name"], "initialSupply");
+        assert_eq!(abi[0]["inputs"][0]["type"], "uint128");
+        let transfer = &abi[1];
+        assert_eq!(transfer["name"], "transfer");
+        assert_eq!(transfer["stateMutability"], "nonpayable");
+        assert_eq!(transfer["inputs"][0]["type"], "bytes32");
+        assert_eq!(transfer["inputs"][0]["internalType"], "AccountId");
+        assert_eq!(transfer["outputs"], json!([]));
+        assert_eq!(abi[2]["stateMutability"], "view");
+        assert_eq!(abi[2]["outputs"][0]["type"], "uint128");
+        let event = &abi[3];
+        assert_eq!(event["type"], "event");
+        assert_eq!(event["inputs"][0]["indexed"], true);
+        assert_eq!(event["inputs"][0]["type"], "tuple");
+        assert_eq!(event["inputs"][0]["components"][0]["type"], "bool");
+        assert_eq!(event["inputs"][0]["components"][1]["type"], "bytes32");
+    }
+}
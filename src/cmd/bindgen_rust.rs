@@ -0,0 +1,443 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use super::{
+    bindgen::{pascal_case, snake_case, Contract, Function},
+    metadata_format::Registry,
+};
+
+/// The derives of the declared types, with `scale` the `parity-scale-codec` crate the same way
+/// ink! contracts import it.
+const DERIVES: &str = "#[derive(Clone, Debug, PartialEq, Eq, scale::Encode, scale::Decode)]";
+
+/// The transport, the error and the client, which are the same for every contract.
+const CLIENT: &str = r#"/// The connection to the chain the client calls the contract through, e.g. implemented with
+/// subxt for the address of the contract and the signer of the extrinsics.
+pub trait Transport {
+    type Error;
+
+    /// Dry-runs the call of a message with the SCALE encoded input data, returning the SCALE
+    /// encoded return value.
+    fn query(
+        &self,
+        input: Vec<u8>,
+        value: u128,
+    ) -> impl Future<Output = Result<Vec<u8>, Self::Error>>;
+
+    /// Submits an extrinsic calling a message with the SCALE encoded input data, returning the
+    /// SCALE encoded events the contract emitted.
+    fn submit(
+        &self,
+        input: Vec<u8>,
+        value: u128,
+    ) -> impl Future<Output = Result<Vec<Vec<u8>>, Self::Error>>;
+}
+
+/// A failed call of the contract.
+#[derive(Debug)]
+pub enum ClientError<E> {
+    /// The transport failed to query or submit the call.
+    Transport(E),
+    /// The return value or an event could not be decoded, e.g. for a contract built from
+    /// another version of the metadata.
+    Decode(scale::Error),
+}
+
+/// The typed client of the contract.
+pub struct Client<T> {
+    transport: T,
+}
+
+fn decode<T: scale::Decode, E>(output: &[u8]) -> Result<T, ClientError<E>> {
+    T::decode(&mut &output[..]).map_err(ClientError::Decode)
+}
+"#;
+
+/// The keywords which cannot name fields, arguments and methods.
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "yield",
+];
+
+/// The identifier, raw if it is a keyword.
+fn ident(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("r#{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Renders the types of the registry as Rust types, declaring the structs and enums.
+struct Types<'a> {
+    registry: &'a Registry<'a>,
+    /// The declared structs and enums by their name.
+    declarations: BTreeMap<String, String>,
+    /// The names of the declared types by their id.
+    names: BTreeMap<u64, String>,
+}
+
+impl<'a> Types<'a> {
+    fn new(registry: &'a Registry<'a>) -> Self {
+        Types {
+            registry,
+            declarations: BTreeMap::new(),
+            names: BTreeMap::new(),
+        }
+    }
+
+    /// The Rust type of the type, declaring the structs and enums it refers to.
+    /// The types of the `Ok` and `Err` values, if the type is a `Result`.
+    fn result(&mut self, id: u64) -> Option<(String, String)> {
+        let ty = self.registry.get(id)?;
+        let path = ty["path"].as_array().and_then(|path| path.last());
+        match (
+            path.and_then(Value::as_str),
+            Registry::params(ty).as_slice(),
+        ) {
+            (Some("Result"), [ok, err]) => Some((self.ty(*ok), self.ty(*err))),
+            _ => None,
+        }
+    }
+
+    fn ty(&mut self, id: u64) -> String {
+        let ty = match self.registry.get(id) {
+            Some(ty) => ty,
+            None => return "()".to_string(),
+        };
+        let def = &ty["def"];
+        let params = Registry::params(ty);
+        let path = ty["path"].as_array().and_then(|path| path.last());
+        match (path.and_then(Value::as_str), params.as_slice()) {
+            (Some("Option"), [some]) => format!("Option<{}>", self.ty(*some)),
+            (Some("Result"), [ok, err]) => format!("Result<{}, {}>", self.ty(*ok), self.ty(*err)),
+            (Some("BTreeMap"), [key, value]) => format!(
+                "std::collections::BTreeMap<{}, {}>",
+                self.ty(*key),
+                self.ty(*value)
+            ),
+            // Generic types are declared for each of their instances.
+            (Some(_), _) => self.declare(id, &pascal_case(&self.registry.name(id)), def),
+            (None, _) => self.def(def),
+        }
+    }
+
+    /// Declares the named type, once, returning its name.
+    fn declare(&mut self, id: u64, name: &str, def: &Value) -> String {
+        if let Some(name) = self.names.get(&id) {
+            return name.clone();
+        }
+        let mut declared = name.to_string();
+        if self.declarations.contains_key(&declared) {
+            declared = format!("{}{}", name, id);
+        }
+        // Declared before rendering the definition, which may refer to the type itself.
+        self.names.insert(id, declared.clone());
+        self.declarations.insert(declared.clone(), String::new());
+        let declaration = if let Some(variant) = def.get("variant") {
+            let variants = variant["variants"]
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let mut declaration = format!("{}\npub enum {} {{\n", DERIVES, declared);
+            for (index, variant) in variants.iter().enumerate() {
+                let index = variant["discriminant"]
+                    .as_u64()
+                    .or_else(|| variant["index"].as_u64())
+                    .unwrap_or(index as u64);
+                let name = variant["name"].as_str().unwrap_or_default();
+                let fields = self.fields(&variant["fields"], "");
+                declaration.push_str(&format!(
+                    "    #[codec(index = {})]\n    {}{},\n",
+                    index, name, fields
+                ));
+            }
+            declaration.push('}');
+            declaration
+        } else if let Some(composite) = def.get("composite") {
+            let fields = self.fields(&composite["fields"], "pub ");
+            let semicolon = if fields.starts_with(" {") { "" } else { ";" };
+            format!(
+                "{}\npub struct {}{}{}",
+                DERIVES, declared, fields, semicolon
+            )
+        } else {
+            format!("pub type {} = {};", declared, self.def(def))
+        };
+        self.declarations.insert(declared.clone(), declaration);
+        declared
+    }
+
+    fn def(&mut self, def: &Value) -> String {
+        let reference = |key: &str| def[key]["type"].as_u64().unwrap_or(u64::MAX);
+        if let Some(primitive) = def["primitive"].as_str() {
+            match primitive {
+                "str" => "String".to_string(),
+                "u256" | "i256" => "[u8; 32]".to_string(),
+                primitive => primitive.to_string(),
+            }
+        } else if def.get("sequence").is_some() {
+            format!("Vec<{}>", self.ty(reference("sequence")))
+        } else if let Some(array) = def.get("array") {
+            format!("[{}; {}]", self.ty(reference("array")), array["len"])
+        } else if def.get("compact").is_some() {
+            format!("scale::Compact<{}>", self.ty(reference("compact")))
+        } else if let Some(Value::Array(ids)) = def.get("tuple") {
+            let types = ids
+                .iter()
+                .map(|id| self.ty(id.as_u64().unwrap_or(u64::MAX)))
+                .collect::<Vec<_>>();
+            match types.as_slice() {
+                [ty] => format!("({},)", ty),
+                types => format!("({})", types.join(", ")),
+            }
+        } else {
+            // Structs and enums are only declared with a path.
+            "()".to_string()
+        }
+    }
+
+    /// The fields of a struct or an enum variant, with the visibility of the fields.
+    fn fields(&mut self, fields: &Value, visibility: &str) -> String {
+        let fields = fields.as_array().map(Vec::as_slice).unwrap_or_default();
+        if fields.is_empty() {
+            return String::new();
+        }
+        let named = fields[0]["name"].is_string();
+        let fields = fields
+            .iter()
+            .map(|field| {
+                let ty = self.ty(field["type"].as_u64().unwrap_or(u64::MAX));
+                match field["name"].as_str() {
+                    Some(name) => format!("{}{}: {}", visibility, ident(name), ty),
+                    None => format!("{}{}", visibility, ty),
+                }
+            })
+            .collect::<Vec<_>>();
+        if named {
+            format!(" {{ {} }}", fields.join(", "))
+        } else {
+            format!("({})", fields.join(", "))
+        }
+    }
+}
+
+/// The doc comment of the item, indented by the `indent`.
+fn doc_comment(docs: &[String], indent: &str) -> String {
+    docs.iter()
+        .map(|line| format!("{}///{}\n", indent, line.trim_end()))
+        .collect()
+}
+
+/// The constant of the selector of the function.
+fn selector(function: &Function) -> String {
+    let bytes = function
+        .selector
+        .as_deref()
+        .unwrap_or_default()
+        .trim_start_matches("0x");
+    let bytes = (0..bytes.len() / 2)
+        .map(|byte| format!("0x{}", &bytes[2 * byte..2 * byte + 2]))
+        .collect::<Vec<_>>();
+    format!(
+        "pub const {}: [u8; 4] = [{}];\n",
+        snake_case(&function.label).to_uppercase(),
+        bytes.join(", ")
+    )
+}
+
+/// The parameters of the arguments and the statements encoding them after the selector.
+fn args(types: &mut Types, function: &Function) -> (String, String) {
+    let mut params = String::new();
+    let mut encode = String::new();
+    for arg in &function.args {
+        let name = ident(&arg.label);
+        params.push_str(&format!(", {}: {}", name, types.ty(arg.ty)));
+        encode.push_str(&format!(
+            "        scale::Encode::encode_to(&{}, &mut input);\n",
+            name
+        ));
+    }
+    (params, encode)
+}
+
+/// The return type and the body after encoding the input data of the method calling the
+/// message.
+///
+/// Mutating messages are dry-run, so that a contract returning an `Err` fails the method with
+/// it, then submitted, returning their return value and the emitted events.
+fn call(types: &mut Types, message: &Function) -> (String, String) {
+    let value = if message.payable { "value" } else { "0" };
+    let output = message.returns.map(|id| types.ty(id));
+    let output = output.unwrap_or_else(|| "()".to_string());
+    let query = format!(
+        "let output = self.transport.query(input{}, {}).await.map_err(ClientError::Transport)?;\n",
+        if message.mutates { ".clone()" } else { "" },
+        value
+    );
+    if !message.mutates {
+        return (output, format!("{}        decode(&output)", query));
+    }
+    let submit = format!(
+        "let events = self.transport.submit(input, {}).await.map_err(ClientError::Transport)?;\n        \
+         let events = events.iter().map(|event| decode(event)).collect::<Result<_, _>>()?;\n",
+        value
+    );
+    match message.returns.and_then(|id| types.result(id)) {
+        Some((ok, err)) => (
+            format!("Result<({}, Vec<Event>), {}>", ok, err),
+            format!(
+                "{}        let output = match decode::<{}, _>(&output)? {{\n            \
+                 Ok(output) => output,\n            Err(err) => return Ok(Err(err)),\n        \
+                 }};\n        {}        Ok(Ok((output, events)))",
+                query, output, submit
+            ),
+        ),
+        None => (
+            format!("({}, Vec<Event>)", output),
+            format!(
+                "{}        let output = decode(&output)?;\n        {}        Ok((output, events))",
+                query, submit
+            ),
+        ),
+    }
+}
+
+/// Generates the Rust client module of the contract: the selectors, the structs and enums of
+/// the contract, a function encoding the input data of each constructor, an async method of
+/// the client for each message and the enum of the events.
+pub(crate) fn generate(contract: &Contract) -> String {
+    let mut types = Types::new(&contract.registry);
+
+    let mut constructors = "/// The selectors of the constructors, and the input data instantiating the contract with \
+                            them.\npub mod constructors {\n    use super::*;\n"
+        .to_string();
+    for constructor in &contract.constructors {
+        let (params, encode) = args(&mut types, constructor);
+        constructors.push_str(&format!(
+            "\n    {}{}    pub fn {}({}) -> Vec<u8> {{\n        let mut input = {}.to_vec();\n{}        \
+             input\n    }}\n",
+            selector(constructor),
+            doc_comment(&constructor.docs, "    "),
+            ident(&snake_case(&constructor.label)),
+            params.trim_start_matches(", "),
+            snake_case(&constructor.label).to_uppercase(),
+            encode
+        ));
+    }
+    constructors.push_str("}\n");
+
+    let mut selectors = "/// The selectors of the messages.\npub mod messages {\n".to_string();
+    let mut methods =
+        "impl<T: Transport> Client<T> {\n    pub fn new(transport: T) -> Self {\n        \
+                       Client { transport }\n    }\n"
+            .to_string();
+    for message in &contract.messages {
+        selectors.push_str(&format!("    {}", selector(message)));
+        let (mut params, encode) = args(&mut types, message);
+        if message.payable {
+            params.push_str(", value: u128");
+        }
+        let (returns, call) = call(&mut types, message);
+        methods.push_str(&format!(
+            "\n{}    pub async fn {}(&self{}) -> Result<{}, ClientError<T::Error>> {{\n        \
+             let mut input = messages::{}.to_vec();\n{}        {}\n    }}\n",
+            doc_comment(&message.docs, "    "),
+            ident(&snake_case(&message.label)),
+            params,
+            returns,
+            snake_case(&message.label).to_uppercase(),
+            encode,
+            call
+        ));
+    }
+    selectors.push_str("}\n");
+    methods.push_str("}\n");
+
+    let mut events = format!(
+        "/// The events emitted by the contract.\n{}\npub enum Event {{\n",
+        DERIVES
+    );
+    for (index, event) in contract.events.iter().enumerate() {
+        let fields = event
+            .args
+            .iter()
+            .map(|arg| format!("{}: {}", ident(&arg.label), types.ty(arg.ty)))
+            .collect::<Vec<_>>();
+        events.push_str(&doc_comment(&event.docs, "    "));
+        events.push_str(&format!(
+            "    #[codec(index = {})]\n    {}",
+            index, event.label
+        ));
+        if !fields.is_empty() {
+            events.push_str(&format!(" {{ {} }}", fields.join(", ")));
+        }
+        events.push_str(",\n");
+    }
+    events.push_str("}\n");
+
+    let mut output = format!(
+        "//! Client of the contract `{}` {}, generated by cargo-contract from its metadata.\n//!\n\
+         //! Regenerate it with `cargo contract bindgen --lang rust` instead of editing it. It \
+         requires\n//! `parity-scale-codec` imported as `scale`, with its `derive` feature.\n\n\
+         #![allow(dead_code, unused_imports, unused_mut, clippy::all)]\n\nuse core::future::Future;\n\n",
+        contract.name, contract.version
+    );
+    output.push_str(CLIENT);
+    for declaration in types.declarations.values() {
+        output.push('\n');
+        output.push_str(declaration);
+        output.push('\n');
+    }
+    for section in &[events, constructors, selectors, methods] {
+        output.push('\n');
+        output.push_str(section);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::bindgen::tests::erc20;
+
+    #[test]
+    fn generates_the_rust_client() {
+        let (metadata, abi) = erc20();
+        let client = generate(&Contract::new(&metadata, &abi));
+
+        assert!(client.contains("pub struct AccountId(pub [u8; 32]);"));
+        assert!(client
+            .contains("pub enum Error {\n    #[codec(index = 0)]\n    InsufficientBalance,\n}"));
+        assert!(client.contains(
+            "    #[codec(index = 0)]\n    Transfer { from: Option<AccountId>, value: u128 },"
+        ));
+        assert!(client.contains("    pub fn new(initial_supply: u128) -> Vec<u8> {"));
+        assert!(client.contains("pub const ERC20_TRANSFER: [u8; 4] = [0x84, 0xa1, 0x5d, 0xa1];"));
+        assert!(client.contains(
+            "pub async fn erc20_transfer(&self, to: AccountId, value: u128) -> \
+             Result<Result<((), Vec<Event>), Error>, ClientError<T::Error>>"
+        ));
+        assert!(client
+            .contains("pub async fn total_supply(&self) -> Result<u128, ClientError<T::Error>>"));
+    }
+}
@@ -0,0 +1,363 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::Value;
+
+use super::{
+    bindgen::{camel_case, Contract, Function},
+    metadata_format::Registry,
+};
+
+/// The type of the integers which may exceed the safe integers of JavaScript.
+const ANY_NUMBER: &str = "AnyNumber";
+
+/// The type of byte arrays, hex encoded or raw.
+const BYTES: &str = "string | Uint8Array";
+
+/// Renders the types of the registry as the plain values polkadot.js encodes the arguments
+/// from and decodes the return values and events to with `toPrimitive()`.
+struct Types<'a> {
+    registry: &'a Registry<'a>,
+    /// The declared interfaces and aliases by their name.
+    declarations: BTreeMap<String, String>,
+    /// The names of the declared types by their id.
+    names: BTreeMap<u64, String>,
+    /// The generic types being inlined, to detect their recursion.
+    inlining: BTreeSet<u64>,
+}
+
+impl<'a> Types<'a> {
+    fn new(registry: &'a Registry<'a>) -> Self {
+        Types {
+            registry,
+            declarations: BTreeMap::new(),
+            names: BTreeMap::new(),
+            inlining: BTreeSet::new(),
+        }
+    }
+
+    /// The TypeScript type of the type, declaring the non-generic structs and enums it refers to.
+    fn ty(&mut self, id: u64) -> String {
+        let ty = match self.registry.get(id) {
+            Some(ty) => ty,
+            None => return "unknown".to_string(),
+        };
+        let def = &ty["def"];
+        let path = ty["path"].as_array().and_then(|path| path.last());
+        let params = Registry::params(ty);
+        match path.and_then(Value::as_str) {
+            Some("Option") if params.len() == 1 => {
+                return format!("{} | null", self.ty(params[0]));
+            }
+            Some("Result") if params.len() == 2 => {
+                let (ok, err) = (self.ty(params[0]), self.ty(params[1]));
+                return format!("{{ ok: {} }} | {{ err: {} }}", ok, err);
+            }
+            Some(name) if params.is_empty() => return self.declare(id, name, def),
+            Some(_) if !self.inlining.insert(id) => return "unknown".to_string(),
+            Some(_) => {
+                let inlined = self.def(def);
+                self.inlining.remove(&id);
+                return inlined;
+            }
+            None => (),
+        }
+        self.def(def)
+    }
+
+    /// Declares the named type, once, returning its name.
+    fn declare(&mut self, id: u64, name: &str, def: &Value) -> String {
+        if let Some(name) = self.names.get(&id) {
+            return name.clone();
+        }
+        let mut declared = name.to_string();
+        if self.declarations.contains_key(&declared) {
+            declared = format!("{}{}", name, id);
+        }
+        // Declared before rendering the definition, which may refer to the type itself.
+        self.names.insert(id, declared.clone());
+        self.declarations.insert(declared.clone(), String::new());
+        let definition = self.def(def);
+        let declaration = if definition.starts_with('{') && !definition.contains('|') {
+            format!("export interface {} {}", declared, definition)
+        } else {
+            format!("export type {} = {};", declared, definition)
+        };
+        self.declarations.insert(declared.clone(), declaration);
+        declared
+    }
+
+    fn def(&mut self, def: &Value) -> String {
+        let is_u8 = |registry: &Registry, id: &Value| {
+            let element = id.as_u64().and_then(|id| registry.get(id));
+            element.map(|element| &element["def"]["primitive"]) == Some(&Value::from("u8"))
+        };
+        if let Some(primitive) = def["primitive"].as_str() {
+            match primitive {
+                "bool" => "boolean",
+                "char" | "str" => "string",
+                "u8" | "u16" | "u32" | "i8" | "i16" | "i32" => "number",
+                _ => ANY_NUMBER,
+            }
+            .to_string()
+        } else if let Some(composite) = def.get("composite") {
+            self.fields(&composite["fields"])
+        } else if let Some(variant) = def.get("variant") {
+            let variants = variant["variants"]
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let basic = variants.iter().all(|variant| {
+                let fields = variant["fields"].as_array().map(Vec::as_slice);
+                fields.unwrap_or_default().is_empty()
+            });
+            let variants = variants.iter().map(|variant| {
+                let name = variant["name"].as_str().unwrap_or_default();
+                if basic {
+                    format!("'{}'", name)
+                } else {
+                    format!(
+                        "{{ {}: {} }}",
+                        camel_case(name),
+                        self.fields(&variant["fields"])
+                    )
+                }
+            });
+            match variants.collect::<Vec<_>>() {
+                variants if variants.is_empty() => "never".to_string(),
+                variants => variants.join(" | "),
+            }
+        } else if let Some(sequence) = def.get("sequence") {
+            if is_u8(self.registry, &sequence["type"]) {
+                BYTES.to_string()
+            } else {
+                self.element(&sequence["type"])
+            }
+        } else if let Some(array) = def.get("array") {
+            if is_u8(self.registry, &array["type"]) {
+                BYTES.to_string()
+            } else {
+                self.element(&array["type"])
+            }
+        } else if let Some(compact) = def.get("compact") {
+            self.ty(compact["type"].as_u64().unwrap_or(u64::MAX))
+        } else if let Some(Value::Array(ids)) = def.get("tuple") {
+            if ids.is_empty() {
+                return "null".to_string();
+            }
+            let types = ids
+                .iter()
+                .map(|id| self.ty(id.as_u64().unwrap_or(u64::MAX)));
+            format!("[{}]", types.collect::<Vec<_>>().join(", "))
+        } else {
+            "unknown".to_string()
+        }
+    }
+
+    /// The array of the elements of the type.
+    fn element(&mut self, id: &Value) -> String {
+        let element = self.ty(id.as_u64().unwrap_or(u64::MAX));
+        if element.contains(' ') {
+            format!("({})[]", element)
+        } else {
+            format!("{}[]", element)
+        }
+    }
+
+    /// The fields of a struct or an enum variant: `null` without fields, the type of a single
+    /// unnamed field, a tuple of unnamed fields or an object of named fields.
+    fn fields(&mut self, fields: &Value) -> String {
+        let fields = fields.as_array().map(Vec::as_slice).unwrap_or_default();
+        let mut types = Vec::new();
+        for field in fields {
+            let ty = self.ty(field["type"].as_u64().unwrap_or(u64::MAX));
+            match field["name"].as_str() {
+                Some(name) => types.push(format!("{}: {}", camel_case(name), ty)),
+                None => types.push(ty),
+            }
+        }
+        match types.as_slice() {
+            [] => "null".to_string(),
+            [ty] if fields[0]["name"].is_null() => ty.clone(),
+            _ if fields[0]["name"].is_null() => format!("[{}]", types.join(", ")),
+            _ => format!("{{ {} }}", types.join("; ")),
+        }
+    }
+}
+
+/// The doc comment of the item, indented by the `indent`.
+fn doc_comment(docs: &[String], indent: &str) -> String {
+    let docs = docs.iter().map(|line| line.trim()).collect::<Vec<_>>();
+    if docs.iter().all(|line| line.is_empty()) {
+        return String::new();
+    }
+    let docs = docs.join(" ").replace("*/", "*\\/");
+    format!("{}/** {} */\n", indent, docs)
+}
+
+/// Quotes the key of an object, unless it is an identifier.
+fn key(label: &str) -> String {
+    let identifier = label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !label.starts_with(|c: char| c.is_ascii_digit());
+    if identifier && !label.is_empty() {
+        label.to_string()
+    } else {
+        format!("'{}'", label)
+    }
+}
+
+/// The constant of the selectors of the constructors or messages by their name.
+fn selectors(constant: &str, functions: &[Function]) -> String {
+    let mut output = format!("export const {} = {{\n", constant);
+    for function in functions {
+        output.push_str(&format!(
+            "  {}: '{}',\n",
+            key(&camel_case(&function.label)),
+            function.selector.as_deref().unwrap_or_default()
+        ));
+    }
+    output.push_str("} as const;\n\n");
+    output
+}
+
+/// The interface of the `member` type of each of the functions by their name.
+fn interface(
+    name: &str,
+    doc: &str,
+    functions: &[Function],
+    types: &mut Types,
+    member: impl Fn(&mut Types, &Function) -> String,
+) -> String {
+    let mut output = format!("/** {} */\nexport interface {} {{\n", doc, name);
+    for function in functions {
+        output.push_str(&doc_comment(&function.docs, "  "));
+        output.push_str(&format!(
+            "  {}: {};\n",
+            key(&camel_case(&function.label)),
+            member(types, function)
+        ));
+    }
+    output.push_str("}\n\n");
+    output
+}
+
+/// The arguments as a labeled tuple, to be spread into the calls of polkadot.js.
+fn args(types: &mut Types, function: &Function) -> String {
+    let args = function
+        .args
+        .iter()
+        .map(|arg| format!("{}: {}", camel_case(&arg.label), types.ty(arg.ty)));
+    format!("[{}]", args.collect::<Vec<_>>().join(", "))
+}
+
+/// Generates the TypeScript bindings of the contract: the selectors, and the types of the
+/// arguments and return values of the constructors and messages and of the events, by the
+/// names polkadot.js gives them.
+pub(crate) fn generate(contract: &Contract) -> String {
+    let mut types = Types::new(&contract.registry);
+    let mut body = selectors("CONSTRUCTOR_SELECTORS", &contract.constructors);
+    body.push_str(&selectors("MESSAGE_SELECTORS", &contract.messages));
+    body.push_str(&interface(
+        "ConstructorArgs",
+        "The arguments of the constructors.",
+        &contract.constructors,
+        &mut types,
+        args,
+    ));
+    body.push_str(&interface(
+        "MessageArgs",
+        "The arguments of the messages.",
+        &contract.messages,
+        &mut types,
+        args,
+    ));
+    body.push_str(&interface(
+        "MessageReturns",
+        "The return values of the messages.",
+        &contract.messages,
+        &mut types,
+        |types, function| match function.returns {
+            Some(id) => types.ty(id),
+            None => "null".to_string(),
+        },
+    ));
+    // polkadot.js keeps the names of the events.
+    body.push_str("/** The fields of the events. */\nexport interface Events {\n");
+    for event in &contract.events {
+        let fields = event
+            .args
+            .iter()
+            .map(|arg| format!("{}: {}", camel_case(&arg.label), types.ty(arg.ty)));
+        let fields = fields.collect::<Vec<_>>().join("; ");
+        body.push_str(&doc_comment(&event.docs, "  "));
+        body.push_str(&format!("  {}: {{ {} }};\n", key(&event.label), fields));
+    }
+    body.push_str(
+        "}\n\n\
+         /** An event emitted by the contract. */\n\
+         export type ContractEvent = {\n  \
+         [Name in keyof Events]: { event: Name; args: Events[Name] };\n\
+         }[keyof Events];\n",
+    );
+
+    let mut output = format!(
+        "// Type bindings of the contract `{}` {}, generated by cargo-contract from its metadata.\n\
+         // Regenerate them with `cargo contract bindgen --lang ts` instead of editing them.\n\n",
+        contract.name, contract.version
+    );
+    let declarations = types.declarations.into_values().collect::<Vec<_>>();
+    if declarations
+        .iter()
+        .chain(Some(&body))
+        .any(|code| code.contains(ANY_NUMBER))
+    {
+        output.push_str(&format!(
+            "import type {{ {} }} from '@polkadot/types/types';\n\n",
+            ANY_NUMBER
+        ));
+    }
+    for declaration in declarations {
+        output.push_str(&declaration);
+        output.push_str("\n\n");
+    }
+    output.push_str(&body);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::bindgen::tests::erc20;
+
+    #[test]
+    fn generates_the_typescript_bindings() {
+        let (metadata, abi) = erc20();
+        let bindings = generate(&Contract::new(&metadata, &abi));
+
+        assert!(bindings.contains("import type { AnyNumber } from '@polkadot/types/types';"));
+        assert!(bindings.contains("export type AccountId = string | Uint8Array;"));
+        assert!(bindings.contains("export type Error = 'InsufficientBalance';"));
+        assert!(bindings.contains("  erc20Transfer: '0x84a15da1',"));
+        assert!(bindings.contains(
+            "  /** Creates a new ERC-20 contract. */\n  new: [initialSupply: AnyNumber];"
+        ));
+        assert!(bindings.contains("  erc20Transfer: [to: AccountId, value: AnyNumber];"));
+        assert!(bindings.contains("  erc20Transfer: { ok: null } | { err: Error };"));
+        assert!(bindings.contains("  Transfer: { from: AccountId | null; value: AnyNumber };"));
+    }
+}
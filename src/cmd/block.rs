@@ -0,0 +1,96 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use sp_core::H256;
+
+use super::extrinsic::Connection;
+
+/// A block whose state is queried, identified by its hash or by its number.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum BlockRef {
+    Hash(H256),
+    /// The number of a canonical block.
+    Number(u32),
+}
+
+impl FromStr for BlockRef {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input.strip_prefix("0x") {
+            Some(hex_hash) => {
+                let bytes = hex::decode(hex_hash).context("Expected a hex encoded block hash")?;
+                if bytes.len() != 32 {
+                    anyhow::bail!("A block hash should be 32 bytes in length")
+                }
+                Ok(BlockRef::Hash(H256::from_slice(&bytes)))
+            }
+            None => {
+                Ok(BlockRef::Number(input.parse().context(
+                    "Expected a block number or a 0x prefixed block hash",
+                )?))
+            }
+        }
+    }
+}
+
+impl BlockRef {
+    /// Returns the hash of the block.
+    pub async fn hash(&self, connection: &Connection) -> Result<H256> {
+        match self {
+            BlockRef::Hash(hash) => Ok(*hash),
+            BlockRef::Number(number) => connection
+                .block_hash(*number)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("No block with the number {}", number)),
+        }
+    }
+}
+
+/// Resolves the block the state is queried at, the best block if none was requested.
+pub(crate) async fn resolve(
+    connection: &Connection,
+    block: Option<&BlockRef>,
+) -> Result<Option<H256>> {
+    match block {
+        Some(block) => {
+            let hash = block.hash(connection).await?;
+            log::info!("Querying the state at block {:?}", hash);
+            Ok(Some(hash))
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_block_hashes_and_numbers() {
+        let hash = format!("0x{}", "ab".repeat(32));
+        assert_eq!(
+            hash.parse::<BlockRef>().unwrap(),
+            BlockRef::Hash(H256::repeat_byte(0xab))
+        );
+        assert_eq!("42".parse::<BlockRef>().unwrap(), BlockRef::Number(42));
+        assert!("0xabcd".parse::<BlockRef>().is_err());
+        assert!("latest".parse::<BlockRef>().is_err());
+    }
+}
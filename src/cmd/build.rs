@@ -16,26 +16,45 @@
 
 use std::{
     convert::TryFrom,
-    fs::{metadata, File},
-    io::{Read, Write},
-    path::PathBuf,
+    fs::{self, metadata},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Instant,
 };
 
 use crate::{
+    cmd::{metadata::ContractFields, wasm_opt::WasmOpt, SizeReport},
     crate_metadata::CrateMetadata,
     util,
     workspace::{ManifestPath, Profile, Workspace},
-    BuildArtifacts, BuildResult, UnstableFlags, UnstableOptions, VerbosityFlags,
+    BuildArtifacts, BuildError, BuildResult, CargoOptions, Error, OptimizationPasses, OutputType,
+    UnstableFlags, UnstableOptions, VerbosityFlags,
 };
-use crate::{OptimizationResult, Verbosity};
+use crate::{progress::Reporter, OptimizationResult, Progress, ProgressHook, Target, Verbosity};
 use anyhow::{Context, Result};
 use colored::Colorize;
 use parity_wasm::elements::{External, MemoryType, Module, Section};
+use serde::Serialize;
 use structopt::StructOpt;
 
 /// This is the maximum number of pages available for a contract to allocate.
 const MAX_MEMORY_PAGES: u32 = 16;
 
+/// The Docker image of verifiable builds, pinning the versions of rustc, cargo-contract and
+/// wasm-opt. It is tagged with the version of cargo-contract.
+const VERIFIABLE_IMAGE: &str = "paritytech/contracts-verifiable";
+
+/// The directory the contract project is mounted to in the container of a verifiable build.
+const CONTAINER_PROJECT: &str = "/contract";
+
+/// Name of the metadata file without the docs and display names, next to the metadata.
+const STRIPPED_METADATA_FILE: &str = "metadata.stripped.json";
+
 /// Executes build of the smart-contract which produces a wasm binary that is ready for deploying.
 ///
 /// It does so by invoking `cargo build` and then post processing the final binary.
@@ -51,32 +70,431 @@ pub struct BuildCommand {
     ///
     /// - `code-only`: Only the Wasm is created, generation of metadata and a bundled
     ///   `<name>.contract` file is skipped.
+    ///
+    /// - `metadata-only`: Only the metadata is generated, e.g. to refresh the ABI after changing
+    ///   the messages, skipping the build of the Wasm. Its code hash is of the previous build.
     #[structopt(
+        name = "generate",
         long = "generate",
         default_value = "all",
-        value_name = "all | code-only",
+        value_name = "all | code-only | metadata-only",
         verbatim_doc_comment
     )]
     build_artifact: BuildArtifacts,
+    /// Only build the Wasm, the same as `--generate code-only`
+    #[structopt(long, conflicts_with = "generate")]
+    wasm_only: bool,
+    /// The binaryen optimization passes to run on the Wasm: `0` to `4` for increasing speed
+    /// optimizations, `s` and `z` to optimize for size
+    #[structopt(long, default_value = "z", value_name = "0 | 1 | 2 | 3 | 4 | s | z")]
+    optimization_passes: OptimizationPasses,
+    /// The architecture to compile the contract for: `wasm`, or `riscv` for the experimental
+    /// PolkaVM based executor, linking the program with `polkatool`
+    #[structopt(long, default_value = "wasm", value_name = "wasm | riscv")]
+    target: Target,
+    /// Fail the build if the optimized Wasm exceeds this size in kilobytes, overriding the
+    /// `max-contract-size` of the `[package.metadata.contract]` section of the Cargo.toml
+    #[structopt(long, value_name = "kb")]
+    max_contract_size: Option<u64>,
+    /// Keep the `name` section and the debug info in the Wasm, to profile it or symbolicate its
+    /// stack traces in a debugger, instead of stripping them for deployment
+    #[structopt(long, conflicts_with = "verifiable")]
+    keep_debug_symbols: bool,
+    /// Print the functions and crates contributing the most to the size of the Wasm
+    #[structopt(long, conflicts_with = "verifiable")]
+    size_report: bool,
+    /// Run the ink! specific lints with `cargo dylint` before building, see `cargo contract lint`
+    #[structopt(long, conflicts_with = "verifiable")]
+    lint: bool,
+    /// Also write a `metadata.stripped.json` without the docs and display names, keeping only
+    /// what is needed to encode and decode the calls, e.g. to store the metadata on-chain
+    #[structopt(long)]
+    stripped_metadata: bool,
+    /// Build inside a Docker image pinning the versions of the toolchain, so that everyone
+    /// building the same sources gets the identical Wasm
+    #[structopt(long)]
+    verifiable: bool,
+    /// The Docker image of the verifiable build, by default the one of this cargo-contract release
+    #[structopt(long, requires = "verifiable")]
+    image: Option<String>,
+    /// The number of contracts of a workspace to build concurrently, by default the number of
+    /// CPUs
+    #[structopt(long)]
+    jobs: Option<usize>,
+    /// The cargo target directory, by default the `CARGO_TARGET_DIR` or the `target` directory of
+    /// the workspace. The artifacts are written to its `ink` directory
+    #[structopt(long, parse(from_os_str), conflicts_with = "verifiable")]
+    target_dir: Option<PathBuf>,
+    /// The `wasm-opt` executable of binaryen to optimize with, instead of the one on the `PATH`
+    #[structopt(long, parse(from_os_str), conflicts_with = "verifiable")]
+    wasm_opt_path: Option<PathBuf>,
+    /// Download the binaryen release the builds are tested with to the cache if no `wasm-opt`
+    /// is installed, instead of optimizing with the bundled binaryen library
+    #[structopt(long, conflicts_with = "verifiable")]
+    download_wasm_opt: bool,
+    /// Export the build output in JSON format, listing the paths of the artifacts, the code hash,
+    /// the sizes and the timings of the build. Implies `--quiet`
+    #[structopt(
+        long,
+        default_value = "human-readable",
+        value_name = "human-readable | json | json-pjs"
+    )]
+    output: OutputType,
+    #[structopt(flatten)]
+    contract_fields: ContractFields,
+    #[structopt(flatten)]
+    cargo_options: CargoOptions,
     #[structopt(flatten)]
     verbosity: VerbosityFlags,
     #[structopt(flatten)]
     unstable_options: UnstableOptions,
+    #[cfg(feature = "extrinsics")]
+    #[structopt(flatten)]
+    attest_opts: super::AttestOpts,
 }
 
 impl BuildCommand {
-    pub fn exec(&self) -> Result<BuildResult> {
+    pub fn exec(&self) -> Result<String> {
         let manifest_path = ManifestPath::try_from(self.manifest_path.as_ref())?;
+        if self.verifiable {
+            // `conflicts_with` would reject the default value of the output as well.
+            if self.output.is_json() {
+                anyhow::bail!("--output json is not supported for verifiable builds")
+            }
+            return self.exec_verifiable(&manifest_path);
+        }
+        #[cfg(feature = "extrinsics")]
+        if self.attest_opts.enabled() {
+            // `requires` would reject the default value of the scheme as well.
+            anyhow::bail!("Attestations are only signed for verifiable builds, add --verifiable")
+        }
+        if let Some(contracts) = workspace_contracts(&manifest_path)? {
+            return self.exec_workspace(&contracts);
+        }
+        let result = self.build(&manifest_path)?;
+        self.output
+            .format(&BuildJson::new(None, &result)?, |_| result.display())
+    }
+
+    /// The artifacts to generate, only the code with `--wasm-only`.
+    fn build_artifact(&self) -> BuildArtifacts {
+        if self.wasm_only {
+            BuildArtifacts::CodeOnly
+        } else {
+            self.build_artifact
+        }
+    }
+
+    /// The verbosity of the build, which is quiet for the JSON output to be parseable.
+    fn verbosity(&self) -> Result<Option<Verbosity>> {
+        match self.output {
+            OutputType::Json | OutputType::JsonPjs => Ok(Some(Verbosity::Quiet)),
+            OutputType::HumanReadable => TryFrom::<&VerbosityFlags>::try_from(&self.verbosity),
+        }
+    }
+
+    fn build(&self, manifest_path: &ManifestPath) -> Result<BuildResult> {
         let unstable_flags: UnstableFlags =
             TryFrom::<&UnstableOptions>::try_from(&self.unstable_options)?;
-        let verbosity: Option<Verbosity> = TryFrom::<&VerbosityFlags>::try_from(&self.verbosity)?;
-        execute(
-            &manifest_path,
-            verbosity,
-            true,
-            self.build_artifact,
-            unstable_flags,
-        )
+        let verbosity = self.verbosity()?;
+        if self.size_report && self.target != Target::Wasm {
+            anyhow::bail!("--size-report is only supported for the Wasm target")
+        }
+        if self.lint {
+            super::execute_lint(manifest_path, verbosity)?;
+        }
+        let mut builder = ContractBuilder::new()
+            .manifest_path(manifest_path.as_ref())
+            .verbosity(verbosity)
+            .generate(self.build_artifact())
+            .target(self.target)
+            .optimization_passes(self.optimization_passes)
+            .keep_debug_symbols(self.keep_debug_symbols)
+            .stripped_metadata(self.stripped_metadata)
+            .size_report(self.size_report)
+            .contract_fields(self.contract_fields.clone());
+        builder.cargo_options = self.cargo_options.clone();
+        builder.cargo_options.target_dir = self.target_dir.clone();
+        builder.unstable_flags = unstable_flags;
+        builder.max_contract_size = self.max_contract_size;
+        builder.wasm_opt = WasmOpt {
+            path: self.wasm_opt_path.clone(),
+            download: self.download_wasm_opt,
+        };
+        builder.try_build()
+    }
+
+    /// Builds each contract of the workspace into the common target directory, and summarizes
+    /// the sizes and code hashes of the contracts.
+    fn exec_workspace(&self, contracts: &[ManifestPath]) -> Result<String> {
+        if contracts.is_empty() {
+            anyhow::bail!("The workspace contains no ink! contracts to build")
+        }
+        if !self.contract_fields.args().is_empty() {
+            anyhow::bail!(
+                "The --contract-* fields of the metadata apply to a single contract, build the \
+                 contracts of the workspace with their --manifest-path"
+            )
+        }
+        let names = contracts
+            .iter()
+            .map(|contract| {
+                let crate_metadata =
+                    CrateMetadata::collect_for(contract, self.target, &self.cargo_options)?;
+                Ok(crate_metadata.package_name)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let jobs = self
+            .jobs
+            .or_else(|| thread::available_parallelism().ok().map(usize::from))
+            .unwrap_or(1)
+            .max(1)
+            .min(contracts.len());
+        let quiet = matches!(self.verbosity()?, Some(Verbosity::Quiet));
+
+        // The builds of the contracts only wait for each other on the lock of the common target
+        // directory in `cargo build`.
+        let next = AtomicUsize::new(0);
+        let results = contracts
+            .iter()
+            .map(|_| Mutex::new(None))
+            .collect::<Vec<_>>();
+        thread::scope(|scope| {
+            for _ in 0..jobs {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    if index >= contracts.len() {
+                        break;
+                    }
+                    if !quiet {
+                        println!(
+                            "\n {} {}",
+                            format!("({}/{})", index + 1, contracts.len()).bold(),
+                            format!("Building the contract {}", names[index])
+                                .bright_green()
+                                .bold()
+                        );
+                    }
+                    let result = self
+                        .build(&contracts[index])
+                        .context(format!("Failed to build the contract {}", names[index]));
+                    *results[index].lock().expect("no build panicked") = Some(result);
+                });
+            }
+        });
+
+        let mut summary = Vec::new();
+        for (name, result) in names.into_iter().zip(results) {
+            let result = result
+                .into_inner()
+                .expect("no build panicked")
+                .expect("every contract was built")?;
+            if !quiet {
+                println!("{}", result.display());
+            }
+            summary.push(BuildJson::new(Some(name), &result)?);
+        }
+        self.output.format(&summary, |summary| {
+            let width = summary
+                .iter()
+                .filter_map(|json| json.name.as_ref())
+                .map(String::len);
+            let width = width.max().unwrap_or_default().max("Contract".len());
+            let mut out = format!(
+                "\nBuilt {} contracts into {}\n\n  {:<width$}  {:>9}  Code hash",
+                summary.len(),
+                summary[0].target_directory.display().to_string().bold(),
+                "Contract",
+                "Size",
+                width = width
+            );
+            for json in summary {
+                out.push_str(&format!(
+                    "\n  {:<width$}  {:>8.1}K  {}",
+                    json.name.as_deref().unwrap_or_default(),
+                    json.optimized_size.unwrap_or_default(),
+                    json.code_hash.as_deref().unwrap_or("-"),
+                    width = width
+                ));
+            }
+            out
+        })
+    }
+
+    /// Runs the build in a container of the pinned Docker image, with the project directory
+    /// mounted, and reports the code hash of the resulting Wasm.
+    ///
+    /// Only the project directory is mounted, so path dependencies outside of it are not found.
+    fn exec_verifiable(&self, manifest_path: &ManifestPath) -> Result<String> {
+        let docker = which::which("docker")
+            .map_err(|_| anyhow::anyhow!("Verifiable builds require Docker to be installed"))?;
+        let crate_metadata = CrateMetadata::collect(manifest_path)?;
+        let project = manifest_path.directory().unwrap_or_else(|| Path::new("."));
+        let project = fs::canonicalize(project).context(format!(
+            "Failed to resolve the directory {}",
+            project.display()
+        ))?;
+        let image = self
+            .image
+            .clone()
+            .unwrap_or_else(|| format!("{}:{}", VERIFIABLE_IMAGE, env!("CARGO_PKG_VERSION")));
+
+        println!(
+            " {} {}",
+            "Building verifiably with".bright_green().bold(),
+            image.bold()
+        );
+        let status = Command::new(&docker)
+            .args(self.docker_args(&project, &image))
+            .status()
+            .context(format!("Failed to execute {}", docker.display()))?;
+        if !status.success() {
+            anyhow::bail!("The verifiable build in {} failed", image)
+        }
+
+        let target_directory = project.join("target").join("ink");
+        let wasm_name = crate_metadata
+            .dest_wasm
+            .file_name()
+            .expect("the wasm path has a file name");
+        let wasm = fs::read(target_directory.join(wasm_name))?;
+        let code_hash = super::metadata::to_hex(&super::metadata::blake2_hash(&wasm).0);
+        let output = format!(
+            "\nThe verifiable build produced the code hash: {}\nYour contract artifacts are \
+             ready. You can find them in:\n{}",
+            code_hash.bold(),
+            target_directory.display().to_string().bold()
+        );
+        #[cfg(feature = "extrinsics")]
+        let output = if self.attest_opts.enabled() {
+            let attestation =
+                self.attest_opts
+                    .attest(&crate_metadata, &target_directory, &image, &code_hash)?;
+            format!(
+                "{}\nThe signed attestation of the build is:\n{}",
+                output,
+                attestation.display().to_string().bold()
+            )
+        } else {
+            output
+        };
+        Ok(output)
+    }
+
+    /// The arguments of `docker` running `cargo contract build` with the options of this build.
+    fn docker_args(&self, project: &Path, image: &str) -> Vec<String> {
+        let mut args = vec![
+            "run".to_string(),
+            "--rm".to_string(),
+            "--volume".to_string(),
+            format!("{}:{}", project.display(), CONTAINER_PROJECT),
+            "--workdir".to_string(),
+            CONTAINER_PROJECT.to_string(),
+            "--env".to_string(),
+            format!("CARGO_TARGET_DIR={}/target", CONTAINER_PROJECT),
+            image.to_string(),
+            "cargo".to_string(),
+            "contract".to_string(),
+            "build".to_string(),
+            "--generate".to_string(),
+            match self.build_artifact() {
+                BuildArtifacts::CodeOnly => "code-only",
+                BuildArtifacts::MetadataOnly => "metadata-only",
+                _ => "all",
+            }
+            .to_string(),
+            "--optimization-passes".to_string(),
+            self.optimization_passes.to_string(),
+        ];
+        if self.target != Target::Wasm {
+            args.push("--target".to_string());
+            args.push(self.target.to_string());
+        }
+        if let Some(max_contract_size) = self.max_contract_size {
+            args.push("--max-contract-size".to_string());
+            args.push(max_contract_size.to_string());
+        }
+        if self.stripped_metadata {
+            args.push("--stripped-metadata".to_string());
+        }
+        args.extend(self.contract_fields.args());
+        args.extend(self.cargo_options.cargo_args());
+        for option in &self.unstable_options.options {
+            args.push("-Z".to_string());
+            args.push(option.clone());
+        }
+        if self.verbosity.quiet {
+            args.push("--quiet".to_string());
+        }
+        for _ in 0..self.verbosity.verbose {
+            args.push("--verbose".to_string());
+        }
+        args
+    }
+}
+
+/// The result of a build, as exported by `--output json`.
+#[derive(Debug, Serialize)]
+struct BuildJson {
+    /// The name of the contract, for the builds of the contracts of a workspace.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    wasm: Option<PathBuf>,
+    metadata: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stripped_metadata: Option<PathBuf>,
+    bundle: Option<PathBuf>,
+    target_directory: PathBuf,
+    /// Hex encoded blake2 hash of the Wasm.
+    code_hash: Option<String>,
+    /// The size of the Wasm in kilobytes before the optimization.
+    original_size: Option<f64>,
+    /// The size of the Wasm in kilobytes after the optimization.
+    optimized_size: Option<f64>,
+    optimization_passes: Option<String>,
+    /// The names of the custom sections kept in the Wasm.
+    custom_sections: Vec<String>,
+    /// The change of the size of the optimized Wasm since the previous build.
+    size_delta: Option<super::SizeDelta>,
+    timings: BuildTimings,
+}
+
+/// The wall-clock timings of a build in seconds.
+#[derive(Debug, Serialize)]
+struct BuildTimings {
+    total: f64,
+    optimization: Option<f64>,
+}
+
+impl BuildJson {
+    fn new(name: Option<String>, result: &BuildResult) -> Result<Self> {
+        let code_hash = match result.dest_wasm {
+            Some(ref wasm) => Some(super::metadata::to_hex(
+                &super::metadata::blake2_hash(&fs::read(wasm)?).0,
+            )),
+            None => None,
+        };
+        let optimization = result.optimization_result.as_ref();
+        Ok(BuildJson {
+            name,
+            wasm: result.dest_wasm.clone(),
+            metadata: result.dest_metadata.clone(),
+            stripped_metadata: result.dest_stripped_metadata.clone(),
+            bundle: result.dest_bundle.clone(),
+            target_directory: result.target_directory.clone(),
+            code_hash,
+            original_size: optimization.map(|optimization| optimization.original_size),
+            optimized_size: optimization.map(|optimization| optimization.optimized_size),
+            optimization_passes: optimization
+                .map(|optimization| optimization.optimization_passes.to_string()),
+            custom_sections: result.custom_sections.clone(),
+            size_delta: result.size_delta.clone(),
+            timings: BuildTimings {
+                total: result.build_time.as_secs_f64(),
+                optimization: optimization
+                    .map(|optimization| optimization.optimization_time.as_secs_f64()),
+            },
+        })
     }
 }
 
@@ -87,6 +505,8 @@ pub struct CheckCommand {
     #[structopt(long, parse(from_os_str))]
     manifest_path: Option<PathBuf>,
     #[structopt(flatten)]
+    cargo_options: CargoOptions,
+    #[structopt(flatten)]
     verbosity: VerbosityFlags,
     #[structopt(flatten)]
     unstable_options: UnstableOptions,
@@ -103,16 +523,27 @@ impl CheckCommand {
             verbosity,
             false,
             BuildArtifacts::CheckOnly,
+            Target::Wasm,
+            OptimizationPasses::default(),
+            false,
+            None,
+            false,
+            &Default::default(),
+            &self.cargo_options,
             unstable_flags,
         )
     }
 }
 
-/// Builds the project in the specified directory, defaults to the current directory.
+/// Builds the project in the specified directory, defaults to the current directory, or only
+/// checks it if the `command` is `check` instead of `build`.
 ///
-/// Uses the unstable cargo feature [`build-std`](https://doc.rust-lang.org/nightly/cargo/reference/unstable.html#build-std)
+/// Builds with the precompiled standard library of the target installed with `rustup`, which
+/// works on the stable toolchain. With the `-Z build-std` flag, which requires nightly, uses the
+/// unstable cargo feature [`build-std`](https://doc.rust-lang.org/nightly/cargo/reference/unstable.html#build-std)
 /// to build the standard library with [`panic_immediate_abort`](https://github.com/johnthagen/min-sized-rust#remove-panic-string-formatting-with-panic_immediate_abort)
 /// which reduces the size of the Wasm binary by not including panic strings and formatting code.
+/// The RISC-V target always rebuilds it, as no precompiled one is distributed.
 ///
 /// # Cargo.toml optimizations
 ///
@@ -125,33 +556,38 @@ impl CheckCommand {
 /// To disable this and use the original `Cargo.toml` as is then pass the `-Z original_manifest` flag.
 fn build_cargo_project(
     crate_metadata: &CrateMetadata,
+    command: &str,
     verbosity: Option<Verbosity>,
+    cargo_options: &CargoOptions,
     unstable_flags: UnstableFlags,
 ) -> Result<()> {
-    util::assert_channel()?;
+    let span = tracing::info_span!("cargo", command);
+    let _enter = span.enter();
+    let target = crate_metadata.target;
+    let build_std = build_std_args(target, &unstable_flags);
+    if build_std.is_empty() {
+        util::assert_target_installed(target.llvm_target())?;
+    } else {
+        util::assert_channel()?;
+        util::assert_rust_src()?;
+    }
 
-    // set linker args via RUSTFLAGS.
+    // set linker args via RUSTFLAGS, of the cargo process only so concurrent builds do not race.
     // Currently will override user defined RUSTFLAGS from .cargo/config. See https://github.com/paritytech/cargo-contract/issues/98.
-    std::env::set_var(
-        "RUSTFLAGS",
-        "-C link-arg=-z -C link-arg=stack-size=65536 -C link-arg=--import-memory",
-    );
+    let env = [("RUSTFLAGS", target.rustflags())];
 
     let cargo_build = |manifest_path: &ManifestPath| {
         let target_dir = &crate_metadata.target_directory;
-        util::invoke_cargo(
-            "build",
-            &[
-                "--target=wasm32-unknown-unknown",
-                "-Zbuild-std",
-                "-Zbuild-std-features=panic_immediate_abort",
-                "--no-default-features",
-                "--release",
-                &format!("--target-dir={}", target_dir.to_string_lossy()),
-            ],
-            manifest_path.directory(),
-            verbosity,
-        )?;
+        let mut args = vec![format!("--target={}", target.llvm_target())];
+        args.extend(build_std.clone());
+        args.extend(vec![
+            "--no-default-features".to_string(),
+            "--release".to_string(),
+            format!("--target-dir={}", target_dir.to_string_lossy()),
+        ]);
+        args.extend(cargo_options.cargo_args());
+        util::invoke_cargo(command, &args, manifest_path.directory(), verbosity, &env)
+            .map_err(|err| BuildError::new(format!("{:#}", err)))?;
         Ok(())
     };
 
@@ -171,15 +607,31 @@ fn build_cargo_project(
                     .with_profile_release_defaults(Profile::default_contract_release())?;
                 Ok(())
             })?
+            .keep_temp_on_failure(matches!(
+                verbosity,
+                Some(Verbosity::Verbose) | Some(Verbosity::VeryVerbose)
+            ))
             .using_temp(cargo_build)?;
     }
 
-    // clear RUSTFLAGS
-    std::env::remove_var("RUSTFLAGS");
-
     Ok(())
 }
 
+/// The arguments of cargo rebuilding the standard library, with aborting panics which leave out
+/// the panic strings and formatting code, or none to use the precompiled one.
+fn build_std_args(target: Target, unstable_flags: &UnstableFlags) -> Vec<String> {
+    let crates = match target {
+        Target::Wasm if unstable_flags.build_std => "std,panic_abort",
+        Target::Wasm => return Vec::new(),
+        // There is no `std` for the bare metal target.
+        Target::RiscV => "core,alloc",
+    };
+    vec![
+        format!("-Zbuild-std={}", crates),
+        "-Zbuild-std-features=panic_immediate_abort".to_string(),
+    ]
+}
+
 /// Ensures the wasm memory import of a given module has the maximum number of pages.
 ///
 /// Iterates over the import section, finds the memory import entry if any and adjusts the maximum
@@ -215,26 +667,58 @@ fn ensure_maximum_memory_pages(module: &mut Module, maximum_allowed_pages: u32)
     Ok(())
 }
 
-/// Strips all custom sections.
+/// The name of the custom section, `None` for the sections of the Wasm specification.
+fn custom_section_name(section: &Section) -> Option<&str> {
+    match section {
+        Section::Custom(custom) => Some(custom.name()),
+        Section::Name(_) => Some("name"),
+        Section::Reloc(reloc) => Some(reloc.name()),
+        _ => None,
+    }
+}
+
+/// Strips the custom sections, except for the ones to `keep`.
 ///
-/// Presently all custom sections are not required so they can be stripped safely.
-fn strip_custom_sections(module: &mut Module) {
-    module.sections_mut().retain(|section| match section {
-        Section::Custom(_) => false,
-        Section::Name(_) => false,
-        Section::Reloc(_) => false,
-        _ => true,
-    });
+/// Custom sections are not required on-chain, so they can be stripped safely.
+fn strip_custom_sections(module: &mut Module, keep: &[String]) {
+    module
+        .sections_mut()
+        .retain(|section| match custom_section_name(section) {
+            Some(name) => keep.iter().any(|kept| kept == name),
+            None => true,
+        });
+}
+
+/// The names of the custom sections of the Wasm file, which survived the stripping and the
+/// optimization.
+fn custom_sections(wasm: &Path) -> Result<Vec<String>> {
+    let module = parity_wasm::deserialize_file(wasm)
+        .context(format!("Loading the wasm file '{}'", wasm.display()))?;
+    Ok(module
+        .sections()
+        .iter()
+        .filter_map(custom_section_name)
+        .map(ToString::to_string)
+        .collect())
 }
 
 /// Performs required post-processing steps on the wasm artifact.
-fn post_process_wasm(crate_metadata: &CrateMetadata) -> Result<()> {
+///
+/// Unless `keep_debug_symbols`, the custom sections are stripped from it, except for the
+/// `keep-sections` of the contract.
+fn post_process_wasm(crate_metadata: &CrateMetadata, keep_debug_symbols: bool) -> Result<()> {
+    let span = tracing::info_span!("post process wasm");
+    let _enter = span.enter();
     // Deserialize wasm module from a file.
     let mut module =
         parity_wasm::deserialize_file(&crate_metadata.original_wasm).context(format!(
             "Loading original wasm file '{}'",
             crate_metadata.original_wasm.display()
         ))?;
+    if keep_debug_symbols {
+        // Parsed, the function names are updated to the indices after the tree-shaking.
+        module = module.parse_names().unwrap_or_else(|(_, module)| module);
+    }
 
     // Perform optimization.
     //
@@ -244,40 +728,81 @@ fn post_process_wasm(crate_metadata: &CrateMetadata) -> Result<()> {
         anyhow::bail!("Optimizer failed");
     }
     ensure_maximum_memory_pages(&mut module, MAX_MEMORY_PAGES)?;
-    strip_custom_sections(&mut module);
+    super::validate::validate_wasm(&module)?;
+    if !keep_debug_symbols {
+        strip_custom_sections(&mut module, &crate_metadata.keep_sections);
+    }
 
     parity_wasm::serialize_to_file(&crate_metadata.dest_wasm, module)?;
     Ok(())
 }
 
+/// Links the RISC-V ELF binary compiled by cargo into a PolkaVM program with `polkatool`, and
+/// validates the program.
+///
+/// Unless `keep_debug_symbols`, the program is stripped.
+fn link_polkavm(crate_metadata: &CrateMetadata, keep_debug_symbols: bool) -> Result<()> {
+    let polkatool = which::which("polkatool").map_err(|_| {
+        anyhow::anyhow!(
+            "The RISC-V target requires polkatool to link the PolkaVM program, install it with \
+             `cargo install polkatool`"
+        )
+    })?;
+    let mut command = Command::new(&polkatool);
+    command.arg("link");
+    if !keep_debug_symbols {
+        command.arg("--strip");
+    }
+    let output = command
+        .arg("--output")
+        .arg(&crate_metadata.dest_wasm)
+        .arg(&crate_metadata.original_wasm)
+        .output()
+        .context(format!("Failed to execute {}", polkatool.display()))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "polkatool failed to link {}: {}",
+            crate_metadata.original_wasm.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    }
+    Ok(())
+}
+
 /// Attempts to perform optional wasm optimization using `binaryen`.
 ///
 /// The intention is to reduce the size of bloated wasm binaries as a result of missing
 /// optimizations (or bugs?) between Rust and Wasm.
-fn optimize_wasm(crate_metadata: &CrateMetadata) -> Result<OptimizationResult> {
+///
+/// Runs the `wasm-opt` executable of binaryen if one is found, which is usually a more recent
+/// release, else the `binaryen` library `cargo-contract` is built with.
+/// See [`WasmOpt::find`].
+fn optimize_wasm(
+    crate_metadata: &CrateMetadata,
+    optimization_passes: OptimizationPasses,
+    keep_debug_symbols: bool,
+    wasm_opt: &WasmOpt,
+) -> Result<OptimizationResult> {
+    let span = tracing::info_span!("wasm-opt", passes = %optimization_passes);
+    let _enter = span.enter();
+    let started = Instant::now();
     let mut optimized = crate_metadata.dest_wasm.clone();
     optimized.set_file_name(format!("{}-opt.wasm", crate_metadata.package_name));
 
-    let codegen_config = binaryen::CodegenConfig {
-        // execute -O3 optimization passes (spends potentially a lot of time optimizing)
-        optimization_level: 3,
-        // the default
-        shrink_level: 1,
-        // the default
-        debug_info: false,
-    };
-
-    let mut dest_wasm_file = File::open(crate_metadata.dest_wasm.as_os_str())?;
-    let mut dest_wasm_file_content = Vec::new();
-    dest_wasm_file.read_to_end(&mut dest_wasm_file_content)?;
-
-    let mut module = binaryen::Module::read(&dest_wasm_file_content)
-        .map_err(|_| anyhow::anyhow!("binaryen failed to read file content"))?;
-    module.optimize(&codegen_config);
-    let optimized_wasm = module.write();
-
-    let mut optimized_wasm_file = File::create(optimized.as_os_str())?;
-    optimized_wasm_file.write_all(&optimized_wasm)?;
+    let wasm = &crate_metadata.dest_wasm;
+    match wasm_opt.find()? {
+        Some(wasm_opt) => run_wasm_opt(
+            &wasm_opt,
+            wasm,
+            &optimized,
+            optimization_passes,
+            keep_debug_symbols,
+        )?,
+        None => {
+            super::wasm_opt::warn_bundled();
+            optimize_with_binaryen(wasm, &optimized, optimization_passes, keep_debug_symbols)?
+        }
+    }
 
     let original_size = metadata(&crate_metadata.dest_wasm)?.len() as f64 / 1000.0;
     let optimized_size = metadata(&optimized)?.len() as f64 / 1000.0;
@@ -287,43 +812,511 @@ fn optimize_wasm(crate_metadata: &CrateMetadata) -> Result<OptimizationResult> {
     Ok(OptimizationResult {
         original_size,
         optimized_size,
+        optimization_passes,
+        optimization_time: started.elapsed(),
     })
 }
 
+/// Returns the manifests of the ink! contracts among the members of the workspace, if the
+/// manifest is the root of a virtual workspace, which has no package to build itself.
+///
+/// Contracts are the members depending on `ink_lang` which build a `cdylib`.
+pub(crate) fn workspace_contracts(
+    manifest_path: &ManifestPath,
+) -> Result<Option<Vec<ManifestPath>>> {
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .manifest_path(manifest_path.as_ref())
+        .no_deps()
+        .exec()
+        .context("Error invoking `cargo metadata`")?;
+    let manifest = fs::canonicalize(manifest_path)?;
+    let is_package = |package: &cargo_metadata::Package| {
+        fs::canonicalize(&package.manifest_path).ok().as_ref() == Some(&manifest)
+    };
+    if metadata.packages.iter().any(is_package) {
+        return Ok(None);
+    }
+    let contracts = metadata
+        .packages
+        .iter()
+        .filter(|package| {
+            let ink = package
+                .dependencies
+                .iter()
+                .any(|dep| dep.name == "ink_lang");
+            let cdylib = package.targets.iter().any(|target| {
+                target
+                    .crate_types
+                    .iter()
+                    .any(|crate_type| crate_type == "cdylib")
+            });
+            ink && cdylib
+        })
+        .map(|package| ManifestPath::new(&package.manifest_path))
+        .collect::<Result<_>>()?;
+    Ok(Some(contracts))
+}
+
+/// The version of the binaryen optimizer used by the builds.
+pub(crate) fn optimizer_version(wasm_opt: &WasmOpt) -> String {
+    match wasm_opt.installed() {
+        Some(wasm_opt) => super::wasm_opt::version_output(&wasm_opt)
+            .unwrap_or_else(|_| format!("{} of an unknown version", wasm_opt.display())),
+        _ => format!(
+            "binaryen library bundled with cargo-contract {}",
+            env!("CARGO_PKG_VERSION")
+        ),
+    }
+}
+
+/// Optimizes the Wasm file with the `wasm-opt` executable.
+fn run_wasm_opt(
+    wasm_opt: &Path,
+    wasm: &Path,
+    optimized: &Path,
+    optimization_passes: OptimizationPasses,
+    keep_debug_symbols: bool,
+) -> Result<()> {
+    log::info!("Optimizing with {}", wasm_opt.display());
+    let mut command = Command::new(wasm_opt);
+    command
+        .arg(wasm)
+        .arg(format!("-O{}", optimization_passes))
+        .arg("-o")
+        .arg(optimized);
+    if keep_debug_symbols {
+        command.arg("--debuginfo");
+    }
+    let output = command
+        .output()
+        .context(format!("Failed to execute {}", wasm_opt.display()))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "wasm-opt failed to optimize {}: {}",
+            wasm.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    }
+    Ok(())
+}
+
+/// Optimizes the Wasm file with the bundled `binaryen` library.
+fn optimize_with_binaryen(
+    wasm: &Path,
+    optimized: &Path,
+    optimization_passes: OptimizationPasses,
+    keep_debug_symbols: bool,
+) -> Result<()> {
+    let codegen_config = binaryen::CodegenConfig {
+        optimization_level: optimization_passes.optimization_level(),
+        shrink_level: optimization_passes.shrink_level(),
+        debug_info: keep_debug_symbols,
+    };
+
+    let content = fs::read(wasm)?;
+    let mut module = binaryen::Module::read(&content)
+        .map_err(|_| anyhow::anyhow!("binaryen failed to read file content"))?;
+    module.optimize(&codegen_config);
+    fs::write(optimized, module.write())?;
+    Ok(())
+}
+
+/// Builds a contract with the options of `cargo contract build`, for tools driving the build
+/// programmatically instead of running the command.
+///
+/// The build prints no steps, unless another [`ContractBuilder::verbosity`] is chosen.
+#[derive(Clone, Debug)]
+pub struct ContractBuilder {
+    manifest_path: Option<PathBuf>,
+    verbosity: Option<Verbosity>,
+    build_artifact: BuildArtifacts,
+    target: Target,
+    optimization_passes: OptimizationPasses,
+    keep_debug_symbols: bool,
+    max_contract_size: Option<u64>,
+    stripped_metadata: bool,
+    size_report: bool,
+    contract_fields: ContractFields,
+    cargo_options: CargoOptions,
+    unstable_flags: UnstableFlags,
+    wasm_opt: WasmOpt,
+    progress: Reporter,
+}
+
+impl Default for ContractBuilder {
+    fn default() -> Self {
+        ContractBuilder {
+            manifest_path: None,
+            verbosity: Some(Verbosity::Quiet),
+            build_artifact: BuildArtifacts::All,
+            target: Target::default(),
+            optimization_passes: OptimizationPasses::default(),
+            keep_debug_symbols: false,
+            max_contract_size: None,
+            stripped_metadata: false,
+            size_report: false,
+            contract_fields: ContractFields::default(),
+            cargo_options: CargoOptions::default(),
+            unstable_flags: UnstableFlags::default(),
+            wasm_opt: WasmOpt::default(),
+            progress: Reporter::default(),
+        }
+    }
+}
+
+impl ContractBuilder {
+    /// Builds all artifacts of the contract in the current directory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The `Cargo.toml` of the contract to build.
+    pub fn manifest_path(mut self, manifest_path: impl Into<PathBuf>) -> Self {
+        self.manifest_path = Some(manifest_path.into());
+        self
+    }
+
+    /// The output of the build, `None` printing its steps as `cargo contract build` does.
+    pub fn verbosity(mut self, verbosity: Option<Verbosity>) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Which artifacts to generate, e.g. only the metadata.
+    pub fn generate(mut self, build_artifact: BuildArtifacts) -> Self {
+        self.build_artifact = build_artifact;
+        self
+    }
+
+    pub fn target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn optimization_passes(mut self, optimization_passes: OptimizationPasses) -> Self {
+        self.optimization_passes = optimization_passes;
+        self
+    }
+
+    /// Keep the debug symbols and the name section in the Wasm.
+    pub fn keep_debug_symbols(mut self, keep_debug_symbols: bool) -> Self {
+        self.keep_debug_symbols = keep_debug_symbols;
+        self
+    }
+
+    /// Fail the build if the optimized Wasm exceeds this size in kilobytes.
+    pub fn max_contract_size(mut self, max_contract_size: u64) -> Self {
+        self.max_contract_size = Some(max_contract_size);
+        self
+    }
+
+    /// Additionally write the metadata without the docs and display names.
+    pub fn stripped_metadata(mut self, stripped_metadata: bool) -> Self {
+        self.stripped_metadata = stripped_metadata;
+        self
+    }
+
+    /// Attribute the size of the Wasm to its functions and crates.
+    pub fn size_report(mut self, size_report: bool) -> Self {
+        self.size_report = size_report;
+        self
+    }
+
+    /// Activate these features of the contract.
+    pub fn features<I, S>(mut self, features: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.cargo_options.features = features.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Activate all features of the contract.
+    pub fn all_features(mut self, all_features: bool) -> Self {
+        self.cargo_options.all_features = all_features;
+        self
+    }
+
+    /// Run cargo without accessing the network.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.cargo_options.offline = offline;
+        self
+    }
+
+    /// Require the `Cargo.lock` of the contract to be up to date.
+    pub fn locked(mut self, locked: bool) -> Self {
+        self.cargo_options.locked = locked;
+        self
+    }
+
+    /// The cargo target directory, by default the `CARGO_TARGET_DIR` or the `target` directory
+    /// of the workspace. The artifacts are written to its `ink` directory.
+    pub fn target_dir(mut self, target_dir: impl Into<PathBuf>) -> Self {
+        self.cargo_options.target_dir = Some(target_dir.into());
+        self
+    }
+
+    /// The `wasm-opt` executable of binaryen to optimize with, instead of the one on the `PATH`.
+    pub fn wasm_opt_path(mut self, wasm_opt_path: impl Into<PathBuf>) -> Self {
+        self.wasm_opt.path = Some(wasm_opt_path.into());
+        self
+    }
+
+    /// Download the binaryen release the builds are tested with if no `wasm-opt` is installed,
+    /// instead of optimizing with the bundled binaryen library.
+    pub fn download_wasm_opt(mut self, download_wasm_opt: bool) -> Self {
+        self.wasm_opt.download = download_wasm_opt;
+        self
+    }
+
+    /// Build with the original manifest, without the modifications for smaller code.
+    pub fn original_manifest(mut self, original_manifest: bool) -> Self {
+        self.unstable_flags.original_manifest = original_manifest;
+        self
+    }
+
+    /// Rebuild the standard library for a smaller Wasm, on the nightly toolchain.
+    pub fn build_std(mut self, build_std: bool) -> Self {
+        self.unstable_flags.build_std = build_std;
+        self
+    }
+
+    /// Report the steps of the build to the hook, e.g. to show a progress bar.
+    pub fn progress_hook(mut self, hook: impl ProgressHook + 'static) -> Self {
+        self.progress = Reporter::new(hook);
+        self
+    }
+
+    pub(crate) fn contract_fields(mut self, contract_fields: ContractFields) -> Self {
+        self.contract_fields = contract_fields;
+        self
+    }
+
+    /// Builds the contract, returning the paths of the generated artifacts.
+    pub fn build(&self) -> Result<BuildResult, Error> {
+        Ok(self.try_build()?)
+    }
+
+    pub(crate) fn try_build(&self) -> Result<BuildResult> {
+        let manifest_path = ManifestPath::try_from(self.manifest_path.as_ref())?;
+        if self.size_report && self.target != Target::Wasm {
+            anyhow::bail!("The size report is only supported for the Wasm target")
+        }
+        let mut result = execute(
+            &manifest_path,
+            self.verbosity,
+            true,
+            self.build_artifact,
+            self.target,
+            self.optimization_passes,
+            self.keep_debug_symbols,
+            self.max_contract_size,
+            self.stripped_metadata,
+            &self.contract_fields,
+            &self.cargo_options,
+            self.unstable_flags.clone(),
+            &self.wasm_opt,
+            &self.progress,
+        )?;
+        if self.size_report {
+            let crate_metadata =
+                CrateMetadata::collect_for(&manifest_path, self.target, &self.cargo_options)?;
+            result.size_report = Some(SizeReport::of_original_wasm(&crate_metadata)?);
+        }
+        Ok(result)
+    }
+}
+
 /// Executes build of the smart-contract which produces a wasm binary that is ready for deploying.
 ///
 /// It does so by invoking `cargo build` and then post processing the final binary.
-fn execute(
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn execute(
     manifest_path: &ManifestPath,
     verbosity: Option<Verbosity>,
     optimize_contract: bool,
     build_artifact: BuildArtifacts,
+    target: Target,
+    optimization_passes: OptimizationPasses,
+    keep_debug_symbols: bool,
+    max_contract_size: Option<u64>,
+    stripped_metadata: bool,
+    contract_fields: &ContractFields,
+    cargo_options: &CargoOptions,
     unstable_flags: UnstableFlags,
+    wasm_opt: &WasmOpt,
+    progress: &Reporter,
 ) -> Result<BuildResult> {
-    let crate_metadata = CrateMetadata::collect(manifest_path)?;
+    let started = Instant::now();
+    let crate_metadata = CrateMetadata::collect_for(manifest_path, target, cargo_options)?;
+    let span = tracing::info_span!("build", package = %crate_metadata.package_name);
+    let _enter = span.enter();
     if build_artifact == BuildArtifacts::CodeOnly || build_artifact == BuildArtifacts::CheckOnly {
         let (maybe_dest_wasm, maybe_optimization_result) = execute_with_crate_metadata(
             &crate_metadata,
             verbosity,
             optimize_contract,
             build_artifact,
+            optimization_passes,
+            keep_debug_symbols,
+            cargo_options,
             unstable_flags,
+            wasm_opt,
+            progress,
         )?;
-        let res = BuildResult {
-            dest_wasm: maybe_dest_wasm,
+        let mut res = BuildResult {
+            dest_wasm: maybe_dest_wasm.clone(),
             dest_metadata: None,
+            dest_stripped_metadata: None,
             dest_bundle: None,
-            target_directory: crate_metadata.target_directory,
+            target_directory: crate_metadata.target_directory.clone(),
             optimization_result: maybe_optimization_result,
             build_artifact,
+            size_report: None,
+            custom_sections: kept_sections(&crate_metadata, maybe_dest_wasm.as_deref())?,
+            size_delta: None,
+            build_time: started.elapsed(),
         };
+        res.size_delta = record_size(&crate_metadata, &res);
+        check_contract_size(&res, max_contract_size, &crate_metadata)?;
+        if build_artifact == BuildArtifacts::CodeOnly {
+            run_post_build_hooks(&crate_metadata, &res, verbosity)?;
+        }
         return Ok(res);
     }
 
-    let res = super::metadata::execute(&manifest_path, verbosity, build_artifact, unstable_flags)?;
+    let mut res = super::metadata::execute(
+        manifest_path,
+        verbosity,
+        build_artifact,
+        target,
+        optimization_passes,
+        keep_debug_symbols,
+        contract_fields,
+        cargo_options,
+        unstable_flags,
+        wasm_opt,
+        progress,
+    )?;
+    res.custom_sections = kept_sections(&crate_metadata, res.dest_wasm.as_deref())?;
+    res.size_delta = record_size(&crate_metadata, &res);
+    check_contract_size(&res, max_contract_size, &crate_metadata)?;
+    if stripped_metadata {
+        if let Some(ref dest_metadata) = res.dest_metadata {
+            res.dest_stripped_metadata = Some(write_stripped_metadata(dest_metadata)?);
+        }
+    }
+    run_post_build_hooks(&crate_metadata, &res, verbosity)?;
     Ok(res)
 }
 
+/// Writes the metadata without the docs and display names next to it, compactly.
+fn write_stripped_metadata(dest_metadata: &Path) -> Result<PathBuf> {
+    let mut metadata = super::metadata_format::read(dest_metadata)?;
+    super::metadata_format::strip(&mut metadata)?;
+    let path = dest_metadata.with_file_name(STRIPPED_METADATA_FILE);
+    fs::write(&path, serde_json::to_string(&metadata)?)
+        .context(format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Records the size of the optimized Wasm in the size history, for the build report.
+fn record_size(crate_metadata: &CrateMetadata, res: &BuildResult) -> Option<super::SizeDelta> {
+    match (&res.dest_wasm, &res.optimization_result) {
+        (Some(wasm), Some(_)) => super::size_history::record(crate_metadata, wasm),
+        _ => None,
+    }
+}
+
+/// The custom sections of the built Wasm, for the build report.
+fn kept_sections(crate_metadata: &CrateMetadata, dest_wasm: Option<&Path>) -> Result<Vec<String>> {
+    match dest_wasm {
+        Some(wasm) if crate_metadata.target == Target::Wasm => custom_sections(wasm),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Runs the `post-build` commands of the contract in its directory, with the paths of the
+/// artifacts in `CARGO_CONTRACT_*` environment variables, failing the build if one fails.
+fn run_post_build_hooks(
+    crate_metadata: &CrateMetadata,
+    res: &BuildResult,
+    verbosity: Option<Verbosity>,
+) -> Result<()> {
+    let artifacts = [
+        ("CARGO_CONTRACT_WASM", res.dest_wasm.as_ref()),
+        ("CARGO_CONTRACT_METADATA", res.dest_metadata.as_ref()),
+        (
+            "CARGO_CONTRACT_STRIPPED_METADATA",
+            res.dest_stripped_metadata.as_ref(),
+        ),
+        ("CARGO_CONTRACT_BUNDLE", res.dest_bundle.as_ref()),
+        ("CARGO_CONTRACT_TARGET_DIR", Some(&res.target_directory)),
+    ];
+    let quiet = matches!(verbosity, Some(Verbosity::Quiet));
+    for hook in &crate_metadata.post_build {
+        if !quiet {
+            println!(
+                " {} {}",
+                "Running post-build hook".bright_green().bold(),
+                hook
+            );
+        }
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        let mut cmd = Command::new(shell);
+        cmd.args([flag, hook.as_str()])
+            .env("CARGO_CONTRACT_NAME", &crate_metadata.package_name);
+        if let Some(dir) = crate_metadata.manifest_path.directory() {
+            cmd.current_dir(dir);
+        }
+        for (name, path) in artifacts.iter() {
+            if let Some(path) = path {
+                cmd.env(name, path);
+            }
+        }
+        if quiet {
+            // Keeps the stdout to the result of the build, e.g. its JSON output.
+            cmd.stdout(std::io::stderr());
+        }
+        let status = cmd
+            .status()
+            .context(format!("Failed to run the post-build hook `{}`", hook))?;
+        if !status.success() {
+            anyhow::bail!("The post-build hook `{}` failed with {}", hook, status)
+        }
+    }
+    Ok(())
+}
+
+/// Fails if the optimized Wasm exceeds the maximum contract size in kilobytes, before a
+/// deployment of the oversized contract fails on-chain.
+///
+/// The `--max-contract-size` overrides the `max-contract-size` of the Cargo.toml.
+fn check_contract_size(
+    res: &BuildResult,
+    max_contract_size: Option<u64>,
+    crate_metadata: &CrateMetadata,
+) -> Result<()> {
+    let max_contract_size = max_contract_size.or(crate_metadata.max_contract_size);
+    let (optimization, max_contract_size) = match (&res.optimization_result, max_contract_size) {
+        (Some(optimization), Some(max_contract_size)) => (optimization, max_contract_size),
+        _ => return Ok(()),
+    };
+    if optimization.optimized_size > max_contract_size as f64 {
+        anyhow::bail!(
+            "The optimized Wasm is {:.1}K, exceeding the maximum contract size of {}K",
+            optimization.optimized_size,
+            max_contract_size
+        )
+    }
+    Ok(())
+}
+
 /// Executes build of the smart-contract which produces a Wasm binary that is ready for deploying.
 ///
 /// It does so by invoking `cargo build` and then post processing the final binary.
@@ -333,44 +1326,361 @@ fn execute(
 /// Uses the supplied `CrateMetadata`. If an instance is not available use [`execute_build`]
 ///
 /// Returns a tuple of `(maybe_optimized_wasm_path, maybe_optimization_result)`.
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn execute_with_crate_metadata(
     crate_metadata: &CrateMetadata,
     verbosity: Option<Verbosity>,
     optimize_contract: bool,
     build_artifact: BuildArtifacts,
+    optimization_passes: OptimizationPasses,
+    keep_debug_symbols: bool,
+    cargo_options: &CargoOptions,
     unstable_flags: UnstableFlags,
+    wasm_opt: &WasmOpt,
+    progress: &Reporter,
 ) -> Result<(Option<PathBuf>, Option<OptimizationResult>)> {
-    println!(
-        " {} {}",
-        format!("[1/{}]", build_artifact.steps()).bold(),
-        "Building cargo project".bright_green().bold()
+    if build_artifact == BuildArtifacts::CheckOnly {
+        // Type checks the contract and the metadata generation, skipping the codegen.
+        util::print_step(
+            verbosity,
+            progress,
+            &crate_metadata.package_name,
+            1,
+            build_artifact.steps(),
+            "Checking cargo project",
+        );
+        let flags = unstable_flags.clone();
+        build_cargo_project(crate_metadata, "check", verbosity, cargo_options, flags)?;
+        util::print_step(
+            verbosity,
+            progress,
+            &crate_metadata.package_name,
+            2,
+            build_artifact.steps(),
+            "Checking metadata generation",
+        );
+        super::metadata::check(crate_metadata, verbosity, cargo_options, unstable_flags)?;
+        return Ok((None, None));
+    }
+    util::print_step(
+        verbosity,
+        progress,
+        &crate_metadata.package_name,
+        1,
+        build_artifact.steps(),
+        "Building cargo project",
     );
-    build_cargo_project(&crate_metadata, verbosity, unstable_flags)?;
-    println!(
-        " {} {}",
-        format!("[2/{}]", build_artifact.steps()).bold(),
-        "Post processing wasm file".bright_green().bold()
+    build_cargo_project(
+        crate_metadata,
+        "build",
+        verbosity,
+        cargo_options,
+        unstable_flags,
+    )?;
+    if crate_metadata.target == Target::RiscV {
+        util::print_step(
+            verbosity,
+            progress,
+            &crate_metadata.package_name,
+            2,
+            build_artifact.steps(),
+            "Linking the PolkaVM program",
+        );
+        link_polkavm(crate_metadata, keep_debug_symbols)?;
+        util::print_step(
+            verbosity,
+            progress,
+            &crate_metadata.package_name,
+            3,
+            build_artifact.steps(),
+            "Validating the PolkaVM program",
+        );
+        super::validate::validate_polkavm(&fs::read(&crate_metadata.dest_wasm)?)?;
+        // The program is not optimized further.
+        return Ok((Some(crate_metadata.dest_wasm.clone()), None));
+    }
+    util::print_step(
+        verbosity,
+        progress,
+        &crate_metadata.package_name,
+        2,
+        build_artifact.steps(),
+        "Post processing wasm file",
     );
-    post_process_wasm(&crate_metadata)?;
+    post_process_wasm(crate_metadata, keep_debug_symbols)?;
     if !optimize_contract {
         return Ok((None, None));
     }
-    println!(
-        " {} {}",
-        format!("[3/{}]", build_artifact.steps()).bold(),
-        "Optimizing wasm file".bright_green().bold()
+    util::print_step(
+        verbosity,
+        progress,
+        &crate_metadata.package_name,
+        3,
+        build_artifact.steps(),
+        "Optimizing wasm file",
     );
-    let optimization_result = optimize_wasm(&crate_metadata)?;
+    let optimization_result = optimize_wasm(
+        crate_metadata,
+        optimization_passes,
+        keep_debug_symbols,
+        wasm_opt,
+    )?;
+    progress.report(Progress::Optimized {
+        package: crate_metadata.package_name.clone(),
+        original_size: optimization_result.original_size,
+        optimized_size: optimization_result.optimized_size,
+    });
     Ok((
         Some(crate_metadata.dest_wasm.clone()),
         Some(optimization_result),
     ))
 }
 
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::{
+        util::tests::with_tmp_dir, BuildArtifacts, BuildResult, ManifestPath, OptimizationPasses,
+        OptimizationResult, UnstableFlags,
+    };
+    use std::{
+        path::{Path, PathBuf},
+        time::Duration,
+    };
+    use structopt::StructOpt;
+
+    /// The result of building only the Wasm of a contract into the target directory.
+    pub(crate) fn code_only_result(
+        target_directory: &Path,
+        dest_wasm: PathBuf,
+        optimization_result: Option<OptimizationResult>,
+    ) -> BuildResult {
+        BuildResult {
+            dest_metadata: None,
+            dest_stripped_metadata: None,
+            dest_wasm: Some(dest_wasm),
+            dest_bundle: None,
+            target_directory: target_directory.to_path_buf(),
+            optimization_result,
+            build_artifact: BuildArtifacts::CodeOnly,
+            size_report: None,
+            custom_sections: Vec::new(),
+            size_delta: None,
+            build_time: Default::default(),
+        }
+    }
+
+    #[test]
+    fn verifiable_build_forwards_the_options() {
+        let build = super::BuildCommand::from_iter(&[
+            "build",
+            "--verifiable",
+            "--generate",
+            "code-only",
+            "--optimization-passes",
+            "s",
+        ]);
+        let args = build.docker_args(Path::new("/home/flipper"), "image:1");
+        assert!(args.contains(&"/home/flipper:/contract".to_string()));
+        assert!(args.ends_with(&[
+            "build".to_string(),
+            "--generate".to_string(),
+            "code-only".to_string(),
+            "--optimization-passes".to_string(),
+            "s".to_string(),
+        ]));
+
+        let wasm_only = super::BuildCommand::from_iter(&["build", "--verifiable", "--wasm-only"]);
+        let args = wasm_only.docker_args(Path::new("/home/flipper"), "image:1");
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--generate", "code-only"]));
+        let both = ["build", "--wasm-only", "--generate", "all"];
+        assert!(super::BuildCommand::from_iter_safe(&both).is_err());
+
+        let debug = ["build", "--verifiable", "--keep-debug-symbols"];
+        assert!(super::BuildCommand::from_iter_safe(&debug).is_err());
+    }
+
+    #[test]
+    fn finds_the_contracts_of_a_virtual_workspace() {
+        with_tmp_dir(|path| {
+            let member = |name: &str, manifest: &str| -> anyhow::Result<()> {
+                std::fs::create_dir_all(path.join(name).join("src"))?;
+                std::fs::write(path.join(name).join("src/lib.rs"), "")?;
+                let package = format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\n", name);
+                std::fs::write(path.join(name).join("Cargo.toml"), package + manifest)?;
+                Ok(())
+            };
+            member(
+                "flipper",
+                "[lib]\ncrate-type = [\"cdylib\"]\n[dependencies]\nink_lang = \"3.0.0-rc2\"\n",
+            )?;
+            member("helpers", "[dependencies]\nink_lang = \"3.0.0-rc2\"\n")?;
+            std::fs::write(
+                path.join("Cargo.toml"),
+                "[workspace]\nmembers = [\"flipper\", \"helpers\"]\n",
+            )?;
+
+            let workspace = ManifestPath::new(&path.join("Cargo.toml"))?;
+            let contracts = super::workspace_contracts(&workspace)?.expect("a virtual workspace");
+            assert_eq!(contracts.len(), 1);
+            assert!(contracts[0].as_ref().ends_with("flipper/Cargo.toml"));
+
+            let flipper = ManifestPath::new(&path.join("flipper").join("Cargo.toml"))?;
+            assert!(super::workspace_contracts(&flipper)?.is_none());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn passes_the_cargo_options_through() {
+        let build = super::BuildCommand::from_iter(&["build", "--features", "a,b c"]);
+        let features = build.cargo_options;
+        assert_eq!(features.cargo_args(), ["--features", "a,b,c"]);
+        let available = vec![
+            "a".to_string(),
+            "b".to_string(),
+            "c".to_string(),
+            "d".to_string(),
+        ];
+        assert_eq!(
+            features.metadata_gen_args("contract", available.iter()),
+            ["--features", "contract/a,contract/b,contract/c"]
+        );
+
+        let all = super::BuildCommand::from_iter(&["build", "--all-features", "--offline"]);
+        let all = all.cargo_options;
+        assert_eq!(all.cargo_args(), ["--offline", "--all-features"]);
+        assert_eq!(all.network_args(), ["--offline"]);
+        assert_eq!(
+            all.metadata_gen_args("contract", available[..1].iter()),
+            ["--offline", "--features", "contract/a"]
+        );
+
+        let check = super::CheckCommand::from_iter(&["check", "--features", "a", "--locked"]);
+        assert_eq!(
+            check.cargo_options.cargo_args(),
+            ["--locked", "--features", "a"]
+        );
+    }
+
+    #[test]
+    fn keeps_the_configured_custom_sections() {
+        with_tmp_dir(|path| {
+            use parity_wasm::elements::{CustomSection, Module, Section};
+            let custom = |name: &str| Section::Custom(CustomSection::new(name.into(), vec![1]));
+            let mut module = Module::new(vec![custom("producers"), custom("provenance")]);
+            super::strip_custom_sections(&mut module, &["provenance".to_string()]);
+            let wasm = path.join("stripped.wasm");
+            parity_wasm::serialize_to_file(&wasm, module)?;
+            assert_eq!(super::custom_sections(&wasm)?, vec!["provenance"]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn forwards_the_verbosity() {
+        let build = super::BuildCommand::from_iter(&["build", "--verifiable", "-vv"]);
+        let args = build.docker_args(Path::new("/home/flipper"), "image:1");
+        assert_eq!(args.iter().filter(|arg| *arg == "--verbose").count(), 2);
+        let verbosity = build.verbosity().unwrap();
+        assert!(matches!(verbosity, Some(crate::Verbosity::VeryVerbose)));
+
+        let json = super::BuildCommand::from_iter(&["build", "-v", "--output", "json"]);
+        assert!(matches!(
+            json.verbosity().unwrap(),
+            Some(crate::Verbosity::Quiet)
+        ));
+        let both = super::BuildCommand::from_iter(&["build", "-q", "-v"]);
+        assert!(both.verbosity().is_err());
+    }
+
+    #[test]
+    fn forwards_the_unstable_options() {
+        let build = super::BuildCommand::from_iter(&["build", "--verifiable", "-Z", "build-std"]);
+        let args = build.docker_args(Path::new("/home/flipper"), "image:1");
+        assert!(args.windows(2).any(|pair| pair == ["-Z", "build-std"]));
+    }
+
+    #[test]
+    fn forwards_the_contract_fields() {
+        let build = super::BuildCommand::from_iter(&[
+            "build",
+            "--verifiable",
+            "--contract-version",
+            "1.2.0",
+            "--contract-author",
+            "Alice",
+            "--contract-author",
+            "Bob",
+        ]);
+        let args = build.docker_args(Path::new("/home/flipper"), "image:1");
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--contract-version", "1.2.0"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--contract-author", "Alice"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--contract-author", "Bob"]));
+        let invalid = ["build", "--contract-repository", "not a url"];
+        assert!(super::BuildCommand::from_iter_safe(&invalid).is_err());
+    }
+
+    #[test]
+    fn rebuilds_std_with_aborting_panics() {
+        let precompiled = UnstableFlags::default();
+        assert!(super::build_std_args(crate::Target::Wasm, &precompiled).is_empty());
+        let build_std = UnstableFlags {
+            build_std: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            super::build_std_args(crate::Target::Wasm, &build_std),
+            [
+                "-Zbuild-std=std,panic_abort",
+                "-Zbuild-std-features=panic_immediate_abort"
+            ]
+        );
+        let riscv = super::build_std_args(crate::Target::RiscV, &precompiled);
+        assert_eq!(riscv[0], "-Zbuild-std=core,alloc");
+    }
+
+    #[test]
+    fn exports_the_build_as_json() {
+        with_tmp_dir(|path| {
+            let wasm = path.join("flipper.wasm");
+            std::fs::write(&wasm, [0, 97, 115, 109, 1, 0, 0, 0])?;
+            let optimization = crate::OptimizationResult {
+                original_size: 2.5,
+                optimized_size: 1.5,
+                optimization_passes: OptimizationPasses::Z,
+                optimization_time: Duration::from_millis(250),
+            };
+            let mut result = code_only_result(path, wasm, Some(optimization));
+            result.build_time = Duration::from_secs(2);
+            let json = serde_json::to_value(super::BuildJson::new(None, &result)?)?;
+            assert!(json.get("name").is_none());
+            assert_eq!(json["code_hash"].as_str().map(str::len), Some(66));
+            assert_eq!(json["optimized_size"], 1.5);
+            assert_eq!(json["optimization_passes"], "z");
+            assert_eq!(json["timings"]["total"], 2.0);
+            assert_eq!(json["timings"]["optimization"], 0.25);
+            assert!(json["metadata"].is_null());
+            Ok(())
+        })
+    }
+}
+
 #[cfg(feature = "test-ci-only")]
 #[cfg(test)]
-mod tests {
-    use crate::{cmd, util::tests::with_tmp_dir, BuildArtifacts, ManifestPath, UnstableFlags};
+mod tests_ci_only {
+    use super::tests::code_only_result;
+    use crate::{
+        cmd, util::tests::with_tmp_dir, BuildArtifacts, ManifestPath, OptimizationPasses,
+        UnstableFlags,
+    };
 
     #[test]
     fn build_template() {
@@ -383,7 +1693,16 @@ mod tests {
                 None,
                 true,
                 BuildArtifacts::All,
+                crate::Target::Wasm,
+                OptimizationPasses::default(),
+                false,
+                None,
+                false,
+                &Default::default(),
+                &Default::default(),
                 UnstableFlags::default(),
+                &Default::default(),
+                &Default::default(),
             )
             .expect("build failed");
 
@@ -396,4 +1715,110 @@ mod tests {
             Ok(())
         })
     }
+
+    #[test]
+    fn collects_the_target_dir_of_the_builder() {
+        with_tmp_dir(|path| {
+            cmd::new::execute("new_project", Some(path)).expect("new project creation failed");
+            let manifest_path = ManifestPath::new(&path.join("new_project").join("Cargo.toml"))?;
+            let builder = super::ContractBuilder::new().target_dir(path.join("custom"));
+            let crate_metadata = crate::crate_metadata::CrateMetadata::collect_for(
+                &manifest_path,
+                crate::Target::Wasm,
+                &builder.cargo_options,
+            )?;
+            assert_eq!(
+                crate_metadata.target_directory,
+                path.join("custom").join("ink")
+            );
+            assert!(crate_metadata.dest_wasm.starts_with(path.join("custom")));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn check_template() {
+        with_tmp_dir(|path| {
+            cmd::new::execute("new_project", Some(path)).expect("new project creation failed");
+            let manifest_path =
+                ManifestPath::new(&path.join("new_project").join("Cargo.toml")).unwrap();
+            let res = super::execute(
+                &manifest_path,
+                None,
+                false,
+                BuildArtifacts::CheckOnly,
+                crate::Target::Wasm,
+                OptimizationPasses::default(),
+                false,
+                None,
+                false,
+                &Default::default(),
+                &Default::default(),
+                UnstableFlags::default(),
+                &Default::default(),
+                &Default::default(),
+            )
+            .expect("check failed");
+            assert!(res.dest_wasm.is_none());
+            assert!(!res.target_directory.join("new_project.wasm").exists());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn runs_the_post_build_hooks() {
+        with_tmp_dir(|path| {
+            cmd::new::execute("new_project", Some(path)).expect("new project creation failed");
+            let project = path.join("new_project");
+            let mut manifest = std::fs::read_to_string(project.join("Cargo.toml"))?;
+            manifest.push_str(
+                "\n[package.metadata.contract]\n\
+                 post-build = [\"echo $CARGO_CONTRACT_NAME $CARGO_CONTRACT_WASM > hook.txt\"]\n",
+            );
+            std::fs::write(project.join("Cargo.toml"), manifest)?;
+            let manifest_path = ManifestPath::new(&project.join("Cargo.toml"))?;
+            let crate_metadata = crate::crate_metadata::CrateMetadata::collect(&manifest_path)?;
+            let result = code_only_result(path, path.join("new_project.wasm"), None);
+            super::run_post_build_hooks(&crate_metadata, &result, None)?;
+            let hook = std::fs::read_to_string(project.join("hook.txt"))?;
+            let wasm = path.join("new_project.wasm");
+            assert_eq!(hook.trim(), format!("new_project {}", wasm.display()));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn checks_the_contract_size() {
+        with_tmp_dir(|path| {
+            cmd::new::execute("new_project", Some(path)).expect("new project creation failed");
+            let project = path.join("new_project");
+            let manifest_path = ManifestPath::new(&project.join("Cargo.toml"))?;
+            let crate_metadata = crate::crate_metadata::CrateMetadata::collect(&manifest_path)?;
+            let optimization = crate::OptimizationResult {
+                original_size: 80.0,
+                optimized_size: 60.0,
+                optimization_passes: OptimizationPasses::default(),
+                optimization_time: Default::default(),
+            };
+            let wasm = path.join("new_project.wasm");
+            let result = code_only_result(path, wasm, Some(optimization));
+            assert!(super::check_contract_size(&result, None, &crate_metadata).is_ok());
+            assert!(super::check_contract_size(&result, Some(64), &crate_metadata).is_ok());
+            let error = super::check_contract_size(&result, Some(50), &crate_metadata)
+                .expect_err("the contract exceeds the maximum size");
+            assert_eq!(
+                error.to_string(),
+                "The optimized Wasm is 60.0K, exceeding the maximum contract size of 50K"
+            );
+
+            let mut manifest = std::fs::read_to_string(project.join("Cargo.toml"))?;
+            manifest.push_str("\n[package.metadata.contract]\nmax-contract-size = 50\n");
+            std::fs::write(project.join("Cargo.toml"), manifest)?;
+            let crate_metadata = crate::crate_metadata::CrateMetadata::collect(&manifest_path)?;
+            assert_eq!(crate_metadata.max_contract_size, Some(50));
+            assert!(super::check_contract_size(&result, None, &crate_metadata).is_err());
+            assert!(super::check_contract_size(&result, Some(64), &crate_metadata).is_ok());
+            Ok(())
+        })
+    }
 }
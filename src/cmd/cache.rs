@@ -0,0 +1,157 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use structopt::StructOpt;
+use walkdir::WalkDir;
+
+use super::wasm_opt::download_dir;
+
+/// The version of the layout of the cache, naming its directory so that a release changing the
+/// layout does not pick up the entries of another.
+const CACHE_VERSION: u32 = 1;
+
+/// The directory of the cache, `~/.cargo-contract/cache`, holding one directory per layout
+/// version.
+fn cache_root() -> Option<PathBuf> {
+    Some(download_dir()?.join("cache"))
+}
+
+/// The directory the templates of `new` and the pinned releases of binaryen and of the contracts
+/// node are cached in, so they are downloaded once and available offline.
+pub(crate) fn cache_dir() -> Option<PathBuf> {
+    Some(cache_root()?.join(format!("v{}", CACHE_VERSION)))
+}
+
+/// Manage the cache of the downloaded templates and releases.
+#[derive(Debug, StructOpt)]
+pub(crate) enum CacheCommand {
+    /// List the cached templates and releases with their sizes
+    #[structopt(name = "list")]
+    List,
+    /// Remove the cache, including the entries of other versions of cargo-contract
+    #[structopt(name = "clean")]
+    Clean,
+}
+
+impl CacheCommand {
+    pub fn exec(&self) -> Result<String> {
+        let root =
+            cache_root().ok_or_else(|| anyhow::anyhow!("The cache requires the HOME directory"))?;
+        match self {
+            CacheCommand::List => {
+                let dir = root.join(format!("v{}", CACHE_VERSION));
+                let mut output = format!("Cache in {}", dir.display());
+                for (name, size) in entries(&dir)? {
+                    output.push_str(&format!("\n\t{}: {:.1}K", name, size as f64 / 1000.0));
+                }
+                Ok(output)
+            }
+            CacheCommand::Clean => {
+                let size = clean(&root)?;
+                Ok(format!(
+                    "Removed {:.1}K from the cache in {}",
+                    size as f64 / 1000.0,
+                    root.display()
+                ))
+            }
+        }
+    }
+}
+
+/// The entries of the cache directory, e.g. `templates/<template>`, with their sizes in bytes.
+fn entries(dir: &Path) -> Result<Vec<(String, u64)>> {
+    let mut entries = Vec::new();
+    if !dir.exists() {
+        return Ok(entries);
+    }
+    let walk = WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(2)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()));
+    for entry in walk {
+        let entry = entry?;
+        // The templates are grouped in a directory, the releases are not.
+        if entry.depth() == 1 && entry.file_name() == "templates" {
+            continue;
+        }
+        if entry.depth() == 2 && !entry.path().starts_with(dir.join("templates")) {
+            continue;
+        }
+        let name = entry
+            .path()
+            .strip_prefix(dir)?
+            .to_string_lossy()
+            .replace(std::path::MAIN_SEPARATOR, "/");
+        entries.push((name, size(entry.path())));
+    }
+    Ok(entries)
+}
+
+/// The size of the files in the directory, in bytes.
+fn size(dir: &Path) -> u64 {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Removes the cache, returning the size of the files removed in bytes.
+fn clean(root: &Path) -> Result<u64> {
+    if !root.exists() {
+        return Ok(0);
+    }
+    let size = size(root);
+    fs::remove_dir_all(root).context(format!("Failed to remove {}", root.display()))?;
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_and_cleans_the_entries() {
+        crate::util::tests::with_tmp_dir(|tmp_dir| {
+            let root = &tmp_dir.join("cache");
+            let dir = root.join("v1");
+            fs::create_dir_all(dir.join("templates/github.com_dex"))?;
+            fs::write(dir.join("templates/github.com_dex/Cargo.toml"), "1234")?;
+            fs::create_dir_all(dir.join("binaryen-version_101/bin"))?;
+            fs::write(dir.join("binaryen-version_101/bin/wasm-opt"), "12")?;
+
+            assert_eq!(
+                entries(&dir)?,
+                vec![
+                    ("binaryen-version_101".to_string(), 2),
+                    ("templates/github.com_dex".to_string(), 4)
+                ]
+            );
+            assert_eq!(clean(root)?, 6);
+            assert!(!root.exists());
+            assert_eq!(clean(root)?, 0);
+            Ok(())
+        })
+    }
+}
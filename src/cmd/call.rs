@@ -0,0 +1,738 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use jsonrpsee::common::{JsonValue, Params};
+use serde::{Deserialize, Serialize};
+use sp_core::{crypto::AccountId32, Bytes, H256};
+use structopt::StructOpt;
+use subxt::{contracts::CallCall, DefaultNodeRuntime};
+
+use super::{
+    address::Address,
+    balance::{BalanceFormat, BalanceVariant},
+    block::{self, BlockRef},
+    events::ExtrinsicDetails,
+    extrinsic::Connection,
+    fork,
+    gas::{display_proof_size, display_ref_time, GasLimit, GasMargin, DRY_RUN_GAS_LIMIT},
+    info::{held_storage_deposit, HeldStorageDeposit},
+    module_error::contracts_hint,
+    sandbox::HostCall,
+    sign::{self, OfflineParams, SignedExtrinsic},
+    signer::ExtrinsicSigner,
+    storage::{self, StorageWrite},
+    transcode::InkMetadata,
+};
+use crate::{ExtrinsicOpts, HexData};
+
+/// The flag of the value returned by a contract signalling that it reverted its changes.
+const REVERT_FLAG: u32 = 1;
+
+/// The maximum number of Wasm instructions of the sandboxed replay of a dry run.
+const REPLAY_MAX_INSTRUCTIONS: u64 = 100_000_000;
+
+/// The message a contract is called with, either its hex encoded data or its label and arguments
+/// in the metadata of the contract.
+#[derive(Debug, StructOpt)]
+pub(crate) struct MessageOpts {
+    /// Hex encoded data to call a contract message, instead of encoding the `--message` with its
+    /// `--args`
+    #[structopt(long, required_unless = "message", conflicts_with_all = &["message", "args"])]
+    data: Option<HexData>,
+    /// The label of the message to call. Messages of ink! trait definitions are qualified with
+    /// the trait, e.g. `Erc20::transfer`, which may be left out unless several traits define it
+    #[structopt(long)]
+    message: Option<String>,
+    /// The arguments of the message, JSON values or else plain strings, e.g. `42`, `true` or an
+    /// SS58 address
+    #[structopt(long)]
+    args: Vec<String>,
+    /// Path to the metadata of the contract, defaults to `./target/ink/metadata.json`
+    #[structopt(long = "contract-metadata", parse(from_os_str))]
+    contract_metadata: Option<PathBuf>,
+}
+
+impl MessageOpts {
+    /// The metadata of `--contract-metadata`, or else the one built for the contract project in
+    /// the current directory, if any.
+    pub fn metadata(&self) -> Result<Option<InkMetadata>> {
+        InkMetadata::load(self.contract_metadata.as_deref())
+    }
+
+    /// The data calling the message, encoded with the metadata unless given as `--data`.
+    pub fn data(&self) -> Result<HexData> {
+        if let Some(ref data) = self.data {
+            return Ok(data.clone());
+        }
+        let metadata = InkMetadata::load(self.contract_metadata.as_deref())?.ok_or_else(|| {
+            anyhow::anyhow!(
+                "Calling a message without --data requires the metadata of the contract to \
+                 encode it, build the contract or supply --contract-metadata"
+            )
+        })?;
+        let message = metadata.find_message(self.message.as_deref(), "")?;
+        Ok(HexData(metadata.encode_call(&message, &self.args)?))
+    }
+}
+
+/// Result of a dry run of a contract call.
+#[derive(Debug, Serialize)]
+pub(crate) struct DryRunResult {
+    /// The error the call failed with, if it did not return.
+    pub error: Option<String>,
+    /// An explanation of the error and its likely fix, for frequent errors of the contracts
+    /// module.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<&'static str>,
+    /// Whether the contract reverted the changes of the call.
+    pub reverted: bool,
+    /// Hex encoded data returned by the contract.
+    pub data: Option<String>,
+    /// The gas consumed by the call, its `ref_time` on nodes with two dimensional weights.
+    pub gas_consumed: u64,
+    /// The proof size consumed by the call, on nodes with two dimensional weights.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proof_size: Option<u64>,
+    /// The storage deposit the call charges, negative if it is refunded, on nodes reporting it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_deposit: Option<i128>,
+    /// The messages printed by the contract with `ink_env::debug_println!`.
+    pub debug_message: String,
+    /// The host functions called by the contract, with `--trace`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<Vec<HostCall>>,
+    /// The storage cells the call writes and removes, with `--storage-diff`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_diff: Option<Vec<StorageWrite>>,
+    /// How the storage deposit is printed in the human readable output.
+    #[serde(skip)]
+    pub balance_format: BalanceFormat,
+}
+
+impl DryRunResult {
+    /// The human readable output, which includes the debug messages if `verbose`.
+    pub fn display(&self, verbose: bool) -> String {
+        let outcome = match self.error {
+            Some(ref error) => format!("Dry run failed: {}", error),
+            None if self.reverted => "Dry run reverted".to_string(),
+            None => "Dry run succeeded".to_string(),
+        };
+        let outcome = match self.hint {
+            Some(hint) => format!("{}\n\tHint: {}", outcome, hint),
+            None => outcome,
+        };
+        let gas_consumed = display_ref_time(self.gas_consumed);
+        let mut output = match self.proof_size {
+            Some(proof_size) => format!(
+                "{}\n\tGas consumed: {} ref time\n\tProof size: {}",
+                outcome,
+                gas_consumed,
+                display_proof_size(proof_size)
+            ),
+            None => format!(
+                "{}\n\tGas consumed: {}, as a single dimensional weight",
+                outcome, gas_consumed
+            ),
+        };
+        if let Some(storage_deposit) = self.storage_deposit {
+            output.push_str(&format!(
+                "\n\tStorage deposit: {}",
+                self.balance_format.display_signed(storage_deposit)
+            ));
+        }
+        if let Some(ref data) = self.data {
+            output.push_str(&format!("\n\tReturn data: {}", data));
+        }
+        if verbose {
+            for line in self.debug_message.lines() {
+                output.push_str(&format!("\n\tDebug: {}", line));
+            }
+        } else if !self.debug_message.is_empty() {
+            output.push_str("\n\tThe contract printed debug messages, pass --verbose to show them");
+        }
+        for call in self.trace.iter().flatten() {
+            output.push_str(&format!("\n\tHost call {}", call.display()));
+        }
+        if let Some(ref storage_diff) = self.storage_diff {
+            if storage_diff.is_empty() {
+                output.push_str("\n\tThe call writes no storage");
+            }
+            for write in storage_diff {
+                output.push_str(&format!("\n\tStorage {}", write.display()));
+            }
+        }
+        output
+    }
+}
+
+/// The result of the `contracts_call` RPC, in the format of the contracts pallet since it returns
+/// the debug messages, or the earlier one without them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RpcCallResult {
+    Current(ContractResult),
+    Legacy(LegacyResult),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContractResult {
+    gas_consumed: Weight,
+    #[serde(default = "no_debug_message")]
+    debug_message: Bytes,
+    result: std::result::Result<ExecReturnValue, JsonValue>,
+    /// `{ "charge": amount }` or `{ "refund": amount }`, reported by later versions of the pallet.
+    #[serde(default)]
+    storage_deposit: Option<JsonValue>,
+}
+
+/// The weight consumed, which later versions of the pallet measure in two dimensions.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Weight {
+    RefTime(u64),
+    #[serde(rename_all = "camelCase")]
+    TwoDimensional {
+        ref_time: u64,
+        proof_size: u64,
+    },
+}
+
+/// The storage deposit charged, or refunded as a negative amount.
+fn storage_deposit(deposit: &JsonValue) -> Option<i128> {
+    let (sign, amount) = match (deposit.get("charge"), deposit.get("refund")) {
+        (Some(amount), _) => (1, amount),
+        (_, Some(amount)) => (-1, amount),
+        _ => return None,
+    };
+    let amount = match amount {
+        JsonValue::Number(amount) => amount.as_u64().map(i128::from),
+        JsonValue::String(amount) => match amount.strip_prefix("0x") {
+            Some(hex) => i128::from_str_radix(hex, 16).ok(),
+            None => amount.parse().ok(),
+        },
+        _ => None,
+    };
+    amount.map(|amount| sign * amount)
+}
+
+#[derive(Deserialize)]
+struct ExecReturnValue {
+    flags: ReturnFlags,
+    data: Bytes,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ReturnFlags {
+    Bits { bits: u32 },
+    Number(u32),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum LegacyResult {
+    #[serde(rename_all = "camelCase")]
+    Success {
+        flags: u32,
+        data: Bytes,
+        gas_consumed: u64,
+    },
+    Error(()),
+}
+
+impl From<RpcCallResult> for DryRunResult {
+    fn from(result: RpcCallResult) -> Self {
+        let returned = |flags, data: Bytes| (flags & REVERT_FLAG != 0, Some(hex_data(&data.0)));
+        match result {
+            RpcCallResult::Current(result) => {
+                let (error, (reverted, data)) = match result.result {
+                    Ok(ExecReturnValue { flags, data }) => {
+                        let flags = match flags {
+                            ReturnFlags::Bits { bits } => bits,
+                            ReturnFlags::Number(flags) => flags,
+                        };
+                        (None, returned(flags, data))
+                    }
+                    Err(error) => (Some(error), (false, None)),
+                };
+                let hint = error
+                    .as_ref()
+                    .and_then(|error| error["Module"]["message"].as_str())
+                    .and_then(contracts_hint);
+                let (gas_consumed, proof_size) = match result.gas_consumed {
+                    Weight::RefTime(ref_time) => (ref_time, None),
+                    Weight::TwoDimensional {
+                        ref_time,
+                        proof_size,
+                    } => (ref_time, Some(proof_size)),
+                };
+                DryRunResult {
+                    error: error.map(|error| error.to_string()),
+                    hint,
+                    reverted,
+                    data,
+                    gas_consumed,
+                    proof_size,
+                    storage_deposit: result.storage_deposit.as_ref().and_then(storage_deposit),
+                    debug_message: String::from_utf8_lossy(&result.debug_message.0).into_owned(),
+                    trace: None,
+                    storage_diff: None,
+                    balance_format: BalanceFormat::default(),
+                }
+            }
+            RpcCallResult::Legacy(LegacyResult::Success {
+                flags,
+                data,
+                gas_consumed,
+            }) => {
+                let (reverted, data) = returned(flags, data);
+                DryRunResult {
+                    error: None,
+                    hint: None,
+                    reverted,
+                    data,
+                    gas_consumed,
+                    proof_size: None,
+                    storage_deposit: None,
+                    debug_message: String::new(),
+                    trace: None,
+                    storage_diff: None,
+                    balance_format: BalanceFormat::default(),
+                }
+            }
+            RpcCallResult::Legacy(LegacyResult::Error(())) => DryRunResult {
+                error: Some("The contract trapped".to_string()),
+                hint: contracts_hint("ContractTrapped"),
+                reverted: false,
+                data: None,
+                gas_consumed: 0,
+                proof_size: None,
+                storage_deposit: None,
+                debug_message: String::new(),
+                trace: None,
+                storage_diff: None,
+                balance_format: BalanceFormat::default(),
+            },
+        }
+    }
+}
+
+fn no_debug_message() -> Bytes {
+    Bytes(Vec::new())
+}
+
+fn hex_data(data: &[u8]) -> String {
+    format!("0x{}", hex::encode(data))
+}
+
+/// The errors of the contracts module after which `--auto-retry` resubmits a call.
+const RETRIED_ERRORS: [&str; 2] = ["OutOfGas", "StorageDepositLimitExhausted"];
+
+/// Result of a successful `call`.
+#[derive(Debug, Serialize)]
+pub(crate) struct CallResult {
+    /// The gas limit of the successful extrinsic.
+    pub gas_limit: u64,
+    /// The first attempt, if it failed and the call was resubmitted.
+    pub retried: Option<FailedAttempt>,
+    /// The storage deposit the contract holds after the call, if its inclusion was awaited.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_deposit: Option<HeldStorageDeposit>,
+    #[serde(flatten)]
+    pub extrinsic: ExtrinsicDetails,
+}
+
+/// A submitted call which failed.
+#[derive(Debug, Serialize)]
+pub(crate) struct FailedAttempt {
+    pub gas_limit: u64,
+    pub error: String,
+}
+
+impl CallResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let mut output = String::new();
+        if let Some(ref retried) = self.retried {
+            output.push_str(&format!(
+                "The call with gas limit {} failed: {}, resubmitted it with gas limit {}\n\t",
+                retried.gas_limit, retried.error, self.gas_limit
+            ));
+        }
+        output.push_str(&format!("Extrinsic hash: {:?}", self.extrinsic.extrinsic));
+        if let Some(ref storage_deposit) = self.storage_deposit {
+            output.push_str(&format!(
+                "\n\t{}",
+                storage_deposit.display(&self.extrinsic.balance_format)
+            ));
+        }
+        self.extrinsic.display_details(output)
+    }
+}
+
+/// Returns the error the extrinsic failed with, if it is one of the contracts module after which
+/// the call is resubmitted.
+fn retried_error(err: &anyhow::Error) -> Option<String> {
+    match err.downcast_ref::<subxt::Error>() {
+        // The module errors are only exposed by their display, `<error> from <module>`.
+        Some(subxt::Error::Runtime(error)) => {
+            Some(error.to_string()).filter(|error| is_retried(error))
+        }
+        _ => None,
+    }
+}
+
+fn is_retried(error: &str) -> bool {
+    RETRIED_ERRORS
+        .iter()
+        .any(|name| error.ends_with(&format!("{} from Contracts", name)))
+}
+
+/// Call a contract, submitting a `Contracts::call` extrinsic.
+///
+/// A gas limit of `auto` is estimated with a dry run, increased by the `gas_margin`. With
+/// `auto_retry`, a call failing for running out of gas or storage deposit is estimated again on
+/// the current state and resubmitted once, with at least one and a half times the gas limit.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn execute_call(
+    extrinsic_opts: &ExtrinsicOpts,
+    signer: &ExtrinsicSigner,
+    contract: &Address,
+    value: BalanceVariant,
+    gas_limit: GasLimit,
+    gas_margin: GasMargin,
+    auto_retry: bool,
+    data: HexData,
+) -> Result<CallResult> {
+    async_std::task::block_on(call_contract(
+        extrinsic_opts,
+        signer,
+        contract,
+        value,
+        gas_limit,
+        gas_margin,
+        auto_retry,
+        &data.0,
+    ))
+}
+
+/// Calls the contract, see [`execute_call`].
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn call_contract(
+    extrinsic_opts: &ExtrinsicOpts,
+    signer: &ExtrinsicSigner,
+    contract: &Address,
+    value: BalanceVariant,
+    gas_limit: GasLimit,
+    gas_margin: GasMargin,
+    auto_retry: bool,
+    data: &[u8],
+) -> Result<CallResult> {
+    let mut connection = Connection::new(&extrinsic_opts.submit).await?;
+    contract.check_format();
+
+    let value = connection.denominate(&value)?;
+    // Estimated as dispatched, from the proxied or multisig account if any.
+    let origin = extrinsic_opts.origin(signer.account_id())?;
+    let request = DryRunRequest {
+        origin: &origin,
+        dest: &contract.account,
+        value,
+        data,
+        at: None,
+    };
+    let dest = contract.account.clone().into();
+    let call = |gas_limit| CallCall::<DefaultNodeRuntime> {
+        dest: &dest,
+        value,
+        gas_limit,
+        data,
+    };
+
+    let first_limit = gas_limit
+        .resolve(&connection, gas_margin, request, signer, extrinsic_opts)
+        .await?;
+    let error = match connection
+        .submit_transferring(call(first_limit), signer, extrinsic_opts, value)
+        .await
+    {
+        Ok(submitted) => {
+            return Ok(CallResult {
+                gas_limit: first_limit,
+                retried: None,
+                storage_deposit: held_storage_deposit(&connection, &contract.account, &submitted)
+                    .await,
+                extrinsic: ExtrinsicDetails::new(&submitted)?,
+            })
+        }
+        Err(err) => match retried_error(&err) {
+            Some(error) if auto_retry => error,
+            _ => return Err(err),
+        },
+    };
+    log::warn!("The call failed with {}, estimating it again", error);
+    let estimate = GasLimit::Auto
+        .resolve(&connection, gas_margin, request, signer, extrinsic_opts)
+        .await
+        .context(format!("The call failed with {}", error))?;
+    let gas_limit = std::cmp::max(estimate, first_limit + first_limit / 2);
+    let submitted = connection
+        .submit_transferring(call(gas_limit), signer, extrinsic_opts, value)
+        .await?;
+    Ok(CallResult {
+        gas_limit,
+        retried: Some(FailedAttempt {
+            gas_limit: first_limit,
+            error,
+        }),
+        storage_deposit: held_storage_deposit(&connection, &contract.account, &submitted).await,
+        extrinsic: ExtrinsicDetails::new(&submitted)?,
+    })
+}
+
+/// A contract call to dry run.
+#[derive(Clone, Copy)]
+pub(crate) struct DryRunRequest<'a> {
+    /// The account the call is executed as.
+    pub origin: &'a AccountId32,
+    pub dest: &'a AccountId32,
+    pub value: u128,
+    pub data: &'a [u8],
+    /// The block whose state the call is executed on, the best block if `None`.
+    pub at: Option<H256>,
+}
+
+/// Dry runs the contract call via the `contracts_call` RPC of the node, on the state of the
+/// requested block.
+pub(crate) async fn dry_run(
+    connection: &Connection,
+    request: DryRunRequest<'_>,
+    gas_limit: u64,
+) -> Result<DryRunResult> {
+    let params = Params::Array(vec![
+        serde_json::json!({
+        "origin": request.origin,
+        "dest": request.dest,
+        "value": format!("0x{:x}", request.value),
+        "gasLimit": gas_limit,
+        "inputData": hex_data(request.data),
+        }),
+        serde_json::json!(request.at),
+    ]);
+    let result: RpcCallResult = connection
+        .request("contracts_call", params)
+        .await
+        .context("Failed to dry run the call")?;
+    let mut result = DryRunResult::from(result);
+    result.balance_format = connection.balance_format();
+    Ok(result)
+}
+
+/// What the replay of a dry run in the sandbox reports, since the contracts pallet offers no RPC
+/// tracing the host functions a call executes or the storage it writes.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Replay<'a> {
+    /// Record the host functions the call executes.
+    pub trace: bool,
+    /// Report the storage cells the call writes and removes.
+    pub storage_diff: bool,
+    /// The metadata decoding the cells written against the storage layout.
+    pub metadata: Option<&'a InkMetadata>,
+}
+
+impl Replay<'_> {
+    fn enabled(&self) -> bool {
+        self.trace || self.storage_diff
+    }
+}
+
+/// Dry run a contract call via the `contracts_call` RPC of the node, without submitting an
+/// extrinsic. The call is executed on the state of the block `at`, defaulting to the best block,
+/// from the origin the call would be dispatched from.
+///
+/// With `replay` enabled the call is replayed in the sandbox on a fork of the contract at the
+/// same block, tracing its host functions or collecting the storage it writes. The replay is
+/// only as faithful as the sandbox: a call to another contract traps in it.
+pub(crate) fn dry_run_call(
+    extrinsic_opts: &ExtrinsicOpts,
+    contract: &Address,
+    value: BalanceVariant,
+    gas_limit: GasLimit,
+    data: HexData,
+    at: Option<&BlockRef>,
+    replay: Replay<'_>,
+) -> Result<DryRunResult> {
+    let origin = extrinsic_opts.origin(extrinsic_opts.signer()?.account_id())?;
+    let (mut result, value, sandbox) = async_std::task::block_on(async {
+        let connection = Connection::new(&extrinsic_opts.submit).await?;
+        contract.check_format();
+
+        let at = match block::resolve(&connection, at).await? {
+            None if replay.enabled() => Some(fork::best_block(&connection).await?),
+            at => at,
+        };
+        let request = DryRunRequest {
+            origin: &origin,
+            dest: &contract.account,
+            value: connection.denominate(&value)?,
+            data: &data.0,
+            at,
+        };
+        let gas_limit = match gas_limit {
+            GasLimit::Limit(limit) => limit,
+            GasLimit::Auto => DRY_RUN_GAS_LIMIT,
+        };
+        let result = dry_run(&connection, request, gas_limit).await?;
+        let sandbox = match at {
+            Some(at) if replay.enabled() => {
+                let account = &contract.account;
+                let sandbox =
+                    fork::fork_at(connection, account, at, None, REPLAY_MAX_INSTRUCTIONS).await?;
+                Some(sandbox)
+            }
+            _ => None,
+        };
+        Ok::<_, anyhow::Error>((result, request.value, sandbox))
+    })?;
+    if let Some(mut sandbox) = sandbox {
+        sandbox.trace = replay.trace;
+        let execution = sandbox.call(&origin, value, data.0)?;
+        if replay.storage_diff {
+            // The changes of a call which traps or reverts are discarded, as on the chain.
+            let written = if execution.succeeded() {
+                storage::written_cells(&execution.storage, replay.metadata)?
+            } else {
+                Vec::new()
+            };
+            result.storage_diff = Some(written);
+        }
+        if replay.trace {
+            result.trace = Some(execution.trace);
+        }
+    }
+    Ok(result)
+}
+
+/// Sign the `Contracts::call` extrinsic without submitting it.
+pub(crate) fn sign_call(
+    extrinsic_opts: &ExtrinsicOpts,
+    params: &OfflineParams,
+    contract: &Address,
+    value: BalanceVariant,
+    gas_limit: GasLimit,
+    data: HexData,
+) -> Result<SignedExtrinsic> {
+    let dest = contract.account.clone().into();
+    let call = CallCall::<DefaultNodeRuntime> {
+        dest: &dest,
+        value: value.without_denomination()?,
+        gas_limit: gas_limit.fixed()?,
+        data: &data.0,
+    };
+    sign::sign_offline(call, extrinsic_opts, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dry_run(json: &str) -> DryRunResult {
+        serde_json::from_str::<RpcCallResult>(json)
+            .expect("valid result")
+            .into()
+    }
+
+    #[test]
+    fn decodes_debug_messages() {
+        let result = dry_run(
+            r#"{
+                "gasConsumed": 1234,
+                "debugMessage": "0x68656c6c6f0a776f726c64",
+                "result": { "Ok": { "flags": { "bits": 1 }, "data": "0x01" } }
+            }"#,
+        );
+        assert_eq!(result.error, None);
+        assert!(result.reverted);
+        assert_eq!(result.data.as_deref(), Some("0x01"));
+        assert_eq!(result.debug_message, "hello\nworld");
+        assert!(result
+            .display(true)
+            .ends_with("Debug: hello\n\tDebug: world"));
+        assert!(!result.display(false).contains("hello"));
+    }
+
+    #[test]
+    fn retries_calls_running_out_of_gas() {
+        assert!(is_retried("Runtime module error: OutOfGas from Contracts"));
+        assert!(is_retried("StorageDepositLimitExhausted from Contracts"));
+        assert!(!is_retried(
+            "Runtime module error: ContractTrapped from Contracts"
+        ));
+        assert!(!is_retried("Runtime module error: OutOfGas from Other"));
+        assert_eq!(
+            retried_error(&anyhow::anyhow!("OutOfGas from Contracts")),
+            None
+        );
+    }
+
+    #[test]
+    fn decodes_legacy_and_failed_results() {
+        let result = dry_run(r#"{ "success": { "flags": 0, "data": "0x", "gasConsumed": 5 } }"#);
+        assert!(!result.reverted);
+        assert_eq!(result.gas_consumed, 5);
+        assert!(dry_run(r#"{ "error": null }"#).error.is_some());
+
+        let failed = dry_run(
+            r#"{ "gasConsumed": 7, "debugMessage": "0x", "result": { "Err": { "Module": {} } } }"#,
+        );
+        assert!(failed.error.is_some());
+        assert_eq!(failed.data, None);
+    }
+
+    #[test]
+    fn decodes_two_dimensional_weights_and_storage_deposits() {
+        let result = dry_run(
+            r#"{
+                "gasConsumed": { "refTime": 1234, "proofSize": 56 },
+                "debugMessage": "0x",
+                "result": { "Ok": { "flags": { "bits": 0 }, "data": "0x" } },
+                "storageDeposit": { "refund": "0x10" }
+            }"#,
+        );
+        assert_eq!(result.gas_consumed, 1234);
+        assert_eq!(result.proof_size, Some(56));
+        assert_eq!(result.storage_deposit, Some(-16));
+    }
+
+    #[test]
+    fn hints_at_the_fix_of_module_errors() {
+        let result = dry_run(
+            r#"{
+                "gasConsumed": 1234,
+                "debugMessage": "0x",
+                "result": {
+                    "Err": { "Module": { "index": 18, "error": 11, "message": "ContractTrapped" } }
+                }
+            }"#,
+        );
+        assert!(result.error.is_some());
+        assert!(result.hint.unwrap().contains("--trace"));
+    }
+}
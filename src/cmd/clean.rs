@@ -0,0 +1,94 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    convert::TryFrom,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use structopt::StructOpt;
+
+use crate::workspace::{ManifestPath, LEGACY_METADATA_PACKAGE_PATH};
+
+/// Removes the artifacts and intermediate files of cargo-contract, which `cargo clean` leaves
+/// behind.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "clean")]
+pub(crate) struct CleanCommand {
+    /// Path to the Cargo.toml of the contract or workspace to clean
+    #[structopt(long, parse(from_os_str))]
+    manifest_path: Option<PathBuf>,
+}
+
+impl CleanCommand {
+    pub fn exec(&self) -> Result<String> {
+        let manifest_path = ManifestPath::try_from(self.manifest_path.as_ref())?;
+        let metadata = cargo_metadata::MetadataCommand::new()
+            .manifest_path(manifest_path.as_ref())
+            .no_deps()
+            .exec()
+            .context("Error invoking `cargo metadata`")?;
+        let mut dirs = vec![metadata.target_directory.join("ink")];
+        for package in &metadata.packages {
+            if let Some(dir) = package.manifest_path.parent() {
+                dirs.push(dir.join(LEGACY_METADATA_PACKAGE_PATH));
+            }
+        }
+        let removed = remove_dirs(&dirs)?;
+        if removed.is_empty() {
+            return Ok("Nothing to clean".to_string());
+        }
+        let mut out = "Removed".to_string();
+        for dir in removed {
+            out.push_str(&format!("\n\t{}", dir.display()));
+        }
+        Ok(out)
+    }
+}
+
+/// Removes those of the directories which exist, returning them.
+fn remove_dirs(dirs: &[PathBuf]) -> Result<Vec<&Path>> {
+    let mut removed = Vec::new();
+    for dir in dirs.iter().filter(|dir| dir.exists()) {
+        fs::remove_dir_all(dir).context(format!("Failed to remove {}", dir.display()))?;
+        removed.push(dir.as_path());
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_the_existing_directories() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            let ink = dir.join("target/ink");
+            fs::create_dir_all(ink.join("wasm32-unknown-unknown"))?;
+            fs::write(ink.join("metadata.json"), "{}")?;
+            let legacy = dir.join(LEGACY_METADATA_PACKAGE_PATH);
+
+            let dirs = [ink.clone(), legacy];
+            assert_eq!(remove_dirs(&dirs)?, [ink.as_path()]);
+            assert!(!ink.exists());
+            assert!(dir.join("target").exists());
+            assert!(remove_dirs(&dirs)?.is_empty());
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,409 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    convert::TryFrom,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use codec::{Decode, Encode, Output};
+use jsonrpsee::common::{to_value, Params};
+use serde::Serialize;
+use sp_core::{
+    crypto::AccountId32,
+    storage::{StorageChangeSet, StorageKey},
+    H256,
+};
+use subxt::{
+    contracts::ContractsEventsDecoder, Call, DefaultNodeRuntime, EventsDecoder, Metadata,
+    MetadataError, Store,
+};
+
+use super::{
+    address::Address,
+    build,
+    events::ExtrinsicDetails,
+    extrinsic::Connection,
+    info::{ContractInfo, ContractInfoOfStore},
+    sign::{self, OfflineParams, SignedExtrinsic},
+};
+use crate::{
+    crate_metadata::CrateMetadata, BuildArtifacts, ExtrinsicOpts, ManifestPath, OptimizationPasses,
+    SubmitOpts, UnstableFlags,
+};
+
+/// The number of storage keys fetched at once when iterating a storage map.
+const KEYS_PAGE_SIZE: u32 = 256;
+
+/// The `Contracts::PristineCode` storage map, holding the Wasm code of a code hash as it was
+/// uploaded.
+pub(crate) struct PristineCodeStore<'a> {
+    pub code_hash: &'a H256,
+}
+
+impl Encode for PristineCodeStore<'_> {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.code_hash.encode_to(dest)
+    }
+}
+
+impl Store<DefaultNodeRuntime> for PristineCodeStore<'_> {
+    const MODULE: &'static str = "Contracts";
+    const FIELD: &'static str = "PristineCode";
+    type Returns = Vec<u8>;
+
+    fn prefix(metadata: &Metadata) -> Result<StorageKey, MetadataError> {
+        Ok(metadata
+            .module(Self::MODULE)?
+            .storage(Self::FIELD)?
+            .prefix())
+    }
+
+    fn key(&self, metadata: &Metadata) -> Result<StorageKey, MetadataError> {
+        Ok(metadata
+            .module(Self::MODULE)?
+            .storage(Self::FIELD)?
+            .map()?
+            .key(self.code_hash))
+    }
+}
+
+/// Result of a successful `download`.
+#[derive(Debug, Serialize)]
+pub(crate) struct DownloadResult {
+    pub code_hash: H256,
+    /// The file the Wasm code was written to.
+    pub path: PathBuf,
+    /// The size of the Wasm code in bytes.
+    pub size: usize,
+}
+
+impl DownloadResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        format!(
+            "Wrote the code of {:?} to {} ({} bytes)",
+            self.code_hash,
+            self.path.display(),
+            self.size
+        )
+    }
+}
+
+/// Fetches the Wasm code of the code hash, as uploaded to the chain.
+pub(crate) async fn fetch_code(connection: &Connection, code_hash: &H256) -> Result<Vec<u8>> {
+    let code = connection
+        .fetch(&PristineCodeStore { code_hash })
+        .await
+        .context("Failed to fetch the code")?
+        .ok_or_else(|| anyhow::anyhow!("No code stored for the code hash {:?}", code_hash))?;
+    if sp_core::blake2_256(&code) != code_hash.0 {
+        log::warn!(
+            "The code stored for {:?} does not hash to the code hash",
+            code_hash
+        );
+    }
+    Ok(code)
+}
+
+/// Downloads the Wasm code of the code hash from the `Contracts::PristineCode` storage of the
+/// chain, writing it to the file at `path`.
+///
+/// Defaults to `<code-hash>.wasm` in the current directory.
+pub(crate) fn execute_download(
+    submit_opts: &SubmitOpts,
+    code_hash: &H256,
+    path: Option<&Path>,
+) -> Result<DownloadResult> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => PathBuf::from(format!("{}.wasm", hex::encode(code_hash))),
+    };
+    let code = async_std::task::block_on(async move {
+        let connection = Connection::new(submit_opts).await?;
+        fetch_code(&connection, code_hash).await
+    })?;
+    fs::write(&path, &code).context(format!("Failed to write {}", path.display()))?;
+    Ok(DownloadResult {
+        code_hash: *code_hash,
+        path,
+        size: code.len(),
+    })
+}
+
+/// A code hash, or the address of a contract instantiated from it.
+#[derive(Debug)]
+pub(crate) enum CodeRef {
+    CodeHash(H256),
+    Contract(Address),
+}
+
+impl FromStr for CodeRef {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match hex::decode(input.trim_start_matches("0x")) {
+            Ok(bytes) if bytes.len() == 32 => Ok(CodeRef::CodeHash(H256::from_slice(&bytes))),
+            _ => input
+                .parse()
+                .map(CodeRef::Contract)
+                .context("Expected a 32 byte hex encoded code hash or a contract address"),
+        }
+    }
+}
+
+impl CodeRef {
+    /// Returns the code hash, looking up the code hash of a contract on the chain.
+    pub async fn code_hash(&self, connection: &Connection) -> Result<H256> {
+        let contract = match self {
+            CodeRef::CodeHash(code_hash) => return Ok(*code_hash),
+            CodeRef::Contract(contract) => contract,
+        };
+        contract.check_format();
+        contract_code_hash(connection, &contract.account).await
+    }
+}
+
+/// Returns the code hash of the contract at the account.
+pub(crate) async fn contract_code_hash(
+    connection: &Connection,
+    account_id: &AccountId32,
+) -> Result<H256> {
+    match connection
+        .fetch(&ContractInfoOfStore { account_id })
+        .await?
+    {
+        Some(ContractInfo::Alive(info)) => Ok(info.code_hash),
+        Some(ContractInfo::Tombstone) => {
+            anyhow::bail!(
+                "The contract at {} was evicted, leaving a tombstone",
+                account_id
+            )
+        }
+        None => anyhow::bail!("No contract found at {}", account_id),
+    }
+}
+
+/// Result of a `verify`.
+#[derive(Debug, Serialize)]
+pub(crate) struct VerifyResult {
+    /// The code hash on the chain.
+    pub code_hash: H256,
+    /// The local Wasm file which was compared.
+    pub wasm: PathBuf,
+    /// The hash of the local Wasm file.
+    pub local_code_hash: H256,
+    /// Whether the local Wasm file hashes to the code hash on the chain.
+    pub verified: bool,
+}
+
+impl VerifyResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        if self.verified {
+            format!(
+                "Verified: {} matches the code hash {:?} on the chain",
+                self.wasm.display(),
+                self.code_hash
+            )
+        } else {
+            format!(
+                "Mismatch: {} hashes to {:?}, but the code hash on the chain is {:?}",
+                self.wasm.display(),
+                self.local_code_hash,
+                self.code_hash
+            )
+        }
+    }
+}
+
+/// Compares the hash of the local Wasm code to the code hash on the chain, of the code hash
+/// itself or of the contract at the address.
+///
+/// The Wasm is read from `wasm`, or else from the artifact of the project at `manifest_path`,
+/// which is rebuilt beforehand if `build` is set.
+pub(crate) fn execute_verify(
+    submit_opts: &SubmitOpts,
+    code: &CodeRef,
+    wasm: Option<&Path>,
+    build: bool,
+    manifest_path: Option<&PathBuf>,
+) -> Result<VerifyResult> {
+    let wasm = match wasm {
+        Some(wasm) => wasm.to_path_buf(),
+        None => {
+            let manifest_path = ManifestPath::try_from(manifest_path)?;
+            let crate_metadata = CrateMetadata::collect(&manifest_path)?;
+            if build {
+                build::execute_with_crate_metadata(
+                    &crate_metadata,
+                    None,
+                    true,
+                    BuildArtifacts::CodeOnly,
+                    OptimizationPasses::default(),
+                    false,
+                    &Default::default(),
+                    UnstableFlags::default(),
+                    &Default::default(),
+                    &Default::default(),
+                )?;
+            } else if !crate_metadata.dest_wasm.exists() {
+                anyhow::bail!(
+                    "No Wasm found at {}, build the contract with `cargo contract build` or pass \
+                     `--build`",
+                    crate_metadata.dest_wasm.display()
+                )
+            }
+            crate_metadata.dest_wasm
+        }
+    };
+    let local_code = fs::read(&wasm).context(format!("Failed to read {}", wasm.display()))?;
+    let local_code_hash = H256(sp_core::blake2_256(&local_code));
+
+    let code_hash = async_std::task::block_on(async move {
+        let connection = Connection::new(submit_opts).await?;
+        code.code_hash(&connection).await
+    })?;
+    Ok(VerifyResult {
+        code_hash,
+        wasm,
+        local_code_hash,
+        verified: local_code_hash == code_hash,
+    })
+}
+
+/// The `Contracts::remove_code` call, which removes unused code, refunding its deposit to the
+/// owner of the code.
+struct RemoveCodeCall<'a> {
+    code_hash: &'a H256,
+}
+
+impl Encode for RemoveCodeCall<'_> {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.code_hash.encode_to(dest)
+    }
+}
+
+impl Call<DefaultNodeRuntime> for RemoveCodeCall<'_> {
+    const MODULE: &'static str = "Contracts";
+    const FUNCTION: &'static str = "remove_code";
+
+    fn events_decoder(decoder: &mut EventsDecoder<DefaultNodeRuntime>) {
+        decoder.with_contracts();
+    }
+}
+
+/// Returns the contracts instantiated from the code hash, iterating the `Contracts::ContractInfoOf`
+/// storage map.
+async fn contracts_of_code(connection: &Connection, code_hash: &H256) -> Result<Vec<AccountId32>> {
+    let prefix = ContractInfoOfStore::prefix(connection.metadata())?;
+    let mut contracts = Vec::new();
+    let mut start_key: Option<StorageKey> = None;
+    loop {
+        let params = Params::Array(vec![
+            to_value(&prefix)?,
+            to_value(KEYS_PAGE_SIZE)?,
+            to_value(&start_key)?,
+        ]);
+        let keys: Vec<StorageKey> = connection.request("state_getKeysPaged", params).await?;
+        if keys.is_empty() {
+            break;
+        }
+        let params = Params::Array(vec![to_value(&keys)?]);
+        let change_sets: Vec<StorageChangeSet<H256>> =
+            connection.request("state_queryStorageAt", params).await?;
+        for (key, data) in change_sets.into_iter().flat_map(|set| set.changes) {
+            let info = match data {
+                Some(data) => ContractInfo::decode(&mut &data.0[..])?,
+                None => continue,
+            };
+            // The keys of the map end with the account id, as it is hashed with `Twox64Concat`.
+            let account = key.0.len().checked_sub(32).map(|start| &key.0[start..]);
+            match (info, account) {
+                (ContractInfo::Alive(info), Some(account)) if info.code_hash == *code_hash => {
+                    let mut account_id = [0u8; 32];
+                    account_id.copy_from_slice(account);
+                    contracts.push(account_id.into())
+                }
+                _ => (),
+            }
+        }
+        if keys.len() < KEYS_PAGE_SIZE as usize {
+            break;
+        }
+        start_key = keys.last().cloned();
+    }
+    Ok(contracts)
+}
+
+/// Removes the code of the code hash from the chain via `Contracts::remove_code`, refunding its
+/// deposit to the owner of the code.
+///
+/// Fails without submitting the extrinsic if contracts instantiated from the code remain.
+pub(crate) fn execute_remove_code(
+    extrinsic_opts: &ExtrinsicOpts,
+    code_hash: &H256,
+) -> Result<ExtrinsicDetails> {
+    let signer = extrinsic_opts.signer()?;
+    async_std::task::block_on(async move {
+        let mut connection = Connection::new(&extrinsic_opts.submit).await?;
+        let contracts = contracts_of_code(&connection, code_hash)
+            .await
+            .context("Failed to look up the contracts instantiated from the code")?;
+        if let Some(contract) = contracts.first() {
+            anyhow::bail!(
+                "The code is still used by {} contracts, e.g. {}",
+                contracts.len(),
+                contract
+            )
+        }
+        let submitted = connection
+            .submit(RemoveCodeCall { code_hash }, &signer, extrinsic_opts)
+            .await?;
+        ExtrinsicDetails::new(&submitted)
+    })
+}
+
+/// Sign the `Contracts::remove_code` extrinsic without submitting it.
+///
+/// The contracts still using the code are not checked, which requires a connection.
+pub(crate) fn sign_remove_code(
+    extrinsic_opts: &ExtrinsicOpts,
+    params: &OfflineParams,
+    code_hash: &H256,
+) -> Result<SignedExtrinsic> {
+    sign::sign_offline(RemoveCodeCall { code_hash }, extrinsic_opts, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_code_hashes_and_addresses() {
+        let code_hash = format!("0x{}", hex::encode([7u8; 32]));
+        match code_hash.parse::<CodeRef>().unwrap() {
+            CodeRef::CodeHash(code_hash) => assert_eq!(code_hash, H256::repeat_byte(7)),
+            CodeRef::Contract(_) => panic!("expected a code hash"),
+        }
+        let alice = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+        assert!(matches!(alice.parse::<CodeRef>(), Ok(CodeRef::Contract(_))));
+        assert!("0x0102".parse::<CodeRef>().is_err());
+    }
+}
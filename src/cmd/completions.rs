@@ -0,0 +1,281 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+#[cfg(feature = "extrinsics")]
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use structopt::{clap, StructOpt};
+
+#[cfg(feature = "extrinsics")]
+use super::transcode::{InkMetadata, METADATA_FILE};
+#[cfg(feature = "extrinsics")]
+use crate::crate_metadata::CrateMetadata;
+use crate::Opts;
+
+/// Completes the values of `--message` and `--constructor`, and the message of `exec`, with the
+/// labels `cargo contract __complete-labels` prints, instead of the bash completion of `_cargo`.
+#[cfg(feature = "extrinsics")]
+const BASH_LABELS: &str = r#"
+_cargo_contract() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local prev="${COMP_WORDS[COMP_CWORD-1]}"
+    if [[ ${cur} != -* && " ${COMP_WORDS[*]} " == *" contract "* ]] ; then
+        case "${prev}" in
+            --message|--constructor|exec)
+                local kind="${prev#--}"
+                [[ ${kind} == exec ]] && kind=message
+                COMPREPLY=( $(compgen -W "$(cargo contract __complete-labels ${kind} 2>/dev/null)" -- "${cur}") )
+                return 0
+                ;;
+        esac
+    fi
+    _cargo "$@"
+}
+
+complete -F _cargo_contract -o bashdefault -o default cargo
+"#;
+
+/// The zsh completion of the labels, which the specs of the arguments naming messages and
+/// constructors refer to.
+#[cfg(feature = "extrinsics")]
+const ZSH_LABELS: &str = r#"(( $+functions[_cargo_contract_labels] )) ||
+_cargo_contract_labels() {
+    local labels; labels=(${(f)"$(cargo contract __complete-labels $1 2>/dev/null)"})
+    compadd -a labels
+}
+
+"#;
+
+/// The fish completions of the labels, added to those of the options.
+#[cfg(feature = "extrinsics")]
+const FISH_LABELS: &str = r#"complete -c cargo -n "__fish_seen_subcommand_from contract" -l message -x -a "(cargo contract __complete-labels message 2>/dev/null)"
+complete -c cargo -n "__fish_seen_subcommand_from contract" -l constructor -x -a "(cargo contract __complete-labels constructor 2>/dev/null)"
+complete -c cargo -n "__fish_seen_subcommand_from exec" -f -a "(cargo contract __complete-labels message 2>/dev/null)"
+"#;
+
+/// The PowerShell completion of the labels, returned before the completions of the command.
+#[cfg(feature = "extrinsics")]
+const POWERSHELL_LABELS: &str = r#"    $previous = $commandElements |
+        Where-Object { $_.Extent.EndOffset -lt $cursorPosition } |
+        Select-Object -Last 1
+    $kind = switch ($previous.Extent.Text) {
+        '--message' { 'message' }
+        '--constructor' { 'constructor' }
+        'exec' { 'message' }
+    }
+    if ($kind -and $command -like 'cargo;contract;*') {
+        return @(cargo contract __complete-labels $kind 2>$null) |
+            Where-Object { $_ -like "$wordToComplete*" } |
+            ForEach-Object { [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, $_) }
+    }
+
+"#;
+
+/// The line of the PowerShell script the completion of the labels is inserted before.
+#[cfg(feature = "extrinsics")]
+const POWERSHELL_COMPLETIONS: &str = "    $completions = @(switch ($command) {";
+
+/// Generates the completion script of a shell for `cargo contract`.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "completions")]
+pub(crate) struct CompletionsCommand {
+    /// The shell to generate the completion script for
+    #[structopt(possible_values = &clap::Shell::variants(), case_insensitive = true)]
+    shell: clap::Shell,
+}
+
+/// Prints the labels of the messages or constructors of the contract, one per line, which the
+/// completion scripts call while completing.
+#[cfg(feature = "extrinsics")]
+#[derive(Debug, StructOpt)]
+#[structopt(name = "__complete-labels")]
+pub(crate) struct CompleteLabelsCommand {
+    /// Whether the labels of the `message`s or of the `constructor`s are printed
+    #[structopt(possible_values = &["message", "constructor"])]
+    kind: String,
+    /// Path to the metadata, defaults to the metadata built for the contract project in the
+    /// current directory
+    #[structopt(long = "contract-metadata", parse(from_os_str))]
+    contract_metadata: Option<PathBuf>,
+}
+
+/// The labels of the messages and constructors of a contract.
+#[cfg(feature = "extrinsics")]
+#[derive(Debug, Default)]
+struct Labels {
+    messages: Vec<String>,
+    constructors: Vec<String>,
+}
+
+#[cfg(feature = "extrinsics")]
+impl Labels {
+    /// Reads the labels from the metadata at `path` or, if none is supplied, the metadata built
+    /// for the contract project in the current directory. Without metadata there are no labels.
+    fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match CrateMetadata::collect(&Default::default()) {
+                Ok(crate_metadata) => crate_metadata.target_directory.join(METADATA_FILE),
+                Err(_) => return Ok(Labels::default()),
+            },
+        };
+        if !path.exists() {
+            return Ok(Labels::default());
+        }
+        let metadata = InkMetadata::read(&path)?;
+        let labels = |messages: Vec<super::transcode::Message>| {
+            messages.into_iter().map(|message| message.label).collect()
+        };
+        Ok(Labels {
+            messages: labels(metadata.messages()?),
+            constructors: labels(metadata.constructors()?),
+        })
+    }
+
+    /// The labels of the `message`s or `constructor`s.
+    fn of_kind(&self, kind: &str) -> &[String] {
+        match kind {
+            "constructor" => &self.constructors,
+            _ => &self.messages,
+        }
+    }
+}
+
+#[cfg(feature = "extrinsics")]
+impl CompleteLabelsCommand {
+    pub fn exec(&self) -> Result<String> {
+        let labels = Labels::load(self.contract_metadata.as_deref())?;
+        Ok(labels.of_kind(&self.kind).join("\n"))
+    }
+}
+
+/// Completes the labels in the zsh script with `_cargo_contract_labels`, in the specs of the
+/// `--message` and `--constructor` options and of the `message` positional.
+#[cfg(feature = "extrinsics")]
+fn zsh_labels(script: &str) -> String {
+    let mut completed = String::new();
+    for line in script.lines() {
+        let kind = ["message", "constructor"].iter().find(|kind| {
+            line.starts_with(&format!("'--{}=[", kind))
+                || line.starts_with(&format!("'*--{}=[", kind))
+                || line.starts_with(&format!("':{} -- ", kind))
+        });
+        if line == "_cargo \"$@\"" {
+            completed.push_str(ZSH_LABELS);
+        }
+        match kind {
+            Some(kind) if line.ends_with(":_files' \\") => {
+                let spec = &line[..line.len() - ":_files' \\".len()];
+                completed.push_str(&format!("{}:_cargo_contract_labels {}' \\", spec, kind))
+            }
+            Some(kind) if line.ends_with("]' \\") => {
+                let spec = &line[..line.len() - "' \\".len()];
+                let spec = format!("{}:label:_cargo_contract_labels {}' \\", spec, kind);
+                completed.push_str(&spec)
+            }
+            _ => completed.push_str(line),
+        }
+        completed.push('\n');
+    }
+    completed
+}
+
+impl CompletionsCommand {
+    /// Returns the completion script for the shell.
+    ///
+    /// With the `extrinsics` feature, the scripts of bash, zsh, fish and PowerShell complete the
+    /// labels of the messages and constructors of the contract completed in by calling
+    /// `cargo contract __complete-labels`.
+    pub fn exec(&self) -> Result<String> {
+        let mut script = Vec::new();
+        Opts::clap().gen_completions_to("cargo", self.shell, &mut script);
+        let script = String::from_utf8(script)?;
+        #[cfg(feature = "extrinsics")]
+        let script = match self.shell {
+            clap::Shell::Bash => script + BASH_LABELS,
+            clap::Shell::Zsh => zsh_labels(&script),
+            clap::Shell::Fish => script + FISH_LABELS,
+            clap::Shell::PowerShell => script.replacen(
+                POWERSHELL_COMPLETIONS,
+                &format!("{}{}", POWERSHELL_LABELS, POWERSHELL_COMPLETIONS),
+                1,
+            ),
+            clap::Shell::Elvish => script,
+        };
+        Ok(script)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_the_script_of_the_shell() {
+        let command = CompletionsCommand::from_iter(&["completions", "bash"]);
+        let script = command.exec().unwrap();
+        assert!(script.contains("cargo__contract__build)"));
+        assert!(script.contains("--manifest-path"));
+    }
+
+    #[cfg(feature = "extrinsics")]
+    #[test]
+    fn completes_the_labels_while_completing() {
+        let script = |shell| {
+            CompletionsCommand::from_iter(&["completions", shell])
+                .exec()
+                .unwrap()
+        };
+        let bash = script("bash");
+        assert!(bash.contains("$(cargo contract __complete-labels ${kind} 2>/dev/null)"));
+        assert!(bash.ends_with("complete -F _cargo_contract -o bashdefault -o default cargo\n"));
+        let zsh = script("zsh");
+        assert!(zsh.contains(":label:_cargo_contract_labels message' \\\n"));
+        assert!(zsh.contains(":label:_cargo_contract_labels constructor' \\\n"));
+        assert!(zsh.contains(":_cargo_contract_labels message' \\\n"));
+        assert!(zsh.contains("_cargo_contract_labels() {"));
+        assert!(script("fish").contains("-l message -x -a \"(cargo contract __complete-labels"));
+        let powershell = script("powershell");
+        assert!(powershell.contains("return @(cargo contract __complete-labels $kind 2>$null)"));
+    }
+
+    #[cfg(feature = "extrinsics")]
+    #[test]
+    fn prints_the_labels_of_the_metadata() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            let path = dir.join(METADATA_FILE);
+            let metadata = serde_json::json!({
+                "spec": {
+                    "constructors": [{ "label": "new", "selector": "0x9bae9d5e", "args": [] }],
+                    "messages": [
+                        { "label": "flip", "selector": "0x633aa551", "args": [] },
+                        { "label": "get", "selector": "0x2f865bd9", "args": [] }
+                    ]
+                }
+            });
+            std::fs::write(&path, metadata.to_string())?;
+            let path = path.to_string_lossy().into_owned();
+            let labels = |kind| {
+                let args = ["__complete-labels", kind, "--contract-metadata", &path];
+                CompleteLabelsCommand::from_iter(&args).exec()
+            };
+            assert_eq!(labels("message")?, "flip\nget");
+            assert_eq!(labels("constructor")?, "new");
+            Ok(())
+        })
+    }
+}
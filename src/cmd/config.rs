@@ -0,0 +1,487 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use structopt::clap::ArgMatches;
+
+use super::{
+    address::parse_address, custom_types::CustomTypes, gas::GasLimit, multisig::MultisigCommand,
+    network::Network,
+};
+use crate::{Command, ExtrinsicOpts, SubmitOpts};
+
+/// Name of the configuration file, looked up in the current directory and its ancestors, and in
+/// the home directory of the user.
+pub(crate) const CONFIG_FILE: &str = ".cargo-contract.toml";
+
+/// Defaults for the options of the commands submitting extrinsics, which apply unless the option
+/// is supplied on the command line.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// Url of the node, or the urls of several nodes to fail over between.
+    url: Option<Urls>,
+    /// Name of the network to connect to, unless a `url` is configured.
+    network: Option<String>,
+    /// Networks selectable with `--network`, in addition to the built-in ones.
+    #[serde(default)]
+    networks: BTreeMap<String, Network>,
+    output: Option<String>,
+    wait_for: Option<String>,
+    /// Gas limit of `instantiate` and the contract calls.
+    gas_limit: Option<u64>,
+    /// Factor the gas estimated for `--gas auto` is increased by.
+    gas_margin: Option<String>,
+    signer: Option<SignerConfig>,
+    /// Types decoding the values of the types of the metadata with the same path, e.g. of
+    /// chain extensions.
+    #[serde(default)]
+    types: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Urls {
+    One(url::Url),
+    Many(Vec<url::Url>),
+}
+
+/// The account signing the extrinsics. Relative paths are relative to the configuration file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SignerConfig {
+    suri: Option<String>,
+    keyfile: Option<PathBuf>,
+    scheme: Option<String>,
+    remote_signer: Option<url::Url>,
+    remote_account: Option<String>,
+    password_file: Option<PathBuf>,
+}
+
+impl Config {
+    /// Loads the project configuration, from the closest `.cargo-contract.toml` in the current
+    /// directory or its ancestors, falling back to the user configuration in the home directory
+    /// for the settings not found in it.
+    pub fn load() -> Result<Self> {
+        let cwd = env::current_dir()?;
+        let project = cwd
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE))
+            .find(|path| path.is_file());
+        let user = env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(CONFIG_FILE))
+            .filter(|path| path.is_file());
+        let mut config = Config::default();
+        for path in project.iter().chain(user.iter()) {
+            config = config.or(Self::read(path)?);
+        }
+        Ok(config)
+    }
+
+    /// Reads the configuration file, resolving the paths in it relative to its directory.
+    fn read(path: &Path) -> Result<Self> {
+        log::debug!("Reading configuration from {}", path.display());
+        let toml =
+            fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+        let mut config: Config =
+            toml::from_str(&toml).context(format!("Failed to parse {}", path.display()))?;
+        if let (Some(signer), Some(dir)) = (config.signer.as_mut(), path.parent()) {
+            signer.keyfile = signer.keyfile.take().map(|keyfile| dir.join(keyfile));
+            signer.password_file = signer.password_file.take().map(|file| dir.join(file));
+        }
+        Ok(config)
+    }
+
+    /// Takes the settings missing in this configuration from the `fallback`.
+    ///
+    /// The signer is taken as a whole, so settings of different signers are never mixed.
+    fn or(self, fallback: Config) -> Config {
+        let mut networks = fallback.networks;
+        networks.extend(self.networks);
+        let mut types = fallback.types;
+        types.extend(self.types);
+        Config {
+            url: self.url.or(fallback.url),
+            network: self.network.or(fallback.network),
+            networks,
+            output: self.output.or(fallback.output),
+            wait_for: self.wait_for.or(fallback.wait_for),
+            gas_limit: self.gas_limit.or(fallback.gas_limit),
+            gas_margin: self.gas_margin.or(fallback.gas_margin),
+            signer: self.signer.or(fallback.signer),
+            types,
+        }
+    }
+
+    /// The types registered in the `[types]`.
+    pub fn custom_types(&self) -> Result<CustomTypes> {
+        CustomTypes::new(&self.types).context(format!("Invalid types in {}", CONFIG_FILE))
+    }
+
+    /// Applies the configuration to the options of the command which were not supplied on the
+    /// command line, as recorded in the `matches` of the command.
+    pub fn apply(&self, cmd: &mut Command, matches: &ArgMatches) -> Result<()> {
+        match cmd {
+            Command::Deploy { extrinsic_opts, .. }
+            | Command::Batch { extrinsic_opts, .. }
+            | Command::RemoveCode { extrinsic_opts, .. }
+            | Command::Transfer { extrinsic_opts, .. } => {
+                self.apply_extrinsic(extrinsic_opts, matches)
+            }
+            Command::Watch(watch) => {
+                self.apply_extrinsic(&mut watch.redeploy.extrinsic_opts, matches)
+            }
+            Command::Instantiate {
+                extrinsic_opts,
+                gas_limit,
+                ..
+            } => {
+                if let Some(limit) = self.gas_limit.filter(|_| !supplied(matches, "gas")) {
+                    *gas_limit = limit;
+                }
+                self.apply_extrinsic(extrinsic_opts, matches)
+            }
+            Command::Call {
+                extrinsic_opts,
+                gas_limit,
+                gas_margin,
+                ..
+            }
+            | Command::Terminate {
+                extrinsic_opts,
+                gas_limit,
+                gas_margin,
+                ..
+            }
+            | Command::Upgrade {
+                extrinsic_opts,
+                gas_limit,
+                gas_margin,
+                ..
+            } => {
+                if let Some(limit) = self.gas_limit.filter(|_| !supplied(matches, "gas")) {
+                    *gas_limit = GasLimit::Limit(limit);
+                }
+                if let Some(margin) = self
+                    .gas_margin
+                    .as_ref()
+                    .filter(|_| !supplied(matches, "gas-margin"))
+                {
+                    *gas_margin = margin
+                        .parse()
+                        .context(format!("Invalid gas_margin in {}", CONFIG_FILE))?;
+                }
+                self.apply_extrinsic(extrinsic_opts, matches)
+            }
+            Command::Submit { submit_opts, .. }
+            | Command::Info { submit_opts, .. }
+            | Command::Storage { submit_opts, .. }
+            | Command::Events { submit_opts, .. }
+            | Command::Download { submit_opts, .. }
+            | Command::Verify { submit_opts, .. }
+            | Command::Account { submit_opts, .. }
+            | Command::AddressBook { submit_opts, .. } => self.apply_submit(submit_opts, matches),
+            Command::Benchmark(benchmark) => self.apply_submit(&mut benchmark.submit_opts, matches),
+            Command::Migrate(migrate) => self.apply_submit(&mut migrate.submit_opts, matches),
+            Command::Faucet(faucet) => self.apply_submit(&mut faucet.submit_opts, matches),
+            Command::Inspect(inspect) => self.apply_submit(&mut inspect.submit_opts, matches),
+            Command::Multisig(multisig) => match multisig {
+                MultisigCommand::Status { submit_opts, .. } => {
+                    self.apply_submit(submit_opts, matches)
+                }
+                MultisigCommand::Approve { extrinsic_opts, .. }
+                | MultisigCommand::Execute { extrinsic_opts, .. } => {
+                    self.apply_extrinsic(extrinsic_opts, matches)
+                }
+            },
+            Command::Metadata(metadata) => match metadata.submit_opts_mut() {
+                Some(submit_opts) => self.apply_submit(submit_opts, matches),
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub fn apply_submit(&self, opts: &mut SubmitOpts, matches: &ArgMatches) -> Result<()> {
+        // The url is taken from, in order of precedence, `--url`, `--network`, the configured
+        // `url` and the configured `network`.
+        let network = match opts.network {
+            Some(ref name) => Some(Network::named(name, &self.networks)?),
+            None => match self.network {
+                Some(ref name) => Some(
+                    Network::named(name, &self.networks)
+                        .context(format!("Invalid network in {}", CONFIG_FILE))?,
+                ),
+                None => None,
+            },
+        };
+        if !supplied(matches, "url") {
+            match (&network, &self.url) {
+                (Some(network), _) if opts.network.is_some() => {
+                    opts.urls = vec![network.url.clone()]
+                }
+                (_, Some(Urls::One(url))) => opts.urls = vec![url.clone()],
+                (_, Some(Urls::Many(urls))) => opts.urls = urls.clone(),
+                (Some(network), None) => opts.urls = vec![network.url.clone()],
+                (None, None) => (),
+            }
+        }
+        opts.network_profile = network;
+        if let Some(output) = self
+            .output
+            .as_ref()
+            .filter(|_| !supplied(matches, "output"))
+        {
+            opts.output = parse_setting("output", output)?;
+        }
+        if let Some(wait_for) = self
+            .wait_for
+            .as_ref()
+            .filter(|_| !supplied(matches, "wait-for"))
+        {
+            opts.wait_for = parse_setting("wait_for", wait_for)?;
+        }
+        Ok(())
+    }
+
+    fn apply_extrinsic(&self, opts: &mut ExtrinsicOpts, matches: &ArgMatches) -> Result<()> {
+        self.apply_submit(&mut opts.submit, matches)?;
+        let signer = match self.signer {
+            Some(ref signer) => signer,
+            None => return Ok(()),
+        };
+        // A signer supplied on the command line, or via `CARGO_CONTRACT_SURI`, replaces the
+        // configured signer entirely.
+        if ["suri", "keyfile", "remote-signer"]
+            .iter()
+            .any(|name| matches.value_of(name).is_some())
+        {
+            return Ok(());
+        }
+        opts.signer_opts.suri = signer.suri.clone();
+        opts.signer_opts.keyfile = signer.keyfile.clone();
+        opts.remote_signer = signer.remote_signer.clone();
+        opts.remote_account = match signer.remote_account {
+            Some(ref account) => Some(parse_address(account).context("Invalid remote_account")?),
+            None => None,
+        };
+        if let Some(scheme) = signer
+            .scheme
+            .as_ref()
+            .filter(|_| !supplied(matches, "scheme"))
+        {
+            opts.signer_opts.scheme = parse_setting("scheme", scheme)?;
+        }
+        let signer_opts = &mut opts.signer_opts;
+        if signer_opts.password.is_none() && signer_opts.password_file.is_none() {
+            signer_opts.password_file = signer.password_file.clone();
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if the option was supplied on the command line, rather than taking its default.
+fn supplied(matches: &ArgMatches, name: &str) -> bool {
+    matches.occurrences_of(name) > 0
+}
+
+fn parse_setting<T: FromStr<Err = String>>(name: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|err| anyhow::anyhow!("Invalid {} {:?} in {}: {}", name, value, CONFIG_FILE, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Opts, OutputType, WaitFor};
+    use structopt::StructOpt;
+
+    fn parse(args: &[&str], config: &Config) -> Command {
+        let mut args = args.to_vec();
+        args.insert(0, "cargo");
+        args.insert(1, "contract");
+        let matches = Opts::clap().get_matches_from(args);
+        let Opts::Contract(contract) = Opts::from_clap(&matches);
+        let mut cmd = contract.cmd;
+        let matches = matches
+            .subcommand_matches("contract")
+            .and_then(|contract| contract.subcommand().1)
+            .expect("subcommand was supplied");
+        config.apply(&mut cmd, matches).unwrap();
+        cmd
+    }
+
+    #[test]
+    fn project_config_overrides_user_config() {
+        crate::util::tests::with_tmp_dir(|path| {
+            let project_path = path.join("project.toml");
+            fs::write(
+                &project_path,
+                r#"
+output = "json"
+
+[signer]
+keyfile = "keys/deployer.json"
+"#,
+            )?;
+            let user_path = path.join("user.toml");
+            fs::write(
+                &user_path,
+                r#"
+url = ["ws://one:9944", "ws://two:9944"]
+output = "human-readable"
+
+[networks.staging]
+url = "ws://staging:9944"
+ss58_prefix = 2
+
+[signer]
+suri = "//Alice"
+"#,
+            )?;
+            let config = Config::read(&project_path)?.or(Config::read(&user_path)?);
+
+            assert_eq!(config.output.as_deref(), Some("json"));
+            assert_eq!(config.networks["staging"].ss58_prefix, Some(2));
+            assert!(matches!(config.url, Some(Urls::Many(ref urls)) if urls.len() == 2));
+            let signer = config.signer.expect("signer is configured");
+            assert_eq!(signer.suri, None);
+            assert_eq!(signer.keyfile, Some(path.join("keys/deployer.json")));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn command_line_overrides_config() {
+        let config: Config = toml::from_str(
+            r#"
+url = "ws://node:9944"
+wait_for = "finalized"
+gas_limit = 42
+
+[signer]
+suri = "//Bob"
+"#,
+        )
+        .unwrap();
+        let code_hash = format!("--code-hash={}", "00".repeat(32));
+
+        let args = ["instantiate", "--data=00", &code_hash];
+        match parse(&args, &config) {
+            Command::Instantiate {
+                extrinsic_opts,
+                gas_limit,
+                ..
+            } => {
+                assert_eq!(extrinsic_opts.submit.urls[0].as_str(), "ws://node:9944/");
+                assert_eq!(extrinsic_opts.submit.wait_for, WaitFor::Finalized);
+                assert_eq!(extrinsic_opts.submit.output, OutputType::HumanReadable);
+                assert_eq!(extrinsic_opts.signer_opts.suri.as_deref(), Some("//Bob"));
+                assert_eq!(gas_limit, 42);
+            }
+            _ => panic!("instantiate command expected"),
+        }
+
+        let args = [
+            "instantiate",
+            "--data=00",
+            &code_hash,
+            "--gas=7",
+            "--url=ws://other:9944",
+            "--wait-for=in-block",
+            "--keyfile=account.json",
+        ];
+        match parse(&args, &config) {
+            Command::Instantiate {
+                extrinsic_opts,
+                gas_limit,
+                ..
+            } => {
+                assert_eq!(extrinsic_opts.submit.urls[0].as_str(), "ws://other:9944/");
+                assert_eq!(extrinsic_opts.submit.wait_for, WaitFor::InBlock);
+                assert_eq!(extrinsic_opts.signer_opts.suri, None);
+                assert_eq!(gas_limit, 7);
+            }
+            _ => panic!("instantiate command expected"),
+        }
+    }
+
+    #[test]
+    fn configured_gas_margin_applies_to_calls() {
+        let config: Config = toml::from_str(
+            r#"
+gas_limit = 42
+gas_margin = "20%"
+"#,
+        )
+        .unwrap();
+        let contract = "--contract=5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+        let margin = |args: &[&str]| match parse(args, &config) {
+            Command::Call {
+                gas_limit,
+                gas_margin,
+                ..
+            } => (gas_limit, gas_margin),
+            _ => panic!("call command expected"),
+        };
+
+        let (gas_limit, gas_margin) = margin(&["call", contract, "--data=00"]);
+        assert_eq!(gas_limit, GasLimit::Limit(42));
+        assert_eq!(gas_margin, "1.2".parse().unwrap());
+        let args = [
+            "call",
+            contract,
+            "--data=00",
+            "--gas=auto",
+            "--gas-margin=2",
+        ];
+        let (gas_limit, gas_margin) = margin(&args);
+        assert_eq!(gas_limit, GasLimit::Auto);
+        assert_eq!(gas_margin, "2".parse().unwrap());
+    }
+
+    #[test]
+    fn network_overrides_configured_url() {
+        let config: Config = toml::from_str(r#"url = "ws://node:9944""#).unwrap();
+        match parse(&["submit", "--network=shibuya", "0x00"], &config) {
+            Command::Submit { submit_opts, .. } => {
+                assert_eq!(
+                    submit_opts.urls[0].as_str(),
+                    "wss://rpc.shibuya.astar.network/"
+                );
+                assert_eq!(submit_opts.network_profile.unwrap().ss58_prefix, Some(5));
+            }
+            _ => panic!("submit command expected"),
+        }
+        let args = ["submit", "--network=shibuya", "--url=ws://own:9944", "0x00"];
+        match parse(&args, &config) {
+            Command::Submit { submit_opts, .. } => {
+                assert_eq!(submit_opts.urls[0].as_str(), "ws://own:9944/");
+                assert!(submit_opts.network_profile.is_some());
+            }
+            _ => panic!("submit command expected"),
+        }
+    }
+}
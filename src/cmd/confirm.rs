@@ -0,0 +1,112 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::{self, BufRead, Write};
+
+use anyhow::Result;
+use sp_core::crypto::AccountId32;
+
+/// Names of the chains of development nodes, e.g. started with `--dev`, on which submitting
+/// extrinsics transferring value requires no confirmation.
+const DEV_CHAINS: &[&str] = &["Development", "Local Testnet"];
+
+/// Whether the chain, by the name its node reports, is a development chain.
+pub(crate) fn is_dev_chain(chain: &str) -> bool {
+    DEV_CHAINS
+        .iter()
+        .any(|dev_chain| chain.eq_ignore_ascii_case(dev_chain))
+}
+
+/// The extrinsic about to be submitted, shown before asking for confirmation.
+pub(crate) struct Summary {
+    /// The network and the name of its chain.
+    pub network: String,
+    pub signer: AccountId32,
+    /// The account the call is dispatched from, if not the signer.
+    pub origin: Option<AccountId32>,
+    /// The value transferred, denominated in the chain's token.
+    pub value: String,
+    /// The estimated deposit reserved, e.g. for storing uploaded code, if any.
+    pub deposit: Option<String>,
+    /// The estimated fee, denominated in the chain's token.
+    pub fee: String,
+}
+
+impl Summary {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let mut output = format!("Network: {}\n\tSigner: {}", self.network, self.signer);
+        if let Some(ref origin) = self.origin {
+            output.push_str(&format!("\n\tOrigin: {}", origin));
+        }
+        output.push_str(&format!("\n\tValue: {}", self.value));
+        if let Some(ref deposit) = self.deposit {
+            output.push_str(&format!("\n\tEstimated deposit: {}", deposit));
+        }
+        output.push_str(&format!("\n\tEstimated fee: {}", self.fee));
+        output
+    }
+}
+
+/// Only `y` and `yes` confirm, any other answer declines.
+fn confirms(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Shows the summary and asks for confirmation on the terminal, failing unless confirmed.
+pub(crate) fn confirm(summary: &Summary) -> Result<()> {
+    eprintln!("\t{}", summary.display());
+    eprint!("Submit the extrinsic? [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    if !confirms(&answer) {
+        anyhow::bail!("The extrinsic was not confirmed, pass --yes to submit it without asking")
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_dev_chains_and_confirmations() {
+        assert!(is_dev_chain("Development"));
+        assert!(is_dev_chain("local testnet"));
+        assert!(!is_dev_chain("Shibuya Testnet"));
+
+        assert!(confirms("y\n"));
+        assert!(confirms(" Yes\n"));
+        assert!(!confirms("\n"));
+        assert!(!confirms("no\n"));
+    }
+
+    #[test]
+    fn shows_the_estimated_deposit() {
+        let summary = Summary {
+            network: "Rococo (Rococo)".to_string(),
+            signer: AccountId32::from([1; 32]),
+            origin: None,
+            value: "0 ROC".to_string(),
+            deposit: Some("1.5 ROC".to_string()),
+            fee: "0.01 ROC".to_string(),
+        };
+        assert!(summary.display().ends_with(
+            "\n\tValue: 0 ROC\n\tEstimated deposit: 1.5 ROC\n\tEstimated fee: 0.01 ROC"
+        ));
+    }
+}
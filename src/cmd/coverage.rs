@@ -0,0 +1,213 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+/// The files excluded from the reports: the sources of the dependencies, of the standard library
+/// and those generated into the target directory.
+const IGNORED_FILES: &str = r"(\.cargo/(registry|git)|/rustc/|/rustlib/|/target/)";
+
+/// The format of the coverage report.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum CoverageFormat {
+    /// An `lcov.info` tracefile, as read by most CI services and editors.
+    Lcov,
+    /// A directory of annotated sources, browsed from its `index.html`.
+    Html,
+}
+
+impl FromStr for CoverageFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input {
+            "lcov" => Ok(CoverageFormat::Lcov),
+            "html" => Ok(CoverageFormat::Html),
+            _ => anyhow::bail!("Unknown coverage format `{}`, use `lcov` or `html`", input),
+        }
+    }
+}
+
+/// The source-based coverage of the test binaries of a contract, collected in the `coverage`
+/// directory of its target directory.
+pub(crate) struct Coverage {
+    dir: PathBuf,
+    format: CoverageFormat,
+}
+
+impl Coverage {
+    /// Prepares the coverage directory, removing the profiles written by previous runs.
+    pub fn new(target_directory: &Path, format: CoverageFormat) -> Result<Self> {
+        let dir = target_directory.join("coverage");
+        let profiles = dir.join("profiles");
+        if profiles.exists() {
+            fs::remove_dir_all(&profiles)
+                .context(format!("Failed to remove {}", profiles.display()))?;
+        }
+        fs::create_dir_all(&profiles)
+            .context(format!("Failed to create {}", profiles.display()))?;
+        Ok(Coverage { dir, format })
+    }
+
+    /// Instruments the binaries the `cargo` command builds, and has them write their profiles
+    /// to the coverage directory.
+    ///
+    /// They are built into a target directory of their own, so that switching between tests
+    /// with and without coverage does not rebuild every dependency. The Wasm of the contract is
+    /// built with the `RUSTFLAGS` of `cargo contract build`, so it is not instrumented.
+    pub fn instrument(&self, cmd: &mut Command) {
+        let rustflags = env::var("RUSTFLAGS").unwrap_or_default();
+        let rustflags = format!("{} -C instrument-coverage", rustflags);
+        cmd.env("RUSTFLAGS", rustflags.trim())
+            .env(
+                "LLVM_PROFILE_FILE",
+                self.dir.join("profiles").join("%p-%m.profraw"),
+            )
+            .arg("--target-dir")
+            .arg(self.dir.join("target"));
+    }
+
+    /// Merges the profiles written by the test `binaries`, and writes the report of the sources
+    /// of the contract from them, returning its path.
+    pub fn report(&self, binaries: &[PathBuf]) -> Result<PathBuf> {
+        let mut profiles = Vec::new();
+        for entry in fs::read_dir(self.dir.join("profiles"))? {
+            let path = entry?.path();
+            if path.extension() == Some("profraw".as_ref()) {
+                profiles.push(path);
+            }
+        }
+        if profiles.is_empty() || binaries.is_empty() {
+            anyhow::bail!("The tests wrote no coverage profiles")
+        }
+        let profdata = self.dir.join("coverage.profdata");
+        let mut merge = Command::new(llvm_tool("llvm-profdata")?);
+        merge
+            .args(["merge", "-sparse"])
+            .args(&profiles)
+            .arg("-o")
+            .arg(&profdata);
+        run(&mut merge)?;
+
+        let mut cov = Command::new(llvm_tool("llvm-cov")?);
+        let report = match self.format {
+            CoverageFormat::Lcov => {
+                cov.args(["export", "-format=lcov"]);
+                self.dir.join("lcov.info")
+            }
+            CoverageFormat::Html => {
+                let html = self.dir.join("html");
+                cov.args(["show", "-format=html"])
+                    .arg(format!("-output-dir={}", html.display()));
+                html.join("index.html")
+            }
+        };
+        cov.arg(format!("-instr-profile={}", profdata.display()))
+            .arg(format!("-ignore-filename-regex={}", IGNORED_FILES))
+            .arg(&binaries[0]);
+        for binary in &binaries[1..] {
+            cov.arg("-object").arg(binary);
+        }
+        let output = run(&mut cov)?;
+        if self.format == CoverageFormat::Lcov {
+            fs::write(&report, output).context(format!("Failed to write {}", report.display()))?;
+        }
+        Ok(report)
+    }
+}
+
+/// Executes the LLVM tool, returning its standard output.
+fn run(cmd: &mut Command) -> Result<Vec<u8>> {
+    log::debug!("Executing {:?}", cmd);
+    let output = cmd
+        .output()
+        .context(format!("Failed to execute {:?}", cmd))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{:?} failed: {}",
+            cmd,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    }
+    Ok(output.stdout)
+}
+
+/// The test binaries built, as listed by `cargo test --no-run --message-format=json`.
+pub(crate) fn test_binaries(messages: &str) -> Vec<PathBuf> {
+    messages
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|message| {
+            message["reason"] == "compiler-artifact" && message["profile"]["test"] == true
+        })
+        .filter_map(|message| message["executable"].as_str().map(PathBuf::from))
+        .collect()
+}
+
+/// The `llvm-profdata` or `llvm-cov` of the `llvm-tools-preview` component of the toolchain,
+/// else the one on the `PATH`.
+fn llvm_tool(name: &str) -> Result<PathBuf> {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let sysroot = Command::new(rustc).args(["--print", "sysroot"]).output()?;
+    let host = rustc_version::version_meta()?.host;
+    let tool = PathBuf::from(String::from_utf8_lossy(&sysroot.stdout).trim())
+        .join("lib")
+        .join("rustlib")
+        .join(host)
+        .join("bin")
+        .join(format!("{}{}", name, env::consts::EXE_SUFFIX));
+    if tool.exists() {
+        return Ok(tool);
+    }
+    which::which(name).map_err(|_| {
+        anyhow::anyhow!(
+            "{} not found, install it with `rustup component add llvm-tools-preview`",
+            name
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_the_test_binaries() {
+        let messages = [
+            r#"{"reason":"compiler-artifact","profile":{"test":false},"executable":null}"#,
+            r#"{"reason":"compiler-artifact","profile":{"test":true},"executable":"/t/flipper-1"}"#,
+            r#"{"reason":"build-script-executed","out_dir":"/t/build"}"#,
+            r#"{"reason":"build-finished","success":true}"#,
+        ];
+        assert_eq!(
+            test_binaries(&messages.join("\n")),
+            [PathBuf::from("/t/flipper-1")]
+        );
+        assert_eq!(
+            "html".parse::<CoverageFormat>().unwrap(),
+            CoverageFormat::Html
+        );
+        assert!("cobertura".parse::<CoverageFormat>().is_err());
+    }
+}
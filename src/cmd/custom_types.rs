@@ -0,0 +1,335 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use anyhow::{Context, Result};
+use codec::{Compact, Decode};
+use serde_json::{Map, Value};
+
+use super::transcode::decode_primitive;
+
+/// The primitive types of type expressions.
+const PRIMITIVES: &[&str] = &[
+    "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "i8", "i16", "i32", "i64", "i128",
+];
+
+/// A type registered in the `[types]` of the configuration, e.g. a type of a chain extension
+/// which the metadata of the contract describes in a way the transcoder does not support.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum CustomType {
+    Primitive(String),
+    Sequence(Box<CustomType>),
+    Array(Box<CustomType>, usize),
+    Tuple(Vec<CustomType>),
+    Option(Box<CustomType>),
+    Compact(Box<CustomType>),
+    /// Another registered type.
+    Named(String),
+    /// The names and types of the fields of a struct.
+    Struct(Vec<(String, CustomType)>),
+    /// The variants of an enum without fields.
+    Enum(Vec<String>),
+}
+
+/// Splits the input at the commas outside of brackets.
+fn split_top_level(input: &str) -> Vec<&str> {
+    let (mut parts, mut depth, mut start) = (Vec::new(), 0, 0);
+    for (index, c) in input.char_indices() {
+        match c {
+            '<' | '(' | '[' | '{' => depth += 1,
+            '>' | ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&input[start..index]);
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+    parts.push(&input[start..]);
+    parts
+}
+
+/// Parses the type expressions of the registered types, e.g. `u32`, `Vec<u8>`, `[u8; 32]`,
+/// `(u32, Option<Balance>)`, `Compact<u128>` or the struct `{ id: AssetId, owner: [u8; 32] }`.
+impl FromStr for CustomType {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let input = input.trim();
+        let generic = |name: &str| {
+            input
+                .strip_prefix(name)
+                .and_then(|rest| rest.trim_start().strip_prefix('<'))
+                .and_then(|rest| rest.strip_suffix('>'))
+        };
+        let ty = if let Some(element) = generic("Vec") {
+            CustomType::Sequence(Box::new(element.parse()?))
+        } else if let Some(some) = generic("Option") {
+            CustomType::Option(Box::new(some.parse()?))
+        } else if let Some(inner) = generic("Compact") {
+            CustomType::Compact(Box::new(inner.parse()?))
+        } else if let Some(array) = input.strip_prefix('[').and_then(|a| a.strip_suffix(']')) {
+            let (element, len) = array
+                .rsplit_once(';')
+                .ok_or_else(|| anyhow::anyhow!("Missing the length of the array `{}`", input))?;
+            let len = len
+                .trim()
+                .parse()
+                .context(format!("Invalid length of the array `{}`", input))?;
+            CustomType::Array(Box::new(element.parse()?), len)
+        } else if let Some(tuple) = input.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+            let elements = split_top_level(tuple)
+                .into_iter()
+                .filter(|element| !element.trim().is_empty())
+                .map(str::parse)
+                .collect::<Result<_>>()?;
+            CustomType::Tuple(elements)
+        } else if let Some(fields) = input.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            let fields = split_top_level(fields)
+                .into_iter()
+                .filter(|field| !field.trim().is_empty())
+                .map(|field| {
+                    let (name, ty) = field.split_once(':').ok_or_else(|| {
+                        anyhow::anyhow!("Missing the type of the field `{}`", field)
+                    })?;
+                    Ok((name.trim().to_string(), ty.parse()?))
+                })
+                .collect::<Result<_>>()?;
+            CustomType::Struct(fields)
+        } else if PRIMITIVES.contains(&input) {
+            CustomType::Primitive(input.to_string())
+        } else if !input.is_empty()
+            && input
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+        {
+            CustomType::Named(input.to_string())
+        } else {
+            anyhow::bail!("Invalid type `{}`", input)
+        };
+        Ok(ty)
+    }
+}
+
+impl CustomType {
+    /// The type of an entry of the `[types]`: a type expression, or the `variants` of an enum.
+    fn from_toml(value: &toml::Value) -> Result<Self> {
+        if let Some(expression) = value.as_str() {
+            return expression.parse();
+        }
+        let variants = value
+            .get("variants")
+            .and_then(toml::Value::as_array)
+            .ok_or_else(|| {
+                anyhow::anyhow!("Types are type expressions or the `variants` of enums")
+            })?;
+        let variants = variants.iter().map(|variant| {
+            variant
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| anyhow::anyhow!("Variants are named by strings"))
+        });
+        Ok(CustomType::Enum(variants.collect::<Result<_>>()?))
+    }
+
+    /// The names of the registered types the type refers to.
+    fn references(&self) -> Vec<&str> {
+        match self {
+            CustomType::Named(name) => vec![name],
+            CustomType::Sequence(ty)
+            | CustomType::Array(ty, _)
+            | CustomType::Option(ty)
+            | CustomType::Compact(ty) => ty.references(),
+            CustomType::Tuple(types) => types.iter().flat_map(CustomType::references).collect(),
+            CustomType::Struct(fields) => {
+                fields.iter().flat_map(|(_, ty)| ty.references()).collect()
+            }
+            CustomType::Primitive(_) | CustomType::Enum(_) => Vec::new(),
+        }
+    }
+}
+
+/// The types registered in the configuration, by their path, e.g. `my_chain::AssetId`, or by
+/// their name.
+#[derive(Debug, Default)]
+pub(crate) struct CustomTypes {
+    types: BTreeMap<String, CustomType>,
+}
+
+impl CustomTypes {
+    pub fn new(types: &BTreeMap<String, toml::Value>) -> Result<Self> {
+        let mut custom = BTreeMap::new();
+        for (path, ty) in types {
+            let ty = CustomType::from_toml(ty).context(format!("Invalid type `{}`", path))?;
+            custom.insert(path.clone(), ty);
+        }
+        let custom = CustomTypes { types: custom };
+        for (path, ty) in &custom.types {
+            if let Some(missing) = ty
+                .references()
+                .into_iter()
+                .find(|name| custom.named(name).is_none())
+            {
+                anyhow::bail!(
+                    "The type `{}` refers to the unknown type `{}`",
+                    path,
+                    missing
+                )
+            }
+        }
+        Ok(custom)
+    }
+
+    /// The registered type of the path, registered by the whole path or by its name.
+    pub fn get(&self, path: &str) -> Option<&CustomType> {
+        self.types
+            .get(path)
+            .or_else(|| self.types.get(path.rsplit("::").next()?))
+    }
+
+    /// The registered type another registered type refers to, by its path or by its name.
+    fn named(&self, name: &str) -> Option<&CustomType> {
+        self.get(name).or_else(|| {
+            let mut types = self.types.iter();
+            let (_, ty) = types.find(|(path, _)| path.rsplit("::").next() == Some(name))?;
+            Some(ty)
+        })
+    }
+
+    /// Decodes a value of the registered type, the same way as the values of the types of the
+    /// metadata.
+    pub fn decode(&self, ty: &CustomType, input: &mut &[u8]) -> Result<Value> {
+        let value = match ty {
+            CustomType::Primitive(primitive) => decode_primitive(primitive, input)?,
+            CustomType::Sequence(element) => {
+                let Compact(len) = Compact::<u32>::decode(input)?;
+                self.decode_elements(element, len as usize, input)?
+            }
+            CustomType::Array(element, len) => self.decode_elements(element, *len, input)?,
+            CustomType::Tuple(types) => Value::Array(
+                types
+                    .iter()
+                    .map(|ty| self.decode(ty, input))
+                    .collect::<Result<_>>()?,
+            ),
+            CustomType::Option(some) => match u8::decode(input)? {
+                0 => Value::from("None"),
+                1 => {
+                    let mut object = Map::new();
+                    object.insert("Some".to_string(), self.decode(some, input)?);
+                    Value::Object(object)
+                }
+                index => anyhow::bail!("Invalid variant index {}", index),
+            },
+            CustomType::Compact(inner) => {
+                let Compact(value) = Compact::<u128>::decode(input)?;
+                match **inner {
+                    CustomType::Primitive(ref primitive) if primitive == "u128" => {
+                        Value::String(value.to_string())
+                    }
+                    _ => Value::from(value as u64),
+                }
+            }
+            CustomType::Named(name) => {
+                let ty = self
+                    .named(name)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown type `{}`", name))?;
+                self.decode(ty, input)?
+            }
+            CustomType::Struct(fields) => {
+                let mut object = Map::new();
+                for (name, ty) in fields {
+                    object.insert(name.clone(), self.decode(ty, input)?);
+                }
+                Value::Object(object)
+            }
+            CustomType::Enum(variants) => {
+                let index = u8::decode(input)?;
+                let variant = variants
+                    .get(index as usize)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid variant index {}", index))?;
+                Value::from(variant.as_str())
+            }
+        };
+        Ok(value)
+    }
+
+    /// Decodes the elements of a sequence or array, combining bytes into a hex string.
+    fn decode_elements(&self, ty: &CustomType, len: usize, input: &mut &[u8]) -> Result<Value> {
+        if *ty == CustomType::Primitive("u8".to_string()) {
+            if input.len() < len {
+                anyhow::bail!("Not enough data for {} bytes", len)
+            }
+            let (bytes, rest) = input.split_at(len);
+            *input = rest;
+            return Ok(Value::String(format!("0x{}", hex::encode(bytes))));
+        }
+        let elements = (0..len)
+            .map(|_| self.decode(ty, input))
+            .collect::<Result<_>>()?;
+        Ok(Value::Array(elements))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+    use serde_json::json;
+
+    #[test]
+    fn decodes_the_registered_types() {
+        let types: BTreeMap<String, toml::Value> = toml::from_str(
+            r#"
+            "my_chain::AssetId" = "u32"
+            Balance = "Compact<u128>"
+            Status = { variants = ["Live", "Frozen"] }
+            Asset = "{ id: AssetId, supply: Balance, owner: [u8; 4], status: Option<Status> }"
+            "#,
+        )
+        .unwrap();
+        let types = CustomTypes::new(&types).unwrap();
+        let asset = types.get("my_chain::Asset").unwrap();
+
+        let encoded = (7u32, Compact(1000u128), [1u8, 2, 3, 4], Some(1u8)).encode();
+        assert_eq!(
+            types.decode(asset, &mut &encoded[..]).unwrap(),
+            json!({
+                "id": 7,
+                "supply": "1000",
+                "owner": "0x01020304",
+                "status": { "Some": "Frozen" }
+            })
+        );
+        assert_eq!(
+            "(u32, Vec<Option<u8>>)".parse::<CustomType>().unwrap(),
+            CustomType::Tuple(vec![
+                CustomType::Primitive("u32".to_string()),
+                CustomType::Sequence(Box::new(CustomType::Option(Box::new(
+                    CustomType::Primitive("u8".to_string())
+                ))))
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_references_to_unknown_types() {
+        let types: BTreeMap<String, toml::Value> =
+            toml::from_str(r#"Asset = "Vec<AssetId>""#).unwrap();
+        assert!(CustomTypes::new(&types).is_err());
+    }
+}
@@ -14,18 +14,114 @@
 // You should have received a copy of the GNU General Public License
 // along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{fs, io::Read, path::PathBuf};
+use std::{fs, marker::PhantomData, path::PathBuf};
 
 use anyhow::{Context, Result};
+use serde::Serialize;
 use sp_core::H256;
-use subxt::{contracts::*, ClientBuilder, DefaultNodeRuntime};
+use subxt::contracts::*;
 
-use crate::{crate_metadata, ExtrinsicOpts};
+use super::{
+    build::workspace_contracts,
+    code::PristineCodeStore,
+    deployments::{self, Deployment},
+    events::ExtrinsicDetails,
+    extrinsic::Connection,
+    pallet::Deposits,
+    sign::{self, OfflineParams, SignedExtrinsic},
+    signer::ExtrinsicSigner,
+};
+use crate::{crate_metadata, workspace::ManifestPath, ExtrinsicOpts, WaitFor};
+
+/// Result of a successful `deploy`.
+#[derive(Debug, Serialize)]
+pub(crate) struct DeployResult {
+    /// The code hash of the uploaded contract code.
+    pub code_hash: H256,
+    /// Whether the code was uploaded, as it was not stored on the chain yet.
+    pub uploaded: bool,
+    /// The details of the extrinsic uploading the code, if it was uploaded.
+    #[serde(flatten)]
+    pub extrinsic: Option<ExtrinsicDetails>,
+}
+
+impl DeployResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        match self.extrinsic {
+            Some(ref extrinsic) => {
+                extrinsic.display_details(format!("Code hash: {:?}", self.code_hash))
+            }
+            None => format!(
+                "Code hash: {:?}, the code was already stored on the chain",
+                self.code_hash
+            ),
+        }
+    }
+}
+
+/// The code uploaded for a contract of the workspace, as reported by `deploy --all`.
+#[derive(Debug, Serialize)]
+pub(crate) struct UploadedContract {
+    /// The package name of the contract.
+    pub name: String,
+    pub code_hash: H256,
+    /// Whether the code was uploaded, as it was not stored on the chain yet.
+    pub uploaded: bool,
+    /// Hash of the extrinsic uploading the code, if it was uploaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extrinsic: Option<H256>,
+}
+
+/// Result of a successful `deploy --all`.
+#[derive(Debug, Serialize)]
+pub(crate) struct DeployAllResult {
+    pub contracts: Vec<UploadedContract>,
+}
+
+impl DeployAllResult {
+    /// The human readable output, a table of the code hash of each contract.
+    pub fn display(&self) -> String {
+        let uploaded = self
+            .contracts
+            .iter()
+            .filter(|contract| contract.uploaded)
+            .count();
+        let width = self
+            .contracts
+            .iter()
+            .map(|contract| contract.name.len())
+            .max()
+            .unwrap_or_default()
+            .max("Contract".len());
+        let mut output = format!(
+            "Uploaded {} of {} contracts\n\n  {:<width$}  Code hash",
+            uploaded,
+            self.contracts.len(),
+            "Contract",
+            width = width
+        );
+        for contract in &self.contracts {
+            output.push_str(&format!(
+                "\n  {:<width$}  {:?}{}",
+                contract.name,
+                contract.code_hash,
+                if contract.uploaded {
+                    ""
+                } else {
+                    " (already stored)"
+                },
+                width = width
+            ));
+        }
+        output
+    }
+}
 
 /// Load the wasm blob from the specified path.
 ///
 /// Defaults to the target contract wasm in the current project, inferred via the crate metadata.
-fn load_contract_code(path: Option<&PathBuf>) -> Result<Vec<u8>> {
+pub(crate) fn load_contract_code(path: Option<&PathBuf>) -> Result<Vec<u8>> {
     let contract_wasm_path = match path {
         Some(path) => path.clone(),
         None => {
@@ -34,49 +130,162 @@ fn load_contract_code(path: Option<&PathBuf>) -> Result<Vec<u8>> {
         }
     };
     log::info!("Contract code path: {}", contract_wasm_path.display());
-    let mut data = Vec::new();
-    let mut file = fs::File::open(&contract_wasm_path)
-        .context(format!("Failed to open {}", contract_wasm_path.display()))?;
-    file.read_to_end(&mut data)?;
-
-    Ok(data)
+    fs::read(&contract_wasm_path)
+        .context(format!("Failed to open {}", contract_wasm_path.display()))
 }
 
 /// Put contract code to a smart contract enabled substrate chain.
-/// Returns the code hash of the deployed contract if successful.
+/// Returns the code hash of the deployed contract, together with the details of the extrinsic,
+/// if successful.
 ///
 /// Optionally supply the contract wasm path, defaults to destination contract file inferred from
 /// Cargo.toml of the current contract project.
 ///
 /// Creates an extrinsic with the `Contracts::put_code` Call, submits via RPC, then waits for
-/// the `ContractsEvent::CodeStored` event.
+/// the `ContractsEvent::CodeStored` event. If the inclusion of the extrinsic is not awaited, the
+/// code hash is computed locally instead.
+///
+/// The upload is skipped if the code is already stored on the chain.
 pub(crate) fn execute_deploy(
     extrinsic_opts: &ExtrinsicOpts,
+    signer: &ExtrinsicSigner,
     contract_wasm_path: Option<&PathBuf>,
-) -> Result<H256> {
+) -> Result<DeployResult> {
     let code = load_contract_code(contract_wasm_path)?;
 
-    async_std::task::block_on(async move {
-        let cli = ClientBuilder::<DefaultNodeRuntime>::new()
-            .set_url(&extrinsic_opts.url.to_string())
-            .build()
-            .await?;
-        let signer = extrinsic_opts.signer()?;
+    async_std::task::block_on(upload_code(extrinsic_opts, signer, &code))
+}
+
+/// Uploads the code signed by the `signer`, unless it is already stored on the chain.
+pub(crate) async fn upload_code(
+    extrinsic_opts: &ExtrinsicOpts,
+    signer: &ExtrinsicSigner,
+    code: &[u8],
+) -> Result<DeployResult> {
+    let mut connection = Connection::new(&extrinsic_opts.submit).await?;
+
+    let code_hash = H256(sp_core::blake2_256(code));
+    if connection
+        .fetch(&PristineCodeStore {
+            code_hash: &code_hash,
+        })
+        .await?
+        .is_some()
+    {
+        log::warn!(
+            "The code {:?} is already stored on the chain, skipping the upload",
+            code_hash
+        );
+        return Ok(DeployResult {
+            code_hash,
+            uploaded: false,
+            extrinsic: None,
+        });
+    }
+
+    let deposit = Deposits::from_metadata(&connection.runtime_metadata().await?)?.code(code);
+    let call = PutCodeCall {
+        _runtime: PhantomData,
+        code,
+    };
+    let submitted = connection
+        .submit_reserving(call, signer, extrinsic_opts, 0, deposit)
+        .await?;
+    let code_stored = match submitted.events() {
+        Some(events) => events.code_stored()?,
+        None => None,
+    };
+    let code_hash = match code_stored {
+        Some(ref event) => event.code_hash,
+        // The code is only stored once the extrinsic is included, and only by the final
+        // approval of a multisig call.
+        None if submitted.included.is_none() || submitted.multisig.is_some() => code_hash,
+        None => anyhow::bail!("Failed to find CodeStored event"),
+    };
 
-        let events = cli.put_code_and_watch(&signer, &code).await?;
-        let code_stored = events
-            .code_stored()?
-            .ok_or(anyhow::anyhow!("Failed to find CodeStored event"))?;
+    let extrinsic = ExtrinsicDetails::new(&submitted)?;
+    if code_stored.is_some() {
+        let deployment = Deployment::new(code_hash, None, None, &extrinsic);
+        deployments::record(&extrinsic_opts.submit, deployment);
+    }
+    Ok(DeployResult {
+        code_hash,
+        uploaded: true,
+        extrinsic: Some(extrinsic),
+    })
+}
 
-        Ok(code_stored.code_hash)
+/// Uploads the code of each contract of the workspace at the current directory, as built into
+/// its target directory, in the order of the members. Code already stored on the chain is
+/// skipped.
+///
+/// Each upload awaits the inclusion of its extrinsic before the next one is submitted.
+pub(crate) fn execute_deploy_all(extrinsic_opts: &ExtrinsicOpts) -> Result<DeployAllResult> {
+    if extrinsic_opts.submit.wait_for == WaitFor::Broadcast {
+        anyhow::bail!("Uploading several contracts requires awaiting the inclusion of each upload")
+    }
+    if extrinsic_opts.nonce.is_some() {
+        anyhow::bail!(
+            "Uploading several contracts submits several extrinsics, which cannot share --nonce"
+        )
+    }
+    let contracts = workspace_contracts(&ManifestPath::default())?.ok_or_else(|| {
+        anyhow::anyhow!("--all uploads the contracts of a workspace, run it at the workspace root")
+    })?;
+    if contracts.is_empty() {
+        anyhow::bail!("The workspace contains no ink! contracts to upload")
+    }
+    let signer = extrinsic_opts.signer()?;
+    let mut uploaded = Vec::new();
+    for contract in contracts {
+        let metadata = crate_metadata::CrateMetadata::collect(&contract)?;
+        let name = metadata.package_name;
+        if !metadata.dest_wasm.exists() {
+            anyhow::bail!(
+                "The contract {} is not built, build the workspace with `cargo contract build`",
+                name
+            )
+        }
+        log::info!("Uploading the contract {}", name);
+        let result = execute_deploy(extrinsic_opts, &signer, Some(&metadata.dest_wasm))
+            .context(format!("Failed to upload the contract {}", name))?;
+        uploaded.push(UploadedContract {
+            name,
+            code_hash: result.code_hash,
+            uploaded: result.uploaded,
+            extrinsic: result.extrinsic.map(|extrinsic| extrinsic.extrinsic),
+        });
+    }
+    Ok(DeployAllResult {
+        contracts: uploaded,
     })
 }
 
+/// Sign the `Contracts::put_code` extrinsic for the contract code without submitting it.
+pub(crate) fn sign_deploy(
+    extrinsic_opts: &ExtrinsicOpts,
+    params: &OfflineParams,
+    contract_wasm_path: Option<&PathBuf>,
+) -> Result<SignedExtrinsic> {
+    let code = load_contract_code(contract_wasm_path)?;
+    let call = PutCodeCall {
+        _runtime: PhantomData,
+        code: &code,
+    };
+    sign::sign_offline(call, extrinsic_opts, params)
+}
+
 #[cfg(test)]
 mod tests {
     use std::{fs, io::Write};
 
-    use crate::{cmd::deploy::execute_deploy, util::tests::with_tmp_dir, ExtrinsicOpts};
+    use sp_core::H256;
+
+    use super::{DeployAllResult, UploadedContract};
+    use crate::{
+        cmd::deploy::execute_deploy, util::tests::with_tmp_dir, ExtrinsicOpts, OutputType,
+        SignerOpts, SubmitOpts, WaitFor,
+    };
     use assert_matches::assert_matches;
 
     const CONTRACT: &str = r#"
@@ -86,6 +295,23 @@ mod tests {
 )
 "#;
 
+    #[test]
+    fn displays_the_code_hashes_of_the_workspace() {
+        let contract = |name: &str, byte, uploaded| UploadedContract {
+            name: name.to_string(),
+            code_hash: H256::repeat_byte(byte),
+            uploaded,
+            extrinsic: None,
+        };
+        let result = DeployAllResult {
+            contracts: vec![contract("erc20", 1, true), contract("multisig_wallet", 2, false)],
+        };
+        let output = result.display();
+        assert!(output.starts_with("Uploaded 1 of 2 contracts"));
+        assert!(output.contains(&format!("\n  erc20            {:?}\n", H256::repeat_byte(1))));
+        assert!(output.ends_with(&format!("{:?} (already stored)", H256::repeat_byte(2))));
+    }
+
     #[test]
     #[ignore] // depends on a local substrate node running
     fn deploy_contract() {
@@ -98,11 +324,40 @@ mod tests {
 
             let url = url::Url::parse("ws://localhost:9944").unwrap();
             let extrinsic_opts = ExtrinsicOpts {
-                url,
-                suri: "//Alice".into(),
-                password: None,
+                submit: SubmitOpts {
+                    urls: vec![url],
+                    network: None,
+                    network_profile: None,
+                    output: OutputType::HumanReadable,
+                    wait_for: WaitFor::InBlock,
+                    tls_ca_cert: None,
+                    raw: false,
+                    record_deployments: false,
+                    progress: Default::default(),
+                },
+                signer_opts: SignerOpts {
+                    suri: Some("//Alice".into()),
+                    keyfile: None,
+                    password: None,
+                    password_file: None,
+                    scheme: crate::cmd::Scheme::Sr25519,
+                },
+                remote_signer: None,
+                remote_account: None,
+                qr_signer: None,
+                nonce: None,
+                lifetime: None,
+                immortal: false,
+                tip: Default::default(),
+                fee_asset: None,
+                sudo: false,
+                proxy: None,
+                multisig: Default::default(),
+                offline: Default::default(),
+                yes: false,
             };
-            let result = execute_deploy(&extrinsic_opts, Some(&wasm_path));
+            let signer = extrinsic_opts.signer()?;
+            let result = execute_deploy(&extrinsic_opts, &signer, Some(&wasm_path));
 
             assert_matches!(result, Ok(_));
             Ok(())
@@ -0,0 +1,221 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sp_core::{
+    crypto::{AccountId32, Ss58AddressFormat},
+    H256,
+};
+
+use super::{
+    address::Address, addressbook::ContractRef, events::ExtrinsicDetails, network::section,
+    transcode::METADATA_FILE,
+};
+use crate::{crate_metadata::CrateMetadata, SubmitOpts};
+
+/// Name of the file recording the deployments, in the directory of the contract project.
+pub(crate) const DEPLOYMENTS_FILE: &str = "deployments.json";
+
+/// An upload of code or an instantiation of a contract.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct Deployment {
+    pub code_hash: H256,
+    /// The instantiated contract, `None` for an upload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract: Option<AccountId32>,
+    /// Hex encoded data the constructor was called with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    /// The block the extrinsic was included in.
+    pub block: H256,
+    pub extrinsic: H256,
+    /// The hash of the metadata of the contract project at the time of the deployment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_hash: Option<H256>,
+}
+
+impl Deployment {
+    /// The deployment by the extrinsic, if its inclusion was awaited.
+    pub fn new(
+        code_hash: H256,
+        contract: Option<AccountId32>,
+        data: Option<&[u8]>,
+        extrinsic: &ExtrinsicDetails,
+    ) -> Option<Self> {
+        Some(Deployment {
+            code_hash,
+            contract,
+            data: data.map(|data| format!("0x{}", hex::encode(data))),
+            block: extrinsic.block?,
+            extrinsic: extrinsic.extrinsic,
+            metadata_hash: None,
+        })
+    }
+}
+
+/// The deployments of the contract project, in the order they happened, for each network.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub(crate) struct Deployments {
+    networks: BTreeMap<String, Vec<Deployment>>,
+}
+
+impl Deployments {
+    /// Reads the deployments, which are empty if the file does not exist yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Deployments::default());
+        }
+        let json =
+            fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&json).context(format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json).context(format!("Failed to write {}", path.display()))
+    }
+
+    pub fn push(&mut self, network: &str, deployment: Deployment) {
+        self.networks
+            .entry(network.to_string())
+            .or_default()
+            .push(deployment)
+    }
+
+    /// The contract instantiated last on the network.
+    pub fn last_contract(&self, network: &str) -> Option<&AccountId32> {
+        self.networks
+            .get(network)?
+            .iter()
+            .rev()
+            .find_map(|deployment| deployment.contract.as_ref())
+    }
+}
+
+/// The project recording the deployments: its `deployments.json` and built metadata.
+struct Project {
+    deployments: PathBuf,
+    metadata: PathBuf,
+}
+
+impl Project {
+    /// The contract project in the current directory, if any.
+    fn current() -> Option<Self> {
+        let crate_metadata = CrateMetadata::collect(&Default::default()).ok()?;
+        let directory = crate_metadata
+            .manifest_path
+            .directory()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Some(Project {
+            deployments: directory.join(DEPLOYMENTS_FILE),
+            metadata: crate_metadata.target_directory.join(METADATA_FILE),
+        })
+    }
+}
+
+/// Appends the deployment to the `deployments.json` of the contract project in the current
+/// directory, if any, and if recording deployments is enabled.
+///
+/// The deployment already succeeded, so failing to record it only logs a warning.
+pub(crate) fn record(submit_opts: &SubmitOpts, deployment: Option<Deployment>) {
+    if !submit_opts.record_deployments {
+        return;
+    }
+    let (project, mut deployment) = match (Project::current(), deployment) {
+        (Some(project), Some(deployment)) => (project, deployment),
+        _ => return,
+    };
+    deployment.metadata_hash = fs::read(&project.metadata)
+        .ok()
+        .map(|metadata| H256(sp_core::blake2_256(&metadata)));
+    let recorded = Deployments::load(&project.deployments).and_then(|mut deployments| {
+        deployments.push(&section(submit_opts), deployment);
+        deployments.save(&project.deployments)
+    });
+    if let Err(err) = recorded {
+        log::warn!("Failed to record the deployment: {:?}", err);
+    }
+}
+
+/// Returns the address of the `contract`, defaulting to the contract instantiated last on the
+/// network by the contract project in the current directory.
+pub(crate) fn resolve_contract(
+    contract: Option<&ContractRef>,
+    submit_opts: &SubmitOpts,
+) -> Result<Address> {
+    if let Some(contract) = contract {
+        return contract.resolve(submit_opts);
+    }
+    let network = section(submit_opts);
+    let missing = || {
+        anyhow::anyhow!(
+            "No contract supplied, and no contract instantiated on {} is recorded in the {} of \
+             the project",
+            network,
+            DEPLOYMENTS_FILE
+        )
+    };
+    let project = Project::current().ok_or_else(missing)?;
+    let deployments = Deployments::load(&project.deployments)?;
+    let account = deployments.last_contract(&network).ok_or_else(missing)?;
+    log::info!("Using the contract {} from {}", account, DEPLOYMENTS_FILE);
+    Ok(Address {
+        account: account.clone(),
+        format: Ss58AddressFormat::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_last_contract_per_network() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            let path = dir.join(DEPLOYMENTS_FILE);
+            let deployment = |contract: Option<u8>| Deployment {
+                code_hash: H256::repeat_byte(1),
+                contract: contract.map(|byte| AccountId32::from([byte; 32])),
+                data: None,
+                block: H256::repeat_byte(2),
+                extrinsic: H256::repeat_byte(3),
+                metadata_hash: None,
+            };
+            let mut deployments = Deployments::load(&path)?;
+            deployments.push("local", deployment(None));
+            deployments.push("local", deployment(Some(4)));
+            deployments.push("local", deployment(Some(5)));
+            deployments.push("shibuya", deployment(Some(6)));
+            deployments.save(&path)?;
+
+            let deployments = Deployments::load(&path)?;
+            assert_eq!(deployments.networks["local"][0], deployment(None));
+            let last = deployments.last_contract("local");
+            assert_eq!(last, Some(&AccountId32::from([5; 32])));
+            assert_eq!(deployments.last_contract("rococo-contracts"), None);
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,303 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{fs, path::PathBuf, str::FromStr};
+
+use anyhow::{Context, Result};
+use structopt::StructOpt;
+
+use super::{
+    bindgen::{Contract, Function},
+    metadata_format::{read, take_latest},
+};
+
+/// The formats to render the reference in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum DocFormat {
+    Markdown,
+    /// A standalone HTML page.
+    Html,
+}
+
+impl FromStr for DocFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input {
+            "markdown" | "md" => Ok(DocFormat::Markdown),
+            "html" => Ok(DocFormat::Html),
+            _ => anyhow::bail!(
+                "The reference is rendered as `markdown` or `html`, not `{}`",
+                input
+            ),
+        }
+    }
+}
+
+/// Renders the reference of the contract from its metadata.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "doc")]
+pub(crate) struct DocCommand {
+    /// Path to the `metadata.json` or `.contract` bundle
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+    /// The format of the reference
+    #[structopt(long, default_value = "markdown", value_name = "markdown | html")]
+    format: DocFormat,
+    /// The file to write the reference to, instead of printing it
+    #[structopt(long, short, parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+impl DocCommand {
+    pub fn exec(&self) -> Result<String> {
+        let mut metadata = read(&self.path)?;
+        let abi = take_latest(&mut metadata).context(format!(
+            "Failed to read the metadata {}",
+            self.path.display()
+        ))?;
+        let contract = Contract::new(&metadata, &abi);
+        let blocks = blocks(&contract);
+        let reference = match self.format {
+            DocFormat::Markdown => markdown(&blocks),
+            DocFormat::Html => html(&format!("{} {}", contract.name, contract.version), &blocks),
+        };
+        match self.output {
+            Some(ref output) => {
+                fs::write(output, reference)
+                    .context(format!("Failed to write {}", output.display()))?;
+                Ok(format!(
+                    "Rendered the reference of {} to {}",
+                    contract.name,
+                    output.display()
+                ))
+            }
+            None => Ok(reference),
+        }
+    }
+}
+
+/// A block of the reference, whose text may contain inline code in backticks.
+#[derive(Debug, PartialEq)]
+enum Block {
+    Heading(usize, String),
+    Paragraph(String),
+    /// The header and the rows of a table.
+    Table(Vec<&'static str>, Vec<Vec<String>>),
+}
+
+/// The paragraphs of a doc comment, separated by blank lines.
+fn paragraphs(docs: &[String]) -> Vec<Block> {
+    let docs = docs.iter().map(|line| line.trim()).collect::<Vec<_>>();
+    docs.split(|line| line.is_empty())
+        .filter(|lines| !lines.is_empty())
+        .map(|lines| Block::Paragraph(lines.join(" ")))
+        .collect()
+}
+
+fn blocks(contract: &Contract) -> Vec<Block> {
+    let registry = &contract.registry;
+    let code = |id: u64| format!("`{}`", registry.name(id));
+    let mut blocks = vec![Block::Heading(
+        1,
+        format!("{} {}", contract.name, contract.version),
+    )];
+    blocks.extend(paragraphs(&contract.docs));
+
+    let mut functions = |heading: &str, functions: &[Function]| {
+        if functions.is_empty() {
+            return;
+        }
+        blocks.push(Block::Heading(2, heading.to_string()));
+        for function in functions {
+            blocks.push(Block::Heading(3, format!("`{}`", function.label)));
+            blocks.extend(paragraphs(&function.docs));
+            let mut properties = Vec::new();
+            if let Some(ref selector) = function.selector {
+                properties.push(format!("Selector `{}`", selector));
+            }
+            if function.mutates {
+                properties.push("mutates the state".to_string());
+            }
+            if function.payable {
+                properties.push("payable".to_string());
+            }
+            if let Some(returns) = function.returns {
+                properties.push(format!("returns {}", code(returns)));
+            }
+            if !properties.is_empty() {
+                blocks.push(Block::Paragraph(properties.join(", ")));
+            }
+            if !function.args.is_empty() {
+                let rows = function
+                    .args
+                    .iter()
+                    .map(|arg| vec![format!("`{}`", arg.label), code(arg.ty)])
+                    .collect();
+                blocks.push(Block::Table(vec!["Argument", "Type"], rows));
+            }
+        }
+    };
+    functions("Constructors", &contract.constructors);
+    functions("Messages", &contract.messages);
+
+    if !contract.events.is_empty() {
+        blocks.push(Block::Heading(2, "Events".to_string()));
+    }
+    for event in &contract.events {
+        blocks.push(Block::Heading(3, format!("`{}`", event.label)));
+        blocks.extend(paragraphs(&event.docs));
+        if !event.args.is_empty() {
+            let rows = event
+                .args
+                .iter()
+                .map(|arg| {
+                    let indexed = if arg.indexed { "yes" } else { "no" };
+                    vec![
+                        format!("`{}`", arg.label),
+                        code(arg.ty),
+                        indexed.to_string(),
+                    ]
+                })
+                .collect();
+            blocks.push(Block::Table(vec!["Field", "Type", "Indexed"], rows));
+        }
+    }
+    blocks
+}
+
+fn markdown(blocks: &[Block]) -> String {
+    let blocks = blocks.iter().map(|block| match block {
+        Block::Heading(level, text) => format!("{} {}", "#".repeat(*level), text),
+        Block::Paragraph(text) => text.clone(),
+        Block::Table(header, rows) => {
+            let row = |cells: &[String]| format!("| {} |", cells.join(" | "));
+            let header = header
+                .iter()
+                .map(|cell| cell.to_string())
+                .collect::<Vec<_>>();
+            let mut table = vec![
+                row(&header),
+                row(&header.iter().map(|_| "---".to_string()).collect::<Vec<_>>()),
+            ];
+            table.extend(rows.iter().map(|cells| row(cells)));
+            table.join("\n")
+        }
+    });
+    let mut markdown = blocks.collect::<Vec<_>>().join("\n\n");
+    markdown.push('\n');
+    markdown
+}
+
+/// Escapes the text for HTML, turning the spans in backticks into inline code.
+fn inline_html(text: &str) -> String {
+    let escaped = text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    escaped
+        .split('`')
+        .enumerate()
+        .map(|(index, span)| {
+            if index % 2 == 1 {
+                format!("<code>{}</code>", span)
+            } else {
+                span.to_string()
+            }
+        })
+        .collect()
+}
+
+fn html(title: &str, blocks: &[Block]) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n\
+         <body>\n",
+        inline_html(title)
+    );
+    for block in blocks {
+        match block {
+            Block::Heading(level, text) => html.push_str(&format!(
+                "<h{level}>{}</h{level}>\n",
+                inline_html(text),
+                level = level
+            )),
+            Block::Paragraph(text) => html.push_str(&format!("<p>{}</p>\n", inline_html(text))),
+            Block::Table(header, rows) => {
+                html.push_str("<table>\n<tr>");
+                for cell in header {
+                    html.push_str(&format!("<th>{}</th>", cell));
+                }
+                html.push_str("</tr>\n");
+                for row in rows {
+                    html.push_str("<tr>");
+                    for cell in row {
+                        html.push_str(&format!("<td>{}</td>", inline_html(cell)));
+                    }
+                    html.push_str("</tr>\n");
+                }
+                html.push_str("</table>\n");
+            }
+        }
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::bindgen::tests::contract;
+    use serde_json::json;
+
+    #[test]
+    fn renders_the_reference() {
+        let metadata = json!({ "contract": { "name": "flipper", "version": "0.1.0" } });
+        let abi = json!({
+            "spec": {
+                "constructors": [],
+                "messages": [{
+                    "label": "set",
+                    "selector": "0xe8c45eb6",
+                    "mutates": true,
+                    "payable": true,
+                    "args": [{ "label": "value", "type": { "type": 0 } }],
+                    "returnType": null,
+                    "docs": [" Sets the value.", "", " Fails if `value` is unchanged."]
+                }],
+                "events": [],
+                "docs": [" A contract flipping a bool."]
+            },
+            "types": [{ "id": 0, "type": { "def": { "sequence": { "type": 1 } } } },
+                      { "id": 1, "type": { "def": { "primitive": "u8" } } }]
+        });
+        let (metadata, abi) = contract(metadata, abi);
+        let blocks = blocks(&Contract::new(&metadata, &abi));
+
+        assert_eq!(
+            markdown(&blocks),
+            "# flipper 0.1.0\n\n\
+             A contract flipping a bool.\n\n\
+             ## Messages\n\n\
+             ### `set`\n\n\
+             Sets the value.\n\n\
+             Fails if `value` is unchanged.\n\n\
+             Selector `0xe8c45eb6`, mutates the state, payable\n\n\
+             | Argument | Type |\n| --- | --- |\n| `value` | `Vec<u8>` |\n"
+        );
+        assert!(html("flipper", &blocks)
+            .contains("<tr><td><code>value</code></td><td><code>Vec&lt;u8&gt;</code></td></tr>"));
+    }
+}
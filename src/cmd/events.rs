@@ -0,0 +1,379 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::Path;
+
+use anyhow::Result;
+use codec::Decode;
+use serde::Serialize;
+use serde_json::Value;
+use sp_core::{crypto::AccountId32, H256};
+use subxt::{system::ExtrinsicSuccessEvent, DefaultNodeRuntime, RawEvent};
+
+use super::{
+    address::Address,
+    balance::BalanceFormat,
+    extrinsic::{self, Connection, Submitted},
+    gas::display_ref_time,
+    multisig::MultisigDetails,
+    transcode::InkMetadata,
+};
+use crate::{OutputType, SubmitOpts};
+
+/// Maximum number of blocks skipped by the subscription which are searched for events.
+const MAX_SKIPPED_BLOCKS: u32 = 64;
+
+/// Details of a submitted extrinsic.
+#[derive(Debug, Serialize)]
+pub(crate) struct ExtrinsicDetails {
+    /// Hash of the block the extrinsic was included in, if its inclusion was awaited.
+    pub block: Option<H256>,
+    /// Hash of the extrinsic itself.
+    pub extrinsic: H256,
+    /// Weight consumed by the extrinsic, as reported by the `System::ExtrinsicSuccess` event.
+    pub weight: Option<u64>,
+    /// All events emitted while executing the extrinsic.
+    pub events: Vec<DisplayEvent>,
+    /// The storage deposits held or released by the contracts module.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub storage_deposits: Vec<StorageDepositTransfer>,
+    /// Details of the multisig operation, if the call was dispatched from a multisig account.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multisig: Option<MultisigDetails>,
+    /// Url of the extrinsic in the block explorer of the network.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explorer: Option<String>,
+    /// How the storage deposits are printed in the human readable output.
+    #[serde(skip)]
+    pub balance_format: BalanceFormat,
+}
+
+impl ExtrinsicDetails {
+    /// Collects the details of a successfully submitted extrinsic.
+    pub fn new(submitted: &Submitted) -> Result<Self> {
+        let (block, weight, events, storage_deposits) = match submitted.events() {
+            Some(result) => (
+                Some(result.block),
+                result
+                    .find_event::<ExtrinsicSuccessEvent<DefaultNodeRuntime>>()?
+                    .map(|event| event.info.weight),
+                result.events.iter().map(DisplayEvent::from).collect(),
+                StorageDepositTransfer::from_events(&result.events)?,
+            ),
+            None => (None, None, Vec::new(), Vec::new()),
+        };
+        Ok(ExtrinsicDetails {
+            block,
+            extrinsic: submitted.extrinsic,
+            weight,
+            events,
+            storage_deposits,
+            multisig: submitted.multisig.clone(),
+            explorer: submitted.explorer.clone(),
+            balance_format: submitted.balance_format.clone(),
+        })
+    }
+
+    /// Appends the weight consumed, the multisig details and the explorer link, if any, to the
+    /// human readable output of a command.
+    pub fn display_details(&self, output: String) -> String {
+        let mut output = output;
+        if let Some(weight) = self.weight {
+            output = format!("{}\n\tWeight: {}", output, display_ref_time(weight));
+        }
+        for deposit in &self.storage_deposits {
+            output = format!("{}\n\t{}", output, deposit.display(&self.balance_format));
+        }
+        if let Some(ref multisig) = self.multisig {
+            output = format!("{}\n\t{}", output, multisig);
+        }
+        if let Some(ref explorer) = self.explorer {
+            output = format!("{}\n\tExplorer: {}", output, explorer);
+        }
+        output
+    }
+}
+
+/// A storage deposit held or released by the contracts module, as reported by its
+/// `StorageDepositTransferAndHeld` and `StorageDepositTransferAndReleased` events.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct StorageDepositTransfer {
+    pub from: AccountId32,
+    pub to: AccountId32,
+    /// The amount charged and held, or released as a negative amount.
+    pub amount: i128,
+}
+
+impl StorageDepositTransfer {
+    /// Collects the storage deposits held or released by the events.
+    fn from_events(events: &[RawEvent]) -> Result<Vec<Self>> {
+        let mut deposits = Vec::new();
+        for event in events.iter().filter(|event| event.module == "Contracts") {
+            let sign = match event.variant.as_str() {
+                "StorageDepositTransferAndHeld" => 1,
+                "StorageDepositTransferAndReleased" => -1,
+                _ => continue,
+            };
+            let (from, to, amount) =
+                <(AccountId32, AccountId32, u128)>::decode(&mut &event.data[..])?;
+            deposits.push(StorageDepositTransfer {
+                from,
+                to,
+                amount: sign * amount as i128,
+            });
+        }
+        Ok(deposits)
+    }
+
+    /// The human readable output.
+    fn display(&self, balance_format: &BalanceFormat) -> String {
+        let amount = balance_format.display(self.amount.wrapping_abs() as u128);
+        if self.amount < 0 {
+            format!(
+                "Storage deposit: {} released by {} to {}",
+                amount, self.from, self.to
+            )
+        } else {
+            format!(
+                "Storage deposit: {} charged to {}, held by {}",
+                amount, self.from, self.to
+            )
+        }
+    }
+}
+
+/// A runtime event with its data left SCALE encoded.
+#[derive(Debug, Serialize)]
+pub(crate) struct DisplayEvent {
+    /// The name of the module which emitted the event.
+    pub module: String,
+    /// The name of the event.
+    pub variant: String,
+    /// Hex encoded event data.
+    pub data: String,
+    /// The SCALE encoded event data.
+    #[serde(skip)]
+    pub bytes: Vec<u8>,
+}
+
+impl From<&RawEvent> for DisplayEvent {
+    fn from(event: &RawEvent) -> Self {
+        DisplayEvent {
+            module: event.module.clone(),
+            variant: event.variant.clone(),
+            data: format!("0x{}", hex::encode(&event.data)),
+            bytes: event.data.clone(),
+        }
+    }
+}
+
+/// An event emitted by a contract.
+#[derive(Debug, Serialize)]
+pub(crate) struct ContractEvent {
+    /// Hash of the block the event was emitted in.
+    pub block: H256,
+    pub block_number: u32,
+    /// The name of the event, if it could be decoded with the metadata.
+    pub name: Option<String>,
+    /// The arguments of the event, if it could be decoded with the metadata.
+    pub args: Option<Value>,
+    /// The hex encoded event data.
+    pub data: String,
+    /// The labels of the arguments which are balances.
+    #[serde(skip)]
+    pub balance_args: Vec<String>,
+    /// How the balance arguments are printed in the human readable output.
+    #[serde(skip)]
+    pub balance_format: BalanceFormat,
+}
+
+impl ContractEvent {
+    /// The human readable output, with the balance arguments denominated in the chain's token.
+    pub fn display(&self) -> String {
+        match (&self.name, &self.args) {
+            (Some(name), Some(args)) => {
+                let mut args = args.clone();
+                for label in &self.balance_args {
+                    let balance = args.get(label).and_then(|balance| match balance {
+                        Value::String(balance) => balance.parse().ok(),
+                        balance => balance.as_u64().map(u128::from),
+                    });
+                    if let Some(balance) = balance {
+                        args[label] = Value::from(self.balance_format.display(balance));
+                    }
+                }
+                format!("#{} {:?}: {} {}", self.block_number, self.block, name, args)
+            }
+            _ => format!("#{} {:?}: {}", self.block_number, self.block, self.data),
+        }
+    }
+}
+
+/// Returns the data of the events emitted by the contract, which the contracts module emits as
+/// `ContractExecution`, or `ContractEmitted` in later versions.
+fn emitted_by(events: &[RawEvent], contract: &AccountId32) -> Result<Vec<Vec<u8>>> {
+    let mut emitted = Vec::new();
+    for event in events {
+        let contract_event = event.module == "Contracts"
+            && (event.variant == "ContractExecution" || event.variant == "ContractEmitted");
+        if contract_event {
+            let (emitter, data) = <(AccountId32, Vec<u8>)>::decode(&mut &event.data[..])?;
+            if emitter == *contract {
+                emitted.push(data);
+            }
+        }
+    }
+    Ok(emitted)
+}
+
+/// Streams the events emitted by the contract in new best blocks, or finalized ones, until
+/// interrupted.
+///
+/// The events are decoded with the contract's metadata where possible, and printed a line each.
+pub(crate) fn execute_events(
+    submit_opts: &SubmitOpts,
+    contract: &Address,
+    finalized: bool,
+    metadata_path: Option<&Path>,
+) -> Result<String> {
+    let metadata =
+        InkMetadata::load(metadata_path)?.map(|metadata| metadata.for_output(submit_opts.output));
+
+    async_std::task::block_on(async move {
+        let connection = Connection::new(submit_opts).await?;
+        contract.check_format();
+
+        let mut heads = connection.subscribe_heads(finalized).await?;
+        log::info!("Listening for the events of contract {}", contract.account);
+        let mut next: Option<u32> = None;
+        loop {
+            let head = heads.next().await;
+            // Finalized blocks are reported in batches, only the last of which is notified.
+            let from = match next {
+                Some(next) if next < head.number => next.max(head.number - MAX_SKIPPED_BLOCKS),
+                _ => head.number,
+            };
+            next = Some(head.number + 1);
+            for number in from..=head.number {
+                let block = match number {
+                    number if number == head.number => head.hash(),
+                    number => match connection.block_hash(number).await? {
+                        Some(hash) => hash,
+                        None => continue,
+                    },
+                };
+                let decoder = extrinsic::contracts_events_decoder(connection.metadata());
+                let events = connection.block_events(block, decoder).await?;
+                for data in emitted_by(&events, &contract.account)? {
+                    let decoded = metadata
+                        .as_ref()
+                        .map(|metadata| metadata.decode_event(&data));
+                    let balance_args = metadata
+                        .as_ref()
+                        .map(|metadata| metadata.event_balance_args(&data))
+                        .unwrap_or_default();
+                    let (name, args) = match decoded {
+                        Some(Ok((name, args))) => (Some(name), Some(args)),
+                        Some(Err(err)) => {
+                            log::warn!("Failed to decode event: {}", err);
+                            (None, None)
+                        }
+                        None => (None, None),
+                    };
+                    let event = ContractEvent {
+                        block,
+                        block_number: number,
+                        name,
+                        args,
+                        data: format!("0x{}", hex::encode(&data)),
+                        balance_args,
+                        balance_format: connection.balance_format(),
+                    };
+                    match submit_opts.output {
+                        OutputType::HumanReadable => println!("{}", event.display()),
+                        OutputType::Json => println!("{}", serde_json::to_string(&event)?),
+                        OutputType::JsonPjs => {
+                            println!("{}", serde_json::to_string(&super::to_pjs_value(&event)?)?)
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+
+    #[test]
+    fn filters_events_of_contract() {
+        let contract = AccountId32::from([1; 32]);
+        let other = AccountId32::from([2; 32]);
+        let event = |variant: &str, emitter: &AccountId32, data: Vec<u8>| RawEvent {
+            module: "Contracts".into(),
+            variant: variant.into(),
+            data: (emitter, data).encode(),
+        };
+        let events = vec![
+            event("ContractExecution", &contract, vec![1]),
+            event("ContractExecution", &other, vec![2]),
+            event("Instantiated", &contract, vec![3]),
+            event("ContractEmitted", &contract, vec![4]),
+        ];
+        assert_eq!(
+            emitted_by(&events, &contract).unwrap(),
+            vec![vec![1], vec![4]]
+        );
+    }
+
+    #[test]
+    fn collects_storage_deposits() {
+        let (payer, contract) = (AccountId32::from([1; 32]), AccountId32::from([2; 32]));
+        let event = |variant: &str, data: Vec<u8>| RawEvent {
+            module: "Contracts".into(),
+            variant: variant.into(),
+            data,
+        };
+        let events = vec![
+            event(
+                "StorageDepositTransferAndHeld",
+                (&payer, &contract, 100u128).encode(),
+            ),
+            event("Called", (&payer, &contract).encode()),
+            event(
+                "StorageDepositTransferAndReleased",
+                (&contract, &payer, 40u128).encode(),
+            ),
+        ];
+        let deposits = StorageDepositTransfer::from_events(&events).unwrap();
+        assert_eq!(
+            deposits
+                .iter()
+                .map(|deposit| deposit.amount)
+                .collect::<Vec<_>>(),
+            vec![100, -40]
+        );
+        assert_eq!(
+            deposits[1].display(&BalanceFormat::new("UNIT", 1)),
+            format!(
+                "Storage deposit: 4 UNIT released by {} to {}",
+                contract, payer
+            )
+        );
+    }
+}
@@ -0,0 +1,122 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::Result;
+use codec::{Compact, Encode};
+use frame_metadata::{DecodeDifferent, RuntimeMetadata, RuntimeMetadataPrefixed};
+
+use super::sign::SigningContext;
+
+/// The signed extensions of the runtime which add data to the extrinsic or to the signed
+/// payload, in the order they appear in the extrinsic.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum SignedExtension {
+    CheckSpecVersion,
+    CheckTxVersion,
+    CheckGenesis,
+    /// The era of the extrinsic, named `CheckEra` by older runtimes.
+    CheckMortality,
+    CheckNonce,
+    /// The tip in the native token.
+    ChargeTransactionPayment,
+    /// The tip and the asset the fee is paid in, the native token if none.
+    ChargeAssetTxPayment,
+    /// An extension adding no data, e.g. `CheckWeight`.
+    Empty,
+}
+
+/// The extensions known to add no data to the extrinsic or the signed payload.
+const EMPTY_EXTENSIONS: &[&str] = &["CheckWeight", "CheckNonZeroSender", "CheckBlockGasLimit"];
+
+impl SignedExtension {
+    /// Looks up the extension by its name in the runtime metadata.
+    fn from_name(name: &str) -> Result<Self> {
+        Ok(match name {
+            "CheckSpecVersion" => SignedExtension::CheckSpecVersion,
+            "CheckTxVersion" => SignedExtension::CheckTxVersion,
+            "CheckGenesis" => SignedExtension::CheckGenesis,
+            "CheckMortality" | "CheckEra" => SignedExtension::CheckMortality,
+            "CheckNonce" => SignedExtension::CheckNonce,
+            "ChargeTransactionPayment" => SignedExtension::ChargeTransactionPayment,
+            "ChargeAssetTxPayment" => SignedExtension::ChargeAssetTxPayment,
+            _ if EMPTY_EXTENSIONS.contains(&name) => SignedExtension::Empty,
+            _ => anyhow::bail!(
+                "The chain uses the signed extension {}, which is not supported, the extrinsic \
+                 would be rejected with a bad signature",
+                name
+            ),
+        })
+    }
+
+    /// Encodes the data the extension adds to the extrinsic into `extra`, and the data it adds
+    /// only to the signed payload into `additional_signed`.
+    pub fn encode_to(
+        &self,
+        context: &SigningContext,
+        extra: &mut Vec<u8>,
+        additional_signed: &mut Vec<u8>,
+    ) {
+        match self {
+            SignedExtension::CheckSpecVersion => context.spec_version.encode_to(additional_signed),
+            SignedExtension::CheckTxVersion => context.tx_version.encode_to(additional_signed),
+            SignedExtension::CheckGenesis => context.genesis_hash.encode_to(additional_signed),
+            SignedExtension::CheckMortality => {
+                context.era.encode_to(extra);
+                context.era_block_hash.encode_to(additional_signed)
+            }
+            SignedExtension::CheckNonce => Compact(context.nonce).encode_to(extra),
+            SignedExtension::ChargeTransactionPayment => Compact(context.tip).encode_to(extra),
+            SignedExtension::ChargeAssetTxPayment => {
+                (Compact(context.tip), context.fee_asset).encode_to(extra)
+            }
+            SignedExtension::Empty => {}
+        }
+    }
+}
+
+/// Returns the signed extensions listed in the runtime metadata.
+pub(crate) fn from_metadata(metadata: &RuntimeMetadataPrefixed) -> Result<Vec<SignedExtension>> {
+    let extrinsic = match metadata.1 {
+        RuntimeMetadata::V12(ref metadata) => &metadata.extrinsic,
+        _ => anyhow::bail!("Unsupported runtime metadata version, expected V12"),
+    };
+    extrinsic
+        .signed_extensions
+        .iter()
+        .map(|name| match name {
+            DecodeDifferent::Encode(name) => SignedExtension::from_name(name),
+            DecodeDifferent::Decoded(name) => SignedExtension::from_name(name),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looks_up_extensions_by_name() {
+        assert_eq!(
+            SignedExtension::from_name("CheckEra").unwrap(),
+            SignedExtension::CheckMortality
+        );
+        assert_eq!(
+            SignedExtension::from_name("CheckWeight").unwrap(),
+            SignedExtension::Empty
+        );
+        assert!(SignedExtension::from_name("ChargeSomethingElse").is_err());
+    }
+}
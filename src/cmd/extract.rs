@@ -0,0 +1,175 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use structopt::StructOpt;
+
+use super::metadata::{blake2_hash, to_hex};
+use crate::Error;
+
+/// Writes the Wasm and the metadata of a `.contract` bundle to separate files.
+///
+/// The PolkaVM program of a contract compiled for RISC-V is written to a `.polkavm` file.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "extract")]
+pub(crate) struct ExtractCommand {
+    /// Path to the `.contract` bundle
+    #[structopt(parse(from_os_str))]
+    bundle: PathBuf,
+    /// The directory to write the files to, by default the directory of the bundle
+    #[structopt(long, parse(from_os_str))]
+    output_dir: Option<PathBuf>,
+}
+
+impl ExtractCommand {
+    pub fn exec(&self) -> Result<String> {
+        let (wasm, extension, metadata) = extract(&self.bundle)?;
+        let output_dir = match self.output_dir {
+            Some(ref output_dir) => output_dir.as_path(),
+            None => self.bundle.parent().unwrap_or_else(|| Path::new("")),
+        };
+        let stem = self
+            .bundle
+            .file_stem()
+            .ok_or_else(|| anyhow::anyhow!("{} is no file", self.bundle.display()))?;
+        let dest_wasm = output_dir.join(stem).with_extension(extension);
+        let dest_metadata = output_dir.join(stem).with_extension("json");
+        fs::write(&dest_wasm, wasm).context(format!("Failed to write {}", dest_wasm.display()))?;
+        fs::write(&dest_metadata, serde_json::to_string_pretty(&metadata)?)
+            .context(format!("Failed to write {}", dest_metadata.display()))?;
+        Ok(format!(
+            "Extracted the code to {} and the metadata to {}",
+            dest_wasm.display(),
+            dest_metadata.display()
+        ))
+    }
+}
+
+/// The code and the metadata of a `.contract` bundle.
+#[derive(Debug)]
+pub struct ContractBundle {
+    /// The Wasm, or the PolkaVM program of a contract compiled for RISC-V.
+    pub code: Vec<u8>,
+    /// The extension of the file of the code, `wasm` or `polkavm`.
+    pub extension: &'static str,
+    /// The metadata, without the code in its `source` section.
+    pub metadata: Value,
+}
+
+impl ContractBundle {
+    /// Reads the bundle, failing if the code does not match the code hash of the metadata.
+    pub fn read(bundle: &Path) -> Result<Self, Error> {
+        let (code, extension, metadata) = extract(bundle)?;
+        Ok(ContractBundle {
+            code,
+            extension,
+            metadata,
+        })
+    }
+}
+
+/// Returns the Wasm of the bundle, the extension of its file and the metadata without the Wasm,
+/// failing if the Wasm does not match the code hash of the metadata.
+pub(crate) fn extract(bundle: &Path) -> Result<(Vec<u8>, &'static str, Value)> {
+    let json = fs::read(bundle).context(format!("Failed to read {}", bundle.display()))?;
+    let mut metadata: Value = serde_json::from_slice(&json)
+        .context(format!("Failed to parse {} as a bundle", bundle.display()))?;
+    let source = metadata
+        .get_mut("source")
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| anyhow::anyhow!("The bundle has no source section"))?;
+    let (wasm, extension) = match (source.remove("wasm"), source.remove("polkavm")) {
+        (Some(Value::String(wasm)), _) => (wasm, "wasm"),
+        (_, Some(Value::String(program))) => (program, "polkavm"),
+        _ => anyhow::bail!("The bundle contains no Wasm, it is metadata only"),
+    };
+    let wasm = decode_hex(&wasm).context("Invalid code in the bundle")?;
+    let hash = to_hex(&blake2_hash(&wasm).0);
+    match source.get("hash").and_then(Value::as_str) {
+        Some(expected) if expected == hash => Ok((wasm, extension, metadata)),
+        Some(expected) => anyhow::bail!(
+            "The Wasm of the bundle hashes to {}, not to the code hash {} of its metadata",
+            hash,
+            expected
+        ),
+        None => anyhow::bail!("The bundle has no code hash"),
+    }
+}
+
+/// Decodes the `0x` prefixed hex the bundle encodes bytes with.
+pub(crate) fn decode_hex(input: &str) -> Result<Vec<u8>> {
+    let hex = input.strip_prefix("0x").unwrap_or(input).as_bytes();
+    let digit = |digit: u8| match digit {
+        b'0'..=b'9' => Ok(digit - b'0'),
+        b'a'..=b'f' => Ok(digit - b'a' + 10),
+        b'A'..=b'F' => Ok(digit - b'A' + 10),
+        _ => anyhow::bail!("Invalid hex digit {:?}", digit as char),
+    };
+    let mut bytes = Vec::with_capacity(hex.len() / 2);
+    for pair in hex.chunks(2) {
+        match *pair {
+            [high, low] => bytes.push(digit(high)? << 4 | digit(low)?),
+            _ => anyhow::bail!("Odd number of hex digits"),
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn validates_the_code_hash() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            let wasm = b"\0asm".to_vec();
+            let bundle = |hash: String| {
+                json!({
+                    "metadataVersion": "0.1.0",
+                    "source": { "hash": hash, "wasm": to_hex(&wasm) },
+                    "spec": {}
+                })
+            };
+            let path = dir.join("flipper.contract");
+            fs::write(&path, bundle(to_hex(&blake2_hash(&wasm).0)).to_string())?;
+            let (extracted, extension, metadata) = extract(&path)?;
+            assert_eq!(extracted, wasm);
+            assert_eq!(extension, "wasm");
+            assert!(metadata["source"].get("wasm").is_none());
+            assert_eq!(metadata["spec"], json!({}));
+
+            fs::write(&path, bundle(to_hex(&[0; 32])).to_string())?;
+            assert!(extract(&path).is_err());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn decodes_the_hex_of_the_bytes() {
+        let bytes = (0..=255).collect::<Vec<u8>>();
+        assert_eq!(decode_hex(&to_hex(&bytes)).unwrap(), bytes);
+        assert_eq!(decode_hex("0xDEad").unwrap(), vec![0xde, 0xad]);
+        assert!(decode_hex("0xabc").is_err());
+        assert!(decode_hex("0xgg").is_err());
+    }
+}
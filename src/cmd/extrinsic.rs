@@ -0,0 +1,1184 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{future::Future, sync::Mutex, time::Duration};
+
+use anyhow::{Context, Result};
+use codec::{Decode, Encode};
+use frame_metadata::RuntimeMetadataPrefixed;
+use futures::future;
+use jsonrpsee::{
+    client::Subscription,
+    common::{to_value, Params},
+};
+use sp_core::{
+    crypto::AccountId32,
+    storage::{StorageChangeSet, StorageData},
+    Bytes, H256,
+};
+use sp_transaction_pool::TransactionStatus;
+use subxt::{
+    balances::AccountData,
+    contracts::ContractsEventsDecoder,
+    sp_runtime::{generic::Era, traits::Hash},
+    system::{AccountStoreExt, System},
+    Call, Client, ClientBuilder, DefaultNodeRuntime, Encoded, EventSubscription, EventsDecoder,
+    ExtrinsicSuccess, Metadata, RawEvent, RuntimeVersion, Store,
+};
+use tracing::Instrument;
+
+use super::address;
+use super::balance::{BalanceFormat, BalanceVariant};
+use super::confirm::{self, Summary};
+use super::extension::{self, SignedExtension};
+use super::http;
+use super::module_error::ModuleError;
+use super::multisig::{MultisigDetails, Timepoint};
+use super::network::{ChainProperties, Network};
+use super::replay;
+use super::sign::{self, Extrinsic, Mortality, SignedExtras, SigningContext};
+use super::signer::ExtrinsicSigner;
+use super::wrap::{SudoKeyStore, WrappedCall};
+use super::ws::{self, WsConfig};
+use crate::{progress::Reporter, ExtrinsicOpts, Progress, RpcError, SubmitOpts, WaitFor};
+
+/// The outcome of submitting an extrinsic.
+pub(crate) struct Submitted {
+    /// Hash of the extrinsic.
+    pub extrinsic: H256,
+    /// The inclusion of the extrinsic in a block, if it was awaited.
+    pub included: Option<Included>,
+    /// Details of the multisig operation, if the call was dispatched from a multisig account.
+    pub multisig: Option<MultisigDetails>,
+    /// Url of the extrinsic in the block explorer of the network, if it defines one.
+    pub explorer: Option<String>,
+    /// How the balances moved by the extrinsic are printed.
+    pub balance_format: BalanceFormat,
+}
+
+impl Submitted {
+    /// The events emitted by the extrinsic, if its inclusion was awaited.
+    pub fn events(&self) -> Option<&ExtrinsicSuccess<DefaultNodeRuntime>> {
+        self.included.as_ref().map(|included| &included.result)
+    }
+}
+
+/// An extrinsic included in a block, which is finalized if that was requested.
+pub(crate) struct Included {
+    /// The block and the events emitted by the extrinsic.
+    pub result: ExtrinsicSuccess<DefaultNodeRuntime>,
+    /// The number of the block and the index of the extrinsic within it.
+    pub timepoint: Timepoint,
+}
+
+/// Interval in which the connection is checked while waiting for the status of an extrinsic.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// Number of attempts to reconnect after the connection to the node was lost.
+const RECONNECT_ATTEMPTS: u32 = 6;
+/// Interval in which new blocks are searched for an extrinsic which can not be watched.
+const RECOVER_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Number of blocks searched for an extrinsic which can not be watched, before giving up.
+const MAX_RECOVER_BLOCKS: u32 = 256;
+/// Time to wait for the events of the best block after it was found to include the extrinsic.
+const RECOVER_EVENTS_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A connection to a substrate node for submitting extrinsics.
+pub(crate) struct Connection {
+    /// The endpoints of the chain's nodes, in order of preference.
+    urls: Vec<url::Url>,
+    /// Index of the endpoint connected to.
+    endpoint: usize,
+    /// The network selected with `--network`, overriding the chain's token.
+    network: Option<Network>,
+    /// The proxy and TLS settings of WebSocket endpoints.
+    ws: WsConfig,
+    client: Client<DefaultNodeRuntime>,
+    /// The underlying RPC client, required for watching the extrinsic status.
+    rpc: jsonrpsee::Client,
+    runtime_version: RuntimeVersion,
+    /// The address format and token of the chain.
+    properties: ChainProperties,
+    /// Whether balances are printed in the smallest unit, as requested with `--raw`.
+    raw: bool,
+    /// Whether the transfer of value by the extrinsics submitted was confirmed.
+    confirmed: bool,
+    /// The state fetched for signing the next extrinsic while running another query.
+    prefetched: Mutex<Option<SigningState>>,
+    /// The hook the statuses of the submitted extrinsics are reported to.
+    progress: Reporter,
+}
+
+/// What signing an extrinsic requires from the node, independent of its call.
+struct SigningState {
+    signer: AccountId32,
+    mortality: Mortality,
+    nonce: u32,
+    era: Era,
+    era_block_hash: H256,
+    extensions: Vec<SignedExtension>,
+}
+
+impl Connection {
+    /// Connects to the first reachable node of the `--url` endpoints, in order of preference.
+    pub async fn new(submit_opts: &SubmitOpts) -> Result<Self> {
+        let ws = WsConfig::new(submit_opts.tls_ca_cert.as_deref())?;
+        let mut connection = Self::failover(&submit_opts.urls, 0, None, &ws).await?;
+        let network = submit_opts.network_profile.as_ref();
+        let ss58_format = network
+            .and_then(|network| network.ss58_prefix)
+            .or(connection.properties.ss58_format);
+        if let Some(ss58_format) = ss58_format {
+            address::set_address_format(ss58_format);
+        }
+        if let Some(network) = network {
+            let properties = &connection.properties;
+            let differs = |setting: Option<u8>, chain: Option<u8>| {
+                matches!((setting, chain), (Some(s), Some(c)) if s != c)
+            };
+            if differs(network.ss58_prefix, properties.ss58_format)
+                || differs(network.token_decimals, properties.token_decimals)
+            {
+                log::warn!(
+                    "The address format or token decimals of the network differ from the chain's"
+                );
+            }
+            connection.network = Some(network.clone());
+        }
+        connection.raw = submit_opts.raw;
+        connection.progress = submit_opts.progress.clone();
+        Ok(connection)
+    }
+
+    /// Connects to the first reachable node of the endpoints, starting at `first`, which serves
+    /// the chain with the given genesis hash.
+    async fn failover(
+        urls: &[url::Url],
+        first: usize,
+        genesis_hash: Option<H256>,
+        ws: &WsConfig,
+    ) -> Result<Self> {
+        let mut error = anyhow::anyhow!("No url of a substrate node supplied");
+        for endpoint in failover_order(urls.len(), first) {
+            let url = &urls[endpoint];
+            let connect = Self::connect(url, ws).instrument(tracing::info_span!("connect", %url));
+            let (client, rpc, runtime_version, properties) = match connect.await {
+                Ok(connected) => connected,
+                Err(err) => {
+                    log::warn!("{:#}", err);
+                    error = RpcError::new(format!("{:#}", err)).into();
+                    continue;
+                }
+            };
+            if let Err(err) = check_genesis(url, *client.genesis(), genesis_hash) {
+                log::warn!("Skipping {}, it serves a different chain", url);
+                error = err;
+                continue;
+            }
+            return Ok(Connection {
+                urls: urls.to_vec(),
+                endpoint,
+                network: None,
+                ws: ws.clone(),
+                client,
+                rpc,
+                runtime_version,
+                properties,
+                raw: false,
+                confirmed: false,
+                prefetched: Mutex::new(None),
+                progress: Reporter::default(),
+            });
+        }
+        Err(error)
+    }
+
+    async fn connect(
+        url: &url::Url,
+        ws: &WsConfig,
+    ) -> Result<(
+        Client<DefaultNodeRuntime>,
+        jsonrpsee::Client,
+        RuntimeVersion,
+        ChainProperties,
+    )> {
+        let rpc = match url.scheme() {
+            "ws" | "wss" => ws::ws_client(url, ws).await?,
+            _ => http::http_client(url),
+        };
+        let client = async {
+            let builder = ClientBuilder::<DefaultNodeRuntime>::new().set_client(rpc.clone());
+            Ok::<_, anyhow::Error>(builder.build().await?)
+        };
+        let runtime_version = async {
+            let params = Params::None;
+            Ok(rpc.request("state_getRuntimeVersion", params).await?)
+        };
+        let properties = async {
+            let params = Params::None;
+            match rpc.request("system_properties", params).await {
+                Ok(properties) => Ok(ChainProperties::from_json(&properties)),
+                Err(err) => {
+                    log::warn!("The node reports no chain properties: {}", err);
+                    Ok(ChainProperties::default())
+                }
+            }
+        };
+        let (client, runtime_version, properties) = future::try_join3(
+            client.instrument(tracing::info_span!("build client")),
+            runtime_version.instrument(rpc_span("state_getRuntimeVersion")),
+            properties.instrument(rpc_span("system_properties")),
+        )
+        .await?;
+        Ok((client, rpc, runtime_version, properties))
+    }
+
+    /// The runtime metadata of the chain.
+    pub fn metadata(&self) -> &Metadata {
+        self.client.metadata()
+    }
+
+    /// Sends an RPC request to the connected node.
+    pub async fn request<T>(&self, method: &str, params: Params) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let response = self
+            .rpc
+            .request(method, params)
+            .instrument(rpc_span(method))
+            .await;
+        Ok(response.map_err(RpcError::new)?)
+    }
+
+    /// Fetches the value of the storage item from the best block.
+    pub async fn fetch<F>(&self, store: &F) -> Result<Option<F::Returns>>
+    where
+        F: Store<DefaultNodeRuntime>,
+    {
+        self.fetch_at(store, None).await
+    }
+
+    /// Fetches the value of the storage item from the block, the best block if `None`.
+    pub async fn fetch_at<F>(&self, store: &F, at: Option<H256>) -> Result<Option<F::Returns>>
+    where
+        F: Store<DefaultNodeRuntime>,
+    {
+        Ok(self.client.fetch(store, at).await.map_err(RpcError::new)?)
+    }
+
+    /// Subscribes to the headers of new best blocks, or of newly finalized ones.
+    pub async fn subscribe_heads(
+        &self,
+        finalized: bool,
+    ) -> Result<Subscription<<DefaultNodeRuntime as System>::Header>> {
+        if finalized {
+            Ok(self.client.subscribe_finalized_blocks().await?)
+        } else {
+            Ok(self.client.subscribe_blocks().await?)
+        }
+    }
+
+    /// Returns the hash of the canonical block with the number.
+    pub async fn block_hash(&self, number: u32) -> Result<Option<H256>> {
+        Ok(self.client.block_hash(Some(number.into())).await?)
+    }
+
+    /// Returns all events emitted in the block.
+    ///
+    /// Decoding stops at the first event which fails to decode, e.g. of a failed extrinsic.
+    pub async fn block_events(
+        &self,
+        block: H256,
+        decoder: EventsDecoder<DefaultNodeRuntime>,
+    ) -> Result<Vec<RawEvent>> {
+        let key = self
+            .metadata()
+            .module("System")?
+            .storage("Events")?
+            .plain()?
+            .key();
+        let params = Params::Array(vec![to_value(&key)?, to_value(block)?]);
+        let data: Option<StorageData> = self.request("state_getStorage", params).await?;
+        let change_set = StorageChangeSet {
+            block,
+            changes: vec![(key, data)],
+        };
+        let mut sub = EventSubscription::new(replay::replay(change_set).await?, decoder);
+        sub.filter_block(block);
+        let mut events = Vec::new();
+        while let Some(event) = sub.next().await {
+            match event {
+                Ok(event) => events.push(event),
+                Err(err) => {
+                    log::warn!("Failed to decode the events of block {:?}: {}", block, err);
+                    break;
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    /// Fetches the balances of the account at the block, the best block if `None`.
+    pub async fn account_data(
+        &self,
+        account: &AccountId32,
+        at: Option<H256>,
+    ) -> Result<AccountData<u128>> {
+        Ok(self.client.account(account, at).await?.data)
+    }
+
+    /// The symbol and number of decimals of the chain's token, as overridden by the network,
+    /// unless neither reports the decimals.
+    pub fn token(&self) -> Option<(&str, u8)> {
+        let properties = &self.properties;
+        let network = self.network.as_ref();
+        let token_symbol = network
+            .and_then(|network| network.token_symbol.as_deref())
+            .or(properties.token_symbol.as_deref())
+            .unwrap_or_default();
+        let token_decimals = network
+            .and_then(|network| network.token_decimals)
+            .or(properties.token_decimals)?;
+        Some((token_symbol, token_decimals))
+    }
+
+    /// Returns the balance in the smallest unit of the chain's token.
+    pub fn denominate(&self, balance: &BalanceVariant) -> Result<u128> {
+        match self.token() {
+            Some((token_symbol, token_decimals)) => {
+                balance.denominate(token_symbol, token_decimals)
+            }
+            None => match balance {
+                BalanceVariant::Default(balance) => Ok(*balance),
+                BalanceVariant::Denominated { amount, unit } => anyhow::bail!(
+                    "The node reports no token decimals to denominate {}{} in, configure the \
+                     `token_decimals` of the network or specify the balance in the smallest unit",
+                    amount,
+                    unit
+                ),
+            },
+        }
+    }
+
+    /// Fetches the signed extensions listed in the runtime metadata, which the client does not
+    /// retain.
+    async fn signed_extensions(&self) -> Result<Vec<SignedExtension>> {
+        extension::from_metadata(&self.runtime_metadata().await?)
+    }
+
+    /// Fetches the runtime metadata including the parts the client does not retain, e.g. the
+    /// documentation.
+    pub async fn runtime_metadata(&self) -> Result<RuntimeMetadataPrefixed> {
+        let bytes: Bytes = self.request("state_getMetadata", Params::None).await?;
+        RuntimeMetadataPrefixed::decode(&mut &bytes[..])
+            .context("Failed to decode runtime metadata")
+    }
+
+    /// Fetches the nonce of the signer, the block a mortal era starts at and the signed
+    /// extensions concurrently.
+    async fn signing_state(
+        &self,
+        signer: &ExtrinsicSigner,
+        extras: &SignedExtras,
+    ) -> Result<SigningState> {
+        let nonce = async {
+            match signer.nonce() {
+                Some(nonce) => Ok(nonce),
+                None => Ok(self.client.account(signer.account_id(), None).await?.nonce),
+            }
+        };
+        let era = async {
+            match extras.mortality {
+                Mortality::Immortal => Ok((Era::Immortal, *self.client.genesis())),
+                Mortality::Mortal(period) => {
+                    // The era starts at the latest finalized block, so it can not be retracted.
+                    let hash = self.client.finalized_head().await?;
+                    let header = self
+                        .client
+                        .header(Some(hash))
+                        .await?
+                        .ok_or_else(|| anyhow::anyhow!("Failed to find block {:?}", hash))?;
+                    Ok((Era::mortal(period, header.number.into()), hash))
+                }
+            }
+        };
+        let (nonce, (era, era_block_hash), extensions) = future::try_join3(
+            nonce.instrument(tracing::info_span!("nonce")),
+            era.instrument(tracing::info_span!("era")),
+            self.signed_extensions()
+                .instrument(tracing::info_span!("signed extensions")),
+        )
+        .await?;
+        Ok(SigningState {
+            signer: signer.account_id().clone(),
+            mortality: extras.mortality,
+            nonce,
+            era,
+            era_block_hash,
+            extensions,
+        })
+    }
+
+    /// Runs the query, e.g. the dry run estimating the gas, while fetching what signing the next
+    /// extrinsic of the signer requires, saving the round trips when submitting it.
+    pub async fn prefetching<T, F>(
+        &self,
+        signer: &ExtrinsicSigner,
+        extrinsic_opts: &ExtrinsicOpts,
+        query: F,
+    ) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let extras = extrinsic_opts.signed_extras();
+        let (result, state) = future::join(
+            query.instrument(tracing::info_span!("query")),
+            self.signing_state(signer, &extras)
+                .instrument(tracing::info_span!("signing state")),
+        )
+        .await;
+        match state {
+            Ok(state) => *self.prefetched.lock().expect("not poisoned") = Some(state),
+            // Fetched again when signing, reporting the error there.
+            Err(err) => log::debug!("Failed to prefetch the signing state: {:?}", err),
+        }
+        result
+    }
+
+    /// Signs the encoded call, fetching the nonce of the signer and the block a mortal era starts
+    /// at from the node unless they were prefetched.
+    pub async fn sign(
+        &self,
+        call: Encoded,
+        signer: &ExtrinsicSigner,
+        extras: &SignedExtras,
+    ) -> Result<Extrinsic> {
+        let prefetched = self.prefetched.lock().expect("not poisoned").take();
+        let state = match prefetched.filter(|state| {
+            state.signer == *signer.account_id() && state.mortality == extras.mortality
+        }) {
+            Some(state) => state,
+            None => self.signing_state(signer, extras).await?,
+        };
+        let context = SigningContext {
+            spec_version: self.runtime_version.spec_version,
+            tx_version: self.runtime_version.transaction_version,
+            genesis_hash: *self.client.genesis(),
+            nonce: state.nonce,
+            era: state.era,
+            era_block_hash: state.era_block_hash,
+            tip: self.denominate(&extras.tip)?,
+            fee_asset: extras.fee_asset,
+            extensions: state.extensions,
+        };
+        sign::sign(call, signer, &context).await
+    }
+
+    /// Signs the call with the signer and submits it as configured by the extrinsic options, then
+    /// waits until the extrinsic has reached the requested status.
+    ///
+    /// If the extrinsic is included in a block, the events it emitted are returned.
+    pub async fn submit<C>(
+        &mut self,
+        call: C,
+        signer: &ExtrinsicSigner,
+        extrinsic_opts: &ExtrinsicOpts,
+    ) -> Result<Submitted>
+    where
+        C: Call<DefaultNodeRuntime>,
+    {
+        self.submit_transferring(call, signer, extrinsic_opts, 0)
+            .await
+    }
+
+    /// Signs and submits the call transferring `value` from the origin, like `submit`.
+    ///
+    /// Unless `--yes` is supplied, transferring value on a chain other than a development chain
+    /// requires confirming a summary of the extrinsic first.
+    pub async fn submit_transferring<C>(
+        &mut self,
+        call: C,
+        signer: &ExtrinsicSigner,
+        extrinsic_opts: &ExtrinsicOpts,
+        value: u128,
+    ) -> Result<Submitted>
+    where
+        C: Call<DefaultNodeRuntime>,
+    {
+        self.submit_reserving(call, signer, extrinsic_opts, value, 0)
+            .await
+    }
+
+    /// Signs and submits the call transferring `value` from the origin and reserving an
+    /// estimated `deposit` from it, like `submit_transferring`, which also requires a
+    /// confirmation if the deposit is not zero.
+    pub async fn submit_reserving<C>(
+        &mut self,
+        call: C,
+        signer: &ExtrinsicSigner,
+        extrinsic_opts: &ExtrinsicOpts,
+        value: u128,
+        deposit: u128,
+    ) -> Result<Submitted>
+    where
+        C: Call<DefaultNodeRuntime>,
+    {
+        extrinsic_opts.check_address_formats();
+        let multisig = &extrinsic_opts.multisig;
+        if multisig.call_file().is_some()
+            && multisig.is_first_approval()
+            && extrinsic_opts.submit.wait_for == WaitFor::Broadcast
+        {
+            anyhow::bail!(
+                "Exporting the multisig call requires the timepoint of the first approval, await \
+                 the inclusion of the extrinsic"
+            )
+        }
+        if extrinsic_opts.sudo {
+            let origin = extrinsic_opts.origin(signer.account_id())?;
+            match self.fetch(&SudoKeyStore).await? {
+                Some(key) if key == origin => {}
+                Some(key) => anyhow::bail!(
+                    "--sudo requires the call to be dispatched by the sudo key {}, not {}",
+                    key,
+                    origin
+                ),
+                None => anyhow::bail!("The chain has no sudo key"),
+            }
+        }
+        let call = self.client.encode(call)?;
+        let wrapped = WrappedCall::new(self.metadata(), call, extrinsic_opts, signer.account_id())?;
+
+        let extrinsic = self
+            .sign(
+                wrapped.call.clone(),
+                signer,
+                &extrinsic_opts.signed_extras(),
+            )
+            .await?
+            .encode();
+        if value > 0 || deposit > 0 {
+            self.confirm(
+                extrinsic_opts,
+                signer.account_id(),
+                value,
+                deposit,
+                &extrinsic,
+            )
+            .await?;
+        }
+        let mut submitted = self
+            .submit_encoded(extrinsic, extrinsic_opts.submit.wait_for, |metadata| {
+                let mut decoder = EventsDecoder::new(metadata.clone());
+                C::events_decoder(&mut decoder);
+                wrapped.events_decoder(&mut decoder);
+                decoder
+            })
+            .await?;
+        let included_at = submitted.included.as_ref().map(|i| i.timepoint);
+        submitted.multisig = wrapped.multisig_details(included_at);
+        if let Some(path) = extrinsic_opts.multisig.call_file() {
+            let call_file = wrapped.multisig_call_file(signer.account_id(), included_at);
+            match call_file {
+                Some(call_file) => {
+                    call_file.save(path)?;
+                    log::info!("Exported the multisig call to {}", path.display());
+                }
+                None => {
+                    log::warn!("The timepoint of the multisig call is unknown, not exporting it")
+                }
+            }
+        }
+        Ok(submitted)
+    }
+
+    /// Asks for confirmation of the signed extrinsic transferring `value` and reserving the
+    /// `deposit`, unless an extrinsic was confirmed before, `--yes` is supplied or the chain is a
+    /// development chain.
+    async fn confirm(
+        &mut self,
+        extrinsic_opts: &ExtrinsicOpts,
+        signer: &AccountId32,
+        value: u128,
+        deposit: u128,
+        extrinsic: &[u8],
+    ) -> Result<()> {
+        if self.confirmed || extrinsic_opts.yes {
+            return Ok(());
+        }
+        let chain: String = self.request("system_chain", Params::None).await?;
+        if confirm::is_dev_chain(&chain) {
+            return Ok(());
+        }
+        let fee = match self.estimate_fee(extrinsic).await {
+            Ok(fee) => self.display_balance(fee),
+            Err(err) => {
+                log::warn!("Failed to estimate the fee: {:?}", err);
+                "unknown".to_string()
+            }
+        };
+        let network = match self.network {
+            Some(ref network) => network.name.clone(),
+            None => self.urls[self.endpoint].to_string(),
+        };
+        let origin = extrinsic_opts.origin(signer)?;
+        confirm::confirm(&Summary {
+            network: format!("{} ({})", network, chain),
+            signer: signer.clone(),
+            origin: Some(origin).filter(|origin| origin != signer),
+            value: self.display_balance(value),
+            deposit: Some(deposit)
+                .filter(|deposit| *deposit > 0)
+                .map(|deposit| self.display_balance(deposit)),
+            fee,
+        })?;
+        self.confirmed = true;
+        Ok(())
+    }
+
+    /// Estimates the fee of the signed extrinsic, as charged by the transaction payment pallet.
+    async fn estimate_fee(&self, extrinsic: &[u8]) -> Result<u128> {
+        let params = Params::Array(vec![to_value(Bytes(extrinsic.to_vec()))?]);
+        let info: serde_json::Value = self.request("payment_queryInfo", params).await?;
+        // Serialized as a number by older nodes, and as a string by newer ones.
+        let fee = &info["partialFee"];
+        fee.as_u64()
+            .map(u128::from)
+            .or_else(|| fee.as_str()?.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("Unexpected fee in {}", info))
+    }
+
+    /// How balances are printed: as amounts of the chain's token, unless `--raw`.
+    pub fn balance_format(&self) -> BalanceFormat {
+        match self.token() {
+            Some((token_symbol, token_decimals)) if !self.raw => {
+                BalanceFormat::new(token_symbol, token_decimals)
+            }
+            _ => BalanceFormat::default(),
+        }
+    }
+
+    /// Formats the balance in the smallest unit as an amount of the chain's token.
+    pub fn display_balance(&self, balance: u128) -> String {
+        self.balance_format().display(balance)
+    }
+
+    /// Adds the name and documentation of the module error the extrinsic failed with, if any.
+    async fn explain(&self, err: anyhow::Error) -> anyhow::Error {
+        let module_error = match ModuleError::from_error(&err) {
+            Some(module_error) => module_error,
+            None => return err,
+        };
+        let documentation = match self.runtime_metadata().await {
+            Ok(metadata) => module_error.documentation(&metadata),
+            Err(err) => {
+                log::warn!("Failed to fetch the documentation of the error: {:?}", err);
+                None
+            }
+        };
+        let message = match documentation {
+            Some(documentation) => {
+                format!(
+                    "The extrinsic failed with {}: {}",
+                    module_error, documentation
+                )
+            }
+            None => format!("The extrinsic failed with {}", module_error),
+        };
+        let message = match module_error.hint() {
+            Some(hint) => format!("{}\nHint: {}", message, hint),
+            None => message,
+        };
+        err.context(message)
+    }
+
+    /// Submits an already signed and SCALE encoded extrinsic, then waits until it has reached
+    /// the requested status.
+    ///
+    /// The events emitted by the extrinsic are decoded with decoders created by `decoder`.
+    ///
+    /// If the connection is lost while waiting, the node is reconnected to and the extrinsic is
+    /// submitted again. Should the node reject it as already known, the blocks are searched for
+    /// the extrinsic instead, so it is never included twice.
+    pub async fn submit_encoded<D>(
+        &mut self,
+        extrinsic: Vec<u8>,
+        wait_for: WaitFor,
+        decoder: D,
+    ) -> Result<Submitted>
+    where
+        D: Fn(&Metadata) -> EventsDecoder<DefaultNodeRuntime>,
+    {
+        let ext_hash = <DefaultNodeRuntime as System>::Hashing::hash(&extrinsic);
+        let submit = self
+            .submit_and_watch(ext_hash, extrinsic, wait_for, decoder)
+            .instrument(tracing::info_span!("submit", extrinsic = ?ext_hash));
+        let mut submitted = match submit.await {
+            Ok(submitted) => submitted,
+            Err(err) => return Err(self.explain(err).await),
+        };
+        let block = submitted.events().map(|result| result.block);
+        submitted.explorer = self
+            .network
+            .as_ref()
+            .and_then(|network| network.extrinsic_link(&submitted.extrinsic, block.as_ref()));
+        submitted.balance_format = self.balance_format();
+        Ok(submitted)
+    }
+
+    /// Returns the url of the account in the block explorer of the network, if it defines one.
+    pub fn account_link(&self, account: &AccountId32) -> Option<String> {
+        self.network.as_ref()?.account_link(&account.to_string())
+    }
+
+    async fn submit_and_watch<D>(
+        &mut self,
+        ext_hash: H256,
+        extrinsic: Vec<u8>,
+        wait_for: WaitFor,
+        decoder: D,
+    ) -> Result<Submitted>
+    where
+        D: Fn(&Metadata) -> EventsDecoder<DefaultNodeRuntime>,
+    {
+        log::info!("Submitting extrinsic {:?}", ext_hash);
+
+        if wait_for == WaitFor::Broadcast {
+            let params = Params::Array(vec![to_value(Bytes(extrinsic))?]);
+            let hash = self.rpc.request("author_submitExtrinsic", params).await?;
+            self.progress
+                .report(Progress::Broadcast { extrinsic: hash });
+            return Ok(Submitted {
+                extrinsic: hash,
+                included: None,
+                multisig: None,
+                explorer: None,
+                balance_format: BalanceFormat::default(),
+            });
+        }
+
+        // The block number the search for the extrinsic starts at after a lost connection.
+        let submitted_at = self.best_number().await?;
+        // Subscribe to the events before submitting, so the change set of the block the
+        // extrinsic is included in can not be missed.
+        let mut events = Some(self.client.subscribe_events().await?);
+        let mut status_sub = self.watch(&extrinsic).await?;
+        self.progress.report(Progress::Broadcast {
+            extrinsic: ext_hash,
+        });
+
+        let mut included: Option<Included> = None;
+        loop {
+            let status =
+                match async_std::future::timeout(HEALTH_CHECK_INTERVAL, status_sub.next()).await {
+                    Ok(status) => status,
+                    Err(_) if self.is_connected().await => continue,
+                    Err(_) => {
+                        self.reconnect().await?;
+                        if included.is_none() {
+                            events = Some(self.client.subscribe_events().await?);
+                        }
+                        match self.watch(&extrinsic).await {
+                            Ok(sub) => status_sub = sub,
+                            Err(err) => {
+                                log::warn!("Extrinsic {:?} not resubmitted: {:?}", ext_hash, err);
+                                // Not reading the events while searching the blocks would stall
+                                // all other RPC traffic.
+                                drop(events);
+                                let included = self
+                                    .recover(ext_hash, submitted_at, included, wait_for, &decoder)
+                                    .await?;
+                                let block = included.result.block;
+                                self.progress.report(Progress::InBlock {
+                                    extrinsic: ext_hash,
+                                    block,
+                                });
+                                if wait_for == WaitFor::Finalized {
+                                    self.progress.report(Progress::Finalized {
+                                        extrinsic: ext_hash,
+                                        block,
+                                    });
+                                }
+                                return Ok(Submitted {
+                                    extrinsic: ext_hash,
+                                    included: Some(included),
+                                    multisig: None,
+                                    explorer: None,
+                                    balance_format: BalanceFormat::default(),
+                                });
+                            }
+                        }
+                        continue;
+                    }
+                };
+            log::info!("Extrinsic {:?} status: {:?}", ext_hash, status);
+            match status {
+                TransactionStatus::Future
+                | TransactionStatus::Ready
+                | TransactionStatus::Broadcast(_) => continue,
+                TransactionStatus::InBlock(block) => {
+                    // The events subscription is consumed here: keeping it around unread
+                    // while waiting for finality would stall all other RPC traffic.
+                    let events = events
+                        .take()
+                        .ok_or_else(|| anyhow::anyhow!("No events subscription for {:?}", block))?;
+                    let result = self
+                        .extrinsic_events(events, decoder(self.metadata()), block, ext_hash)
+                        .await?;
+                    self.progress.report(Progress::InBlock {
+                        extrinsic: ext_hash,
+                        block,
+                    });
+                    if wait_for == WaitFor::InBlock {
+                        return Ok(Submitted {
+                            extrinsic: ext_hash,
+                            included: Some(result),
+                            multisig: None,
+                            explorer: None,
+                            balance_format: BalanceFormat::default(),
+                        });
+                    }
+                    included = Some(result);
+                }
+                TransactionStatus::Retracted(block) => {
+                    log::warn!("Block {:?} containing the extrinsic was retracted", block);
+                    included = None;
+                    events = Some(self.client.subscribe_events().await?);
+                }
+                TransactionStatus::Finalized(block) => {
+                    return match included {
+                        Some(result) if result.result.block == block => {
+                            self.progress.report(Progress::Finalized {
+                                extrinsic: ext_hash,
+                                block,
+                            });
+                            Ok(Submitted {
+                                extrinsic: ext_hash,
+                                included: Some(result),
+                                multisig: None,
+                                explorer: None,
+                                balance_format: BalanceFormat::default(),
+                            })
+                        }
+                        _ => Err(anyhow::anyhow!(
+                            "Extrinsic finalized in block {:?}, which it was not seen in",
+                            block
+                        )),
+                    }
+                }
+                TransactionStatus::FinalityTimeout(block) => {
+                    let error = format!("Timed out waiting for finality of block {:?}", block);
+                    return Err(RpcError::new(error).into());
+                }
+                TransactionStatus::Usurped(_) => {
+                    return Err(RpcError::new("Extrinsic usurped").into())
+                }
+                TransactionStatus::Dropped => return Err(RpcError::new("Extrinsic dropped").into()),
+                TransactionStatus::Invalid => return Err(RpcError::new("Extrinsic invalid").into()),
+            }
+        }
+    }
+
+    /// Submits the extrinsic and subscribes to its status.
+    async fn watch(&self, extrinsic: &[u8]) -> Result<Subscription<TransactionStatus<H256, H256>>> {
+        let params = Params::Array(vec![to_value(Bytes(extrinsic.to_vec()))?]);
+        let subscription = self
+            .rpc
+            .subscribe(
+                "author_submitAndWatchExtrinsic",
+                params,
+                "author_unwatchExtrinsic",
+            )
+            .await;
+        Ok(subscription.map_err(RpcError::new)?)
+    }
+
+    /// Returns false if the connection to the node was lost.
+    async fn is_connected(&self) -> bool {
+        self.rpc
+            .request::<serde_json::Value>("system_health", Params::None)
+            .await
+            .is_ok()
+    }
+
+    /// Connects to a node again after the connection was lost, failing over to the next
+    /// endpoint serving the same chain and retrying with exponential backoff.
+    async fn reconnect(&mut self) -> Result<()> {
+        let genesis_hash = *self.client.genesis();
+        let mut delay = Duration::from_secs(1);
+        for attempt in 1..=RECONNECT_ATTEMPTS {
+            log::warn!(
+                "Connection to {} lost, reconnecting in {:?} (attempt {}/{})",
+                self.urls[self.endpoint],
+                delay,
+                attempt,
+                RECONNECT_ATTEMPTS
+            );
+            async_std::task::sleep(delay).await;
+            match Self::failover(&self.urls, self.endpoint + 1, Some(genesis_hash), &self.ws).await
+            {
+                Ok(mut connection) => {
+                    log::info!("Connected to {}", connection.urls[connection.endpoint]);
+                    connection.network = self.network.take();
+                    connection.confirmed = self.confirmed;
+                    connection.raw = self.raw;
+                    connection.progress = self.progress.clone();
+                    *self = connection;
+                    return Ok(());
+                }
+                Err(err) => log::warn!("Failed to reconnect: {:?}", err),
+            }
+            delay *= 2;
+        }
+        Err(RpcError::new("Lost the connection to all nodes").into())
+    }
+
+    /// Searches the blocks from `from` onwards for the extrinsic, after it could not be watched
+    /// again on reconnecting. Returns once it has reached the requested status.
+    ///
+    /// The events of a block can only be collected while it is the best block, so if the
+    /// extrinsic was included while the connection was lost, only its block is reported.
+    async fn recover<D>(
+        &self,
+        ext_hash: H256,
+        from: u32,
+        included: Option<Included>,
+        wait_for: WaitFor,
+        decoder: &D,
+    ) -> Result<Included>
+    where
+        D: Fn(&Metadata) -> EventsDecoder<DefaultNodeRuntime>,
+    {
+        let included = match included {
+            Some(included) => included,
+            None => {
+                let mut next = from;
+                loop {
+                    let best = self.best_number().await?;
+                    if let Some((hash, number)) = self.find_extrinsic(ext_hash, next, best).await? {
+                        if number != best {
+                            anyhow::bail!(
+                                "Extrinsic {:?} was included in block {:?} while the connection \
+                                 was lost, its events could not be collected",
+                                ext_hash,
+                                hash
+                            )
+                        }
+                        let events = self.client.subscribe_events().await?;
+                        let collect =
+                            self.extrinsic_events(events, decoder(self.metadata()), hash, ext_hash);
+                        break async_std::future::timeout(RECOVER_EVENTS_TIMEOUT, collect)
+                            .await
+                            .map_err(|_| {
+                                anyhow::anyhow!(
+                                    "Extrinsic {:?} was included in block {:?}, its events could \
+                                     not be collected",
+                                    ext_hash,
+                                    hash
+                                )
+                            })??;
+                    }
+                    if best >= from + MAX_RECOVER_BLOCKS {
+                        anyhow::bail!(
+                            "Extrinsic {:?} was not included within {} blocks",
+                            ext_hash,
+                            MAX_RECOVER_BLOCKS
+                        )
+                    }
+                    next = best + 1;
+                    async_std::task::sleep(RECOVER_POLL_INTERVAL).await;
+                }
+            }
+        };
+        if wait_for == WaitFor::Finalized {
+            self.wait_for_finality(&included).await?;
+        }
+        Ok(included)
+    }
+
+    /// Waits until the block which included the extrinsic is finalized.
+    async fn wait_for_finality(&self, included: &Included) -> Result<()> {
+        loop {
+            let finalized = self.client.finalized_head().await?;
+            let header = self
+                .client
+                .header(Some(finalized))
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Failed to find block {:?}", finalized))?;
+            if header.number >= included.timepoint.height {
+                let canonical = self
+                    .client
+                    .block_hash(Some(included.timepoint.height.into()))
+                    .await?;
+                if canonical != Some(included.result.block) {
+                    anyhow::bail!(
+                        "Block {:?} containing the extrinsic was retracted",
+                        included.result.block
+                    )
+                }
+                return Ok(());
+            }
+            async_std::task::sleep(RECOVER_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Returns the number of the best block.
+    async fn best_number(&self) -> Result<u32> {
+        let header = self
+            .client
+            .header::<H256>(None)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to fetch the best block"))?;
+        Ok(header.number)
+    }
+
+    /// Searches the blocks `from..=to` for the extrinsic, returning the hash and number of the
+    /// block including it.
+    async fn find_extrinsic(
+        &self,
+        ext_hash: H256,
+        from: u32,
+        to: u32,
+    ) -> Result<Option<(H256, u32)>> {
+        for number in from..=to {
+            let hash = match self.client.block_hash(Some(number.into())).await? {
+                Some(hash) => hash,
+                None => continue,
+            };
+            if let Some(block) = self.client.block(Some(hash)).await? {
+                let found =
+                    block.block.extrinsics.iter().any(|ext| {
+                        <DefaultNodeRuntime as System>::Hashing::hash_of(ext) == ext_hash
+                    });
+                if found {
+                    return Ok(Some((hash, number)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Collects the events emitted by the extrinsic in the given block.
+    async fn extrinsic_events(
+        &self,
+        events: Subscription<StorageChangeSet<H256>>,
+        decoder: EventsDecoder<DefaultNodeRuntime>,
+        block_hash: H256,
+        ext_hash: H256,
+    ) -> Result<Included> {
+        let block = self
+            .client
+            .block(Some(block_hash))
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to find block {:?}", block_hash))?;
+        let ext_index = block
+            .block
+            .extrinsics
+            .iter()
+            .position(|ext| <DefaultNodeRuntime as System>::Hashing::hash_of(ext) == ext_hash)
+            .ok_or_else(|| anyhow::anyhow!("Failed to find extrinsic {:?}", ext_hash))?;
+
+        let mut sub = EventSubscription::new(events, decoder);
+        sub.filter_extrinsic(block_hash, ext_index);
+        let mut events = Vec::new();
+        while let Some(event) = sub.next().await {
+            events.push(event?);
+        }
+        Ok(Included {
+            result: ExtrinsicSuccess {
+                block: block_hash,
+                extrinsic: ext_hash,
+                events,
+            },
+            timepoint: Timepoint {
+                height: block.block.header.number,
+                index: ext_index as u32,
+            },
+        })
+    }
+}
+
+/// The indices of the endpoints in the order they are tried, starting at `first` and wrapping
+/// around, so that a reconnection tries the node after the lost one first.
+fn failover_order(len: usize, first: usize) -> impl Iterator<Item = usize> {
+    (0..len).map(move |offset| (first + offset) % len)
+}
+
+/// The span of an RPC request, which the records of the concurrent requests are told apart by.
+fn rpc_span(method: &str) -> tracing::Span {
+    tracing::info_span!("rpc", method)
+}
+
+/// Fails if the node at the url serves another chain than the one with the expected genesis
+/// hash, if any.
+fn check_genesis(url: &url::Url, genesis: H256, expected: Option<H256>) -> Result<()> {
+    match expected {
+        Some(expected) if genesis != expected => anyhow::bail!(
+            "{} serves a chain with genesis hash {:?} instead of {:?}",
+            url,
+            genesis,
+            expected
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Returns an events decoder for all events emitted by the contracts module and the modules it
+/// depends on.
+pub(crate) fn contracts_events_decoder(metadata: &Metadata) -> EventsDecoder<DefaultNodeRuntime> {
+    let mut decoder = EventsDecoder::new(metadata.clone());
+    decoder.with_contracts();
+    decoder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    #[test]
+    fn fails_over_to_the_next_endpoint() {
+        assert_eq!(failover_order(3, 0).collect::<Vec<_>>(), [0, 1, 2]);
+        // Reconnecting after losing the last endpoint wraps around to the first.
+        assert_eq!(failover_order(3, 2 + 1).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(failover_order(3, 1 + 1).collect::<Vec<_>>(), [2, 0, 1]);
+        assert_eq!(failover_order(0, 1).count(), 0);
+
+        let url = url::Url::parse("ws://localhost:9944").unwrap();
+        let genesis = H256::repeat_byte(1);
+        assert!(check_genesis(&url, genesis, None).is_ok());
+        assert!(check_genesis(&url, genesis, Some(genesis)).is_ok());
+        assert!(check_genesis(&url, genesis, Some(H256::repeat_byte(2))).is_err());
+    }
+
+    #[test]
+    fn parses_the_urls_in_order() {
+        let submit_opts = SubmitOpts::from_iter(&[
+            "call",
+            "--url",
+            "ws://a:9944",
+            "--url",
+            "ws://b:9944,ws://c:9944",
+        ]);
+        let urls = submit_opts.urls.iter().map(url::Url::as_str);
+        assert_eq!(
+            urls.collect::<Vec<_>>(),
+            ["ws://a:9944/", "ws://b:9944/", "ws://c:9944/"]
+        );
+        let submit_opts = SubmitOpts::from_iter(&["call"]);
+        assert_eq!(submit_opts.urls.len(), 1);
+    }
+}
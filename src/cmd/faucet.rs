@@ -0,0 +1,252 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::{Context, Result};
+use codec::Encode;
+use jsonrpsee::common::{to_value, Params};
+use serde::Serialize;
+use serde_json::Value;
+use sp_core::crypto::{AccountId32, Ss58Codec};
+use structopt::StructOpt;
+use subxt::{balances::BalancesEventsDecoder, EventsDecoder};
+
+use super::{
+    address::Address,
+    balance::{BalanceFormat, BalanceVariant},
+    confirm,
+    events::ExtrinsicDetails,
+    extrinsic::Connection,
+    sign::{self, Mortality, SignedExtras},
+    signer::{ExtrinsicSigner, Scheme},
+    transfer::TransferKeepAliveCall,
+};
+use crate::{RpcError, SubmitOpts};
+
+/// The secret key URI of the account funding others on development chains.
+const DEV_FAUCET: &str = "//Alice";
+/// The number of tokens transferred from the development account by default.
+const DEV_FAUCET_TOKENS: u128 = 1_000;
+
+/// Request funds for an account from the faucet of a test network, or transfer them from
+/// `//Alice` on a development chain
+#[derive(Debug, StructOpt)]
+#[structopt(name = "faucet")]
+pub(crate) struct FaucetCommand {
+    /// The address of the account to fund, instead of deriving it from the secret key URI
+    #[structopt(long, parse(try_from_str = super::parse_address), required_unless = "suri")]
+    account: Option<Address>,
+    /// Secret key URI to derive the account to fund from.
+    ///
+    /// Read from the `CARGO_CONTRACT_SURI` environment variable if the flag is not supplied.
+    #[structopt(
+        name = "suri",
+        long,
+        short,
+        env = "CARGO_CONTRACT_SURI",
+        hide_env_values = true
+    )]
+    suri: Option<String>,
+    /// Password for the secret key
+    #[structopt(long, short)]
+    password: Option<String>,
+    /// Signature scheme of the key pair derived from the secret key URI
+    #[structopt(
+        long,
+        default_value = "sr25519",
+        value_name = "sr25519 | ed25519 | ecdsa"
+    )]
+    scheme: Scheme,
+    /// The balance transferred from `//Alice` on a development chain, by default 1000 of the
+    /// chain's token
+    #[structopt(long)]
+    value: Option<BalanceVariant>,
+    #[structopt(flatten)]
+    pub submit_opts: SubmitOpts,
+}
+
+/// Result of a successful `faucet`.
+#[derive(Debug, Serialize)]
+pub(crate) struct FaucetResult {
+    /// The funded account.
+    pub account: AccountId32,
+    /// The url of the faucet, or the development account the funds were transferred from.
+    pub faucet: String,
+    /// The balance transferred from the development account.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<u128>,
+    /// The response of the faucet, e.g. the hash of its transfer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<Value>,
+    #[serde(flatten)]
+    pub extrinsic: Option<ExtrinsicDetails>,
+    /// How the balance is printed in the human readable output.
+    #[serde(skip)]
+    pub balance_format: BalanceFormat,
+}
+
+impl FaucetResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        match (self.value, &self.extrinsic) {
+            (Some(value), Some(extrinsic)) => {
+                let output = format!(
+                    "Transferred {} from {} to {}\n\tExtrinsic hash: {:?}",
+                    self.balance_format.display(value),
+                    self.faucet,
+                    self.account,
+                    extrinsic.extrinsic
+                );
+                extrinsic.display_details(output)
+            }
+            _ => {
+                let mut output =
+                    format!("Requested funds for {} from {}", self.account, self.faucet);
+                if let Some(ref response) = self.response {
+                    output.push_str(&format!("\n\tResponse: {}", response));
+                }
+                output
+            }
+        }
+    }
+}
+
+impl FaucetCommand {
+    /// Calls the faucet of the network for the account, or transfers to it from `//Alice` on a
+    /// development chain.
+    pub fn exec(&self) -> Result<String> {
+        let account = match (&self.account, &self.suri) {
+            (Some(address), _) => {
+                address.check_format();
+                address.account.clone()
+            }
+            (None, Some(suri)) => {
+                ExtrinsicSigner::from_suri(self.scheme, suri, self.password.as_deref())?
+                    .account_id()
+                    .clone()
+            }
+            (None, None) => anyhow::bail!("Either --account or --suri is required"),
+        };
+        let network = self.submit_opts.network_profile.as_ref();
+        let result = match network.and_then(|network| network.faucet.as_ref()) {
+            Some(url) => request_funds(url, account)?,
+            None => async_std::task::block_on(self.transfer_from_dev_account(account))?,
+        };
+        self.submit_opts
+            .output
+            .format(&result, |result| result.display())
+    }
+
+    /// Transfers the `--value` from `//Alice` to the account, unless the chain is not a
+    /// development chain.
+    async fn transfer_from_dev_account(&self, account: AccountId32) -> Result<FaucetResult> {
+        let mut connection = Connection::new(&self.submit_opts).await?;
+        let chain: String = connection.request("system_chain", Params::None).await?;
+        if !confirm::is_dev_chain(&chain) {
+            let address = account.to_ss58check();
+            let network = self.submit_opts.network_profile.as_ref();
+            if let Some(instructions) =
+                network.and_then(|network| network.faucet_instructions(&address))
+            {
+                anyhow::bail!("{}", instructions)
+            }
+            anyhow::bail!(
+                "{} is not a development chain, and no faucet is configured for its network, \
+                 set the `faucet` of the network in .cargo-contract.toml",
+                chain
+            )
+        }
+        let value = match self.value {
+            Some(ref value) => connection.denominate(value)?,
+            None => {
+                let decimals = connection.token().map(|(_, decimals)| decimals).unwrap_or(12);
+                DEV_FAUCET_TOKENS * 10u128.pow(decimals.into())
+            }
+        };
+        let signer = ExtrinsicSigner::from_suri(Scheme::Sr25519, DEV_FAUCET, None)?;
+        let call = TransferKeepAliveCall {
+            dest: &account.clone().into(),
+            value,
+        };
+        let call = sign::encode_call(connection.metadata(), call)?;
+        let extras = SignedExtras {
+            mortality: Mortality::Mortal(64),
+            tip: BalanceVariant::default(),
+            fee_asset: None,
+        };
+        let extrinsic = connection.sign(call, &signer, &extras).await?.encode();
+        let submitted = connection
+            .submit_encoded(extrinsic, self.submit_opts.wait_for, |metadata| {
+                let mut decoder = EventsDecoder::new(metadata.clone());
+                decoder.with_balances();
+                decoder
+            })
+            .await?;
+        Ok(FaucetResult {
+            account,
+            faucet: DEV_FAUCET.to_string(),
+            value: Some(value),
+            response: None,
+            extrinsic: Some(ExtrinsicDetails::new(&submitted)?),
+            balance_format: connection.balance_format(),
+        })
+    }
+}
+
+/// Calls the `faucet_drip` method of the faucet's JSON-RPC service with the SS58 address of the
+/// account.
+fn request_funds(url: &url::Url, account: AccountId32) -> Result<FaucetResult> {
+    let client = jsonrpsee::http_client(url.as_str());
+    let params = Params::Array(vec![to_value(account.to_ss58check())?]);
+    let response: Value = async_std::task::block_on(client.request("faucet_drip", params))
+        .map_err(RpcError::new)
+        .context(format!("The faucet at {} failed to send funds", url))?;
+    Ok(FaucetResult {
+        account,
+        faucet: url.to_string(),
+        value: None,
+        response: Some(response).filter(|response| !response.is_null()),
+        extrinsic: None,
+        balance_format: BalanceFormat::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn displays_the_source_of_the_funds() {
+        let account = AccountId32::from([1; 32]);
+        let result = FaucetResult {
+            account: account.clone(),
+            faucet: "https://faucet.example.com/".to_string(),
+            value: None,
+            response: Some(Value::from("0x01")),
+            extrinsic: None,
+            balance_format: BalanceFormat::default(),
+        };
+        assert_eq!(
+            result.display(),
+            format!(
+                "Requested funds for {} from https://faucet.example.com/\n\tResponse: \"0x01\"",
+                account
+            )
+        );
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["faucet"], "https://faucet.example.com/");
+        assert!(json.get("value").is_none());
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::rc::Rc;
+
+use anyhow::{Context, Result};
+use jsonrpsee::common::{to_value, Params};
+use serde_json::Value;
+use sp_core::{crypto::AccountId32, Bytes, H256};
+use structopt::StructOpt;
+
+use super::{
+    block::BlockRef,
+    code::fetch_code,
+    extrinsic::Connection,
+    info::{ContractInfo, ContractInfoOfStore},
+    sandbox::{Remote, Sandbox},
+    storage::CHILD_STORAGE_KEY_PREFIX,
+    ContractRef,
+};
+use crate::SubmitOpts;
+
+/// The storage of a contract on the chain at a fixed block.
+struct ChildTrie {
+    connection: Connection,
+    /// The key of the contract's child trie.
+    child_key: Vec<u8>,
+    at: H256,
+}
+
+impl Remote for ChildTrie {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        // The contracts pallet stores the values under the hash of the keys the contract uses.
+        let trie_key = Bytes(sp_core::blake2_256(key).to_vec());
+        let params = Params::Array(vec![
+            to_value(Bytes(self.child_key.clone()))?,
+            to_value(trie_key)?,
+            to_value(self.at)?,
+        ]);
+        let value: Option<Bytes> =
+            async_std::task::block_on(self.connection.request("childstate_getStorage", params))?;
+        log::debug!("Fetched the storage key 0x{} of the fork", hex::encode(key));
+        Ok(value.map(|value| value.0))
+    }
+}
+
+/// Forks the contract on the chain of the node at `url`, as of the block `at` or the best block.
+///
+/// The sandbox executes the contract's code, or the `wasm` replacing it, and reads its storage
+/// from the chain the first time a key is accessed.
+pub(crate) fn fork(
+    url: &url::Url,
+    contract: &ContractRef,
+    at: Option<&BlockRef>,
+    wasm: Option<Vec<u8>>,
+    max_instructions: u64,
+) -> Result<Sandbox> {
+    let submit_opts = SubmitOpts::from_iter(&["fork", "--url", url.as_str()]);
+    let address = contract.resolve(&submit_opts)?;
+    async_std::task::block_on(async move {
+        let connection = Connection::new(&submit_opts).await?;
+        address.check_format();
+        let at = match at {
+            Some(at) => at.hash(&connection).await?,
+            None => best_block(&connection).await?,
+        };
+        fork_at(connection, &address.account, at, wasm, max_instructions).await
+    })
+}
+
+/// The hash of the best block of the node.
+pub(crate) async fn best_block(connection: &Connection) -> Result<H256> {
+    connection
+        .request::<Option<H256>>("chain_getBlockHash", Params::None)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("The node returned no best block"))
+}
+
+/// Forks the contract `account_id` as of the block `at`, reading its storage through the
+/// `connection`.
+pub(crate) async fn fork_at(
+    connection: Connection,
+    account_id: &AccountId32,
+    at: H256,
+    wasm: Option<Vec<u8>>,
+    max_instructions: u64,
+) -> Result<Sandbox> {
+    let header: Value = connection
+        .request("chain_getHeader", Params::Array(vec![to_value(at)?]))
+        .await?;
+    let block_number = header["number"]
+        .as_str()
+        .and_then(|number| u32::from_str_radix(number.trim_start_matches("0x"), 16).ok())
+        .context(format!("No header of the block {:?}", at))?;
+
+    let info = match connection
+        .fetch_at(&ContractInfoOfStore { account_id }, Some(at))
+        .await?
+    {
+        Some(ContractInfo::Alive(info)) => info,
+        Some(ContractInfo::Tombstone) => {
+            anyhow::bail!(
+                "The contract at {} was evicted with its storage",
+                account_id
+            )
+        }
+        None => anyhow::bail!("No contract found at {} in block {:?}", account_id, at),
+    };
+    let wasm = match wasm {
+        Some(wasm) => wasm,
+        None => fetch_code(&connection, &info.code_hash).await?,
+    };
+    let balance = connection.account_data(account_id, Some(at)).await?.free;
+    log::info!(
+        "Forked the contract {} with {} storage items at block {} ({:?})",
+        account_id,
+        info.pair_count,
+        block_number,
+        at
+    );
+    let trie = ChildTrie {
+        connection,
+        child_key: [CHILD_STORAGE_KEY_PREFIX, &info.trie_id].concat(),
+        at,
+    };
+    Ok(Sandbox::fork(
+        wasm,
+        account_id.clone(),
+        balance,
+        block_number,
+        Rc::new(trie),
+        max_instructions,
+    ))
+}
@@ -0,0 +1,597 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sp_core::crypto::{AccountId32, Ss58Codec};
+use structopt::StructOpt;
+
+use super::{
+    sandbox::{Execution, Sandbox, SandboxOpts},
+    transcode::{type_id, InkMetadata, Message, TypeRegistry},
+    BalanceVariant,
+};
+use crate::OutputType;
+
+/// The depth of nested types up to which values are generated.
+const MAX_DEPTH: usize = 16;
+
+/// Call a message of the contract with random arguments in the sandbox, reporting panics, traps
+/// and violated invariants
+#[derive(Debug, StructOpt)]
+#[structopt(name = "fuzz")]
+pub(crate) struct FuzzCommand {
+    /// The label of the message to call
+    #[structopt(long)]
+    message: String,
+    /// The number of calls, each on the state of the freshly deployed contract
+    #[structopt(long, default_value = "1000")]
+    runs: u64,
+    /// The seed of the first call, by default taken from the clock. Each following call uses the
+    /// next seed, so a failure is reproduced with its seed and `--runs 1`
+    #[structopt(long)]
+    seed: Option<u64>,
+    /// A message without arguments, e.g. `total_supply`, whose return value must not be changed
+    /// by a successful call
+    #[structopt(long)]
+    invariant: Vec<String>,
+    /// The balance transferred to the contract with each call, in the smallest unit
+    #[structopt(long, default_value = "0")]
+    value: BalanceVariant,
+    #[structopt(flatten)]
+    sandbox_opts: SandboxOpts,
+    /// Export the result in JSON format
+    #[structopt(
+        long,
+        default_value = "human-readable",
+        value_name = "human-readable | json | json-pjs"
+    )]
+    output: OutputType,
+}
+
+/// The pseudo random numbers of a seed, generated with SplitMix64 so that they stay the same
+/// across platforms and releases.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
+
+/// Generates random values of the types of the metadata, in the JSON the arguments are encoded
+/// from.
+struct Generator<'a> {
+    types: TypeRegistry<'a>,
+    /// The accounts generated for `AccountId`s, so that the caller and a few others recur.
+    accounts: &'a [AccountId32],
+    random: Random,
+}
+
+impl Generator<'_> {
+    fn value(&mut self, ty: u64, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            anyhow::bail!("The type {} nests too deeply to generate values", ty)
+        }
+        if self.types.name(ty) == Some("AccountId") {
+            let account = &self.accounts[self.random.below(self.accounts.len() as u64) as usize];
+            return Ok(Value::from(account.to_ss58check()));
+        }
+        let def = self.types.definition(ty)?;
+        if let Some(primitive) = def.get("primitive").and_then(Value::as_str) {
+            return self.primitive(primitive);
+        }
+        if let Some(composite) = def.get("composite") {
+            return self.fields(&composite["fields"], depth);
+        }
+        if let Some(variant) = def.get("variant") {
+            let variants = variant["variants"].as_array().map(Vec::as_slice);
+            let variants = variants.unwrap_or_default();
+            if variants.is_empty() {
+                anyhow::bail!("The type {} has no variants to generate", ty)
+            }
+            let variant = &variants[self.random.below(variants.len() as u64) as usize];
+            let name = variant["name"].as_str().unwrap_or_default().to_string();
+            return match variant.get("fields") {
+                Some(Value::Array(fields)) if !fields.is_empty() => {
+                    let mut object = Map::new();
+                    object.insert(name, self.fields(&variant["fields"], depth)?);
+                    Ok(Value::Object(object))
+                }
+                _ => Ok(Value::String(name)),
+            };
+        }
+        if let Some(sequence) = def.get("sequence") {
+            let len = self.random.below(5);
+            return self.elements(type_id(sequence)?, len, depth);
+        }
+        if let Some(array) = def.get("array") {
+            let len = array["len"]
+                .as_u64()
+                .ok_or_else(|| anyhow::anyhow!("Array without length"))?;
+            return self.elements(type_id(array)?, len, depth);
+        }
+        if let Some(tuple) = def.get("tuple").and_then(Value::as_array) {
+            if tuple.is_empty() {
+                return Ok(Value::Null);
+            }
+            let values = tuple
+                .iter()
+                .map(|ty| {
+                    let ty = ty.as_u64().ok_or_else(|| anyhow::anyhow!("Invalid type"))?;
+                    self.value(ty, depth + 1)
+                })
+                .collect::<Result<_>>()?;
+            return Ok(Value::Array(values));
+        }
+        if let Some(compact) = def.get("compact") {
+            return self.value(type_id(compact)?, depth + 1);
+        }
+        anyhow::bail!("Unsupported type definition {}", def)
+    }
+
+    /// Generates the fields of a composite or variant, as an object for named fields and the
+    /// value itself for a single unnamed one.
+    fn fields(&mut self, fields: &Value, depth: usize) -> Result<Value> {
+        let fields = fields.as_array().map(Vec::as_slice).unwrap_or_default();
+        let mut values = Vec::new();
+        for field in fields {
+            values.push(self.value(type_id(field)?, depth + 1)?);
+        }
+        if !fields.is_empty() && fields.iter().all(|field| field["name"].is_string()) {
+            let names = fields
+                .iter()
+                .map(|field| field["name"].as_str().unwrap_or_default().to_string());
+            Ok(Value::Object(names.zip(values).collect()))
+        } else if values.len() == 1 {
+            Ok(values.remove(0))
+        } else {
+            Ok(Value::Array(values))
+        }
+    }
+
+    fn elements(&mut self, ty: u64, len: u64, depth: usize) -> Result<Value> {
+        let elements = (0..len)
+            .map(|_| self.value(ty, depth + 1))
+            .collect::<Result<_>>()?;
+        Ok(Value::Array(elements))
+    }
+
+    fn primitive(&mut self, primitive: &str) -> Result<Value> {
+        let (bits, signed) = match primitive {
+            "bool" => return Ok(Value::from(self.random.below(2) == 1)),
+            "char" | "str" => {
+                let len = if primitive == "char" {
+                    1
+                } else {
+                    self.random.below(9)
+                };
+                let string = (0..len)
+                    .map(|_| (b'a' + self.random.below(26) as u8) as char)
+                    .collect::<String>();
+                return Ok(Value::from(string));
+            }
+            "u8" => (8, false),
+            "u16" => (16, false),
+            "u32" => (32, false),
+            "u64" => (64, false),
+            "u128" => (128, false),
+            "i8" => (8, true),
+            "i16" => (16, true),
+            "i32" => (32, true),
+            "i64" => (64, true),
+            "i128" => (128, true),
+            _ => anyhow::bail!("Unsupported primitive {}", primitive),
+        };
+        // The edge cases are generated as often as arbitrary integers.
+        let raw = match self.random.below(8) {
+            0 => 0,
+            1 => 1,
+            2 => u128::MAX,
+            3 => 1 << (bits - 1),
+            4 => (1 << (bits - 1)) - 1,
+            5 => u128::from(self.random.below(100)),
+            _ => u128::from(self.random.next()) << 64 | u128::from(self.random.next()),
+        };
+        let shift = 128 - bits;
+        let raw = raw << shift >> shift;
+        Ok(match (signed, bits) {
+            (false, 128) => Value::from(raw.to_string()),
+            (false, _) => Value::from(raw as u64),
+            (true, _) => {
+                let value = (raw << shift) as i128 >> shift;
+                match bits {
+                    128 => Value::from(value.to_string()),
+                    _ => Value::from(value as i64),
+                }
+            }
+        })
+    }
+}
+
+/// Generates the random arguments of the constructor or message from the seed, in the JSON they
+/// are encoded from, with `AccountId`s taken from the `accounts`.
+pub(crate) fn generate_args(
+    metadata: &InkMetadata,
+    function: &Message,
+    accounts: &[AccountId32],
+    seed: u64,
+) -> Result<Vec<Value>> {
+    let mut generator = Generator {
+        types: metadata.types(),
+        accounts,
+        random: Random(seed),
+    };
+    function
+        .args
+        .iter()
+        .map(|ty| generator.value(*ty, 0))
+        .collect()
+}
+
+/// What went wrong in a call.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FailureKind {
+    /// The contract panicked, trapping with `unreachable`.
+    Panic,
+    /// The contract trapped otherwise, e.g. accessing memory out of bounds or running out of
+    /// instructions.
+    Trap,
+    /// A successful call changed the return value of an invariant.
+    Invariant,
+}
+
+/// A distinct failure, with the first call causing it.
+#[derive(Debug, Serialize)]
+pub(crate) struct Failure {
+    pub kind: FailureKind,
+    pub reason: String,
+    /// The seed of the first call failing for the reason.
+    pub seed: u64,
+    pub args: Vec<Value>,
+    /// The number of calls failing for the reason.
+    pub calls: u64,
+}
+
+/// The outcome of a call with random arguments.
+enum Outcome {
+    Succeeded,
+    Reverted,
+    Failed(FailureKind, String, Vec<Value>),
+}
+
+/// Calls the message of a deployed contract with random arguments.
+struct Fuzzer<'a> {
+    metadata: &'a InkMetadata,
+    message: Message,
+    /// The invariants with their return value on the deployed contract.
+    invariants: Vec<(Message, Vec<u8>)>,
+    caller: AccountId32,
+    value: u128,
+    accounts: Vec<AccountId32>,
+}
+
+impl Fuzzer<'_> {
+    /// Calls the message with the arguments generated from the seed, on a copy of the deployed
+    /// contract.
+    fn run(&self, deployed: &Sandbox, seed: u64) -> Result<Outcome> {
+        let args = generate_args(self.metadata, &self.message, &self.accounts, seed)?;
+        let encoded = args.iter().map(Value::to_string).collect::<Vec<_>>();
+        let input = self.metadata.encode_call(&self.message, &encoded)?;
+
+        let mut sandbox = deployed.clone();
+        let execution = sandbox.call(&self.caller, self.value, input)?;
+        if let Some(ref trap) = execution.trap {
+            let (kind, reason) = classify(trap, &execution);
+            return Ok(Outcome::Failed(kind, reason, args));
+        }
+        if execution.reverted {
+            return Ok(Outcome::Reverted);
+        }
+        for (invariant, before) in &self.invariants {
+            let after = call(&mut sandbox, invariant, &self.caller)?;
+            if after != *before {
+                let reason = format!(
+                    "`{}` changed from {} to {}",
+                    invariant.label,
+                    self.display(invariant, before),
+                    self.display(invariant, &after)
+                );
+                return Ok(Outcome::Failed(FailureKind::Invariant, reason, args));
+            }
+        }
+        Ok(Outcome::Succeeded)
+    }
+
+    /// The decoded return value of the invariant, or the hex encoded data.
+    fn display(&self, invariant: &Message, data: &[u8]) -> String {
+        let decoded = invariant
+            .returns
+            .and_then(|ty| self.metadata.types().decode_all(ty, data).ok());
+        match decoded {
+            Some(value) => value.to_string(),
+            None => format!("0x{}", hex::encode(data)),
+        }
+    }
+}
+
+/// The reason of the trap, the panic message if the contract printed one.
+fn classify(trap: &str, execution: &Execution) -> (FailureKind, String) {
+    if !trap.contains("unreachable") {
+        return (FailureKind::Trap, trap.to_string());
+    }
+    let message = execution
+        .debug_messages
+        .iter()
+        .rev()
+        .find(|message| message.contains("panicked"));
+    let reason = message.map_or(trap, String::as_str);
+    (FailureKind::Panic, reason.trim().to_string())
+}
+
+/// Calls the invariant, returning its data.
+fn call(sandbox: &mut Sandbox, invariant: &Message, caller: &AccountId32) -> Result<Vec<u8>> {
+    let execution = sandbox.call(caller, 0, invariant.selector.clone())?;
+    match execution.trap {
+        Some(trap) => anyhow::bail!("The invariant `{}` trapped: {}", invariant.label, trap),
+        None => Ok(execution.data),
+    }
+}
+
+/// Result of a `fuzz`.
+#[derive(Debug, Serialize)]
+pub(crate) struct FuzzResult {
+    pub message: String,
+    /// The seed of the first call.
+    pub seed: u64,
+    pub runs: u64,
+    pub succeeded: u64,
+    pub reverted: u64,
+    pub failures: Vec<Failure>,
+}
+
+impl FuzzResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let mut output = format!(
+            "Called `{}` {} times from seed {}: {} succeeded, {} reverted, {} failed",
+            self.message,
+            self.runs,
+            self.seed,
+            self.succeeded,
+            self.reverted,
+            self.failures
+                .iter()
+                .map(|failure| failure.calls)
+                .sum::<u64>()
+        );
+        for failure in &self.failures {
+            let args = failure.args.iter().map(Value::to_string);
+            output.push_str(&format!(
+                "\n\t{:?}: {}\n\t\t{} calls, first with seed {} and arguments {}\n\t\tReproduce \
+                 with `cargo contract fuzz --message {} --seed {} --runs 1`",
+                failure.kind,
+                failure.reason,
+                failure.calls,
+                failure.seed,
+                args.collect::<Vec<_>>().join(" "),
+                self.message,
+                failure.seed
+            ));
+        }
+        output
+    }
+}
+
+impl FuzzCommand {
+    /// Deploys the contract in the sandbox and calls the message with random arguments.
+    ///
+    /// Fails if any call panics, traps or violates an invariant.
+    pub fn exec(&self) -> Result<String> {
+        let metadata = self.sandbox_opts.metadata(self.output)?;
+        let message = metadata.find_message(Some(&self.message), "")?;
+        let deployed = self.sandbox_opts.deploy(&metadata)?;
+        let caller = self.sandbox_opts.caller.account.clone();
+
+        let mut invariants = Vec::new();
+        for label in &self.invariant {
+            let invariant = metadata.find_message(Some(label), "")?;
+            if !invariant.args.is_empty() {
+                anyhow::bail!("The invariant `{}` must take no arguments", label)
+            }
+            let before = call(&mut deployed.clone(), &invariant, &caller)?;
+            invariants.push((invariant, before));
+        }
+        let fuzzer = Fuzzer {
+            metadata: &metadata,
+            message,
+            invariants,
+            value: self.value.without_denomination()?,
+            accounts: vec![
+                caller.clone(),
+                deployed.contract.clone(),
+                AccountId32::from([0; 32]),
+                AccountId32::from([1; 32]),
+            ],
+            caller,
+        };
+        let seed = self.seed.unwrap_or_else(|| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH);
+            now.map_or(0, |now| now.as_nanos() as u64)
+        });
+        let result = fuzz(&fuzzer, &deployed, seed, self.runs)?;
+        let output = self.output.format(&result, |result| result.display())?;
+        match result.failures.is_empty() {
+            true => Ok(output),
+            false => Err(anyhow::anyhow!(output)),
+        }
+    }
+}
+
+/// Runs the fuzzer with the seeds from `seed`, collecting the distinct failures.
+fn fuzz(fuzzer: &Fuzzer, deployed: &Sandbox, seed: u64, runs: u64) -> Result<FuzzResult> {
+    let mut result = FuzzResult {
+        message: fuzzer.message.label.clone(),
+        seed,
+        runs,
+        succeeded: 0,
+        reverted: 0,
+        failures: Vec::new(),
+    };
+    let mut failures = BTreeMap::new();
+    for run in 0..runs {
+        let seed = seed.wrapping_add(run);
+        match fuzzer.run(deployed, seed)? {
+            Outcome::Succeeded => result.succeeded += 1,
+            Outcome::Reverted => result.reverted += 1,
+            Outcome::Failed(kind, reason, args) => {
+                let failure = failures
+                    .entry((kind, reason.clone()))
+                    .or_insert_with(|| Failure {
+                        kind,
+                        reason,
+                        seed,
+                        args,
+                        calls: 0,
+                    });
+                failure.calls += 1;
+            }
+        }
+    }
+    result.failures = failures.into_values().collect();
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+
+    /// Metadata for the sandbox's test contract, which traps if the second byte of the input, the
+    /// first argument, is `0xff`.
+    fn metadata(dir: &std::path::Path) -> Result<InkMetadata> {
+        let metadata = json!({
+            "spec": {
+                "constructors": [{ "label": "new", "selector": "0x00", "args": [] }],
+                "messages": [{
+                    "label": "store",
+                    "selector": "0x01",
+                    "args": [
+                        { "label": "byte", "type": { "type": 1 } },
+                        { "label": "entry", "type": { "type": 2 } }
+                    ]
+                }, {
+                    "label": "flag",
+                    "selector": "0x02",
+                    "args": [{ "label": "byte", "type": { "type": 1 } }]
+                }]
+            },
+            "types": [
+                { "def": { "primitive": "u8" } },
+                { "def": { "variant": { "variants": [
+                    { "name": "None" },
+                    { "name": "Some", "fields": [{ "type": 3 }] }
+                ] } } },
+                { "def": { "composite": { "fields": [
+                    { "name": "owner", "type": 4 },
+                    { "name": "amounts", "type": 6 }
+                ] } } },
+                { "path": ["AccountId"], "def": { "composite": { "fields": [{ "type": 5 }] } } },
+                { "def": { "array": { "len": 32, "type": 1 } } },
+                { "def": { "sequence": { "type": 7 } } },
+                { "def": { "primitive": "i128" } }
+            ]
+        });
+        let path = dir.join("metadata.json");
+        fs::write(&path, metadata.to_string())?;
+        InkMetadata::read(&path)
+    }
+
+    #[test]
+    fn generates_reproducible_arguments_of_the_types() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            let metadata = metadata(dir)?;
+            let message = metadata.find_message(Some("store"), "")?;
+            let accounts = [AccountId32::from([7; 32])];
+            let generate = |seed| {
+                let mut generator = Generator {
+                    types: metadata.types(),
+                    accounts: &accounts,
+                    random: Random(seed),
+                };
+                message
+                    .args
+                    .iter()
+                    .map(|ty| generator.value(*ty, 0))
+                    .collect::<Result<Vec<_>>>()
+            };
+            for seed in 0..100 {
+                let args = generate(seed)?;
+                assert_eq!(args, generate(seed)?);
+                let encoded = args.iter().map(Value::to_string).collect::<Vec<_>>();
+                let input = metadata.encode_call(&message, &encoded)?;
+                let entry = metadata.types().decode_all(2, &input[2..])?;
+                assert_eq!(entry.get("Some").is_some(), args[1] != "None");
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn reports_traps_with_their_seed() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            let metadata = metadata(dir)?;
+            let caller = AccountId32::from([1; 32]);
+            let mut deployed = Sandbox::new(super::super::sandbox::tests::contract(), 10_000);
+            deployed.deploy(&caller, 0, vec![0])?;
+            let fuzzer = Fuzzer {
+                metadata: &metadata,
+                message: metadata.find_message(Some("flag"), "")?,
+                invariants: Vec::new(),
+                caller: caller.clone(),
+                value: 0,
+                accounts: vec![caller],
+            };
+            let result = fuzz(&fuzzer, &deployed, 42, 100)?;
+            assert_eq!(result.succeeded, 0);
+            assert_eq!(result.failures.len(), 1);
+            let failure = &result.failures[0];
+            assert_eq!(failure.kind, FailureKind::Panic);
+            assert_eq!(failure.args[0], 255);
+            assert_eq!(result.reverted + failure.calls, 100);
+
+            let reproduced = fuzz(&fuzzer, &deployed, failure.seed, 1)?;
+            assert_eq!(reproduced.failures[0].args, failure.args);
+            Ok(())
+        })
+    }
+}
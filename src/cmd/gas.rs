@@ -0,0 +1,187 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+
+use super::{call, extrinsic::Connection, signer::ExtrinsicSigner};
+use crate::ExtrinsicOpts;
+
+/// The gas limit of the dry runs estimating the gas of a call, the maximum the `contracts_call`
+/// RPC accepts.
+pub(crate) const DRY_RUN_GAS_LIMIT: u64 = 5_000_000_000_000;
+
+/// The `ref_time` of a weight, in picoseconds of execution time on the reference hardware.
+const REF_TIME_PER_SECOND: f64 = 1_000_000_000_000.0;
+
+/// The gas limit of a contract call, either fixed or estimated with a dry run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum GasLimit {
+    /// Estimate the gas with a dry run of the call.
+    Auto,
+    Limit(u64),
+}
+
+impl FromStr for GasLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input {
+            "auto" => Ok(GasLimit::Auto),
+            _ => Ok(GasLimit::Limit(
+                input.parse().context("Expected a gas limit or `auto`")?,
+            )),
+        }
+    }
+}
+
+impl GasLimit {
+    /// Returns the fixed gas limit, as `auto` requires a connection for the dry run.
+    pub fn fixed(&self) -> Result<u64> {
+        match self {
+            GasLimit::Limit(limit) => Ok(*limit),
+            GasLimit::Auto => anyhow::bail!("`--gas auto` requires a connection to the chain"),
+        }
+    }
+
+    /// Returns the gas limit of the call, which for `auto` is the gas consumed by a dry run on
+    /// the state of the best block, increased by the margin.
+    ///
+    /// The dry run runs while fetching what signing the extrinsic of the call requires.
+    pub async fn resolve(
+        &self,
+        connection: &Connection,
+        margin: GasMargin,
+        request: call::DryRunRequest<'_>,
+        signer: &ExtrinsicSigner,
+        extrinsic_opts: &ExtrinsicOpts,
+    ) -> Result<u64> {
+        if let GasLimit::Limit(limit) = self {
+            return Ok(*limit);
+        }
+        let dry_run = call::dry_run(connection, request, DRY_RUN_GAS_LIMIT);
+        let result = connection
+            .prefetching(signer, extrinsic_opts, dry_run)
+            .await
+            .context("Failed to estimate the gas")?;
+        if let Some(error) = result.error {
+            anyhow::bail!("Failed to estimate the gas, the dry run failed: {}", error)
+        }
+        if result.reverted {
+            log::warn!("The dry run estimating the gas reverted");
+        }
+        let limit = margin.apply(result.gas_consumed);
+        log::info!(
+            "Estimated gas: {}, limit with margin: {}",
+            display_ref_time(result.gas_consumed),
+            display_ref_time(limit)
+        );
+        Ok(limit)
+    }
+}
+
+/// The factor the gas estimated by a dry run is multiplied with, as the gas consumed may differ
+/// once the state changed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct GasMargin(f64);
+
+impl Default for GasMargin {
+    fn default() -> Self {
+        GasMargin(1.0)
+    }
+}
+
+impl FromStr for GasMargin {
+    type Err = anyhow::Error;
+
+    /// Parses either a factor, e.g. `1.2`, or a percentage added, e.g. `20%`.
+    fn from_str(input: &str) -> Result<Self> {
+        let factor = match input.strip_suffix('%') {
+            Some(percentage) => 1.0 + percentage.trim().parse::<f64>()? / 100.0,
+            None => input.parse::<f64>()?,
+        };
+        if !factor.is_finite() || factor < 1.0 {
+            anyhow::bail!("The gas margin must not reduce the estimate, expected e.g. 1.2 or 20%")
+        }
+        Ok(GasMargin(factor))
+    }
+}
+
+impl GasMargin {
+    /// Applies the margin to the estimated gas.
+    pub fn apply(&self, gas: u64) -> u64 {
+        (gas as f64 * self.0).ceil() as u64
+    }
+}
+
+/// Formats the `ref_time` of a weight, or a single dimensional weight, together with the
+/// execution time it approximates, e.g. `2500000000 (~2.5 ms)`.
+pub(crate) fn display_ref_time(ref_time: u64) -> String {
+    let seconds = ref_time as f64 / REF_TIME_PER_SECOND;
+    let (time, unit) = if seconds >= 1.0 {
+        (seconds, "s")
+    } else if seconds >= 1e-3 {
+        (seconds * 1e3, "ms")
+    } else if seconds >= 1e-6 {
+        (seconds * 1e6, "µs")
+    } else {
+        (seconds * 1e9, "ns")
+    };
+    format!("{} (~{} {})", ref_time, round(time), unit)
+}
+
+/// Formats the proof size of a weight together with its size in KiB, e.g. `3072 (~3 KiB)`.
+pub(crate) fn display_proof_size(proof_size: u64) -> String {
+    format!("{} (~{} KiB)", proof_size, round(proof_size as f64 / 1024.0))
+}
+
+/// Rounds to two decimals, leaving out trailing zeros.
+fn round(value: f64) -> String {
+    let rounded = format!("{:.2}", value);
+    rounded
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_gas_limits_and_margins() {
+        assert_eq!("auto".parse::<GasLimit>().unwrap(), GasLimit::Auto);
+        assert_eq!("42".parse::<GasLimit>().unwrap(), GasLimit::Limit(42));
+        assert!("lots".parse::<GasLimit>().is_err());
+
+        assert_eq!("1.5".parse::<GasMargin>().unwrap().apply(100), 150);
+        assert_eq!("20%".parse::<GasMargin>().unwrap().apply(100), 120);
+        assert_eq!(GasMargin::default().apply(7), 7);
+        assert!("0.5".parse::<GasMargin>().is_err());
+        assert!("-10%".parse::<GasMargin>().is_err());
+    }
+
+    #[test]
+    fn displays_weights_with_their_magnitude() {
+        assert_eq!(display_ref_time(2_500_000_000), "2500000000 (~2.5 ms)");
+        assert_eq!(display_ref_time(1_234_000), "1234000 (~1.23 µs)");
+        assert_eq!(display_ref_time(3_000_000_000_000), "3000000000000 (~3 s)");
+        assert_eq!(display_ref_time(0), "0 (~0 ns)");
+        assert_eq!(display_proof_size(3072), "3072 (~3 KiB)");
+        assert_eq!(display_proof_size(100), "100 (~0.1 KiB)");
+    }
+}
@@ -0,0 +1,376 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    convert::TryFrom,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use structopt::StructOpt;
+
+use super::{
+    bindgen::{Contract, Function},
+    metadata::METADATA_FILE,
+    metadata_format::{read, take_latest, Registry},
+};
+use crate::{crate_metadata::CrateMetadata, workspace::ManifestPath};
+
+/// The feature of ink! contracts enabling their end-to-end tests, as enabled by `test --e2e`.
+const E2E_FEATURE: &str = "e2e-tests";
+
+/// The helpers of the generated end-to-end tests, deploying and calling the contract with
+/// `cargo contract`.
+const E2E_PRELUDE: &str = r#"//! End-to-end tests of the `{{name}}` contract, generated by `cargo contract generate e2e`.
+//!
+//! `cargo contract test --e2e` runs them against a contracts node started for them, and `cargo
+//! test --features e2e-tests` against the one of `cargo contract node start`. They deploy the
+//! contract with `cargo contract`, which requires its `extrinsics` feature.
+#![cfg(feature = "e2e-tests")]
+
+use std::process::Command;
+
+/// The websocket URL of the contracts node.
+fn url() -> String {
+    std::env::var("CONTRACTS_NODE_URL").unwrap_or_else(|_| "ws://127.0.0.1:9944".to_string())
+}
+
+/// Runs `cargo contract` in the contract project, returning its output.
+fn cargo_contract(args: &[&str]) -> String {
+    let output = Command::new("cargo")
+        .arg("contract")
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("cargo contract is installed");
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    assert!(
+        output.status.success(),
+        "`cargo contract {}` failed: {}{}",
+        args.join(" "),
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    stdout
+}
+
+/// Submits the extrinsic of the `cargo contract` command to the node, signed by `//Alice`.
+fn submit(args: &[&str]) -> String {
+    let url = url();
+    let mut args = args.to_vec();
+    args.extend(&["--url", &url, "--suri", "//Alice"]);
+    cargo_contract(&args)
+}
+
+/// The value following the label of the output, e.g. the code hash of `Code hash: 0x…`.
+fn field(output: &str, label: &str) -> String {
+    let prefix = format!("{}: ", label);
+    output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(&prefix))
+        .and_then(|value| value.split(|c| c == ',' || c == ' ').next())
+        .unwrap_or_else(|| panic!("No `{}` in the output: {}", label, output))
+        .to_string()
+}
+"#;
+
+/// Generate code for the contract project from its metadata.
+#[derive(Debug, StructOpt)]
+pub(crate) enum GenerateCommand {
+    /// Generate an end-to-end test, building the contract and instantiating it on a contracts
+    /// node, then calling each of its messages
+    #[structopt(name = "e2e")]
+    E2e {
+        /// Path to the Cargo.toml of the contract
+        #[structopt(long, parse(from_os_str))]
+        manifest_path: Option<PathBuf>,
+        /// Path to the `metadata.json` or `.contract` bundle, by default the metadata built for
+        /// the contract
+        #[structopt(long, parse(from_os_str))]
+        metadata: Option<PathBuf>,
+    },
+}
+
+impl GenerateCommand {
+    pub fn exec(&self) -> Result<String> {
+        let GenerateCommand::E2e {
+            manifest_path,
+            metadata,
+        } = self;
+        let manifest_path = ManifestPath::try_from(manifest_path.as_ref())?;
+        let crate_metadata = CrateMetadata::collect(&manifest_path)?;
+        let path = match metadata {
+            Some(path) => path.clone(),
+            None => {
+                let path = crate_metadata.target_directory.join(METADATA_FILE);
+                if !path.exists() {
+                    anyhow::bail!(
+                        "No metadata in {}, build the contract with `cargo contract build` first",
+                        path.display()
+                    )
+                }
+                path
+            }
+        };
+        let mut metadata = read(&path)?;
+        let abi = take_latest(&mut metadata)
+            .context(format!("Failed to read the metadata {}", path.display()))?;
+        let contract = Contract::new(&metadata, &abi);
+        let test = generate_e2e(&contract)?;
+
+        let dir = manifest_path
+            .directory()
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf();
+        let test_path = dir.join("tests").join("e2e.rs");
+        fs::create_dir_all(dir.join("tests"))?;
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&test_path)
+            .map_err(|err| match err.kind() {
+                std::io::ErrorKind::AlreadyExists => anyhow::anyhow!(
+                    "{} already exists, remove it to generate it again",
+                    test_path.display()
+                ),
+                _ => anyhow::anyhow!(err),
+            })?;
+        file.write_all(test.as_bytes())?;
+
+        if !crate_metadata
+            .root_package
+            .features
+            .contains_key(E2E_FEATURE)
+        {
+            declare_feature(manifest_path.as_ref())?;
+        }
+        Ok(format!(
+            "Generated the end-to-end test {}, run it with `cargo contract test --e2e`",
+            test_path.display()
+        ))
+    }
+}
+
+/// Declares the `e2e-tests` feature in the `[features]` of the manifest, keeping its formatting.
+fn declare_feature(manifest: &Path) -> Result<()> {
+    let toml =
+        fs::read_to_string(manifest).context(format!("Failed to read {}", manifest.display()))?;
+    let feature = format!("{} = []\n", E2E_FEATURE);
+    let toml = match toml.find("[features]\n") {
+        Some(index) => {
+            let end = index + "[features]\n".len();
+            format!("{}{}{}", &toml[..end], feature, &toml[end..])
+        }
+        None => format!("{}\n[features]\n{}", toml.trim_end(), feature),
+    };
+    fs::write(manifest, toml).context(format!("Failed to write {}", manifest.display()))
+}
+
+/// The SCALE encoding of the zero value of the type: `false`, `0`, empty strings and sequences,
+/// and the first variant of enums.
+fn zero(registry: &Registry, id: u64, encoded: &mut Vec<u8>) -> Result<()> {
+    let def = registry
+        .get(id)
+        .map(|ty| &ty["def"])
+        .ok_or_else(|| anyhow::anyhow!("The metadata has no type {}", id))?;
+    let fields = |fields: &Value, encoded: &mut Vec<u8>| -> Result<()> {
+        for field in fields.as_array().map(Vec::as_slice).unwrap_or_default() {
+            zero(
+                registry,
+                field["type"].as_u64().unwrap_or_default(),
+                encoded,
+            )?;
+        }
+        Ok(())
+    };
+    if let Some(primitive) = def["primitive"].as_str() {
+        let len = match primitive {
+            "bool" | "u8" | "i8" | "str" => 1,
+            "u16" | "i16" => 2,
+            "u32" | "i32" | "char" => 4,
+            "u64" | "i64" => 8,
+            "u128" | "i128" => 16,
+            "u256" | "i256" => 32,
+            _ => anyhow::bail!("Unsupported primitive type {}", primitive),
+        };
+        encoded.resize(encoded.len() + len, 0);
+    } else if def.get("composite").is_some() {
+        fields(&def["composite"]["fields"], encoded)?;
+    } else if def.get("variant").is_some() {
+        let variant = &def["variant"]["variants"][0];
+        if variant.is_null() {
+            anyhow::bail!("The enum {} has no variants", registry.name(id))
+        }
+        let index = variant["index"]
+            .as_u64()
+            .or_else(|| variant["discriminant"].as_u64())
+            .unwrap_or_default();
+        encoded.push(index as u8);
+        fields(&variant["fields"], encoded)?;
+    } else if let Some(element) = def["array"]["type"].as_u64() {
+        for _ in 0..def["array"]["len"].as_u64().unwrap_or_default() {
+            zero(registry, element, encoded)?;
+        }
+    } else if let Some(elements) = def["tuple"].as_array() {
+        for element in elements {
+            zero(registry, element.as_u64().unwrap_or_default(), encoded)?;
+        }
+    } else {
+        // Sequences, compacts and bit sequences, whose zero value is a single compact `0`.
+        encoded.push(0);
+    }
+    Ok(())
+}
+
+/// The hex encoded data calling the constructor or message with the zero values of its
+/// arguments.
+fn data(registry: &Registry, function: &Function) -> Result<String> {
+    let mut encoded = Vec::new();
+    for arg in &function.args {
+        zero(registry, arg.ty, &mut encoded)?;
+    }
+    let selector = function.selector.as_deref().unwrap_or_default();
+    let args: String = encoded.iter().map(|byte| format!("{:02x}", byte)).collect();
+    Ok(format!("{}{}", selector, args))
+}
+
+/// The signature of the constructor or message, e.g. `transfer(to: AccountId, value: u128) ->
+/// Result<(), Error>`.
+fn signature(registry: &Registry, function: &Function) -> String {
+    let args = function
+        .args
+        .iter()
+        .map(|arg| format!("{}: {}", arg.label, registry.name(arg.ty)))
+        .collect::<Vec<_>>();
+    let returns = function
+        .returns
+        .map(|ty| format!(" -> {}", registry.name(ty)))
+        .unwrap_or_default();
+    format!("{}({}){}", function.label, args.join(", "), returns)
+}
+
+/// Generates the end-to-end test, instantiating the contract with its `new` constructor, or the
+/// first one, and calling each message with the zero values of its arguments.
+///
+/// Messages which do not mutate the storage are dry run.
+fn generate_e2e(contract: &Contract) -> Result<String> {
+    let registry = &contract.registry;
+    let constructor = contract
+        .constructors
+        .iter()
+        .find(|constructor| constructor.label.rsplit("::").next() == Some("new"))
+        .or_else(|| contract.constructors.first())
+        .ok_or_else(|| anyhow::anyhow!("The contract has no constructor"))?;
+
+    let mut test = E2E_PRELUDE.replace("{{name}}", &contract.name);
+    test.push_str(&format!(
+        "\n#[test]\nfn exercises_the_messages() {{\n    cargo_contract(&[\"build\"]);\n    let \
+         code_hash = field(&submit(&[\"deploy\"]), \"Code hash\");\n\n    // `{}`, with the zero \
+         values of its arguments.\n    let instantiated = submit(&[\n        \"instantiate\",\n        \
+         \"--code-hash\",\n        code_hash.trim_start_matches(\"0x\"),\n        \"--data\",\n        \
+         \"{}\",\n    ]);\n    let contract = field(&instantiated, \"Contract account\");\n",
+        signature(registry, constructor),
+        data(registry, constructor)?
+    ));
+    for message in &contract.messages {
+        let dry_run = match message.mutates {
+            true => "",
+            false => ", \"--dry-run\"",
+        };
+        test.push_str(&format!(
+            "\n    // `{}`\n    submit(&[\"call\", \"--contract\", &contract, \"--data\", \"{}\"{}]);\n",
+            signature(registry, message),
+            data(registry, message)?,
+            dry_run
+        ));
+    }
+    test.push_str("}\n");
+    Ok(test)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::bindgen::tests::contract;
+    use serde_json::json;
+
+    #[test]
+    fn calls_the_messages_with_zero_arguments() {
+        let metadata = json!({ "contract": { "name": "flipper", "version": "0.1.0" } });
+        let abi = json!({
+            "spec": {
+                "constructors": [
+                    { "label": "default", "selector": "0x6a3712e2", "args": [] },
+                    {
+                        "label": "new",
+                        "selector": "0x9bae9d5e",
+                        "args": [{ "label": "init_value", "type": { "type": 0 } }]
+                    }
+                ],
+                "messages": [
+                    {
+                        "label": "flip",
+                        "selector": "0x633aa551",
+                        "mutates": true,
+                        "args": [{ "label": "by", "type": { "type": 2 } }]
+                    },
+                    {
+                        "label": "get",
+                        "selector": "0x2f865bd9",
+                        "args": [],
+                        "returnType": { "type": 0 }
+                    }
+                ]
+            },
+            "types": [
+                { "id": 0, "type": { "def": { "primitive": "bool" } } },
+                { "id": 1, "type": { "def": { "primitive": "u32" } } },
+                {
+                    "id": 2,
+                    "type": {
+                        "path": ["Option"],
+                        "params": [{ "name": "T", "type": 1 }],
+                        "def": {
+                            "variant": {
+                                "variants": [
+                                    { "name": "None", "index": 0 },
+                                    { "name": "Some", "index": 1, "fields": [{ "type": 1 }] }
+                                ]
+                            }
+                        }
+                    }
+                }
+            ]
+        });
+        let (metadata, abi) = contract(metadata, abi);
+        let test = generate_e2e(&Contract::new(&metadata, &abi)).unwrap();
+
+        assert!(test.starts_with("//! End-to-end tests of the `flipper` contract"));
+        assert!(test.contains("    // `new(init_value: bool)`, with the zero values"));
+        assert!(test.contains("        \"0x9bae9d5e00\",\n"));
+        assert!(test.contains(
+            "    // `flip(by: Option<u32>)`\n    submit(&[\"call\", \"--contract\", &contract, \
+             \"--data\", \"0x633aa55100\"]);\n"
+        ));
+        assert!(test.contains(
+            "    submit(&[\"call\", \"--contract\", &contract, \"--data\", \"0x2f865bd9\", \
+             \"--dry-run\"]);\n"
+        ));
+    }
+}
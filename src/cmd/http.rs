@@ -0,0 +1,481 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    time::{Duration, Instant},
+};
+
+use futures::future::{self, Either};
+use jsonrpsee::{
+    common::{
+        self, Call, ErrorCode, Id, JsonValue, MethodCall, Output, Params, Request, Response,
+        SubscriptionId, SubscriptionNotif, SubscriptionNotifParams, Version,
+    },
+    raw::RawClient,
+    transport::{
+        http::{HttpTransportClient, RequestError},
+        TransportClient,
+    },
+};
+use serde_json::json;
+
+/// Interval in which the node is polled for the emulated subscriptions.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Maximum number of blocks reported per subscription and poll, so a subscription falling behind
+/// does not hold up the others.
+const MAX_BLOCKS_PER_POLL: u64 = 16;
+/// Number of consecutive polls a watched extrinsic may be missing from both the new blocks and
+/// the transaction pool, before it is reported as dropped.
+const DROPPED_AFTER_POLLS: u32 = 2;
+
+/// Creates a client for the HTTP JSON-RPC endpoint of a node.
+///
+/// HTTP only supports requests, so the subscriptions required for watching extrinsics and their
+/// events are emulated by polling the node.
+pub(crate) fn http_client(url: &url::Url) -> jsonrpsee::Client {
+    let transport = PollingTransport {
+        http: HttpTransportClient::new(url.as_str()),
+        pending: 0,
+        node: Node {
+            http: HttpTransportClient::new(url.as_str()),
+            next_id: 0,
+        },
+        responses: VecDeque::new(),
+        subscriptions: Vec::new(),
+        next_subscription_id: 0,
+        next_poll: Instant::now(),
+    };
+    jsonrpsee::Client::from(RawClient::new(transport))
+}
+
+/// HTTP transport forwarding requests to the node, emulating the subscriptions.
+struct PollingTransport {
+    /// Forwards the requests of the client.
+    http: HttpTransportClient,
+    /// Number of forwarded requests awaiting their response.
+    pending: usize,
+    /// Polls the node for the subscriptions.
+    node: Node,
+    /// Responses and notifications of the emulated subscriptions, returned before the responses
+    /// to forwarded requests.
+    responses: VecDeque<Response>,
+    subscriptions: Vec<(u64, Subscription)>,
+    next_subscription_id: u64,
+    next_poll: Instant,
+}
+
+impl PollingTransport {
+    /// Handles the (un)subscription requests, returning the responses to them. Returns `None`
+    /// for all other requests, which are forwarded.
+    async fn subscription_request(&mut self, call: &MethodCall) -> Option<Vec<Response>> {
+        let params = match call.params {
+            Params::Array(ref params) => params.clone(),
+            Params::None => Vec::new(),
+            Params::Map(_) => return None,
+        };
+        let respond = |result| Response::Single(Output::from(result, call.id.clone(), Version::V2));
+        let subscription = match call.method.as_str() {
+            "state_subscribeStorage" => Subscription::Storage {
+                keys: params
+                    .first()
+                    .and_then(JsonValue::as_array)
+                    .cloned()
+                    .unwrap_or_default(),
+                next: None,
+            },
+            "chain_subscribeNewHeads" => Subscription::NewHeads { next: None },
+            "chain_subscribeFinalizedHeads" => Subscription::FinalizedHeads { last: None },
+            "author_submitAndWatchExtrinsic" => match self.submit(&params).await {
+                Ok(extrinsic) => Subscription::Extrinsic(extrinsic),
+                Err(err) => return Some(vec![respond(Err(err))]),
+            },
+            "state_unsubscribeStorage"
+            | "chain_unsubscribeNewHeads"
+            | "chain_unsubscribeFinalizedHeads"
+            | "author_unwatchExtrinsic" => {
+                let id = params.first().and_then(JsonValue::as_u64);
+                let subscriptions = self.subscriptions.len();
+                self.subscriptions.retain(|(active, _)| Some(*active) != id);
+                let unsubscribed = self.subscriptions.len() < subscriptions;
+                return Some(vec![respond(Ok(unsubscribed.into()))]);
+            }
+            _ => return None,
+        };
+
+        let id = self.next_subscription_id;
+        self.next_subscription_id += 1;
+        let mut responses = vec![respond(Ok(id.into()))];
+        if let Subscription::Extrinsic(_) = subscription {
+            responses.push(notification(id, &subscription, json!("ready")));
+        }
+        self.subscriptions.push((id, subscription));
+        Some(responses)
+    }
+
+    /// Submits the extrinsic, to watch it being included in a block and finalized.
+    async fn submit(&mut self, params: &[JsonValue]) -> Result<WatchedExtrinsic, common::Error> {
+        let hash = params
+            .first()
+            .and_then(extrinsic_hash)
+            .ok_or_else(|| common::Error::invalid_params("Expected a hex encoded extrinsic"))?;
+        let best = self.node.header_number(JsonValue::Null).await?;
+        self.node
+            .request("author_submitExtrinsic", params.to_vec())
+            .await?;
+        Ok(WatchedExtrinsic {
+            hash,
+            next: best + 1,
+            in_block: None,
+            missing: 0,
+            done: false,
+        })
+    }
+
+    /// Polls the node for all subscriptions, queueing the notifications.
+    async fn poll(&mut self) {
+        for (id, subscription) in self.subscriptions.iter_mut() {
+            match subscription.poll(&mut self.node).await {
+                Ok(results) => {
+                    for result in results {
+                        let notification = notification(*id, subscription, result);
+                        self.responses.push_back(notification);
+                    }
+                }
+                Err(err) => log::warn!("Failed to poll the node: {}", err.message),
+            }
+        }
+    }
+}
+
+impl TransportClient for PollingTransport {
+    type Error = RequestError;
+
+    fn send_request<'a>(
+        &'a mut self,
+        request: Request,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Self::Error>> + Send + 'a>> {
+        Box::pin(async move {
+            if let Request::Single(Call::MethodCall(ref call)) = request {
+                if let Some(responses) = self.subscription_request(call).await {
+                    self.responses.extend(responses);
+                    return Ok(());
+                }
+            }
+            self.http.send_request(request).await?;
+            self.pending += 1;
+            Ok(())
+        })
+    }
+
+    fn next_response<'a>(
+        &'a mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Response, Self::Error>> + Send + 'a>> {
+        Box::pin(async move {
+            loop {
+                if let Some(response) = self.responses.pop_front() {
+                    return Ok(response);
+                }
+                let polling = self.subscriptions.iter().any(|(_, sub)| sub.is_active());
+                let until_poll = self.next_poll.saturating_duration_since(Instant::now());
+                if self.pending > 0 {
+                    let response = self.http.next_response();
+                    if polling {
+                        let timer = Box::pin(async_std::task::sleep(until_poll));
+                        if let Either::Left((response, _)) = future::select(response, timer).await {
+                            self.pending -= 1;
+                            return response;
+                        }
+                    } else {
+                        let response = response.await;
+                        self.pending -= 1;
+                        return response;
+                    }
+                } else if polling {
+                    async_std::task::sleep(until_poll).await;
+                } else {
+                    // Nothing to wait for until the next request is sent.
+                    future::pending::<()>().await;
+                }
+                self.poll().await;
+                self.next_poll = Instant::now() + POLL_INTERVAL;
+            }
+        })
+    }
+}
+
+/// The connection polling the node, separate from the forwarded requests.
+struct Node {
+    http: HttpTransportClient,
+    next_id: u64,
+}
+
+impl Node {
+    async fn request(
+        &mut self,
+        method: &str,
+        params: Vec<JsonValue>,
+    ) -> Result<JsonValue, common::Error> {
+        self.next_id += 1;
+        let id = Id::Num(self.next_id);
+        let call = MethodCall {
+            jsonrpc: Version::V2,
+            method: method.to_string(),
+            params: Params::Array(params),
+            id: id.clone(),
+        };
+        let transport_error = |err: RequestError| common::Error {
+            code: ErrorCode::InternalError,
+            message: err.to_string(),
+            data: None,
+        };
+        self.http
+            .send_request(Request::Single(Call::MethodCall(call)))
+            .await
+            .map_err(transport_error)?;
+        loop {
+            // Responses to the requests of an interrupted poll are skipped.
+            match self.http.next_response().await.map_err(transport_error)? {
+                Response::Single(Output::Success(success)) if success.id == id => {
+                    return Ok(success.result)
+                }
+                Response::Single(Output::Failure(failure)) if failure.id == id => {
+                    return Err(failure.error)
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    async fn block_hash(&mut self, number: u64) -> Result<JsonValue, common::Error> {
+        self.request("chain_getBlockHash", vec![number.into()])
+            .await
+    }
+
+    /// Returns the number of the block with the given hash, or of the best block for `null`.
+    async fn header_number(&mut self, hash: JsonValue) -> Result<u64, common::Error> {
+        let header = self.request("chain_getHeader", vec![hash]).await?;
+        block_number(&header)
+            .ok_or_else(|| common::Error::invalid_params("Invalid block number in header"))
+    }
+}
+
+/// A subscription emulated by polling the node.
+enum Subscription {
+    /// Reports the values of the storage keys in each new best block.
+    Storage {
+        keys: Vec<JsonValue>,
+        /// The next block to report, the best block if none was reported yet.
+        next: Option<u64>,
+    },
+    NewHeads {
+        next: Option<u64>,
+    },
+    FinalizedHeads {
+        last: Option<JsonValue>,
+    },
+    Extrinsic(WatchedExtrinsic),
+}
+
+/// An extrinsic submitted via `author_submitAndWatchExtrinsic`.
+struct WatchedExtrinsic {
+    hash: [u8; 32],
+    /// The next block to search for the extrinsic.
+    next: u64,
+    /// The hash and number of the block including the extrinsic.
+    in_block: Option<(JsonValue, u64)>,
+    /// Number of consecutive polls the extrinsic was neither included nor in the pool.
+    missing: u32,
+    /// Whether the extrinsic was finalized or dropped.
+    done: bool,
+}
+
+impl Subscription {
+    /// The method of the notifications.
+    fn method(&self) -> &'static str {
+        match self {
+            Subscription::Storage { .. } => "state_storage",
+            Subscription::NewHeads { .. } => "chain_newHead",
+            Subscription::FinalizedHeads { .. } => "chain_finalizedHead",
+            Subscription::Extrinsic(_) => "author_extrinsicUpdate",
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        match self {
+            Subscription::Extrinsic(extrinsic) => !extrinsic.done,
+            _ => true,
+        }
+    }
+
+    /// Polls the node, returning the results of the notifications.
+    ///
+    /// The state of the subscription is only updated once all requests are done, so an
+    /// interrupted poll is simply repeated.
+    async fn poll(&mut self, node: &mut Node) -> Result<Vec<JsonValue>, common::Error> {
+        match self {
+            Subscription::Storage { keys, next } => {
+                let best = node.header_number(JsonValue::Null).await?;
+                let from = next.unwrap_or(best);
+                let mut results = Vec::new();
+                for number in from..=best.min(from + MAX_BLOCKS_PER_POLL - 1) {
+                    let block = node.block_hash(number).await?;
+                    let mut changes = Vec::new();
+                    for key in keys.iter() {
+                        let value = node
+                            .request("state_getStorage", vec![key.clone(), block.clone()])
+                            .await?;
+                        changes.push(json!([key, value]));
+                    }
+                    results.push(json!({ "block": block, "changes": changes }));
+                }
+                *next = Some(from + results.len() as u64);
+                Ok(results)
+            }
+            Subscription::NewHeads { next } => {
+                let best = node.header_number(JsonValue::Null).await?;
+                let from = next.unwrap_or(best);
+                let mut results = Vec::new();
+                for number in from..=best.min(from + MAX_BLOCKS_PER_POLL - 1) {
+                    let block = node.block_hash(number).await?;
+                    results.push(node.request("chain_getHeader", vec![block]).await?);
+                }
+                *next = Some(from + results.len() as u64);
+                Ok(results)
+            }
+            Subscription::FinalizedHeads { last } => {
+                let finalized = node.request("chain_getFinalizedHead", Vec::new()).await?;
+                if last.as_ref() == Some(&finalized) {
+                    return Ok(Vec::new());
+                }
+                let header = node
+                    .request("chain_getHeader", vec![finalized.clone()])
+                    .await?;
+                *last = Some(finalized);
+                Ok(vec![header])
+            }
+            Subscription::Extrinsic(extrinsic) => extrinsic.poll(node).await,
+        }
+    }
+}
+
+impl WatchedExtrinsic {
+    /// Searches the new blocks for the extrinsic and, once included, waits for the block to be
+    /// finalized, returning the status updates.
+    async fn poll(&mut self, node: &mut Node) -> Result<Vec<JsonValue>, common::Error> {
+        if self.done {
+            return Ok(Vec::new());
+        }
+        if let Some((ref block, number)) = self.in_block {
+            if node.block_hash(number).await? != *block {
+                let retracted = json!({ "retracted": block });
+                self.next = number;
+                self.in_block = None;
+                return Ok(vec![retracted]);
+            }
+            let finalized = node.request("chain_getFinalizedHead", Vec::new()).await?;
+            if node.header_number(finalized).await? >= number {
+                let finalized = json!({ "finalized": block });
+                self.done = true;
+                return Ok(vec![finalized]);
+            }
+            return Ok(Vec::new());
+        }
+
+        let best = node.header_number(JsonValue::Null).await?;
+        let to = best.min(self.next + MAX_BLOCKS_PER_POLL - 1);
+        for number in self.next..=to {
+            let block = node.block_hash(number).await?;
+            let contents = node.request("chain_getBlock", vec![block.clone()]).await?;
+            if contains(&contents["block"]["extrinsics"], &self.hash) {
+                let in_block = json!({ "inBlock": block });
+                self.in_block = Some((block, number));
+                self.next = number + 1;
+                self.missing = 0;
+                return Ok(vec![in_block]);
+            }
+        }
+        let pool = node.request("author_pendingExtrinsics", Vec::new()).await?;
+        self.next = self.next.max(to + 1);
+        if contains(&pool, &self.hash) {
+            self.missing = 0;
+        } else if to == best {
+            self.missing += 1;
+        }
+        if self.missing >= DROPPED_AFTER_POLLS {
+            self.done = true;
+            return Ok(vec![json!("dropped")]);
+        }
+        Ok(Vec::new())
+    }
+}
+
+fn notification(id: u64, subscription: &Subscription, result: JsonValue) -> Response {
+    Response::Notif(SubscriptionNotif {
+        jsonrpc: Version::V2,
+        method: subscription.method().to_string(),
+        params: SubscriptionNotifParams {
+            subscription: SubscriptionId::Num(id),
+            result,
+        },
+    })
+}
+
+/// Returns the hash of the hex encoded extrinsic.
+fn extrinsic_hash(extrinsic: &JsonValue) -> Option<[u8; 32]> {
+    let bytes = hex::decode(extrinsic.as_str()?.trim_start_matches("0x")).ok()?;
+    Some(sp_core::blake2_256(&bytes))
+}
+
+/// Returns true if the list of hex encoded extrinsics contains the one with the given hash.
+fn contains(extrinsics: &JsonValue, hash: &[u8; 32]) -> bool {
+    extrinsics
+        .as_array()
+        .map(|extrinsics| {
+            extrinsics
+                .iter()
+                .any(|extrinsic| extrinsic_hash(extrinsic).as_ref() == Some(hash))
+        })
+        .unwrap_or(false)
+}
+
+/// Returns the hex encoded number of the block header.
+fn block_number(header: &JsonValue) -> Option<u64> {
+    let number = header["number"].as_str()?;
+    u64::from_str_radix(number.trim_start_matches("0x"), 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_extrinsics_by_hash() {
+        let extrinsics = json!(["0x0c0102", "0x080304"]);
+        let hash = sp_core::blake2_256(&[8, 3, 4]);
+        assert!(contains(&extrinsics, &hash));
+        assert!(!contains(&extrinsics, &sp_core::blake2_256(&[3, 4])));
+        assert!(!contains(&JsonValue::Null, &hash));
+    }
+
+    #[test]
+    fn parses_block_number() {
+        assert_eq!(block_number(&json!({ "number": "0x1a" })), Some(26));
+        assert_eq!(block_number(&json!({ "number": 26 })), None);
+    }
+}
@@ -0,0 +1,297 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::Result;
+use codec::{Decode, Encode, Input, Output};
+use serde::Serialize;
+use sp_core::{crypto::AccountId32, storage::StorageKey, H256};
+use subxt::{DefaultNodeRuntime, Metadata, MetadataError, Store};
+
+use super::{
+    address::Address,
+    balance::BalanceFormat,
+    block::{self, BlockRef},
+    extrinsic::{Connection, Submitted},
+};
+use crate::SubmitOpts;
+
+/// The `Contracts::ContractInfoOf` storage map, holding the contract of an account.
+pub(crate) struct ContractInfoOfStore<'a> {
+    pub account_id: &'a AccountId32,
+}
+
+impl Encode for ContractInfoOfStore<'_> {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.account_id.encode_to(dest)
+    }
+}
+
+impl Store<DefaultNodeRuntime> for ContractInfoOfStore<'_> {
+    const MODULE: &'static str = "Contracts";
+    const FIELD: &'static str = "ContractInfoOf";
+    type Returns = ContractInfo;
+
+    fn prefix(metadata: &Metadata) -> Result<StorageKey, MetadataError> {
+        Ok(metadata
+            .module(Self::MODULE)?
+            .storage(Self::FIELD)?
+            .prefix())
+    }
+
+    fn key(&self, metadata: &Metadata) -> Result<StorageKey, MetadataError> {
+        Ok(metadata
+            .module(Self::MODULE)?
+            .storage(Self::FIELD)?
+            .map()?
+            .key(self.account_id))
+    }
+}
+
+/// The contract of an account, which is left as a tombstone if it was evicted for not paying
+/// rent.
+#[derive(Debug)]
+pub(crate) enum ContractInfo {
+    Alive(AliveContractInfo),
+    Tombstone,
+}
+
+impl Decode for ContractInfo {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+        match input.read_byte()? {
+            0 => Ok(ContractInfo::Alive(AliveContractInfo {
+                trie_id: Decode::decode(input)?,
+                storage_size: Decode::decode(input)?,
+                pair_count: Decode::decode(input)?,
+                code_hash: Decode::decode(input)?,
+                rent_allowance: Decode::decode(input)?,
+            })),
+            1 => Ok(ContractInfo::Tombstone),
+            _ => Err("Invalid contract info variant".into()),
+        }
+    }
+}
+
+/// The information stored about a live contract, omitting the blocks the rent was last paid and
+/// the storage last written in.
+#[derive(Debug)]
+pub(crate) struct AliveContractInfo {
+    /// The unique id of the child trie holding the contract's storage.
+    pub trie_id: Vec<u8>,
+    /// The size of the contract's storage in bytes.
+    pub storage_size: u32,
+    /// The number of items in the contract's storage.
+    pub pair_count: u32,
+    pub code_hash: H256,
+    /// The balance the contract may spend on rent.
+    pub rent_allowance: u128,
+}
+
+/// Result of a successful `info`.
+#[derive(Debug, Serialize)]
+pub(crate) struct InfoResult {
+    pub contract: AccountId32,
+    /// Hash of the block the state was read at, if `--at` selected one instead of the best block.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block: Option<H256>,
+    /// Whether the contract was evicted, leaving a tombstone.
+    pub tombstone: bool,
+    pub code_hash: Option<H256>,
+    /// Hex encoded id of the child trie holding the contract's storage.
+    pub trie_id: Option<String>,
+    /// The size of the contract's storage in bytes.
+    pub storage_size: Option<u32>,
+    /// The number of items in the contract's storage.
+    pub storage_items: Option<u32>,
+    pub rent_allowance: Option<u128>,
+    /// The free balance of the contract's account.
+    pub balance: u128,
+    /// The balance reserved on the contract's account, held as storage deposit.
+    pub storage_deposit: u128,
+    /// How the balances are printed in the human readable output.
+    #[serde(skip)]
+    pub balance_format: BalanceFormat,
+}
+
+impl InfoResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let mut output = format!("Contract: {}", self.contract);
+        if let Some(ref block) = self.block {
+            output.push_str(&format!("\n\tAt block: {:?}", block));
+        }
+        if self.tombstone {
+            output.push_str("\n\tThe contract was evicted, leaving a tombstone");
+        }
+        if let Some(ref code_hash) = self.code_hash {
+            output.push_str(&format!("\n\tCode hash: {:?}", code_hash));
+        }
+        if let Some(ref trie_id) = self.trie_id {
+            output.push_str(&format!("\n\tTrie id: {}", trie_id));
+        }
+        if let (Some(size), Some(items)) = (self.storage_size, self.storage_items) {
+            output.push_str(&format!("\n\tStorage: {} bytes in {} items", size, items));
+        }
+        if let Some(rent_allowance) = self.rent_allowance {
+            output.push_str(&format!(
+                "\n\tRent allowance: {}",
+                self.balance_format.display(rent_allowance)
+            ));
+        }
+        output.push_str(&format!(
+            "\n\tBalance: {}\n\tStorage deposit: {}",
+            self.balance_format.display(self.balance),
+            self.balance_format.display(self.storage_deposit)
+        ));
+        output
+    }
+}
+
+/// The storage deposit held by a contract, as reserved on its account.
+#[derive(Debug, Serialize)]
+pub(crate) struct HeldStorageDeposit {
+    /// The deposit held after the block including the extrinsic.
+    pub held: u128,
+    /// The change of the deposit by the block, negative if deposit was released.
+    pub change: i128,
+}
+
+impl HeldStorageDeposit {
+    /// The human readable output.
+    pub fn display(&self, balance_format: &BalanceFormat) -> String {
+        let sign = if self.change > 0 { "+" } else { "" };
+        format!(
+            "Storage deposit held by the contract: {} ({}{})",
+            balance_format.display(self.held),
+            sign,
+            balance_format.display_signed(self.change)
+        )
+    }
+}
+
+/// Fetches the storage deposit the contract holds after the block including the extrinsic, and
+/// its change by the block, unless the inclusion of the extrinsic was not awaited.
+///
+/// As the extrinsic was submitted already, a failure to fetch the deposit is only logged.
+pub(crate) async fn held_storage_deposit(
+    connection: &Connection,
+    contract: &AccountId32,
+    submitted: &Submitted,
+) -> Option<HeldStorageDeposit> {
+    let included = submitted.included.as_ref()?;
+    let fetched = async {
+        let held = connection
+            .account_data(contract, Some(included.result.block))
+            .await?
+            .reserved;
+        let parent = match included.timepoint.height.checked_sub(1) {
+            Some(number) => connection.block_hash(number).await?,
+            None => None,
+        };
+        let before = match parent {
+            Some(parent) => connection.account_data(contract, Some(parent)).await?.reserved,
+            None => held,
+        };
+        Ok::<_, anyhow::Error>(HeldStorageDeposit {
+            held,
+            change: held as i128 - before as i128,
+        })
+    };
+    match fetched.await {
+        Ok(deposit) => Some(deposit),
+        Err(err) => {
+            log::warn!("Failed to fetch the storage deposit of the contract: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Fetches the information about the contract at the address, from the `Contracts::ContractInfoOf`
+/// storage of the chain, together with the balance of its account.
+///
+/// The state is queried at the block `at`, defaulting to the best block.
+pub(crate) fn execute_info(
+    submit_opts: &SubmitOpts,
+    contract: &Address,
+    at: Option<&BlockRef>,
+) -> Result<InfoResult> {
+    async_std::task::block_on(async move {
+        let connection = Connection::new(submit_opts).await?;
+        contract.check_format();
+
+        let at = block::resolve(&connection, at).await?;
+        let account_id = &contract.account;
+        let info = connection
+            .fetch_at(&ContractInfoOfStore { account_id }, at)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("No contract found at {}", account_id))?;
+        let account = connection.account_data(account_id, at).await?;
+
+        let mut result = InfoResult {
+            contract: account_id.clone(),
+            block: at,
+            tombstone: false,
+            code_hash: None,
+            trie_id: None,
+            storage_size: None,
+            storage_items: None,
+            rent_allowance: None,
+            balance: account.free,
+            storage_deposit: account.reserved,
+            balance_format: connection.balance_format(),
+        };
+        match info {
+            ContractInfo::Alive(info) => {
+                result.code_hash = Some(info.code_hash);
+                result.trie_id = Some(format!("0x{}", hex::encode(&info.trie_id)));
+                result.storage_size = Some(info.storage_size);
+                result.storage_items = Some(info.pair_count);
+                result.rent_allowance = Some(info.rent_allowance);
+            }
+            ContractInfo::Tombstone => result.tombstone = true,
+        }
+        Ok(result)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_contract_info() {
+        let encoded = (
+            0u8,
+            vec![1u8, 2],
+            64u32,
+            3u32,
+            H256::repeat_byte(7),
+            1000u128,
+            5u32,
+            Some(6u32),
+        )
+            .encode();
+        let info = ContractInfo::decode(&mut &encoded[..]).unwrap();
+        match info {
+            ContractInfo::Alive(info) => {
+                assert_eq!(info.trie_id, vec![1, 2]);
+                assert_eq!(info.pair_count, 3);
+                assert_eq!(info.code_hash, H256::repeat_byte(7));
+                assert_eq!(info.rent_allowance, 1000);
+            }
+            ContractInfo::Tombstone => panic!("expected a live contract"),
+        }
+    }
+}
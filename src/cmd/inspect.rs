@@ -0,0 +1,713 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::BTreeMap, fmt::Write, fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use parity_wasm::elements::{
+    BlockType, External, FuncBody, FunctionType, InitExpr, Instruction, Internal, Module,
+    ResizableLimits, Type, ValueType,
+};
+use structopt::StructOpt;
+
+use super::extract::extract;
+#[cfg(feature = "extrinsics")]
+use super::{extrinsic::Connection, network::section, pallet::ContractsVersion};
+use crate::crate_metadata::CrateMetadata;
+#[cfg(feature = "extrinsics")]
+use crate::SubmitOpts;
+
+/// Inspect the Wasm of a contract, e.g. printing it in the WebAssembly text format
+#[derive(Debug, StructOpt)]
+#[structopt(name = "inspect")]
+pub(crate) struct InspectCommand {
+    /// Path to the Wasm or the `.contract` bundle, by default the Wasm built for the contract
+    /// project in the current directory
+    #[structopt(parse(from_os_str))]
+    artifact: Option<PathBuf>,
+    /// Print the Wasm in the WebAssembly text format
+    #[structopt(long, conflicts_with = "imports")]
+    wat: bool,
+    /// Print only this function, by its index, its export or the name in the name section
+    #[structopt(long, requires = "wat")]
+    function: Option<String>,
+    /// Print the imported host functions, with the version of the contracts API they belong
+    /// to, and the exported symbols
+    #[structopt(long)]
+    imports: bool,
+    /// Flag the imported host functions the contracts pallet of the chain does not provide, by
+    /// default of the node at `ws://localhost:9944`
+    #[cfg(feature = "extrinsics")]
+    #[structopt(long, requires = "imports")]
+    chain: bool,
+    #[cfg(feature = "extrinsics")]
+    #[structopt(flatten)]
+    pub submit_opts: SubmitOpts,
+}
+
+impl InspectCommand {
+    pub fn exec(&self) -> Result<String> {
+        if !self.wat && !self.imports {
+            anyhow::bail!("Choose what to inspect, e.g. --wat or --imports")
+        }
+        let module = load(self.artifact.as_ref())?;
+        if self.imports {
+            return self.symbols(&module);
+        }
+        let printer = Printer::new(&module)?;
+        match self.function {
+            Some(ref function) => {
+                let index = printer.find(function)?;
+                Ok(printer.function(index))
+            }
+            None => Ok(printer.module()),
+        }
+    }
+
+    #[cfg(feature = "extrinsics")]
+    fn symbols(&self, module: &Module) -> Result<String> {
+        if !self.chain {
+            return Ok(symbols(module, None));
+        }
+        let version = async_std::task::block_on(async {
+            let connection = Connection::new(&self.submit_opts).await?;
+            ContractsVersion::from_metadata(&connection.runtime_metadata().await?)
+        })?;
+        let provides = |module: &str, name: &str| version.provides(module, name);
+        Ok(symbols(
+            module,
+            Some((section(&self.submit_opts), &provides)),
+        ))
+    }
+
+    #[cfg(not(feature = "extrinsics"))]
+    fn symbols(&self, module: &Module) -> Result<String> {
+        Ok(symbols(module, None))
+    }
+}
+
+/// The name of a chain, and whether its contracts pallet provides a host function of a module.
+type Chain<'a> = (String, &'a dyn Fn(&str, &str) -> bool);
+
+/// Lists the imports and exports of the module, flagging the imported host functions the
+/// contracts pallet of the `chain` does not provide.
+fn symbols(module: &Module, chain: Option<Chain>) -> String {
+    let mut out = "Imports:".to_string();
+    let mut missing = 0;
+    for import in module.import_section().map_or(&[][..], |s| s.entries()) {
+        let (module, name) = (import.module(), import.field());
+        let _ = write!(out, "\n\t{}::{}: ", module, name);
+        match import.external() {
+            External::Function(_) => {
+                out.push_str("function");
+                let version = module
+                    .strip_prefix("seal")
+                    .and_then(|v| v.parse::<u32>().ok());
+                if let Some(version) = version {
+                    let _ = write!(out, ", contracts API version {}", version);
+                }
+                if let Some((ref chain, provides)) = chain {
+                    if !provides(module, name) {
+                        missing += 1;
+                        let _ = write!(out, ", NOT PROVIDED by {}", chain);
+                    }
+                }
+            }
+            External::Memory(ty) => {
+                let _ = write!(out, "memory, {} pages", range(ty.limits()));
+            }
+            External::Table(ty) => {
+                let _ = write!(out, "table, {} elements", range(ty.limits()));
+            }
+            External::Global(ty) => {
+                let _ = write!(
+                    out,
+                    "global {}",
+                    global_type(ty.content_type(), ty.is_mutable())
+                );
+            }
+        }
+    }
+    out.push_str("\nExports:");
+    for export in module.export_section().map_or(&[][..], |s| s.entries()) {
+        let (kind, index) = match export.internal() {
+            Internal::Function(index) => ("function", index),
+            Internal::Table(index) => ("table", index),
+            Internal::Memory(index) => ("memory", index),
+            Internal::Global(index) => ("global", index),
+        };
+        let _ = write!(out, "\n\t{}: {} {}", export.field(), kind, index);
+    }
+    if let Some((chain, _)) = chain {
+        match missing {
+            0 => {
+                let _ = write!(out, "\n{} provides all imported host functions", chain);
+            }
+            _ => {
+                let _ = write!(
+                    out,
+                    "\n{} does not provide {} of the imported host functions, deploying the \
+                     contract will fail",
+                    chain, missing
+                );
+            }
+        }
+    }
+    out
+}
+
+/// Reads the Wasm module of the `.contract` bundle or the Wasm file.
+pub(crate) fn load(artifact: Option<&PathBuf>) -> Result<Module> {
+    let path = match artifact {
+        Some(path) => path.clone(),
+        None => CrateMetadata::collect(&Default::default())?.dest_wasm,
+    };
+    let wasm = match path.extension() {
+        Some(extension) if extension == "contract" => match extract(&path)? {
+            (wasm, "wasm", _) => wasm,
+            _ => anyhow::bail!("The bundle contains a PolkaVM program, not Wasm"),
+        },
+        _ => fs::read(&path).context(format!("Failed to read {}", path.display()))?,
+    };
+    let module: Module = parity_wasm::deserialize_buffer(&wasm)
+        .context(format!("Failed to parse the Wasm of {}", path.display()))?;
+    // A missing or invalid name section only loses the names of the functions.
+    Ok(module.parse_names().unwrap_or_else(|(_, module)| module))
+}
+
+/// Prints a module in the WebAssembly text format, as `wasm2wat` does, with the names of the
+/// functions as comments.
+struct Printer<'a> {
+    module: &'a Module,
+    /// The types of the imported functions followed by those of the defined ones.
+    functions: Vec<u32>,
+    imported_functions: usize,
+    names: BTreeMap<u32, String>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(module: &'a Module) -> Result<Self> {
+        let imports = module
+            .import_section()
+            .map_or(&[][..], |section| section.entries());
+        let mut functions: Vec<u32> = imports
+            .iter()
+            .filter_map(|import| match import.external() {
+                External::Function(ty) => Some(*ty),
+                _ => None,
+            })
+            .collect();
+        let imported_functions = functions.len();
+        if let Some(section) = module.function_section() {
+            functions.extend(section.entries().iter().map(|func| func.type_ref()));
+        }
+        let mut names = BTreeMap::new();
+        if let Some(subsection) = module.names_section().and_then(|names| names.functions()) {
+            for (index, name) in subsection.names().iter() {
+                names.insert(index, name.clone());
+            }
+        }
+        Ok(Printer {
+            module,
+            functions,
+            imported_functions,
+            names,
+        })
+    }
+
+    /// The index of the function, supplied as its index, its export or its name.
+    fn find(&self, function: &str) -> Result<u32> {
+        let exports = self
+            .module
+            .export_section()
+            .map_or(&[][..], |s| s.entries());
+        let exported = exports.iter().find_map(|export| match export.internal() {
+            Internal::Function(index) if export.field() == function => Some(*index),
+            _ => None,
+        });
+        let named = || {
+            self.names
+                .iter()
+                .find(|(_, name)| name.as_str() == function)
+                .map(|(index, _)| *index)
+        };
+        let index = function
+            .parse()
+            .ok()
+            .or(exported)
+            .or_else(named)
+            .ok_or_else(|| anyhow::anyhow!("No function `{}` in the Wasm", function))?;
+        if (index as usize) < self.imported_functions || index as usize >= self.functions.len() {
+            anyhow::bail!("The Wasm defines no function {}", index)
+        }
+        Ok(index)
+    }
+
+    fn function_type(&self, ty: u32) -> Option<&FunctionType> {
+        let types = self.module.type_section()?.types();
+        match types.get(ty as usize)? {
+            Type::Function(function_type) => Some(function_type),
+        }
+    }
+
+    /// The `(type n) (param …) (result …)` of a function of the type.
+    fn signature(&self, ty: u32) -> String {
+        let mut output = format!("(type {})", ty);
+        if let Some(function_type) = self.function_type(ty) {
+            output.push_str(&signature(function_type));
+        }
+        output
+    }
+
+    fn comment(&self, index: u32) -> String {
+        match self.names.get(&index) {
+            Some(name) => format!(" ;; {}", name),
+            None => String::new(),
+        }
+    }
+
+    /// The module, with its sections in the order of the text format.
+    fn module(&self) -> String {
+        let module = self.module;
+        let mut out = "(module".to_string();
+        if let Some(section) = module.type_section() {
+            for (index, Type::Function(ty)) in section.types().iter().enumerate() {
+                let _ = write!(out, "\n  (type (;{};) (func{}))", index, signature(ty));
+            }
+        }
+        let (mut function, mut table, mut memory, mut global) = (0, 0, 0, 0);
+        for import in module.import_section().map_or(&[][..], |s| s.entries()) {
+            let desc = match import.external() {
+                External::Function(ty) => {
+                    function += 1;
+                    format!("(func (;{};) {})", function - 1, self.signature(*ty))
+                }
+                External::Table(ty) => {
+                    table += 1;
+                    format!("(table (;{};) {} funcref)", table - 1, limits(ty.limits()))
+                }
+                External::Memory(ty) => {
+                    memory += 1;
+                    format!("(memory (;{};) {})", memory - 1, limits(ty.limits()))
+                }
+                External::Global(ty) => {
+                    global += 1;
+                    format!(
+                        "(global (;{};) {})",
+                        global - 1,
+                        global_type(ty.content_type(), ty.is_mutable())
+                    )
+                }
+            };
+            let _ = write!(
+                out,
+                "\n  (import {:?} {:?} {})",
+                import.module(),
+                import.field(),
+                desc
+            );
+        }
+        for index in self.imported_functions..self.functions.len() {
+            out.push('\n');
+            out.push_str(&indent(&self.function(index as u32), "  "));
+        }
+        for (index, ty) in module
+            .table_section()
+            .map_or(&[][..], |s| s.entries())
+            .iter()
+            .enumerate()
+        {
+            let _ = write!(
+                out,
+                "\n  (table (;{};) {} funcref)",
+                table + index,
+                limits(ty.limits())
+            );
+        }
+        for (index, ty) in module
+            .memory_section()
+            .map_or(&[][..], |s| s.entries())
+            .iter()
+            .enumerate()
+        {
+            let _ = write!(
+                out,
+                "\n  (memory (;{};) {})",
+                memory + index,
+                limits(ty.limits())
+            );
+        }
+        for (index, entry) in module
+            .global_section()
+            .map_or(&[][..], |s| s.entries())
+            .iter()
+            .enumerate()
+        {
+            let ty = entry.global_type();
+            let _ = write!(
+                out,
+                "\n  (global (;{};) {} {})",
+                global + index,
+                global_type(ty.content_type(), ty.is_mutable()),
+                init_expr(entry.init_expr())
+            );
+        }
+        for export in module.export_section().map_or(&[][..], |s| s.entries()) {
+            let internal = match export.internal() {
+                Internal::Function(index) => format!("func {}", index),
+                Internal::Table(index) => format!("table {}", index),
+                Internal::Memory(index) => format!("memory {}", index),
+                Internal::Global(index) => format!("global {}", index),
+            };
+            let _ = write!(out, "\n  (export {:?} ({}))", export.field(), internal);
+        }
+        if let Some(start) = module.start_section() {
+            let _ = write!(out, "\n  (start {})", start);
+        }
+        for (index, segment) in module
+            .elements_section()
+            .map_or(&[][..], |s| s.entries())
+            .iter()
+            .enumerate()
+        {
+            let members = segment.members().iter().map(u32::to_string);
+            let offset = segment.offset().as_ref().map(init_expr).unwrap_or_default();
+            let _ = write!(
+                out,
+                "\n  (elem (;{};) {} func {})",
+                index,
+                offset,
+                members.collect::<Vec<_>>().join(" ")
+            );
+        }
+        for (index, segment) in module
+            .data_section()
+            .map_or(&[][..], |s| s.entries())
+            .iter()
+            .enumerate()
+        {
+            let offset = segment.offset().as_ref().map(init_expr).unwrap_or_default();
+            let _ = write!(
+                out,
+                "\n  (data (;{};) {} \"{}\")",
+                index,
+                offset,
+                escape(segment.value())
+            );
+        }
+        out.push(')');
+        out
+    }
+
+    /// The function defined at the index, which must be in the range of the defined functions.
+    fn function(&self, index: u32) -> String {
+        let ty = self.functions[index as usize];
+        let defined = index as usize - self.imported_functions;
+        let body = self
+            .module
+            .code_section()
+            .and_then(|section| section.bodies().get(defined));
+        let mut out = format!(
+            "(func (;{};) {}{}",
+            index,
+            self.signature(ty),
+            self.comment(index)
+        );
+        if let Some(body) = body {
+            body_into(&mut out, body);
+        }
+        out.push(')');
+        out
+    }
+}
+
+/// Appends the locals and the instructions of the body, indented by their nesting.
+fn body_into(out: &mut String, body: &FuncBody) {
+    let locals = body
+        .locals()
+        .iter()
+        .flat_map(|local| (0..local.count()).map(move |_| local.value_type()))
+        .map(|ty| ty.to_string())
+        .collect::<Vec<_>>();
+    if !locals.is_empty() {
+        let _ = write!(out, "\n  (local {})", locals.join(" "));
+    }
+    let mut depth = 1;
+    let instructions = body.code().elements();
+    // The final `end` closes the function itself.
+    for instruction in &instructions[..instructions.len().saturating_sub(1)] {
+        if matches!(instruction, Instruction::End | Instruction::Else) {
+            depth -= 1;
+        }
+        let _ = write!(
+            out,
+            "\n{}{}",
+            "  ".repeat(depth),
+            instruction_text(instruction)
+        );
+        if matches!(
+            instruction,
+            Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) | Instruction::Else
+        ) {
+            depth += 1;
+        }
+    }
+}
+
+/// The instruction in the text format, fixing up the older mnemonics and the immediates
+/// `parity-wasm` displays differently.
+fn instruction_text(instruction: &Instruction) -> String {
+    let block = |name: &str, ty: &BlockType| match ty {
+        BlockType::NoResult => name.to_string(),
+        BlockType::Value(ty) => format!("{} (result {})", name, ty),
+    };
+    match instruction {
+        Instruction::Block(ty) => block("block", ty),
+        Instruction::Loop(ty) => block("loop", ty),
+        Instruction::If(ty) => block("if", ty),
+        Instruction::BrTable(table) => {
+            let targets = table.table.iter().chain(Some(&table.default));
+            let targets = targets.map(u32::to_string).collect::<Vec<_>>();
+            format!("br_table {}", targets.join(" "))
+        }
+        Instruction::CallIndirect(ty, _) => format!("call_indirect (type {})", ty),
+        Instruction::GetLocal(index) => format!("local.get {}", index),
+        Instruction::SetLocal(index) => format!("local.set {}", index),
+        Instruction::TeeLocal(index) => format!("local.tee {}", index),
+        Instruction::GetGlobal(index) => format!("global.get {}", index),
+        Instruction::SetGlobal(index) => format!("global.set {}", index),
+        Instruction::CurrentMemory(_) => "memory.size".to_string(),
+        Instruction::GrowMemory(_) => "memory.grow".to_string(),
+        Instruction::F32Const(bits) => {
+            format!("f32.const {}", float(f64::from(f32::from_bits(*bits))))
+        }
+        Instruction::F64Const(bits) => format!("f64.const {}", float(f64::from_bits(*bits))),
+        instruction => {
+            let text = instruction.to_string();
+            match memory_alignment(instruction) {
+                Some(align) => format!("{} align={}", text, 1u32 << align),
+                None => text,
+            }
+        }
+    }
+}
+
+/// The alignment exponent of a load or store, if it differs from the natural one the text format
+/// defaults to.
+fn memory_alignment(instruction: &Instruction) -> Option<u32> {
+    use Instruction::*;
+    let (align, natural) = match *instruction {
+        I32Load8S(align, _) | I32Load8U(align, _) | I64Load8S(align, _) | I64Load8U(align, _) => {
+            (align, 0)
+        }
+        I32Store8(align, _) | I64Store8(align, _) => (align, 0),
+        I32Load16S(align, _)
+        | I32Load16U(align, _)
+        | I64Load16S(align, _)
+        | I64Load16U(align, _) => (align, 1),
+        I32Store16(align, _) | I64Store16(align, _) => (align, 1),
+        I32Load(align, _) | F32Load(align, _) | I64Load32S(align, _) | I64Load32U(align, _) => {
+            (align, 2)
+        }
+        I32Store(align, _) | F32Store(align, _) | I64Store32(align, _) => (align, 2),
+        I64Load(align, _) | F64Load(align, _) | I64Store(align, _) | F64Store(align, _) => {
+            (align, 3)
+        }
+        _ => return None,
+    };
+    Some(align).filter(|align| *align != natural)
+}
+
+fn float(value: f64) -> String {
+    match value {
+        value if value.is_nan() => "nan".to_string(),
+        value if value.is_infinite() && value > 0.0 => "inf".to_string(),
+        value if value.is_infinite() => "-inf".to_string(),
+        value => format!("{:?}", value),
+    }
+}
+
+fn signature(ty: &FunctionType) -> String {
+    let types = |types: &[ValueType]| types.iter().map(ValueType::to_string).collect::<Vec<_>>();
+    let mut output = String::new();
+    if !ty.params().is_empty() {
+        let _ = write!(output, " (param {})", types(ty.params()).join(" "));
+    }
+    if !ty.results().is_empty() {
+        let _ = write!(output, " (result {})", types(ty.results()).join(" "));
+    }
+    output
+}
+
+fn limits(limits: &ResizableLimits) -> String {
+    match limits.maximum() {
+        Some(maximum) => format!("{} {}", limits.initial(), maximum),
+        None => limits.initial().to_string(),
+    }
+}
+
+/// The limits in words, e.g. `2 to 16`.
+fn range(limits: &ResizableLimits) -> String {
+    match limits.maximum() {
+        Some(maximum) => format!("{} to {}", limits.initial(), maximum),
+        None => format!("at least {}", limits.initial()),
+    }
+}
+
+fn global_type(ty: ValueType, mutable: bool) -> String {
+    match mutable {
+        true => format!("(mut {})", ty),
+        false => ty.to_string(),
+    }
+}
+
+/// The constant expression, e.g. the offset of a data segment, without its `end`.
+fn init_expr(expr: &InitExpr) -> String {
+    let code = expr.code().iter().filter(|i| **i != Instruction::End);
+    let code = code.map(instruction_text).collect::<Vec<_>>();
+    format!("({})", code.join(" "))
+}
+
+/// The bytes as the contents of a string in the text format.
+fn escape(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| match byte {
+            b'"' | b'\\' => format!("\\{:02x}", byte),
+            0x20..=0x7e => (*byte as char).to_string(),
+            byte => format!("\\{:02x}", byte),
+        })
+        .collect()
+}
+
+/// Indents every line of the text.
+fn indent(text: &str, prefix: &str) -> String {
+    text.lines()
+        .map(|line| format!("{}{}", prefix, line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parity_wasm::{
+        builder,
+        elements::{BlockType, BrTableData, Instruction::*, Instructions, Local},
+    };
+
+    fn contract() -> Module {
+        let mut module = builder::module();
+        let two = module.push_signature(
+            builder::signature()
+                .with_params(vec![ValueType::I32; 2])
+                .build_sig(),
+        );
+        let body = vec![
+            GetLocal(0),
+            If(BlockType::NoResult),
+            I32Const(0),
+            I32Load8U(0, 4),
+            BrTable(Box::new(BrTableData {
+                table: Box::new([0, 1]),
+                default: 0,
+            })),
+            End,
+            I64Const(-1),
+            Drop,
+            End,
+        ];
+        module
+            .import()
+            .module("seal0")
+            .field("seal_input")
+            .external()
+            .func(two)
+            .build()
+            .import()
+            .module("env")
+            .field("memory")
+            .external()
+            .memory(2, Some(16))
+            .build()
+            .function()
+            .signature()
+            .build()
+            .body()
+            .with_locals(vec![
+                Local::new(1, ValueType::I32),
+                Local::new(1, ValueType::I64),
+            ])
+            .with_instructions(Instructions::new(body))
+            .build()
+            .build()
+            .export()
+            .field("call")
+            .internal()
+            .func(1)
+            .build()
+            .data()
+            .offset(I32Const(1024))
+            .value(b"ink\0\"".to_vec())
+            .build()
+            .build()
+    }
+
+    #[test]
+    fn prints_the_text_format() {
+        let module = contract();
+        let printer = Printer::new(&module).unwrap();
+        assert_eq!(
+            printer.module(),
+            r#"(module
+  (type (;0;) (func (param i32 i32)))
+  (type (;1;) (func))
+  (import "seal0" "seal_input" (func (;0;) (type 0) (param i32 i32)))
+  (import "env" "memory" (memory (;0;) 2 16))
+  (func (;1;) (type 1)
+    (local i32 i64)
+    local.get 0
+    if
+      i32.const 0
+      i32.load8_u offset=4
+      br_table 0 1 0
+    end
+    i64.const -1
+    drop)
+  (export "call" (func 1))
+  (data (;0;) (i32.const 1024) "ink\00\22"))"#
+        );
+        assert_eq!(printer.find("call").unwrap(), 1);
+        assert_eq!(printer.find("1").unwrap(), 1);
+        assert!(printer.find("0").is_err());
+        assert!(printer.find("transfer").is_err());
+    }
+
+    #[test]
+    fn lists_the_imports_and_exports() {
+        let module = contract();
+        let expected = "Imports:
+\tseal0::seal_input: function, contracts API version 0
+\tenv::memory: memory, 2 to 16 pages
+Exports:
+\tcall: function 1";
+        assert_eq!(symbols(&module, None), expected);
+
+        let provides = |_: &str, name: &str| name != "seal_input";
+        let flagged = symbols(&module, Some(("local".to_string(), &provides)));
+        assert!(flagged.contains("seal_input: function, contracts API version 0, NOT PROVIDED"));
+        assert!(flagged.ends_with("local does not provide 1 of the imported host functions, deploying the contract will fail"));
+    }
+}
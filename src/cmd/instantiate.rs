@@ -14,47 +14,332 @@
 // You should have received a copy of the GNU General Public License
 // along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::path::PathBuf;
+
 use anyhow::Result;
-use subxt::{balances::Balances, contracts::*, system::System, ClientBuilder, DefaultNodeRuntime};
+use codec::{Compact, Encode, Output};
+use serde::Serialize;
+use sp_core::{crypto::AccountId32, H256};
+use structopt::StructOpt;
+use subxt::{contracts::*, system::System, Call, DefaultNodeRuntime, EventsDecoder};
 
+use super::{
+    balance::BalanceVariant,
+    deployments::{self, Deployment},
+    events::ExtrinsicDetails,
+    extrinsic::Connection,
+    pallet::{ContractsVersion, Deposits},
+    sign::{self, OfflineParams, SignedExtrinsic},
+    signer::ExtrinsicSigner,
+    transcode::InkMetadata,
+};
 use crate::{ExtrinsicOpts, HexData};
 
+/// Result of a successful `instantiate`.
+#[derive(Debug, Serialize)]
+pub(crate) struct InstantiateResult {
+    /// The account id of the instantiated contract, if the inclusion of the extrinsic was awaited.
+    pub contract: Option<<DefaultNodeRuntime as System>::AccountId>,
+    /// The account id of the caller which instantiated the contract, if the inclusion of the
+    /// extrinsic was awaited.
+    pub caller: Option<<DefaultNodeRuntime as System>::AccountId>,
+    /// The account id of the contract predicted before submitting the extrinsic.
+    pub predicted: AccountId32,
+    /// Url of the contract account in the block explorer of the network, if it defines one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract_explorer: Option<String>,
+    #[serde(flatten)]
+    pub extrinsic: ExtrinsicDetails,
+}
+
+/// The constructor a contract is instantiated with, either its hex encoded data or its label and
+/// arguments in the metadata of the contract.
+#[derive(Debug, StructOpt)]
+pub(crate) struct ConstructorOpts {
+    /// Hex encoded data to call a contract constructor, instead of encoding the `--constructor`
+    /// with its `--args`
+    #[structopt(long, conflicts_with_all = &["constructor", "args"])]
+    data: Option<HexData>,
+    /// The label of the constructor to call, by default the only one or else `new`
+    #[structopt(long)]
+    constructor: Option<String>,
+    /// The arguments of the constructor, JSON values or else plain strings, e.g. `42`, `true` or
+    /// an SS58 address
+    #[structopt(long)]
+    args: Vec<String>,
+    /// Path to the metadata of the contract, defaults to `./target/ink/metadata.json`
+    #[structopt(long = "contract-metadata", parse(from_os_str))]
+    contract_metadata: Option<PathBuf>,
+}
+
+impl ConstructorOpts {
+    /// The data calling the constructor, checking with the metadata that it accepts a non-zero
+    /// `endowment`.
+    ///
+    /// The constructor of `--data` is looked up by its selector, only if the metadata is supplied.
+    pub fn data(&self, endowment: &BalanceVariant) -> Result<HexData> {
+        let (constructor, data) = match self.data {
+            Some(ref data) => {
+                let constructor = match self.contract_metadata {
+                    Some(ref path) => InkMetadata::read(path)?
+                        .constructors()?
+                        .into_iter()
+                        .find(|constructor| data.0.starts_with(&constructor.selector)),
+                    None => None,
+                };
+                (constructor, data.clone())
+            }
+            None => {
+                let metadata = InkMetadata::load(self.contract_metadata.as_deref())?
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Instantiating without --data requires the metadata of the contract to \
+                             encode the constructor, build the contract or supply \
+                             --contract-metadata"
+                        )
+                    })?;
+                let constructor = metadata.find_constructor(self.constructor.as_deref())?;
+                let data = metadata.encode_call(&constructor, &self.args)?;
+                (Some(constructor), HexData(data))
+            }
+        };
+        match constructor {
+            Some(constructor) if !constructor.payable && !endowment.is_zero() => anyhow::bail!(
+                "The constructor `{}` is not payable, remove the --endowment",
+                constructor.label
+            ),
+            _ => Ok(data),
+        }
+    }
+}
+
+/// The account id of a contract before instantiating it, as returned by `instantiate --dry-run`.
+#[derive(Debug, Serialize)]
+pub(crate) struct PredictedContract {
+    pub contract: AccountId32,
+    /// The account the contract is instantiated from.
+    pub deployer: AccountId32,
+    pub code_hash: H256,
+}
+
+impl PredictedContract {
+    /// Predicts the account id of the contract instantiated by the signer, or by the account the
+    /// call is dispatched from for `--proxy` and `--multisig-threshold`.
+    pub fn new(
+        extrinsic_opts: &ExtrinsicOpts,
+        signer: &ExtrinsicSigner,
+        version: ContractsVersion,
+        code_hash: H256,
+        data: &[u8],
+    ) -> Result<Self> {
+        let deployer = extrinsic_opts.origin(signer.account_id())?;
+        Ok(PredictedContract {
+            contract: contract_address(version, &deployer, &code_hash, data),
+            deployer,
+            code_hash,
+        })
+    }
+
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        format!(
+            "Contract account (predicted): {}\n\tDeployer: {}, code hash: {:?}",
+            self.contract, self.deployer, self.code_hash
+        )
+    }
+}
+
+/// Predicts the account of the contract without instantiating it, derived as the revision of the
+/// contracts pallet of the chain does: the one of the `--offline` chain parameters, else the one
+/// detected from the runtime metadata of the connected node.
+pub(crate) fn predict_contract(
+    extrinsic_opts: &ExtrinsicOpts,
+    signer: &ExtrinsicSigner,
+    code_hash: H256,
+    data: &[u8],
+) -> Result<PredictedContract> {
+    let version = match extrinsic_opts.offline_params()? {
+        Some(params) => params.contracts_version()?,
+        None => async_std::task::block_on(async {
+            let connection = Connection::new(&extrinsic_opts.submit).await?;
+            ContractsVersion::from_metadata(&connection.runtime_metadata().await?)
+        })?,
+    };
+    PredictedContract::new(extrinsic_opts, signer, version, code_hash, data)
+}
+
+/// Returns the account id of the contract instantiated by `deployer` with the code and the
+/// constructor data, derived like the contracts module does: the hash of the code hash, the hash
+/// of the data and the deployer, or since Substrate 3.0 the hash of the deployer, the code hash
+/// and the salt, which is always empty.
+///
+/// Neither derives a unique salt, instantiating the same code with the same data twice from one
+/// account fails.
+pub(crate) fn contract_address(
+    version: ContractsVersion,
+    deployer: &AccountId32,
+    code_hash: &H256,
+    data: &[u8],
+) -> AccountId32 {
+    let input = match version {
+        ContractsVersion::V2 => {
+            let data_hash = sp_core::blake2_256(data);
+            [code_hash.as_ref(), &data_hash[..], deployer.as_ref()].concat()
+        }
+        ContractsVersion::V3 => [deployer.as_ref(), code_hash.as_ref(), SALT].concat(),
+    };
+    AccountId32::from(sp_core::blake2_256(&input))
+}
+
+/// The salt contracts are instantiated with by the contracts pallet of Substrate 3.0.
+const SALT: &[u8] = &[];
+
+/// The `Contracts::instantiate` call in the arguments of the revision of the pallet.
+struct VersionedInstantiateCall<'a> {
+    version: ContractsVersion,
+    endowment: u128,
+    gas_limit: u64,
+    code_hash: &'a H256,
+    data: &'a [u8],
+}
+
+impl Encode for VersionedInstantiateCall<'_> {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        Compact(self.endowment).encode_to(dest);
+        Compact(self.gas_limit).encode_to(dest);
+        self.code_hash.encode_to(dest);
+        self.data.encode_to(dest);
+        if self.version == ContractsVersion::V3 {
+            SALT.encode_to(dest)
+        }
+    }
+}
+
+impl Call<DefaultNodeRuntime> for VersionedInstantiateCall<'_> {
+    const MODULE: &'static str = "Contracts";
+    const FUNCTION: &'static str = "instantiate";
+
+    fn events_decoder(decoder: &mut EventsDecoder<DefaultNodeRuntime>) {
+        decoder.with_contracts();
+    }
+}
+
 /// Instantiate a contract stored at the supplied code hash.
-/// Returns the account id of the instantiated contract if successful.
+/// Returns the account id of the instantiated contract, together with the details of the
+/// extrinsic, if successful.
 ///
 /// Creates an extrinsic with the `Contracts::instantiate` Call, submits via RPC, then waits for
 /// the `ContractsEvent::Instantiated` event.
 pub(crate) fn execute_instantiate(
     extrinsic_opts: &ExtrinsicOpts,
-    endowment: <DefaultNodeRuntime as Balances>::Balance,
+    signer: &ExtrinsicSigner,
+    endowment: BalanceVariant,
     gas_limit: u64,
     code_hash: <DefaultNodeRuntime as System>::Hash,
     data: HexData,
-) -> Result<<DefaultNodeRuntime as System>::AccountId> {
-    async_std::task::block_on(async move {
-        let cli = ClientBuilder::<DefaultNodeRuntime>::new()
-            .set_url(&extrinsic_opts.url.to_string())
-            .build()
-            .await?;
-        let signer = extrinsic_opts.signer()?;
-
-        let events = cli
-            .instantiate_and_watch(&signer, endowment, gas_limit, &code_hash, &data.0)
-            .await?;
-        let instantiated = events
-            .instantiated()?
-            .ok_or(anyhow::anyhow!("Failed to find Instantiated event"))?;
-
-        Ok(instantiated.contract)
+) -> Result<InstantiateResult> {
+    async_std::task::block_on(instantiate_contract(
+        extrinsic_opts,
+        signer,
+        endowment,
+        gas_limit,
+        code_hash,
+        &data.0,
+    ))
+}
+
+/// Instantiates the contract, see [`execute_instantiate`].
+pub(crate) async fn instantiate_contract(
+    extrinsic_opts: &ExtrinsicOpts,
+    signer: &ExtrinsicSigner,
+    endowment: BalanceVariant,
+    gas_limit: u64,
+    code_hash: <DefaultNodeRuntime as System>::Hash,
+    data: &[u8],
+) -> Result<InstantiateResult> {
+    let mut connection = Connection::new(&extrinsic_opts.submit).await?;
+    let runtime_metadata = connection.runtime_metadata().await?;
+    let version = ContractsVersion::from_metadata(&runtime_metadata)?;
+    let deposit = Deposits::from_metadata(&runtime_metadata)?.contract();
+    let predicted =
+        PredictedContract::new(extrinsic_opts, signer, version, code_hash, data)?.contract;
+    log::info!("Predicted contract account: {}", predicted);
+
+    let endowment = connection.denominate(&endowment)?;
+    let call = VersionedInstantiateCall {
+        version,
+        endowment,
+        gas_limit,
+        code_hash: &code_hash,
+        data,
+    };
+    let submitted = connection
+        .submit_reserving(call, signer, extrinsic_opts, endowment, deposit)
+        .await?;
+    let instantiated = match submitted.events() {
+        Some(events) => events.instantiated()?,
+        None => None,
+    };
+    // Only the final approval of a multisig call instantiates the contract.
+    if instantiated.is_none() && submitted.included.is_some() && submitted.multisig.is_none() {
+        anyhow::bail!("Failed to find Instantiated event")
+    }
+    if let Some(ref event) = instantiated {
+        if event.contract != predicted {
+            log::warn!(
+                "The contract was instantiated at {} instead of the predicted {}",
+                event.contract,
+                predicted
+            );
+        }
+    }
+
+    let contract = instantiated.as_ref().map(|event| event.contract.clone());
+    let extrinsic = ExtrinsicDetails::new(&submitted)?;
+    if contract.is_some() {
+        let deployment = Deployment::new(code_hash, contract.clone(), Some(data), &extrinsic);
+        deployments::record(&extrinsic_opts.submit, deployment);
+    }
+    Ok(InstantiateResult {
+        contract_explorer: connection.account_link(contract.as_ref().unwrap_or(&predicted)),
+        contract,
+        caller: instantiated.map(|event| event.caller),
+        predicted,
+        extrinsic,
     })
 }
 
+/// Sign the `Contracts::instantiate` extrinsic without submitting it.
+pub(crate) fn sign_instantiate(
+    extrinsic_opts: &ExtrinsicOpts,
+    params: &OfflineParams,
+    endowment: BalanceVariant,
+    gas_limit: u64,
+    code_hash: <DefaultNodeRuntime as System>::Hash,
+    data: HexData,
+) -> Result<SignedExtrinsic> {
+    let call = VersionedInstantiateCall {
+        version: params.contracts_version()?,
+        endowment: endowment.without_denomination()?,
+        gas_limit,
+        code_hash: &code_hash,
+        data: &data.0,
+    };
+    sign::sign_offline(call, extrinsic_opts, params)
+}
+
 #[cfg(test)]
 mod tests {
     use std::{fs, io::Write};
 
-    use crate::{cmd::deploy::execute_deploy, util::tests::with_tmp_dir, ExtrinsicOpts, HexData};
+    use super::{contract_address, BalanceVariant, ConstructorOpts, ContractsVersion};
+    use crate::{
+        cmd::deploy::execute_deploy, util::tests::with_tmp_dir, ExtrinsicOpts, HexData, OutputType,
+        SignerOpts, SubmitOpts, WaitFor,
+    };
     use assert_matches::assert_matches;
+    use serde_json::json;
+    use structopt::StructOpt;
 
     const CONTRACT: &str = r#"
 (module
@@ -63,6 +348,65 @@ mod tests {
 )
 "#;
 
+    #[test]
+    fn derives_the_contract_address() {
+        let deployer = sp_core::crypto::AccountId32::from([1; 32]);
+        let code_hash = sp_core::H256([2; 32]);
+        let address = contract_address(ContractsVersion::V2, &deployer, &code_hash, &[0xde, 0xad]);
+
+        let mut input = vec![2; 32];
+        input.extend_from_slice(&sp_core::blake2_256(&[0xde, 0xad]));
+        input.extend_from_slice(&[1; 32]);
+        assert_eq!(address, sp_core::blake2_256(&input).into());
+        assert_ne!(
+            address,
+            contract_address(ContractsVersion::V2, &deployer, &code_hash, &[])
+        );
+
+        let salted = contract_address(ContractsVersion::V3, &deployer, &code_hash, &[0xde, 0xad]);
+        let input = [[1; 32], [2; 32]].concat();
+        assert_eq!(salted, sp_core::blake2_256(&input).into());
+    }
+
+    #[test]
+    fn selects_the_constructor_and_checks_its_payability() {
+        with_tmp_dir(|path| {
+            let metadata = path.join("metadata.json");
+            let constructors = json!([
+                { "label": "default", "selector": "0x01", "args": [], "payable": false },
+                { "label": "new_with_owner", "selector": "0x02", "payable": true, "args": [
+                    { "label": "owner", "type": { "type": 0 } }
+                ] }
+            ]);
+            let types = json!([{ "id": 0, "type": { "def": { "primitive": "u8" } } }]);
+            fs::write(
+                &metadata,
+                json!({ "V3": { "spec": { "constructors": constructors }, "types": types } })
+                    .to_string(),
+            )?;
+            let opts = |args: &[&str]| {
+                let metadata = format!("--contract-metadata={}", metadata.display());
+                let args = [&["instantiate", &metadata][..], args].concat();
+                ConstructorOpts::from_iter(&args)
+            };
+            let zero = BalanceVariant::default();
+            let endowment: BalanceVariant = "1UNIT".parse()?;
+
+            let selected = opts(&["--constructor=new_with_owner", "--args=7"]);
+            assert_eq!(selected.data(&endowment)?, HexData(vec![2, 7]));
+            assert!(opts(&[]).data(&zero).is_err());
+            assert!(opts(&["--constructor=new"]).data(&zero).is_err());
+            assert_eq!(
+                opts(&["--constructor=default"]).data(&zero)?,
+                HexData(vec![1])
+            );
+            assert!(opts(&["--constructor=default"]).data(&endowment).is_err());
+            assert!(opts(&["--data=0100"]).data(&endowment).is_err());
+            assert!(opts(&["--data=0200"]).data(&endowment).is_ok());
+            Ok(())
+        })
+    }
+
     #[test]
     #[ignore] // depends on a local substrate node running
     fn instantiate_contract() {
@@ -75,17 +419,48 @@ mod tests {
 
             let url = url::Url::parse("ws://localhost:9944").unwrap();
             let extrinsic_opts = ExtrinsicOpts {
-                url,
-                suri: "//Alice".into(),
-                password: None,
+                submit: SubmitOpts {
+                    urls: vec![url],
+                    network: None,
+                    network_profile: None,
+                    output: OutputType::HumanReadable,
+                    wait_for: WaitFor::InBlock,
+                    tls_ca_cert: None,
+                    raw: false,
+                    record_deployments: false,
+                    progress: Default::default(),
+                },
+                signer_opts: SignerOpts {
+                    suri: Some("//Alice".into()),
+                    keyfile: None,
+                    password: None,
+                    password_file: None,
+                    scheme: crate::cmd::Scheme::Sr25519,
+                },
+                remote_signer: None,
+                remote_account: None,
+                qr_signer: None,
+                nonce: None,
+                lifetime: None,
+                immortal: false,
+                tip: Default::default(),
+                fee_asset: None,
+                sudo: false,
+                proxy: None,
+                multisig: Default::default(),
+                offline: Default::default(),
+                yes: false,
             };
-            let code_hash =
-                execute_deploy(&extrinsic_opts, Some(&wasm_path)).expect("Deploy should succeed");
+            let signer = extrinsic_opts.signer()?;
+            let code_hash = execute_deploy(&extrinsic_opts, &signer, Some(&wasm_path))
+                .expect("Deploy should succeed")
+                .code_hash;
 
             let gas_limit = 500_000_000;
             let result = super::execute_instantiate(
                 &extrinsic_opts,
-                100000000000000,
+                &signer,
+                BalanceVariant::Default(100000000000000),
                 gas_limit,
                 code_hash,
                 HexData::default(),
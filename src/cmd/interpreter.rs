@@ -0,0 +1,448 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Executes the Wasm of contracts with wasmi, the interpreter `pallet-contracts` executes them
+//! with, metering the instructions the way it does.
+
+use std::{cell::RefCell, fmt};
+
+use anyhow::{Context, Result};
+use parity_wasm::elements::Module;
+use wasmi::{
+    memory_units::Pages, Externals, FuncInstance, FuncRef, GlobalDescriptor, GlobalRef, HostError,
+    ImportResolver, MemoryDescriptor, MemoryInstance, MemoryRef, ModuleInstance, ModuleRef,
+    RuntimeArgs, RuntimeValue, Signature, TableDescriptor, TableRef, Trap, TrapKind, ValueType,
+};
+
+/// The module of the function charging the instructions of each block, which is injected into
+/// the Wasm.
+const GAS_MODULE: &str = "__sandbox";
+
+/// Why the execution stopped before returning.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Stop {
+    /// The code trapped, or called a host function with invalid arguments.
+    Trap(String),
+    /// A host function ended the execution, e.g. `seal_return`.
+    Exit,
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Trap(reason) => f.write_str(reason),
+            Stop::Exit => f.write_str("Exited"),
+        }
+    }
+}
+
+impl HostError for Stop {}
+
+fn trap<T>(reason: impl Into<String>) -> Result<T, Stop> {
+    Err(Stop::Trap(reason.into()))
+}
+
+/// The functions the instance imports from its host.
+pub(crate) trait Host {
+    /// Calls the function `name` imported from `module`, with its arguments as the raw bits of
+    /// their values.
+    fn call(
+        &mut self,
+        module: &str,
+        name: &str,
+        args: &[u64],
+        memory: &mut Memory,
+        steps_left: u64,
+    ) -> Result<Option<u64>, Stop>;
+}
+
+/// The linear memory of an instance, `None` if it has none.
+#[derive(Clone)]
+pub(crate) struct Memory(Option<MemoryRef>);
+
+impl Memory {
+    fn memory(&self) -> Result<&MemoryRef, Stop> {
+        self.0
+            .as_ref()
+            .ok_or_else(|| Stop::Trap("The Wasm has no memory".into()))
+    }
+
+    pub fn read(&self, ptr: u32, len: u32) -> Result<Vec<u8>, Stop> {
+        self.memory()?
+            .get(ptr, len as usize)
+            .or_else(|_| trap(format!("Out of bounds memory access at {}", ptr)))
+    }
+
+    pub fn write(&mut self, ptr: u32, data: &[u8]) -> Result<(), Stop> {
+        self.memory()?
+            .set(ptr, data)
+            .or_else(|_| trap(format!("Out of bounds memory access at {}", ptr)))
+    }
+
+    pub fn read_u32(&self, ptr: u32) -> Result<u32, Stop> {
+        self.memory()?
+            .get_value(ptr)
+            .or_else(|_| trap(format!("Out of bounds memory access at {}", ptr)))
+    }
+
+    pub fn write_u32(&mut self, ptr: u32, value: u32) -> Result<(), Stop> {
+        self.memory()?
+            .set_value(ptr, value)
+            .or_else(|_| trap(format!("Out of bounds memory access at {}", ptr)))
+    }
+}
+
+/// A function imported by the module.
+struct Import {
+    module: String,
+    name: String,
+    signature: Signature,
+}
+
+/// Resolves the functions imported by the module to host functions, and allocates the memory it
+/// imports.
+#[derive(Default)]
+struct Resolver {
+    imports: RefCell<Vec<Import>>,
+    memory: RefCell<Option<MemoryRef>>,
+}
+
+impl ImportResolver for Resolver {
+    fn resolve_func(
+        &self,
+        module: &str,
+        name: &str,
+        signature: &Signature,
+    ) -> Result<FuncRef, wasmi::Error> {
+        let mut imports = self.imports.borrow_mut();
+        imports.push(Import {
+            module: module.to_string(),
+            name: name.to_string(),
+            signature: signature.clone(),
+        });
+        Ok(FuncInstance::alloc_host(
+            signature.clone(),
+            imports.len() - 1,
+        ))
+    }
+
+    fn resolve_global(
+        &self,
+        module: &str,
+        name: &str,
+        _: &GlobalDescriptor,
+    ) -> Result<GlobalRef, wasmi::Error> {
+        Err(wasmi::Error::Instantiation(format!(
+            "Importing the global {}::{} is not supported, only functions and the memory",
+            module, name
+        )))
+    }
+
+    fn resolve_memory(
+        &self,
+        _: &str,
+        _: &str,
+        descriptor: &MemoryDescriptor,
+    ) -> Result<MemoryRef, wasmi::Error> {
+        let maximum = descriptor.maximum().map(|pages| Pages(pages as usize));
+        let memory = MemoryInstance::alloc(Pages(descriptor.initial() as usize), maximum)?;
+        *self.memory.borrow_mut() = Some(memory.clone());
+        Ok(memory)
+    }
+
+    fn resolve_table(
+        &self,
+        module: &str,
+        name: &str,
+        _: &TableDescriptor,
+    ) -> Result<TableRef, wasmi::Error> {
+        Err(wasmi::Error::Instantiation(format!(
+            "Importing the table {}::{} is not supported, only functions and the memory",
+            module, name
+        )))
+    }
+}
+
+/// Forwards the calls of imported functions to the host, and charges the instructions executed.
+struct Environment<'a> {
+    imports: &'a [Import],
+    host: &'a mut dyn Host,
+    memory: &'a mut Memory,
+    steps_left: &'a mut u64,
+}
+
+impl Externals for Environment<'_> {
+    fn invoke_index(
+        &mut self,
+        index: usize,
+        args: RuntimeArgs,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let import = &self.imports[index];
+        let args = args
+            .as_ref()
+            .iter()
+            .map(|arg| match arg {
+                RuntimeValue::I32(value) => Ok(*value as u32 as u64),
+                RuntimeValue::I64(value) => Ok(*value as u64),
+                _ => trap("Floating point arguments are not supported"),
+            })
+            .collect::<Result<Vec<_>, Stop>>()?;
+        if import.module == GAS_MODULE {
+            let steps = args.first().copied().unwrap_or_default();
+            if steps > *self.steps_left {
+                *self.steps_left = 0;
+                return Err(Stop::Trap("Ran out of gas".into()).into());
+            }
+            *self.steps_left -= steps;
+            return Ok(None);
+        }
+        let result = self.host.call(
+            &import.module,
+            &import.name,
+            &args,
+            self.memory,
+            *self.steps_left,
+        )?;
+        Ok(match (result, import.signature.return_type()) {
+            (Some(value), Some(ValueType::I64)) => Some(RuntimeValue::I64(value as i64)),
+            (Some(value), Some(_)) => Some(RuntimeValue::I32(value as u32 as i32)),
+            (_, _) => None,
+        })
+    }
+}
+
+/// The reason of a trap of the Wasm code.
+fn describe(kind: &TrapKind) -> Stop {
+    let reason = match kind {
+        TrapKind::Host(stop) => {
+            return match stop.downcast_ref::<Stop>() {
+                Some(stop) => stop.clone(),
+                None => Stop::Trap(stop.to_string()),
+            }
+        }
+        TrapKind::Unreachable => "Executed an unreachable instruction",
+        TrapKind::MemoryAccessOutOfBounds => "Out of bounds memory access",
+        TrapKind::TableAccessOutOfBounds => "Out of bounds table access",
+        TrapKind::ElemUninitialized => "Called an uninitialized table element",
+        TrapKind::DivisionByZero => "Integer division by zero",
+        TrapKind::InvalidConversionToInt => "Integer overflow",
+        TrapKind::StackOverflow => "Stack overflow",
+        TrapKind::UnexpectedSignature => "Indirect call with an unexpected signature",
+    };
+    Stop::Trap(reason.to_string())
+}
+
+/// An instance of a Wasm module, executed by wasmi.
+///
+/// Floating point instructions are rejected, since contracts must not use them.
+pub(crate) struct Instance {
+    module: ModuleRef,
+    imports: Vec<Import>,
+    memory: Memory,
+    steps_left: u64,
+    max_steps: u64,
+}
+
+impl Instance {
+    /// Instantiates the module, which is allowed to execute up to `max_steps` instructions.
+    ///
+    /// The instructions are charged at the start of each block, as `pallet-contracts` charges
+    /// them, so an execution running out of them stops before the block.
+    pub fn new(wasm: &[u8], max_steps: u64) -> Result<Self> {
+        let module: Module =
+            parity_wasm::deserialize_buffer(wasm).context("Failed to parse the Wasm")?;
+        let rules = pwasm_utils::rules::Set::default().with_forbidden_floats();
+        let module = pwasm_utils::inject_gas_counter(module, &rules, GAS_MODULE)
+            .map_err(|_| anyhow::anyhow!("The Wasm uses floating point instructions"))?;
+        let wasm = parity_wasm::serialize(module).context("Failed to serialize the Wasm")?;
+        let module = wasmi::Module::from_buffer(wasm)
+            .map_err(|err| anyhow::anyhow!("Invalid Wasm: {}", err))?;
+        let resolver = Resolver::default();
+        let instance = ModuleInstance::new(&module, &resolver)
+            .map_err(|err| anyhow::anyhow!("Failed to instantiate the Wasm: {}", err))?;
+        if instance.has_start() {
+            anyhow::bail!("Start functions are not supported")
+        }
+        let instance = instance.assert_no_start();
+        let memory = match resolver.memory.into_inner() {
+            Some(memory) => Some(memory),
+            None => instance
+                .export_by_name("memory")
+                .and_then(|export| export.as_memory().cloned()),
+        };
+        Ok(Instance {
+            module: instance,
+            imports: resolver.imports.into_inner(),
+            memory: Memory(memory),
+            steps_left: max_steps,
+            max_steps,
+        })
+    }
+
+    /// Calls the exported function, which takes no arguments and returns nothing, like the
+    /// `deploy` and `call` functions of contracts.
+    pub fn invoke(&mut self, export: &str, host: &mut dyn Host) -> Result<(), Stop> {
+        if self.module.export_by_name(export).is_none() {
+            return trap(format!("The Wasm exports no `{}` function", export));
+        }
+        let mut environment = Environment {
+            imports: &self.imports,
+            host,
+            memory: &mut self.memory,
+            steps_left: &mut self.steps_left,
+        };
+        match self.module.invoke_export(export, &[], &mut environment) {
+            Ok(_) => Ok(()),
+            Err(wasmi::Error::Trap(trap)) => Err(describe(trap.kind())),
+            Err(err) => trap(err.to_string()),
+        }
+    }
+
+    /// The number of instructions executed so far.
+    pub fn steps(&self) -> u64 {
+        self.max_steps - self.steps_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parity_wasm::{
+        builder,
+        elements::{BlockType, Instruction, Instructions, Local, ValueType},
+    };
+
+    /// A host recording the values passed to its `env::log`.
+    #[derive(Default)]
+    struct Log(Vec<u64>);
+
+    impl Host for Log {
+        fn call(
+            &mut self,
+            _: &str,
+            _: &str,
+            args: &[u64],
+            _: &mut Memory,
+            _: u64,
+        ) -> Result<Option<u64>, Stop> {
+            self.0.extend(args);
+            Ok(None)
+        }
+    }
+
+    /// A module exporting `run`, which passes the result of the `code` to `env::log`.
+    fn module(code: Vec<Instruction>, locals: Vec<ValueType>) -> Vec<u8> {
+        use Instruction::*;
+        let mut body = code;
+        body.extend([Call(0), End]);
+        let mut module = builder::module();
+        let log =
+            module.push_signature(builder::signature().with_param(ValueType::I64).build_sig());
+        let module = module
+            .import()
+            .module("env")
+            .field("log")
+            .external()
+            .func(log)
+            .build()
+            .function()
+            .signature()
+            .build()
+            .body()
+            .with_locals(locals.into_iter().map(|ty| Local::new(1, ty)).collect())
+            .with_instructions(Instructions::new(body))
+            .build()
+            .build()
+            .export()
+            .field("run")
+            .internal()
+            .func(1)
+            .build()
+            .memory()
+            .with_min(1)
+            .build()
+            .build();
+        parity_wasm::serialize(module).unwrap()
+    }
+
+    fn run(code: Vec<Instruction>, locals: Vec<ValueType>) -> Result<Vec<u64>, Stop> {
+        let mut instance = Instance::new(&module(code, locals), 10_000).unwrap();
+        let mut log = Log::default();
+        instance.invoke("run", &mut log)?;
+        Ok(log.0)
+    }
+
+    #[test]
+    fn executes_loops_and_branches() {
+        use Instruction::*;
+        // Sums 1 to 10 in a loop, and selects the sum in an if.
+        let code = vec![
+            Block(BlockType::NoResult),
+            Loop(BlockType::NoResult),
+            GetLocal(0),
+            I64Const(1),
+            I64Add,
+            SetLocal(0),
+            GetLocal(1),
+            GetLocal(0),
+            I64Add,
+            SetLocal(1),
+            GetLocal(0),
+            I64Const(10),
+            I64GeU,
+            BrIf(1),
+            Br(0),
+            End,
+            End,
+            I32Const(1),
+            If(BlockType::Value(ValueType::I64)),
+            GetLocal(1),
+            Else,
+            I64Const(0),
+            End,
+        ];
+        assert_eq!(
+            run(code, vec![ValueType::I64, ValueType::I64]),
+            Ok(vec![55])
+        );
+    }
+
+    #[test]
+    fn traps_and_accesses_the_memory() {
+        use Instruction::*;
+        let code = vec![
+            I32Const(8),
+            I64Const(-2),
+            I64Store(3, 0),
+            I32Const(0),
+            I32Load8S(0, 8),
+            I64ExtendSI32,
+        ];
+        assert_eq!(run(code, vec![]), Ok(vec![-2i64 as u64]));
+        let code = vec![I32Const(1), I32Const(0), I32DivU, I64ExtendUI32];
+        assert_eq!(
+            run(code, vec![]),
+            Err(Stop::Trap("Integer division by zero".to_string()))
+        );
+        let code = vec![I32Const(65536), I64Load(3, 0)];
+        assert!(run(code, vec![]).is_err());
+        let code = vec![Loop(BlockType::NoResult), Br(0), End, I64Const(0)];
+        assert_eq!(
+            run(code, vec![]),
+            Err(Stop::Trap("Ran out of gas".to_string()))
+        );
+    }
+}
@@ -0,0 +1,239 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{convert::TryInto, fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use xsalsa20poly1305::{
+    aead::{Aead, NewAead},
+    Key, Nonce, XSalsa20Poly1305,
+};
+
+use super::signer::Scheme;
+
+/// Start of the PKCS#8 encoded key pair, followed by the secret key.
+const PKCS8_HEADER: [u8; 16] = [48, 83, 2, 1, 1, 48, 5, 6, 3, 43, 101, 112, 4, 34, 4, 32];
+/// Separates the secret from the public key in the PKCS#8 encoded key pair.
+const PKCS8_DIVIDER: [u8; 5] = [161, 35, 3, 33, 0];
+/// Length of the scrypt salt and parameters prepended to the encrypted key pair.
+const SCRYPT_LENGTH: usize = 32 + 3 * 4;
+const NONCE_LENGTH: usize = 24;
+
+/// A key pair as exported from polkadot.js, e.g. via the Polkadot Apps UI.
+#[derive(Debug, Deserialize)]
+struct KeystoreJson {
+    /// The base64 encoded, usually encrypted, PKCS#8 key pair.
+    encoded: String,
+    encoding: Encoding,
+}
+
+#[derive(Debug, Deserialize)]
+struct Encoding {
+    /// The key encoding followed by the signature scheme, e.g. `["pkcs8", "sr25519"]`.
+    content: Vec<String>,
+    /// The encryption applied, e.g. `["scrypt", "xsalsa20-poly1305"]`, or `"none"`.
+    #[serde(rename = "type")]
+    encryption: Encryption,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Encryption {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Encryption {
+    fn contains(&self, kind: &str) -> bool {
+        match self {
+            Encryption::One(encryption) => encryption == kind,
+            Encryption::Many(encryptions) => encryptions.iter().any(|e| e == kind),
+        }
+    }
+}
+
+/// The secret key of a key pair loaded from a keystore file.
+pub(crate) struct KeystoreSecret {
+    pub scheme: Scheme,
+    /// The secret key in the format exported by polkadot.js: for `sr25519` the 64 byte ed25519
+    /// compatible secret key, for `ed25519` the seed followed by the public key, and for
+    /// `ecdsa` the 32 byte secret.
+    pub secret: Vec<u8>,
+    pub public: Vec<u8>,
+}
+
+/// Returns true if the key pair in the keystore file is encrypted, and requires a password.
+pub(crate) fn is_encrypted(path: &Path) -> Result<bool> {
+    Ok(read_keystore(path)?
+        .encoding
+        .encryption
+        .contains("xsalsa20-poly1305"))
+}
+
+/// Loads the secret key from the keystore file, decrypting it with the password.
+pub(crate) fn load(path: &Path, password: Option<&str>) -> Result<KeystoreSecret> {
+    let keystore = read_keystore(path)?;
+    let scheme = match keystore.encoding.content.get(1).map(String::as_str) {
+        Some("sr25519") => Scheme::Sr25519,
+        Some("ed25519") => Scheme::Ed25519,
+        Some("ecdsa") | Some("ethereum") => Scheme::Ecdsa,
+        other => anyhow::bail!("Unsupported key type {:?} in {}", other, path.display()),
+    };
+    let encoded =
+        base64::decode(keystore.encoded.trim()).context("Keystore should be base64 encoded")?;
+    let pkcs8 = if keystore.encoding.encryption.contains("xsalsa20-poly1305") {
+        let password = password.ok_or_else(|| {
+            anyhow::anyhow!("A password is required to decrypt {}", path.display())
+        })?;
+        decrypt(
+            &encoded,
+            password,
+            keystore.encoding.encryption.contains("scrypt"),
+        )
+        .context(format!("Failed to decrypt {}", path.display()))?
+    } else {
+        encoded
+    };
+    decode_pkcs8(&pkcs8, scheme).context(format!("Invalid key pair in {}", path.display()))
+}
+
+fn read_keystore(path: &Path) -> Result<KeystoreJson> {
+    let json = fs::read(path).context(format!("Failed to read {}", path.display()))?;
+    serde_json::from_slice(&json).context(format!("Failed to parse keystore {}", path.display()))
+}
+
+/// Decrypts the key pair, deriving the key from the password with scrypt if `scrypt` is set.
+fn decrypt(encoded: &[u8], password: &str, scrypt: bool) -> Result<Vec<u8>> {
+    let mut key = [0u8; 32];
+    let encrypted = if scrypt {
+        if encoded.len() < SCRYPT_LENGTH {
+            anyhow::bail!("Missing scrypt parameters")
+        }
+        let (salt, rest) = encoded.split_at(32);
+        let param = |index: usize| {
+            u32::from_le_bytes(rest[index * 4..index * 4 + 4].try_into().expect("4 bytes"))
+        };
+        let (n, p, r) = (param(0), param(1), param(2));
+        if !n.is_power_of_two() {
+            anyhow::bail!("Invalid scrypt parameter N = {}", n)
+        }
+        let params = scrypt::ScryptParams::new(n.trailing_zeros() as u8, r, p)
+            .map_err(|_| anyhow::anyhow!("Invalid scrypt parameters"))?;
+        scrypt::scrypt(password.as_bytes(), salt, &params, &mut key)
+            .map_err(|_| anyhow::anyhow!("Failed to derive the key from the password"))?;
+        &encoded[SCRYPT_LENGTH..]
+    } else {
+        // Older keystores use the password itself as the key, padded with zeros.
+        let password = password.as_bytes();
+        let len = password.len().min(key.len());
+        key[..len].copy_from_slice(&password[..len]);
+        encoded
+    };
+    if encrypted.len() < NONCE_LENGTH {
+        anyhow::bail!("Missing nonce")
+    }
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+    XSalsa20Poly1305::new(Key::from_slice(&key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Invalid password"))
+}
+
+/// Splits the PKCS#8 encoded key pair into its secret and public key.
+fn decode_pkcs8(pkcs8: &[u8], scheme: Scheme) -> Result<KeystoreSecret> {
+    let secret_len = match scheme {
+        Scheme::Sr25519 | Scheme::Ed25519 => 64,
+        Scheme::Ecdsa => 32,
+    };
+    if !pkcs8.starts_with(&PKCS8_HEADER) {
+        anyhow::bail!("Invalid PKCS#8 header")
+    }
+    let rest = &pkcs8[PKCS8_HEADER.len()..];
+    if rest.len() < secret_len + PKCS8_DIVIDER.len() {
+        anyhow::bail!("Secret key too short")
+    }
+    let (secret, rest) = rest.split_at(secret_len);
+    if !rest.starts_with(&PKCS8_DIVIDER) {
+        anyhow::bail!("Invalid PKCS#8 divider")
+    }
+    Ok(KeystoreSecret {
+        scheme,
+        secret: secret.to_vec(),
+        public: rest[PKCS8_DIVIDER.len()..].to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes a keystore file as polkadot.js does, with cheap scrypt parameters.
+    fn encode_keystore(secret: &[u8], public: &[u8], password: &str) -> String {
+        let mut pkcs8 = PKCS8_HEADER.to_vec();
+        pkcs8.extend_from_slice(secret);
+        pkcs8.extend_from_slice(&PKCS8_DIVIDER);
+        pkcs8.extend_from_slice(public);
+
+        let salt = [7u8; 32];
+        let (n, p, r) = (1u32 << 4, 1u32, 8u32);
+        let mut key = [0u8; 32];
+        let params = scrypt::ScryptParams::new(4, r, p).unwrap();
+        scrypt::scrypt(password.as_bytes(), &salt, &params, &mut key).unwrap();
+        let nonce = [9u8; NONCE_LENGTH];
+        let ciphertext = XSalsa20Poly1305::new(Key::from_slice(&key))
+            .encrypt(Nonce::from_slice(&nonce), &pkcs8[..])
+            .unwrap();
+
+        let mut encoded = salt.to_vec();
+        for param in &[n, p, r] {
+            encoded.extend_from_slice(&param.to_le_bytes());
+        }
+        encoded.extend_from_slice(&nonce);
+        encoded.extend_from_slice(&ciphertext);
+        serde_json::json!({
+            "encoded": base64::encode(&encoded),
+            "encoding": {
+                "content": ["pkcs8", "ed25519"],
+                "type": ["scrypt", "xsalsa20-poly1305"],
+                "version": "3"
+            },
+            "address": "",
+            "meta": {}
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn decrypts_keystore() {
+        crate::util::tests::with_tmp_dir(|path| {
+            let keystore_path = path.join("account.json");
+            fs::write(
+                &keystore_path,
+                encode_keystore(&[1u8; 64], &[2u8; 32], "secret"),
+            )?;
+
+            assert!(is_encrypted(&keystore_path)?);
+            let secret = load(&keystore_path, Some("secret"))?;
+            assert_eq!(secret.scheme, Scheme::Ed25519);
+            assert_eq!(secret.secret, vec![1u8; 64]);
+            assert_eq!(secret.public, vec![2u8; 32]);
+
+            assert!(load(&keystore_path, Some("wrong")).is_err());
+            assert!(load(&keystore_path, None).is_err());
+            Ok(())
+        })
+    }
+}
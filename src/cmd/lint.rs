@@ -0,0 +1,105 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    convert::TryFrom,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use colored::Colorize;
+use structopt::StructOpt;
+
+use crate::{util, workspace::ManifestPath, Verbosity, VerbosityFlags};
+
+/// The repository of the ink! lints, loaded by `cargo dylint`.
+const INK_LINTING_REPO: &str = "https://github.com/paritytech/ink";
+
+/// The directory of the ink! lints in their repository.
+const INK_LINTING_PATTERN: &str = "linting";
+
+/// Runs the ink! specific lints on the contract, e.g. for messages taking `&mut self` without
+/// mutating the storage, or constructors transferring value which are not `payable`.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "lint")]
+pub(crate) struct LintCommand {
+    /// Path to the Cargo.toml of the contract to lint
+    #[structopt(long, parse(from_os_str))]
+    manifest_path: Option<PathBuf>,
+    #[structopt(flatten)]
+    verbosity: VerbosityFlags,
+}
+
+impl LintCommand {
+    pub fn exec(&self) -> Result<String> {
+        let manifest_path = ManifestPath::try_from(self.manifest_path.as_ref())?;
+        let verbosity = TryFrom::<&VerbosityFlags>::try_from(&self.verbosity)?;
+        execute_lint(&manifest_path, verbosity)?;
+        Ok("No ink! lints were violated".to_string())
+    }
+}
+
+/// The arguments of `cargo dylint`, passing the manifest on to `cargo check`.
+fn dylint_args(manifest_path: &ManifestPath) -> Vec<String> {
+    vec![
+        "--git".to_string(),
+        INK_LINTING_REPO.to_string(),
+        "--pattern".to_string(),
+        INK_LINTING_PATTERN.to_string(),
+        "--".to_string(),
+        manifest_path.cargo_arg(),
+    ]
+}
+
+/// Runs the ink! lints with `cargo dylint`, failing if it reports an error.
+pub(crate) fn execute_lint(
+    manifest_path: &ManifestPath,
+    verbosity: Option<Verbosity>,
+) -> Result<()> {
+    if which::which("cargo-dylint").is_err() {
+        anyhow::bail!(
+            "Linting requires cargo-dylint, install it with `cargo install cargo-dylint dylint-link`"
+        )
+    }
+    if !matches!(verbosity, Some(Verbosity::Quiet)) {
+        println!(" {}", "Running the ink! lints".bright_green().bold());
+    }
+    // The manifest path is relative to the current directory.
+    util::invoke_cargo(
+        "dylint",
+        dylint_args(manifest_path),
+        None::<&Path>,
+        verbosity,
+        &[],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_the_manifest_to_cargo_check() {
+        let manifest_path = ManifestPath::new("contracts/flipper/Cargo.toml").unwrap();
+        let args = dylint_args(&manifest_path);
+        assert_eq!(args[..2], ["--git", INK_LINTING_REPO]);
+        assert_eq!(
+            args[4..],
+            ["--", "--manifest-path=contracts/flipper/Cargo.toml"]
+        );
+    }
+}
@@ -16,12 +16,16 @@
 
 use crate::{
     crate_metadata::CrateMetadata,
+    progress::Reporter,
     util,
     workspace::{ManifestPath, Workspace},
-    BuildArtifacts, BuildResult, OptimizationResult, UnstableFlags, Verbosity,
+    BuildArtifacts, BuildResult, CargoOptions, OptimizationPasses, OptimizationResult, Target,
+    UnstableFlags, Verbosity,
 };
 
-use anyhow::Result;
+use super::wasm_opt::WasmOpt;
+
+use anyhow::{Context, Result};
 use blake2::digest::{Update as _, VariableOutput as _};
 use colored::Colorize;
 use contract_metadata::{
@@ -29,17 +33,140 @@ use contract_metadata::{
     SourceLanguage, SourceWasm, User,
 };
 use semver::Version;
-use std::{fs, path::PathBuf};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Instant, SystemTime},
+};
+use structopt::StructOpt;
 use url::Url;
 
-const METADATA_FILE: &str = "metadata.json";
+pub(crate) const METADATA_FILE: &str = "metadata.json";
+
+/// Serializes the runs of the `metadata-gen` binary of concurrent builds, which share its path in
+/// the target directory.
+static METADATA_GEN: Mutex<()> = Mutex::new(());
+
+/// Suffix of the hidden file in the target directory caching the output of the `metadata-gen`
+/// crate for the fingerprint of its inputs, after the name of the contract.
+const METADATA_GEN_CACHE: &str = "metadata-gen.json";
+
+/// The output of the `metadata-gen` crate of the last build.
+#[derive(Deserialize, Serialize)]
+struct MetadataGenCache {
+    fingerprint: String,
+    ink_meta: Map<String, Value>,
+}
+
+impl MetadataGenCache {
+    /// The cached output of the `metadata-gen` crate, if its fingerprint matches.
+    fn load(path: &Path, fingerprint: &str) -> Option<Map<String, Value>> {
+        let cache = fs::read(path).ok()?;
+        let cache: MetadataGenCache = serde_json::from_slice(&cache).ok()?;
+        if cache.fingerprint == fingerprint {
+            Some(cache.ink_meta)
+        } else {
+            None
+        }
+    }
+}
+
+/// The fields of the `contract` section of the metadata overriding those taken from the
+/// `[package]` of the Cargo.toml, e.g. to publish a contract under another name than its crate.
+#[derive(Clone, Debug, Default, StructOpt)]
+pub(crate) struct ContractFields {
+    /// The name of the contract in the metadata, instead of the name of the package
+    #[structopt(long = "contract-name")]
+    name: Option<String>,
+    /// The version of the contract in the metadata, instead of the version of the package
+    #[structopt(long = "contract-version")]
+    version: Option<Version>,
+    /// An author of the contract in the metadata, replacing the authors of the package. Supply it
+    /// once for each author
+    #[structopt(long = "contract-author", number_of_values = 1)]
+    authors: Vec<String>,
+    /// The description of the contract in the metadata
+    #[structopt(long = "contract-description")]
+    description: Option<String>,
+    /// The URL of the documentation of the contract in the metadata
+    #[structopt(long = "contract-documentation")]
+    documentation: Option<Url>,
+    /// The URL of the repository of the contract in the metadata
+    #[structopt(long = "contract-repository")]
+    repository: Option<Url>,
+    /// The URL of the homepage of the contract in the metadata
+    #[structopt(long = "contract-homepage")]
+    homepage: Option<Url>,
+    /// The SPDX license expression of the contract in the metadata
+    #[structopt(long = "contract-license")]
+    license: Option<String>,
+}
+
+impl ContractFields {
+    /// The arguments overriding the same fields, e.g. for the build in a container.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut push = |flag: &str, value: Option<String>| {
+            if let Some(value) = value {
+                args.push(format!("--contract-{}", flag));
+                args.push(value);
+            }
+        };
+        push("name", self.name.clone());
+        push("version", self.version.as_ref().map(Version::to_string));
+        for author in &self.authors {
+            push("author", Some(author.clone()));
+        }
+        push("description", self.description.clone());
+        push(
+            "documentation",
+            self.documentation.as_ref().map(Url::to_string),
+        );
+        push("repository", self.repository.as_ref().map(Url::to_string));
+        push("homepage", self.homepage.as_ref().map(Url::to_string));
+        push("license", self.license.clone());
+        args
+    }
+}
 
 /// Executes the metadata generation process
 struct GenerateMetadataCommand {
     crate_metadata: CrateMetadata,
+    contract_fields: ContractFields,
     verbosity: Option<Verbosity>,
     build_artifact: BuildArtifacts,
+    optimization_passes: OptimizationPasses,
+    keep_debug_symbols: bool,
+    cargo_options: CargoOptions,
     unstable_options: UnstableFlags,
+    wasm_opt: WasmOpt,
+    progress: Reporter,
+}
+
+/// The environment of the build, recorded in the `source` section of the metadata so that anyone
+/// holding the artifacts can reproduce or audit the build.
+#[derive(Serialize)]
+struct BuildInfo {
+    rust_toolchain: String,
+    cargo_contract_version: String,
+    /// The architecture the contract is compiled for, `wasm` or `riscv`.
+    target: String,
+    /// The settings of the optimizer, which only runs on Wasm.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wasm_opt_settings: Option<WasmOptSettings>,
+    /// Hex encoded blake2 hash of the `Cargo.lock` pinning the versions of the dependencies.
+    cargo_lock_hash: Option<String>,
+}
+
+/// The binaryen optimizer and the settings the Wasm was optimized with.
+#[derive(Serialize)]
+struct WasmOptSettings {
+    version: String,
+    optimization_passes: String,
+    keep_debug_symbols: bool,
 }
 
 /// Result of generating the extended contract project metadata
@@ -53,7 +180,7 @@ struct ExtendedMetadataResult {
 
 impl GenerateMetadataCommand {
     pub fn exec(&self) -> Result<BuildResult> {
-        util::assert_channel()?;
+        let started = Instant::now();
 
         let target_directory = self.crate_metadata.target_directory.clone();
         let out_path_metadata = target_directory.join(METADATA_FILE);
@@ -71,28 +198,64 @@ impl GenerateMetadataCommand {
         } = self.extended_metadata()?;
 
         let generate_metadata = |manifest_path: &ManifestPath| -> Result<()> {
-            let mut current_progress = 4;
-            println!(
-                " {} {}",
-                format!("[{}/{}]", current_progress, self.build_artifact.steps()).bold(),
-                "Generating metadata".bright_green().bold()
-            );
-            let target_dir_arg = format!("--target-dir={}", target_directory.to_string_lossy());
-            let stdout = util::invoke_cargo(
-                "run",
-                &[
-                    "--package",
-                    "metadata-gen",
-                    &manifest_path.cargo_arg(),
-                    &target_dir_arg,
-                    "--release",
-                ],
-                self.crate_metadata.manifest_path.directory(),
+            let mut current_progress = match self.build_artifact {
+                BuildArtifacts::MetadataOnly => 1,
+                _ => 4,
+            };
+            util::print_step(
                 self.verbosity,
-            )?;
-
-            let ink_meta: serde_json::Map<String, serde_json::Value> =
-                serde_json::from_slice(&stdout)?;
+                &self.progress,
+                &self.crate_metadata.package_name,
+                current_progress,
+                self.build_artifact.steps(),
+                "Generating metadata",
+            );
+            let mut args = vec![
+                "--package".to_string(),
+                "metadata-gen".to_string(),
+                manifest_path.cargo_arg(),
+                format!("--target-dir={}", target_directory.to_string_lossy()),
+                "--release".to_string(),
+            ];
+            // The metadata package depends on the contract as `contract`.
+            let features = self.crate_metadata.root_package.features.keys();
+            let metadata_gen_args = self.cargo_options.metadata_gen_args("contract", features);
+            args.extend(metadata_gen_args.iter().cloned());
+
+            let cache_path = target_directory.join(format!(
+                ".{}.{}",
+                self.crate_metadata.package_name, METADATA_GEN_CACHE
+            ));
+            let fingerprint = self.metadata_gen_fingerprint(&metadata_gen_args)?;
+            let ink_meta = match MetadataGenCache::load(&cache_path, &fingerprint) {
+                Some(ink_meta) => {
+                    log::info!("The sources are unchanged, reusing the generated metadata");
+                    ink_meta
+                }
+                None => {
+                    let _running = METADATA_GEN.lock().unwrap_or_else(|err| err.into_inner());
+                    let stdout = util::invoke_cargo(
+                        "run",
+                        &args,
+                        self.crate_metadata.manifest_path.directory(),
+                        self.verbosity,
+                        &[],
+                    )
+                    .context(
+                        "Failed to build and run the generated `metadata-gen` crate, pass \
+                         `--verbose` to see its build and keep its temporary workspace",
+                    )?;
+                    let ink_meta: Map<String, Value> = serde_json::from_slice(&stdout)
+                        .context("The `metadata-gen` crate printed no valid metadata")?;
+                    let cache = MetadataGenCache {
+                        fingerprint,
+                        ink_meta,
+                    };
+                    fs::write(&cache_path, serde_json::to_string(&cache)?)?;
+                    cache.ink_meta
+                }
+            };
+            super::metadata_validate::check_selectors(&ink_meta)?;
             let metadata = ContractMetadata::new(source, contract, user, ink_meta);
             {
                 let mut metadata = metadata.clone();
@@ -103,10 +266,13 @@ impl GenerateMetadataCommand {
             }
 
             if self.build_artifact == BuildArtifacts::All {
-                println!(
-                    " {} {}",
-                    format!("[{}/{}]", current_progress, self.build_artifact.steps()).bold(),
-                    "Generating bundle".bright_green().bold()
+                util::print_step(
+                    self.verbosity,
+                    &self.progress,
+                    &self.crate_metadata.package_name,
+                    current_progress,
+                    self.build_artifact.steps(),
+                    "Generating bundle",
                 );
                 let contents = serde_json::to_string(&metadata)?;
                 fs::write(&out_path_bundle, contents)?;
@@ -118,18 +284,8 @@ impl GenerateMetadataCommand {
         if self.unstable_options.original_manifest {
             generate_metadata(&self.crate_metadata.manifest_path)?;
         } else {
-            Workspace::new(
-                &self.crate_metadata.cargo_meta,
-                &self.crate_metadata.root_package.id,
-            )?
-            .with_root_package_manifest(|manifest| {
-                manifest
-                    .with_added_crate_type("rlib")?
-                    .with_profile_release_lto(false)?;
-                Ok(())
-            })?
-            .with_metadata_gen_package()?
-            .using_temp(generate_metadata)?;
+            metadata_gen_workspace(&self.crate_metadata, self.verbosity)?
+                .using_temp(generate_metadata)?;
         }
 
         let dest_bundle = if self.build_artifact == BuildArtifacts::All {
@@ -139,33 +295,69 @@ impl GenerateMetadataCommand {
         };
         Ok(BuildResult {
             dest_metadata: Some(out_path_metadata),
+            dest_stripped_metadata: None,
             dest_wasm,
             dest_bundle,
             optimization_result,
             target_directory,
             build_artifact: self.build_artifact,
+            size_report: None,
+            custom_sections: Vec::new(),
+            size_delta: None,
+            build_time: started.elapsed(),
         })
     }
 
     /// Generate the extended contract project metadata
     fn extended_metadata(&self) -> Result<ExtendedMetadataResult> {
         let contract_package = &self.crate_metadata.root_package;
+        let fields = &self.contract_fields;
         let ink_version = &self.crate_metadata.ink_version;
         let rust_version = Version::parse(&rustc_version::version()?.to_string())?;
-        let contract_name = contract_package.name.clone();
-        let contract_version = Version::parse(&contract_package.version.to_string())?;
-        let contract_authors = contract_package.authors.clone();
+        let contract_name = match fields.name {
+            Some(ref name) => name.clone(),
+            None => contract_package.name.clone(),
+        };
+        let contract_version = match fields.version {
+            Some(ref version) => version.clone(),
+            None => Version::parse(&contract_package.version.to_string())?,
+        };
+        let contract_authors = match fields.authors.as_slice() {
+            [] => contract_package.authors.clone(),
+            authors => authors.to_vec(),
+        };
         // optional
-        let description = contract_package.description.clone();
-        let documentation = self.crate_metadata.documentation.clone();
-        let repository = contract_package
-            .repository
-            .as_ref()
-            .map(|repo| Url::parse(&repo))
-            .transpose()?;
-        let homepage = self.crate_metadata.homepage.clone();
-        let license = contract_package.license.clone();
-        let (dest_wasm, hash, optimization_result) = self.wasm_hash()?;
+        let description = fields
+            .description
+            .clone()
+            .or_else(|| contract_package.description.clone());
+        let documentation = fields
+            .documentation
+            .clone()
+            .or_else(|| self.crate_metadata.documentation.clone());
+        let repository = match fields.repository {
+            Some(ref repository) => Some(repository.clone()),
+            None => contract_package
+                .repository
+                .as_ref()
+                .map(|repo| Url::parse(&repo))
+                .transpose()?,
+        };
+        let homepage = fields
+            .homepage
+            .clone()
+            .or_else(|| self.crate_metadata.homepage.clone());
+        let license = fields
+            .license
+            .clone()
+            .or_else(|| contract_package.license.clone());
+        let (dest_wasm, hash, optimization_result) =
+            if self.build_artifact == BuildArtifacts::MetadataOnly {
+                (None, self.previous_wasm_hash()?, None)
+            } else {
+                let (dest_wasm, hash, optimization_result) = self.wasm_hash()?;
+                (Some(dest_wasm), hash, optimization_result)
+            };
         let source = {
             let lang = SourceLanguage::new(Language::Ink, ink_version.clone());
             let compiler = SourceCompiler::new(Compiler::RustC, rust_version);
@@ -180,7 +372,16 @@ impl GenerateMetadataCommand {
             } else {
                 None
             };
-            Source::new(maybe_wasm, hash, lang, compiler)
+            // Without a build, the environment of the previous build of the Wasm is unknown.
+            let build_info = match self.build_artifact {
+                BuildArtifacts::MetadataOnly => None,
+                _ => Some(self.build_info()?),
+            };
+            let source = Source::new(maybe_wasm, hash, lang, compiler, build_info);
+            match self.crate_metadata.target {
+                Target::Wasm => source,
+                Target::RiscV => source.into_polkavm(),
+            }
         };
 
         // Required contract fields
@@ -218,35 +419,134 @@ impl GenerateMetadataCommand {
         let user = self.crate_metadata.user.clone().map(User::new);
 
         Ok(ExtendedMetadataResult {
-            dest_wasm: Some(dest_wasm),
+            dest_wasm,
             source,
             contract,
             user,
-            optimization_result: Some(optimization_result),
+            optimization_result,
         })
     }
 
+    /// Hash of the inputs of the `metadata-gen` crate: the files of the contract project, the
+    /// `Cargo.lock`, the versions of ink! and cargo-contract and the cargo arguments.
+    ///
+    /// Changes to path dependencies outside of the contract project are not detected.
+    fn metadata_gen_fingerprint(&self, args: &[String]) -> Result<String> {
+        let mut inputs = format!(
+            "{}\n{}\n{}\n{}\n",
+            env!("CARGO_PKG_VERSION"),
+            self.crate_metadata.ink_version,
+            args.join(" "),
+            self.unstable_options.original_manifest
+        )
+        .into_bytes();
+        let cargo_meta = &self.crate_metadata.cargo_meta;
+        if let Ok(cargo_lock) = fs::read(cargo_meta.workspace_root.join("Cargo.lock")) {
+            inputs.extend(cargo_lock);
+        }
+        let manifest = &self.crate_metadata.root_package.manifest_path;
+        let project = manifest.parent().expect("the manifest is in a directory");
+        for path in util::project_files(project)? {
+            inputs.extend(path.strip_prefix(project)?.to_string_lossy().as_bytes());
+            inputs.extend(fs::read(&path)?);
+        }
+        Ok(to_hex(&blake2_hash(&inputs).0))
+    }
+
+    /// The environment of the build, as a JSON object.
+    fn build_info(&self) -> Result<Map<String, Value>> {
+        let cargo_lock = self
+            .crate_metadata
+            .cargo_meta
+            .workspace_root
+            .join("Cargo.lock");
+        let build_info = BuildInfo {
+            rust_toolchain: rustc_version::version_meta()?.short_version_string,
+            cargo_contract_version: env!("CARGO_PKG_VERSION").to_string(),
+            target: self.crate_metadata.target.to_string(),
+            wasm_opt_settings: match self.crate_metadata.target {
+                Target::Wasm => Some(WasmOptSettings {
+                    version: super::build::optimizer_version(&self.wasm_opt),
+                    optimization_passes: self.optimization_passes.to_string(),
+                    keep_debug_symbols: self.keep_debug_symbols,
+                }),
+                Target::RiscV => None,
+            },
+            cargo_lock_hash: fs::read(cargo_lock)
+                .ok()
+                .map(|cargo_lock| to_hex(&blake2_hash(&cargo_lock).0)),
+        };
+        match serde_json::to_value(build_info)? {
+            Value::Object(build_info) => Ok(build_info),
+            _ => unreachable!("the build info is serialized as an object"),
+        }
+    }
+
+    /// Hash the Wasm of the previous build, for generating the metadata without building.
+    fn previous_wasm_hash(&self) -> Result<CodeHash> {
+        let wasm = &self.crate_metadata.dest_wasm;
+        let wasm = fs::read(wasm).map_err(|_| {
+            anyhow::anyhow!(
+                "No Wasm found at {}, the code hash of the metadata requires building the \
+                 contract once with `cargo contract build`",
+                wasm.display()
+            )
+        })?;
+        let built = fs::metadata(&self.crate_metadata.dest_wasm)?.modified()?;
+        let manifest = &self.crate_metadata.root_package.manifest_path;
+        let project = manifest.parent().expect("the manifest is in a directory");
+        let mut sources = util::project_files(project)?;
+        sources.push(
+            self.crate_metadata
+                .cargo_meta
+                .workspace_root
+                .join("Cargo.lock"),
+        );
+        if let Some(changed) = changed_since(&sources, built)? {
+            eprintln!(
+                "{} {}",
+                "warning:".yellow().bold(),
+                format!(
+                    "{} changed since the previous build, the code hash of the metadata is of \
+                     the stale Wasm until the contract is built again",
+                    changed.display()
+                )
+                .bold()
+            );
+        }
+        Ok(blake2_hash(&wasm))
+    }
+
     /// Compile the contract and then hash the resulting Wasm.
     ///
-    /// Return a tuple of `(dest_wasm, hash, optimization_result)`.
-    fn wasm_hash(&self) -> Result<(PathBuf, CodeHash, OptimizationResult)> {
+    /// Return a tuple of `(dest_wasm, hash, optimization_result)`, without an optimization of the
+    /// PolkaVM program of the RISC-V target.
+    fn wasm_hash(&self) -> Result<(PathBuf, CodeHash, Option<OptimizationResult>)> {
         let (maybe_dest_wasm, maybe_optimization_res) = super::build::execute_with_crate_metadata(
             &self.crate_metadata,
             self.verbosity,
             true, // for the hash we always use the optimized version of the contract
             self.build_artifact,
+            self.optimization_passes,
+            self.keep_debug_symbols,
+            &self.cargo_options,
             self.unstable_options.clone(),
+            &self.wasm_opt,
+            &self.progress,
         )?;
 
         let wasm = fs::read(&self.crate_metadata.dest_wasm)?;
         let dest_wasm = maybe_dest_wasm.expect("dest wasm must exist");
-        let optimization_res = maybe_optimization_res.expect("optimization result must exist");
-        Ok((dest_wasm, blake2_hash(wasm.as_slice()), optimization_res))
+        Ok((
+            dest_wasm,
+            blake2_hash(wasm.as_slice()),
+            maybe_optimization_res,
+        ))
     }
 }
 
 /// Returns the blake2 hash of the submitted slice.
-fn blake2_hash(code: &[u8]) -> CodeHash {
+pub(crate) fn blake2_hash(code: &[u8]) -> CodeHash {
     let mut output = [0u8; 32];
     let mut blake2 = blake2::VarBlake2b::new_keyed(&[], 32);
     blake2.update(code);
@@ -254,33 +554,166 @@ fn blake2_hash(code: &[u8]) -> CodeHash {
     CodeHash(output)
 }
 
+/// Encodes the bytes as `0x` prefixed hex, into a buffer of the final size as the Wasm of big
+/// contracts is hundreds of kilobytes.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut hex = String::with_capacity(2 + bytes.len() * 2);
+    hex.push_str("0x");
+    for byte in bytes {
+        hex.push(DIGITS[(byte >> 4) as usize] as char);
+        hex.push(DIGITS[(byte & 0xf) as usize] as char);
+    }
+    hex
+}
+
 /// Generates a file with metadata describing the ABI of the smart-contract.
 ///
 /// It does so by generating and invoking a temporary workspace member.
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn execute(
     manifest_path: &ManifestPath,
     verbosity: Option<Verbosity>,
     build_artifact: BuildArtifacts,
+    target: Target,
+    optimization_passes: OptimizationPasses,
+    keep_debug_symbols: bool,
+    contract_fields: &ContractFields,
+    cargo_options: &CargoOptions,
     unstable_options: UnstableFlags,
+    wasm_opt: &WasmOpt,
+    progress: &Reporter,
 ) -> Result<BuildResult> {
-    let crate_metadata = CrateMetadata::collect(manifest_path)?;
+    let crate_metadata = CrateMetadata::collect_for(manifest_path, target, cargo_options)?;
+    let span = tracing::info_span!("metadata", package = %crate_metadata.package_name);
+    let _enter = span.enter();
     let res = GenerateMetadataCommand {
         crate_metadata,
+        contract_fields: contract_fields.clone(),
         verbosity,
         build_artifact,
+        optimization_passes,
+        keep_debug_symbols,
+        cargo_options: cargo_options.clone(),
         unstable_options,
+        wasm_opt: wasm_opt.clone(),
+        progress: progress.clone(),
     }
     .exec()?;
     Ok(res)
 }
 
-#[cfg(feature = "test-ci-only")]
+/// The temporary workspace with the `metadata-gen` package, which depends on the contract as an
+/// `rlib`.
+fn metadata_gen_workspace(
+    crate_metadata: &CrateMetadata,
+    verbosity: Option<Verbosity>,
+) -> Result<Workspace> {
+    let mut workspace =
+        Workspace::new(&crate_metadata.cargo_meta, &crate_metadata.root_package.id)?;
+    workspace
+        .with_root_package_manifest(|manifest| {
+            manifest
+                .with_added_crate_type("rlib")?
+                .with_profile_release_lto(false)?;
+            Ok(())
+        })?
+        .with_metadata_gen_package()?
+        .keep_temp_on_failure(matches!(
+            verbosity,
+            Some(Verbosity::Verbose) | Some(Verbosity::VeryVerbose)
+        ));
+    Ok(workspace)
+}
+
+/// Checks that the `metadata-gen` crate compiles against the contract, without building or
+/// running it.
+pub(crate) fn check(
+    crate_metadata: &CrateMetadata,
+    verbosity: Option<Verbosity>,
+    cargo_options: &CargoOptions,
+    unstable_options: UnstableFlags,
+) -> Result<()> {
+    let check_metadata_gen = |manifest_path: &ManifestPath| -> Result<()> {
+        let mut args = vec![
+            "--package".to_string(),
+            "metadata-gen".to_string(),
+            manifest_path.cargo_arg(),
+            format!(
+                "--target-dir={}",
+                crate_metadata.target_directory.to_string_lossy()
+            ),
+            "--release".to_string(),
+        ];
+        let features = crate_metadata.root_package.features.keys();
+        args.extend(cargo_options.metadata_gen_args("contract", features));
+        util::invoke_cargo(
+            "check",
+            &args,
+            crate_metadata.manifest_path.directory(),
+            verbosity,
+            &[],
+        )
+        .context("The generated `metadata-gen` crate does not compile against the contract")?;
+        Ok(())
+    };
+    if unstable_options.original_manifest {
+        check_metadata_gen(&crate_metadata.manifest_path)
+    } else {
+        metadata_gen_workspace(crate_metadata, verbosity)?.using_temp(check_metadata_gen)
+    }
+}
+
+/// The first of the `paths` modified after `time`, skipping the missing ones.
+fn changed_since(paths: &[PathBuf], time: SystemTime) -> Result<Option<&PathBuf>> {
+    for path in paths {
+        match fs::metadata(path) {
+            Ok(metadata) if metadata.modified()? > time => return Ok(Some(path)),
+            Ok(_) => (),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(None)
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::util::tests::with_tmp_dir;
+    use serde_json::{Map, Value};
+    use std::{
+        fs,
+        time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn reuses_the_cached_metadata_of_unchanged_inputs() {
+        with_tmp_dir(|path| {
+            let cache_path = path.join(super::METADATA_GEN_CACHE);
+            assert!(super::MetadataGenCache::load(&cache_path, "0x01").is_none());
+
+            let mut ink_meta = Map::new();
+            ink_meta.insert("spec".to_string(), Value::Bool(true));
+            let cache = super::MetadataGenCache {
+                fingerprint: "0x01".to_string(),
+                ink_meta: ink_meta.clone(),
+            };
+            fs::write(&cache_path, serde_json::to_string(&cache)?)?;
+            let cached = super::MetadataGenCache::load(&cache_path, "0x01");
+            assert_eq!(cached, Some(ink_meta));
+            assert!(super::MetadataGenCache::load(&cache_path, "0x02").is_none());
+            Ok(())
+        })
+    }
+}
+
+#[cfg(feature = "test-ci-only")]
+#[cfg(test)]
+mod tests_ci_only {
     use crate::cmd::metadata::blake2_hash;
     use crate::{
         cmd, crate_metadata::CrateMetadata, util::tests::with_tmp_dir, BuildArtifacts,
-        ManifestPath, UnstableFlags,
+        ManifestPath, OptimizationPasses, UnstableFlags,
     };
     use contract_metadata::*;
     use serde_json::{Map, Value};
@@ -371,6 +804,10 @@ mod tests {
                 "more-user-provided-fields",
                 vec!["and", "their", "values"].into(),
             )?;
+            test_manifest.add_user_metadata_value(
+                "audited-on",
+                "2021-03-01".parse::<value::Datetime>()?.into(),
+            )?;
             test_manifest.write()?;
 
             let crate_metadata = CrateMetadata::collect(&test_manifest.manifest_path)?;
@@ -378,7 +815,14 @@ mod tests {
                 &test_manifest.manifest_path,
                 None,
                 BuildArtifacts::All,
+                crate::Target::Wasm,
+                OptimizationPasses::default(),
+                false,
+                &Default::default(),
+                &Default::default(),
                 UnstableFlags::default(),
+                &Default::default(),
+                &Default::default(),
             )?
             .dest_bundle
             .expect("bundle file not found");
@@ -395,6 +839,9 @@ mod tests {
             let language = source.get("language").expect("source.language not found");
             let compiler = source.get("compiler").expect("source.compiler not found");
             let wasm = source.get("wasm").expect("source.wasm not found");
+            let build_info = source
+                .get("build_info")
+                .expect("source.build_info not found");
 
             let contract = metadata_json.get("contract").expect("contract not found");
             let name = contract.get("name").expect("contract.name not found");
@@ -443,11 +890,17 @@ mod tests {
                     vec!["and".into(), "their".into(), "values".into()].into(),
                 ),
             );
+            expected_user_metadata.insert("audited-on".into(), "2021-03-01".into());
 
             assert_eq!(build_byte_str(&expected_hash.0[..]), hash.as_str().unwrap());
             assert_eq!(expected_wasm, wasm.as_str().unwrap());
             assert_eq!(expected_language, language.as_str().unwrap());
             assert_eq!(expected_compiler, compiler.as_str().unwrap());
+            assert_eq!(
+                build_info["cargo_contract_version"],
+                env!("CARGO_PKG_VERSION")
+            );
+            assert_eq!(build_info["wasm_opt_settings"]["optimization_passes"], "z");
             assert_eq!(crate_metadata.package_name, name.as_str().unwrap());
             assert_eq!(
                 crate_metadata.root_package.version.to_string(),
@@ -473,4 +926,21 @@ mod tests {
         }
         str
     }
+
+    #[test]
+    fn finds_the_sources_changed_since_the_build() {
+        with_tmp_dir(|path| {
+            let built = SystemTime::now() - Duration::from_secs(60);
+            let lib = path.join("lib.rs");
+            let cargo_lock = path.join("Cargo.lock");
+            fs::write(&lib, "")?;
+            fs::File::create(&lib)?.set_modified(built - Duration::from_secs(60))?;
+            let sources = vec![lib.clone(), cargo_lock.clone()];
+            assert_eq!(super::changed_since(&sources, built)?, None);
+
+            fs::write(&cargo_lock, "")?;
+            assert_eq!(super::changed_since(&sources, built)?, Some(&cargo_lock));
+            Ok(())
+        })
+    }
 }
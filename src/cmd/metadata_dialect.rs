@@ -0,0 +1,159 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+/// The compilers of contracts for pallet-contracts, which describe the messages of a contract
+/// in slight variations of the metadata written by ink!.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Dialect {
+    Ink,
+    /// Solang, compiling Solidity. Names functions and arguments by a plain string.
+    Solang,
+    /// ask!, compiling AssemblyScript. May reference types by their bare id and encode
+    /// selectors as an array of bytes.
+    Ask,
+}
+
+impl Dialect {
+    /// Detects the dialect from the `language` or `compiler` of the `source` of the metadata,
+    /// assuming ink! if neither is known.
+    pub fn of(source: Option<&Value>) -> Self {
+        let name = |key: &str| {
+            let name = source.and_then(|source| source[key].as_str());
+            name.unwrap_or_default().to_lowercase()
+        };
+        let (language, compiler) = (name("language"), name("compiler"));
+        if language.starts_with("solidity") || compiler.starts_with("solang") {
+            Dialect::Solang
+        } else if language.starts_with("ask!") || language.starts_with("assemblyscript") {
+            Dialect::Ask
+        } else {
+            Dialect::Ink
+        }
+    }
+
+    /// Rewrites the spec of the metadata into the shape written by ink!, so its constructors,
+    /// messages and events are encoded and decoded the same way.
+    pub fn normalize(self, abi: &mut Map<String, Value>) {
+        if self == Dialect::Ink {
+            return;
+        }
+        let spec = match abi.get_mut("spec").and_then(Value::as_object_mut) {
+            Some(spec) => spec,
+            None => return,
+        };
+        for section in &["constructors", "messages", "events"] {
+            let items = spec.get_mut(*section).and_then(Value::as_array_mut);
+            for item in items.into_iter().flatten() {
+                normalize_item(item);
+                let args = item.get_mut("args").and_then(Value::as_array_mut);
+                for arg in args.into_iter().flatten() {
+                    normalize_item(arg);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dialect::Ink => f.write_str("ink!"),
+            Dialect::Solang => f.write_str("Solang"),
+            Dialect::Ask => f.write_str("ask!"),
+        }
+    }
+}
+
+/// Labels a function, event or argument by its name, references its types by an object and
+/// encodes its selector as a hex string.
+fn normalize_item(item: &mut Value) {
+    let item = match item.as_object_mut() {
+        Some(item) => item,
+        None => return,
+    };
+    if !item.contains_key("label") {
+        let label = match item.get("name") {
+            Some(Value::String(name)) => Some(name.clone()),
+            Some(Value::Array(path)) => {
+                let path = path.iter().filter_map(Value::as_str).collect::<Vec<_>>();
+                Some(path.join("::"))
+            }
+            _ => None,
+        };
+        if let Some(label) = label {
+            item.insert("label".into(), Value::from(label));
+        }
+    }
+    for key in &["type", "returnType"] {
+        if let Some(Value::Number(id)) = item.get(*key) {
+            let reference = serde_json::json!({ "type": id.clone() });
+            item.insert((*key).into(), reference);
+        }
+    }
+    if let Some(Value::Array(bytes)) = item.get("selector") {
+        let bytes = bytes
+            .iter()
+            .map(|byte| byte.as_u64().map(|byte| byte as u8))
+            .collect::<Option<Vec<_>>>();
+        if let Some(bytes) = bytes {
+            let selector = format!("0x{}", hex::encode(bytes));
+            item.insert("selector".into(), Value::from(selector));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn normalizes_the_spec_of_other_compilers() {
+        let solang = json!({ "language": "Solidity 0.1.7", "compiler": "solang 0.1.7" });
+        let ask = json!({ "language": "ask! v0.1.0", "compiler": "asc 0.18.9" });
+        assert_eq!(Dialect::of(Some(&solang)), Dialect::Solang);
+        assert_eq!(Dialect::of(Some(&ask)), Dialect::Ask);
+        let ink = json!({ "language": "ink! 3.0.0", "compiler": "rustc 1.49.0" });
+        assert_eq!(Dialect::of(Some(&ink)), Dialect::Ink);
+        assert_eq!(Dialect::of(None), Dialect::Ink);
+
+        let mut abi = json!({ "spec": {
+            "constructors": [{ "name": "new", "selector": "0x9bae9d5e", "args": [] }],
+            "messages": [{ "name": ["transfer"], "selector": [1, 2, 3, 4], "args": [
+                { "name": "to", "type": 2 },
+                { "name": "value", "type": { "type": 1, "displayName": ["u256"] } }
+            ], "returnType": 3 }],
+            "events": [{ "name": "Transfer", "args": [] }]
+        } });
+        Dialect::Ask.normalize(abi.as_object_mut().unwrap());
+        let spec = &abi["spec"];
+        assert_eq!(spec["constructors"][0]["label"], json!("new"));
+        let message = &spec["messages"][0];
+        assert_eq!(message["label"], json!("transfer"));
+        assert_eq!(message["selector"], json!("0x01020304"));
+        assert_eq!(message["returnType"], json!({ "type": 3 }));
+        assert_eq!(
+            message["args"][0],
+            json!({ "name": "to", "label": "to", "type": { "type": 2 } })
+        );
+        assert_eq!(message["args"][1]["type"]["displayName"], json!(["u256"]));
+        assert_eq!(spec["events"][0]["label"], json!("Transfer"));
+    }
+}
@@ -0,0 +1,312 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::Path;
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::metadata_format::{read_latest, Registry};
+
+/// An item of the ABI of a contract, described for the comparison.
+struct Item {
+    label: String,
+    selector: Option<String>,
+    /// The labels and the shapes of the types of the arguments.
+    args: Vec<(String, String)>,
+    /// The shape of the return type, if any.
+    returns: Option<String>,
+    /// The flags of messages and events, `mutates`, `payable` and whether arguments are
+    /// `indexed`.
+    flags: Vec<String>,
+}
+
+impl Item {
+    fn new(item: &Value, registry: &Registry) -> Self {
+        let shape = |ty: &Value| match ty["type"].as_u64() {
+            Some(id) => registry.shape(id),
+            None => "()".to_string(),
+        };
+        let args = item["args"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .map(|arg| (label(arg), shape(&arg["type"])))
+            .collect();
+        let mut flags = Vec::new();
+        for flag in &["mutates", "payable"] {
+            if item[*flag].as_bool() == Some(true) {
+                flags.push(flag.to_string());
+            }
+        }
+        for arg in item["args"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+        {
+            if arg["indexed"].as_bool() == Some(true) {
+                flags.push(format!("indexed {}", label(arg)));
+            }
+        }
+        Item {
+            label: label(item),
+            selector: item["selector"].as_str().map(str::to_string),
+            args,
+            returns: item.get("returnType").filter(|ty| !ty.is_null()).map(shape),
+            flags,
+        }
+    }
+}
+
+fn label(item: &Value) -> String {
+    item["label"].as_str().unwrap_or_default().to_string()
+}
+
+/// A difference between the ABIs.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct Change {
+    /// `added`, `removed` or `changed`.
+    pub change: &'static str,
+    /// `constructor`, `message` or `event`.
+    pub kind: &'static str,
+    pub label: String,
+    /// What changed about a changed item.
+    pub details: Vec<String>,
+    /// Whether callers or indexers built against the old ABI break.
+    pub breaking: bool,
+}
+
+/// Result of `metadata diff`.
+#[derive(Debug, Serialize)]
+pub(crate) struct MetadataDiff {
+    pub changes: Vec<Change>,
+    pub breaking: bool,
+}
+
+impl MetadataDiff {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        if self.changes.is_empty() {
+            return "The ABIs are identical".to_string();
+        }
+        let mut output = String::new();
+        for change in &self.changes {
+            let marker = if change.breaking { " (breaking)" } else { "" };
+            output.push_str(&format!(
+                "{} {} `{}`{}\n",
+                capitalized(change.change),
+                change.kind,
+                change.label,
+                marker
+            ));
+            for detail in &change.details {
+                output.push_str(&format!("\t{}\n", detail));
+            }
+        }
+        output.trim_end().to_string()
+    }
+}
+
+fn capitalized(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Compares the ABIs of the metadata, of any format.
+pub(crate) fn execute_diff(old: &Path, new: &Path) -> Result<MetadataDiff> {
+    let changes = diff(&read_latest(old)?, &read_latest(new)?);
+    Ok(MetadataDiff {
+        breaking: changes.iter().any(|change| change.breaking),
+        changes,
+    })
+}
+
+fn diff(old: &Map<String, Value>, new: &Map<String, Value>) -> Vec<Change> {
+    let (old_registry, new_registry) = (Registry::new(old), Registry::new(new));
+    let items = |abi: &Map<String, Value>, registry: &Registry, section: &str| {
+        abi.get("spec")
+            .and_then(|spec| spec[section].as_array())
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .map(|item| Item::new(item, registry))
+            .collect::<Vec<_>>()
+    };
+    let mut changes = Vec::new();
+    for (section, kind) in &[
+        ("constructors", "constructor"),
+        ("messages", "message"),
+        ("events", "event"),
+    ] {
+        let old = items(old, &old_registry, section);
+        let new = items(new, &new_registry, section);
+        for item in &old {
+            match new.iter().find(|new| new.label == item.label) {
+                Some(new) => {
+                    let details = compare(item, new);
+                    if !details.is_empty() {
+                        changes.push(Change {
+                            change: "changed",
+                            kind,
+                            label: item.label.clone(),
+                            details,
+                            breaking: true,
+                        })
+                    }
+                }
+                None => changes.push(Change {
+                    change: "removed",
+                    kind,
+                    label: item.label.clone(),
+                    details: Vec::new(),
+                    breaking: true,
+                }),
+            }
+        }
+        for item in new
+            .iter()
+            .filter(|new| old.iter().all(|old| old.label != new.label))
+        {
+            changes.push(Change {
+                change: "added",
+                kind,
+                label: item.label.clone(),
+                details: Vec::new(),
+                // Events are decoded by their index, which an added event may shift.
+                breaking: *kind == "event",
+            })
+        }
+    }
+    changes
+}
+
+/// The differences of the items with the same label, each of which breaks the old callers.
+fn compare(old: &Item, new: &Item) -> Vec<String> {
+    let mut details = Vec::new();
+    if old.selector != new.selector {
+        details.push(format!(
+            "selector: {} -> {}",
+            old.selector.as_deref().unwrap_or("none"),
+            new.selector.as_deref().unwrap_or("none")
+        ));
+    }
+    if old.args != new.args {
+        let args = |item: &Item| {
+            let args = item
+                .args
+                .iter()
+                .map(|(label, ty)| format!("{}: {}", label, ty));
+            format!("({})", args.collect::<Vec<_>>().join(", "))
+        };
+        details.push(format!("arguments: {} -> {}", args(old), args(new)));
+    }
+    if old.returns != new.returns {
+        let returns = |item: &Item| item.returns.clone().unwrap_or_else(|| "()".to_string());
+        details.push(format!("return type: {} -> {}", returns(old), returns(new)));
+    }
+    if old.flags != new.flags {
+        let flags = |item: &Item| match item.flags.as_slice() {
+            [] => "none".to_string(),
+            flags => flags.join(", "),
+        };
+        details.push(format!("flags: {} -> {}", flags(old), flags(new)));
+    }
+    details
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn abi(messages: Value) -> Map<String, Value> {
+        let abi = json!({
+            "spec": { "constructors": [], "messages": messages, "events": [] },
+            "types": [
+                { "id": 0, "type": { "def": { "primitive": "bool" } } },
+                { "id": 1, "type": { "def": { "primitive": "u32" } } },
+                {
+                    "id": 2,
+                    "type": {
+                        "path": ["MyStruct"],
+                        "def": { "composite": { "fields": [{ "name": "a", "type": 0 }] } }
+                    }
+                },
+                {
+                    "id": 3,
+                    "type": {
+                        "path": ["MyStruct"],
+                        "def": { "composite": { "fields": [{ "name": "a", "type": 1 }] } }
+                    }
+                }
+            ]
+        });
+        match abi {
+            Value::Object(abi) => abi,
+            _ => unreachable!(),
+        }
+    }
+
+    fn message(label: &str, selector: &str, ty: u64) -> Value {
+        json!({
+            "label": label,
+            "selector": selector,
+            "mutates": true,
+            "args": [{ "label": "value", "type": { "type": ty, "displayName": [] } }],
+            "returnType": null
+        })
+    }
+
+    #[test]
+    fn reports_breaking_changes() {
+        let old = abi(json!([
+            message("flip", "0x01", 0),
+            message("get", "0x02", 0),
+            message("set", "0x03", 2)
+        ]));
+        let new = abi(json!([
+            message("flip", "0x01", 0),
+            message("set", "0x04", 3),
+            message("inc", "0x05", 1)
+        ]));
+        let changes = diff(&old, &new);
+        let summary = changes
+            .iter()
+            .map(|change| (change.change, change.label.as_str(), change.breaking))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("removed", "get", true),
+                ("changed", "set", true),
+                ("added", "inc", false)
+            ]
+        );
+        assert_eq!(
+            changes[1].details,
+            [
+                "selector: 0x03 -> 0x04",
+                "arguments: (value: MyStruct { a: bool }) -> (value: MyStruct { a: u32 })"
+            ]
+        );
+        assert!(diff(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,744 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use structopt::StructOpt;
+
+use crate::OutputType;
+#[cfg(feature = "extrinsics")]
+use crate::{cmd::ContractRef, SubmitOpts};
+
+/// The sections of the metadata written by ink!, as opposed to those added by cargo-contract.
+pub(crate) const ABI_SECTIONS: &[&str] = &["spec", "storage", "types"];
+
+/// The versions of the format of the ink! part of the metadata, oldest first.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) enum MetadataFormat {
+    /// The sections at the top level, messages named by their path and types referenced by
+    /// their 1-based index, as written by this release.
+    V0,
+    /// The sections nested in a `V1` object.
+    V1,
+    /// The sections nested in a `V3` object, messages, events and arguments with a `label`, and
+    /// types with a 0-based `id`.
+    V3,
+}
+
+impl MetadataFormat {
+    pub const LATEST: Self = MetadataFormat::V3;
+
+    /// The key nesting the sections, if any.
+    pub fn key(self) -> Option<&'static str> {
+        match self {
+            MetadataFormat::V0 => None,
+            MetadataFormat::V1 => Some("V1"),
+            MetadataFormat::V3 => Some("V3"),
+        }
+    }
+
+    /// Detects the format of the metadata.
+    pub fn of(metadata: &Map<String, Value>) -> Result<Self> {
+        for format in &[MetadataFormat::V1, MetadataFormat::V3] {
+            let key = format.key().expect("the later formats are nested");
+            if let Some(Value::Object(_)) = metadata.get(key) {
+                return Ok(*format);
+            }
+        }
+        if metadata.contains_key("spec") {
+            return Ok(MetadataFormat::V0);
+        }
+        if metadata.contains_key("registry") {
+            anyhow::bail!(
+                "The metadata of ink! 2 with interned strings in a `registry` is not supported"
+            )
+        }
+        anyhow::bail!("Unknown metadata format, expected a `spec` section")
+    }
+}
+
+impl FromStr for MetadataFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "V0" | "v0" => Ok(MetadataFormat::V0),
+            "V1" | "v1" => Ok(MetadataFormat::V1),
+            "V3" | "v3" => Ok(MetadataFormat::V3),
+            _ => Err("Expected one of V0, V1 or V3".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for MetadataFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Convert the metadata of a contract between the versions of its format, compare the metadata
+/// of two versions of a contract, or check it against the contract deployed on a chain.
+#[derive(Debug, StructOpt)]
+pub(crate) enum MetadataCommand {
+    /// Convert the metadata, or the metadata of a `.contract` bundle, to the latest format
+    #[structopt(name = "upgrade")]
+    Upgrade {
+        /// Path to the `metadata.json` or `.contract` bundle
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// The file to write the converted metadata to, instead of printing it
+        #[structopt(long, short, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Compare the ABIs of two versions of a contract, listing the added, removed and changed
+    /// constructors, messages and events
+    #[structopt(name = "diff")]
+    Diff {
+        /// Path to the metadata of the old version
+        #[structopt(parse(from_os_str))]
+        old: PathBuf,
+        /// Path to the metadata of the new version
+        #[structopt(parse(from_os_str))]
+        new: PathBuf,
+        /// Fail if a change breaks callers of the old version
+        #[structopt(long)]
+        deny_breaking: bool,
+        /// Export the changes in JSON format
+        #[structopt(
+            long,
+            default_value = "human-readable",
+            value_name = "human-readable | json | json-pjs"
+        )]
+        output: OutputType,
+    },
+    /// Convert the metadata to an older format, for tools which do not support the latest one
+    #[structopt(name = "downgrade")]
+    Downgrade {
+        /// Path to the `metadata.json` or `.contract` bundle
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// The format to convert to
+        #[structopt(long, value_name = "V0 | V1")]
+        to: MetadataFormat,
+        /// The file to write the converted metadata to, instead of printing it
+        #[structopt(long, short, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Check the structure of the metadata, that the referenced types exist, that the selectors
+    /// are unique and that the hashes are well-formed and match the Wasm
+    #[structopt(name = "validate")]
+    Validate {
+        /// Path to the `metadata.json` or `.contract` bundle
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// Export the problems in JSON format
+        #[structopt(
+            long,
+            default_value = "human-readable",
+            value_name = "human-readable | json | json-pjs"
+        )]
+        output: OutputType,
+    },
+    /// Check that the metadata describes the code of a contract on the chain, by the code hash
+    /// recorded in its `source`
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "verify")]
+    Verify {
+        #[structopt(flatten)]
+        submit_opts: Box<SubmitOpts>,
+        /// The address or alias of the contract, by default the one instantiated last by the
+        /// contract project in the current directory
+        #[structopt(long)]
+        contract: Option<ContractRef>,
+        /// Path to the `metadata.json` or `.contract` bundle, by default the metadata built for
+        /// the contract project in the current directory
+        #[structopt(parse(from_os_str))]
+        path: Option<PathBuf>,
+    },
+}
+
+impl MetadataCommand {
+    /// The options for connecting to the chain, if the command queries it.
+    #[cfg(feature = "extrinsics")]
+    pub fn submit_opts(&self) -> Option<&SubmitOpts> {
+        match self {
+            MetadataCommand::Verify { submit_opts, .. } => Some(submit_opts),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "extrinsics")]
+    pub fn submit_opts_mut(&mut self) -> Option<&mut SubmitOpts> {
+        match self {
+            MetadataCommand::Verify { submit_opts, .. } => Some(submit_opts),
+            _ => None,
+        }
+    }
+
+    pub fn exec(&self) -> Result<String> {
+        let (path, to, output) = match self {
+            MetadataCommand::Upgrade { path, output } => (path, MetadataFormat::LATEST, output),
+            MetadataCommand::Downgrade { path, to, output } => (path, *to, output),
+            MetadataCommand::Diff {
+                old,
+                new,
+                deny_breaking,
+                output,
+            } => {
+                let diff = super::metadata_diff::execute_diff(old, new)?;
+                let formatted = output.format(&diff, |diff| diff.display())?;
+                if *deny_breaking && diff.breaking {
+                    anyhow::bail!(
+                        "{}\nThe new version breaks callers of the old one",
+                        formatted
+                    )
+                }
+                return Ok(formatted);
+            }
+            MetadataCommand::Validate { path, output } => {
+                let validation = super::metadata_validate::execute_validate(path)?;
+                let formatted = output.format(&validation, |validation| validation.display())?;
+                if !validation.problems.is_empty() {
+                    println!("{}", formatted);
+                    anyhow::bail!("The metadata {} is invalid", path.display())
+                }
+                return Ok(formatted);
+            }
+            #[cfg(feature = "extrinsics")]
+            MetadataCommand::Verify {
+                submit_opts,
+                contract,
+                path,
+            } => {
+                let result = super::metadata_verify::execute_metadata_verify(
+                    submit_opts,
+                    contract.as_ref(),
+                    path.as_deref(),
+                )?;
+                let output = submit_opts
+                    .output
+                    .format(&result, |result| result.display())?;
+                // A mismatch fails the command, so that scripts can rely on the exit code.
+                return match result.verified {
+                    true => Ok(output),
+                    false => Err(anyhow::anyhow!(output)),
+                };
+            }
+        };
+        let mut metadata = read(path)?;
+        let from = MetadataFormat::of(&metadata)?;
+        if matches!(self, MetadataCommand::Downgrade { .. }) && to > from {
+            anyhow::bail!("The metadata is {}, which is older than {}", from, to)
+        }
+        convert(&mut metadata, to)?;
+        let json = serde_json::to_string_pretty(&metadata)?;
+        match output {
+            Some(output) => {
+                write(output, &json)?;
+                Ok(format!(
+                    "Converted the metadata from {} to {}, written to {}",
+                    from,
+                    to,
+                    output.display()
+                ))
+            }
+            None => Ok(json),
+        }
+    }
+}
+
+fn write(path: &Path, json: &str) -> Result<()> {
+    fs::write(path, json).context(format!("Failed to write {}", path.display()))
+}
+
+/// Reads the metadata or the metadata of a `.contract` bundle.
+pub(crate) fn read(path: &Path) -> Result<Map<String, Value>> {
+    let json = fs::read(path).context(format!("Failed to read {}", path.display()))?;
+    serde_json::from_slice(&json).context(format!("Failed to parse metadata {}", path.display()))
+}
+
+/// Reads the `spec`, `storage` and `types` of the metadata or `.contract` bundle, converted to
+/// the latest format.
+pub(crate) fn read_latest(path: &Path) -> Result<Map<String, Value>> {
+    take_latest(&mut read(path)?).context(format!("Failed to read the metadata {}", path.display()))
+}
+
+/// Removes the `spec`, `storage` and `types` from the metadata, converted to the latest format.
+pub(crate) fn take_latest(metadata: &mut Map<String, Value>) -> Result<Map<String, Value>> {
+    convert(metadata, MetadataFormat::LATEST)?;
+    match metadata.remove(MetadataFormat::LATEST.key().expect("nested")) {
+        Some(Value::Object(abi)) => Ok(abi),
+        _ => unreachable!("the metadata was converted to the latest format"),
+    }
+}
+
+/// The types of metadata in the latest format, by their `id`.
+pub(crate) struct Registry<'a> {
+    types: &'a [Value],
+}
+
+impl<'a> Registry<'a> {
+    pub fn new(abi: &'a Map<String, Value>) -> Self {
+        let types = abi
+            .get("types")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        Registry { types }
+    }
+
+    /// The `path`, `params` and `def` of the type.
+    pub fn get(&self, id: u64) -> Option<&'a Value> {
+        self.types
+            .iter()
+            .find(|ty| ty["id"].as_u64() == Some(id))
+            .map(|ty| &ty["type"])
+    }
+
+    /// The ids of the types of the generic parameters, which are plain ids in the earlier
+    /// formats.
+    pub fn params(ty: &Value) -> Vec<u64> {
+        ty["params"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|param| param.as_u64().or_else(|| param["type"].as_u64()))
+            .collect()
+    }
+
+    /// The Rust name of the type, e.g. `Option<Vec<u8>>`.
+    pub fn name(&self, id: u64) -> String {
+        let ty = match self.get(id) {
+            Some(ty) => ty,
+            None => return format!("<unknown type {}>", id),
+        };
+        let path = ty["path"]
+            .as_array()
+            .and_then(|path| path.last())
+            .and_then(Value::as_str);
+        if let Some(name) = path {
+            let params = Self::params(ty);
+            if params.is_empty() {
+                return name.to_string();
+            }
+            let params = params.iter().map(|param| self.name(*param));
+            return format!("{}<{}>", name, params.collect::<Vec<_>>().join(", "));
+        }
+        let def = &ty["def"];
+        let reference = |key: &str| def[key]["type"].as_u64().map(|id| self.name(id));
+        if let Some(primitive) = def["primitive"].as_str() {
+            primitive.to_string()
+        } else if let Some(element) = reference("sequence") {
+            format!("Vec<{}>", element)
+        } else if let Some(element) = reference("array") {
+            format!("[{}; {}]", element, def["array"]["len"])
+        } else if let Some(inner) = reference("compact") {
+            format!("Compact<{}>", inner)
+        } else if let Some(Value::Array(ids)) = def.get("tuple") {
+            let names = ids.iter().filter_map(Value::as_u64).map(|id| self.name(id));
+            format!("({})", names.collect::<Vec<_>>().join(", "))
+        } else {
+            format!("<type {}>", id)
+        }
+    }
+
+    /// The name of the type with the fields of its structs and the variants of its enums, to
+    /// detect changes to the encoding of types whose name stays the same.
+    pub fn shape(&self, id: u64) -> String {
+        self.shape_of(id, &mut Vec::new())
+    }
+
+    fn shape_of(&self, id: u64, visiting: &mut Vec<u64>) -> String {
+        let ty = match self.get(id) {
+            Some(ty) if !visiting.contains(&id) => ty,
+            // Recursive types are named at their recursion.
+            _ => return self.name(id),
+        };
+        visiting.push(id);
+        let mut fields = |fields: &Value| {
+            let fields = fields
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+                .iter()
+                .map(|field| {
+                    let ty = field["type"].as_u64().unwrap_or_default();
+                    let ty = self.shape_of(ty, visiting);
+                    match field["name"].as_str() {
+                        Some(name) => format!("{}: {}", name, ty),
+                        None => ty,
+                    }
+                })
+                .collect::<Vec<_>>();
+            fields.join(", ")
+        };
+        let def = &ty["def"];
+        let shape = if let Some(composite) = def.get("composite") {
+            format!("{} {{ {} }}", self.name(id), fields(&composite["fields"]))
+        } else if let Some(variant) = def.get("variant") {
+            let variants = variant["variants"]
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+                .iter()
+                .map(|variant| {
+                    let name = variant["name"].as_str().unwrap_or_default();
+                    format!("{}({})", name, fields(&variant["fields"]))
+                })
+                .collect::<Vec<_>>();
+            format!("{} {{ {} }}", self.name(id), variants.join(", "))
+        } else {
+            self.name(id)
+        };
+        visiting.pop();
+        shape
+    }
+}
+
+/// Converts the metadata to the format, keeping the sections added by cargo-contract.
+pub(crate) fn convert(metadata: &mut Map<String, Value>, to: MetadataFormat) -> Result<()> {
+    let mut from = MetadataFormat::of(metadata)?;
+    let mut abi = match from.key() {
+        Some(key) => match metadata.remove(key) {
+            Some(Value::Object(abi)) => abi,
+            _ => unreachable!("the format is detected by the object"),
+        },
+        None => ABI_SECTIONS
+            .iter()
+            .filter_map(|section| Some((section.to_string(), metadata.remove(*section)?)))
+            .collect(),
+    };
+    while from != to {
+        from = match (from, from < to) {
+            (MetadataFormat::V0, true) => MetadataFormat::V1,
+            (MetadataFormat::V1, true) => {
+                labels_from_names(&mut abi);
+                index_types_by_id(&mut abi)?;
+                MetadataFormat::V3
+            }
+            (MetadataFormat::V3, false) => {
+                names_from_labels(&mut abi);
+                index_types_by_position(&mut abi)?;
+                MetadataFormat::V1
+            }
+            (MetadataFormat::V1, false) => MetadataFormat::V0,
+            _ => unreachable!("no format is beyond the oldest or latest one"),
+        };
+    }
+    match to.key() {
+        Some(key) => {
+            metadata.insert(key.to_string(), Value::Object(abi));
+        }
+        None => metadata.extend(abi),
+    }
+    Ok(())
+}
+
+/// Calls `f` with the section of each constructor, message and event of the `spec` and its
+/// object, and with each of their arguments.
+fn for_each_item<F>(abi: &mut Map<String, Value>, mut f: F)
+where
+    F: FnMut(&str, &mut Map<String, Value>),
+{
+    let spec = match abi.get_mut("spec").and_then(Value::as_object_mut) {
+        Some(spec) => spec,
+        None => return,
+    };
+    for section in &["constructors", "messages", "events"] {
+        let items = spec.get_mut(*section).and_then(Value::as_array_mut);
+        for item in items.into_iter().flatten().filter_map(Value::as_object_mut) {
+            f(section, item);
+            let args = item.get_mut("args").and_then(Value::as_array_mut);
+            for arg in args.into_iter().flatten().filter_map(Value::as_object_mut) {
+                f("args", arg);
+            }
+        }
+    }
+}
+
+/// Moves the value of the key `from` to the key `to`, converted by `f`.
+fn rename<F>(item: &mut Map<String, Value>, from: &str, to: &str, f: F)
+where
+    F: FnOnce(Value) -> Value,
+{
+    if let Some(value) = item.remove(from) {
+        item.insert(to.to_string(), f(value));
+    }
+}
+
+/// Replaces the `name` of the items and of their arguments with a `label`, joining the paths
+/// naming the constructors and messages.
+fn labels_from_names(abi: &mut Map<String, Value>) {
+    for_each_item(abi, |_, item| {
+        rename(item, "name", "label", |name| match name {
+            Value::Array(path) => Value::from(
+                path.iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join("::"),
+            ),
+            name => name,
+        })
+    })
+}
+
+/// Replaces the `label` of the items and of their arguments with a `name`, the path of the
+/// constructors and messages.
+fn names_from_labels(abi: &mut Map<String, Value>) {
+    for_each_item(abi, |section, item| {
+        let is_path = section == "constructors" || section == "messages";
+        rename(item, "label", "name", |label| match label.as_str() {
+            Some(label) if is_path => Value::from(label.split("::").collect::<Vec<_>>()),
+            _ => label,
+        })
+    })
+}
+
+/// Replaces the list of types referenced by their 1-based position with one of types with a
+/// 0-based `id`.
+fn index_types_by_id(abi: &mut Map<String, Value>) -> Result<()> {
+    shift_type_references(abi, -1)?;
+    if let Some(Value::Array(types)) = abi.get_mut("types") {
+        for (id, ty) in types.iter_mut().enumerate() {
+            *ty = serde_json::json!({ "id": id, "type": ty.take() });
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the list of types with a 0-based `id` with one of types referenced by their 1-based
+/// position, which requires the ids to be consecutive.
+fn index_types_by_position(abi: &mut Map<String, Value>) -> Result<()> {
+    if let Some(Value::Array(types)) = abi.get_mut("types") {
+        types.sort_by_key(|ty| ty["id"].as_u64());
+        for (position, ty) in types.iter_mut().enumerate() {
+            if ty["id"].as_u64() != Some(position as u64) {
+                anyhow::bail!(
+                    "The type ids are not consecutive, so the metadata cannot be downgraded"
+                )
+            }
+            *ty = ty["type"].take();
+        }
+    }
+    shift_type_references(abi, 1)
+}
+
+/// Adds `delta` to the references to types of the spec, the storage layout and the type
+/// definitions.
+fn shift_type_references(abi: &mut Map<String, Value>, delta: i64) -> Result<()> {
+    fn shift(value: &mut Value, delta: i64) -> Result<()> {
+        let reference = |id: &mut Value| match id.as_i64() {
+            Some(index) if index + delta >= 0 => {
+                *id = Value::from(index + delta);
+                Ok(())
+            }
+            _ => Err(anyhow::anyhow!("Invalid type reference {}", id)),
+        };
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object.iter_mut() {
+                    match (key.as_str(), value) {
+                        ("type" | "ty", id @ Value::Number(_)) => reference(id)?,
+                        ("tuple" | "params", Value::Array(ids)) => {
+                            for id in ids {
+                                match id {
+                                    Value::Number(_) => reference(id)?,
+                                    id => shift(id, delta)?,
+                                }
+                            }
+                        }
+                        (_, value) => shift(value, delta)?,
+                    }
+                }
+            }
+            Value::Array(values) => {
+                for value in values {
+                    shift(value, delta)?
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+    for section in ABI_SECTIONS {
+        if let Some(value) = abi.get_mut(*section) {
+            shift(value, delta)?
+        }
+    }
+    Ok(())
+}
+
+/// Removes the docs and display names from the ink! part of the metadata, which are not
+/// needed to encode and decode the calls.
+pub(crate) fn strip(metadata: &mut Map<String, Value>) -> Result<()> {
+    fn remove(value: &mut Value) {
+        match value {
+            Value::Object(object) => {
+                object.remove("docs");
+                object.remove("displayName");
+                object.values_mut().for_each(remove)
+            }
+            Value::Array(values) => values.iter_mut().for_each(remove),
+            _ => (),
+        }
+    }
+    let abi = match MetadataFormat::of(metadata)?.key() {
+        Some(key) => match metadata.get_mut(key) {
+            Some(Value::Object(abi)) => abi,
+            _ => unreachable!("the format is detected by the object"),
+        },
+        None => metadata,
+    };
+    for section in ABI_SECTIONS {
+        if let Some(value) = abi.get_mut(*section) {
+            remove(value)
+        }
+    }
+    Ok(())
+}
+
+/// The references to types in the value, with the JSON pointers to them below `pointer`.
+pub(crate) fn type_references(value: &Value, pointer: &str) -> Vec<(String, u64)> {
+    fn collect(value: &Value, pointer: String, references: &mut Vec<(String, u64)>) {
+        let child = |key: &dyn fmt::Display| format!("{}/{}", pointer, key);
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    match (key.as_str(), value) {
+                        ("type" | "ty", Value::Number(id)) => {
+                            references.push((child(key), id.as_u64().unwrap_or(u64::MAX)))
+                        }
+                        ("tuple" | "params", Value::Array(ids)) => {
+                            for (index, id) in ids.iter().enumerate() {
+                                let pointer = format!("{}/{}", child(key), index);
+                                match id {
+                                    Value::Number(id) => {
+                                        references.push((pointer, id.as_u64().unwrap_or(u64::MAX)))
+                                    }
+                                    id => collect(id, pointer, references),
+                                }
+                            }
+                        }
+                        (_, value) => collect(value, child(key), references),
+                    }
+                }
+            }
+            Value::Array(values) => {
+                for (index, value) in values.iter().enumerate() {
+                    collect(value, child(&index), references)
+                }
+            }
+            _ => (),
+        }
+    }
+    let mut references = Vec::new();
+    collect(value, pointer.to_string(), &mut references);
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn v0() -> Map<String, Value> {
+        let metadata = json!({
+            "metadataVersion": "0.1.0",
+            "source": { "hash": "0x00" },
+            "contract": { "name": "flipper", "version": "0.1.0" },
+            "spec": {
+                "constructors": [{
+                    "name": ["new"],
+                    "selector": "0xd183512b",
+                    "args": [{ "name": "init_value", "type": { "type": 1, "displayName": ["bool"] } }]
+                }],
+                "messages": [{
+                    "name": ["Flip", "flip"],
+                    "selector": "0x8c97db39",
+                    "args": [],
+                    "returnType": { "type": 2, "displayName": ["Option"] }
+                }],
+                "events": [{
+                    "name": "Flipped",
+                    "args": [{ "name": "value", "type": { "type": 1, "displayName": ["bool"] } }]
+                }]
+            },
+            "storage": { "struct": { "fields": [{ "name": "value", "layout": { "cell": { "key": "0x00", "ty": 1 } } }] } },
+            "types": [
+                { "def": { "primitive": "bool" } },
+                { "def": { "tuple": [1, 1] }, "params": [1] }
+            ]
+        });
+        match metadata {
+            Value::Object(metadata) => metadata,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn upgrades_and_downgrades_the_metadata() {
+        let mut metadata = v0();
+        convert(&mut metadata, MetadataFormat::LATEST).unwrap();
+        assert_eq!(MetadataFormat::of(&metadata).unwrap(), MetadataFormat::V3);
+        assert_eq!(metadata["contract"]["name"], "flipper");
+        let abi = &metadata["V3"];
+        assert_eq!(abi["spec"]["messages"][0]["label"], "Flip::flip");
+        assert_eq!(abi["spec"]["messages"][0]["returnType"]["type"], 1);
+        assert_eq!(abi["spec"]["events"][0]["args"][0]["label"], "value");
+        assert_eq!(
+            abi["storage"]["struct"]["fields"][0]["layout"]["cell"]["ty"],
+            0
+        );
+        assert_eq!(
+            abi["types"][1],
+            json!({ "id": 1, "type": { "def": { "tuple": [0, 0] }, "params": [0] } })
+        );
+
+        convert(&mut metadata, MetadataFormat::V0).unwrap();
+        assert_eq!(Value::Object(metadata), Value::Object(v0()));
+    }
+
+    #[test]
+    fn rejects_references_to_no_type() {
+        let mut metadata = v0();
+        metadata["types"][1]["def"]["tuple"] = json!([0]);
+        assert!(convert(&mut metadata, MetadataFormat::V3).is_err());
+        let mut legacy = Map::new();
+        legacy.insert("registry".to_string(), json!({ "strings": [] }));
+        assert!(MetadataFormat::of(&legacy).is_err());
+    }
+
+    #[test]
+    fn strips_the_docs_and_display_names() {
+        let mut metadata = v0();
+        metadata["spec"]["messages"][0]["docs"] = json!([" Flips the value."]);
+        metadata.insert("user".to_string(), json!({ "docs": "kept" }));
+        convert(&mut metadata, MetadataFormat::V3).unwrap();
+        strip(&mut metadata).unwrap();
+        let message = &metadata["V3"]["spec"]["messages"][0];
+        assert_eq!(message["returnType"], json!({ "type": 1 }));
+        assert!(message.get("docs").is_none());
+        assert_eq!(message["selector"], "0x8c97db39");
+        assert_eq!(metadata["user"]["docs"], "kept");
+    }
+}
@@ -0,0 +1,535 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use parity_wasm::elements::Module;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use structopt::StructOpt;
+
+use super::{
+    extract::decode_hex,
+    metadata::{blake2_hash, to_hex},
+    metadata_format::{type_references, MetadataFormat, ABI_SECTIONS},
+    validate::{validate_polkavm, validate_wasm},
+};
+use crate::OutputType;
+
+/// The sections of the contract spec, which must be present even if empty.
+const SPEC_SECTIONS: &[&str] = &["constructors", "messages", "events"];
+
+/// A problem with the metadata.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct Problem {
+    /// The JSON pointer to the offending value.
+    pub pointer: String,
+    pub message: String,
+}
+
+/// Result of `metadata validate`.
+#[derive(Debug, Serialize)]
+pub(crate) struct Validation {
+    /// The format of the metadata, if it could be determined.
+    pub format: Option<String>,
+    pub problems: Vec<Problem>,
+}
+
+impl Validation {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        if self.problems.is_empty() {
+            return format!(
+                "The metadata is valid {}",
+                self.format.as_deref().unwrap_or_default()
+            );
+        }
+        let mut output = format!("Found {} problems:", self.problems.len());
+        for problem in &self.problems {
+            output.push_str(&format!("\n\t{}: {}", problem.pointer, problem.message));
+        }
+        output
+    }
+}
+
+/// Check a `.contract` bundle before deploying it: that the hash recorded in its metadata is the
+/// hash of the embedded Wasm, that the metadata is well-formed and that pallet-contracts would
+/// accept the code, reporting the compiler and the ink! version it was built with
+#[derive(Debug, StructOpt)]
+#[structopt(name = "validate")]
+pub(crate) struct ValidateCommand {
+    /// Path to the `.contract` bundle
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+    /// Export the result in JSON format
+    #[structopt(
+        long,
+        default_value = "human-readable",
+        value_name = "human-readable | json | json-pjs"
+    )]
+    output: OutputType,
+}
+
+/// Result of `validate`.
+#[derive(Debug, Serialize)]
+pub(crate) struct BundleValidation {
+    /// The name and version of the contract.
+    pub contract: Option<String>,
+    /// The language of the contract, e.g. `ink! 3.0.0`.
+    pub language: Option<String>,
+    /// The compiler of the contract, e.g. `rustc 1.49.0`.
+    pub compiler: Option<String>,
+    /// The hash of the embedded Wasm, which is the code hash of the contract on the chain.
+    pub code_hash: Option<String>,
+    #[serde(flatten)]
+    pub validation: Validation,
+}
+
+impl BundleValidation {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| "?".to_string());
+        format!(
+            "Contract: {}\n\tLanguage: {}\n\tCompiler: {}\n\tCode hash: {}\n{}",
+            unknown(&self.contract),
+            unknown(&self.language),
+            unknown(&self.compiler),
+            unknown(&self.code_hash),
+            self.validation.display()
+        )
+    }
+}
+
+impl ValidateCommand {
+    /// Validates the bundle, failing with the problems found.
+    pub fn exec(&self) -> Result<String> {
+        let json = fs::read(&self.path).context(format!("Failed to read {}", self.path.display()))?;
+        let bundle: Value = serde_json::from_slice(&json)
+            .context(format!("Failed to parse bundle {}", self.path.display()))?;
+        let validation = validate_bundle(&bundle);
+        let formatted = self
+            .output
+            .format(&validation, |validation| validation.display())?;
+        if !validation.validation.problems.is_empty() {
+            println!("{}", formatted);
+            anyhow::bail!("The bundle {} is invalid", self.path.display())
+        }
+        Ok(formatted)
+    }
+}
+
+/// Validates the metadata of the bundle, and the embedded code as pallet-contracts would on
+/// upload.
+fn validate_bundle(bundle: &Value) -> BundleValidation {
+    let string = |pointer: &str| bundle.pointer(pointer).and_then(Value::as_str);
+    let contract = match (string("/contract/name"), string("/contract/version")) {
+        (Some(name), Some(version)) => Some(format!("{} {}", name, version)),
+        (name, _) => name.map(str::to_string),
+    };
+    let mut validation = validate(bundle);
+    let mut problems = Problems(std::mem::take(&mut validation.problems));
+    let code = match bundle.pointer("/source/wasm") {
+        Some(Value::String(wasm)) => decode_hex(wasm).ok(),
+        // Not being a string is reported by the validation of the metadata.
+        Some(_) => None,
+        None => {
+            let error = "Missing, expected a `.contract` bundle embedding the code";
+            problems.push("/source/wasm", error);
+            None
+        }
+    };
+    if let Some(ref code) = code {
+        let checked = if code.starts_with(b"\0asm") {
+            parity_wasm::deserialize_buffer::<Module>(code)
+                .context("Failed to parse the Wasm")
+                .and_then(|module| validate_wasm(&module))
+        } else {
+            validate_polkavm(code)
+        };
+        if let Err(err) = checked {
+            problems.push("/source/wasm", format!("{:#}", err));
+        }
+    }
+    validation.problems = problems.0;
+    BundleValidation {
+        contract,
+        language: string("/source/language").map(str::to_string),
+        compiler: string("/source/compiler").map(str::to_string),
+        code_hash: code.map(|code| to_hex(&blake2_hash(&code).0)),
+        validation,
+    }
+}
+
+/// Collects the problems of the metadata, pointing to the values.
+#[derive(Default)]
+struct Problems(Vec<Problem>);
+
+impl Problems {
+    fn push(&mut self, pointer: impl Into<String>, message: impl Into<String>) {
+        self.0.push(Problem {
+            pointer: pointer.into(),
+            message: message.into(),
+        })
+    }
+
+    /// Returns the value at the pointer, reporting it if it is missing or not of the `kind`.
+    fn expect<'a>(
+        &mut self,
+        value: &'a Value,
+        pointer: &str,
+        kind: &str,
+        is_kind: fn(&Value) -> bool,
+    ) -> Option<&'a Value> {
+        match value.pointer(pointer) {
+            Some(value) if is_kind(value) => Some(value),
+            Some(_) => {
+                self.push(pointer, format!("Expected {}", kind));
+                None
+            }
+            None => {
+                self.push(pointer, "Missing");
+                None
+            }
+        }
+    }
+}
+
+/// Validates the metadata, or the metadata of a `.contract` bundle, at the path.
+pub(crate) fn execute_validate(path: &Path) -> Result<Validation> {
+    let json = fs::read(path).context(format!("Failed to read {}", path.display()))?;
+    let metadata: Value = serde_json::from_slice(&json)
+        .context(format!("Failed to parse metadata {}", path.display()))?;
+    Ok(validate(&metadata))
+}
+
+fn validate(metadata: &Value) -> Validation {
+    let mut problems = Problems::default();
+    let object = match metadata {
+        Value::Object(object) => object,
+        _ => {
+            problems.push("", "Expected an object");
+            return Validation {
+                format: None,
+                problems: problems.0,
+            };
+        }
+    };
+    validate_source(metadata, &mut problems);
+    problems.expect(metadata, "/metadataVersion", "a string", Value::is_string);
+    problems.expect(metadata, "/contract/name", "a string", Value::is_string);
+    problems.expect(metadata, "/contract/version", "a string", Value::is_string);
+
+    let format = match MetadataFormat::of(object) {
+        Ok(format) => format,
+        Err(err) => {
+            problems.push("", err.to_string());
+            return Validation {
+                format: None,
+                problems: problems.0,
+            };
+        }
+    };
+    let root = format
+        .key()
+        .map(|key| format!("/{}", key))
+        .unwrap_or_default();
+    let abi = metadata.pointer(&root).unwrap_or(metadata);
+    for section in SPEC_SECTIONS {
+        let pointer = format!("{}/spec/{}", root, section);
+        let items = problems.expect(metadata, &pointer, "an array", Value::is_array);
+        // Events have no selectors.
+        if let (Some(items), false) = (items, *section == "events") {
+            validate_selectors(items, &pointer, &mut problems);
+        }
+    }
+    validate_types(abi, &root, format, &mut problems);
+    Validation {
+        format: Some(format.to_string()),
+        problems: problems.0,
+    }
+}
+
+fn validate_source(metadata: &Value, problems: &mut Problems) {
+    let is_hash = |value: &Value| matches!(value.as_str(), Some(hash) if is_hex(hash, 32));
+    problems.expect(metadata, "/source/language", "a string", Value::is_string);
+    problems.expect(metadata, "/source/compiler", "a string", Value::is_string);
+    let hash = problems.expect(metadata, "/source/hash", "a 32 byte hex string", is_hash);
+    let wasm = match metadata.pointer("/source/wasm") {
+        Some(Value::String(wasm)) if is_hex(wasm, 0) => {
+            decode_hex(wasm).expect("checked to be hex")
+        }
+        Some(_) => {
+            problems.push("/source/wasm", "Expected a hex string");
+            return;
+        }
+        None => return,
+    };
+    let actual = to_hex(&blake2_hash(&wasm).0);
+    if let Some(hash) = hash.and_then(Value::as_str) {
+        if !hash.eq_ignore_ascii_case(&actual) {
+            problems.push(
+                "/source/hash",
+                format!("Does not match the hash {} of the Wasm", actual),
+            )
+        }
+    }
+}
+
+/// Whether the string is `0x` prefixed hex of the `len` bytes, any number of bytes if zero.
+fn is_hex(hex: &str, len: usize) -> bool {
+    let digits = match hex.strip_prefix("0x") {
+        Some(digits) => digits,
+        None => return false,
+    };
+    digits.len() % 2 == 0
+        && (len == 0 || digits.len() == 2 * len)
+        && digits.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn validate_selectors(items: &Value, pointer: &str, problems: &mut Problems) {
+    let items = items.as_array().map(Vec::as_slice).unwrap_or_default();
+    for (index, item) in items.iter().enumerate() {
+        match item.get("selector") {
+            Some(Value::String(selector)) if is_hex(selector, 4) => (),
+            _ => problems.push(
+                format!("{}/{}/selector", pointer, index),
+                "Expected a 4 byte hex string",
+            ),
+        }
+    }
+    for (index, earlier) in collisions(items) {
+        problems.push(
+            format!("{}/{}/selector", pointer, index),
+            format!(
+                "The selector of `{}` is already the selector of `{}`",
+                label(&items[index]),
+                label(&items[earlier])
+            ),
+        )
+    }
+}
+
+/// The label of the constructor or message, joining its `name` path in the earlier formats.
+fn label(item: &Value) -> String {
+    match item.get("label").or_else(|| item.get("name")) {
+        Some(Value::String(label)) => label.clone(),
+        Some(Value::Array(path)) => {
+            let path = path.iter().filter_map(Value::as_str);
+            path.collect::<Vec<_>>().join("::")
+        }
+        _ => String::new(),
+    }
+}
+
+/// The indexes of the items sharing the selector of an earlier item, with the index of that
+/// item.
+fn collisions(items: &[Value]) -> Vec<(usize, usize)> {
+    let mut selectors = BTreeMap::new();
+    let mut collisions = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        if let Some(selector) = item["selector"].as_str() {
+            match selectors.entry(selector.to_ascii_lowercase()) {
+                Entry::Occupied(earlier) => collisions.push((index, *earlier.get())),
+                Entry::Vacant(entry) => {
+                    entry.insert(index);
+                }
+            }
+        }
+    }
+    collisions
+}
+
+/// Fails if several constructors or several messages of the ink! part of the metadata share a
+/// selector, e.g. with a `selector` overridden by hand, as the contract would dispatch calls of
+/// one of them to the other.
+pub(crate) fn check_selectors(ink_meta: &Map<String, Value>) -> Result<()> {
+    let spec = match MetadataFormat::of(ink_meta) {
+        Ok(format) => match format.key() {
+            Some(key) => ink_meta.get(key).and_then(|abi| abi.get("spec")),
+            None => ink_meta.get("spec"),
+        },
+        // The metadata of ink! 2 interns its labels.
+        Err(_) => return Ok(()),
+    };
+    let mut shared = Vec::new();
+    for section in &["constructors", "messages"] {
+        let items = spec.and_then(|spec| spec[*section].as_array());
+        let items = items.map(Vec::as_slice).unwrap_or_default();
+        for (index, earlier) in collisions(items) {
+            shared.push(format!(
+                "The {} `{}` and `{}` share the selector {}",
+                section,
+                label(&items[earlier]),
+                label(&items[index]),
+                items[index]["selector"].as_str().unwrap_or_default()
+            ));
+        }
+    }
+    if !shared.is_empty() {
+        anyhow::bail!(
+            "{}\nThe contract would dispatch the calls of one to the other, change their \
+             `selector`",
+            shared.join("\n")
+        )
+    }
+    Ok(())
+}
+
+fn validate_types(abi: &Value, root: &str, format: MetadataFormat, problems: &mut Problems) {
+    let types_pointer = format!("{}/types", root);
+    let types = match abi.get("types") {
+        Some(Value::Array(types)) => types,
+        Some(_) => return problems.push(types_pointer, "Expected an array"),
+        None => return problems.push(types_pointer, "Missing"),
+    };
+    let ids: BTreeSet<u64> = match format {
+        MetadataFormat::V0 | MetadataFormat::V1 => (1..=types.len() as u64).collect(),
+        MetadataFormat::V3 => {
+            let mut ids = BTreeSet::new();
+            for (index, ty) in types.iter().enumerate() {
+                let pointer = format!("{}/{}/id", types_pointer, index);
+                match ty["id"].as_u64() {
+                    Some(id) if !ids.insert(id) => {
+                        problems.push(pointer, format!("Duplicate type id {}", id))
+                    }
+                    Some(_) => (),
+                    None => problems.push(pointer, "Expected a type id"),
+                }
+            }
+            ids
+        }
+    };
+    let abi = match abi {
+        Value::Object(abi) => abi,
+        _ => return,
+    };
+    let sections: Map<String, Value> = ABI_SECTIONS
+        .iter()
+        .filter_map(|section| Some((section.to_string(), abi.get(*section)?.clone())))
+        .collect();
+    for (pointer, id) in type_references(&Value::Object(sections), root) {
+        if !ids.contains(&id) {
+            problems.push(pointer, format!("Reference to the missing type {}", id))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn metadata(wasm: &str, hash: &str, messages: Value) -> Value {
+        json!({
+            "metadataVersion": "0.1.0",
+            "source": { "hash": hash, "language": "ink! 3.0.0", "compiler": "rustc", "wasm": wasm },
+            "contract": { "name": "flipper", "version": "0.1.0" },
+            "V3": {
+                "spec": { "constructors": [], "messages": messages, "events": [] },
+                "storage": { "struct": { "fields": [{ "layout": { "cell": { "ty": 0 } } }] } },
+                "types": [{ "id": 0, "type": { "def": { "primitive": "bool" } } }]
+            }
+        })
+    }
+
+    fn message(selector: &str, ty: u64) -> Value {
+        json!({
+            "label": "flip",
+            "selector": selector,
+            "args": [{ "label": "value", "type": { "type": ty } }],
+            "returnType": null
+        })
+    }
+
+    #[test]
+    fn accepts_valid_metadata() {
+        let wasm = "0x0061736d";
+        let hash = to_hex(&blake2_hash(&decode_hex(wasm).unwrap()).0);
+        let messages = json!([message("0x633aa551", 0), message("0x2f865bd9", 0)]);
+        let validation = validate(&metadata(wasm, &hash, messages));
+        assert_eq!(validation.problems, vec![]);
+        assert_eq!(validation.format.as_deref(), Some("V3"));
+    }
+
+    #[test]
+    fn names_the_messages_sharing_a_selector() {
+        let ink_meta = json!({
+            "spec": {
+                "constructors": [{ "name": ["new"], "selector": "0x9bae9d5e" }],
+                "messages": [
+                    { "name": ["Erc20", "transfer"], "selector": "0x84a15da1" },
+                    { "name": ["approve"], "selector": "0x84A15DA1" }
+                ]
+            }
+        });
+        let ink_meta = serde_json::from_value(ink_meta).unwrap();
+        let err = check_selectors(&ink_meta).unwrap_err().to_string();
+        assert!(err.starts_with(
+            "The messages `Erc20::transfer` and `approve` share the selector 0x84A15DA1\n"
+        ));
+    }
+
+    #[test]
+    fn validates_the_code_of_bundles() {
+        let wasm = "0x0061736d01000000";
+        let hash = to_hex(&blake2_hash(&decode_hex(wasm).unwrap()).0);
+        let validation = validate_bundle(&metadata(wasm, &hash, json!([])));
+        assert_eq!(validation.contract.as_deref(), Some("flipper 0.1.0"));
+        assert_eq!(validation.language.as_deref(), Some("ink! 3.0.0"));
+        assert_eq!(validation.code_hash.as_deref(), Some(hash.as_str()));
+        // The empty module exports neither `deploy` nor `call`.
+        let pointers: Vec<_> = validation
+            .validation
+            .problems
+            .iter()
+            .map(|problem| problem.pointer.as_str())
+            .collect();
+        assert_eq!(pointers, vec!["/source/wasm"]);
+
+        let mut metadata = metadata(wasm, &hash, json!([]));
+        metadata["source"].as_object_mut().unwrap().remove("wasm");
+        let validation = validate_bundle(&metadata);
+        assert_eq!(validation.code_hash, None);
+        assert_eq!(
+            validation.validation.problems[0].message,
+            "Missing, expected a `.contract` bundle embedding the code"
+        );
+    }
+
+    #[test]
+    fn reports_the_problems() {
+        let hash = format!("0x{}", "00".repeat(32));
+        let messages = json!([message("0x633aa551", 0), message("0x633AA551", 7)]);
+        let pointers: Vec<_> = validate(&metadata("0x0061736d", &hash, messages))
+            .problems
+            .into_iter()
+            .map(|problem| problem.pointer)
+            .collect();
+        assert_eq!(
+            pointers,
+            vec![
+                "/source/hash",
+                "/V3/spec/messages/1/selector",
+                "/V3/spec/messages/1/args/0/type/type",
+            ]
+        );
+    }
+}
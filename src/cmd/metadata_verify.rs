@@ -0,0 +1,135 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sp_core::{crypto::AccountId32, H256};
+
+use super::{
+    addressbook::ContractRef, code::contract_code_hash, deployments::resolve_contract,
+    extract::decode_hex, extrinsic::Connection, metadata_format::read, transcode::METADATA_FILE,
+};
+use crate::{crate_metadata::CrateMetadata, SubmitOpts};
+
+/// Result of a `metadata verify`.
+#[derive(Debug, Serialize)]
+pub(crate) struct MetadataVerifyResult {
+    pub contract: AccountId32,
+    /// The code hash of the contract on the chain.
+    pub code_hash: H256,
+    /// The metadata which was compared.
+    pub metadata: PathBuf,
+    /// The code hash recorded in the `source` of the metadata.
+    pub metadata_code_hash: H256,
+    /// Whether the metadata describes the code of the contract.
+    pub verified: bool,
+}
+
+impl MetadataVerifyResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        if self.verified {
+            format!(
+                "Verified: {} describes the code {:?} of the contract {}",
+                self.metadata.display(),
+                self.code_hash,
+                self.contract
+            )
+        } else {
+            format!(
+                "Mismatch: {} describes the code {:?}, but the code of the contract {} is {:?}. \
+                 Calls transcoded with this metadata may not match the messages of the contract",
+                self.metadata.display(),
+                self.metadata_code_hash,
+                self.contract,
+                self.code_hash
+            )
+        }
+    }
+}
+
+/// The code hash in the `source` section of the metadata.
+fn metadata_code_hash(path: &Path) -> Result<H256> {
+    let metadata = read(path)?;
+    let hash = metadata
+        .get("source")
+        .and_then(|source| source["hash"].as_str())
+        .ok_or_else(|| anyhow::anyhow!("The metadata {} has no source.hash", path.display()))?;
+    let hash = decode_hex(hash).context(format!("Invalid source.hash in {}", path.display()))?;
+    if hash.len() != 32 {
+        anyhow::bail!("The source.hash in {} is not 32 bytes", path.display())
+    }
+    Ok(H256::from_slice(&hash))
+}
+
+/// Compares the code hash recorded in the metadata to the code hash of the contract on the chain,
+/// so that calls are not transcoded with an ABI of other code.
+///
+/// The metadata is read from `path`, or else from the artifacts of the contract project in the
+/// current directory. The contract defaults to the one instantiated last by the project.
+pub(crate) fn execute_metadata_verify(
+    submit_opts: &SubmitOpts,
+    contract: Option<&ContractRef>,
+    path: Option<&Path>,
+) -> Result<MetadataVerifyResult> {
+    let metadata = match path {
+        Some(path) => path.to_path_buf(),
+        None => CrateMetadata::collect(&Default::default())
+            .context("No metadata supplied, and no contract project in the current directory")?
+            .target_directory
+            .join(METADATA_FILE),
+    };
+    let metadata_code_hash = metadata_code_hash(&metadata)?;
+    let contract = resolve_contract(contract, submit_opts)?;
+    contract.check_format();
+    let code_hash = async_std::task::block_on(async {
+        let connection = Connection::new(submit_opts).await?;
+        contract_code_hash(&connection, &contract.account).await
+    })?;
+    Ok(MetadataVerifyResult {
+        contract: contract.account,
+        code_hash,
+        metadata,
+        metadata_code_hash,
+        verified: metadata_code_hash == code_hash,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_code_hash_of_the_metadata() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            let path = dir.join(METADATA_FILE);
+            let hash = format!("0x{}", "01".repeat(32));
+            std::fs::write(
+                &path,
+                format!(r#"{{ "source": {{ "hash": "{}" }} }}"#, hash),
+            )?;
+            assert_eq!(metadata_code_hash(&path)?, H256::repeat_byte(1));
+
+            std::fs::write(&path, r#"{ "source": { "hash": "0x0102" } }"#)?;
+            assert!(metadata_code_hash(&path).is_err());
+            std::fs::write(&path, r#"{ "contract": {} }"#)?;
+            assert!(metadata_code_hash(&path).is_err());
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,386 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sp_core::crypto::AccountId32;
+use structopt::StructOpt;
+
+use super::{
+    addressbook::ContractRef,
+    block::{self, BlockRef},
+    deployments::resolve_contract,
+    extrinsic::Connection,
+    storage::{self, LayoutCell},
+    transcode::InkMetadata,
+};
+use crate::SubmitOpts;
+
+/// Read the storage of a contract with its current metadata, re-encode it into the storage
+/// layout of the new metadata, and encode the calls moving the state into the upgraded contract
+#[derive(Debug, StructOpt)]
+#[structopt(name = "migrate")]
+pub(crate) struct MigrateCommand {
+    /// The address of the contract, or its alias in the address book. Defaults to the contract
+    /// instantiated last on the network, as recorded in `deployments.json`
+    contract: Option<ContractRef>,
+    /// Path to the metadata of the code the contract runs before the upgrade
+    #[structopt(long, parse(from_os_str))]
+    old_metadata: PathBuf,
+    /// Path to the metadata of the new code, defaults to `./target/ink/metadata.json`
+    #[structopt(long, parse(from_os_str))]
+    new_metadata: Option<PathBuf>,
+    /// TOML file mapping the storage fields of the new layout to those of the old one, and
+    /// listing the messages to call with the migrated state
+    #[structopt(long, parse(from_os_str))]
+    mapping: Option<PathBuf>,
+    /// Read the storage at this block, a block number or a 0x prefixed hash, e.g. the last one
+    /// before the code was upgraded, instead of the best block
+    #[structopt(long)]
+    at: Option<BlockRef>,
+    #[structopt(flatten)]
+    pub submit_opts: SubmitOpts,
+}
+
+/// How the state of the old storage layout is moved into the new one, as read from the mapping
+/// file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Mapping {
+    /// The fields of the old layout by the field of the new layout they are read into. The fields
+    /// left out are read from the old field of the same name.
+    #[serde(default)]
+    fields: BTreeMap<String, String>,
+    /// The values of fields of the new layout, e.g. of those without a counterpart in the old
+    /// one.
+    #[serde(default)]
+    values: BTreeMap<String, Value>,
+    /// The messages of the new code to call with the migrated state.
+    #[serde(default)]
+    calls: Vec<MappedCall>,
+}
+
+/// A message to call with the values of fields of the new layout as its arguments.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MappedCall {
+    message: String,
+    /// The fields of the new layout whose values are passed as the arguments, in order.
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+impl Mapping {
+    fn load(path: &Path) -> Result<Self> {
+        let toml =
+            fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+        toml::from_str(&toml).context(format!("Failed to parse {}", path.display()))
+    }
+}
+
+/// A cell of the new storage layout with its migrated value.
+#[derive(Debug, Serialize)]
+pub(crate) struct MigratedCell {
+    /// The field of the new layout.
+    pub field: String,
+    /// The field of the old layout the value was read from, `None` if set by the mapping.
+    pub from: Option<String>,
+    /// Hex encoded key of the cell in the new layout.
+    pub key: String,
+    pub value: Value,
+    /// The hex encoded value, encoded with the type of the field in the new layout.
+    pub data: String,
+}
+
+/// A call moving state into the upgraded contract.
+#[derive(Debug, Serialize)]
+pub(crate) struct MigrationCall {
+    pub message: String,
+    pub args: Vec<Value>,
+    /// Hex encoded data of the call, to pass to `call --data`.
+    pub data: String,
+}
+
+/// Result of a successful `migrate`.
+#[derive(Debug, Serialize)]
+pub(crate) struct MigrateResult {
+    pub contract: AccountId32,
+    /// The cells of the new layout with their re-encoded values.
+    pub cells: Vec<MigratedCell>,
+    /// The fields of the new layout without a value in the old storage or in the mapping.
+    pub missing: Vec<String>,
+    /// The fields of the old layout which no field of the new layout is read from.
+    pub dropped: Vec<String>,
+    pub calls: Vec<MigrationCall>,
+}
+
+impl MigrateResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let mut output = format!(
+            "Migrated {} storage cells of contract {}",
+            self.cells.len(),
+            self.contract
+        );
+        for cell in &self.cells {
+            let from = match cell.from {
+                Some(ref from) if *from != cell.field => format!(" (from {})", from),
+                Some(_) => String::new(),
+                None => " (set by the mapping)".to_string(),
+            };
+            output.push_str(&format!(
+                "\n\t{}{}: {}\n\t  key {}: {}",
+                cell.field, from, cell.value, cell.key, cell.data
+            ));
+        }
+        if !self.missing.is_empty() {
+            output.push_str(&format!(
+                "\n\tFields without a value: {}",
+                self.missing.join(", ")
+            ));
+        }
+        if !self.dropped.is_empty() {
+            output.push_str(&format!(
+                "\n\tFields of the old layout left behind: {}",
+                self.dropped.join(", ")
+            ));
+        }
+        for call in &self.calls {
+            output.push_str(&format!("\n\tCall {}: {}", call.message, call.data));
+        }
+        output
+    }
+}
+
+/// Re-encodes the `old` values of the contract's storage fields into the cells of the `new`
+/// layout, as directed by the mapping, and encodes the calls of the mapping with the migrated
+/// values.
+fn migrate(
+    contract: &AccountId32,
+    old: &BTreeMap<String, Value>,
+    new: &[LayoutCell],
+    metadata: &InkMetadata,
+    mapping: &Mapping,
+) -> Result<MigrateResult> {
+    if let Some(field) = mapping
+        .fields
+        .keys()
+        .chain(mapping.values.keys())
+        .find(|field| !new.iter().any(|cell| cell.field == **field))
+    {
+        anyhow::bail!("The mapping names {}, which is no field of the new layout", field)
+    }
+    let types = metadata.types();
+    let mut cells = Vec::new();
+    let mut missing = Vec::new();
+    for cell in new {
+        let from = mapping.fields.get(&cell.field).unwrap_or(&cell.field);
+        let (from, value) = match (mapping.values.get(&cell.field), old.get(from)) {
+            (Some(value), _) => (None, value),
+            (None, Some(value)) => (Some(from.clone()), value),
+            (None, None) => {
+                missing.push(cell.field.clone());
+                continue;
+            }
+        };
+        let mut data = Vec::new();
+        types
+            .encode(cell.ty, value, &mut data)
+            .context(format!("Failed to encode {} into the new layout", cell.field))?;
+        cells.push(MigratedCell {
+            field: cell.field.clone(),
+            from,
+            key: format!("0x{}", hex::encode(&cell.key)),
+            value: value.clone(),
+            data: format!("0x{}", hex::encode(&data)),
+        });
+    }
+    let dropped = old
+        .keys()
+        .filter(|field| !cells.iter().any(|cell| cell.from.as_ref() == Some(*field)))
+        .cloned()
+        .collect();
+
+    let mut calls = Vec::new();
+    for call in &mapping.calls {
+        let message = metadata.find_message(Some(&call.message), "")?;
+        let args = call
+            .args
+            .iter()
+            .map(|field| {
+                cells
+                    .iter()
+                    .find(|cell| cell.field == *field)
+                    .map(|cell| cell.value.clone())
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "No migrated value of {} to call {} with",
+                            field,
+                            call.message
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let encoded = args.iter().map(Value::to_string).collect::<Vec<_>>();
+        let data = metadata.encode_call(&message, &encoded)?;
+        calls.push(MigrationCall {
+            message: message.label,
+            args,
+            data: format!("0x{}", hex::encode(&data)),
+        });
+    }
+    Ok(MigrateResult {
+        contract: contract.clone(),
+        cells,
+        missing,
+        dropped,
+        calls,
+    })
+}
+
+impl MigrateCommand {
+    pub fn exec(&self) -> Result<String> {
+        let old_metadata = InkMetadata::read(&self.old_metadata)?;
+        let new_metadata = InkMetadata::load(self.new_metadata.as_deref())?.ok_or_else(|| {
+            anyhow::anyhow!("The new metadata is required, supply it with --new-metadata")
+        })?;
+        let mapping = match self.mapping {
+            Some(ref path) => Mapping::load(path)?,
+            None => Mapping::default(),
+        };
+        let old_cells = storage::select_cells(Some(&old_metadata), None)?;
+        let new_cells = storage::select_cells(Some(&new_metadata), None)?;
+        if new_cells.is_empty() {
+            anyhow::bail!("The new metadata has no storage fields at fixed keys to migrate into")
+        }
+        let contract = resolve_contract(self.contract.as_ref(), &self.submit_opts)?;
+
+        let storage = async_std::task::block_on(async {
+            let connection = Connection::new(&self.submit_opts).await?;
+            contract.check_format();
+
+            let at = block::resolve(&connection, self.at.as_ref()).await?;
+            let child_key = storage::child_key(&connection, &contract.account, at).await?;
+            let types = old_metadata.types();
+            let index = storage::index_cells(&old_cells);
+            storage::read_cells(&connection, &child_key, &index, Some(&types), true, at).await
+        })?;
+        let mut old = BTreeMap::new();
+        for cell in storage {
+            match (cell.field, cell.value) {
+                (Some(field), Some(value)) => {
+                    old.insert(field, value);
+                }
+                (field, _) => log::warn!(
+                    "Failed to decode the storage cell {} of {}, leaving it behind",
+                    cell.key,
+                    field.unwrap_or_default()
+                ),
+            }
+        }
+
+        let result = migrate(
+            &contract.account,
+            &old,
+            &new_cells,
+            &new_metadata,
+            &mapping,
+        )?;
+        self.submit_opts
+            .output
+            .format(&result, |result| result.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+    use serde_json::json;
+
+    #[test]
+    fn reencodes_the_storage_into_the_new_layout() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            let metadata = json!({
+                "storage": { "struct": { "fields": [
+                    { "name": "supply", "layout": { "cell": { "key": "0x01", "ty": 2 } } },
+                    { "name": "paused", "layout": { "cell": { "key": "0x02", "ty": 1 } } },
+                    { "name": "version", "layout": { "cell": { "key": "0x03", "ty": 3 } } }
+                ] } },
+                "spec": { "messages": [{
+                    "label": "migrate",
+                    "selector": "0x01020304",
+                    "args": [
+                        { "label": "supply", "type": { "type": 2 } },
+                        { "label": "version", "type": { "type": 3 } }
+                    ]
+                }] },
+                "types": [
+                    { "def": { "primitive": "bool" } },
+                    { "def": { "primitive": "u128" } },
+                    { "def": { "primitive": "u8" } }
+                ]
+            });
+            let path = dir.join("metadata.json");
+            fs::write(&path, metadata.to_string())?;
+            let metadata = InkMetadata::read(&path)?;
+            let new = storage::select_cells(Some(&metadata), None)?;
+            let mut old = BTreeMap::new();
+            old.insert("total_supply".to_string(), json!("1000"));
+            old.insert("owner".to_string(), json!("0x00"));
+            let mapping: Mapping = toml::from_str(
+                r#"
+                [fields]
+                supply = "total_supply"
+
+                [values]
+                version = 2
+
+                [[calls]]
+                message = "migrate"
+                args = ["supply", "version"]
+                "#,
+            )?;
+
+            let contract = AccountId32::from([1; 32]);
+            let result = migrate(&contract, &old, &new, &metadata, &mapping)?;
+            let cells = &result.cells;
+            assert_eq!(cells.len(), 2);
+            assert_eq!(cells[0].from.as_deref(), Some("total_supply"));
+            assert_eq!(
+                cells[0].data,
+                format!("0x{}", hex::encode(1000u128.encode()))
+            );
+            assert_eq!(cells[1].from, None);
+            assert_eq!(cells[1].data, "0x02");
+            assert_eq!(result.missing, vec!["paused"]);
+            assert_eq!(result.dropped, vec!["owner"]);
+            let data = [&[1, 2, 3, 4][..], &1000u128.encode(), &[2]].concat();
+            assert_eq!(result.calls[0].data, format!("0x{}", hex::encode(data)));
+
+            let unknown: Mapping = toml::from_str("[values]\nowner = 1")?;
+            assert!(migrate(&contract, &old, &new, &metadata, &unknown).is_err());
+            Ok(())
+        })
+    }
+}
@@ -14,14 +14,190 @@
 // You should have received a copy of the GNU General Public License
 // along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
 
+#[cfg(feature = "extrinsics")]
+mod account;
+#[cfg(feature = "extrinsics")]
+mod address;
+#[cfg(feature = "extrinsics")]
+mod addressbook;
+#[cfg(feature = "extrinsics")]
+mod attest;
+mod audit;
+#[cfg(feature = "extrinsics")]
+mod balance;
+#[cfg(feature = "extrinsics")]
+mod batch;
+#[cfg(feature = "extrinsics")]
+mod benchmark;
+mod bindgen;
+mod bindgen_abi;
+mod bindgen_rust;
+mod bindgen_ts;
+#[cfg(feature = "extrinsics")]
+mod block;
 pub mod build;
+mod cache;
+#[cfg(feature = "extrinsics")]
+mod call;
+mod clean;
+#[cfg(feature = "extrinsics")]
+mod code;
+mod completions;
+#[cfg(feature = "extrinsics")]
+mod config;
+#[cfg(feature = "extrinsics")]
+mod confirm;
+mod coverage;
+#[cfg(feature = "extrinsics")]
+mod custom_types;
 #[cfg(feature = "extrinsics")]
 mod deploy;
 #[cfg(feature = "extrinsics")]
+mod deployments;
+mod doc;
+#[cfg(feature = "extrinsics")]
+mod events;
+#[cfg(feature = "extrinsics")]
+mod extension;
+pub mod extract;
+#[cfg(feature = "extrinsics")]
+mod extrinsic;
+#[cfg(feature = "extrinsics")]
+mod faucet;
+#[cfg(feature = "extrinsics")]
+mod fork;
+#[cfg(feature = "extrinsics")]
+mod fuzz;
+#[cfg(feature = "extrinsics")]
+mod gas;
+mod generate;
+#[cfg(feature = "extrinsics")]
+mod http;
+#[cfg(feature = "extrinsics")]
+mod info;
+mod inspect;
+#[cfg(feature = "extrinsics")]
 mod instantiate;
+#[cfg(feature = "extrinsics")]
+mod interpreter;
+#[cfg(feature = "extrinsics")]
+mod keystore;
+mod lint;
 pub mod metadata;
+#[cfg(feature = "extrinsics")]
+mod metadata_dialect;
+mod metadata_diff;
+mod metadata_format;
+mod metadata_validate;
+#[cfg(feature = "extrinsics")]
+mod metadata_verify;
+#[cfg(feature = "extrinsics")]
+mod migrate;
+#[cfg(feature = "extrinsics")]
+mod module_error;
+#[cfg(feature = "extrinsics")]
+mod multisig;
+#[cfg(feature = "extrinsics")]
+mod network;
 pub mod new;
+mod node;
+#[cfg(feature = "extrinsics")]
+mod pallet;
+mod pjs;
+#[cfg(feature = "extrinsics")]
+mod plan;
+mod plugin;
+#[cfg(feature = "extrinsics")]
+mod replay;
+#[cfg(feature = "extrinsics")]
+mod sandbox;
+#[cfg(feature = "extrinsics")]
+mod sign;
+#[cfg(feature = "extrinsics")]
+pub mod signer;
+mod size;
+mod size_history;
+#[cfg(feature = "extrinsics")]
+mod storage;
+#[cfg(feature = "extrinsics")]
+mod submit;
+#[cfg(feature = "extrinsics")]
+mod terminate;
+mod test;
+#[cfg(feature = "extrinsics")]
+mod transcode;
+#[cfg(feature = "extrinsics")]
+mod transfer;
+#[cfg(feature = "extrinsics")]
+mod uos;
+#[cfg(feature = "extrinsics")]
+mod upgrade;
+mod validate;
+mod wasm_opt;
+mod watch;
+#[cfg(feature = "extrinsics")]
+mod wrap;
+#[cfg(feature = "extrinsics")]
+mod ws;
 
-pub(crate) use self::build::{BuildCommand, CheckCommand};
 #[cfg(feature = "extrinsics")]
-pub(crate) use self::{deploy::execute_deploy, instantiate::execute_instantiate};
+pub(crate) use self::{
+    account::execute_account,
+    address::{parse_address, set_address_format, Address},
+    addressbook::{AddressBookCommand, ContractRef},
+    attest::{execute_verify_attestation, AttestOpts},
+    balance::BalanceVariant,
+    batch::{execute_batch, sign_batch},
+    benchmark::BenchmarkCommand,
+    block::BlockRef,
+    call::{call_contract, dry_run_call, execute_call, sign_call, MessageOpts, Replay},
+    code::{execute_download, execute_remove_code, execute_verify, sign_remove_code, CodeRef},
+    completions::CompleteLabelsCommand,
+    config::Config,
+    deploy::{execute_deploy, execute_deploy_all, sign_deploy, upload_code},
+    deployments::resolve_contract,
+    events::{execute_events, DisplayEvent, ExtrinsicDetails},
+    faucet::FaucetCommand,
+    fuzz::FuzzCommand,
+    gas::{GasLimit, GasMargin},
+    info::execute_info,
+    instantiate::{
+        execute_instantiate, instantiate_contract, predict_contract, sign_instantiate,
+        ConstructorOpts,
+    },
+    migrate::MigrateCommand,
+    multisig::{MultisigCommand, MultisigOpts},
+    network::Network,
+    plan::execute_plan,
+    sandbox::ExecCommand,
+    sign::{Mortality, OfflineOpts, OfflineParams, SignedExtras},
+    signer::{read_password_file, ExtrinsicSigner, Scheme},
+    storage::{execute_storage, watch_storage},
+    submit::execute_submit,
+    terminate::execute_terminate,
+    transfer::{execute_transfer, sign_transfer},
+    upgrade::execute_upgrade,
+};
+pub(crate) use self::{
+    audit::AuditCommand,
+    bindgen::BindgenCommand,
+    build::{BuildCommand, CheckCommand},
+    cache::CacheCommand,
+    clean::CleanCommand,
+    completions::CompletionsCommand,
+    doc::DocCommand,
+    extract::ExtractCommand,
+    generate::GenerateCommand,
+    inspect::InspectCommand,
+    lint::{execute_lint, LintCommand},
+    metadata_format::MetadataCommand,
+    metadata_validate::ValidateCommand,
+    new::NewCommand,
+    node::NodeCommand,
+    pjs::to_pjs_value,
+    plugin::execute_plugin,
+    size::SizeReport,
+    size_history::SizeDelta,
+    test::TestCommand,
+    watch::WatchCommand,
+};
@@ -0,0 +1,196 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fmt;
+
+use frame_metadata::{DecodeDifferent, RuntimeMetadata, RuntimeMetadataPrefixed};
+
+/// An error of a runtime module an extrinsic failed with, e.g. `Contracts::OutOfGas`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ModuleError {
+    pub module: String,
+    pub error: String,
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}::{}", self.module, self.error)
+    }
+}
+
+impl ModuleError {
+    /// The module error the extrinsic failed with, if it failed with one.
+    pub fn from_error(err: &anyhow::Error) -> Option<Self> {
+        let error = match err.downcast_ref::<subxt::Error>()? {
+            // subxt only exposes module errors by their display, `<error> from <module>`.
+            subxt::Error::Runtime(error) => error.to_string(),
+            _ => return None,
+        };
+        let (error, module) = error
+            .strip_prefix("Runtime module error: ")?
+            .split_once(" from ")?;
+        Some(ModuleError {
+            module: module.to_string(),
+            error: error.to_string(),
+        })
+    }
+
+    /// The documentation of the error in the runtime metadata, joined into a single line.
+    pub fn documentation(&self, metadata: &RuntimeMetadataPrefixed) -> Option<String> {
+        let modules = match metadata.1 {
+            RuntimeMetadata::V12(ref metadata) => decoded(&metadata.modules)?,
+            _ => return None,
+        };
+        let module = modules
+            .iter()
+            .find(|module| decoded(&module.name) == Some(&self.module))?;
+        let error = decoded(&module.errors)?
+            .iter()
+            .find(|error| decoded(&error.name) == Some(&self.error))?;
+        let lines = decoded(&error.documentation)?
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        Some(lines.join(" ")).filter(|documentation| !documentation.is_empty())
+    }
+
+    /// A short explanation of a frequent failure of contracts, and its likely fix.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self.module.as_str() {
+            "Contracts" => contracts_hint(&self.error),
+            _ => None,
+        }
+    }
+}
+
+/// A short explanation of a frequent error of the contracts module, and its likely fix.
+pub(crate) fn contracts_hint(error: &str) -> Option<&'static str> {
+    let hint = match error {
+        "ContractTrapped" => {
+            "the contract panicked, e.g. on a failed `assert!` or an `unwrap()` of `None`. Dry \
+             run the call with `--verbose` to print its debug messages, or with `--trace` to \
+             print the host functions it called"
+        }
+        "CodeRejected" => {
+            "the node rejected the Wasm, e.g. as it uses floating point operations or imports \
+             host functions the node does not offer. Rebuild the contract with `cargo contract \
+             build`, which validates and optimizes the Wasm"
+        }
+        "OutOfGas" => {
+            "the gas limit is too low for the call. Raise it with `--gas`, or pass `--gas auto` \
+             to estimate it with a dry run"
+        }
+        "TransferFailed" => {
+            "the value could not be transferred, as the free balance of the sender is too low, \
+             or the transfer would leave an account below the existential deposit. Fund the \
+             account or change the value"
+        }
+        "DuplicateContract" => {
+            "a contract with the same code was already instantiated by this account, at the \
+             same address as the salt is always empty. Instantiate it from another account"
+        }
+        _ => return None,
+    };
+    Some(hint)
+}
+
+/// The value of metadata decoded from a node, which is never in its encodable form.
+pub(crate) fn decoded<B, O>(value: &DecodeDifferent<B, O>) -> Option<&O>
+where
+    B: 'static,
+    O: 'static,
+{
+    match value {
+        DecodeDifferent::Decoded(value) => Some(value),
+        DecodeDifferent::Encode(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_metadata::{
+        ErrorMetadata, ExtrinsicMetadata, ModuleMetadata, RuntimeMetadataV12, META_RESERVED,
+    };
+
+    #[test]
+    fn looks_up_the_documentation_of_module_errors() {
+        let error = |name: &str, documentation: &[&str]| ErrorMetadata {
+            name: DecodeDifferent::Decoded(name.to_string()),
+            documentation: DecodeDifferent::Decoded(
+                documentation.iter().map(ToString::to_string).collect(),
+            ),
+        };
+        let module = ModuleMetadata {
+            name: DecodeDifferent::Decoded("Balances".to_string()),
+            storage: None,
+            calls: None,
+            event: None,
+            constants: DecodeDifferent::Decoded(Vec::new()),
+            errors: DecodeDifferent::Decoded(vec![
+                error(
+                    "VestingBalance",
+                    &[" Vesting balance too high to send value"],
+                ),
+                error(
+                    "InsufficientBalance",
+                    &[" Balance too low to send", " value", ""],
+                ),
+            ]),
+            index: 5,
+        };
+        let metadata = RuntimeMetadataPrefixed(
+            META_RESERVED,
+            RuntimeMetadata::V12(RuntimeMetadataV12 {
+                modules: DecodeDifferent::Decoded(vec![module]),
+                extrinsic: ExtrinsicMetadata {
+                    version: 4,
+                    signed_extensions: Vec::new(),
+                },
+            }),
+        );
+        let module_error = |module: &str, error: &str| ModuleError {
+            module: module.to_string(),
+            error: error.to_string(),
+        };
+
+        let insufficient = module_error("Balances", "InsufficientBalance");
+        assert_eq!(insufficient.to_string(), "Balances::InsufficientBalance");
+        assert_eq!(
+            insufficient.documentation(&metadata).as_deref(),
+            Some("Balance too low to send value")
+        );
+        assert_eq!(
+            module_error("Contracts", "OutOfGas").documentation(&metadata),
+            None
+        );
+    }
+
+    #[test]
+    fn hints_at_fixes_of_contracts_errors() {
+        let module_error = |module: &str, error: &str| ModuleError {
+            module: module.to_string(),
+            error: error.to_string(),
+        };
+        assert!(module_error("Contracts", "CodeRejected")
+            .hint()
+            .unwrap()
+            .contains("cargo contract build"));
+        assert!(module_error("Contracts", "StorageExhausted").hint().is_none());
+        assert!(module_error("Balances", "OutOfGas").hint().is_none());
+    }
+}
@@ -0,0 +1,823 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use codec::{Decode, Encode, Input, Output};
+use serde::{Deserialize, Serialize};
+use sp_core::{
+    crypto::{AccountId32, Ss58Codec},
+    storage::StorageKey,
+    Bytes, H256,
+};
+use structopt::StructOpt;
+use subxt::{Call, DefaultNodeRuntime, Encoded, EventsDecoder, Metadata, MetadataError, Store};
+
+use super::{
+    address::{parse_address, Address},
+    balance::BalanceFormat,
+    events::ExtrinsicDetails,
+    extrinsic::Connection,
+};
+use crate::{ExtrinsicOpts, SubmitOpts};
+
+/// Arguments for dispatching the call from a multisig account via `Multisig::as_multi`.
+#[derive(Debug, Default, StructOpt)]
+pub(crate) struct MultisigOpts {
+    /// Dispatch the call from the multisig account with this approval threshold, approving it
+    /// with the signer's account
+    #[structopt(long)]
+    multisig_threshold: Option<u16>,
+    /// Comma separated SS58 addresses of the other signatories of the multisig account
+    #[structopt(
+        long,
+        requires = "multisig-threshold",
+        require_delimiter = true,
+        parse(try_from_str = parse_address)
+    )]
+    multisig_signatories: Vec<Address>,
+    /// Timepoint `<block number>:<extrinsic index>` of the first approval, required for all
+    /// further approvals of the call
+    #[structopt(long, requires = "multisig-threshold")]
+    multisig_timepoint: Option<Timepoint>,
+    /// Maximum weight of the call, which the approval executing the call is charged for
+    #[structopt(long, default_value = "10000000000")]
+    multisig_max_weight: u64,
+    /// Export the call, its hash and the timepoint of the first approval to this JSON file, for
+    /// the other signatories to approve with `cargo contract multisig approve` and `execute`
+    #[structopt(
+        long,
+        requires = "multisig-threshold",
+        conflicts_with = "offline",
+        parse(from_os_str)
+    )]
+    multisig_call_file: Option<PathBuf>,
+}
+
+impl MultisigOpts {
+    /// Returns the multisig the call should be dispatched from, if `--multisig-threshold` was
+    /// supplied.
+    ///
+    /// The `signer` is the account approving the call, which must not be one of the other
+    /// signatories.
+    pub fn multisig(&self, signer: &AccountId32) -> Result<Option<Multisig>> {
+        let threshold = match self.multisig_threshold {
+            Some(threshold) => threshold,
+            None => return Ok(None),
+        };
+        let mut other_signatories = self
+            .multisig_signatories
+            .iter()
+            .map(|address| address.account.clone())
+            .collect::<Vec<_>>();
+        other_signatories.sort();
+        other_signatories.dedup();
+        if other_signatories.contains(signer) {
+            anyhow::bail!("The signer must not be one of the --multisig-signatories")
+        }
+        if threshold < 2 || usize::from(threshold) > other_signatories.len() + 1 {
+            anyhow::bail!(
+                "The multisig threshold must be between 2 and the number of signatories, {}",
+                other_signatories.len() + 1
+            )
+        }
+        Ok(Some(Multisig {
+            threshold,
+            account: multi_account_id(signer, &other_signatories, threshold),
+            other_signatories,
+            timepoint: self.multisig_timepoint,
+            max_weight: self.multisig_max_weight,
+        }))
+    }
+
+    /// Whether the call is dispatched from a multisig account.
+    pub fn is_enabled(&self) -> bool {
+        self.multisig_threshold.is_some()
+    }
+
+    /// The file to export the multisig call to, if `--multisig-call-file` was supplied.
+    pub fn call_file(&self) -> Option<&Path> {
+        self.multisig_call_file.as_deref()
+    }
+
+    /// Whether the timepoint of the first approval is unknown before the extrinsic is included,
+    /// as the call is approved for the first time.
+    pub fn is_first_approval(&self) -> bool {
+        self.multisig_timepoint.is_none()
+    }
+
+    /// Warns about signatories whose addresses were encoded for another network.
+    pub fn check_address_formats(&self) {
+        self.multisig_signatories
+            .iter()
+            .for_each(Address::check_format)
+    }
+}
+
+/// The block number and the index within the block of the extrinsic, which identifies the first
+/// approval of a multisig operation.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub(crate) struct Timepoint {
+    pub height: u32,
+    pub index: u32,
+}
+
+impl Encode for Timepoint {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        (self.height, self.index).encode_to(dest)
+    }
+}
+
+impl Decode for Timepoint {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+        let (height, index) = Decode::decode(input)?;
+        Ok(Timepoint { height, index })
+    }
+}
+
+impl fmt::Display for Timepoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.height, self.index)
+    }
+}
+
+impl FromStr for Timepoint {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let mut parts = input.splitn(2, ':');
+        let height = parts.next().unwrap_or_default();
+        let index = parts
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Timepoint should be <block number>:<index>"))?;
+        Ok(Timepoint {
+            height: height.parse().context("Invalid timepoint block number")?,
+            index: index.parse().context("Invalid timepoint extrinsic index")?,
+        })
+    }
+}
+
+/// A multisig account approving a call.
+pub(crate) struct Multisig {
+    threshold: u16,
+    /// The address of the multisig account.
+    account: AccountId32,
+    /// The signatories besides the signer, sorted as required by the multisig module.
+    other_signatories: Vec<AccountId32>,
+    timepoint: Option<Timepoint>,
+    max_weight: u64,
+}
+
+impl Multisig {
+    /// The address of the multisig account.
+    pub fn account(&self) -> &AccountId32 {
+        &self.account
+    }
+
+    /// Wraps the encoded call in a `Multisig::as_multi` call.
+    pub fn as_multi(&self, call: Encoded) -> AsMultiCall {
+        AsMultiCall {
+            threshold: self.threshold,
+            other_signatories: self.other_signatories.clone(),
+            maybe_timepoint: self.timepoint,
+            call: call.0,
+            store_call: false,
+            max_weight: self.max_weight,
+        }
+    }
+
+    /// Returns the call file exporting the multisig operation of the `call` for the other
+    /// signatories, once the timepoint of the first approval is known.
+    pub fn call_file(
+        &self,
+        signer: &AccountId32,
+        call: &[u8],
+        included_at: Option<Timepoint>,
+    ) -> Option<CallFile> {
+        let mut signatories = self.other_signatories.clone();
+        signatories.push(signer.clone());
+        signatories.sort();
+        Some(CallFile {
+            account: self.account.clone(),
+            threshold: self.threshold,
+            signatories,
+            call_hash: H256(sp_core::blake2_256(call)),
+            call: Bytes(call.to_vec()),
+            timepoint: self.timepoint.or(included_at)?,
+            max_weight: self.max_weight,
+        })
+    }
+
+    /// Returns the details required for the remaining approvals of the call.
+    ///
+    /// For the first approval, the timepoint is the one the extrinsic was included at.
+    pub fn details(&self, call_hash: H256, included_at: Option<Timepoint>) -> MultisigDetails {
+        MultisigDetails {
+            account: self.account.clone(),
+            call_hash,
+            timepoint: self.timepoint.or(included_at),
+        }
+    }
+}
+
+/// Returns the address of the multisig account with the given signatories and threshold.
+fn multi_account_id(
+    signer: &AccountId32,
+    other_signatories: &[AccountId32],
+    threshold: u16,
+) -> AccountId32 {
+    let mut signatories = other_signatories.to_vec();
+    signatories.push(signer.clone());
+    signatories.sort();
+    let entropy = (b"modlpy/utilisuba", signatories, threshold).using_encoded(sp_core::blake2_256);
+    AccountId32::decode(&mut &entropy[..]).expect("32 bytes decode into an account id")
+}
+
+/// The `Multisig::as_multi` call, approving and, once the threshold is reached, dispatching the
+/// wrapped call from the multisig account.
+pub(crate) struct AsMultiCall {
+    threshold: u16,
+    other_signatories: Vec<AccountId32>,
+    maybe_timepoint: Option<Timepoint>,
+    call: Vec<u8>,
+    store_call: bool,
+    max_weight: u64,
+}
+
+impl AsMultiCall {
+    /// The hash identifying the wrapped call in the multisig module.
+    pub fn call_hash(&self) -> H256 {
+        H256(sp_core::blake2_256(&self.call))
+    }
+}
+
+impl Encode for AsMultiCall {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        (
+            self.threshold,
+            &self.other_signatories,
+            &self.maybe_timepoint,
+            &self.call,
+            self.store_call,
+            self.max_weight,
+        )
+            .encode_to(dest)
+    }
+}
+
+impl Call<DefaultNodeRuntime> for AsMultiCall {
+    const MODULE: &'static str = "Multisig";
+    const FUNCTION: &'static str = "as_multi";
+
+    fn events_decoder(decoder: &mut EventsDecoder<DefaultNodeRuntime>) {
+        decoder.register_type_size::<[u8; 32]>("CallHash");
+        decoder.register_type_size::<(u32, u32)>("Timepoint<BlockNumber>");
+    }
+}
+
+/// The `Multisig::approve_as_multi` call, approving the call by its hash without dispatching it.
+struct ApproveAsMultiCall {
+    threshold: u16,
+    other_signatories: Vec<AccountId32>,
+    maybe_timepoint: Option<Timepoint>,
+    call_hash: H256,
+    max_weight: u64,
+}
+
+impl Encode for ApproveAsMultiCall {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        (
+            self.threshold,
+            &self.other_signatories,
+            &self.maybe_timepoint,
+            self.call_hash.as_fixed_bytes(),
+            self.max_weight,
+        )
+            .encode_to(dest)
+    }
+}
+
+impl Call<DefaultNodeRuntime> for ApproveAsMultiCall {
+    const MODULE: &'static str = "Multisig";
+    const FUNCTION: &'static str = "approve_as_multi";
+
+    fn events_decoder(decoder: &mut EventsDecoder<DefaultNodeRuntime>) {
+        AsMultiCall::events_decoder(decoder)
+    }
+}
+
+/// The `Multisig::Multisigs` storage double map, holding the approvals of the pending operations
+/// of a multisig account by call hash.
+struct MultisigsStore<'a> {
+    account: &'a AccountId32,
+    call_hash: &'a H256,
+}
+
+impl Encode for MultisigsStore<'_> {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.account.encode_to(dest);
+        self.call_hash.encode_to(dest)
+    }
+}
+
+impl Store<DefaultNodeRuntime> for MultisigsStore<'_> {
+    const MODULE: &'static str = "Multisig";
+    const FIELD: &'static str = "Multisigs";
+    type Returns = PendingMultisig;
+
+    fn prefix(metadata: &Metadata) -> Result<StorageKey, MetadataError> {
+        Ok(metadata
+            .module(Self::MODULE)?
+            .storage(Self::FIELD)?
+            .prefix())
+    }
+
+    fn key(&self, metadata: &Metadata) -> Result<StorageKey, MetadataError> {
+        Ok(metadata
+            .module(Self::MODULE)?
+            .storage(Self::FIELD)?
+            .double_map()?
+            .key(self.account, self.call_hash.as_fixed_bytes()))
+    }
+}
+
+/// A multisig operation awaiting approvals, as stored by the multisig module.
+#[derive(Clone, Debug, Decode)]
+pub(crate) struct PendingMultisig {
+    /// The timepoint of the first approval.
+    when: Timepoint,
+    deposit: u128,
+    depositor: AccountId32,
+    /// The signatories who approved the call so far, sorted.
+    approvals: Vec<AccountId32>,
+}
+
+/// A multisig operation exported by its first approval with `--multisig-call-file`, from which
+/// the other signatories approve and execute the call.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct CallFile {
+    /// The multisig account the call is dispatched from.
+    pub account: AccountId32,
+    pub threshold: u16,
+    /// All signatories of the multisig account, sorted.
+    pub signatories: Vec<AccountId32>,
+    /// Hash of the call.
+    pub call_hash: H256,
+    /// The encoded call dispatched from the multisig account.
+    pub call: Bytes,
+    /// Timepoint of the first approval.
+    pub timepoint: Timepoint,
+    /// Maximum weight of the call, which the approval executing the call is charged for.
+    pub max_weight: u64,
+}
+
+impl CallFile {
+    /// Reads the call file, checking that the call matches its hash and the signatories the
+    /// multisig account.
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read(path).context(format!("Failed to read {}", path.display()))?;
+        let file: CallFile = serde_json::from_slice(&json)
+            .context(format!("Failed to parse the call file {}", path.display()))?;
+        if H256(sp_core::blake2_256(&file.call.0)) != file.call_hash {
+            anyhow::bail!("The call of {} does not match its hash", path.display())
+        }
+        let first = file.signatories.first().ok_or_else(|| {
+            anyhow::anyhow!("The call file {} has no signatories", path.display())
+        })?;
+        let others = file.other_signatories(first)?;
+        if multi_account_id(first, &others, file.threshold) != file.account {
+            anyhow::bail!(
+                "The signatories and threshold of {} do not match its multisig account",
+                path.display()
+            )
+        }
+        Ok(file)
+    }
+
+    /// Writes the call file as JSON.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json).context(format!("Failed to write {}", path.display()))
+    }
+
+    /// The signatories besides the `signer`, sorted as required by the multisig module.
+    fn other_signatories(&self, signer: &AccountId32) -> Result<Vec<AccountId32>> {
+        if !self.signatories.contains(signer) {
+            anyhow::bail!(
+                "{} is not a signatory of the multisig account {}",
+                signer,
+                self.account
+            )
+        }
+        let mut others = self
+            .signatories
+            .iter()
+            .filter(|signatory| *signatory != signer)
+            .cloned()
+            .collect::<Vec<_>>();
+        others.sort();
+        Ok(others)
+    }
+
+    /// The result reporting the approvals of the pending call.
+    fn result(
+        &self,
+        pending: Option<PendingMultisig>,
+        extrinsic: Option<ExtrinsicDetails>,
+        balance_format: BalanceFormat,
+    ) -> MultisigResult {
+        let deposit = pending
+            .as_ref()
+            .map(|pending| (pending.deposit, pending.depositor.clone()));
+        let approvals = pending.map(|pending| pending.approvals);
+        let not_approved = self
+            .signatories
+            .iter()
+            .filter(|signatory| {
+                approvals
+                    .as_ref()
+                    .map_or(false, |approvals| !approvals.contains(signatory))
+            })
+            .cloned()
+            .collect();
+        MultisigResult {
+            account: self.account.clone(),
+            call_hash: self.call_hash,
+            threshold: self.threshold,
+            approvals,
+            pending: not_approved,
+            deposit,
+            extrinsic,
+            balance_format,
+        }
+    }
+}
+
+/// Coordinate the approvals of a call dispatched from a multisig account, as exported by the
+/// first approval with `--multisig-call-file`
+#[derive(Debug, StructOpt)]
+pub(crate) enum MultisigCommand {
+    /// Print the signatories who approved the call so far
+    #[structopt(name = "status")]
+    Status {
+        /// Path to the call file
+        #[structopt(parse(from_os_str))]
+        call_file: PathBuf,
+        #[structopt(flatten)]
+        submit_opts: SubmitOpts,
+    },
+    /// Approve the call by its hash, with an approval short of the threshold
+    #[structopt(name = "approve")]
+    Approve {
+        /// Path to the call file
+        #[structopt(parse(from_os_str))]
+        call_file: PathBuf,
+        #[structopt(flatten)]
+        extrinsic_opts: ExtrinsicOpts,
+    },
+    /// Approve the call with the final approval, which dispatches the call
+    #[structopt(name = "execute")]
+    Execute {
+        /// Path to the call file
+        #[structopt(parse(from_os_str))]
+        call_file: PathBuf,
+        #[structopt(flatten)]
+        extrinsic_opts: ExtrinsicOpts,
+    },
+}
+
+/// Result of a `multisig` command.
+#[derive(Debug, Serialize)]
+pub(crate) struct MultisigResult {
+    pub account: AccountId32,
+    pub call_hash: H256,
+    pub threshold: u16,
+    /// The signatories who approved the call, `None` if it is not pending, as it was executed,
+    /// cancelled or never approved.
+    pub approvals: Option<Vec<AccountId32>>,
+    /// The signatories yet to approve the pending call.
+    pub pending: Vec<AccountId32>,
+    /// The deposit reserved for the pending call, and the signatory it is reserved from.
+    pub deposit: Option<(u128, AccountId32)>,
+    /// The details of the extrinsic approving the call, unless the status was queried.
+    #[serde(flatten)]
+    pub extrinsic: Option<ExtrinsicDetails>,
+    /// How the deposit is printed in the human readable output.
+    #[serde(skip)]
+    pub balance_format: BalanceFormat,
+}
+
+impl MultisigResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let mut output = format!(
+            "Multisig account: {}, call hash: {:?}",
+            self.account.to_ss58check(),
+            self.call_hash
+        );
+        match self.approvals {
+            Some(ref approvals) => {
+                output.push_str(&format!(
+                    "\n\tApprovals: {} of {}",
+                    approvals.len(),
+                    self.threshold
+                ));
+                for approval in approvals {
+                    output.push_str(&format!("\n\t  {}", approval.to_ss58check()));
+                }
+                output.push_str("\n\tNot approved by:");
+                for signatory in &self.pending {
+                    output.push_str(&format!("\n\t  {}", signatory.to_ss58check()));
+                }
+                if let Some((deposit, ref depositor)) = self.deposit {
+                    output.push_str(&format!(
+                        "\n\tDeposit: {} reserved from {}",
+                        self.balance_format.display(deposit),
+                        depositor.to_ss58check()
+                    ));
+                }
+            }
+            None => output.push_str("\n\tThe call is not pending: it was executed or cancelled"),
+        }
+        match self.extrinsic {
+            Some(ref extrinsic) => extrinsic.display_details(output),
+            None => output,
+        }
+    }
+}
+
+impl MultisigCommand {
+    /// The options for connecting to the chain.
+    pub fn submit_opts(&self) -> &SubmitOpts {
+        match self {
+            MultisigCommand::Status { submit_opts, .. } => submit_opts,
+            MultisigCommand::Approve { extrinsic_opts, .. }
+            | MultisigCommand::Execute { extrinsic_opts, .. } => &extrinsic_opts.submit,
+        }
+    }
+
+    pub fn exec(&self) -> Result<MultisigResult> {
+        let (call_file, extrinsic_opts) = match self {
+            MultisigCommand::Status {
+                call_file,
+                submit_opts,
+            } => {
+                let file = CallFile::load(call_file)?;
+                return async_std::task::block_on(async {
+                    let connection = Connection::new(submit_opts).await?;
+                    let pending = fetch_pending(&connection, &file).await?;
+                    Ok(file.result(pending, None, connection.balance_format()))
+                });
+            }
+            MultisigCommand::Approve {
+                call_file,
+                extrinsic_opts,
+            }
+            | MultisigCommand::Execute {
+                call_file,
+                extrinsic_opts,
+            } => (call_file, extrinsic_opts),
+        };
+        if extrinsic_opts.multisig.is_enabled() {
+            anyhow::bail!(
+                "The call file determines the multisig, approve it without --multisig-threshold"
+            )
+        }
+        let file = CallFile::load(call_file)?;
+        let signer = extrinsic_opts.signer()?;
+        // The signatory is the account the signer is a proxy for, if any.
+        let signatory = extrinsic_opts.origin(signer.account_id())?;
+        let other_signatories = file.other_signatories(&signatory)?;
+        let execute = matches!(self, MultisigCommand::Execute { .. });
+        async_std::task::block_on(async {
+            let mut connection = Connection::new(&extrinsic_opts.submit).await?;
+            let pending = fetch_pending(&connection, &file).await?.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "The call {:?} is not pending, it was executed or cancelled",
+                    file.call_hash
+                )
+            })?;
+            if pending.approvals.contains(&signatory) {
+                anyhow::bail!("{} approved the call already", signatory)
+            }
+            if pending.when != file.timepoint {
+                log::warn!(
+                    "The call was first approved at {}, not at the timepoint {} of the call file",
+                    pending.when,
+                    file.timepoint
+                );
+            }
+            let missing = usize::from(file.threshold).saturating_sub(pending.approvals.len());
+            let submitted = match (execute, missing) {
+                (false, 1) => anyhow::bail!(
+                    "This approval reaches the threshold, dispatch the call with `cargo contract \
+                     multisig execute`"
+                ),
+                (true, missing) if missing > 1 => anyhow::bail!(
+                    "The call requires {} more approvals before it can be executed",
+                    missing - 1
+                ),
+                (false, _) => {
+                    let call = ApproveAsMultiCall {
+                        threshold: file.threshold,
+                        other_signatories,
+                        maybe_timepoint: Some(pending.when),
+                        call_hash: file.call_hash,
+                        max_weight: file.max_weight,
+                    };
+                    connection.submit(call, &signer, extrinsic_opts).await?
+                }
+                (true, _) => {
+                    let call = AsMultiCall {
+                        threshold: file.threshold,
+                        other_signatories,
+                        maybe_timepoint: Some(pending.when),
+                        call: file.call.0.clone(),
+                        store_call: false,
+                        max_weight: file.max_weight,
+                    };
+                    connection.submit(call, &signer, extrinsic_opts).await?
+                }
+            };
+            let extrinsic = ExtrinsicDetails::new(&submitted)?;
+            let pending = match submitted.included {
+                Some(_) => fetch_pending(&connection, &file).await?,
+                None => None,
+            };
+            Ok(file.result(pending, Some(extrinsic), connection.balance_format()))
+        })
+    }
+}
+
+/// Fetches the approvals of the call, if it is pending.
+async fn fetch_pending(
+    connection: &Connection,
+    file: &CallFile,
+) -> Result<Option<PendingMultisig>> {
+    let store = MultisigsStore {
+        account: &file.account,
+        call_hash: &file.call_hash,
+    };
+    connection.fetch(&store).await
+}
+
+/// Details of a multisig operation, required to coordinate the remaining approvals.
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct MultisigDetails {
+    /// The multisig account the call is dispatched from.
+    pub account: AccountId32,
+    /// Hash of the wrapped call.
+    pub call_hash: H256,
+    /// Timepoint of the first approval, unless the inclusion of the first approval was not
+    /// awaited.
+    pub timepoint: Option<Timepoint>,
+}
+
+impl fmt::Display for MultisigDetails {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Multisig account: {}, call hash: {:?}",
+            self.account.to_ss58check(),
+            self.call_hash
+        )?;
+        if let Some(timepoint) = self.timepoint {
+            write!(f, ", timepoint: {}", timepoint)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::crypto::Pair;
+
+    fn account(seed: &str) -> AccountId32 {
+        sp_core::sr25519::Pair::from_string(seed, None)
+            .unwrap()
+            .public()
+            .into()
+    }
+
+    fn address(account: &AccountId32) -> Address {
+        account.to_ss58check().parse().unwrap()
+    }
+
+    #[test]
+    fn parses_timepoint() {
+        let timepoint: Timepoint = "1234:2".parse().unwrap();
+        assert_eq!(
+            timepoint,
+            Timepoint {
+                height: 1234,
+                index: 2
+            }
+        );
+        assert_eq!(timepoint.to_string(), "1234:2");
+        assert!("1234".parse::<Timepoint>().is_err());
+        assert!("a:2".parse::<Timepoint>().is_err());
+    }
+
+    #[test]
+    fn sorts_other_signatories() {
+        let (alice, bob, charlie) = (account("//Alice"), account("//Bob"), account("//Charlie"));
+        let opts = MultisigOpts {
+            multisig_threshold: Some(2),
+            multisig_signatories: vec![address(&charlie), address(&bob)],
+            ..Default::default()
+        };
+        let multisig = opts.multisig(&alice).unwrap().expect("threshold is set");
+        let mut expected = vec![bob.clone(), charlie.clone()];
+        expected.sort();
+        assert_eq!(multisig.other_signatories, expected);
+
+        // The multisig account is the same, whichever signatory approves the call.
+        let opts = MultisigOpts {
+            multisig_threshold: Some(2),
+            multisig_signatories: vec![address(&alice), address(&charlie)],
+            ..Default::default()
+        };
+        assert_eq!(
+            opts.multisig(&bob).unwrap().unwrap().account,
+            multisig.account
+        );
+
+        assert!(opts.multisig(&alice).is_err());
+    }
+
+    #[test]
+    fn rejects_unreachable_threshold() {
+        let opts = MultisigOpts {
+            multisig_threshold: Some(3),
+            multisig_signatories: vec![address(&account("//Bob"))],
+            ..Default::default()
+        };
+        assert!(opts.multisig(&account("//Alice")).is_err());
+        assert!(MultisigOpts::default()
+            .multisig(&account("//Alice"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn exports_and_loads_the_call_file() {
+        crate::util::tests::with_tmp_dir(|path| {
+            let (alice, bob, charlie) = (
+                account("//Alice"),
+                account("//Bob"),
+                account("//Charlie"),
+            );
+            let opts = MultisigOpts {
+                multisig_threshold: Some(2),
+                multisig_signatories: vec![address(&bob), address(&charlie)],
+                ..Default::default()
+            };
+            let multisig = opts.multisig(&alice)?.expect("threshold is set");
+            assert!(multisig.call_file(&alice, &[1, 2, 3], None).is_none());
+            let timepoint = Timepoint {
+                height: 7,
+                index: 1,
+            };
+            let call_file = multisig
+                .call_file(&alice, &[1, 2, 3], Some(timepoint))
+                .expect("the timepoint is known");
+            assert_eq!(call_file.account, multisig.account);
+            assert_eq!(call_file.signatories.len(), 3);
+
+            let file = path.join("call.json");
+            call_file.save(&file)?;
+            let loaded = CallFile::load(&file)?;
+            assert_eq!(loaded, call_file);
+            let others = loaded.other_signatories(&bob)?;
+            assert!(others.contains(&alice) && others.contains(&charlie));
+            assert!(loaded.other_signatories(&account("//Dave")).is_err());
+
+            let tampered = CallFile {
+                call: Bytes(vec![4]),
+                ..loaded
+            };
+            tampered.save(&file)?;
+            assert!(CallFile::load(&file).is_err());
+            Ok(())
+        })
+    }
+}
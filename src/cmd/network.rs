@@ -0,0 +1,292 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::BTreeMap, convert::TryFrom};
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::Value;
+use sp_core::H256;
+
+use crate::SubmitOpts;
+
+/// The networks known without configuring them: `(name, url, ss58 prefix, symbol, decimals)`.
+const BUILTIN_NETWORKS: [(&str, &str, u8, &str, u8); 3] = [
+    ("local", "ws://localhost:9944", 42, "UNIT", 12),
+    (
+        "rococo-contracts",
+        "wss://rococo-contracts-rpc.polkadot.io",
+        42,
+        "ROC",
+        12,
+    ),
+    ("shibuya", "wss://rpc.shibuya.astar.network", 5, "SBY", 18),
+];
+
+/// The explorers of the built-in networks: `(name, extrinsic url, account url)`.
+const BUILTIN_EXPLORERS: [(&str, &str, Option<&str>); 3] = [
+    (
+        "local",
+        "https://polkadot.js.org/apps/?rpc=ws%3A%2F%2F127.0.0.1%3A9944#/explorer/query/{block}",
+        None,
+    ),
+    (
+        "rococo-contracts",
+        "https://polkadot.js.org/apps/?rpc=wss%3A%2F%2Frococo-contracts-rpc.polkadot.io\
+         #/explorer/query/{block}",
+        None,
+    ),
+    (
+        "shibuya",
+        "https://shibuya.subscan.io/extrinsic/{extrinsic}",
+        Some("https://shibuya.subscan.io/account/{address}"),
+    ),
+];
+
+/// How to request funds on the built-in test networks without a faucet `cargo contract faucet`
+/// can call: `(name, instructions)`.
+const BUILTIN_FAUCETS: [(&str, &str); 2] = [
+    (
+        "rococo-contracts",
+        "Request ROC in the Rococo faucet Matrix room #rococo-faucet:matrix.org, sending \
+         `!drip {address}:1002`",
+    ),
+    (
+        "shibuya",
+        "Request SBY from the faucet of the Astar portal at https://portal.astar.network",
+    ),
+];
+
+/// A chain the extrinsics can be submitted to, selected by name with `--network`.
+///
+/// The settings not supplied are queried from the node.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Network {
+    /// The name the network was selected by.
+    #[serde(skip)]
+    pub name: String,
+    /// Url of a node of the network.
+    pub url: url::Url,
+    /// The address format of the network.
+    pub ss58_prefix: Option<u8>,
+    /// The symbol of the network's token.
+    pub token_symbol: Option<String>,
+    /// The number of decimals of the network's token.
+    pub token_decimals: Option<u8>,
+    /// Url of an extrinsic in a block explorer, with the placeholders `{extrinsic}` for the
+    /// hash of the extrinsic and `{block}` for the hash of the block including it.
+    pub explorer_extrinsic: Option<String>,
+    /// Url of an account in a block explorer, with the placeholder `{address}`.
+    pub explorer_account: Option<String>,
+    /// Url of the HTTP JSON-RPC service of the network's faucet, which `faucet` calls with the
+    /// method `faucet_drip` and the params `[address]`.
+    pub faucet: Option<url::Url>,
+}
+
+/// The address format and token of a chain, as reported by the `system_properties` RPC of its
+/// nodes.
+///
+/// Chains with several tokens report a list of symbols and decimals, of which the first is the
+/// native token. The settings a node does not report are `None`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ChainProperties {
+    /// The address format of the chain.
+    pub ss58_format: Option<u8>,
+    /// The symbol of the chain's native token.
+    pub token_symbol: Option<String>,
+    /// The number of decimals of the chain's native token.
+    pub token_decimals: Option<u8>,
+}
+
+impl ChainProperties {
+    /// Reads the properties from the JSON object returned by `system_properties`.
+    pub fn from_json(properties: &Value) -> Self {
+        let first = |key: &str| match &properties[key] {
+            Value::Array(values) => values.first().cloned().unwrap_or(Value::Null),
+            value => value.clone(),
+        };
+        let number = |value: Value| match value {
+            Value::String(number) => number.parse().ok(),
+            value => value.as_u64().and_then(|number| u8::try_from(number).ok()),
+        };
+        ChainProperties {
+            ss58_format: number(first("ss58Format")),
+            token_symbol: first("tokenSymbol").as_str().map(str::to_string),
+            token_decimals: number(first("tokenDecimals")),
+        }
+    }
+}
+
+/// The section of the address book and the deployments for the network the options connect to:
+/// the name of the network if one was selected, else the url of the node.
+pub(crate) fn section(submit_opts: &SubmitOpts) -> String {
+    match submit_opts.network_profile {
+        Some(ref network) => network.name.clone(),
+        None => submit_opts
+            .urls
+            .first()
+            .map(ToString::to_string)
+            .unwrap_or_default(),
+    }
+}
+
+impl Network {
+    /// Returns the network with the given name, either one of the `configured` networks or a
+    /// built-in one.
+    pub fn named(name: &str, configured: &BTreeMap<String, Network>) -> Result<Self> {
+        if let Some(network) = configured.get(name) {
+            return Ok(Network {
+                name: name.to_string(),
+                ..network.clone()
+            });
+        }
+        let builtin = BUILTIN_NETWORKS
+            .iter()
+            .find(|(builtin, ..)| *builtin == name);
+        match builtin {
+            Some((_, url, ss58_prefix, token_symbol, token_decimals)) => {
+                let explorer = BUILTIN_EXPLORERS
+                    .iter()
+                    .find(|(builtin, ..)| *builtin == name);
+                Ok(Network {
+                    name: name.to_string(),
+                    url: url.parse().expect("built-in network urls are valid"),
+                    ss58_prefix: Some(*ss58_prefix),
+                    token_symbol: Some(token_symbol.to_string()),
+                    token_decimals: Some(*token_decimals),
+                    explorer_extrinsic: explorer.map(|(_, extrinsic, _)| extrinsic.to_string()),
+                    explorer_account: explorer
+                        .and_then(|(_, _, account)| account.map(str::to_string)),
+                    faucet: None,
+                })
+            }
+            None => {
+                let known = configured
+                    .keys()
+                    .map(String::as_str)
+                    .chain(BUILTIN_NETWORKS.iter().map(|(name, ..)| *name))
+                    .collect::<Vec<_>>();
+                anyhow::bail!(
+                    "Unknown network {}, expected one of {}",
+                    name,
+                    known.join(", ")
+                )
+            }
+        }
+    }
+
+    /// Returns the explorer url of the extrinsic, unless it requires the block including the
+    /// extrinsic and its inclusion was not awaited.
+    pub fn extrinsic_link(&self, extrinsic: &H256, block: Option<&H256>) -> Option<String> {
+        let template = self.explorer_extrinsic.as_ref()?;
+        let mut link = template.replace("{extrinsic}", &format!("{:?}", extrinsic));
+        if link.contains("{block}") {
+            link = link.replace("{block}", &format!("{:?}", block?));
+        }
+        Some(link)
+    }
+
+    /// Returns how to request funds for the account with the SS58 address on a built-in test
+    /// network, whose faucet can not be called.
+    pub fn faucet_instructions(&self, address: &str) -> Option<String> {
+        BUILTIN_FAUCETS
+            .iter()
+            .find(|(builtin, _)| *builtin == self.name)
+            .map(|(_, instructions)| instructions.replace("{address}", address))
+    }
+
+    /// Returns the explorer url of the account with the SS58 address.
+    pub fn account_link(&self, address: &str) -> Option<String> {
+        self.explorer_account
+            .as_ref()
+            .map(|template| template.replace("{address}", address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_configured_before_builtin_networks() {
+        let shibuya = Network::named("shibuya", &BTreeMap::new()).unwrap();
+        assert_eq!(shibuya.ss58_prefix, Some(5));
+        assert_eq!(shibuya.token_decimals, Some(18));
+
+        let mut configured = BTreeMap::new();
+        let custom = Network {
+            name: "shibuya".to_string(),
+            url: "ws://shibuya.example.com".parse().unwrap(),
+            ss58_prefix: None,
+            token_symbol: None,
+            token_decimals: None,
+            explorer_extrinsic: None,
+            explorer_account: None,
+            faucet: None,
+        };
+        configured.insert("shibuya".to_string(), custom.clone());
+        assert_eq!(Network::named("shibuya", &configured).unwrap(), custom);
+
+        assert!(Network::named("unknown", &configured).is_err());
+    }
+
+    #[test]
+    fn reads_chain_properties() {
+        let properties = ChainProperties::from_json(&serde_json::json!({
+            "ss58Format": 5,
+            "tokenSymbol": "SBY",
+            "tokenDecimals": 18
+        }));
+        assert_eq!(properties.ss58_format, Some(5));
+        assert_eq!(properties.token_symbol.as_deref(), Some("SBY"));
+        assert_eq!(properties.token_decimals, Some(18));
+
+        let properties = ChainProperties::from_json(&serde_json::json!({
+            "tokenSymbol": ["ACA", "AUSD"],
+            "tokenDecimals": [12, 12]
+        }));
+        assert_eq!(properties.ss58_format, None);
+        assert_eq!(properties.token_symbol.as_deref(), Some("ACA"));
+        assert_eq!(properties.token_decimals, Some(12));
+
+        let properties = ChainProperties::from_json(&serde_json::json!({}));
+        assert_eq!(properties, ChainProperties::default());
+    }
+
+    #[test]
+    fn fills_in_explorer_links() {
+        let extrinsic = H256::repeat_byte(1);
+        let block = H256::repeat_byte(2);
+        let shibuya = Network::named("shibuya", &BTreeMap::new()).unwrap();
+        assert_eq!(
+            shibuya.extrinsic_link(&extrinsic, None).unwrap(),
+            format!("https://shibuya.subscan.io/extrinsic/{:?}", extrinsic)
+        );
+        assert_eq!(
+            shibuya.account_link("5Gr").unwrap(),
+            "https://shibuya.subscan.io/account/5Gr"
+        );
+
+        let local = Network::named("local", &BTreeMap::new()).unwrap();
+        assert_eq!(local.extrinsic_link(&extrinsic, None), None);
+        assert!(local
+            .extrinsic_link(&extrinsic, Some(&block))
+            .unwrap()
+            .ends_with(&format!("#/explorer/query/{:?}", block)));
+        assert_eq!(local.account_link("5Gr"), None);
+    }
+}
@@ -15,15 +15,167 @@
 // along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::{
+    collections::BTreeMap,
     env, fs,
-    io::{Cursor, Read, Seek, SeekFrom, Write},
-    path::Path,
+    io::{self, BufRead, Cursor, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process::Command,
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use heck::CamelCase as _;
+use structopt::StructOpt;
+use walkdir::WalkDir;
 
-pub(crate) fn execute<P>(name: &str, dir: Option<P>) -> Result<String>
+use super::cache::cache_dir;
+
+/// The file of a git template declaring its placeholders, as used by `cargo-generate`.
+const TEMPLATE_CONFIG: &str = "cargo-generate.toml";
+
+/// The version of ink! the built-in template depends on by default.
+const INK_VERSION: &str = "3.0.0-rc2";
+
+/// Setup and create a new smart contract project
+#[derive(Debug, StructOpt)]
+#[structopt(name = "new")]
+pub(crate) struct NewCommand {
+    /// The name of the newly created smart contract
+    name: String,
+    /// The optional target directory for the contract project
+    #[structopt(short, long, parse(from_os_str))]
+    target_dir: Option<PathBuf>,
+    /// The git URL of a template repository to create the project from, instead of the built-in
+    /// `flipper` template
+    #[structopt(long)]
+    template: Option<String>,
+    /// The branch or tag of the template repository, by default its default branch
+    #[structopt(long, requires = "template")]
+    branch: Option<String>,
+    /// Clone the template again instead of using the copy cached by an earlier `new`
+    #[structopt(long, requires = "template")]
+    refresh: bool,
+    /// The value of a placeholder of the template, `key=value`, instead of prompting for it
+    #[structopt(
+        long = "define",
+        short = "d",
+        parse(try_from_str = parse_define),
+        requires = "template"
+    )]
+    defines: Vec<(String, String)>,
+    #[structopt(flatten)]
+    manifest: ManifestOptions,
+}
+
+/// The fields of the `Cargo.toml` of the new project.
+#[derive(Debug, Default, StructOpt)]
+pub(crate) struct ManifestOptions {
+    /// The author of the contract, by default `[your_name] <[your_email]>`
+    #[structopt(long)]
+    author: Option<String>,
+    /// The SPDX license expression of the contract, e.g. `Apache-2.0`
+    #[structopt(long)]
+    license: Option<String>,
+    /// The Rust edition of the contract, by default `2018`
+    #[structopt(long, possible_values = &["2015", "2018", "2021"])]
+    edition: Option<String>,
+    /// The version of the ink! crates the contract depends on
+    #[structopt(long)]
+    ink_version: Option<String>,
+}
+
+impl ManifestOptions {
+    /// The placeholders of the fields in the `Cargo.toml` of the built-in template.
+    fn placeholders(&self) -> Vec<(String, String)> {
+        let author = self.author.as_deref();
+        let license = self
+            .license
+            .as_deref()
+            .map(|license| format!("license = \"{}\"\n", escape(license)));
+        vec![
+            (
+                "author".to_string(),
+                escape(author.unwrap_or("[your_name] <[your_email]>")),
+            ),
+            ("license".to_string(), license.unwrap_or_default()),
+            (
+                "edition".to_string(),
+                self.edition.as_deref().unwrap_or("2018").to_string(),
+            ),
+            (
+                "ink_version".to_string(),
+                escape(self.ink_version.as_deref().unwrap_or(INK_VERSION)),
+            ),
+        ]
+    }
+
+    /// The supplied fields, as the placeholders `authors`, `license`, `edition` and `ink_version`
+    /// of a git template.
+    fn defines(&self) -> BTreeMap<String, String> {
+        let fields = [
+            ("authors", &self.author),
+            ("license", &self.license),
+            ("edition", &self.edition),
+            ("ink_version", &self.ink_version),
+        ];
+        fields
+            .iter()
+            .filter_map(|(key, value)| Some((key.to_string(), (*value).clone()?)))
+            .collect()
+    }
+}
+
+/// Escapes the value for a TOML basic string.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl NewCommand {
+    pub fn exec(&self) -> Result<String> {
+        match self.template {
+            Some(ref template) => {
+                let out_dir = project_dir(&self.name, self.target_dir.as_ref())?;
+                let template_dir = cached_template(template, self.branch.as_deref(), self.refresh)?;
+                let mut placeholders = placeholders(&self.name);
+                placeholders.push(("project-name".to_string(), self.name.clone()));
+                placeholders.push(("crate_name".to_string(), self.name.clone()));
+                let mut defines = self.manifest.defines();
+                defines.extend(self.defines.iter().cloned());
+                placeholders.extend(template_placeholders(&template_dir, defines)?);
+                write_git_template(&template_dir, &out_dir, &placeholders)?;
+                Ok(format!("Created contract {} from {}", self.name, template))
+            }
+            None => unpack_template(&self.name, self.target_dir.as_ref(), &self.manifest),
+        }
+    }
+}
+
+fn parse_define(input: &str) -> Result<(String, String)> {
+    let mut parts = input.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(key), Some(value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => anyhow::bail!("Placeholder values are defined as `key=value`"),
+    }
+}
+
+/// The placeholders of the built-in template.
+fn placeholders(name: &str) -> Vec<(String, String)> {
+    vec![
+        ("name".to_string(), name.to_string()),
+        ("camel_name".to_string(), name.to_camel_case()),
+    ]
+}
+
+/// Replaces the `{{key}}` placeholders of the template file.
+fn substitute(contents: &str, placeholders: &[(String, String)]) -> String {
+    placeholders
+        .iter()
+        .fold(contents.to_string(), |contents, (key, value)| {
+            contents.replace(&format!("{{{{{}}}}}", key), value)
+        })
+}
+
+/// Returns the directory of the new project, creating it if it does not exist yet.
+fn project_dir<P>(name: &str, dir: Option<P>) -> Result<PathBuf>
 where
     P: AsRef<Path>,
 {
@@ -40,6 +192,59 @@ where
     if !out_dir.exists() {
         fs::create_dir(&out_dir)?;
     }
+    Ok(out_dir)
+}
+
+/// Writes the file of the template to the project, failing instead of overwriting an existing
+/// file.
+fn write_file(out_dir: &Path, name: &str, contents: &[u8], mode: Option<u32>) -> Result<()> {
+    let outpath = out_dir.join(name);
+    if let Some(p) = outpath.parent() {
+        if !p.exists() {
+            fs::create_dir_all(&p)?;
+        }
+    }
+    let mut outfile = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(outpath.clone())
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::AlreadyExists {
+                anyhow::anyhow!("New contract file {} already exists", name)
+            } else {
+                anyhow::anyhow!(e)
+            }
+        })?;
+    outfile.write_all(contents)?;
+
+    // Set permissions
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Some(mode) = mode {
+            fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    Ok(())
+}
+
+/// Creates the project from the built-in template, with the default fields of the manifest.
+#[cfg(test)]
+pub(crate) fn execute<P>(name: &str, dir: Option<P>) -> Result<String>
+where
+    P: AsRef<Path>,
+{
+    unpack_template(name, dir, &ManifestOptions::default())
+}
+
+fn unpack_template<P>(name: &str, dir: Option<P>, manifest: &ManifestOptions) -> Result<String>
+where
+    P: AsRef<Path>,
+{
+    let out_dir = project_dir(name, dir)?;
 
     let template = include_bytes!(concat!(env!("OUT_DIR"), "/template.zip"));
     let mut cursor = Cursor::new(Vec::new());
@@ -47,6 +252,8 @@ where
     cursor.seek(SeekFrom::Start(0))?;
 
     let mut archive = zip::ZipArchive::new(cursor)?;
+    let mut placeholders = placeholders(name);
+    placeholders.extend(manifest.placeholders());
 
     for i in 0..archive.len() {
         let mut file = archive.by_index(i)?;
@@ -54,46 +261,171 @@ where
         file.read_to_string(&mut contents)?;
 
         // replace template placeholders
-        let contents = contents.replace("{{name}}", name);
-        let contents = contents.replace("{{camel_name}}", &name.to_camel_case());
-
-        let outpath = out_dir.join(file.name());
+        let contents = substitute(&contents, &placeholders);
 
         if (&*file.name()).ends_with('/') {
-            fs::create_dir_all(&outpath)?;
+            fs::create_dir_all(out_dir.join(file.name()))?;
         } else {
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    fs::create_dir_all(&p)?;
-                }
-            }
-            let mut outfile = fs::OpenOptions::new()
-                .write(true)
-                .create_new(true)
-                .open(outpath.clone())
-                .map_err(|e| {
-                    if e.kind() == std::io::ErrorKind::AlreadyExists {
-                        anyhow::anyhow!("New contract file {} already exists", file.name())
-                    } else {
-                        anyhow::anyhow!(e)
-                    }
-                })?;
-
-            outfile.write_all(contents.as_bytes())?;
+            write_file(&out_dir, file.name(), contents.as_bytes(), file.unix_mode())?;
         }
+    }
 
-        // Get and set permissions
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
+    Ok(format!("Created contract {}", name))
+}
 
-            if let Some(mode) = file.unix_mode() {
-                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
+/// The name of the cache directory of the template at the url and branch, e.g.
+/// `github.com_org_dex.git@main`.
+fn template_key(url: &str, branch: Option<&str>) -> String {
+    let url = url.split("://").last().unwrap_or(url);
+    let mut key: String = url
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect();
+    if let Some(branch) = branch {
+        key.push('@');
+        key.extend(branch.chars().map(|c| match c {
+            '/' | '\\' | ':' => '_',
+            _ => c,
+        }));
+    }
+    key
+}
+
+/// Returns the directory of the template in the cache, cloning it unless it was cached by an
+/// earlier `new` or `refresh` is set.
+///
+/// If cloning the template again fails, e.g. offline, the cached copy is used.
+fn cached_template(url: &str, branch: Option<&str>, refresh: bool) -> Result<PathBuf> {
+    let templates = cache_dir()
+        .ok_or_else(|| anyhow::anyhow!("Caching the template requires the HOME directory"))?
+        .join("templates");
+    let dir = templates.join(template_key(url, branch));
+    if dir.exists() && !refresh {
+        log::info!("Using the template cached in {}", dir.display());
+        return Ok(dir);
+    }
+    fs::create_dir_all(&templates).context(format!("Failed to create {}", templates.display()))?;
+    // Cloned next to the cached copy, which is only replaced once the clone succeeded.
+    let tmp_dir = tempfile::Builder::new()
+        .prefix("cargo-contract-template_")
+        .tempdir_in(&templates)?;
+    match clone_template(url, branch, tmp_dir.path()) {
+        Ok(()) => {
+            if dir.exists() {
+                fs::remove_dir_all(&dir)?;
             }
+            fs::rename(tmp_dir.into_path(), &dir)
+                .context(format!("Failed to cache the template in {}", dir.display()))?;
+            Ok(dir)
         }
+        Err(err) if dir.exists() => {
+            log::warn!("{:#}, using the cached copy", err);
+            Ok(dir)
+        }
+        Err(err) => Err(err),
     }
+}
 
-    Ok(format!("Created contract {}", name))
+/// Clones the template repository with git, without its history.
+fn clone_template(url: &str, branch: Option<&str>, dir: &Path) -> Result<()> {
+    let mut cmd = Command::new("git");
+    cmd.args(["clone", "--depth", "1"]);
+    if let Some(branch) = branch {
+        cmd.args(["--branch", branch]);
+    }
+    let status = cmd
+        .arg(url)
+        .arg(dir)
+        .status()
+        .context("Creating a contract from a template requires git to be installed")?;
+    if !status.success() {
+        anyhow::bail!("Failed to clone the template {}", url)
+    }
+    Ok(())
+}
+
+/// The values of the placeholders declared in the `cargo-generate.toml` of the template, taken
+/// from the `defines` or else prompted for on the terminal.
+fn template_placeholders(
+    template: &Path,
+    mut defines: BTreeMap<String, String>,
+) -> Result<Vec<(String, String)>> {
+    let config = template.join(TEMPLATE_CONFIG);
+    let declared = if config.exists() {
+        let config: toml::value::Table = toml::from_str(&fs::read_to_string(&config)?).context(
+            format!("Failed to parse the {} of the template", TEMPLATE_CONFIG),
+        )?;
+        match config.get("placeholders") {
+            Some(toml::Value::Table(placeholders)) => placeholders.clone(),
+            _ => Default::default(),
+        }
+    } else {
+        Default::default()
+    };
+    for (key, placeholder) in declared {
+        if defines.contains_key(&key) {
+            continue;
+        }
+        let prompt = placeholder.get("prompt").and_then(|v| v.as_str());
+        let default = placeholder.get("default").and_then(|v| v.as_str());
+        let value = ask(prompt.unwrap_or(&key), default)?;
+        defines.insert(key, value);
+    }
+    Ok(defines.into_iter().collect())
+}
+
+/// Prompts for the value of a placeholder on the terminal.
+fn ask(prompt: &str, default: Option<&str>) -> Result<String> {
+    match default {
+        Some(default) => eprint!("{} [{}]: ", prompt, default),
+        None => eprint!("{}: ", prompt),
+    }
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    match (answer.trim(), default) {
+        ("", Some(default)) => Ok(default.to_string()),
+        ("", None) => anyhow::bail!("No value supplied for `{}`, pass it with --define", prompt),
+        (answer, _) => Ok(answer.to_string()),
+    }
+}
+
+/// Writes the files of the cloned template to the project, replacing the placeholders in the
+/// text files and skipping the git repository and the `cargo-generate.toml`.
+fn write_git_template(
+    template: &Path,
+    out_dir: &Path,
+    placeholders: &[(String, String)],
+) -> Result<()> {
+    let entries = WalkDir::new(template)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|entry| entry.depth() != 1 || entry.file_name() != ".git");
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.path().strip_prefix(template)?.to_string_lossy();
+        if !entry.file_type().is_file() || name == TEMPLATE_CONFIG {
+            continue;
+        }
+        let name = name.replace(std::path::MAIN_SEPARATOR, "/");
+        let contents = fs::read(entry.path())?;
+        let contents = match String::from_utf8(contents) {
+            Ok(text) => substitute(&text, placeholders).into_bytes(),
+            Err(binary) => binary.into_bytes(),
+        };
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(entry.metadata()?.permissions().mode())
+        };
+        #[cfg(not(unix))]
+        let mode = None;
+        write_file(out_dir, &name, &contents, mode)?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -130,6 +462,84 @@ mod tests {
         })
     }
 
+    #[test]
+    fn fills_in_the_manifest_fields() {
+        with_tmp_dir(|path| {
+            let manifest = ManifestOptions::from_iter(&[
+                "new",
+                "--author",
+                "Alice <alice@example.com>",
+                "--license",
+                "Apache-2.0",
+                "--edition",
+                "2021",
+                "--ink-version",
+                "3.0.0-rc3",
+            ]);
+            unpack_template("fields", Some(path), &manifest)?;
+
+            let manifest = fs::read_to_string(path.join("fields/Cargo.toml"))?;
+            let manifest: toml::value::Table = toml::from_str(&manifest)?;
+            let package = &manifest["package"];
+            assert_eq!(
+                package["authors"][0].as_str(),
+                Some("Alice <alice@example.com>")
+            );
+            assert_eq!(package["license"].as_str(), Some("Apache-2.0"));
+            assert_eq!(package["edition"].as_str(), Some("2021"));
+            let ink_lang = &manifest["dependencies"]["ink_lang"];
+            assert_eq!(ink_lang["version"].as_str(), Some("3.0.0-rc3"));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn expands_a_git_template() {
+        with_tmp_dir(|path| {
+            let template = path.join("template");
+            fs::create_dir_all(template.join(".git"))?;
+            fs::write(template.join(".git/HEAD"), "{{name}}")?;
+            fs::create_dir_all(template.join("src"))?;
+            fs::write(template.join("Cargo.toml"), "name = \"{{project-name}}\"")?;
+            fs::write(
+                template.join("src/lib.rs"),
+                "// {{camel_name}}: {{description}}",
+            )?;
+            fs::write(
+                template.join(TEMPLATE_CONFIG),
+                "[placeholders.description]\nprompt = \"Description\"\n",
+            )?;
+
+            let mut defines = BTreeMap::new();
+            defines.insert("description".to_string(), "a dex".to_string());
+            let mut placeholders = placeholders("my_dex");
+            placeholders.push(("project-name".to_string(), "my_dex".to_string()));
+            placeholders.extend(template_placeholders(&template, defines)?);
+            let out_dir = project_dir("my_dex", Some(path))?;
+            write_git_template(&template, &out_dir, &placeholders)?;
+
+            let manifest = fs::read_to_string(out_dir.join("Cargo.toml"))?;
+            assert_eq!(manifest, "name = \"my_dex\"");
+            let lib = fs::read_to_string(out_dir.join("src/lib.rs"))?;
+            assert_eq!(lib, "// MyDex: a dex");
+            assert!(!out_dir.join(".git").exists());
+            assert!(!out_dir.join(TEMPLATE_CONFIG).exists());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn names_the_cached_templates_after_url_and_branch() {
+        assert_eq!(
+            template_key("https://github.com/org/dex.git", Some("release/1.0")),
+            "github.com_org_dex.git@release_1.0"
+        );
+        assert_eq!(
+            template_key("git@github.com:org/dex.git", None),
+            "git_github.com_org_dex.git"
+        );
+    }
+
     #[test]
     fn dont_overwrite_existing_files_not_in_cargo_project() {
         with_tmp_dir(|path| {
@@ -0,0 +1,374 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    env, fs,
+    net::{Ipv4Addr, SocketAddr, TcpStream},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+use walkdir::WalkDir;
+
+use super::{cache::cache_dir, wasm_opt::download_dir};
+use crate::util;
+
+/// The name of the contracts node binary.
+const NODE_BINARY: &str = "substrate-contracts-node";
+
+/// The release of `substrate-contracts-node` downloaded if none is installed.
+const PINNED_VERSION: &str = "v0.24.0";
+
+/// Name of the file recording the node started by `node start`, in the download directory.
+const STATE_FILE: &str = "node.json";
+
+/// Name of the file the node logs to, in its base path.
+const LOG_FILE: &str = "node.log";
+
+/// The time to wait for a started node to accept connections, or a stopped one to exit.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Start, stop or inspect a local contracts node.
+#[derive(Debug, StructOpt)]
+pub(crate) enum NodeCommand {
+    /// Start a `--dev` contracts node in the background, with a temporary base path
+    #[structopt(name = "start")]
+    Start {
+        /// The port of the websocket endpoint
+        #[structopt(long, default_value = "9944")]
+        port: u16,
+        /// The contracts node binary, by default the `substrate-contracts-node` on the `PATH`, else
+        /// the pinned release, which is downloaded if missing
+        #[structopt(long, parse(from_os_str))]
+        node: Option<PathBuf>,
+    },
+    /// Stop the contracts node, removing its base path
+    #[structopt(name = "stop")]
+    Stop,
+    /// Show the websocket endpoint of the running contracts node
+    #[structopt(name = "status")]
+    Status,
+}
+
+/// The contracts node started by `node start`.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct RunningNode {
+    pid: u32,
+    port: u16,
+    base_path: PathBuf,
+}
+
+impl RunningNode {
+    /// The path of the file recording the running node.
+    fn path() -> Result<PathBuf> {
+        let dir = download_dir()
+            .ok_or_else(|| anyhow::anyhow!("Managing the node requires the HOME directory"))?;
+        Ok(dir.join(STATE_FILE))
+    }
+
+    /// Reads the running node, `None` if none was started.
+    fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json =
+            fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+        let node =
+            serde_json::from_str(&json).context(format!("Failed to parse {}", path.display()))?;
+        Ok(Some(node))
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json).context(format!("Failed to write {}", path.display()))
+    }
+
+    fn url(&self) -> String {
+        format!("ws://127.0.0.1:{}", self.port)
+    }
+}
+
+impl NodeCommand {
+    pub fn exec(&self) -> Result<String> {
+        let path = RunningNode::path()?;
+        let running = RunningNode::load(&path)?;
+        match self {
+            NodeCommand::Start { port, node } => {
+                if let Some(running) = running.filter(|running| is_running(running.pid)) {
+                    anyhow::bail!(
+                        "A contracts node is already running at {}, stop it with `cargo contract \
+                         node stop`",
+                        running.url()
+                    )
+                }
+                let running = start(*port, node.as_deref())?;
+                running.save(&path)?;
+                Ok(format!(
+                    "Contracts node running at {}, logging to {}\n\tStop it with `cargo contract \
+                     node stop`",
+                    running.url().bold(),
+                    running.base_path.join(LOG_FILE).display()
+                ))
+            }
+            NodeCommand::Stop => {
+                let running = running.ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "No contracts node was started with `cargo contract node start`"
+                    )
+                })?;
+                if is_running(running.pid) {
+                    terminate(running.pid)?;
+                }
+                if running.base_path.exists() {
+                    fs::remove_dir_all(&running.base_path)
+                        .context(format!("Failed to remove {}", running.base_path.display()))?;
+                }
+                fs::remove_file(&path).context(format!("Failed to remove {}", path.display()))?;
+                Ok(format!("Stopped the contracts node at {}", running.url()))
+            }
+            NodeCommand::Status => match running {
+                Some(running) if is_running(running.pid) => Ok(format!(
+                    "Contracts node running at {}, with the base path {}",
+                    running.url().bold(),
+                    running.base_path.display()
+                )),
+                Some(running) => anyhow::bail!(
+                    "The contracts node at {} exited, see its log in {}",
+                    running.url(),
+                    running.base_path.join(LOG_FILE).display()
+                ),
+                None => anyhow::bail!(
+                    "No contracts node is running, start one with `cargo contract node start`"
+                ),
+            },
+        }
+    }
+}
+
+/// Starts the node in the background with a temporary base path, and waits until it accepts
+/// connections.
+///
+/// The base path is kept if the node fails to start, for its log.
+fn start(port: u16, node: Option<&Path>) -> Result<RunningNode> {
+    let binary = find(node)?;
+    let base_path = tempfile::Builder::new()
+        .prefix("cargo-contract-node.")
+        .tempdir()?
+        .into_path();
+    let log_path = base_path.join(LOG_FILE);
+    let log =
+        fs::File::create(&log_path).context(format!("Failed to create {}", log_path.display()))?;
+    println!(
+        " {} {} on port {}",
+        "Starting".bright_green().bold(),
+        binary.display(),
+        port
+    );
+    let mut child = Command::new(&binary)
+        .arg("--dev")
+        .arg("--base-path")
+        .arg(&base_path)
+        .args(["--ws-port", &port.to_string()])
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .spawn()
+        .context(format!(
+            "Failed to start the contracts node {}, pass its path with --node",
+            binary.display()
+        ))?;
+    if let Err(err) = wait_until_listening(&mut child, port) {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(err.context(format!("See the log of the node in {}", log_path.display())));
+    }
+    Ok(RunningNode {
+        pid: child.id(),
+        port,
+        base_path,
+    })
+}
+
+/// Waits until the started node accepts connections on the port of its websocket endpoint.
+pub(crate) fn wait_until_listening(child: &mut Child, port: u16) -> Result<()> {
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let started = Instant::now();
+    while TcpStream::connect(address).is_err() {
+        if let Some(status) = child.try_wait()? {
+            anyhow::bail!("The contracts node exited with {}", status)
+        }
+        if started.elapsed() > TIMEOUT {
+            anyhow::bail!(
+                "The contracts node did not start listening on port {}",
+                port
+            )
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    Ok(())
+}
+
+/// Returns the contracts node binary: the supplied one, else the one on the `PATH`, else the
+/// pinned release, downloading it if missing.
+pub(crate) fn find(node: Option<&Path>) -> Result<PathBuf> {
+    if let Some(node) = node {
+        return Ok(node.to_path_buf());
+    }
+    if let Ok(path) = which::which(NODE_BINARY) {
+        return Ok(path);
+    }
+    let dir = cache_dir()
+        .ok_or_else(|| anyhow::anyhow!("Downloading the node requires the HOME directory"))?
+        .join(format!("{}-{}", NODE_BINARY, PINNED_VERSION));
+    if let Some(binary) = release_binary(&dir) {
+        return Ok(binary);
+    }
+    download(&dir)?;
+    release_binary(&dir).ok_or_else(|| {
+        anyhow::anyhow!(
+            "The {} release has no {} binary",
+            PINNED_VERSION,
+            NODE_BINARY
+        )
+    })
+}
+
+/// The node binary in the unpacked release, whose archives nest it in directories named after
+/// the platform.
+fn release_binary(dir: &Path) -> Option<PathBuf> {
+    let binary = format!("{}{}", NODE_BINARY, env::consts::EXE_SUFFIX);
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .find(|entry| entry.file_type().is_file() && entry.file_name() == binary.as_str())
+        .map(|entry| entry.into_path())
+}
+
+/// Downloads and unpacks the pinned release of the node.
+fn download(dir: &Path) -> Result<()> {
+    // FIXME: pin the SHA-256 digests of the published archives.
+    let (archive, sha256) = match (env::consts::ARCH, env::consts::OS) {
+        ("x86_64", "linux") => (
+            "substrate-contracts-node-linux.tar.gz",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+        ),
+        (_, "macos") => (
+            "substrate-contracts-node-mac-universal.tar.gz",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+        ),
+        (arch, os) => anyhow::bail!(
+            "There is no release of {} for {}-{}, install it and pass its path with --node",
+            NODE_BINARY,
+            arch,
+            os
+        ),
+    };
+    let url = format!(
+        "https://github.com/paritytech/substrate-contracts-node/releases/download/{}/{}",
+        PINNED_VERSION, archive
+    );
+    util::download_archive(&url, sha256, dir, "the node")
+}
+
+/// Whether the process is alive.
+fn is_running(pid: u32) -> bool {
+    if cfg!(windows) {
+        let output = Command::new("tasklist")
+            .args(["/NH", "/FI", &format!("PID eq {}", pid)])
+            .output();
+        return output
+            .map(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+            .unwrap_or(false);
+    }
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Stops the process and waits until it exited.
+fn terminate(pid: u32) -> Result<()> {
+    let status = if cfg!(windows) {
+        Command::new("taskkill")
+            .args(["/F", "/PID", &pid.to_string()])
+            .status()
+    } else {
+        Command::new("kill").arg(pid.to_string()).status()
+    };
+    if !status.map(|status| status.success()).unwrap_or(false) {
+        anyhow::bail!(
+            "Failed to stop the contracts node with the process id {}",
+            pid
+        )
+    }
+    let stopped = Instant::now();
+    while is_running(pid) {
+        if stopped.elapsed() > TIMEOUT {
+            anyhow::bail!(
+                "The contracts node with the process id {} did not exit",
+                pid
+            )
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_the_running_node() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            let path = dir.join(".cargo-contract").join(STATE_FILE);
+            assert_eq!(RunningNode::load(&path)?, None);
+            let running = RunningNode {
+                pid: std::process::id(),
+                port: 9944,
+                base_path: dir.join("node"),
+            };
+            running.save(&path)?;
+            assert_eq!(RunningNode::load(&path)?, Some(running));
+            assert!(is_running(std::process::id()));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn finds_the_binary_in_the_release() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            assert_eq!(release_binary(dir), None);
+            let nested = dir.join("artifacts").join("substrate-contracts-node-linux");
+            fs::create_dir_all(&nested)?;
+            let binary = nested.join(format!("{}{}", NODE_BINARY, env::consts::EXE_SUFFIX));
+            fs::write(&binary, "")?;
+            assert_eq!(release_binary(dir), Some(binary));
+            Ok(())
+        })
+    }
+}
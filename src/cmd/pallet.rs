@@ -0,0 +1,253 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::Result;
+use codec::Decode;
+use frame_metadata::{ModuleMetadata, RuntimeMetadata, RuntimeMetadataPrefixed};
+
+use super::module_error::decoded;
+
+/// The revision of the contracts pallet of the chain, whose calls take different arguments
+/// across Substrate releases.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum ContractsVersion {
+    /// Substrate 2.0: code is uploaded with `put_code`, and `instantiate` takes no salt.
+    V2,
+    /// Substrate 3.0: `instantiate` takes a salt for deriving the contract address, and code is
+    /// only uploaded together with instantiating it.
+    V3,
+}
+
+/// The arguments of `Contracts::instantiate` in each revision.
+const INSTANTIATE_V2: [&str; 4] = ["endowment", "gas_limit", "code_hash", "data"];
+const INSTANTIATE_V3: [&str; 5] = ["endowment", "gas_limit", "code_hash", "data", "salt"];
+/// The arguments of `Contracts::call`, unchanged between the revisions.
+const CALL: [&str; 4] = ["dest", "value", "gas_limit", "data"];
+
+/// The host functions of the `seal0` module of the contracts API provided by Substrate 2.0.
+const HOST_FUNCTIONS_V2: [&str; 30] = [
+    "gas",
+    "seal_set_storage",
+    "seal_clear_storage",
+    "seal_get_storage",
+    "seal_transfer",
+    "seal_call",
+    "seal_instantiate",
+    "seal_terminate",
+    "seal_input",
+    "seal_return",
+    "seal_caller",
+    "seal_address",
+    "seal_weight_to_fee",
+    "seal_gas_left",
+    "seal_balance",
+    "seal_value_transferred",
+    "seal_random",
+    "seal_now",
+    "seal_minimum_balance",
+    "seal_tombstone_deposit",
+    "seal_restore_to",
+    "seal_deposit_event",
+    "seal_set_rent_allowance",
+    "seal_rent_allowance",
+    "seal_println",
+    "seal_block_number",
+    "seal_hash_sha2_256",
+    "seal_hash_keccak_256",
+    "seal_hash_blake2_256",
+    "seal_hash_blake2_128",
+];
+/// Substrate 3.0 added calling the chain extensions of the runtime.
+const HOST_FUNCTIONS_V3: [&str; 1] = ["seal_call_chain_extension"];
+
+/// The contracts pallet in the runtime metadata.
+fn contracts_module(metadata: &RuntimeMetadataPrefixed) -> Result<&ModuleMetadata> {
+    let modules = match metadata.1 {
+        RuntimeMetadata::V12(ref metadata) => decoded(&metadata.modules),
+        _ => anyhow::bail!("Unsupported runtime metadata version, expected V12"),
+    };
+    modules
+        .into_iter()
+        .flatten()
+        .find(|module| decoded(&module.name).map(String::as_str) == Some("Contracts"))
+        .ok_or_else(|| {
+            anyhow::anyhow!("The runtime of the chain does not include the Contracts pallet")
+        })
+}
+
+impl ContractsVersion {
+    /// Detects the revision from the arguments of the calls of the pallet in the runtime metadata.
+    pub fn from_metadata(metadata: &RuntimeMetadataPrefixed) -> Result<Self> {
+        let module = contracts_module(metadata)?;
+        let arguments = |call: &str| -> Option<Vec<&str>> {
+            let calls = decoded(module.calls.as_ref()?)?;
+            let function = calls
+                .iter()
+                .find(|function| decoded(&function.name).map(String::as_str) == Some(call))?;
+            decoded(&function.arguments)?
+                .iter()
+                .map(|argument| decoded(&argument.name).map(String::as_str))
+                .collect()
+        };
+        let unsupported = |call: &str, arguments: Option<Vec<&str>>| {
+            anyhow::anyhow!(
+                "Unsupported contracts pallet, its {} call takes the arguments {:?}",
+                call,
+                arguments.unwrap_or_default()
+            )
+        };
+        let call = arguments("call");
+        if call.as_deref() != Some(&CALL[..]) {
+            return Err(unsupported("call", call));
+        }
+        match arguments("instantiate") {
+            Some(ref instantiate) if instantiate[..] == INSTANTIATE_V2 => Ok(ContractsVersion::V2),
+            Some(ref instantiate) if instantiate[..] == INSTANTIATE_V3 => Ok(ContractsVersion::V3),
+            instantiate => Err(unsupported("instantiate", instantiate)),
+        }
+    }
+
+    /// Whether the revision provides the host function, imported from the `module` of the
+    /// contracts API.
+    ///
+    /// Both revisions only provide the `seal0` module, later versions of the API were added to
+    /// the pallet afterwards.
+    pub fn provides(self, module: &str, name: &str) -> bool {
+        module == "seal0"
+            && (HOST_FUNCTIONS_V2.contains(&name)
+                || self == ContractsVersion::V3 && HOST_FUNCTIONS_V3.contains(&name))
+    }
+}
+
+/// The deposits the contracts pallet reserves from the origin for what it stores, from the
+/// constants of the pallet. Zero if the pallet has no such constant.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Deposits {
+    /// The deposit per byte of storage, `DepositPerStorageByte`, renamed `DepositPerByte` by
+    /// later releases.
+    pub per_byte: u128,
+    /// The deposit of each contract, `DepositPerContract`.
+    pub per_contract: u128,
+}
+
+impl Deposits {
+    pub fn from_metadata(metadata: &RuntimeMetadataPrefixed) -> Result<Self> {
+        let module = contracts_module(metadata)?;
+        let constant = |names: &[&str]| -> u128 {
+            let constants = decoded(&module.constants).map(Vec::as_slice);
+            let value = constants.unwrap_or_default().iter().find_map(|constant| {
+                let name = decoded(&constant.name)?;
+                if !names.contains(&name.as_str()) {
+                    return None;
+                }
+                u128::decode(&mut &decoded(&constant.value)?[..]).ok()
+            });
+            value.unwrap_or_default()
+        };
+        Ok(Deposits {
+            per_byte: constant(&["DepositPerStorageByte", "DepositPerByte"]),
+            per_contract: constant(&["DepositPerContract"]),
+        })
+    }
+
+    /// The deposit for storing the code.
+    pub fn code(&self, code: &[u8]) -> u128 {
+        self.per_byte.saturating_mul(code.len() as u128)
+    }
+
+    /// The deposit for instantiating a contract, excluding the storage its constructor writes.
+    pub fn contract(&self) -> u128 {
+        self.per_contract
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+    use frame_metadata::{
+        DecodeDifferent, ExtrinsicMetadata, FunctionArgumentMetadata, FunctionMetadata,
+        ModuleConstantMetadata, RuntimeMetadataV12, META_RESERVED,
+    };
+
+    fn metadata(instantiate: &[&str]) -> RuntimeMetadataPrefixed {
+        let function = |name: &str, arguments: &[&str]| FunctionMetadata {
+            name: DecodeDifferent::Decoded(name.to_string()),
+            arguments: DecodeDifferent::Decoded(
+                arguments
+                    .iter()
+                    .map(|argument| FunctionArgumentMetadata {
+                        name: DecodeDifferent::Decoded(argument.to_string()),
+                        ty: DecodeDifferent::Decoded("Vec<u8>".to_string()),
+                    })
+                    .collect(),
+            ),
+            documentation: DecodeDifferent::Decoded(Vec::new()),
+        };
+        let module = ModuleMetadata {
+            name: DecodeDifferent::Decoded("Contracts".to_string()),
+            storage: None,
+            calls: Some(DecodeDifferent::Decoded(vec![
+                function("call", &CALL),
+                function("instantiate", instantiate),
+            ])),
+            event: None,
+            constants: DecodeDifferent::Decoded(vec![ModuleConstantMetadata {
+                name: DecodeDifferent::Decoded("DepositPerStorageByte".to_string()),
+                ty: DecodeDifferent::Decoded("BalanceOf<T>".to_string()),
+                value: DecodeDifferent::Decoded(10u128.encode()),
+                documentation: DecodeDifferent::Decoded(Vec::new()),
+            }]),
+            errors: DecodeDifferent::Decoded(Vec::new()),
+            index: 18,
+        };
+        RuntimeMetadataPrefixed(
+            META_RESERVED,
+            RuntimeMetadata::V12(RuntimeMetadataV12 {
+                modules: DecodeDifferent::Decoded(vec![module]),
+                extrinsic: ExtrinsicMetadata {
+                    version: 4,
+                    signed_extensions: Vec::new(),
+                },
+            }),
+        )
+    }
+
+    #[test]
+    fn detects_the_revision_from_the_arguments() {
+        let version =
+            |instantiate: &[&str]| ContractsVersion::from_metadata(&metadata(instantiate));
+        assert_eq!(version(&INSTANTIATE_V2).unwrap(), ContractsVersion::V2);
+        assert_eq!(version(&INSTANTIATE_V3).unwrap(), ContractsVersion::V3);
+        assert!(version(&["value", "gas_limit", "storage_deposit_limit"]).is_err());
+    }
+
+    #[test]
+    fn estimates_the_deposits_from_the_constants() {
+        let deposits = Deposits::from_metadata(&metadata(&INSTANTIATE_V3)).unwrap();
+        assert_eq!(deposits.code(&[0; 1000]), 10_000);
+        assert_eq!(deposits.contract(), 0);
+    }
+
+    #[test]
+    fn provides_the_host_functions_of_the_revision() {
+        assert!(ContractsVersion::V2.provides("seal0", "seal_input"));
+        assert!(!ContractsVersion::V2.provides("seal0", "seal_call_chain_extension"));
+        assert!(ContractsVersion::V3.provides("seal0", "seal_call_chain_extension"));
+        assert!(!ContractsVersion::V3.provides("seal1", "seal_call"));
+        assert!(!ContractsVersion::V3.provides("env", "seal_input"));
+    }
+}
@@ -0,0 +1,346 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{ser, Serialize};
+use serde_json::{value::Serializer as ValueSerializer, Error, Map, Value};
+
+use super::bindgen::camel_case;
+
+/// Converts the result to JSON with the `camelCase` field names of polkadot.js.
+///
+/// Only the names of struct fields are converted, the keys of maps, e.g. the aliases of the
+/// address book, are kept.
+pub(crate) fn to_pjs_value<T: Serialize>(result: &T) -> Result<Value, Error> {
+    result.serialize(Serializer)
+}
+
+/// Serializes into a [`Value`] like [`serde_json::to_value`], renaming the struct fields.
+struct Serializer;
+
+/// A sequence, tuple or map being serialized, with the variant enclosing it, if any.
+struct Compound {
+    variant: Option<&'static str>,
+    elements: Vec<Value>,
+    fields: Map<String, Value>,
+    key: Option<String>,
+}
+
+impl Compound {
+    fn new(variant: Option<&'static str>) -> Self {
+        Compound {
+            variant,
+            elements: Vec::new(),
+            fields: Map::new(),
+            key: None,
+        }
+    }
+
+    /// Nests the value in an object keyed by the variant, the way serde_json encodes variants.
+    fn wrap(variant: Option<&'static str>, value: Value) -> Value {
+        match variant {
+            Some(variant) => {
+                let mut object = Map::new();
+                object.insert(variant.to_string(), value);
+                Value::Object(object)
+            }
+            None => value,
+        }
+    }
+
+    fn elements(self) -> Result<Value, Error> {
+        Ok(Self::wrap(self.variant, Value::Array(self.elements)))
+    }
+
+    fn fields(self) -> Result<Value, Error> {
+        Ok(Self::wrap(self.variant, Value::Object(self.fields)))
+    }
+
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.elements.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn field<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        self.fields
+            .insert(camel_case(key), value.serialize(Serializer)?);
+        Ok(())
+    }
+}
+
+macro_rules! serialize_with_serde_json {
+    ($($method:ident($ty:ty),)*) => {
+        $(fn $method(self, value: $ty) -> Result<Value, Error> {
+            ValueSerializer.$method(value)
+        })*
+    };
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = Compound;
+    type SerializeTuple = Compound;
+    type SerializeTupleStruct = Compound;
+    type SerializeTupleVariant = Compound;
+    type SerializeMap = Compound;
+    type SerializeStruct = Compound;
+    type SerializeStructVariant = Compound;
+
+    serialize_with_serde_json! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+    }
+
+    fn serialize_none(self) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        Ok(Value::from(variant))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        Ok(Compound::wrap(Some(variant), value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound, Error> {
+        Ok(Compound::new(None))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound, Error> {
+        Ok(Compound::new(None))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Compound, Error> {
+        Ok(Compound::new(None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound, Error> {
+        Ok(Compound::new(Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound, Error> {
+        Ok(Compound::new(None))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound, Error> {
+        Ok(Compound::new(None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound, Error> {
+        Ok(Compound::new(Some(variant)))
+    }
+}
+
+impl ser::SerializeSeq for Compound {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.elements()
+    }
+}
+
+impl ser::SerializeTuple for Compound {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.elements()
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.elements()
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.elements()
+    }
+}
+
+impl ser::SerializeMap for Compound {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        // Keys are kept, and like in serde_json numbers are written as strings.
+        self.key = Some(match key.serialize(ValueSerializer)? {
+            Value::String(key) => key,
+            key => key.to_string(),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().expect("serialize_key is called first");
+        self.fields.insert(key, value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.fields()
+    }
+}
+
+impl ser::SerializeStruct for Compound {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.fields()
+    }
+}
+
+impl ser::SerializeStructVariant for Compound {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.fields()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn renames_the_struct_fields_only() {
+        #[derive(Serialize)]
+        struct Result {
+            code_hash: String,
+            block_number: u32,
+            contracts: BTreeMap<String, u32>,
+            args: Value,
+        }
+        let result = Result {
+            code_hash: "0x00".to_string(),
+            block_number: 1,
+            contracts: vec![("my_dex".to_string(), 2)].into_iter().collect(),
+            args: json!({ "total_supply": 3 }),
+        };
+        assert_eq!(
+            to_pjs_value(&result).unwrap(),
+            json!({
+                "codeHash": "0x00",
+                "blockNumber": 1,
+                "contracts": { "my_dex": 2 },
+                "args": { "total_supply": 3 }
+            })
+        );
+    }
+}
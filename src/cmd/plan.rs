@@ -0,0 +1,392 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sp_core::{crypto::AccountId32, H256};
+
+use super::{
+    address::Address,
+    call, deploy,
+    gas::{GasLimit, GasMargin},
+    instantiate,
+    signer::ExtrinsicSigner,
+};
+use crate::{ExtrinsicOpts, HexData, WaitFor};
+
+/// The steps of a deployment plan, as read from the plan file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlanFile {
+    step: Vec<Step>,
+}
+
+/// A single step of a deployment plan, each submitted as an extrinsic of its own.
+///
+/// The string fields may reference the outputs of earlier steps as `${<step>.code_hash}` and
+/// `${<step>.contract}`.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "action", rename_all = "kebab-case", deny_unknown_fields)]
+enum Step {
+    /// Upload the code of a Wasm file, relative to the plan file.
+    Upload { name: Option<String>, wasm: PathBuf },
+    /// Instantiate a contract from the code hash.
+    Instantiate {
+        name: Option<String>,
+        code_hash: String,
+        /// Hex encoded data to call the constructor with.
+        data: String,
+        endowment: Option<String>,
+        #[serde(default = "default_gas_limit")]
+        gas_limit: u64,
+    },
+    /// Call a contract.
+    Call {
+        name: Option<String>,
+        contract: String,
+        /// Hex encoded data to call the message with.
+        data: String,
+        value: Option<String>,
+        /// The gas limit, estimated with a dry run by default.
+        gas_limit: Option<u64>,
+    },
+}
+
+fn default_gas_limit() -> u64 {
+    500_000_000
+}
+
+impl Step {
+    fn name(&self) -> Option<&str> {
+        match self {
+            Step::Upload { name, .. }
+            | Step::Instantiate { name, .. }
+            | Step::Call { name, .. } => name.as_deref(),
+        }
+    }
+
+    fn action(&self) -> &'static str {
+        match self {
+            Step::Upload { .. } => "upload",
+            Step::Instantiate { .. } => "instantiate",
+            Step::Call { .. } => "call",
+        }
+    }
+}
+
+/// The outcome of an executed step.
+#[derive(Debug, Serialize)]
+pub(crate) struct StepResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub action: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_hash: Option<H256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract: Option<AccountId32>,
+    /// Hash of the extrinsic of the step, `None` for an upload of code already stored.
+    pub extrinsic: Option<H256>,
+}
+
+/// Result of a successful `deploy --plan`.
+#[derive(Debug, Serialize)]
+pub(crate) struct PlanResult {
+    pub steps: Vec<StepResult>,
+}
+
+impl PlanResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let mut output = format!("Executed {} steps", self.steps.len());
+        for (index, step) in self.steps.iter().enumerate() {
+            output.push_str(&format!("\n\t#{} {}", index + 1, step.action));
+            if let Some(ref name) = step.name {
+                output.push_str(&format!(" `{}`", name));
+            }
+            if let Some(ref contract) = step.contract {
+                output.push_str(&format!(": contract {}", contract));
+            } else if let Some(ref code_hash) = step.code_hash {
+                output.push_str(&format!(": code hash {:?}", code_hash));
+            }
+        }
+        output
+    }
+}
+
+/// Load the steps from a TOML file with a `[[step]]` table per step.
+fn load_steps(path: &Path) -> Result<Vec<Step>> {
+    let toml = fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    let file: PlanFile =
+        toml::from_str(&toml).context(format!("Failed to parse {}", path.display()))?;
+    if file.step.is_empty() {
+        anyhow::bail!("No steps found in {}", path.display())
+    }
+    let mut names = Vec::new();
+    for name in file.step.iter().filter_map(Step::name) {
+        if names.contains(&name) {
+            anyhow::bail!("The step name `{}` is used more than once", name)
+        }
+        names.push(name);
+    }
+    Ok(file.step)
+}
+
+/// Replaces the references to outputs of earlier steps, `${<step>.<output>}`, in the `input`.
+///
+/// Within hex encoded data the outputs are inserted hex encoded, e.g. to pass the address of a
+/// contract as a constructor argument, elsewhere as their usual text form.
+fn expand(input: &str, results: &[StepResult], hex_encoded: bool) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = input;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow::anyhow!("Unterminated reference in {}", input))?;
+        let reference = &rest[start + 2..start + end];
+        let (step, output) = reference.split_at(reference.rfind('.').ok_or_else(|| {
+            anyhow::anyhow!(
+                "Expected `${{<step>.<output>}}`, found `${{{}}}`",
+                reference
+            )
+        })?);
+        let result = results
+            .iter()
+            .find(|result| result.name.as_deref() == Some(step))
+            .ok_or_else(|| anyhow::anyhow!("No earlier step named `{}`", step))?;
+        let value = match (&output[1..], hex_encoded) {
+            ("code_hash", true) => result.code_hash.map(hex::encode),
+            ("code_hash", false) => result.code_hash.map(|hash| format!("{:?}", hash)),
+            ("contract", true) => result.contract.as_ref().map(hex::encode),
+            ("contract", false) => result.contract.as_ref().map(ToString::to_string),
+            (output, _) => anyhow::bail!(
+                "Unknown output `{}`, expected `code_hash` or `contract`",
+                output
+            ),
+        };
+        let value = value.ok_or_else(|| {
+            anyhow::anyhow!("The step `{}` has no output `{}`", step, &output[1..])
+        })?;
+        expanded.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn parse_code_hash(input: &str) -> Result<H256> {
+    let HexData(bytes) = input.parse()?;
+    if bytes.len() != 32 {
+        anyhow::bail!("Code hash should be 32 bytes in length")
+    }
+    Ok(H256::from_slice(&bytes))
+}
+
+/// Executes a step, with the references to the outputs of earlier steps replaced.
+fn execute_step(
+    extrinsic_opts: &ExtrinsicOpts,
+    signer: &ExtrinsicSigner,
+    step: &Step,
+    dir: &Path,
+    results: &[StepResult],
+) -> Result<StepResult> {
+    let mut result = StepResult {
+        name: step.name().map(str::to_string),
+        action: step.action(),
+        code_hash: None,
+        contract: None,
+        extrinsic: None,
+    };
+    match step {
+        Step::Upload { wasm, .. } => {
+            let deployed = deploy::execute_deploy(extrinsic_opts, signer, Some(&dir.join(wasm)))?;
+            result.code_hash = Some(deployed.code_hash);
+            result.extrinsic = deployed.extrinsic.map(|extrinsic| extrinsic.extrinsic);
+        }
+        Step::Instantiate {
+            code_hash,
+            data,
+            endowment,
+            gas_limit,
+            ..
+        } => {
+            let code_hash = parse_code_hash(&expand(code_hash, results, false)?)?;
+            let data = expand(data, results, true)?.parse()?;
+            let endowment = endowment.as_deref().unwrap_or("0").parse()?;
+            let instantiated = instantiate::execute_instantiate(
+                extrinsic_opts,
+                signer,
+                endowment,
+                *gas_limit,
+                code_hash,
+                data,
+            )?;
+            let contract = instantiated.contract.ok_or_else(|| {
+                anyhow::anyhow!("The contract was not instantiated by the extrinsic")
+            })?;
+            result.code_hash = Some(code_hash);
+            result.contract = Some(contract);
+            result.extrinsic = Some(instantiated.extrinsic.extrinsic);
+        }
+        Step::Call {
+            contract,
+            data,
+            value,
+            gas_limit,
+            ..
+        } => {
+            let contract: Address = expand(contract, results, false)?.parse()?;
+            let data = expand(data, results, true)?.parse()?;
+            let value = value.as_deref().unwrap_or("0").parse()?;
+            let gas_limit = gas_limit.map_or(GasLimit::Auto, GasLimit::Limit);
+            let called = call::execute_call(
+                extrinsic_opts,
+                signer,
+                &contract,
+                value,
+                gas_limit,
+                GasMargin::default(),
+                false,
+                data,
+            )?;
+            result.contract = Some(contract.account);
+            result.extrinsic = Some(called.extrinsic.extrinsic);
+        }
+    }
+    Ok(result)
+}
+
+/// Executes the uploads, instantiations and calls of a deployment plan in order, each awaiting
+/// the inclusion of its extrinsic before the next one is submitted.
+///
+/// The steps are read from a TOML file, for example:
+///
+/// ```toml
+/// [[step]]
+/// name = "token-code"
+/// action = "upload"
+/// wasm = "token/target/ink/token.wasm"
+///
+/// [[step]]
+/// name = "token"
+/// action = "instantiate"
+/// code_hash = "${token-code.code_hash}"
+/// data = "0x5ebd88d6"
+///
+/// [[step]]
+/// action = "call"
+/// contract = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"
+/// data = "0x1234${token.contract}"
+/// ```
+pub(crate) fn execute_plan(extrinsic_opts: &ExtrinsicOpts, path: &Path) -> Result<PlanResult> {
+    let steps = load_steps(path)?;
+    if extrinsic_opts.submit.wait_for == WaitFor::Broadcast {
+        anyhow::bail!("A deployment plan requires awaiting the inclusion of each extrinsic")
+    }
+    if extrinsic_opts.nonce.is_some() {
+        anyhow::bail!("A deployment plan submits several extrinsics, which cannot share --nonce")
+    }
+    let signer = extrinsic_opts.signer()?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut results = Vec::new();
+    for (index, step) in steps.iter().enumerate() {
+        log::info!("Executing step #{} ({})", index + 1, step.action());
+        let result =
+            execute_step(extrinsic_opts, &signer, step, dir, &results).context(format!(
+                "Step #{} failed{}",
+                index + 1,
+                step.name()
+                    .map(|name| format!(" (`{}`)", name))
+                    .unwrap_or_default()
+            ))?;
+        results.push(result);
+    }
+    Ok(PlanResult { steps: results })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_plan_file() {
+        crate::util::tests::with_tmp_dir(|path| {
+            let plan_path = path.join("deploy.toml");
+            fs::write(
+                &plan_path,
+                r#"
+[[step]]
+name = "code"
+action = "upload"
+wasm = "token.wasm"
+
+[[step]]
+action = "instantiate"
+code_hash = "${code.code_hash}"
+data = "0x00"
+"#,
+            )?;
+            let steps = load_steps(&plan_path)?;
+
+            assert_eq!(
+                steps[0],
+                Step::Upload {
+                    name: Some("code".to_string()),
+                    wasm: "token.wasm".into()
+                }
+            );
+            assert!(
+                matches!(steps[1], Step::Instantiate { gas_limit, .. } if gas_limit == default_gas_limit())
+            );
+
+            fs::write(&plan_path, "[[step]]\naction = \"upload\"\nwasm = 1")?;
+            assert!(load_steps(&plan_path).is_err());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn expands_references_to_earlier_steps() {
+        let contract = AccountId32::from([1; 32]);
+        let results = vec![StepResult {
+            name: Some("token".to_string()),
+            action: "instantiate",
+            code_hash: Some(H256::repeat_byte(2)),
+            contract: Some(contract.clone()),
+            extrinsic: None,
+        }];
+        assert_eq!(
+            expand("0x12${token.contract}34", &results, true).unwrap(),
+            format!("0x12{}34", "01".repeat(32))
+        );
+        assert_eq!(
+            expand("${token.contract}", &results, false).unwrap(),
+            contract.to_string()
+        );
+        assert_eq!(
+            expand("${token.code_hash}", &results, false).unwrap(),
+            format!("0x{}", "02".repeat(32))
+        );
+        assert!(expand("${other.contract}", &results, true).is_err());
+        assert!(expand("${token.extrinsic}", &results, true).is_err());
+        assert!(expand("${token.contract", &results, true).is_err());
+    }
+}
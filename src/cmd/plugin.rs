@@ -0,0 +1,222 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use super::metadata::METADATA_FILE;
+#[cfg(feature = "extrinsics")]
+use crate::SubmitOpts;
+use crate::{crate_metadata::CrateMetadata, workspace::ManifestPath};
+
+/// The prefix of the executables providing the subcommands `cargo contract` does not know.
+const PLUGIN_PREFIX: &str = "cargo-contract-";
+
+/// The environment variable holding the [`PluginContext`] as JSON.
+const CONTEXT_ENV: &str = "CARGO_CONTRACT_CONTEXT";
+
+/// What a plugin needs to know about the invocation, resolved as `cargo contract` resolves it
+/// for its own commands from the options passed to the plugin.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub(crate) struct PluginContext {
+    /// The version of cargo-contract dispatching to the plugin.
+    pub version: &'static str,
+    /// The Cargo.toml of the contract project, from `--manifest-path` or in the current
+    /// directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest_path: Option<PathBuf>,
+    /// The metadata of the contract, from `--metadata` or built for the contract project.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<PathBuf>,
+    /// The comma separated urls of the nodes, from `--url`, `--network` or the configuration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The name of the network, from `--network` or the configuration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+}
+
+impl PluginContext {
+    /// Resolves the context from the arguments of the plugin.
+    fn new(args: &[String]) -> Result<Self> {
+        let manifest_path = match option_value(args, "--manifest-path") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(PathBuf::from("Cargo.toml")).filter(|path| path.exists()),
+        };
+        let metadata = match option_value(args, "--metadata") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => manifest_path
+                .as_ref()
+                .and_then(|path| built_metadata(path).ok())
+                .filter(|path| path.exists()),
+        };
+        let (url, network) = resolve_node(args)?;
+        Ok(PluginContext {
+            version: env!("CARGO_PKG_VERSION"),
+            manifest_path,
+            metadata,
+            url,
+            network,
+        })
+    }
+
+    /// The environment variables passing the context on to the plugin.
+    fn env(&self) -> Result<Vec<(&'static str, String)>> {
+        let mut env = vec![(CONTEXT_ENV, serde_json::to_string(self)?)];
+        let path = |path: &PathBuf| path.display().to_string();
+        let vars = [
+            (
+                "CARGO_CONTRACT_MANIFEST_PATH",
+                self.manifest_path.as_ref().map(path),
+            ),
+            ("CARGO_CONTRACT_METADATA", self.metadata.as_ref().map(path)),
+            ("CARGO_CONTRACT_URL", self.url.clone()),
+            ("CARGO_CONTRACT_NETWORK", self.network.clone()),
+        ];
+        env.extend(
+            vars.iter()
+                .filter_map(|(name, value)| Some((*name, value.clone()?))),
+        );
+        Ok(env)
+    }
+}
+
+fn built_metadata(manifest_path: &Path) -> Result<PathBuf> {
+    let crate_metadata = CrateMetadata::collect(&ManifestPath::new(manifest_path)?)?;
+    Ok(crate_metadata.target_directory.join(METADATA_FILE))
+}
+
+/// The value of the `option` among the arguments, supplied as `--option value` or
+/// `--option=value`.
+fn option_value<'a>(args: &'a [String], option: &str) -> Option<&'a str> {
+    args.iter().enumerate().find_map(|(index, arg)| {
+        if arg == option {
+            return args.get(index + 1).map(String::as_str);
+        }
+        arg.strip_prefix(option)?.strip_prefix('=')
+    })
+}
+
+/// The urls and the network of the node, as the commands submitting extrinsics resolve them.
+#[cfg(feature = "extrinsics")]
+fn resolve_node(args: &[String]) -> Result<(Option<String>, Option<String>)> {
+    use structopt::StructOpt;
+
+    let mut submit_args = vec!["plugin".to_string()];
+    for option in &["--url", "--network"] {
+        if let Some(value) = option_value(args, option) {
+            submit_args.extend(vec![option.to_string(), value.to_string()]);
+        }
+    }
+    let matches = SubmitOpts::clap().get_matches_from_safe(&submit_args)?;
+    let mut submit_opts = SubmitOpts::from_clap(&matches);
+    super::Config::load()?.apply_submit(&mut submit_opts, &matches)?;
+    let urls = submit_opts
+        .urls
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    let network = submit_opts.network_profile.map(|network| network.name);
+    Ok((Some(urls.join(",")), network))
+}
+
+/// The urls and the network of the node, as supplied.
+#[cfg(not(feature = "extrinsics"))]
+fn resolve_node(args: &[String]) -> Result<(Option<String>, Option<String>)> {
+    let value = |option| option_value(args, option).map(ToString::to_string);
+    Ok((value("--url"), value("--network")))
+}
+
+/// Runs the `cargo-contract-<name>` executable in the `PATH` for the unknown subcommand `name`,
+/// with the following arguments and the resolved [`PluginContext`], returning its exit code.
+pub(crate) fn execute_plugin(args: &[String]) -> Result<i32> {
+    let (name, args) = args
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("No subcommand supplied"))?;
+    let executable = format!("{}{}", PLUGIN_PREFIX, name);
+    let plugin = which::which(&executable).map_err(|_| {
+        anyhow::anyhow!(
+            "No such subcommand `{}`, and no plugin `{}` in the PATH providing it. See \
+             `cargo contract --help` for the subcommands",
+            name,
+            executable
+        )
+    })?;
+    let context = PluginContext::new(args)?;
+    log::info!("Running the plugin {}", plugin.display());
+    let mut command = Command::new(&plugin);
+    command.args(args).envs(context.env()?);
+    if let Some(suri) = option_value(args, "--suri") {
+        command.env("CARGO_CONTRACT_SURI", suri);
+    }
+    let status = command
+        .status()
+        .context(format!("Failed to run {}", plugin.display()))?;
+    // A plugin killed by a signal has no exit code.
+    Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn finds_the_values_of_options() {
+        let args = args(&[
+            "--url",
+            "ws://node:9944",
+            "--metadata=target/m.json",
+            "call",
+        ]);
+        assert_eq!(option_value(&args, "--url"), Some("ws://node:9944"));
+        assert_eq!(option_value(&args, "--metadata"), Some("target/m.json"));
+        assert_eq!(option_value(&args, "--network"), None);
+        assert_eq!(option_value(&args, "--meta"), None);
+    }
+
+    #[test]
+    fn passes_the_context_in_the_environment() {
+        let context = PluginContext {
+            version: "0.8.0",
+            metadata: Some(PathBuf::from("metadata.json")),
+            url: Some("ws://localhost:9944".to_string()),
+            ..Default::default()
+        };
+        let env = context.env().unwrap();
+        assert_eq!(
+            env,
+            vec![
+                (
+                    CONTEXT_ENV,
+                    r#"{"version":"0.8.0","metadata":"metadata.json","url":"ws://localhost:9944"}"#
+                        .to_string()
+                ),
+                ("CARGO_CONTRACT_METADATA", "metadata.json".to_string()),
+                ("CARGO_CONTRACT_URL", "ws://localhost:9944".to_string()),
+            ]
+        );
+        assert!(execute_plugin(&args(&["no-such-plugin-of-cargo-contract"])).is_err());
+    }
+}
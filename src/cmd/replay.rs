@@ -0,0 +1,116 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::VecDeque, convert::Infallible, future::Future, pin::Pin};
+
+use anyhow::Result;
+use jsonrpsee::{
+    client::Subscription,
+    common::{
+        Call, JsonValue, Output, Params, Request, Response, SubscriptionId, SubscriptionNotif,
+        SubscriptionNotifParams, Version,
+    },
+    raw::RawClient,
+    transport::TransportClient,
+};
+use sp_core::{storage::StorageChangeSet, H256};
+
+/// Returns a subscription to storage changes, which yields the given change set only.
+///
+/// subxt only decodes events received via a subscription, so the events stored in past blocks
+/// are decoded by replaying their change sets.
+pub(crate) async fn replay(
+    change_set: StorageChangeSet<H256>,
+) -> Result<Subscription<StorageChangeSet<H256>>> {
+    let transport = ReplayTransport {
+        change_set: Some(serde_json::to_value(change_set)?),
+        responses: VecDeque::new(),
+    };
+    let client = jsonrpsee::Client::from(RawClient::new(transport));
+    let subscription = client
+        .subscribe(
+            "state_subscribeStorage",
+            Params::None,
+            "state_unsubscribeStorage",
+        )
+        .await?;
+    Ok(subscription)
+}
+
+/// In-memory transport answering the storage subscription with the change set.
+struct ReplayTransport {
+    change_set: Option<JsonValue>,
+    responses: VecDeque<Response>,
+}
+
+impl TransportClient for ReplayTransport {
+    type Error = Infallible;
+
+    fn send_request<'a>(
+        &'a mut self,
+        request: Request,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Self::Error>> + Send + 'a>> {
+        if let Request::Single(Call::MethodCall(call)) = request {
+            let subscribed = call.method == "state_subscribeStorage";
+            // Both subscribing and unsubscribing succeed.
+            let result = if subscribed { 0.into() } else { true.into() };
+            let output = Output::from(Ok(result), call.id, Version::V2);
+            self.responses.push_back(Response::Single(output));
+            if let Some(change_set) = self.change_set.take().filter(|_| subscribed) {
+                self.responses.push_back(Response::Notif(SubscriptionNotif {
+                    jsonrpc: Version::V2,
+                    method: "state_storage".to_string(),
+                    params: SubscriptionNotifParams {
+                        subscription: SubscriptionId::Num(0),
+                        result: change_set,
+                    },
+                }));
+            }
+        }
+        Box::pin(futures::future::ready(Ok(())))
+    }
+
+    fn next_response<'a>(
+        &'a mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Response, Self::Error>> + Send + 'a>> {
+        let response = self.responses.pop_front();
+        Box::pin(async move {
+            match response {
+                Some(response) => Ok(response),
+                None => futures::future::pending().await,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::storage::{StorageData, StorageKey};
+
+    #[test]
+    fn replays_the_change_set() {
+        let change_set = || StorageChangeSet {
+            block: H256::repeat_byte(1),
+            changes: vec![(StorageKey(vec![2]), Some(StorageData(vec![3])))],
+        };
+        let replayed = async_std::task::block_on(async {
+            let mut subscription = replay(change_set()).await.unwrap();
+            subscription.next().await
+        });
+        assert_eq!(replayed, change_set());
+    }
+}
@@ -0,0 +1,1035 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use codec::{Decode, Encode};
+use serde::Serialize;
+use serde_json::Value;
+use sp_core::crypto::AccountId32;
+use structopt::StructOpt;
+
+use super::{
+    address::Address,
+    block::BlockRef,
+    interpreter::{Host, Instance, Memory, Stop},
+    transcode::{InkMetadata, Message},
+    BalanceVariant, ContractRef,
+};
+use crate::{crate_metadata::CrateMetadata, OutputType};
+
+/// The existential deposit of the sandbox, reported by `seal_minimum_balance`.
+const MINIMUM_BALANCE: u128 = 1;
+
+/// The return codes of the host functions, as defined by `pallet-contracts`.
+const SUCCESS: u32 = 0;
+const KEY_NOT_FOUND: u32 = 3;
+const TRANSFER_FAILED: u32 = 5;
+
+/// Execute a message of the contract in an in-memory sandbox, without a node
+#[derive(Debug, StructOpt)]
+#[structopt(name = "exec")]
+pub(crate) struct ExecCommand {
+    /// Execute the message with wasmi in an in-memory sandbox, which is the only
+    /// supported backend
+    #[structopt(long)]
+    sandbox: bool,
+    /// The label of the message to execute
+    message: String,
+    /// The arguments of the message, as JSON values or plain strings, e.g. `42`, `true` or an SS58
+    /// address
+    #[structopt(long)]
+    args: Vec<String>,
+    /// The balance transferred to the contract with the message, in the smallest unit
+    #[structopt(long, default_value = "0")]
+    value: BalanceVariant,
+    /// Print the host functions the message calls, with their decoded arguments and results
+    #[structopt(long)]
+    trace: bool,
+    #[structopt(flatten)]
+    sandbox_opts: SandboxOpts,
+    /// Export the result in JSON format
+    #[structopt(
+        long,
+        default_value = "human-readable",
+        value_name = "human-readable | json | json-pjs"
+    )]
+    output: OutputType,
+}
+
+/// Options deploying the contract in a new sandbox.
+#[derive(Debug, StructOpt)]
+pub(crate) struct SandboxOpts {
+    /// The label of the constructor deploying the contract, by default the only one or `new`
+    #[structopt(long)]
+    constructor: Option<String>,
+    /// Websockets url of a node to fork the state of the `--contract` from, instead of deploying
+    /// it with the constructor. Its storage is fetched from the chain as the messages read it
+    #[structopt(long, requires = "contract")]
+    pub fork: Option<url::Url>,
+    /// The address of the contract on the forked chain, or its alias in the address book
+    #[structopt(long)]
+    pub contract: Option<ContractRef>,
+    /// Fork the state of this block, a block number or a 0x prefixed hash, instead of the best
+    /// block
+    #[structopt(long, requires = "fork")]
+    at: Option<BlockRef>,
+    /// The arguments of the constructor
+    #[structopt(long)]
+    constructor_args: Vec<String>,
+    /// Path to the Wasm of the contract, by default the one built for the contract project in the
+    /// current directory. When forking, it replaces the code of the contract on the chain, e.g. to
+    /// try out an upgrade
+    #[structopt(long, parse(from_os_str))]
+    wasm: Option<PathBuf>,
+    /// Path to the `metadata.json` or `.contract` bundle describing the messages, by default the
+    /// one built for the contract project in the current directory
+    #[structopt(long, parse(from_os_str))]
+    metadata: Option<PathBuf>,
+    /// The account calling the constructor and the messages
+    #[structopt(
+        long,
+        default_value = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"
+    )]
+    pub caller: Address,
+    /// The balance transferred to the contract with the constructor, in the smallest unit
+    #[structopt(long, default_value = "0")]
+    endowment: BalanceVariant,
+    /// The maximum number of Wasm instructions the constructor and each message may execute,
+    /// standing in for the gas limit
+    #[structopt(long, default_value = "100000000")]
+    max_instructions: u64,
+}
+
+impl SandboxOpts {
+    /// Reads the metadata supplied, or built for the contract project in the current directory.
+    pub fn metadata(&self, output: OutputType) -> Result<InkMetadata> {
+        let metadata = InkMetadata::load(self.metadata.as_deref())?
+            .ok_or_else(|| anyhow::anyhow!("No metadata found, supply it with --metadata"))?;
+        Ok(metadata.for_output(output))
+    }
+
+    /// Deploys the contract with the constructor in a new sandbox, or forks it from the chain.
+    pub fn deploy(&self, metadata: &InkMetadata) -> Result<Sandbox> {
+        if self.contract.is_some() && self.fork.is_none() {
+            anyhow::bail!("--contract selects the contract to fork, supply the node with --fork")
+        }
+        let read =
+            |wasm: &PathBuf| fs::read(wasm).context(format!("Failed to read {}", wasm.display()));
+        if let (Some(url), Some(contract)) = (&self.fork, &self.contract) {
+            let wasm = self.wasm.as_ref().map(read).transpose()?;
+            return super::fork::fork(url, contract, self.at.as_ref(), wasm, self.max_instructions);
+        }
+        let wasm = match self.wasm {
+            Some(ref wasm) => wasm.clone(),
+            None => CrateMetadata::collect(&Default::default())?.dest_wasm,
+        };
+        let wasm = read(&wasm)?;
+        let constructor = metadata.find_constructor(self.constructor.as_deref())?;
+        let mut sandbox = Sandbox::new(wasm, self.max_instructions);
+        let deployed = sandbox.deploy(
+            &self.caller.account,
+            self.endowment.without_denomination()?,
+            metadata.encode_call(&constructor, &self.constructor_args)?,
+        )?;
+        if !deployed.succeeded() {
+            let reason = deployed.trap.as_deref().unwrap_or("reverted");
+            anyhow::bail!("The constructor `{}` failed: {}", constructor.label, reason)
+        }
+        Ok(sandbox)
+    }
+}
+
+/// An event deposited by the contract.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Event {
+    pub topics: Vec<[u8; 32]>,
+    pub data: Vec<u8>,
+}
+
+/// The outcome of executing the constructor or a message of the contract in the sandbox.
+#[derive(Debug, Default)]
+pub(crate) struct Execution {
+    /// Why the contract trapped, e.g. on a panic, `None` if it returned.
+    pub trap: Option<String>,
+    /// Whether the contract returned with the revert flag.
+    pub reverted: bool,
+    /// The data the contract returned.
+    pub data: Vec<u8>,
+    /// The values written to the storage by key, `None` for cleared keys.
+    pub storage: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    pub events: Vec<Event>,
+    pub debug_messages: Vec<String>,
+    /// The balances the contract transferred to other accounts.
+    pub transfers: Vec<(AccountId32, u128)>,
+    /// The number of Wasm instructions executed.
+    pub instructions: u64,
+    /// The number of bytes of the keys and values read from the storage.
+    pub storage_read: u64,
+    /// The number of bytes of the keys and values added to the storage, negative if more were
+    /// removed.
+    pub storage_added: i64,
+    /// The host functions called, in order, if the sandbox traces them.
+    pub trace: Vec<HostCall>,
+}
+
+/// A call of a host function by the contract.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct HostCall {
+    pub function: String,
+    /// The decoded arguments, e.g. the key and the value written to the storage.
+    pub args: Vec<String>,
+    /// The decoded output or return code, or why the contract trapped in the call.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+}
+
+impl HostCall {
+    /// The human readable output, e.g. `seal_get_storage(key 0x…) -> 0x2a00`.
+    pub fn display(&self) -> String {
+        let mut output = format!("{}({})", self.function, self.args.join(", "));
+        if let Some(ref result) = self.result {
+            output.push_str(&format!(" -> {}", result));
+        }
+        output
+    }
+}
+
+impl Execution {
+    /// Whether the changes of the execution were applied, i.e. it neither trapped nor reverted.
+    pub fn succeeded(&self) -> bool {
+        self.trap.is_none() && !self.reverted
+    }
+}
+
+/// The storage of a contract on a chain, read for the keys the sandbox holds no value for.
+pub(crate) trait Remote {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+}
+
+/// A contract deployed in memory, whose messages are executed by wasmi, the interpreter of
+/// `pallet-contracts`.
+///
+/// The host functions of `pallet-contracts` are provided for a single contract: calling or
+/// instantiating other contracts traps.
+#[derive(Clone)]
+pub(crate) struct Sandbox {
+    wasm: Vec<u8>,
+    pub contract: AccountId32,
+    pub storage: BTreeMap<Vec<u8>, Vec<u8>>,
+    pub balance: u128,
+    block_number: u32,
+    terminated: bool,
+    max_instructions: u64,
+    /// Whether the host functions called by the executions are recorded.
+    pub trace: bool,
+    /// The storage of the forked contract, if any.
+    remote: Option<Rc<dyn Remote>>,
+    /// The keys read from the remote, or written, which are not read from the remote again.
+    fetched: BTreeSet<Vec<u8>>,
+}
+
+impl Sandbox {
+    pub fn new(wasm: Vec<u8>, max_instructions: u64) -> Self {
+        Sandbox {
+            contract: AccountId32::from(sp_core::blake2_256(&wasm)),
+            wasm,
+            storage: BTreeMap::new(),
+            balance: 0,
+            block_number: 0,
+            terminated: false,
+            max_instructions,
+            trace: false,
+            remote: None,
+            fetched: BTreeSet::new(),
+        }
+    }
+
+    /// The contract at the address on a chain, with its code, balance and the block number,
+    /// whose storage is read from the `remote`.
+    pub fn fork(
+        wasm: Vec<u8>,
+        contract: AccountId32,
+        balance: u128,
+        block_number: u32,
+        remote: Rc<dyn Remote>,
+        max_instructions: u64,
+    ) -> Self {
+        Sandbox {
+            contract,
+            balance,
+            block_number,
+            remote: Some(remote),
+            ..Sandbox::new(wasm, max_instructions)
+        }
+    }
+
+    /// Calls the constructor with the `input`, transferring the `endowment`.
+    pub fn deploy(
+        &mut self,
+        caller: &AccountId32,
+        endowment: u128,
+        input: Vec<u8>,
+    ) -> Result<Execution> {
+        self.execute("deploy", caller, endowment, input)
+    }
+
+    /// Calls the message with the `input`, transferring the `value`.
+    pub fn call(&mut self, caller: &AccountId32, value: u128, input: Vec<u8>) -> Result<Execution> {
+        self.execute("call", caller, value, input)
+    }
+
+    /// Executes the exported function in a new block, applying its changes unless it traps or
+    /// reverts.
+    fn execute(
+        &mut self,
+        export: &str,
+        caller: &AccountId32,
+        value: u128,
+        input: Vec<u8>,
+    ) -> Result<Execution> {
+        if self.terminated {
+            anyhow::bail!("The contract terminated itself")
+        }
+        let mut instance = Instance::new(&self.wasm, self.max_instructions)?;
+        self.block_number += 1;
+        let mut host = Environment {
+            caller: caller.clone(),
+            value,
+            input,
+            storage: self.storage.clone(),
+            remote: self.remote.clone(),
+            fetched: self.fetched.clone(),
+            balance: self.balance.saturating_add(value),
+            address: self.contract.clone(),
+            block_number: self.block_number,
+            terminated: false,
+            trace: self.trace,
+            execution: Execution::default(),
+        };
+        if let Err(Stop::Trap(reason)) = instance.invoke(export, &mut host) {
+            host.execution.trap = Some(reason);
+        }
+        host.execution.instructions = instance.steps();
+        if host.execution.succeeded() {
+            self.storage = host.storage;
+            self.fetched = host.fetched;
+            self.balance = host.balance;
+            self.terminated = host.terminated;
+        }
+        Ok(host.execution)
+    }
+}
+
+/// The state of the contract during an execution, provided to it by the host functions.
+struct Environment {
+    caller: AccountId32,
+    value: u128,
+    input: Vec<u8>,
+    storage: BTreeMap<Vec<u8>, Vec<u8>>,
+    remote: Option<Rc<dyn Remote>>,
+    fetched: BTreeSet<Vec<u8>>,
+    balance: u128,
+    address: AccountId32,
+    block_number: u32,
+    terminated: bool,
+    trace: bool,
+    execution: Execution,
+}
+
+impl Environment {
+    /// The value of the key, read from the remote the first time if the contract is forked.
+    fn value(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>, Stop> {
+        if let Some(value) = self.storage.get(key) {
+            return Ok(Some(value.clone()));
+        }
+        let remote = match self.remote {
+            Some(ref remote) if self.fetched.insert(key.to_vec()) => remote,
+            _ => return Ok(None),
+        };
+        let value = remote.get(key).map_err(|err| {
+            Stop::Trap(format!(
+                "Failed to fetch the storage of the fork: {:#}",
+                err
+            ))
+        })?;
+        if let Some(ref value) = value {
+            self.storage.insert(key.to_vec(), value.clone());
+        }
+        Ok(value)
+    }
+
+    fn set(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) {
+        let size =
+            |value: Option<&Vec<u8>>| value.map_or(0, |value| (key.len() + value.len()) as i64);
+        self.execution.storage_added += size(value.as_ref()) - size(self.storage.get(&key));
+        self.fetched.insert(key.clone());
+        self.execution.storage.insert(key.clone(), value.clone());
+        match value {
+            Some(value) => self.storage.insert(key, value),
+            None => self.storage.remove(&key),
+        };
+    }
+}
+
+fn arg(args: &[u64], index: usize) -> Result<u32, Stop> {
+    match args.get(index) {
+        Some(arg) => Ok(*arg as u32),
+        None => Err(Stop::Trap(
+            "A host function was called with too few arguments".into(),
+        )),
+    }
+}
+
+/// Writes the `data` to the buffer at `out_ptr`, whose capacity is read from and whose length is
+/// written to `out_len_ptr`.
+fn write_output(
+    memory: &mut Memory,
+    out_ptr: u32,
+    out_len_ptr: u32,
+    data: &[u8],
+) -> Result<(), Stop> {
+    let capacity = memory.read_u32(out_len_ptr)?;
+    if (capacity as usize) < data.len() {
+        return Err(Stop::Trap(format!(
+            "The output buffer of {} bytes is too small for {} bytes",
+            capacity,
+            data.len()
+        )));
+    }
+    memory.write(out_ptr, data)?;
+    memory.write_u32(out_len_ptr, data.len() as u32)
+}
+
+fn decode<T: Decode>(data: &[u8]) -> Result<T, Stop> {
+    T::decode(&mut &data[..])
+        .map_err(|_| Stop::Trap("Failed to decode a host function argument".into()))
+}
+
+/// The name of the return code of a host function.
+fn return_code(code: u64) -> String {
+    match code as u32 {
+        SUCCESS => "Success".to_string(),
+        KEY_NOT_FOUND => "KeyNotFound".to_string(),
+        TRANSFER_FAILED => "TransferFailed".to_string(),
+        code => code.to_string(),
+    }
+}
+
+/// Describes the call of the host function, decoding its arguments and output from the memory of
+/// the contract after the call.
+fn describe(
+    name: &str,
+    args: &[u64],
+    memory: &Memory,
+    result: &Result<Option<u64>, Stop>,
+) -> HostCall {
+    let hex = |data: &[u8]| format!("0x{}", hex::encode(data));
+    let read = |ptr, len| memory.read(arg(args, ptr)?, arg(args, len)?);
+    let account = |data: &[u8]| match AccountId32::decode(&mut &data[..]) {
+        Ok(account) => account.to_string(),
+        Err(_) => hex(data),
+    };
+    let balance = |data: &[u8]| match u128::decode(&mut &data[..]) {
+        Ok(balance) => balance.to_string(),
+        Err(_) => hex(data),
+    };
+    let decoded = || -> Result<Vec<String>, Stop> {
+        let key = || Ok::<_, Stop>(format!("key {}", hex(&memory.read(arg(args, 0)?, 32)?)));
+        Ok(match name {
+            "seal_get_storage" | "seal_clear_storage" => vec![key()?],
+            "seal_set_storage" => vec![key()?, format!("value {}", hex(&read(1, 2)?))],
+            "seal_transfer" => vec![
+                format!("to {}", account(&read(0, 1)?)),
+                format!("value {}", balance(&read(2, 3)?)),
+            ],
+            "seal_call" => vec![
+                format!("callee {}", account(&read(0, 1)?)),
+                format!("gas {}", args.get(2).copied().unwrap_or_default()),
+                format!("value {}", balance(&read(3, 4)?)),
+                format!("input {}", hex(&read(5, 6)?)),
+            ],
+            "seal_instantiate" => vec![
+                format!("code hash {}", hex(&read(0, 1)?)),
+                format!("gas {}", args.get(2).copied().unwrap_or_default()),
+                format!("value {}", balance(&read(3, 4)?)),
+                format!("input {}", hex(&read(5, 6)?)),
+            ],
+            "seal_terminate" => vec![format!("beneficiary {}", account(&read(0, 1)?))],
+            "seal_deposit_event" => vec![
+                format!("topics {}", hex(&read(0, 1)?)),
+                format!("data {}", hex(&read(2, 3)?)),
+            ],
+            "seal_return" => vec![
+                format!("flags {}", arg(args, 0)?),
+                format!("data {}", hex(&read(1, 2)?)),
+            ],
+            "seal_println" | "seal_debug_message" => {
+                vec![format!("{:?}", String::from_utf8_lossy(&read(0, 1)?))]
+            }
+            name if name.starts_with("seal_hash_") => vec![format!("input {}", hex(&read(0, 1)?))],
+            _ => Vec::new(),
+        })
+    };
+    // The output written to the buffer at the pointer argument, whose length is written to the
+    // next one.
+    let output = |ptr| -> Result<Vec<u8>, Stop> {
+        let len = memory.read_u32(arg(args, ptr + 1)?)?;
+        memory.read(arg(args, ptr)?, len)
+    };
+    let result = match result {
+        Err(Stop::Trap(reason)) => Some(format!("trapped: {}", reason)),
+        Err(Stop::Exit) => None,
+        Ok(code) => match name {
+            "seal_get_storage" if *code == Some(u64::from(SUCCESS)) => {
+                output(1).ok().map(|data| hex(&data))
+            }
+            "seal_caller" | "seal_address" => output(0).ok().map(|data| account(&data)),
+            "seal_balance" | "seal_value_transferred" | "seal_minimum_balance" => {
+                output(0).ok().map(|data| balance(&data))
+            }
+            "seal_input" => output(0).ok().map(|data| hex(&data)),
+            "seal_random" => output(2).ok().map(|data| hex(&data)),
+            _ => code.map(return_code),
+        },
+    };
+    HostCall {
+        function: name.to_string(),
+        args: decoded().unwrap_or_default(),
+        result,
+    }
+}
+
+impl Host for Environment {
+    fn call(
+        &mut self,
+        module: &str,
+        name: &str,
+        args: &[u64],
+        memory: &mut Memory,
+        steps_left: u64,
+    ) -> Result<Option<u64>, Stop> {
+        let result = self.dispatch(module, name, args, memory, steps_left);
+        if self.trace {
+            let call = describe(name, args, memory, &result);
+            self.execution.trace.push(call);
+        }
+        result
+    }
+}
+
+impl Environment {
+    /// Executes the host function, returning its return code if it has one.
+    fn dispatch(
+        &mut self,
+        module: &str,
+        name: &str,
+        args: &[u64],
+        memory: &mut Memory,
+        steps_left: u64,
+    ) -> Result<Option<u64>, Stop> {
+        if module != "seal0" {
+            return Err(Stop::Trap(format!(
+                "Unknown host function {}::{}",
+                module, name
+            )));
+        }
+        let arg = |index| arg(args, index);
+        let output =
+            |memory: &mut Memory, data: &[u8]| write_output(memory, arg(0)?, arg(1)?, data);
+        let code = match name {
+            "seal_input" => {
+                let input = self.input.clone();
+                output(memory, &input)?;
+                None
+            }
+            "seal_return" => {
+                self.execution.reverted = arg(0)? & 1 == 1;
+                self.execution.data = memory.read(arg(1)?, arg(2)?)?;
+                return Err(Stop::Exit);
+            }
+            "seal_get_storage" => {
+                let key = memory.read(arg(0)?, 32)?;
+                let value = self.value(&key)?;
+                let read = key.len() + value.as_ref().map_or(0, Vec::len);
+                self.execution.storage_read += read as u64;
+                match value {
+                    Some(value) => {
+                        write_output(memory, arg(1)?, arg(2)?, &value)?;
+                        Some(SUCCESS)
+                    }
+                    None => Some(KEY_NOT_FOUND),
+                }
+            }
+            "seal_set_storage" => {
+                let key = memory.read(arg(0)?, 32)?;
+                let value = memory.read(arg(1)?, arg(2)?)?;
+                self.set(key, Some(value));
+                None
+            }
+            "seal_clear_storage" => {
+                let key = memory.read(arg(0)?, 32)?;
+                self.set(key, None);
+                None
+            }
+            "seal_caller" => {
+                output(memory, &self.caller.encode())?;
+                None
+            }
+            "seal_address" => {
+                output(memory, &self.address.encode())?;
+                None
+            }
+            "seal_balance" => {
+                output(memory, &self.balance.encode())?;
+                None
+            }
+            "seal_value_transferred" => {
+                output(memory, &self.value.encode())?;
+                None
+            }
+            "seal_gas_left" => {
+                output(memory, &steps_left.encode())?;
+                None
+            }
+            "seal_now" => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |now| now.as_millis() as u64);
+                output(memory, &now.encode())?;
+                None
+            }
+            "seal_block_number" => {
+                output(memory, &self.block_number.encode())?;
+                None
+            }
+            "seal_minimum_balance" | "seal_tombstone_deposit" => {
+                output(memory, &MINIMUM_BALANCE.encode())?;
+                None
+            }
+            "seal_rent_allowance" => {
+                output(memory, &u128::MAX.encode())?;
+                None
+            }
+            "seal_weight_to_fee" => {
+                let fee = u128::from(args.first().copied().unwrap_or_default());
+                write_output(memory, arg(1)?, arg(2)?, &fee.encode())?;
+                None
+            }
+            "seal_set_rent_allowance" => None,
+            "seal_deposit_event" => {
+                let topics = decode(&memory.read(arg(0)?, arg(1)?)?)?;
+                let data = memory.read(arg(2)?, arg(3)?)?;
+                self.execution.events.push(Event { topics, data });
+                None
+            }
+            "seal_println" | "seal_debug_message" => {
+                let message = memory.read(arg(0)?, arg(1)?)?;
+                let message = String::from_utf8_lossy(&message).into_owned();
+                self.execution.debug_messages.push(message);
+                match name {
+                    "seal_debug_message" => Some(SUCCESS),
+                    _ => None,
+                }
+            }
+            "seal_hash_sha2_256"
+            | "seal_hash_keccak_256"
+            | "seal_hash_blake2_256"
+            | "seal_hash_blake2_128" => {
+                let input = memory.read(arg(0)?, arg(1)?)?;
+                let hash = match name {
+                    "seal_hash_sha2_256" => sp_core::hashing::sha2_256(&input).to_vec(),
+                    "seal_hash_keccak_256" => sp_core::keccak_256(&input).to_vec(),
+                    "seal_hash_blake2_256" => sp_core::blake2_256(&input).to_vec(),
+                    _ => sp_core::blake2_128(&input).to_vec(),
+                };
+                memory.write(arg(2)?, &hash)?;
+                None
+            }
+            "seal_random" => {
+                let mut subject = memory.read(arg(0)?, arg(1)?)?;
+                subject.extend(self.block_number.encode());
+                let random = sp_core::blake2_256(&subject);
+                // Later versions of the host function also return the block the randomness is
+                // determined by.
+                write_output(memory, arg(2)?, arg(3)?, &random.encode())?;
+                None
+            }
+            "seal_transfer" => {
+                let account = decode(&memory.read(arg(0)?, arg(1)?)?)?;
+                let value: u128 = decode(&memory.read(arg(2)?, arg(3)?)?)?;
+                match self.balance.checked_sub(value) {
+                    Some(balance) if balance >= MINIMUM_BALANCE => {
+                        self.balance = balance;
+                        self.execution.transfers.push((account, value));
+                        Some(SUCCESS)
+                    }
+                    _ => Some(TRANSFER_FAILED),
+                }
+            }
+            "seal_terminate" => {
+                let beneficiary = decode(&memory.read(arg(0)?, arg(1)?)?)?;
+                let balance = std::mem::take(&mut self.balance);
+                self.execution.transfers.push((beneficiary, balance));
+                self.terminated = true;
+                return Err(Stop::Exit);
+            }
+            _ => {
+                return Err(Stop::Trap(format!(
+                    "The host function {} is not supported in the sandbox, e.g. calling other \
+                     contracts",
+                    name
+                )))
+            }
+        };
+        Ok(code.map(u64::from))
+    }
+}
+
+/// A change of the storage of the contract.
+#[derive(Debug, Serialize)]
+pub(crate) struct StorageChange {
+    pub key: String,
+    /// Hex encoded value written to the key, `None` if it was cleared.
+    pub value: Option<String>,
+}
+
+/// An event emitted by the message, decoded with the metadata if possible.
+#[derive(Debug, Serialize)]
+pub(crate) struct DecodedEvent {
+    /// The name of the event, `None` if it could not be decoded.
+    pub name: Option<String>,
+    /// The arguments of the event, or its hex encoded data if it could not be decoded.
+    pub args: Value,
+}
+
+/// Result of executing a message with `exec --sandbox`.
+#[derive(Debug, Serialize)]
+pub(crate) struct SandboxResult {
+    pub message: String,
+    /// Why the message trapped, e.g. on a panic, `None` if it returned.
+    pub trap: Option<String>,
+    pub reverted: bool,
+    /// The decoded return value, `None` if the message returns nothing or it could not be
+    /// decoded.
+    pub return_value: Option<Value>,
+    /// Hex encoded data returned by the message.
+    pub data: String,
+    pub storage: Vec<StorageChange>,
+    pub events: Vec<DecodedEvent>,
+    pub debug_messages: Vec<String>,
+    /// The balances transferred to other accounts, in the smallest unit.
+    pub transfers: Vec<(AccountId32, u128)>,
+    /// The number of Wasm instructions executed by the message.
+    pub instructions: u64,
+    /// The host functions called by the message, with `--trace`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub trace: Vec<HostCall>,
+}
+
+impl SandboxResult {
+    fn new(metadata: &InkMetadata, message: &Message, execution: Execution) -> Self {
+        let hex = |data: &[u8]| format!("0x{}", hex::encode(data));
+        let types = metadata.types();
+        let return_value = match (message.returns, &execution.trap) {
+            (Some(ty), None) => types.decode_all(ty, &execution.data).ok(),
+            _ => None,
+        };
+        let events = execution
+            .events
+            .iter()
+            .map(|event| match metadata.decode_event(&event.data) {
+                Ok((name, args)) => DecodedEvent {
+                    name: Some(name),
+                    args,
+                },
+                Err(_) => DecodedEvent {
+                    name: None,
+                    args: Value::from(hex(&event.data)),
+                },
+            })
+            .collect();
+        SandboxResult {
+            message: message.label.clone(),
+            trap: execution.trap,
+            reverted: execution.reverted,
+            return_value,
+            data: hex(&execution.data),
+            storage: execution
+                .storage
+                .iter()
+                .map(|(key, value)| StorageChange {
+                    key: hex(key),
+                    value: value.as_deref().map(hex),
+                })
+                .collect(),
+            events,
+            debug_messages: execution.debug_messages,
+            transfers: execution.transfers,
+            instructions: execution.instructions,
+            trace: execution.trace,
+        }
+    }
+
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let mut output = match (&self.trap, self.reverted) {
+            (Some(trap), _) => format!("`{}` trapped: {}", self.message, trap),
+            (None, true) => format!("`{}` reverted", self.message),
+            (None, false) => format!("`{}` succeeded", self.message),
+        };
+        if let Some(ref value) = self.return_value {
+            output.push_str(&format!("\n\tReturn value: {}", value));
+        }
+        output.push_str(&format!("\n\tData: {}", self.data));
+        for change in &self.storage {
+            let value = change.value.as_deref().unwrap_or("cleared");
+            output.push_str(&format!("\n\tStorage {}: {}", change.key, value));
+        }
+        for event in &self.events {
+            match event.name {
+                Some(ref name) => output.push_str(&format!("\n\tEvent {}: {}", name, event.args)),
+                None => output.push_str(&format!("\n\tEvent: {}", event.args)),
+            }
+        }
+        for (account, value) in &self.transfers {
+            output.push_str(&format!("\n\tTransferred {} to {}", value, account));
+        }
+        for message in &self.debug_messages {
+            output.push_str(&format!("\n\tDebug message: {}", message));
+        }
+        output.push_str(&format!("\n\tInstructions: {}", self.instructions));
+        for call in &self.trace {
+            output.push_str(&format!("\n\tHost call {}", call.display()));
+        }
+        output
+    }
+}
+
+impl ExecCommand {
+    /// Deploys the contract with the constructor in a new sandbox, and executes the message.
+    ///
+    /// Fails if the constructor fails or the message traps.
+    pub fn exec(&self) -> Result<String> {
+        if !self.sandbox {
+            anyhow::bail!(
+                "Only the in-memory sandbox is supported, pass --sandbox. To execute the message \
+                 on a node use `cargo contract call --dry-run`"
+            )
+        }
+        let metadata = self.sandbox_opts.metadata(self.output)?;
+        let message = metadata.find_message(Some(&self.message), "")?;
+        let mut sandbox = self.sandbox_opts.deploy(&metadata)?;
+        sandbox.trace = self.trace;
+        let execution = sandbox.call(
+            &self.sandbox_opts.caller.account,
+            self.value.without_denomination()?,
+            metadata.encode_call(&message, &self.args)?,
+        )?;
+        let result = SandboxResult::new(&metadata, &message, execution);
+        let output = self.output.format(&result, |result| result.display())?;
+        match result.trap {
+            Some(_) => Err(anyhow::anyhow!(output)),
+            None => Ok(output),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use parity_wasm::{
+        builder,
+        elements::{BlockType, Instruction::*, Instructions, ValueType::I32},
+    };
+
+    /// A contract storing `[1, 2, 3, 4]` when deployed, and whose message stores and returns its
+    /// input with the revert flag, trapping if the second byte of the input is `0xff`.
+    pub fn contract() -> Vec<u8> {
+        let mut module = builder::module();
+        let two = module.push_signature(builder::signature().with_params(vec![I32; 2]).build_sig());
+        let three =
+            module.push_signature(builder::signature().with_params(vec![I32; 3]).build_sig());
+        let import = |module: builder::ModuleBuilder, name: &str, ty| {
+            module
+                .import()
+                .module("seal0")
+                .field(name)
+                .external()
+                .func(ty)
+                .build()
+        };
+        let module = import(module, "seal_input", two);
+        let module = import(module, "seal_set_storage", three);
+        let module = import(module, "seal_return", three);
+        let function = |module: builder::ModuleBuilder, code| {
+            module
+                .function()
+                .signature()
+                .build()
+                .body()
+                .with_instructions(Instructions::new(code))
+                .build()
+                .build()
+        };
+        let module = function(
+            module,
+            vec![I32Const(0), I32Const(32), I32Const(4), Call(1), End],
+        );
+        let module = function(
+            module,
+            vec![
+                I32Const(100),
+                I32Const(64),
+                I32Store(2, 0),
+                I32Const(200),
+                I32Const(100),
+                Call(0),
+                I32Const(0),
+                I32Const(200),
+                I32Const(100),
+                I32Load(2, 0),
+                Call(1),
+                I32Const(200),
+                I32Load8U(0, 1),
+                I32Const(255),
+                I32Eq,
+                If(BlockType::NoResult),
+                Unreachable,
+                End,
+                I32Const(1),
+                I32Const(200),
+                I32Const(100),
+                I32Load(2, 0),
+                Call(2),
+                End,
+            ],
+        );
+        let module = module
+            .import()
+            .module("env")
+            .field("memory")
+            .external()
+            .memory(1, Some(1))
+            .build()
+            .export()
+            .field("deploy")
+            .internal()
+            .func(3)
+            .build()
+            .export()
+            .field("call")
+            .internal()
+            .func(4)
+            .build()
+            .data()
+            .offset(I32Const(32))
+            .value(vec![1, 2, 3, 4])
+            .build()
+            .build();
+        parity_wasm::serialize(module).unwrap()
+    }
+
+    /// A remote counting the reads of its single value.
+    struct Counting(std::cell::Cell<usize>);
+
+    impl Remote for Counting {
+        fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+            self.0.set(self.0.get() + 1);
+            Ok(Some(key.to_vec()).filter(|key| key[0] == 1))
+        }
+    }
+
+    #[test]
+    fn reads_the_storage_of_the_fork_once() {
+        let remote = Rc::new(Counting(Default::default()));
+        let sandbox = Sandbox::fork(contract(), [2; 32].into(), 10, 7, remote.clone(), 1_000);
+        let mut environment = Environment {
+            caller: [1; 32].into(),
+            value: 0,
+            input: Vec::new(),
+            storage: sandbox.storage,
+            remote: sandbox.remote,
+            fetched: sandbox.fetched,
+            balance: sandbox.balance,
+            address: sandbox.contract,
+            block_number: sandbox.block_number,
+            terminated: false,
+            trace: false,
+            execution: Execution::default(),
+        };
+        assert_eq!(environment.value(&[1; 32]), Ok(Some(vec![1; 32])));
+        assert_eq!(environment.value(&[1; 32]), Ok(Some(vec![1; 32])));
+        assert_eq!(environment.value(&[0; 32]), Ok(None));
+        assert_eq!(environment.value(&[0; 32]), Ok(None));
+        assert_eq!(remote.0.get(), 2);
+        // Cleared keys are not read from the fork again.
+        environment.set(vec![1; 32], None);
+        assert_eq!(environment.value(&[1; 32]), Ok(None));
+        assert_eq!(environment.execution.storage_added, -64);
+        assert_eq!(remote.0.get(), 2);
+    }
+
+    #[test]
+    fn traces_the_host_functions() {
+        let alice = AccountId32::from([1; 32]);
+        let mut sandbox = Sandbox::new(contract(), 1_000);
+        sandbox.deploy(&alice, 0, vec![]).unwrap();
+        sandbox.trace = true;
+        let called = sandbox.call(&alice, 0, vec![5, 6]).unwrap();
+        let functions = called.trace.iter().map(|call| call.function.as_str());
+        assert_eq!(
+            functions.collect::<Vec<_>>(),
+            ["seal_input", "seal_set_storage", "seal_return"]
+        );
+        assert_eq!(called.trace[0].result.as_deref(), Some("0x0506"));
+        assert_eq!(
+            called.trace[1].display(),
+            format!("seal_set_storage(key 0x{}, value 0x0506)", "00".repeat(32))
+        );
+        let trapped = sandbox.call(&alice, 0, vec![0, 0xff]).unwrap();
+        assert_eq!(trapped.trace.len(), 2);
+    }
+
+    #[test]
+    fn executes_messages_and_rolls_back_traps() {
+        let alice = AccountId32::from([1; 32]);
+        let mut sandbox = Sandbox::new(contract(), 1_000);
+        let deployed = sandbox.deploy(&alice, 10, vec![]).unwrap();
+        assert!(deployed.succeeded());
+        assert_eq!(sandbox.storage[&vec![0; 32]], vec![1, 2, 3, 4]);
+        assert_eq!(sandbox.balance, 10);
+
+        let called = sandbox.call(&alice, 0, vec![5, 6]).unwrap();
+        assert_eq!(called.trap, None);
+        assert!(called.reverted);
+        assert_eq!(called.data, vec![5, 6]);
+        assert_eq!(called.storage[&vec![0; 32]], Some(vec![5, 6]));
+        // Reverting rolls back the changes to the storage.
+        assert_eq!(sandbox.storage[&vec![0; 32]], vec![1, 2, 3, 4]);
+
+        let trapped = sandbox.call(&alice, 5, vec![0, 0xff]).unwrap();
+        assert!(trapped.trap.unwrap().contains("unreachable"));
+        assert_eq!(sandbox.balance, 10);
+    }
+}
@@ -0,0 +1,482 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{convert::TryFrom, fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use codec::{Decode, Encode, Output};
+use frame_metadata::RuntimeMetadataPrefixed;
+use serde::{Deserialize, Serialize};
+use sp_core::H256;
+use structopt::StructOpt;
+use subxt::{
+    sp_runtime::{generic::Era, traits::Hash},
+    system::System,
+    Call, DefaultNodeRuntime, Encoded, Metadata,
+};
+
+use super::{
+    balance::BalanceVariant,
+    extension::{self, SignedExtension},
+    multisig::MultisigDetails,
+    pallet::ContractsVersion,
+    signer::ExtrinsicSigner,
+    uos,
+    wrap::WrappedCall,
+};
+use crate::ExtrinsicOpts;
+
+/// The period for which a signed extrinsic is valid.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Mortality {
+    /// The extrinsic is valid forever.
+    Immortal,
+    /// The extrinsic is valid for the given number of blocks.
+    Mortal(u64),
+}
+
+/// Settings for the signed extensions of an extrinsic, besides the nonce.
+#[derive(Clone, Debug)]
+pub(crate) struct SignedExtras {
+    /// The period for which the extrinsic is valid.
+    pub mortality: Mortality,
+    /// Tip for the block author, increasing the priority of the extrinsic.
+    pub tip: BalanceVariant,
+    /// The asset the fee is paid in, instead of the native token.
+    pub fee_asset: Option<u32>,
+}
+
+/// The chain specific values an extrinsic is signed with.
+pub(crate) struct SigningContext {
+    pub spec_version: u32,
+    pub tx_version: u32,
+    pub genesis_hash: H256,
+    pub nonce: u32,
+    pub era: Era,
+    /// Hash of the block the era starts at, or the genesis hash for an immortal extrinsic.
+    pub era_block_hash: H256,
+    /// Tip in the smallest unit of the chain's token.
+    pub tip: u128,
+    /// The asset the fee is paid in, instead of the native token.
+    pub fee_asset: Option<u32>,
+    /// The signed extensions of the runtime, as listed in its metadata.
+    pub extensions: Vec<SignedExtension>,
+}
+
+/// A signed extrinsic, SCALE encoded like an `UncheckedExtrinsic` of the runtime.
+pub(crate) struct Extrinsic(Vec<u8>);
+
+impl Encode for Extrinsic {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        // The extrinsic is prefixed with its length.
+        self.0.encode_to(dest)
+    }
+}
+
+/// The version of signed extrinsics: the signed bit and extrinsic format version 4.
+const SIGNED_EXTRINSIC_VERSION: u8 = 0b1000_0100;
+
+/// Signs the encoded call.
+///
+/// The signed extensions are encoded in the order and layout the runtime metadata lists them
+/// in, e.g. spec and transaction version, genesis hash, era, nonce, weight and transaction
+/// payment for the default node runtime.
+pub(crate) async fn sign(
+    call: Encoded,
+    signer: &ExtrinsicSigner,
+    context: &SigningContext,
+) -> Result<Extrinsic> {
+    if context.fee_asset.is_some()
+        && !context
+            .extensions
+            .contains(&SignedExtension::ChargeAssetTxPayment)
+    {
+        anyhow::bail!("The chain does not support paying fees in assets via ChargeAssetTxPayment")
+    }
+    let mut extra = Vec::new();
+    let mut additional_signed = Vec::new();
+    for extension in &context.extensions {
+        extension.encode_to(context, &mut extra, &mut additional_signed);
+    }
+
+    // Sized up front, as the call of an upload holds the whole code.
+    let mut payload = Vec::with_capacity(call.0.len() + extra.len() + additional_signed.len());
+    payload.extend_from_slice(&call.0);
+    payload.extend_from_slice(&extra);
+    payload.extend_from_slice(&additional_signed);
+    let signature = if let Some(scheme) = signer.air_gapped_scheme() {
+        // The air-gapped signer hashes long payloads itself.
+        uos::sign(scheme, signer.account_id(), &payload, context.genesis_hash)?
+    } else if payload.len() > 256 {
+        // Payloads longer than 256 bytes are signed by their hash.
+        signer.sign(&sp_core::blake2_256(&payload)).await?
+    } else {
+        signer.sign(&payload).await?
+    };
+
+    let address: <DefaultNodeRuntime as System>::Address = signer.account_id().clone().into();
+    let mut extrinsic = Vec::with_capacity(
+        1 + address.size_hint() + signature.size_hint() + extra.len() + call.0.len(),
+    );
+    extrinsic.push(SIGNED_EXTRINSIC_VERSION);
+    address.encode_to(&mut extrinsic);
+    signature.encode_to(&mut extrinsic);
+    extrinsic.extend_from_slice(&extra);
+    extrinsic.extend_from_slice(&call.0);
+    Ok(Extrinsic(extrinsic))
+}
+
+/// Encodes the call, resolving the indices of its module and function from the runtime metadata.
+pub(crate) fn encode_call<C>(metadata: &Metadata, call: C) -> Result<Encoded>
+where
+    C: Call<DefaultNodeRuntime>,
+{
+    let module = metadata.module_with_calls(C::MODULE).context(format!(
+        "The runtime of the chain does not include the {} pallet",
+        C::MODULE
+    ))?;
+    module.call(C::FUNCTION, call).context(format!(
+        "The {} pallet of the chain has no {} call",
+        C::MODULE,
+        C::FUNCTION
+    ))
+}
+
+/// Arguments for signing an extrinsic without a connection to a node.
+#[derive(Debug, Default, StructOpt)]
+pub(crate) struct OfflineOpts {
+    /// Sign the extrinsic without connecting to a node and print it hex encoded, instead of
+    /// submitting it.
+    #[structopt(long)]
+    offline: bool,
+    /// JSON file with the chain parameters required for offline signing: `genesisHash`,
+    /// `specVersion`, `transactionVersion`, `nonce` and the hex encoded runtime `metadata`,
+    /// optionally `blockNumber` and `blockHash` for a mortal extrinsic.
+    ///
+    /// The individual flags take precedence over the values in the file.
+    #[structopt(long, parse(from_os_str))]
+    chain_params: Option<PathBuf>,
+    /// Genesis hash of the chain, for offline signing
+    #[structopt(long, parse(try_from_str = parse_hash))]
+    genesis_hash: Option<H256>,
+    /// Spec version of the runtime, for offline signing
+    #[structopt(long)]
+    spec_version: Option<u32>,
+    /// Transaction version of the runtime, for offline signing
+    #[structopt(long)]
+    tx_version: Option<u32>,
+    /// File with the hex encoded runtime metadata as returned by the `state_getMetadata` RPC,
+    /// for offline signing
+    #[structopt(long, parse(from_os_str))]
+    metadata: Option<PathBuf>,
+    /// Number of the block the era of a mortal extrinsic starts at, for offline signing
+    #[structopt(long)]
+    block_number: Option<u64>,
+    /// Hash of the block the era of a mortal extrinsic starts at, for offline signing
+    #[structopt(long, parse(try_from_str = parse_hash))]
+    block_hash: Option<H256>,
+}
+
+/// Chain parameters as read from the `--chain-params` file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChainParams {
+    genesis_hash: Option<H256>,
+    spec_version: Option<u32>,
+    transaction_version: Option<u32>,
+    nonce: Option<u32>,
+    metadata: Option<String>,
+    block_number: Option<u64>,
+    block_hash: Option<H256>,
+}
+
+/// Everything required to sign an extrinsic without a connection to a node.
+pub(crate) struct OfflineParams {
+    genesis_hash: H256,
+    spec_version: u32,
+    tx_version: u32,
+    nonce: u32,
+    metadata: Metadata,
+    extensions: Vec<SignedExtension>,
+    /// The revision of the contracts pallet, or why it could not be detected.
+    contracts_version: Result<ContractsVersion>,
+    /// Number and hash of the block the era of a mortal extrinsic starts at.
+    era_block: Option<(u64, H256)>,
+}
+
+impl OfflineParams {
+    /// The runtime metadata of the chain.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// The revision of the contracts pallet of the chain.
+    pub fn contracts_version(&self) -> Result<ContractsVersion> {
+        match self.contracts_version {
+            Ok(version) => Ok(version),
+            Err(ref err) => Err(anyhow::anyhow!("{:#}", err)),
+        }
+    }
+}
+
+impl OfflineOpts {
+    /// Returns the parameters for offline signing, if `--offline` was requested.
+    ///
+    /// The `nonce` supplied via `--nonce` takes precedence over the one in the chain params file.
+    pub fn params(&self, nonce: Option<u32>) -> Result<Option<OfflineParams>> {
+        if !self.offline {
+            return Ok(None);
+        }
+        let file = match self.chain_params {
+            Some(ref path) => {
+                let json = fs::read(path).context(format!("Failed to read {}", path.display()))?;
+                serde_json::from_slice(&json)
+                    .context(format!("Failed to parse chain params {}", path.display()))?
+            }
+            None => ChainParams::default(),
+        };
+        let metadata = match self.metadata {
+            Some(ref path) => {
+                fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?
+            }
+            None => file
+                .metadata
+                .ok_or_else(|| anyhow::anyhow!("Missing --metadata for offline signing"))?,
+        };
+        let era_block = match (
+            self.block_number.or(file.block_number),
+            self.block_hash.or(file.block_hash),
+        ) {
+            (Some(number), Some(hash)) => Some((number, hash)),
+            (None, None) => None,
+            _ => anyhow::bail!("Both --block-number and --block-hash are required"),
+        };
+
+        let metadata = decode_metadata(&metadata)?;
+        let extensions = extension::from_metadata(&metadata)?;
+        let contracts_version = ContractsVersion::from_metadata(&metadata);
+        let missing = |flag| anyhow::anyhow!("Missing --{} for offline signing", flag);
+        Ok(Some(OfflineParams {
+            genesis_hash: self
+                .genesis_hash
+                .or(file.genesis_hash)
+                .ok_or_else(|| missing("genesis-hash"))?,
+            spec_version: self
+                .spec_version
+                .or(file.spec_version)
+                .ok_or_else(|| missing("spec-version"))?,
+            tx_version: self
+                .tx_version
+                .or(file.transaction_version)
+                .ok_or_else(|| missing("tx-version"))?,
+            nonce: nonce.or(file.nonce).ok_or_else(|| missing("nonce"))?,
+            metadata: Metadata::try_from(metadata)?,
+            extensions,
+            contracts_version,
+            era_block,
+        }))
+    }
+}
+
+/// Decodes hex encoded runtime metadata.
+fn decode_metadata(metadata: &str) -> Result<RuntimeMetadataPrefixed> {
+    let bytes = hex::decode(metadata.trim().trim_start_matches("0x"))
+        .context("Runtime metadata should be hex encoded")?;
+    RuntimeMetadataPrefixed::decode(&mut &bytes[..]).context("Failed to decode runtime metadata")
+}
+
+/// Parses a hex encoded 32 byte hash, with or without the `0x` prefix.
+fn parse_hash(input: &str) -> Result<H256> {
+    let bytes = hex::decode(input.trim_start_matches("0x"))?;
+    if bytes.len() != 32 {
+        anyhow::bail!("Hash should be 32 bytes in length")
+    }
+    Ok(H256::from_slice(&bytes))
+}
+
+/// A signed extrinsic, ready to be submitted.
+#[derive(Debug, Serialize)]
+pub(crate) struct SignedExtrinsic {
+    /// Hash of the extrinsic.
+    pub hash: H256,
+    /// The hex encoded extrinsic.
+    pub extrinsic: String,
+    /// Details of the multisig operation, if the call is dispatched from a multisig account.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multisig: Option<MultisigDetails>,
+}
+
+impl SignedExtrinsic {
+    /// Returns the human readable output: the hex encoded extrinsic, followed by the multisig
+    /// details if any.
+    pub fn display(&self) -> String {
+        match self.multisig {
+            Some(ref multisig) => format!("{}\n\t{}", self.extrinsic, multisig),
+            None => self.extrinsic.clone(),
+        }
+    }
+}
+
+/// Signs the call as configured by the extrinsic options, using the supplied chain parameters
+/// instead of connecting to a node.
+pub(crate) fn sign_offline<C>(
+    call: C,
+    extrinsic_opts: &ExtrinsicOpts,
+    params: &OfflineParams,
+) -> Result<SignedExtrinsic>
+where
+    C: Call<DefaultNodeRuntime>,
+{
+    let signer = extrinsic_opts.signer()?;
+    extrinsic_opts.check_address_formats();
+    let extras = extrinsic_opts.signed_extras();
+    let call = encode_call(&params.metadata, call)?;
+    let wrapped = WrappedCall::new(&params.metadata, call, extrinsic_opts, signer.account_id())?;
+    let (era, era_block_hash) = match (extras.mortality, params.era_block) {
+        (Mortality::Immortal, _) => (Era::Immortal, params.genesis_hash),
+        (Mortality::Mortal(period), Some((number, hash))) => (Era::mortal(period, number), hash),
+        (Mortality::Mortal(_), None) => anyhow::bail!(
+            "Signing a mortal extrinsic offline requires --block-number and --block-hash, \
+             or use --immortal"
+        ),
+    };
+    let context = SigningContext {
+        spec_version: params.spec_version,
+        tx_version: params.tx_version,
+        genesis_hash: params.genesis_hash,
+        nonce: params.nonce,
+        era,
+        era_block_hash,
+        tip: extras.tip.without_denomination()?,
+        fee_asset: extras.fee_asset,
+        extensions: params.extensions.clone(),
+    };
+    let extrinsic = async_std::task::block_on(sign(wrapped.call.clone(), &signer, &context))?;
+    Ok(SignedExtrinsic {
+        hash: <DefaultNodeRuntime as System>::Hashing::hash_of(&extrinsic),
+        extrinsic: format!("0x{}", hex::encode(extrinsic.encode())),
+        multisig: wrapped.multisig_details(None),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Scheme;
+    use codec::Compact;
+    use subxt::sp_runtime::{traits::Verify, MultiSignature};
+
+    /// The signed extensions of the default node runtime.
+    const NODE_EXTENSIONS: &[SignedExtension] = &[
+        SignedExtension::CheckSpecVersion,
+        SignedExtension::CheckTxVersion,
+        SignedExtension::CheckGenesis,
+        SignedExtension::CheckMortality,
+        SignedExtension::CheckNonce,
+        SignedExtension::Empty,
+        SignedExtension::ChargeTransactionPayment,
+    ];
+
+    fn signing_context(extensions: &[SignedExtension], fee_asset: Option<u32>) -> SigningContext {
+        SigningContext {
+            spec_version: 1,
+            tx_version: 2,
+            genesis_hash: H256::repeat_byte(1),
+            nonce: 7,
+            era: Era::mortal(64, 100),
+            era_block_hash: H256::repeat_byte(2),
+            tip: 5,
+            fee_asset,
+            extensions: extensions.to_vec(),
+        }
+    }
+
+    #[test]
+    fn parse_hash_accepts_optional_prefix() {
+        let hash = H256::from([1u8; 32]);
+        let hex = hex::encode(hash);
+        assert_eq!(parse_hash(&hex).unwrap(), hash);
+        assert_eq!(parse_hash(&format!("0x{}", hex)).unwrap(), hash);
+        assert!(parse_hash("0x0101").is_err());
+    }
+
+    #[test]
+    fn offline_params_require_all_values() {
+        let opts = OfflineOpts {
+            offline: true,
+            genesis_hash: Some(H256::zero()),
+            spec_version: Some(1),
+            tx_version: Some(1),
+            metadata: None,
+            ..Default::default()
+        };
+        let err = opts
+            .params(Some(0))
+            .err()
+            .expect("params should be incomplete");
+        assert_eq!(err.to_string(), "Missing --metadata for offline signing");
+
+        assert!(OfflineOpts::default().params(None).unwrap().is_none());
+    }
+
+    #[test]
+    fn signs_mortal_extrinsic() {
+        let signer = ExtrinsicSigner::from_suri(Scheme::Sr25519, "//Alice", None).unwrap();
+        let context = signing_context(NODE_EXTENSIONS, None);
+        let extrinsic =
+            async_std::task::block_on(sign(Encoded(vec![0, 1, 2]), &signer, &context)).unwrap();
+        let encoded = extrinsic.encode();
+        let mut input = &encoded[..];
+        let _length = Compact::<u32>::decode(&mut input).unwrap();
+        assert_eq!(u8::decode(&mut input).unwrap(), SIGNED_EXTRINSIC_VERSION);
+        let address = <DefaultNodeRuntime as System>::Address::decode(&mut input).unwrap();
+        assert_eq!(address, signer.account_id().clone().into());
+        let signature = MultiSignature::decode(&mut input).unwrap();
+        let (era, nonce, tip) = <(Era, Compact<u32>, Compact<u128>)>::decode(&mut input).unwrap();
+        assert_eq!((era, nonce.0, tip.0), (Era::mortal(64, 100), 7, 5));
+        assert_eq!(input, &[0, 1, 2]);
+
+        let mut payload = vec![0, 1, 2];
+        payload.extend((Era::mortal(64, 100), Compact(7u32), Compact(5u128)).encode());
+        payload.extend((1u32, 2u32, H256::repeat_byte(1), H256::repeat_byte(2)).encode());
+        assert!(signature.verify(&payload[..], signer.account_id()));
+    }
+
+    #[test]
+    fn signs_fee_asset_with_charge_asset_tx_payment() {
+        let signer = ExtrinsicSigner::from_suri(Scheme::Sr25519, "//Alice", None).unwrap();
+        let mut extensions = NODE_EXTENSIONS.to_vec();
+        *extensions.last_mut().unwrap() = SignedExtension::ChargeAssetTxPayment;
+        let context = signing_context(&extensions, Some(3));
+        let extrinsic =
+            async_std::task::block_on(sign(Encoded(vec![0, 1, 2]), &signer, &context)).unwrap();
+
+        let encoded = extrinsic.0;
+        let extra = (
+            Era::mortal(64, 100),
+            Compact(7u32),
+            Compact(5u128),
+            Some(3u32),
+        )
+            .encode();
+        assert!(encoded.ends_with(&[&extra[..], &[0, 1, 2]].concat()));
+
+        let context = signing_context(NODE_EXTENSIONS, Some(3));
+        assert!(
+            async_std::task::block_on(sign(Encoded(vec![0, 1, 2]), &signer, &context)).is_err()
+        );
+    }
+}
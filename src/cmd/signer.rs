@@ -0,0 +1,300 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::{Context, Result};
+use codec::Decode;
+use jsonrpsee::common::{to_value, Params};
+use sp_core::{
+    crypto::{AccountId32, Pair, Ss58Codec},
+    ecdsa, ed25519, sr25519,
+};
+use subxt::sp_runtime::{
+    traits::{IdentifyAccount, Verify},
+    MultiSignature, MultiSigner,
+};
+
+use super::keystore::{self, KeystoreSecret};
+use crate::SignerError;
+
+/// The signature scheme of the signer's key pair.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Scheme {
+    Sr25519,
+    Ed25519,
+    Ecdsa,
+}
+
+impl FromStr for Scheme {
+    type Err = String;
+
+    fn from_str(scheme: &str) -> Result<Self, Self::Err> {
+        match scheme {
+            "sr25519" => Ok(Scheme::Sr25519),
+            "ed25519" => Ok(Scheme::Ed25519),
+            "ecdsa" => Ok(Scheme::Ecdsa),
+            _ => Err("Could not parse signature scheme".to_string()),
+        }
+    }
+}
+
+/// The key pair of the signer, or the external service holding it.
+enum KeyPair {
+    Sr25519(sr25519::Pair),
+    Ed25519(ed25519::Pair),
+    Ecdsa(ecdsa::Pair),
+    /// An HTTP JSON-RPC service signing payloads with the key of the signer's account.
+    Remote(jsonrpsee::Client),
+    /// An air-gapped device holding a key of the scheme, e.g. Polkadot Vault, exchanging the
+    /// signing payloads and signatures via QR codes.
+    AirGapped(Scheme),
+}
+
+/// Signer for extrinsics submitted to the default node runtime.
+pub(crate) struct ExtrinsicSigner {
+    pair: KeyPair,
+    account_id: AccountId32,
+    nonce: Option<u32>,
+}
+
+impl ExtrinsicSigner {
+    /// Derives the key pair of the given scheme from the secret key URI.
+    ///
+    /// A secret key URI ending in an empty password junction, e.g. `//Alice///`, prompts for the
+    /// password unless one was supplied.
+    pub fn from_suri(scheme: Scheme, suri: &str, password: Option<&str>) -> Result<Self> {
+        let prompted;
+        let (suri, password) = match (suri.strip_suffix("///"), password) {
+            (Some(suri), None) => {
+                prompted = prompt_password("Password for the secret key URI: ")?;
+                (suri, Some(prompted.as_str()))
+            }
+            (Some(suri), password) => (suri, password),
+            (None, password) => (suri, password),
+        };
+        let secret_error = |_| SignerError::new("Secret string error");
+        let (pair, signer) = match scheme {
+            Scheme::Sr25519 => {
+                let pair = sr25519::Pair::from_string(suri, password).map_err(secret_error)?;
+                let signer = MultiSigner::from(pair.public());
+                (KeyPair::Sr25519(pair), signer)
+            }
+            Scheme::Ed25519 => {
+                let pair = ed25519::Pair::from_string(suri, password).map_err(secret_error)?;
+                let signer = MultiSigner::from(pair.public());
+                (KeyPair::Ed25519(pair), signer)
+            }
+            Scheme::Ecdsa => {
+                let pair = ecdsa::Pair::from_string(suri, password).map_err(secret_error)?;
+                let signer = MultiSigner::from(pair.public());
+                (KeyPair::Ecdsa(pair), signer)
+            }
+        };
+        Ok(ExtrinsicSigner {
+            pair,
+            account_id: signer.into_account(),
+            nonce: None,
+        })
+    }
+
+    /// Loads the key pair from a polkadot.js keystore file, prompting for the password if it is
+    /// encrypted and no password was supplied.
+    pub fn from_keyfile(path: &Path, password: Option<&str>) -> Result<Self> {
+        let prompted;
+        let password = match password {
+            None if keystore::is_encrypted(path)? => {
+                prompted = prompt_password(&format!("Password for {}: ", path.display()))?;
+                Some(prompted.as_str())
+            }
+            password => password,
+        };
+        let keystore =
+            keystore::load(path, password).map_err(|err| SignerError::new(format!("{:#}", err)))?;
+        Self::from_keystore(keystore)
+    }
+
+    /// Creates the signer from a key pair loaded from a polkadot.js keystore file.
+    fn from_keystore(keystore: KeystoreSecret) -> Result<Self> {
+        let invalid = |_| SignerError::new("Invalid secret key in keystore");
+        let (pair, signer) = match keystore.scheme {
+            Scheme::Sr25519 => {
+                // polkadot.js exports the secret key in its ed25519 compatible form.
+                let secret = schnorrkel::SecretKey::from_ed25519_bytes(&keystore.secret)
+                    .map_err(|_| SignerError::new("Invalid secret key in keystore"))?;
+                let pair = sr25519::Pair::from_seed_slice(&secret.to_bytes()).map_err(invalid)?;
+                let signer = MultiSigner::from(pair.public());
+                (KeyPair::Sr25519(pair), signer)
+            }
+            Scheme::Ed25519 => {
+                let pair =
+                    ed25519::Pair::from_seed_slice(&keystore.secret[..32]).map_err(invalid)?;
+                let signer = MultiSigner::from(pair.public());
+                (KeyPair::Ed25519(pair), signer)
+            }
+            Scheme::Ecdsa => {
+                let pair = ecdsa::Pair::from_seed_slice(&keystore.secret).map_err(invalid)?;
+                let signer = MultiSigner::from(pair.public());
+                (KeyPair::Ecdsa(pair), signer)
+            }
+        };
+        if signer.as_ref() != &keystore.public[..] {
+            let error = "The public key in the keystore does not match its secret key";
+            return Err(SignerError::new(error).into());
+        }
+        Ok(ExtrinsicSigner {
+            pair,
+            account_id: signer.into_account(),
+            nonce: None,
+        })
+    }
+
+    /// Creates a signer which forwards the signing payloads to the JSON-RPC service at `url`,
+    /// which holds the key of `account_id`.
+    ///
+    /// The service is called with the `signer_sign` method and the params `[address, payload]`,
+    /// the SS58 address of the account and the hex encoded payload. It is expected to return the
+    /// hex encoded, SCALE encoded `MultiSignature` of the payload.
+    pub fn remote(url: &url::Url, account_id: AccountId32) -> Self {
+        ExtrinsicSigner {
+            pair: KeyPair::Remote(jsonrpsee::http_client(url.as_str())),
+            account_id,
+            nonce: None,
+        }
+    }
+
+    /// Creates a signer which shows the signing payloads of extrinsics as QR codes, for the
+    /// air-gapped device holding the key of `account_id` to sign them.
+    pub fn air_gapped(scheme: Scheme, account_id: AccountId32) -> Self {
+        ExtrinsicSigner {
+            pair: KeyPair::AirGapped(scheme),
+            account_id,
+            nonce: None,
+        }
+    }
+
+    /// The scheme of the key held by an air-gapped signer, `None` for other signers.
+    pub fn air_gapped_scheme(&self) -> Option<Scheme> {
+        match self.pair {
+            KeyPair::AirGapped(scheme) => Some(scheme),
+            _ => None,
+        }
+    }
+
+    /// The account id of the signer, which for `ecdsa` is the hash of the public key.
+    pub fn account_id(&self) -> &AccountId32 {
+        &self.account_id
+    }
+
+    /// The nonce to sign with, if it should not be fetched from the node.
+    pub fn nonce(&self) -> Option<u32> {
+        self.nonce
+    }
+
+    pub fn set_nonce(&mut self, nonce: u32) {
+        self.nonce = Some(nonce)
+    }
+
+    /// Signs the message with the signer's key pair, or by the remote signer.
+    pub async fn sign(&self, message: &[u8]) -> Result<MultiSignature> {
+        let client = match self.pair {
+            KeyPair::Sr25519(ref pair) => return Ok(pair.sign(message).into()),
+            KeyPair::Ed25519(ref pair) => return Ok(pair.sign(message).into()),
+            KeyPair::Ecdsa(ref pair) => return Ok(pair.sign(message).into()),
+            KeyPair::Remote(ref client) => client,
+            KeyPair::AirGapped(_) => {
+                let error = "An air-gapped signer only signs extrinsics, via QR codes";
+                return Err(SignerError::new(error).into());
+            }
+        };
+        let params = Params::Array(vec![
+            to_value(self.account_id.to_ss58check())?,
+            to_value(format!("0x{}", hex::encode(message)))?,
+        ]);
+        let signature: String = client
+            .request("signer_sign", params)
+            .await
+            .map_err(SignerError::new)
+            .context("Remote signer failed to sign the extrinsic")?;
+        let bytes = hex::decode(signature.trim_start_matches("0x")).map_err(|_| {
+            SignerError::new("Remote signer returned a signature which is not hex encoded")
+        })?;
+        let signature = MultiSignature::decode(&mut &bytes[..])
+            .map_err(|_| SignerError::new("Remote signer returned an invalid signature"))?;
+        if !signature.verify(message, &self.account_id) {
+            return Err(SignerError::new(format!(
+                "Remote signer returned a signature not matching the account {}",
+                self.account_id
+            ))
+            .into());
+        }
+        Ok(signature)
+    }
+}
+
+/// Reads the password from the file, ignoring a trailing newline.
+pub(crate) fn read_password_file(path: &Path) -> Result<String> {
+    let password =
+        fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    Ok(password.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+/// Reads a password from the terminal, without echoing it.
+fn prompt_password(prompt: &str) -> Result<String> {
+    rpassword::read_password_from_tty(Some(prompt))
+        .context("Failed to read the password from the terminal")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_with_all_schemes() {
+        for scheme in &[Scheme::Sr25519, Scheme::Ed25519, Scheme::Ecdsa] {
+            let signer = ExtrinsicSigner::from_suri(*scheme, "//Alice", None).unwrap();
+            let signature = async_std::task::block_on(signer.sign(b"payload")).unwrap();
+            assert!(signature.verify(&b"payload"[..], signer.account_id()));
+        }
+
+        let sr25519 = ExtrinsicSigner::from_suri(Scheme::Sr25519, "//Alice", None).unwrap();
+        let ed25519 = ExtrinsicSigner::from_suri(Scheme::Ed25519, "//Alice", None).unwrap();
+        assert_ne!(sr25519.account_id(), ed25519.account_id());
+    }
+
+    #[test]
+    fn reads_password_file() {
+        crate::util::tests::with_tmp_dir(|path| {
+            let password_path = path.join("password");
+            fs::write(&password_path, "secret\n")?;
+            assert_eq!(read_password_file(&password_path)?, "secret");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn loads_sr25519_keystore_secret() {
+        let pair = sr25519::Pair::from_string("//Alice", None).unwrap();
+        let keystore = KeystoreSecret {
+            scheme: Scheme::Sr25519,
+            secret: pair.as_ref().secret.to_ed25519_bytes().to_vec(),
+            public: pair.public().0.to_vec(),
+        };
+        let signer = ExtrinsicSigner::from_keystore(keystore).unwrap();
+        let alice = ExtrinsicSigner::from_suri(Scheme::Sr25519, "//Alice", None).unwrap();
+        assert_eq!(signer.account_id(), alice.account_id());
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use parity_wasm::elements::{ImportCountType, Module};
+
+use crate::crate_metadata::CrateMetadata;
+
+/// The number of functions and crates listed by the report.
+const TOP_CONTRIBUTORS: usize = 10;
+
+/// The number of bytes attributed to a function or a crate.
+#[derive(Debug, PartialEq)]
+pub struct SizeEntry {
+    pub name: String,
+    pub size: usize,
+    /// The number of functions, for a function the monomorphizations of its generic parameters.
+    pub count: usize,
+}
+
+/// Attributes the bytes of the code of a contract to its functions and to the crates defining
+/// them, like `twiggy top`.
+#[derive(Debug)]
+pub struct SizeReport {
+    /// The bytes of the bodies of all functions.
+    pub code: usize,
+    /// The bytes of the data segments.
+    pub data: usize,
+    /// The largest functions, by their demangled names without generic parameters.
+    pub functions: Vec<SizeEntry>,
+    /// The largest crates, by the total size of their functions.
+    pub crates: Vec<SizeEntry>,
+}
+
+impl SizeReport {
+    /// Measures the Wasm emitted by rustc after removing the code not used by the `call` and
+    /// `deploy` entrypoints, the same way the build does.
+    pub fn of_original_wasm(crate_metadata: &CrateMetadata) -> Result<Self> {
+        let path = &crate_metadata.original_wasm;
+        let module = parity_wasm::deserialize_file(path)
+            .context(format!("Loading original wasm file '{}'", path.display()))?;
+        // The report falls back to the function indices for names which fail to parse.
+        let mut module = module.parse_names().unwrap_or_else(|(_, module)| module);
+        if pwasm_utils::optimize(&mut module, ["call", "deploy"].to_vec()).is_err() {
+            anyhow::bail!("Optimizer failed");
+        }
+        Ok(Self::new(&module))
+    }
+
+    /// Measures the functions of the module, whose names are taken from its parsed `name`
+    /// section.
+    pub fn new(module: &Module) -> Self {
+        let names = module
+            .names_section()
+            .and_then(|names| names.functions())
+            .map(|functions| functions.names());
+        let imported = module.import_count(ImportCountType::Function) as u32;
+        let bodies = module
+            .code_section()
+            .map(|code| code.bodies())
+            .unwrap_or_default();
+
+        let mut functions = BTreeMap::<String, (usize, usize)>::new();
+        let mut crates = BTreeMap::<String, (usize, usize)>::new();
+        for (index, body) in bodies.iter().enumerate() {
+            let index = imported + index as u32;
+            let size = parity_wasm::serialize(body.clone())
+                .map(|bytes| bytes.len())
+                .unwrap_or_default();
+            let (name, krate) = match names.and_then(|names| names.get(index)) {
+                Some(name) => {
+                    let demangled = format!("{:#}", rustc_demangle::demangle(name));
+                    let krate = crate_of(&demangled);
+                    (without_generics(&demangled), krate)
+                }
+                None => (format!("function[{}]", index), UNKNOWN_CRATE.to_string()),
+            };
+            for (entry, name) in [(&mut functions, name), (&mut crates, krate)] {
+                let (total, count) = entry.entry(name).or_default();
+                *total += size;
+                *count += 1;
+            }
+        }
+        let data = module
+            .data_section()
+            .map(|data| data.entries().iter().map(|s| s.value().len()).sum())
+            .unwrap_or_default();
+
+        SizeReport {
+            code: functions.values().map(|(size, _)| size).sum(),
+            data,
+            functions: top(functions),
+            crates: top(crates),
+        }
+    }
+
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let percent = |size: usize| 100.0 * size as f64 / self.code.max(1) as f64;
+        let mut out = format!(
+            "Code: {} bytes, data: {} bytes, before the binaryen optimization\n\nLargest functions:\n",
+            self.code, self.data
+        );
+        for function in &self.functions {
+            let copies = match function.count {
+                1 => String::new(),
+                count => format!(" ({} monomorphizations)", count),
+            };
+            out.push_str(&format!(
+                "  {:>8} {:>5.1}%  {}{}\n",
+                function.size,
+                percent(function.size),
+                function.name,
+                copies
+            ));
+        }
+        out.push_str("\nLargest crates:\n");
+        for krate in &self.crates {
+            out.push_str(&format!(
+                "  {:>8} {:>5.1}%  {}\n",
+                krate.size,
+                percent(krate.size),
+                krate.name
+            ));
+        }
+        out
+    }
+}
+
+/// The crate of functions which are not named after a Rust path, e.g. compiler intrinsics.
+const UNKNOWN_CRATE: &str = "[unknown]";
+
+/// Returns the crate of the demangled function path, the first segment of the path or of the
+/// type or trait of an impl, e.g. `alloc` for `<alloc::vec::Vec<T> as core::ops::Drop>::drop`.
+fn crate_of(path: &str) -> String {
+    let path = path.trim_start_matches('<');
+    match path.find("::") {
+        Some(end) if end > 0 => path[..end].to_string(),
+        _ => UNKNOWN_CRATE.to_string(),
+    }
+}
+
+/// Removes the generic parameters of the demangled path, so that the monomorphizations of a
+/// function are reported together, e.g. `ink_storage::Mapping::insert` for
+/// `ink_storage::Mapping<u32, u128>::insert::<&u32>`.
+///
+/// The angle brackets of qualified paths like `<T as Trait>::f` are kept.
+fn without_generics(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    let mut depth = 0;
+    let mut previous = ' ';
+    for c in path.chars() {
+        match c {
+            '<' if depth > 0 => depth += 1,
+            '<' if previous.is_alphanumeric() || previous == '_' => depth = 1,
+            '<' if out.ends_with("::") => {
+                // The turbofish of the generic parameters of a function.
+                out.truncate(out.len() - 2);
+                depth = 1
+            }
+            // The `>` of `->` in a function pointer type does not close the parameters.
+            '>' if depth > 0 && previous != '-' => depth -= 1,
+            _ if depth > 0 => (),
+            c => out.push(c),
+        }
+        previous = c;
+    }
+    out
+}
+
+/// The largest entries, in descending order of size.
+fn top(entries: BTreeMap<String, (usize, usize)>) -> Vec<SizeEntry> {
+    let mut entries = entries
+        .into_iter()
+        .map(|(name, (size, count))| SizeEntry { name, size, count })
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    entries.truncate(TOP_CONTRIBUTORS);
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parity_wasm::{
+        builder,
+        elements::{
+            FuncBody, FunctionNameSubsection, Instruction, Instructions, NameSection, Section,
+        },
+    };
+
+    #[test]
+    fn attributes_code_to_functions_and_crates() {
+        let function = |instructions: &[Instruction]| {
+            let mut instructions = instructions.to_vec();
+            instructions.push(Instruction::End);
+            FuncBody::new(Vec::new(), Instructions::new(instructions))
+        };
+        let mut names = FunctionNameSubsection::default();
+        for (index, name) in [
+            "_ZN4core3fmt5write17h0123456789abcdefE",
+            "_ZN5alloc3vec3Vec4push17h0123456789abcdefE",
+            "memcpy",
+        ]
+        .iter()
+        .enumerate()
+        {
+            names.names_mut().insert(index as u32, name.to_string());
+        }
+        let add = [
+            Instruction::I32Const(1),
+            Instruction::I32Const(2),
+            Instruction::I32Add,
+            Instruction::Drop,
+        ];
+        let module = builder::module()
+            .function()
+            .signature()
+            .build()
+            .body()
+            .with_func(function(&add))
+            .build()
+            .build()
+            .function()
+            .signature()
+            .build()
+            .body()
+            .with_func(function(&[]))
+            .build()
+            .build()
+            .function()
+            .signature()
+            .build()
+            .body()
+            .with_func(function(&[]))
+            .build()
+            .build()
+            .data()
+            .offset(Instruction::I32Const(0))
+            .value(b"hello".to_vec())
+            .build()
+            .with_section(Section::Name(NameSection::new(None, Some(names), None)))
+            .build();
+
+        let report = SizeReport::new(&module);
+        assert_eq!(report.data, 5);
+        assert_eq!(report.functions[0].name, "core::fmt::write");
+        assert_eq!(report.crates[0].name, "core");
+        let crates = report
+            .crates
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>();
+        assert!(crates.contains(&"alloc") && crates.contains(&UNKNOWN_CRATE));
+        assert_eq!(
+            crate_of("<alloc::vec::Vec<T> as core::ops::Drop>::drop"),
+            "alloc"
+        );
+    }
+
+    #[test]
+    fn collapses_the_monomorphizations() {
+        assert_eq!(
+            without_generics("ink_storage::Mapping<u32, u128>::insert::<&u32>"),
+            "ink_storage::Mapping::insert"
+        );
+        assert_eq!(
+            without_generics("<alloc::vec::Vec<T> as core::ops::Drop>::drop"),
+            "<alloc::vec::Vec as core::ops::Drop>::drop"
+        );
+        assert_eq!(
+            without_generics("core::ptr::drop_in_place<alloc::boxed::Box<dyn Fn() -> u8>>"),
+            "core::ptr::drop_in_place"
+        );
+        assert_eq!(
+            without_generics("flipper::{{closure}}"),
+            "flipper::{{closure}}"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::BTreeMap, fs, path::Path, process::Command};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::crate_metadata::CrateMetadata;
+
+/// Name of the file recording the sizes of the builds, in the `target/ink` directory.
+pub(crate) const SIZE_HISTORY_FILE: &str = "size-history.json";
+
+/// The number of builds recorded for each contract.
+const MAX_RECORDS: usize = 50;
+
+/// The size of the optimized Wasm of a build.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct SizeRecord {
+    /// The abbreviated git commit of the sources, if the project is in a git repository.
+    pub commit: Option<String>,
+    /// The size in bytes.
+    pub size: u64,
+}
+
+/// The change of the size of the optimized Wasm since the previous build.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SizeDelta {
+    /// The change in bytes.
+    pub bytes: i64,
+    /// The commit of the previous build.
+    pub since: Option<String>,
+}
+
+impl SizeDelta {
+    /// The human readable output, e.g. `+1.2 KiB since a1b2c3`.
+    pub fn display(&self) -> String {
+        let since = match self.since {
+            Some(ref commit) => format!("since {}", commit),
+            None => "since the previous build".to_string(),
+        };
+        if self.bytes == 0 {
+            return format!("unchanged {}", since);
+        }
+        let sign = if self.bytes > 0 { "+" } else { "-" };
+        let size = self.bytes.unsigned_abs();
+        if size < 1024 {
+            format!("{}{} B {}", sign, size, since)
+        } else {
+            format!("{}{:.1} KiB {}", sign, size as f64 / 1024.0, since)
+        }
+    }
+}
+
+/// The sizes of the builds, of each contract, oldest first.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub(crate) struct SizeHistory {
+    contracts: BTreeMap<String, Vec<SizeRecord>>,
+}
+
+impl SizeHistory {
+    /// Reads the history, which is empty if the file does not exist yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(SizeHistory::default());
+        }
+        let json =
+            fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&json).context(format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json).context(format!("Failed to write {}", path.display()))
+    }
+
+    /// Records the build of the contract, returning the change since its previous build.
+    ///
+    /// A build of the same commit as the previous one replaces its record, so that the history
+    /// keeps one size per commit.
+    pub fn push(&mut self, contract: &str, record: SizeRecord) -> Option<SizeDelta> {
+        let records = self.contracts.entry(contract.to_string()).or_default();
+        let delta = records.last().map(|previous| SizeDelta {
+            bytes: record.size as i64 - previous.size as i64,
+            since: previous.commit.clone(),
+        });
+        match records.last_mut() {
+            Some(previous) if previous.commit.is_some() && previous.commit == record.commit => {
+                *previous = record
+            }
+            _ => records.push(record),
+        }
+        if records.len() > MAX_RECORDS {
+            records.drain(..records.len() - MAX_RECORDS);
+        }
+        delta
+    }
+}
+
+/// Records the size of the optimized Wasm in the history of the target directory, returning the
+/// change since the previous build of the contract.
+///
+/// The build already succeeded, so failing to record its size only logs a warning.
+pub(crate) fn record(crate_metadata: &CrateMetadata, wasm: &Path) -> Option<SizeDelta> {
+    let path = crate_metadata.target_directory.join(SIZE_HISTORY_FILE);
+    let recorded = SizeHistory::load(&path).and_then(|mut history| {
+        let record = SizeRecord {
+            commit: commit(crate_metadata),
+            size: fs::metadata(wasm)?.len(),
+        };
+        let delta = history.push(&crate_metadata.package_name, record);
+        history.save(&path)?;
+        Ok(delta)
+    });
+    match recorded {
+        Ok(delta) => delta,
+        Err(err) => {
+            log::warn!("Failed to record the size of the build: {:?}", err);
+            None
+        }
+    }
+}
+
+/// The abbreviated git commit of the sources of the contract.
+fn commit(crate_metadata: &CrateMetadata) -> Option<String> {
+    let dir = crate_metadata.manifest_path.directory()?;
+    Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .current_dir(dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_change_since_the_previous_commit() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            let path = dir.join(SIZE_HISTORY_FILE);
+            let record = |commit: &str, size| SizeRecord {
+                commit: Some(commit.to_string()),
+                size,
+            };
+            let mut history = SizeHistory::load(&path)?;
+            assert_eq!(history.push("flipper", record("a1b2c3", 10_000)), None);
+            history.save(&path)?;
+
+            let mut history = SizeHistory::load(&path)?;
+            let delta = history.push("flipper", record("d4e5f6", 11_229)).unwrap();
+            assert_eq!(delta.display(), "+1.2 KiB since a1b2c3");
+            let delta = history.push("flipper", record("d4e5f6", 11_000)).unwrap();
+            assert_eq!(delta.display(), "-229 B since d4e5f6");
+            assert_eq!(history.contracts["flipper"].len(), 2);
+            assert_eq!(history.push("erc20", record("d4e5f6", 1)), None);
+            Ok(())
+        })
+    }
+}
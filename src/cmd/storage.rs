@@ -0,0 +1,579 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use jsonrpsee::common::{to_value, JsonValue, Params};
+use serde::Serialize;
+use serde_json::Value;
+use sp_core::{crypto::AccountId32, Bytes, H256};
+
+use super::{
+    address::Address,
+    block::{self, BlockRef},
+    extrinsic::Connection,
+    info::{ContractInfo, ContractInfoOfStore},
+    transcode::{InkMetadata, TypeRegistry},
+};
+use crate::{OutputType, SubmitOpts};
+
+/// The prefix of the keys of default child tries, which hold the storage of contracts.
+pub(crate) const CHILD_STORAGE_KEY_PREFIX: &[u8] = b":child_storage:default:";
+
+/// A storage cell of a contract.
+#[derive(Debug, Serialize)]
+pub(crate) struct StorageCell {
+    /// Hex encoded key of the cell in the contract's child trie.
+    pub key: String,
+    /// The name of the storage field the cell belongs to, if known from the metadata.
+    pub field: Option<String>,
+    /// The value of the cell, if it could be decoded with the metadata.
+    pub value: Option<Value>,
+    /// The hex encoded value of the cell.
+    pub data: String,
+}
+
+/// Result of a successful `storage`.
+#[derive(Debug, Serialize)]
+pub(crate) struct StorageResult {
+    pub contract: AccountId32,
+    /// Hash of the block the storage was read at, if `--at` selected one instead of the best
+    /// block.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block: Option<H256>,
+    pub cells: Vec<StorageCell>,
+}
+
+impl StorageResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let mut output = format!(
+            "Contract {} has {} storage cells",
+            self.contract,
+            self.cells.len()
+        );
+        if let Some(ref block) = self.block {
+            output.push_str(&format!(" at block {:?}", block));
+        }
+        for cell in &self.cells {
+            let value = match cell.value {
+                Some(ref value) => value.to_string(),
+                None => cell.data.clone(),
+            };
+            match cell.field {
+                Some(ref field) => output.push_str(&format!("\n\t{}: {}", field, value)),
+                None => output.push_str(&format!("\n\t{}: {}", cell.key, value)),
+            }
+        }
+        output
+    }
+}
+
+/// A cell of the storage layout in the metadata, holding a storage field.
+#[derive(Debug, PartialEq)]
+pub(crate) struct LayoutCell {
+    /// The path of the field within the contract's storage struct, e.g. `owner.name`.
+    pub field: String,
+    pub key: Vec<u8>,
+    pub ty: u64,
+}
+
+/// Collects the cells of the storage layout, which ink! places at fixed keys.
+///
+/// The cells of dynamic layouts, e.g. of hash maps, are not known in advance, so they are left
+/// out.
+fn layout_cells(layout: &Value, path: &str, cells: &mut Vec<LayoutCell>) {
+    if let Some(cell) = layout.get("cell") {
+        let key = cell["key"]
+            .as_str()
+            .and_then(|key| hex::decode(key.trim_start_matches("0x")).ok());
+        if let (Some(key), Some(ty)) = (key, cell["ty"].as_u64()) {
+            cells.push(LayoutCell {
+                field: path.to_string(),
+                key,
+                ty,
+            });
+        }
+    } else if let Some(fields) = layout["struct"]["fields"].as_array() {
+        for (index, field) in fields.iter().enumerate() {
+            let name = field["name"]
+                .as_str()
+                .map(String::from)
+                .unwrap_or_else(|| index.to_string());
+            let path = match path {
+                "" => name,
+                _ => format!("{}.{}", path, name),
+            };
+            layout_cells(&field["layout"], &path, cells);
+        }
+    }
+}
+
+/// The cells of the layout by the keys of the child trie they are stored under, which are the
+/// hashes of the keys the contract uses, hashing each key once however many keys are looked up.
+pub(crate) fn index_cells(cells: &[LayoutCell]) -> HashMap<Vec<u8>, &LayoutCell> {
+    let mut index = HashMap::new();
+    // In reverse, so the first cell of the layout wins for a key used twice.
+    for cell in cells.iter().rev() {
+        index.insert(cell.key.clone(), cell);
+        index.insert(sp_core::blake2_256(&cell.key).to_vec(), cell);
+    }
+    index
+}
+
+fn decode_cell(types: &TypeRegistry, cell: &LayoutCell, data: &[u8]) -> Option<Value> {
+    match types.decode_all(cell.ty, data) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            log::debug!("Failed to decode storage field {}: {}", cell.field, err);
+            None
+        }
+    }
+}
+
+/// Collects the cells of the metadata's storage layout, restricted to those of the storage
+/// `field` if supplied.
+pub(crate) fn select_cells(
+    metadata: Option<&InkMetadata>,
+    field: Option<&str>,
+) -> Result<Vec<LayoutCell>> {
+    let mut cells = Vec::new();
+    if let Some(layout) = metadata.and_then(InkMetadata::storage) {
+        layout_cells(layout, "", &mut cells);
+    }
+    if let Some(field) = field {
+        if metadata.is_none() {
+            anyhow::bail!("Filtering by storage field requires the metadata of the contract")
+        }
+        let matches = |cell: &LayoutCell| {
+            cell.field == field || cell.field.starts_with(&format!("{}.", field))
+        };
+        if !cells.iter().any(matches) {
+            let fields = cells
+                .iter()
+                .map(|cell| cell.field.as_str())
+                .collect::<Vec<_>>();
+            anyhow::bail!(
+                "No storage field {} in the metadata, expected one of {}",
+                field,
+                fields.join(", ")
+            )
+        }
+        cells.retain(matches);
+    }
+    Ok(cells)
+}
+
+/// Returns the key of the contract's child trie, at the block `at`.
+pub(crate) async fn child_key(
+    connection: &Connection,
+    account_id: &AccountId32,
+    at: Option<H256>,
+) -> Result<JsonValue> {
+    let trie_id = match connection
+        .fetch_at(&ContractInfoOfStore { account_id }, at)
+        .await?
+    {
+        Some(ContractInfo::Alive(info)) => info.trie_id,
+        Some(ContractInfo::Tombstone) => {
+            anyhow::bail!(
+                "The contract at {} was evicted with its storage",
+                account_id
+            )
+        }
+        None => anyhow::bail!("No contract found at {}", account_id),
+    };
+    Ok(to_value(Bytes([CHILD_STORAGE_KEY_PREFIX, &trie_id].concat()))?)
+}
+
+/// Reads the cells of the child trie at the block `at`, decoding those of the layout's `index`
+/// with the `types`. With `only_layout`, the cells outside the layout are left out.
+pub(crate) async fn read_cells(
+    connection: &Connection,
+    child_key: &JsonValue,
+    index: &HashMap<Vec<u8>, &LayoutCell>,
+    types: Option<&TypeRegistry>,
+    only_layout: bool,
+    at: Option<H256>,
+) -> Result<Vec<StorageCell>> {
+    let params = Params::Array(vec![
+        child_key.clone(),
+        to_value(Bytes(Vec::new()))?,
+        to_value(at)?,
+    ]);
+    let keys: Vec<Bytes> = connection
+        .request("childstate_getKeys", params)
+        .await
+        .context("Failed to fetch the storage keys of the contract")?;
+
+    let mut storage = Vec::new();
+    for key in keys {
+        let cell = index.get(&key.0).copied();
+        if only_layout && cell.is_none() {
+            continue;
+        }
+        let params = Params::Array(vec![child_key.clone(), to_value(&key)?, to_value(at)?]);
+        let data: Option<Bytes> = connection.request("childstate_getStorage", params).await?;
+        let data = match data {
+            Some(data) => data.0,
+            None => continue,
+        };
+        let value = match (types, cell) {
+            (Some(types), Some(cell)) => decode_cell(types, cell, &data),
+            _ => None,
+        };
+        storage.push(StorageCell {
+            key: format!("0x{}", hex::encode(&key.0)),
+            field: cell.map(|cell| cell.field.clone()),
+            value,
+            data: format!("0x{}", hex::encode(&data)),
+        });
+    }
+    Ok(storage)
+}
+
+/// Fetches all cells of the contract's storage, iterating its child trie.
+///
+/// The cells are decoded against the storage layout of the contract's metadata where possible,
+/// and may be restricted to those of a storage `field`. The storage is read at the block `at`,
+/// defaulting to the best block.
+pub(crate) fn execute_storage(
+    submit_opts: &SubmitOpts,
+    contract: &Address,
+    field: Option<&str>,
+    metadata_path: Option<&Path>,
+    at: Option<&BlockRef>,
+) -> Result<StorageResult> {
+    let metadata =
+        InkMetadata::load(metadata_path)?.map(|metadata| metadata.for_output(submit_opts.output));
+    let cells = select_cells(metadata.as_ref(), field)?;
+
+    async_std::task::block_on(async move {
+        let connection = Connection::new(submit_opts).await?;
+        contract.check_format();
+
+        let at = block::resolve(&connection, at).await?;
+        let account_id = &contract.account;
+        let child_key = child_key(&connection, account_id, at).await?;
+        let types = metadata.as_ref().map(InkMetadata::types);
+        let index = index_cells(&cells);
+        let storage = read_cells(
+            &connection,
+            &child_key,
+            &index,
+            types.as_ref(),
+            field.is_some(),
+            at,
+        )
+        .await?;
+        Ok(StorageResult {
+            contract: account_id.clone(),
+            block: at,
+            cells: storage,
+        })
+    })
+}
+
+/// A cell of the contract's storage written or removed by a call.
+#[derive(Debug, Serialize)]
+pub(crate) struct StorageWrite {
+    /// Hex encoded key the contract wrote the cell under.
+    pub key: String,
+    /// The name of the storage field the cell belongs to, if known from the metadata.
+    pub field: Option<String>,
+    /// The value written, decoded or else hex encoded, `None` if the cell was removed.
+    pub value: Option<Value>,
+}
+
+impl StorageWrite {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let cell = self.field.as_ref().unwrap_or(&self.key);
+        match self.value {
+            Some(ref value) => format!("{}: {}", cell, value),
+            None => format!("{}: removed", cell),
+        }
+    }
+}
+
+/// The cells written by a call, `None` for those it removed, decoded against the storage layout
+/// of the metadata where possible.
+pub(crate) fn written_cells(
+    written: &BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    metadata: Option<&InkMetadata>,
+) -> Result<Vec<StorageWrite>> {
+    let cells = select_cells(metadata, None)?;
+    let index = index_cells(&cells);
+    let types = metadata.map(InkMetadata::types);
+    let writes = written
+        .iter()
+        .map(|(key, data)| {
+            let cell = index.get(key).copied();
+            let value = data.as_ref().map(|data| {
+                let decoded = match (&types, cell) {
+                    (Some(types), Some(cell)) => decode_cell(types, cell, data),
+                    _ => None,
+                };
+                decoded.unwrap_or_else(|| Value::from(format!("0x{}", hex::encode(data))))
+            });
+            StorageWrite {
+                key: format!("0x{}", hex::encode(key)),
+                field: cell.map(|cell| cell.field.clone()),
+                value,
+            }
+        })
+        .collect();
+    Ok(writes)
+}
+
+/// A change of a cell of the contract's storage in a new block.
+#[derive(Debug, Serialize)]
+pub(crate) struct StorageCellChange {
+    /// Hash of the block the cell changed in.
+    pub block: H256,
+    pub block_number: u32,
+    /// Hex encoded key of the cell in the contract's child trie.
+    pub key: String,
+    /// The name of the storage field the cell belongs to, if known from the metadata.
+    pub field: Option<String>,
+    /// The value before the block, decoded or else hex encoded, `None` if the cell was created.
+    pub old: Option<Value>,
+    /// The value after the block, decoded or else hex encoded, `None` if the cell was removed.
+    pub new: Option<Value>,
+}
+
+impl StorageCellChange {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let value = |value: &Option<Value>| match value {
+            Some(value) => value.to_string(),
+            None => "(none)".to_string(),
+        };
+        format!(
+            "#{} {:?}: {}: {} → {}",
+            self.block_number,
+            self.block,
+            self.field.as_ref().unwrap_or(&self.key),
+            value(&self.old),
+            value(&self.new)
+        )
+    }
+}
+
+/// The cells whose value differs between the `old` and the `new` storage, as pairs of the old
+/// and the new cell, `None` for a cell which was created or removed.
+fn changed_cells<'a>(
+    old: &'a BTreeMap<String, StorageCell>,
+    new: &'a BTreeMap<String, StorageCell>,
+) -> Vec<(Option<&'a StorageCell>, Option<&'a StorageCell>)> {
+    let removed = old
+        .iter()
+        .filter(|(key, _)| !new.contains_key(*key))
+        .map(|(_, cell)| (Some(cell), None));
+    let changed = new
+        .iter()
+        .filter(|(key, cell)| old.get(*key).map(|old| &old.data) != Some(&cell.data))
+        .map(|(key, cell)| (old.get(key), Some(cell)));
+    removed.chain(changed).collect()
+}
+
+/// Watches the cells of the contract's storage, restricted to those of a storage `field` if
+/// supplied, printing the old and the new value of each cell changed by a new best block until
+/// interrupted.
+///
+/// The node offers no subscription to the changes of child tries, so the cells are read again
+/// at each new best block and compared with those of the previous one.
+pub(crate) fn watch_storage(
+    submit_opts: &SubmitOpts,
+    contract: &Address,
+    field: Option<&str>,
+    metadata_path: Option<&Path>,
+) -> Result<String> {
+    let metadata =
+        InkMetadata::load(metadata_path)?.map(|metadata| metadata.for_output(submit_opts.output));
+    let cells = select_cells(metadata.as_ref(), field)?;
+
+    async_std::task::block_on(async move {
+        let connection = Connection::new(submit_opts).await?;
+        contract.check_format();
+
+        let account_id = &contract.account;
+        let types = metadata.as_ref().map(InkMetadata::types);
+        let index = index_cells(&cells);
+        let mut heads = connection.subscribe_heads(false).await?;
+        let mut previous: Option<BTreeMap<String, StorageCell>> = None;
+        log::info!("Watching the storage of contract {}", account_id);
+        loop {
+            let head = heads.next().await;
+            let block = head.hash();
+            let child_key = child_key(&connection, account_id, Some(block)).await?;
+            let storage = read_cells(
+                &connection,
+                &child_key,
+                &index,
+                types.as_ref(),
+                field.is_some(),
+                Some(block),
+            )
+            .await?;
+            let storage = storage
+                .into_iter()
+                .map(|cell| (cell.key.clone(), cell))
+                .collect::<BTreeMap<_, _>>();
+            let value = |cell: &StorageCell| {
+                cell.value
+                    .clone()
+                    .unwrap_or_else(|| Value::from(cell.data.clone()))
+            };
+            let changes = match previous {
+                Some(ref previous) => changed_cells(previous, &storage),
+                None => Vec::new(),
+            };
+            for (old, new) in changes {
+                let cell = new.or(old).expect("either cell exists");
+                let change = StorageCellChange {
+                    block,
+                    block_number: head.number,
+                    key: cell.key.clone(),
+                    field: cell.field.clone(),
+                    old: old.map(value),
+                    new: new.map(value),
+                };
+                match submit_opts.output {
+                    OutputType::HumanReadable => println!("{}", change.display()),
+                    OutputType::Json => println!("{}", serde_json::to_string(&change)?),
+                    OutputType::JsonPjs => {
+                        println!("{}", serde_json::to_string(&super::to_pjs_value(&change)?)?)
+                    }
+                }
+            }
+            previous = Some(storage);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn collects_fixed_cells_of_storage_layout() {
+        let layout = json!({ "struct": { "fields": [
+            { "name": "value", "layout": { "cell": { "key": "0x0100", "ty": 1 } } },
+            { "name": "owner", "layout": { "struct": { "fields": [
+                { "name": "name", "layout": { "cell": { "key": "0x0200", "ty": 2 } } }
+            ] } } },
+            { "name": "balances", "layout": { "hash": { "offset": "0x0300" } } }
+        ] } });
+        let mut cells = Vec::new();
+        layout_cells(&layout, "", &mut cells);
+
+        assert_eq!(
+            cells,
+            vec![
+                LayoutCell {
+                    field: "value".into(),
+                    key: vec![1, 0],
+                    ty: 1,
+                },
+                LayoutCell {
+                    field: "owner.name".into(),
+                    key: vec![2, 0],
+                    ty: 2,
+                },
+            ]
+        );
+        let trie_key = sp_core::blake2_256(&[2, 0]);
+        let index = index_cells(&cells);
+        assert_eq!(index.get(&trie_key[..]), Some(&&cells[1]));
+        assert_eq!(index.get(&[3, 0][..]), None);
+    }
+
+    #[test]
+    fn compares_the_cells_of_two_blocks() {
+        let storage = |cells: &[(&str, &str)]| {
+            cells
+                .iter()
+                .map(|(key, data)| {
+                    let cell = StorageCell {
+                        key: key.to_string(),
+                        field: None,
+                        value: None,
+                        data: data.to_string(),
+                    };
+                    (key.to_string(), cell)
+                })
+                .collect::<BTreeMap<_, _>>()
+        };
+        let old = storage(&[("0x01", "0x00"), ("0x02", "0x00"), ("0x03", "0x00")]);
+        let new = storage(&[("0x01", "0x00"), ("0x02", "0x01"), ("0x04", "0x02")]);
+        let changes = changed_cells(&old, &new)
+            .into_iter()
+            .map(|(old, new)| {
+                (
+                    old.map(|cell| cell.data.as_str()),
+                    new.map(|cell| cell.data.as_str()),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                (Some("0x00"), None),
+                (Some("0x00"), Some("0x01")),
+                (None, Some("0x02")),
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_the_cells_written_by_a_call() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            let metadata = json!({
+                "spec": { "messages": [] },
+                "storage": { "struct": { "fields": [
+                    { "name": "value", "layout": { "cell": {
+                        "key": format!("0x{}", "00".repeat(32)), "ty": 1
+                    } } }
+                ] } },
+                "types": [{ "def": { "primitive": "u32" } }]
+            });
+            let path = dir.join("metadata.json");
+            std::fs::write(&path, metadata.to_string())?;
+            let metadata = InkMetadata::read(&path)?;
+            let mut written = BTreeMap::new();
+            written.insert(vec![0; 32], Some(vec![7, 0, 0, 0]));
+            written.insert(vec![1; 32], Some(vec![1, 2]));
+            written.insert(vec![2; 32], None);
+
+            let writes = written_cells(&written, Some(&metadata))?;
+            assert_eq!(writes[0].display(), "value: 7");
+            assert_eq!(
+                writes[1].display(),
+                format!("0x{}: \"0x0102\"", "01".repeat(32))
+            );
+            assert_eq!(writes[2].display(), format!("0x{}: removed", "02".repeat(32)));
+            let undecoded = written_cells(&written, None)?;
+            assert_eq!(undecoded[0].value, Some(json!("0x07000000")));
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,45 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::Result;
+
+use super::{
+    events::ExtrinsicDetails,
+    extrinsic::{self, Connection},
+};
+use crate::{HexData, SubmitOpts};
+
+/// Submit an extrinsic which has already been signed, e.g. with `--offline`.
+/// Returns the details of the extrinsic, including the events it emitted if it was included in a
+/// block.
+///
+/// The extrinsic is submitted as is, so its events are decoded assuming it is a contracts call.
+pub(crate) fn execute_submit(
+    submit_opts: &SubmitOpts,
+    extrinsic: HexData,
+) -> Result<ExtrinsicDetails> {
+    async_std::task::block_on(async move {
+        let mut connection = Connection::new(submit_opts).await?;
+        let submitted = connection
+            .submit_encoded(
+                extrinsic.0,
+                submit_opts.wait_for,
+                extrinsic::contracts_events_decoder,
+            )
+            .await?;
+        ExtrinsicDetails::new(&submitted)
+    })
+}
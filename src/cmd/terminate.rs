@@ -0,0 +1,206 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::Path;
+
+use anyhow::Result;
+use codec::Decode;
+use serde::Serialize;
+use sp_core::crypto::AccountId32;
+use subxt::{contracts::CallCall, DefaultNodeRuntime, RawEvent};
+
+use super::{
+    address::Address,
+    call::DryRunRequest,
+    events::ExtrinsicDetails,
+    extrinsic::Connection,
+    gas::{GasLimit, GasMargin},
+    info::ContractInfoOfStore,
+    transcode::InkMetadata,
+};
+use crate::{ExtrinsicOpts, HexData};
+
+/// Result of a successful `terminate`.
+#[derive(Debug, Serialize)]
+pub(crate) struct TerminateResult {
+    pub contract: AccountId32,
+    /// The label of the message called to terminate the contract.
+    pub message: String,
+    /// Whether the contract was confirmed to be removed, which requires awaiting the inclusion
+    /// of the extrinsic.
+    pub confirmed: bool,
+    /// The account the remaining balance of the contract was transferred to.
+    pub beneficiary: Option<AccountId32>,
+    /// The balance transferred to the beneficiary.
+    pub refunded: Option<u128>,
+    #[serde(flatten)]
+    pub extrinsic: ExtrinsicDetails,
+}
+
+impl TerminateResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let mut output = if self.confirmed {
+            format!(
+                "Contract {} terminated by calling `{}`",
+                self.contract, self.message
+            )
+        } else {
+            format!(
+                "Called `{}` of contract {}, the termination is not confirmed without awaiting \
+                 the inclusion of the extrinsic",
+                self.message, self.contract
+            )
+        };
+        if let (Some(beneficiary), Some(refunded)) = (&self.beneficiary, self.refunded) {
+            output.push_str(&format!("\n\tRefunded {} to {}", refunded, beneficiary));
+        }
+        output.push_str(&format!(
+            "\n\tExtrinsic hash: {:?}",
+            self.extrinsic.extrinsic
+        ));
+        self.extrinsic.display_details(output)
+    }
+}
+
+/// Returns the beneficiary and the balance transferred from the contract by the
+/// `Balances::Transfer` events.
+fn refund(events: &[RawEvent], contract: &AccountId32) -> Result<Option<(AccountId32, u128)>> {
+    for event in events {
+        if event.module == "Balances" && event.variant == "Transfer" {
+            let (from, to, amount) =
+                <(AccountId32, AccountId32, u128)>::decode(&mut &event.data[..])?;
+            if from == *contract {
+                return Ok(Some((to, amount)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Terminates the contract by calling its terminating message, looked up by label in the
+/// metadata, and confirms that the contract was removed from the chain.
+///
+/// The SCALE encoded `args` are appended to the selector of the message.
+pub(crate) fn execute_terminate(
+    extrinsic_opts: &ExtrinsicOpts,
+    contract: &Address,
+    message: Option<&str>,
+    args: Option<&HexData>,
+    gas_limit: GasLimit,
+    gas_margin: GasMargin,
+    metadata_path: Option<&Path>,
+) -> Result<TerminateResult> {
+    let metadata = InkMetadata::load(metadata_path)?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "Terminating a contract requires its metadata to look up the terminating message, \
+             supply it with `--contract-metadata`, or call the message with `cargo contract call`"
+        )
+    })?;
+    let message = metadata.find_message(message, "terminate")?;
+    if !message.args.is_empty() && args.is_none() {
+        anyhow::bail!(
+            "The message `{}` takes {} arguments, supply them SCALE encoded with `--args`",
+            message.label,
+            message.args.len()
+        )
+    }
+    let mut data = message.selector.clone();
+    data.extend(args.map(|args| args.0.as_slice()).unwrap_or_default());
+
+    async_std::task::block_on(async move {
+        let mut connection = Connection::new(&extrinsic_opts.submit).await?;
+        contract.check_format();
+
+        let account_id = &contract.account;
+        if connection
+            .fetch(&ContractInfoOfStore { account_id })
+            .await?
+            .is_none()
+        {
+            anyhow::bail!("No contract found at {}", account_id)
+        }
+        let signer = extrinsic_opts.signer()?;
+        let origin = extrinsic_opts.origin(signer.account_id())?;
+        let request = DryRunRequest {
+            origin: &origin,
+            dest: account_id,
+            value: 0,
+            data: &data,
+            at: None,
+        };
+        let gas_limit = gas_limit
+            .resolve(&connection, gas_margin, request, &signer, extrinsic_opts)
+            .await?;
+        let dest = account_id.clone().into();
+        let call = CallCall::<DefaultNodeRuntime> {
+            dest: &dest,
+            value: 0,
+            gas_limit,
+            data: &data,
+        };
+        let submitted = connection.submit(call, &signer, extrinsic_opts).await?;
+
+        let (confirmed, refund) = match submitted.events() {
+            Some(result) => {
+                let removed = connection
+                    .fetch(&ContractInfoOfStore { account_id })
+                    .await?
+                    .is_none();
+                if !removed {
+                    anyhow::bail!(
+                        "The contract still exists after calling `{}`, which may have reverted \
+                         or not be permitted for the signer",
+                        message.label
+                    )
+                }
+                (true, refund(&result.events, account_id)?)
+            }
+            None => (false, None),
+        };
+        Ok(TerminateResult {
+            contract: account_id.clone(),
+            message: message.label,
+            confirmed,
+            beneficiary: refund.as_ref().map(|(beneficiary, _)| beneficiary.clone()),
+            refunded: refund.map(|(_, amount)| amount),
+            extrinsic: ExtrinsicDetails::new(&submitted)?,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+
+    #[test]
+    fn finds_the_refund_to_the_beneficiary() {
+        let contract = AccountId32::from([1; 32]);
+        let beneficiary = AccountId32::from([2; 32]);
+        let transfer = |from: &AccountId32, amount: u128| RawEvent {
+            module: "Balances".into(),
+            variant: "Transfer".into(),
+            data: (from, &beneficiary, amount).encode(),
+        };
+        let events = vec![transfer(&beneficiary, 5), transfer(&contract, 7)];
+        assert_eq!(
+            refund(&events, &contract).unwrap(),
+            Some((beneficiary.clone(), 7))
+        );
+        assert_eq!(refund(&events[..1], &contract).unwrap(), None);
+    }
+}
@@ -0,0 +1,206 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    convert::TryFrom,
+    net::{Ipv4Addr, TcpListener},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use structopt::StructOpt;
+
+use super::{
+    coverage::{self, Coverage, CoverageFormat},
+    node,
+};
+use crate::{crate_metadata::CrateMetadata, workspace::ManifestPath, CargoOptions};
+
+/// The feature of ink! contracts running their off-chain tests on the experimental engine.
+const EXPERIMENTAL_ENGINE_FEATURE: &str = "ink-experimental-engine";
+
+/// The feature of ink! contracts enabling their end-to-end tests.
+const E2E_FEATURE: &str = "e2e-tests";
+
+/// Runs the off-chain tests of the contract, and optionally its end-to-end tests against a
+/// contracts node started for them.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "test")]
+pub(crate) struct TestCommand {
+    /// Path to the Cargo.toml of the contract to test
+    #[structopt(long, parse(from_os_str))]
+    manifest_path: Option<PathBuf>,
+    /// Run the end-to-end tests as well, enabling the `e2e-tests` feature, against a node started
+    /// with `--dev --tmp`. Its websocket URL is passed in `CONTRACTS_NODE_URL`
+    #[structopt(long)]
+    e2e: bool,
+    /// The contracts node binary of the end-to-end tests, by default the
+    /// `substrate-contracts-node` on the `PATH`, else the release `cargo contract node` downloads
+    #[structopt(long, parse(from_os_str))]
+    node: Option<PathBuf>,
+    /// Collect the source-based coverage of the tests, and write an `lcov` report to
+    /// `target/ink/coverage/lcov.info` or an `html` one to `target/ink/coverage/html`. The LLVM
+    /// tools come from `rustup component add llvm-tools-preview`
+    #[structopt(long, value_name = "lcov | html")]
+    coverage: Option<CoverageFormat>,
+    #[structopt(flatten)]
+    cargo_options: CargoOptions,
+    /// Arguments passed on to the test binaries, e.g. a filter of the test names
+    #[structopt(last = true)]
+    test_args: Vec<String>,
+}
+
+impl TestCommand {
+    pub fn exec(&self) -> Result<String> {
+        let manifest_path = ManifestPath::try_from(self.manifest_path.as_ref())?;
+        let crate_metadata = CrateMetadata::collect(&manifest_path)?;
+        let features = self.test_features(crate_metadata.root_package.features.keys());
+        let coverage = self
+            .coverage
+            .map(|format| Coverage::new(&crate_metadata.target_directory, format))
+            .transpose()?;
+        let mut cmd = cargo_test(&manifest_path, &self.cargo_options, features.clone());
+        if let Some(ref coverage) = coverage {
+            coverage.instrument(&mut cmd);
+        }
+        cmd.arg("--").args(&self.test_args);
+
+        let _node = if self.e2e {
+            let (node, url) = Node::start(&node::find(self.node.as_deref())?)?;
+            cmd.env("CONTRACTS_NODE_URL", url);
+            Some(node)
+        } else {
+            None
+        };
+        let status = cmd.status().context("Failed to execute `cargo test`")?;
+        if !status.success() {
+            anyhow::bail!("The tests of the contract failed with {}", status)
+        }
+        let coverage = match coverage {
+            Some(coverage) => coverage,
+            None => return Ok("All tests of the contract passed".to_string()),
+        };
+        // Listing the test binaries only reuses the build of the tests, so it is cheap.
+        let mut list = cargo_test(&manifest_path, &self.cargo_options, features);
+        coverage.instrument(&mut list);
+        let output = list
+            .args(["--no-run", "--message-format=json"])
+            .stderr(Stdio::inherit())
+            .output()
+            .context("Failed to list the test binaries")?;
+        let binaries = coverage::test_binaries(&String::from_utf8_lossy(&output.stdout));
+        let report = coverage.report(&binaries)?;
+        Ok(format!(
+            "All tests of the contract passed\n\tCoverage report: {}",
+            report.display()
+        ))
+    }
+
+    /// The features enabling the tests, among the `available` ones of the contract.
+    fn test_features<'a>(&self, available: impl Iterator<Item = &'a String>) -> Vec<String> {
+        available
+            .filter(|feature| {
+                *feature == EXPERIMENTAL_ENGINE_FEATURE || (self.e2e && *feature == E2E_FEATURE)
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// The `cargo test` invocation of the contract, with the default `std` features and the
+/// `features` enabling its tests in addition to the supplied cargo options.
+///
+/// The arguments of the test binaries follow a `--` yet to be added.
+fn cargo_test(
+    manifest_path: &ManifestPath,
+    cargo_options: &CargoOptions,
+    features: Vec<String>,
+) -> Command {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut cmd = Command::new(cargo);
+    cmd.arg("test")
+        .arg(manifest_path.cargo_arg())
+        .args(cargo_options.cargo_args());
+    if !features.is_empty() {
+        cmd.arg("--features").arg(features.join(","));
+    }
+    cmd
+}
+
+/// A contracts node running for the end-to-end tests, killed when dropped.
+struct Node(Child);
+
+impl Node {
+    /// Starts the node on a free port and waits until it accepts connections, returning its
+    /// websocket URL.
+    fn start(binary: &Path) -> Result<(Self, String)> {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?
+            .local_addr()?
+            .port();
+        println!(
+            " {} {} on port {}",
+            "Starting".bright_green().bold(),
+            binary.display(),
+            port
+        );
+        let child = Command::new(binary)
+            .args(["--dev", "--tmp", "--ws-port", &port.to_string()])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .context(format!(
+                "Failed to start the contracts node {}, pass its path with --node",
+                binary.display()
+            ))?;
+        let mut node = Node(child);
+        node::wait_until_listening(&mut node.0, port)?;
+        Ok((node, format!("ws://127.0.0.1:{}", port)))
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enables_the_test_features_of_the_contract() {
+        let available = [
+            "std".to_string(),
+            EXPERIMENTAL_ENGINE_FEATURE.to_string(),
+            E2E_FEATURE.to_string(),
+        ];
+        let test = TestCommand::from_iter(&["test"]);
+        assert_eq!(
+            test.test_features(available.iter()),
+            [EXPERIMENTAL_ENGINE_FEATURE]
+        );
+        let e2e = TestCommand::from_iter(&["test", "--e2e", "--", "flip"]);
+        assert_eq!(
+            e2e.test_features(available.iter()),
+            [EXPERIMENTAL_ENGINE_FEATURE, E2E_FEATURE]
+        );
+        assert_eq!(e2e.test_args, ["flip"]);
+    }
+}
@@ -0,0 +1,1284 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::HashMap,
+    fmt, fs,
+    path::Path,
+    rc::Rc,
+};
+
+use anyhow::{Context, Result};
+use codec::{Compact, Decode, Encode};
+use serde::de::{DeserializeSeed, IgnoredAny, MapAccess, Visitor};
+use serde_json::{Map, Value};
+use sp_core::{
+    crypto::{AccountId32, Ss58Codec},
+    U256,
+};
+
+use super::{
+    bindgen::camel_case, config::Config, custom_types::CustomTypes, metadata_dialect::Dialect,
+};
+use crate::{crate_metadata::CrateMetadata, OutputType, TranscodeError};
+
+/// The file the metadata of a contract is written to by `build`.
+pub(crate) const METADATA_FILE: &str = "metadata.json";
+
+/// The ink! metadata of a contract, used to decode the values of its storage and events, and to
+/// look up its messages.
+///
+/// Only the spec and the storage layout are parsed up front. The registry of the types, by far
+/// the largest section of big contracts, is parsed when a value is first decoded or encoded, and
+/// the other sections, e.g. the Wasm in the `source` of a bundle, are skipped.
+pub(crate) struct InkMetadata {
+    json: String,
+    abi: Map<String, Value>,
+    types: OnceCell<Vec<Value>>,
+    /// The types resolved so far, shared by all registries of the metadata.
+    resolved: Rc<TypeCache>,
+    /// The types registered in the configuration, taking precedence over those of the metadata.
+    custom: CustomTypes,
+    /// Whether values are decoded the way polkadot.js does.
+    polkadot_js: bool,
+}
+
+impl InkMetadata {
+    /// Reads the metadata from either a `metadata.json` or a `.contract` bundle.
+    pub fn read(path: &Path) -> Result<Self> {
+        let json =
+            fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+        Self::parse(json).context(format!("Failed to parse metadata {}", path.display()))
+    }
+
+    /// Parses the spec and the storage layout of the metadata, normalizing the metadata of
+    /// contracts compiled by Solang or ask! into the shape written by ink!.
+    fn parse(json: String) -> Result<Self> {
+        let mut abi = sections(&json, &["spec", "storage", "source"])?;
+        let dialect = Dialect::of(abi.remove("source").as_ref());
+        if dialect != Dialect::Ink {
+            log::debug!("Normalizing the metadata of a contract compiled by {}", dialect);
+            dialect.normalize(&mut abi);
+        }
+        Ok(InkMetadata {
+            json,
+            abi,
+            types: OnceCell::new(),
+            resolved: Rc::default(),
+            custom: CustomTypes::default(),
+            polkadot_js: false,
+        })
+    }
+
+    /// Reads the metadata from `path` or, if none is supplied, the metadata built for the
+    /// contract project in the current directory. Returns `None` if there is no such metadata.
+    ///
+    /// The types registered in the `[types]` of the configuration are used to decode the values
+    /// of the types with the same path.
+    pub fn load(path: Option<&Path>) -> Result<Option<Self>> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => {
+                let path = match CrateMetadata::collect(&Default::default()) {
+                    Ok(crate_metadata) => crate_metadata.target_directory.join(METADATA_FILE),
+                    Err(_) => return Ok(None),
+                };
+                if !path.exists() {
+                    log::warn!(
+                        "No metadata found at {}, values are printed undecoded",
+                        path.display()
+                    );
+                    return Ok(None);
+                }
+                path
+            }
+        };
+        let mut metadata = Self::read(&path)?;
+        metadata.custom = Config::load()?.custom_types()?;
+        Ok(Some(metadata))
+    }
+
+    /// Decodes the values the way polkadot.js does for `--output json-pjs`: with `camelCase`
+    /// field names, accounts as SS58 addresses, and options as `null` or their value.
+    pub fn for_output(mut self, output: OutputType) -> Self {
+        self.polkadot_js = output == OutputType::JsonPjs;
+        self
+    }
+
+    /// The layout of the contract's storage.
+    pub fn storage(&self) -> Option<&Value> {
+        self.abi.get("storage")
+    }
+
+    /// Decodes an event emitted by the contract, returning its name and arguments.
+    pub fn decode_event(&self, data: &[u8]) -> Result<(String, Value)> {
+        let span = tracing::info_span!("decode event");
+        let _enter = span.enter();
+        let (index, mut input) = data
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("Empty event data"))?;
+        let event = self.abi["spec"]["events"]
+            .get(*index as usize)
+            .ok_or_else(|| anyhow::anyhow!("No event with index {} in the metadata", index))?;
+        let types = self.types();
+        let mut args = Map::new();
+        for arg in event["args"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+        {
+            let value = types.decode(type_id(&arg["type"])?, &mut input);
+            args.insert(label(arg), value.map_err(|err| at(err, &label(arg)))?);
+        }
+        if !input.is_empty() {
+            anyhow::bail!("{} bytes left after decoding the event", input.len())
+        }
+        Ok((label(event), Value::Object(args)))
+    }
+
+    /// The labels of the arguments of the event which the metadata declares as the `Balance` of
+    /// the contract's environment.
+    pub fn event_balance_args(&self, data: &[u8]) -> Vec<String> {
+        let event = data
+            .first()
+            .and_then(|index| self.abi["spec"]["events"].get(*index as usize));
+        let args = event
+            .and_then(|event| event["args"].as_array())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        args.iter()
+            .filter(|arg| {
+                let display_name = arg["type"]["displayName"].as_array();
+                display_name.and_then(|name| name.last()) == Some(&Value::from("Balance"))
+            })
+            .map(label)
+            .collect()
+    }
+
+    /// The messages of the contract.
+    pub fn messages(&self) -> Result<Vec<Message>> {
+        self.functions("messages")
+    }
+
+    /// The constructors of the contract.
+    pub fn constructors(&self) -> Result<Vec<Message>> {
+        self.functions("constructors")
+    }
+
+    /// The constructors or messages of the `section` of the spec.
+    fn functions(&self, section: &str) -> Result<Vec<Message>> {
+        let messages = self.abi["spec"][section]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        messages
+            .iter()
+            .map(|message| {
+                // The earliest metadata versions name a message by its path, later ones by a label.
+                let label = match message.get("label").and_then(Value::as_str) {
+                    Some(label) => label.to_string(),
+                    None => message["name"]
+                        .as_array()
+                        .map(Vec::as_slice)
+                        .unwrap_or_default()
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .collect::<Vec<_>>()
+                        .join("::"),
+                };
+                let selector = message["selector"]
+                    .as_str()
+                    .and_then(|selector| hex::decode(selector.trim_start_matches("0x")).ok())
+                    .ok_or_else(|| anyhow::anyhow!("Invalid selector of message {}", label))?;
+                let args = message["args"]
+                    .as_array()
+                    .map(Vec::as_slice)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|arg| type_id(&arg["type"]))
+                    .collect::<Result<_>>()?;
+                Ok(Message {
+                    label,
+                    selector,
+                    args,
+                    returns: message["returnType"]["type"].as_u64(),
+                    payable: message["payable"].as_bool().unwrap_or(true),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the message with the `label`, or else the only one with `default` in its label.
+    ///
+    /// Messages of an ink! trait definition are labeled with the trait, e.g. `Erc20::transfer`,
+    /// and are also found by the method alone, unless several traits define it.
+    pub fn find_message(&self, label: Option<&str>, default: &str) -> Result<Message> {
+        let messages = self.messages()?;
+        let labels = messages
+            .iter()
+            .map(|message| message.label.clone())
+            .collect::<Vec<_>>();
+        let exact = label.filter(|label| labels.iter().any(|other| other == label));
+        let mut candidates = messages
+            .into_iter()
+            .filter(|message| match (exact, label) {
+                (Some(label), _) => message.label == label,
+                (None, Some(label)) => message.label.rsplit("::").next() == Some(label),
+                (None, None) => message.label.to_lowercase().contains(default),
+            })
+            .collect::<Vec<_>>();
+        match (candidates.len(), label) {
+            (1, _) => Ok(candidates.remove(0)),
+            (0, Some(label)) => anyhow::bail!(
+                "No message {} in the metadata, expected one of {}",
+                label,
+                labels.join(", ")
+            ),
+            (0, None) => anyhow::bail!(
+                "The contract has no message with `{}` in its label, select the message with \
+                 `--message`, one of {}",
+                default,
+                labels.join(", ")
+            ),
+            (_, Some(label)) => anyhow::bail!(
+                "Several traits define the message {}, qualify it with the trait: {}",
+                label,
+                candidates
+                    .iter()
+                    .map(|message| message.label.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            _ => anyhow::bail!(
+                "Several messages have `{}` in their label, select one with `--message`: {}",
+                default,
+                candidates
+                    .iter()
+                    .map(|message| message.label.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    /// Returns the constructor with the `label`, or else the only one, or else the one labeled
+    /// `new`.
+    pub fn find_constructor(&self, label: Option<&str>) -> Result<Message> {
+        let mut constructors = self.constructors()?;
+        let labels = constructors
+            .iter()
+            .map(|constructor| constructor.label.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let position = match label {
+            Some(label) => constructors
+                .iter()
+                .position(|constructor| constructor.label == label),
+            None if constructors.len() == 1 => Some(0),
+            None => constructors
+                .iter()
+                .position(|constructor| constructor.label == "new"),
+        };
+        match (position, label) {
+            (Some(position), _) => Ok(constructors.swap_remove(position)),
+            (None, Some(label)) => anyhow::bail!(
+                "No constructor {} in the metadata, expected one of {}",
+                label,
+                labels
+            ),
+            (None, None) if constructors.is_empty() => {
+                anyhow::bail!("The metadata has no constructors")
+            }
+            (None, None) => anyhow::bail!(
+                "The contract has several constructors, select one with `--constructor`: {}",
+                labels
+            ),
+        }
+    }
+
+    /// The input calling the constructor or message with the `args`, which are JSON values or
+    /// else plain strings, e.g. `42`, `true`, `[1, 2]` or an SS58 address.
+    pub fn encode_call(&self, function: &Message, args: &[String]) -> Result<Vec<u8>> {
+        let span = tracing::info_span!("encode", function = %function.label);
+        let _enter = span.enter();
+        if args.len() != function.args.len() {
+            return Err(TranscodeError::new(format!(
+                "`{}` takes {} arguments, not {}",
+                function.label,
+                function.args.len(),
+                args.len()
+            ))
+            .into());
+        }
+        let types = self.types();
+        let mut input = function.selector.clone();
+        for (ty, arg) in function.args.iter().zip(args) {
+            let value = serde_json::from_str(arg).unwrap_or_else(|_| Value::from(arg.as_str()));
+            types
+                .encode(*ty, &value, &mut input)
+                .context(format!("Invalid argument {} of `{}`", arg, function.label))?;
+        }
+        Ok(input)
+    }
+
+    /// The registry of the types referenced by the metadata.
+    pub fn types(&self) -> TypeRegistry<'_> {
+        let types = self.types.get_or_init(|| {
+            // The JSON was parsed successfully already.
+            let mut types = sections(&self.json, &["types"]).unwrap_or_default();
+            match types.remove("types") {
+                Some(Value::Array(types)) => types,
+                _ => Vec::new(),
+            }
+        });
+        TypeRegistry {
+            types,
+            custom: &self.custom,
+            polkadot_js: self.polkadot_js,
+            resolved: self.resolved.clone(),
+        }
+    }
+}
+
+/// Parses the `wanted` sections of the metadata, skipping the others without building their
+/// values.
+fn sections(json: &str, wanted: &[&str]) -> Result<Map<String, Value>> {
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let sections = Sections { wanted }.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(sections)
+}
+
+/// Collects the wanted sections of the abi, which later metadata versions nest in an object
+/// named after the version, e.g. `V3`.
+///
+/// Of the `source`, only the `language` and `compiler` are collected, skipping the Wasm of a
+/// bundle.
+struct Sections<'a> {
+    wanted: &'a [&'a str],
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for Sections<'a> {
+    type Value = Map<String, Value>;
+
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for Sections<'a> {
+    type Value = Map<String, Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the metadata of a contract")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut sections = Map::new();
+        let mut versioned = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "source" && self.wanted.contains(&"source") {
+                let source = map.next_value::<Source>()?;
+                let source = serde_json::json!({
+                    "language": source.language,
+                    "compiler": source.compiler,
+                });
+                sections.insert(key, source);
+            } else if self.wanted.contains(&key.as_str()) {
+                sections.insert(key, map.next_value()?);
+            } else if is_version(&key) {
+                let wanted = self.wanted;
+                versioned = Some(map.next_value_seed(Sections { wanted })?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        match versioned {
+            Some(mut versioned) => {
+                // The versioned metadata keeps the `source` at the top level.
+                if let Some(source) = sections.remove("source") {
+                    versioned.insert("source".into(), source);
+                }
+                Ok(versioned)
+            }
+            None => Ok(sections),
+        }
+    }
+}
+
+/// The compiler of a contract, as recorded in the `source` of its metadata.
+#[derive(serde::Deserialize)]
+struct Source {
+    language: Option<String>,
+    compiler: Option<String>,
+}
+
+/// Whether the key names a version of the metadata, e.g. `V3`.
+fn is_version(key: &str) -> bool {
+    match key.strip_prefix('V') {
+        Some(version) => !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// A constructor or message of a contract, as described by its metadata.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Message {
+    pub label: String,
+    pub selector: Vec<u8>,
+    /// The types of the arguments.
+    pub args: Vec<u64>,
+    /// The return type, `None` for constructors and messages returning `()`.
+    pub returns: Option<u64>,
+    /// Whether it accepts value transferred with it, which metadata versions not recording it
+    /// are assumed to.
+    pub payable: bool,
+}
+
+/// A type of the registry, resolved when it is first encountered.
+#[derive(Clone)]
+struct Resolved {
+    /// The position of the type in the registry.
+    index: usize,
+    /// The segments of the path of the type joined by `::`, e.g. `ink_env::types::AccountId`.
+    path: Option<Rc<str>>,
+}
+
+/// The types resolved so far by their id, `None` for unknown ones.
+///
+/// Shared by the registries of a metadata, so decoding a storage dump or a stream of events
+/// looks up each type in the registry once rather than for every value.
+#[derive(Default)]
+pub(crate) struct TypeCache(RefCell<HashMap<u64, Option<Resolved>>>);
+
+/// The types of a contract's metadata, for decoding SCALE encoded values into JSON.
+pub(crate) struct TypeRegistry<'a> {
+    types: &'a [Value],
+    custom: &'a CustomTypes,
+    polkadot_js: bool,
+    resolved: Rc<TypeCache>,
+}
+
+impl<'a> TypeRegistry<'a> {
+    /// Resolves the type, which the earliest metadata versions reference by their position
+    /// starting at 1, the later ones by their `id`.
+    fn resolve(&self, id: u64) -> Option<Resolved> {
+        if let Some(resolved) = self.resolved.0.borrow().get(&id) {
+            return resolved.clone();
+        }
+        let index = match self.types.first() {
+            // The ids are usually the positions, so the registry is only searched otherwise.
+            Some(first) if first.get("id").is_some() => self
+                .types
+                .get(id as usize)
+                .filter(|ty| ty["id"].as_u64() == Some(id))
+                .map(|_| id as usize)
+                .or_else(|| {
+                    self.types
+                        .iter()
+                        .position(|ty| ty["id"].as_u64() == Some(id))
+                }),
+            _ => id
+                .checked_sub(1)
+                .map(|index| index as usize)
+                .filter(|index| *index < self.types.len()),
+        };
+        let resolved = index.map(|index| {
+            let path = self.entry(index)["path"].as_array().map(|path| {
+                let segments = path.iter().filter_map(Value::as_str);
+                Rc::from(segments.collect::<Vec<_>>().join("::"))
+            });
+            Resolved { index, path }
+        });
+        self.resolved.0.borrow_mut().insert(id, resolved.clone());
+        resolved
+    }
+
+    /// The type at the position in the registry, unwrapped from its `id` if it has one.
+    fn entry(&self, index: usize) -> &'a Value {
+        let ty = &self.types[index];
+        match ty.get("id") {
+            Some(_) => &ty["type"],
+            None => ty,
+        }
+    }
+
+    /// Returns the type.
+    fn get(&self, id: u64) -> Option<&'a Value> {
+        self.resolve(id).map(|resolved| self.entry(resolved.index))
+    }
+
+    /// Returns the last segment of the path of the type, e.g. `AccountId` or `Option`.
+    pub fn name(&self, id: u64) -> Option<&'a str> {
+        let path = self.get(id).and_then(|ty| ty["path"].as_array());
+        path.and_then(|path| path.last()).and_then(Value::as_str)
+    }
+
+    /// Returns the definition of the type.
+    pub fn definition(&self, id: u64) -> Result<&'a Value> {
+        self.get(id)
+            .map(|ty| &ty["def"])
+            .filter(|def| def.is_object())
+            .ok_or_else(|| anyhow::anyhow!("Unknown type {}", id))
+    }
+
+    /// Decodes a value of the type, which has to use up all of the `data`.
+    pub fn decode_all(&self, ty: u64, data: &[u8]) -> Result<Value> {
+        let mut input = data;
+        let value = self
+            .decode(ty, &mut input)
+            .map_err(|err| anyhow::Error::new(transcode_error(err)))?;
+        if !input.is_empty() {
+            let error = format!("{} bytes left after decoding", input.len());
+            return Err(TranscodeError::new(error).into());
+        }
+        Ok(value)
+    }
+
+    /// Decodes a value of the type from the input.
+    pub fn decode(&self, ty: u64, input: &mut &[u8]) -> Result<Value> {
+        let path = self.resolve(ty).and_then(|resolved| resolved.path);
+        if let Some(custom) = path.as_deref().and_then(|path| self.custom.get(path)) {
+            return self.custom.decode(custom, input);
+        }
+        if self.polkadot_js {
+            match path.as_deref().and_then(|path| path.rsplit("::").next()) {
+                Some("AccountId") => {
+                    let account = AccountId32::from(<[u8; 32]>::decode(input)?);
+                    return Ok(Value::from(account.to_ss58check()));
+                }
+                Some("Option") => {
+                    // `Some` is converted to `some` like any variant.
+                    return match self.decode_def(ty, input)? {
+                        Value::Object(mut some) => Ok(some.remove("some").unwrap_or_default()),
+                        _ => Ok(Value::Null),
+                    };
+                }
+                _ => (),
+            }
+        }
+        self.decode_def(ty, input)
+    }
+
+    /// Decodes a value of the type by its definition.
+    fn decode_def(&self, ty: u64, input: &mut &[u8]) -> Result<Value> {
+        let def = self.definition(ty)?;
+        if let Some(primitive) = def.get("primitive").and_then(Value::as_str) {
+            return decode_primitive(primitive, input);
+        }
+        if let Some(composite) = def.get("composite") {
+            return self.decode_fields(&composite["fields"], input);
+        }
+        if let Some(variant) = def.get("variant") {
+            let variants = variant["variants"].as_array().map(Vec::as_slice);
+            let index = u8::decode(input)?;
+            let (position, variant) = variants
+                .unwrap_or_default()
+                .iter()
+                .enumerate()
+                .find(|(position, variant)| {
+                    let discriminant = variant
+                        .get("index")
+                        .or_else(|| variant.get("discriminant"))
+                        .and_then(Value::as_u64);
+                    discriminant.unwrap_or(*position as u64) == u64::from(index)
+                })
+                .ok_or_else(|| anyhow::anyhow!("Invalid variant index {}", index))?;
+            let name = variant["name"]
+                .as_str()
+                .map(String::from)
+                .unwrap_or_else(|| position.to_string());
+            return match variant.get("fields") {
+                Some(Value::Array(fields)) if !fields.is_empty() => {
+                    let mut object = Map::new();
+                    let fields = self.decode_fields(&variant["fields"], input);
+                    let fields = fields.map_err(|err| at(err, &name))?;
+                    object.insert(self.key(&name), fields);
+                    Ok(Value::Object(object))
+                }
+                _ => Ok(Value::String(name)),
+            };
+        }
+        if let Some(sequence) = def.get("sequence") {
+            let Compact(len) = Compact::<u32>::decode(input)?;
+            return self.decode_elements(type_id(sequence)?, len as usize, input);
+        }
+        if let Some(array) = def.get("array") {
+            let len = array["len"]
+                .as_u64()
+                .ok_or_else(|| anyhow::anyhow!("Array without length"))?;
+            return self.decode_elements(type_id(array)?, len as usize, input);
+        }
+        if let Some(tuple) = def.get("tuple").and_then(Value::as_array) {
+            let values = tuple
+                .iter()
+                .enumerate()
+                .map(|(index, ty)| {
+                    let ty = ty.as_u64().ok_or_else(|| anyhow::anyhow!("Invalid type"))?;
+                    self.decode(ty, input).map_err(|err| at_index(err, index))
+                })
+                .collect::<Result<_>>()?;
+            return Ok(Value::Array(values));
+        }
+        if let Some(compact) = def.get("compact") {
+            let inner = self.definition(type_id(compact)?)?;
+            let Compact(value) = Compact::<u128>::decode(input)?;
+            return match inner.get("primitive").and_then(Value::as_str) {
+                Some("u128") => Ok(Value::String(value.to_string())),
+                _ => Ok(Value::from(value as u64)),
+            };
+        }
+        anyhow::bail!("Unsupported type definition {}", def)
+    }
+
+    /// Decodes the fields of a composite or variant, into an object for named fields and the
+    /// value itself for a single unnamed one.
+    fn decode_fields(&self, fields: &Value, input: &mut &[u8]) -> Result<Value> {
+        let fields = fields.as_array().map(Vec::as_slice).unwrap_or_default();
+        let named = fields.iter().all(|field| field["name"].is_string());
+        let mut values = Vec::new();
+        for (index, field) in fields.iter().enumerate() {
+            let value = self.decode(type_id(field)?, input);
+            values.push(value.map_err(|err| match field["name"].as_str() {
+                Some(name) => at(err, name),
+                None => at_index(err, index),
+            })?);
+        }
+        if named && !fields.is_empty() {
+            let names = fields
+                .iter()
+                .map(|field| self.key(field["name"].as_str().unwrap_or_default()));
+            Ok(Value::Object(names.zip(values).collect::<Map<_, _>>()))
+        } else if values.len() == 1 {
+            Ok(values.remove(0))
+        } else {
+            Ok(Value::Array(values))
+        }
+    }
+
+    /// The key of a field or variant, which polkadot.js converts to `camelCase`.
+    fn key(&self, name: &str) -> String {
+        match self.polkadot_js {
+            true => camel_case(name),
+            false => name.to_string(),
+        }
+    }
+
+    /// Encodes the JSON value as a value of the type, accepting the values it is decoded to.
+    ///
+    /// Newtypes also accept the value of their field, options `null` or the value of `Some`,
+    /// accounts SS58 addresses, and integers strings.
+    pub fn encode(&self, ty: u64, value: &Value, output: &mut Vec<u8>) -> Result<()> {
+        self.encode_value(ty, value, output)
+            .map_err(|err| transcode_error(err).into())
+    }
+
+    fn encode_value(&self, ty: u64, value: &Value, output: &mut Vec<u8>) -> Result<()> {
+        match (self.name(ty), value) {
+            (Some("AccountId"), Value::String(address)) if !address.starts_with("0x") => {
+                let account = AccountId32::from_ss58check(address)
+                    .map_err(|err| anyhow::anyhow!("Invalid address {}: {:?}", address, err))?;
+                output.extend_from_slice(account.as_ref());
+                return Ok(());
+            }
+            (Some("Option"), Value::Null) => {
+                output.push(0);
+                return Ok(());
+            }
+            (Some("Option"), value) if value.get("Some").is_none() && value != "None" => {
+                output.push(1);
+                let some = &self.definition(ty)?["variant"]["variants"][1]["fields"][0];
+                return self.encode_value(type_id(some)?, value, output);
+            }
+            _ => (),
+        }
+        let def = self.definition(ty)?;
+        if let Some(primitive) = def.get("primitive").and_then(Value::as_str) {
+            return encode_primitive(primitive, value, output);
+        }
+        if let Some(composite) = def.get("composite") {
+            return self.encode_fields(&composite["fields"], value, output);
+        }
+        if let Some(variant) = def.get("variant") {
+            let (name, fields) = match value {
+                Value::String(name) => (name.as_str(), &Value::Null),
+                Value::Object(object) if object.len() == 1 => {
+                    let (name, fields) = object.iter().next().expect("the object has one entry");
+                    (name.as_str(), fields)
+                }
+                _ => anyhow::bail!("Expected a variant name or a `{{\"Variant\": fields}}` object"),
+            };
+            let variants = variant["variants"].as_array().map(Vec::as_slice);
+            let (position, variant) = variants
+                .unwrap_or_default()
+                .iter()
+                .enumerate()
+                .find(|(_, variant)| {
+                    variant["name"].as_str().map(|variant| self.key(variant))
+                        == Some(self.key(name))
+                })
+                .ok_or_else(|| anyhow::anyhow!("Unknown variant {}", name))?;
+            let index = variant
+                .get("index")
+                .or_else(|| variant.get("discriminant"))
+                .and_then(Value::as_u64)
+                .unwrap_or(position as u64);
+            output.push(index as u8);
+            return self
+                .encode_fields(&variant["fields"], fields, output)
+                .map_err(|err| at(err, name));
+        }
+        if let Some(sequence) = def.get("sequence") {
+            let elements = self.elements(type_id(sequence)?, value)?;
+            Compact(elements.len() as u32).encode_to(output);
+            return self.encode_elements(type_id(sequence)?, &elements, output);
+        }
+        if let Some(array) = def.get("array") {
+            let elements = self.elements(type_id(array)?, value)?;
+            if Some(elements.len() as u64) != array["len"].as_u64() {
+                anyhow::bail!("Expected {} elements, not {}", array["len"], elements.len())
+            }
+            return self.encode_elements(type_id(array)?, &elements, output);
+        }
+        if let Some(tuple) = def.get("tuple").and_then(Value::as_array) {
+            let elements = match value {
+                Value::Array(elements) if elements.len() == tuple.len() => elements.as_slice(),
+                Value::Null if tuple.is_empty() => &[],
+                _ => anyhow::bail!("Expected an array of {} elements", tuple.len()),
+            };
+            for (index, (ty, element)) in tuple.iter().zip(elements).enumerate() {
+                let ty = ty.as_u64().ok_or_else(|| anyhow::anyhow!("Invalid type"))?;
+                self.encode_value(ty, element, output)
+                    .map_err(|err| at_index(err, index))?;
+            }
+            return Ok(());
+        }
+        if def.get("compact").is_some() {
+            Compact(integer::<u128>(value)?).encode_to(output);
+            return Ok(());
+        }
+        anyhow::bail!("Unsupported type definition {}", def)
+    }
+
+    /// Encodes the fields of a composite or variant from an object for named fields, an array,
+    /// or the value itself for a single field.
+    fn encode_fields(&self, fields: &Value, value: &Value, output: &mut Vec<u8>) -> Result<()> {
+        let fields = fields.as_array().map(Vec::as_slice).unwrap_or_default();
+        match (fields, value) {
+            ([], Value::Null) => Ok(()),
+            ([field], value)
+                if value
+                    .get(field["name"].as_str().unwrap_or_default())
+                    .is_none() =>
+            {
+                self.encode_value(type_id(field)?, value, output)
+            }
+            (fields, Value::Object(object)) => {
+                for field in fields {
+                    let name = field["name"].as_str().unwrap_or_default();
+                    let value = object
+                        .get(name)
+                        .or_else(|| object.get(&camel_case(name)))
+                        .ok_or_else(|| anyhow::anyhow!("Missing field {}", name))?;
+                    self.encode_value(type_id(field)?, value, output)
+                        .map_err(|err| at(err, name))?;
+                }
+                Ok(())
+            }
+            (fields, Value::Array(values)) if fields.len() == values.len() => {
+                for (index, (field, value)) in fields.iter().zip(values).enumerate() {
+                    self.encode_value(type_id(field)?, value, output)
+                        .map_err(|err| at_index(err, index))?;
+                }
+                Ok(())
+            }
+            _ => anyhow::bail!("Expected {} fields, not {}", fields.len(), value),
+        }
+    }
+
+    /// The elements of a sequence or array, which for bytes may also be a hex string.
+    fn elements(&self, ty: u64, value: &Value) -> Result<Vec<Value>> {
+        match value {
+            Value::Array(elements) => Ok(elements.clone()),
+            Value::String(bytes) if self.definition(ty)?["primitive"] == "u8" => {
+                let bytes = hex::decode(bytes.trim_start_matches("0x"))?;
+                Ok(bytes.into_iter().map(Value::from).collect())
+            }
+            _ => anyhow::bail!("Expected an array, not {}", value),
+        }
+    }
+
+    fn encode_elements(&self, ty: u64, elements: &[Value], output: &mut Vec<u8>) -> Result<()> {
+        for (index, element) in elements.iter().enumerate() {
+            self.encode_value(ty, element, output)
+                .map_err(|err| at_index(err, index))?;
+        }
+        Ok(())
+    }
+
+    /// Decodes the elements of a sequence or array. Bytes are combined into a hex string.
+    fn decode_elements(&self, ty: u64, len: usize, input: &mut &[u8]) -> Result<Value> {
+        if self
+            .definition(ty)?
+            .get("primitive")
+            .and_then(Value::as_str)
+            == Some("u8")
+        {
+            if input.len() < len {
+                anyhow::bail!("Not enough data for {} bytes", len)
+            }
+            let (bytes, rest) = input.split_at(len);
+            *input = rest;
+            return Ok(Value::String(format!("0x{}", hex::encode(bytes))));
+        }
+        let elements = (0..len)
+            .map(|index| self.decode(ty, input).map_err(|err| at_index(err, index)))
+            .collect::<Result<_>>()?;
+        Ok(Value::Array(elements))
+    }
+}
+
+/// The error as a transcode error of the value itself, unless it already is one.
+fn transcode_error(err: anyhow::Error) -> TranscodeError {
+    match err.downcast::<TranscodeError>() {
+        Ok(err) => err,
+        Err(err) => TranscodeError::new(format!("{:#}", err)),
+    }
+}
+
+/// The error at the field, variant or argument with the `name` of the value.
+fn at(err: anyhow::Error, name: &str) -> anyhow::Error {
+    transcode_error(err).at(name).into()
+}
+
+/// The error at the element or unnamed field with the `index` of the value.
+fn at_index(err: anyhow::Error, index: usize) -> anyhow::Error {
+    at(err, &format!("[{}]", index))
+}
+
+/// The `label` of an event or an argument, its `name` in the earlier metadata versions.
+fn label(item: &Value) -> String {
+    let label = item.get("label").or_else(|| item.get("name"));
+    label
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Returns the type id referenced by a field or type definition.
+pub(crate) fn type_id(reference: &Value) -> Result<u64> {
+    reference["type"]
+        .as_u64()
+        .ok_or_else(|| anyhow::anyhow!("Missing type in {}", reference))
+}
+
+/// The integer of a JSON number, or of a string for integers wider than JSON numbers.
+fn integer<T: std::str::FromStr>(value: &Value) -> Result<T> {
+    let digits = match value {
+        Value::Number(number) => number.to_string(),
+        Value::String(digits) => digits.clone(),
+        _ => anyhow::bail!("Expected an integer, not {}", value),
+    };
+    digits
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid integer {}", digits))
+}
+
+fn encode_primitive(primitive: &str, value: &Value, output: &mut Vec<u8>) -> Result<()> {
+    match (primitive, value) {
+        ("bool", Value::Bool(value)) => value.encode_to(output),
+        ("bool", Value::String(value)) => value.parse::<bool>()?.encode_to(output),
+        ("char", Value::String(value)) if value.chars().count() == 1 => {
+            let char = value.chars().next().expect("the string has one char");
+            (char as u32).encode_to(output)
+        }
+        ("str", Value::String(value)) => value.encode_to(output),
+        ("u8", value) => integer::<u8>(value)?.encode_to(output),
+        ("u16", value) => integer::<u16>(value)?.encode_to(output),
+        ("u32", value) => integer::<u32>(value)?.encode_to(output),
+        ("u64", value) => integer::<u64>(value)?.encode_to(output),
+        ("u128", value) => integer::<u128>(value)?.encode_to(output),
+        ("i8", value) => integer::<i8>(value)?.encode_to(output),
+        ("i16", value) => integer::<i16>(value)?.encode_to(output),
+        ("i32", value) => integer::<i32>(value)?.encode_to(output),
+        ("i64", value) => integer::<i64>(value)?.encode_to(output),
+        ("i128", value) => integer::<i128>(value)?.encode_to(output),
+        ("u256", value) | ("i256", value) => {
+            output.extend_from_slice(&encode_256(primitive == "i256", value)?)
+        }
+        _ => anyhow::bail!("Expected a {}, not {}", primitive, value),
+    }
+    Ok(())
+}
+
+pub(crate) fn decode_primitive(primitive: &str, input: &mut &[u8]) -> Result<Value> {
+    let value = match primitive {
+        "bool" => Value::from(bool::decode(input)?),
+        "char" => {
+            let char = std::char::from_u32(u32::decode(input)?)
+                .ok_or_else(|| anyhow::anyhow!("Invalid char"))?;
+            Value::from(char.to_string())
+        }
+        "str" => Value::from(String::decode(input)?),
+        "u8" => Value::from(u8::decode(input)?),
+        "u16" => Value::from(u16::decode(input)?),
+        "u32" => Value::from(u32::decode(input)?),
+        "u64" => Value::from(u64::decode(input)?),
+        "u128" => Value::from(u128::decode(input)?.to_string()),
+        "i8" => Value::from(i8::decode(input)?),
+        "i16" => Value::from(i16::decode(input)?),
+        "i32" => Value::from(i32::decode(input)?),
+        "i64" => Value::from(i64::decode(input)?),
+        "i128" => Value::from(i128::decode(input)?.to_string()),
+        "u256" | "i256" => Value::from(decode_256(primitive == "i256", input)?),
+        _ => anyhow::bail!("Unsupported primitive type {}", primitive),
+    };
+    Ok(value)
+}
+
+/// Encodes the integer of the 256 bit types of Solidity contracts, in little-endian two's
+/// complement if `signed`.
+fn encode_256(signed: bool, value: &Value) -> Result<[u8; 32]> {
+    let digits = integer::<String>(value)?;
+    let (negative, magnitude) = match digits.strip_prefix('-') {
+        Some(magnitude) if signed => (true, magnitude),
+        _ => (false, digits.as_str()),
+    };
+    let invalid = || anyhow::anyhow!("Invalid integer {}", digits);
+    let mut integer = U256::from_dec_str(magnitude).map_err(|_| invalid())?;
+    if signed && integer.bit(255) && !(negative && integer == U256::one() << 255) {
+        return Err(invalid());
+    }
+    if negative {
+        integer = (!integer).overflowing_add(U256::one()).0;
+    }
+    let mut bytes = [0; 32];
+    integer.to_little_endian(&mut bytes);
+    Ok(bytes)
+}
+
+/// Decodes the integer of the 256 bit types of Solidity contracts into its decimal digits.
+fn decode_256(signed: bool, input: &mut &[u8]) -> Result<String> {
+    let integer = U256::from_little_endian(&<[u8; 32]>::decode(input)?);
+    if signed && integer.bit(255) {
+        let magnitude = (!integer).overflowing_add(U256::one()).0;
+        return Ok(format!("-{}", magnitude));
+    }
+    Ok(integer.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn registry<'a>(types: &'a Value, custom: &'a CustomTypes) -> TypeRegistry<'a> {
+        TypeRegistry {
+            types: types.as_array().unwrap(),
+            custom,
+            polkadot_js: false,
+            resolved: Rc::default(),
+        }
+    }
+
+    #[test]
+    fn decodes_values_of_the_registered_types() {
+        let types = json!([
+            { "def": { "primitive": "u32" } },
+            { "def": { "primitive": "bool" } },
+            { "def": { "composite": { "fields": [
+                { "name": "value", "type": 1 },
+                { "name": "flag", "type": 2 }
+            ] } } },
+            { "def": { "sequence": { "type": 5 } } },
+            { "def": { "primitive": "u8" } },
+            { "def": { "variant": { "variants": [
+                { "name": "None" },
+                { "name": "Some", "fields": [{ "type": 1 }] }
+            ] } } }
+        ]);
+        let custom = CustomTypes::default();
+        let types = registry(&types, &custom);
+
+        let encoded = (7u32, true).encode();
+        assert_eq!(
+            types.decode_all(3, &encoded).unwrap(),
+            json!({ "value": 7, "flag": true })
+        );
+        assert_eq!(
+            types.decode_all(4, &vec![1u8, 2].encode()).unwrap(),
+            json!("0x0102")
+        );
+        assert_eq!(
+            types.decode_all(6, &Some(3u32).encode()).unwrap(),
+            json!({ "Some": 3 })
+        );
+        assert_eq!(types.decode_all(6, &[0]).unwrap(), json!("None"));
+        assert!(types.decode_all(1, &[1, 2, 3, 4, 5]).is_err());
+        assert!(types.decode_all(9, &[]).is_err());
+    }
+
+    #[test]
+    fn reports_the_path_of_invalid_fields() {
+        let types = json!([
+            { "def": { "primitive": "u32" } },
+            { "def": { "sequence": { "type": 1 } } },
+            { "def": { "composite": { "fields": [{ "name": "amounts", "type": 2 }] } } }
+        ]);
+        let custom = CustomTypes::default();
+        let types = registry(&types, &custom);
+        let path = |err: anyhow::Error| err.downcast::<TranscodeError>().unwrap().path;
+
+        let err = types
+            .encode(3, &json!({ "amounts": [1, "many"] }), &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(path(err), "amounts[1]");
+        let err = types.decode_all(3, &vec![1u32].encode()[..3]).unwrap_err();
+        assert_eq!(path(err), "amounts[0]");
+    }
+
+    #[test]
+    fn decodes_the_values_like_polkadot_js() {
+        let types = json!([
+            { "def": { "primitive": "u8" } },
+            { "def": { "array": { "len": 32, "type": 1 } } },
+            { "path": ["ink_env", "types", "AccountId"], "def": { "composite": { "fields": [
+                { "type": 2 }
+            ] } } },
+            { "path": ["Option"], "params": [3], "def": { "variant": { "variants": [
+                { "name": "None" },
+                { "name": "Some", "fields": [{ "type": 3 }] }
+            ] } } },
+            { "def": { "composite": { "fields": [
+                { "name": "new_owner", "type": 4 }
+            ] } } }
+        ]);
+        let custom = CustomTypes::default();
+        let types = TypeRegistry {
+            polkadot_js: true,
+            ..registry(&types, &custom)
+        };
+        let owner = AccountId32::from([1; 32]);
+        assert_eq!(
+            types.decode_all(5, &Some([1u8; 32]).encode()).unwrap(),
+            json!({ "newOwner": owner.to_ss58check() })
+        );
+        assert_eq!(
+            types.decode_all(5, &[0]).unwrap(),
+            json!({ "newOwner": null })
+        );
+    }
+
+    #[test]
+    fn decodes_events() {
+        let metadata = InkMetadata::parse(
+            json!({
+                "spec": { "events": [
+                    { "name": "Flipped", "args": [] },
+                    { "name": "Transferred", "args": [
+                        { "name": "value", "type": { "type": 1, "displayName": ["u32"] } },
+                        { "name": "amount", "type": { "type": 2, "displayName": ["Balance"] } }
+                    ] }
+                ] },
+                "types": [
+                    { "def": { "primitive": "u32" } },
+                    { "def": { "primitive": "u128" } }
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let data = (1u8, 5u32, 7u128).encode();
+        assert_eq!(
+            metadata.decode_event(&data).unwrap(),
+            ("Transferred".to_string(), json!({ "value": 5, "amount": "7" }))
+        );
+        assert_eq!(metadata.event_balance_args(&data), vec!["amount"]);
+        assert!(metadata.decode_event(&[2]).is_err());
+    }
+
+    #[test]
+    fn lists_messages() {
+        let metadata = InkMetadata::parse(
+            json!({ "spec": { "messages": [
+                { "name": ["flip"], "selector": "0x633aa551", "args": [] },
+                { "label": "Ownable::terminate", "selector": "0x01020304", "args": [
+                    { "name": "beneficiary", "type": { "type": 1 } }
+                ] }
+            ] } })
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            metadata.messages().unwrap(),
+            vec![
+                Message {
+                    label: "flip".into(),
+                    selector: vec![0x63, 0x3a, 0xa5, 0x51],
+                    args: vec![],
+                    returns: None,
+                    payable: true,
+                },
+                Message {
+                    label: "Ownable::terminate".into(),
+                    selector: vec![1, 2, 3, 4],
+                    args: vec![1],
+                    returns: None,
+                    payable: true,
+                },
+            ]
+        );
+        let message = metadata.find_message(None, "terminate").unwrap();
+        assert_eq!(message.label, "Ownable::terminate");
+        let message = metadata.find_message(Some("flip"), "terminate").unwrap();
+        assert_eq!(message.selector, vec![0x63, 0x3a, 0xa5, 0x51]);
+        let message = metadata
+            .find_message(Some("terminate"), "terminate")
+            .unwrap();
+        assert_eq!(message.label, "Ownable::terminate");
+        assert!(metadata.find_message(Some("Flip::flip"), "").is_err());
+        assert!(metadata.find_message(None, "set_code_hash").is_err());
+    }
+
+    #[test]
+    fn disambiguates_messages_of_traits() {
+        let metadata = InkMetadata::parse(
+            json!({ "spec": { "messages": [
+                { "label": "Erc20::transfer", "selector": "0x01", "args": [] },
+                { "label": "Erc721::transfer", "selector": "0x02", "args": [] },
+                { "label": "Erc20::balance_of", "selector": "0x03", "args": [] },
+                { "label": "balance_of", "selector": "0x04", "args": [] }
+            ] } })
+            .to_string(),
+        )
+        .unwrap();
+        let message = metadata.find_message(Some("Erc721::transfer"), "").unwrap();
+        assert_eq!(message.selector, vec![2]);
+        let err = metadata.find_message(Some("transfer"), "").unwrap_err();
+        assert!(err.to_string().contains("Erc20::transfer, Erc721::transfer"));
+        let message = metadata.find_message(Some("balance_of"), "").unwrap();
+        assert_eq!(message.selector, vec![4]);
+    }
+
+    #[test]
+    fn parses_the_types_when_first_used() {
+        let metadata = InkMetadata::parse(
+            json!({
+                "source": { "wasm": "0x0061736d" },
+                "V3": {
+                    "spec": { "messages": [{ "label": "get", "selector": "0x2f865bd9", "args": [] }] },
+                    "types": [{ "id": 0, "type": { "def": { "primitive": "u32" } } }]
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(metadata.messages().unwrap()[0].label, "get");
+        assert!(metadata.abi.get("source").is_none());
+        assert!(metadata.types.get().is_none());
+        assert_eq!(
+            metadata.types().decode_all(0, &[7, 0, 0, 0]).unwrap(),
+            json!(7)
+        );
+        assert!(metadata.types.get().is_some());
+    }
+
+    #[test]
+    fn reads_the_metadata_of_solang_contracts() {
+        let metadata = InkMetadata::parse(
+            json!({
+                "source": { "language": "Solidity 0.1.7", "compiler": "solang 0.1.7" },
+                "types": [{ "def": { "primitive": "i256" } }],
+                "spec": { "messages": [{ "name": "add", "selector": "0x2e9c3a1f", "args": [
+                    { "name": "delta", "type": { "type": 1, "displayName": ["int256"] } }
+                ], "returnType": { "type": 1 }, "payable": false }] }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let message = metadata.find_message(Some("add"), "").unwrap();
+        assert_eq!(message.args, vec![1]);
+        assert!(!message.payable);
+
+        let types = metadata.types();
+        let mut encoded = Vec::new();
+        types.encode(1, &json!("-2"), &mut encoded).unwrap();
+        assert_eq!(encoded, [&[0xfe][..], &[0xff; 31][..]].concat());
+        assert_eq!(types.decode_all(1, &encoded).unwrap(), json!("-2"));
+        let limit = U256::one() << 255;
+        let encode = |value: String| types.encode(1, &json!(value), &mut Vec::new());
+        assert!(encode(format!("-{}", limit)).is_ok());
+        assert!(encode((limit - 1).to_string()).is_ok());
+        assert!(encode(limit.to_string()).is_err());
+    }
+
+    #[test]
+    fn resolves_the_decoded_types_once_for_all_values() {
+        let metadata = InkMetadata::parse(
+            json!({ "V3": { "spec": {}, "types": [
+                { "id": 0, "type": { "def": { "primitive": "u8" } } },
+                { "id": 1, "type": { "path": ["Unused"], "def": { "primitive": "bool" } } },
+                { "id": 3, "type": { "path": ["demo", "Pair"], "def": { "tuple": [0, 0] } } }
+            ] } })
+            .to_string(),
+        )
+        .unwrap();
+        for data in &[[1, 2], [3, 4]] {
+            metadata.types().decode_all(3, data).unwrap();
+        }
+        let resolved = metadata.resolved.0.borrow();
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(resolved[&3].as_ref().unwrap().index, 2);
+        assert_eq!(
+            resolved[&3].as_ref().unwrap().path.as_deref(),
+            Some("demo::Pair")
+        );
+    }
+
+    #[test]
+    fn resolves_types_by_id() {
+        let types = json!([
+            { "id": 0, "type": { "def": { "primitive": "u16" } } },
+            { "id": 1, "type": { "def": { "tuple": [0, 0] } } }
+        ]);
+        let encoded = (1u16, 2u16).encode();
+        assert_eq!(
+            registry(&types, &CustomTypes::default())
+                .decode_all(1, &encoded)
+                .unwrap(),
+            json!([1, 2])
+        );
+    }
+
+    #[test]
+    fn prefers_the_registered_types() {
+        // A chain extension type the metadata describes as an opaque composite.
+        let types = json!([
+            { "def": { "primitive": "u8" } },
+            { "path": ["my_chain", "AssetId"], "def": { "composite": {} } }
+        ]);
+        let custom = toml::from_str(r#""my_chain::AssetId" = "(u8, u8)""#).unwrap();
+        let custom = CustomTypes::new(&custom).unwrap();
+        assert_eq!(
+            registry(&types, &custom).decode_all(2, &[1, 2]).unwrap(),
+            json!([1, 2])
+        );
+    }
+}
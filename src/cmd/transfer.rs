@@ -0,0 +1,141 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::Result;
+use codec::{Compact, Encode, Output};
+use serde::Serialize;
+use sp_core::crypto::AccountId32;
+use subxt::{
+    balances::BalancesEventsDecoder, system::System, Call, DefaultNodeRuntime, EventsDecoder,
+};
+
+use super::{
+    address::Address,
+    balance::{BalanceFormat, BalanceVariant},
+    events::ExtrinsicDetails,
+    extrinsic::Connection,
+    info::ContractInfoOfStore,
+    sign::{self, OfflineParams, SignedExtrinsic},
+};
+use crate::ExtrinsicOpts;
+
+/// The `Balances::transfer_keep_alive` call, which fails rather than reaping the sender's
+/// account.
+pub(crate) struct TransferKeepAliveCall<'a> {
+    pub dest: &'a <DefaultNodeRuntime as System>::Address,
+    pub value: u128,
+}
+
+impl Encode for TransferKeepAliveCall<'_> {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.dest.encode_to(dest);
+        Compact(self.value).encode_to(dest)
+    }
+}
+
+impl Call<DefaultNodeRuntime> for TransferKeepAliveCall<'_> {
+    const MODULE: &'static str = "Balances";
+    const FUNCTION: &'static str = "transfer_keep_alive";
+
+    fn events_decoder(decoder: &mut EventsDecoder<DefaultNodeRuntime>) {
+        decoder.with_balances();
+    }
+}
+
+/// Result of a successful `transfer`.
+#[derive(Debug, Serialize)]
+pub(crate) struct TransferResult {
+    pub dest: AccountId32,
+    /// The balance transferred, in the smallest unit of the chain's token.
+    pub value: u128,
+    /// How the balance is printed in the human readable output.
+    #[serde(skip)]
+    pub balance_format: BalanceFormat,
+    #[serde(flatten)]
+    pub extrinsic: ExtrinsicDetails,
+}
+
+/// Transfers the balance to the account, usually a contract to fund it.
+///
+/// Warns if there is no contract at the account, which hints at a mixed up address.
+pub(crate) fn execute_transfer(
+    extrinsic_opts: &ExtrinsicOpts,
+    dest: &Address,
+    value: &BalanceVariant,
+) -> Result<TransferResult> {
+    let signer = extrinsic_opts.signer()?;
+    async_std::task::block_on(async move {
+        let mut connection = Connection::new(&extrinsic_opts.submit).await?;
+        dest.check_format();
+
+        let account_id = &dest.account;
+        if connection
+            .fetch(&ContractInfoOfStore { account_id })
+            .await?
+            .is_none()
+        {
+            log::warn!("There is no contract at {}", account_id);
+        }
+        let value = connection.denominate(value)?;
+        let call = TransferKeepAliveCall {
+            dest: &account_id.clone().into(),
+            value,
+        };
+        let submitted = connection
+            .submit_transferring(call, &signer, extrinsic_opts, value)
+            .await?;
+        Ok(TransferResult {
+            dest: account_id.clone(),
+            value,
+            balance_format: connection.balance_format(),
+            extrinsic: ExtrinsicDetails::new(&submitted)?,
+        })
+    })
+}
+
+/// Sign the `Balances::transfer_keep_alive` extrinsic without submitting it.
+pub(crate) fn sign_transfer(
+    extrinsic_opts: &ExtrinsicOpts,
+    params: &OfflineParams,
+    dest: &Address,
+    value: &BalanceVariant,
+) -> Result<SignedExtrinsic> {
+    let call = TransferKeepAliveCall {
+        dest: &dest.account.clone().into(),
+        value: value.without_denomination()?,
+    };
+    sign::sign_offline(call, extrinsic_opts, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_the_value_compact() {
+        let dest = AccountId32::from([1; 32]).into();
+        let call = TransferKeepAliveCall {
+            dest: &dest,
+            value: 1_000,
+        };
+        let encoded = call.encode();
+        assert_eq!(&encoded[..encoded.len() - 2], &dest.encode()[..]);
+        assert_eq!(
+            &encoded[encoded.len() - 2..],
+            &Compact(1_000u128).encode()[..]
+        );
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Signing payloads exchanged with air-gapped signers, e.g. Polkadot Vault, in the Universal
+//! Offline Signatures (UOS) format: the payload is shown as QR codes and the signature is scanned
+//! or typed back.
+
+use std::io::{self, BufRead, Write};
+
+use anyhow::{Context, Result};
+use codec::Decode;
+use qrcode::{render::unicode::Dense1x2, EcLevel, QrCode};
+use sp_core::{crypto::AccountId32, ed25519, sr25519, H256};
+use subxt::sp_runtime::{traits::Verify, MultiSignature};
+
+use super::signer::Scheme;
+use crate::SignerError;
+
+/// The prefix of payloads for Substrate chains.
+const SUBSTRATE: u8 = 0x53;
+/// The command signing a transaction, which the signer hashes itself if it is longer than 256
+/// bytes.
+const SIGN_TRANSACTION: u8 = 0x02;
+/// The number of bytes of the payload in each QR code.
+const FRAME_SIZE: usize = 256;
+
+/// Shows the signing payload of an extrinsic as QR codes on the terminal and reads the signature
+/// the air-gapped signer returns, checking it was made by the `account`.
+pub(crate) fn sign(
+    scheme: Scheme,
+    account: &AccountId32,
+    payload: &[u8],
+    genesis_hash: H256,
+) -> Result<MultiSignature> {
+    let frames = frames(&uos_payload(scheme, account, payload, genesis_hash)?);
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    for (index, frame) in frames.iter().enumerate() {
+        let code = QrCode::with_error_correction_level(frame, EcLevel::L)
+            .context("Failed to encode the signing payload as a QR code")?;
+        let image = code
+            .render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .build();
+        eprintln!("{}", image);
+        if index + 1 < frames.len() {
+            eprint!("Frame {}/{}, press enter for the next one", index + 1, frames.len());
+            io::stderr().flush()?;
+            lines.next().transpose()?;
+        }
+    }
+    eprint!("Scan the payload with the signer, then enter the signature it shows: ");
+    io::stderr().flush()?;
+    let signature = lines
+        .next()
+        .transpose()?
+        .ok_or_else(|| SignerError::new("No signature entered"))?;
+    let signature = parse_signature(scheme, &signature)?;
+    // Payloads longer than 256 bytes are signed by their hash.
+    let hash;
+    let signed = if payload.len() > 256 {
+        hash = sp_core::blake2_256(payload);
+        &hash[..]
+    } else {
+        payload
+    };
+    if !signature.verify(signed, account) {
+        let error = format!("The signature was not made by the account {}", account);
+        return Err(SignerError::new(error).into());
+    }
+    Ok(signature)
+}
+
+/// The UOS payload signing the extrinsic's payload with the key of the `account`: the Substrate
+/// prefix, the scheme, the command, the public key, the payload and the genesis hash.
+fn uos_payload(
+    scheme: Scheme,
+    account: &AccountId32,
+    payload: &[u8],
+    genesis_hash: H256,
+) -> Result<Vec<u8>> {
+    let crypto = match scheme {
+        Scheme::Ed25519 => 0x00,
+        Scheme::Sr25519 => 0x01,
+        Scheme::Ecdsa => {
+            // The account of an ecdsa key is the hash of its public key, which it can not be
+            // recovered from.
+            let error = "Air-gapped signers are only supported for sr25519 and ed25519 accounts";
+            return Err(SignerError::new(error).into());
+        }
+    };
+    let account: &[u8] = account.as_ref();
+    let mut uos = Vec::with_capacity(3 + account.len() + payload.len() + 32);
+    uos.extend_from_slice(&[SUBSTRATE, crypto, SIGN_TRANSACTION]);
+    uos.extend_from_slice(account);
+    uos.extend_from_slice(payload);
+    uos.extend_from_slice(genesis_hash.as_bytes());
+    Ok(uos)
+}
+
+/// Splits the payload into the frames of a multipart QR code, each prefixed with the multipart
+/// flag, the number of frames and its index.
+fn frames(payload: &[u8]) -> Vec<Vec<u8>> {
+    let chunks = payload.chunks(FRAME_SIZE).collect::<Vec<_>>();
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut frame = vec![0x00];
+            frame.extend_from_slice(&(chunks.len() as u16).to_be_bytes());
+            frame.extend_from_slice(&(index as u16).to_be_bytes());
+            frame.extend_from_slice(chunk);
+            frame
+        })
+        .collect()
+}
+
+/// Parses the hex encoded signature, either a SCALE encoded `MultiSignature` or the bare
+/// signature of the `scheme`.
+fn parse_signature(scheme: Scheme, signature: &str) -> Result<MultiSignature> {
+    let bytes = hex::decode(signature.trim().trim_start_matches("0x"))
+        .map_err(|_| SignerError::new("The signature should be hex encoded"))?;
+    let signature = match (scheme, bytes.len()) {
+        (Scheme::Sr25519, 64) => Some(sr25519::Signature::from_slice(&bytes).into()),
+        (Scheme::Ed25519, 64) => Some(ed25519::Signature::from_slice(&bytes).into()),
+        _ => MultiSignature::decode(&mut &bytes[..]).ok(),
+    };
+    signature.ok_or_else(|| SignerError::new("Invalid signature").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::ExtrinsicSigner;
+
+    #[test]
+    fn encodes_the_payload_in_frames() {
+        let alice = ExtrinsicSigner::from_suri(Scheme::Sr25519, "//Alice", None).unwrap();
+        let account = alice.account_id();
+        let payload = vec![7; 300];
+        let uos = uos_payload(Scheme::Sr25519, account, &payload, H256::repeat_byte(1)).unwrap();
+        assert_eq!(uos[..3], [0x53, 0x01, 0x02]);
+        assert_eq!(uos[3..35], *AsRef::<[u8]>::as_ref(account));
+        assert_eq!(uos[35..335], payload[..]);
+        assert_eq!(uos[335..], [1; 32]);
+        assert!(uos_payload(Scheme::Ecdsa, account, &payload, H256::zero()).is_err());
+
+        let frames = frames(&uos);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0][..5], [0, 0, 2, 0, 0]);
+        assert_eq!(frames[1][..5], [0, 0, 2, 0, 1]);
+        assert_eq!(frames[0][5..], uos[..256]);
+        assert_eq!(frames[1][5..], uos[256..]);
+    }
+
+    #[test]
+    fn parses_bare_and_multi_signatures() {
+        let alice = ExtrinsicSigner::from_suri(Scheme::Sr25519, "//Alice", None).unwrap();
+        let signature = async_std::task::block_on(alice.sign(b"payload")).unwrap();
+        let bare = match signature {
+            MultiSignature::Sr25519(ref signature) => hex::encode(signature),
+            _ => unreachable!("signed with sr25519"),
+        };
+        let multi = format!("0x{}", hex::encode(codec::Encode::encode(&signature)));
+        assert_eq!(parse_signature(Scheme::Sr25519, &bare).unwrap(), signature);
+        assert_eq!(parse_signature(Scheme::Sr25519, &multi).unwrap(), signature);
+        assert!(parse_signature(Scheme::Sr25519, "0x0102").is_err());
+        assert!(parse_signature(Scheme::Sr25519, "signature").is_err());
+    }
+}
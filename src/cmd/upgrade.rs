@@ -0,0 +1,187 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use codec::Encode;
+use serde::Serialize;
+use sp_core::{crypto::AccountId32, H256};
+use subxt::{
+    contracts::{CallCall, PutCodeCall},
+    DefaultNodeRuntime,
+};
+
+use super::{
+    address::Address,
+    call::DryRunRequest,
+    code::{contract_code_hash, PristineCodeStore},
+    deploy,
+    events::ExtrinsicDetails,
+    extrinsic::Connection,
+    gas::{GasLimit, GasMargin},
+    pallet::Deposits,
+    transcode::InkMetadata,
+};
+use crate::ExtrinsicOpts;
+
+/// Result of a successful `upgrade`.
+#[derive(Debug, Serialize)]
+pub(crate) struct UpgradeResult {
+    pub contract: AccountId32,
+    /// The code hash of the contract before the upgrade.
+    pub old_code_hash: H256,
+    /// The code hash of the new code.
+    pub code_hash: H256,
+    /// Whether the new code was uploaded, as it was not stored on the chain yet.
+    pub uploaded: bool,
+    /// The label of the message called to set the code hash.
+    pub message: String,
+    /// Whether the contract was confirmed to use the new code, which requires awaiting the
+    /// inclusion of the extrinsic.
+    pub confirmed: bool,
+    #[serde(flatten)]
+    pub extrinsic: ExtrinsicDetails,
+}
+
+impl UpgradeResult {
+    /// The human readable output.
+    pub fn display(&self) -> String {
+        let mut output = if self.confirmed {
+            format!(
+                "Contract {} upgraded from {:?} to {:?}",
+                self.contract, self.old_code_hash, self.code_hash
+            )
+        } else {
+            format!(
+                "Called `{}` of contract {} with {:?}, the upgrade is not confirmed without \
+                 awaiting the inclusion of the extrinsic",
+                self.message, self.contract, self.code_hash
+            )
+        };
+        if self.uploaded {
+            output.push_str("\n\tUploaded the new code");
+        }
+        output.push_str(&format!(
+            "\n\tExtrinsic hash: {:?}",
+            self.extrinsic.extrinsic
+        ));
+        self.extrinsic.display_details(output)
+    }
+}
+
+/// Upgrades the contract to the code of the Wasm file, by calling its message setting the code
+/// hash, e.g. `set_code_hash`, with the hash of the new code. The code is uploaded beforehand if
+/// it is not stored on the chain yet.
+///
+/// Defaults to the Wasm built for the contract project in the current directory.
+pub(crate) fn execute_upgrade(
+    extrinsic_opts: &ExtrinsicOpts,
+    contract: &Address,
+    wasm_path: Option<&PathBuf>,
+    message: Option<&str>,
+    gas_limit: GasLimit,
+    gas_margin: GasMargin,
+    metadata_path: Option<&Path>,
+) -> Result<UpgradeResult> {
+    let metadata = InkMetadata::load(metadata_path)?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "Upgrading a contract requires its metadata to look up the message setting the code \
+             hash, supply it with `--contract-metadata`"
+        )
+    })?;
+    let message = metadata.find_message(message, "set_code_hash")?;
+    if message.args.len() != 1 {
+        anyhow::bail!(
+            "The message `{}` takes {} arguments instead of only the code hash",
+            message.label,
+            message.args.len()
+        )
+    }
+    let code = deploy::load_contract_code(wasm_path)?;
+    let code_hash = H256(sp_core::blake2_256(&code));
+    let signer = extrinsic_opts.signer()?;
+
+    async_std::task::block_on(async move {
+        let mut connection = Connection::new(&extrinsic_opts.submit).await?;
+        contract.check_format();
+
+        let account_id = &contract.account;
+        let old_code_hash = contract_code_hash(&connection, account_id).await?;
+        if old_code_hash == code_hash {
+            anyhow::bail!("The contract already uses the code {:?}", code_hash)
+        }
+
+        let stored = connection
+            .fetch(&PristineCodeStore {
+                code_hash: &code_hash,
+            })
+            .await?
+            .is_some();
+        if !stored {
+            let deposit = Deposits::from_metadata(&connection.runtime_metadata().await?)?;
+            let call = PutCodeCall {
+                _runtime: PhantomData,
+                code: &code,
+            };
+            connection
+                .submit_reserving(call, &signer, extrinsic_opts, 0, deposit.code(&code))
+                .await?;
+        }
+
+        let data = [message.selector.clone(), code_hash.encode()].concat();
+        let origin = extrinsic_opts.origin(signer.account_id())?;
+        let request = DryRunRequest {
+            origin: &origin,
+            dest: account_id,
+            value: 0,
+            data: &data,
+            at: None,
+        };
+        let gas_limit = gas_limit
+            .resolve(&connection, gas_margin, request, &signer, extrinsic_opts)
+            .await?;
+        let dest = account_id.clone().into();
+        let call = CallCall::<DefaultNodeRuntime> {
+            dest: &dest,
+            value: 0,
+            gas_limit,
+            data: &data,
+        };
+        let submitted = connection.submit(call, &signer, extrinsic_opts).await?;
+        let confirmed = submitted.events().is_some();
+        if confirmed && contract_code_hash(&connection, account_id).await? != code_hash {
+            anyhow::bail!(
+                "The contract still uses the code {:?} after calling `{}`, which may have \
+                 reverted or not be permitted for the signer",
+                old_code_hash,
+                message.label
+            )
+        }
+        Ok(UpgradeResult {
+            contract: account_id.clone(),
+            old_code_hash,
+            code_hash,
+            uploaded: !stored,
+            message: message.label,
+            confirmed,
+            extrinsic: ExtrinsicDetails::new(&submitted)?,
+        })
+    })
+}
@@ -0,0 +1,271 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::Result;
+use parity_wasm::elements::{
+    External, FunctionType, Instruction, Internal, Module, Type, ValueType,
+};
+
+/// The exports `pallet-contracts` requires, and the only ones it accepts.
+const ENTRYPOINTS: [&str; 2] = ["deploy", "call"];
+
+/// Checks the Wasm for what `pallet-contracts` rejects on upload, so that the build fails with
+/// actionable messages instead of an opaque `CodeRejected` on-chain.
+///
+/// The size of the memory is checked as the maximum number of pages is set.
+pub(crate) fn validate_wasm(module: &Module) -> Result<()> {
+    let mut errors = Vec::new();
+    check_floats(module, &mut errors);
+    check_imports(module, &mut errors);
+    check_exports(module, &mut errors);
+    let memories = module
+        .memory_section()
+        .map_or(&[][..], |memory| memory.entries());
+    if !memories.is_empty() {
+        errors.push(
+            "The contract declares its own memory instead of importing it, is --import-memory \
+             specified in the linker args?"
+                .to_string(),
+        );
+    }
+    if errors.is_empty() {
+        return Ok(());
+    }
+    anyhow::bail!(
+        "The Wasm would be rejected by pallet-contracts:\n  - {}",
+        errors.join("\n  - ")
+    )
+}
+
+fn is_float(value_type: &ValueType) -> bool {
+    matches!(value_type, ValueType::F32 | ValueType::F64)
+}
+
+/// Floating point operations are not deterministic across platforms.
+fn check_floats(module: &Module, errors: &mut Vec<String>) {
+    let signatures = module.type_section().map_or(&[][..], |types| types.types());
+    let float_signature = signatures.iter().any(|Type::Function(signature)| {
+        signature.params().iter().any(is_float) || signature.results().iter().any(is_float)
+    });
+    let globals = module
+        .global_section()
+        .map_or(&[][..], |globals| globals.entries());
+    let float_global = globals
+        .iter()
+        .any(|global| is_float(&global.global_type().content_type()));
+    let bodies = module.code_section().map_or(&[][..], |code| code.bodies());
+    let float_local = bodies.iter().any(|body| {
+        body.locals()
+            .iter()
+            .any(|local| is_float(&local.value_type()))
+    });
+    // The names of all float instructions, including the conversions, mention their type.
+    let float_instruction = bodies.iter().find_map(|body| {
+        body.code().elements().iter().find(|instruction| {
+            let name = format!("{:?}", instruction);
+            name.contains("F32") || name.contains("F64")
+        })
+    });
+    if let Some(instruction) = float_instruction {
+        errors.push(format!(
+            "The contract uses the floating point instruction {}, e.g. through an `f32` or `f64` \
+             in the code or a dependency",
+            instruction_name(instruction)
+        ));
+    } else if float_signature || float_global || float_local {
+        errors.push("The contract uses floating point types".to_string());
+    }
+}
+
+fn instruction_name(instruction: &Instruction) -> String {
+    let name = format!("{:?}", instruction);
+    name.split('(').next().unwrap_or_default().to_string()
+}
+
+/// Only functions of the `seal` modules of the contracts API and the memory can be imported.
+fn check_imports(module: &Module, errors: &mut Vec<String>) {
+    let imports = module
+        .import_section()
+        .map_or(&[][..], |imports| imports.entries());
+    for import in imports {
+        let name = format!("{}::{}", import.module(), import.field());
+        match import.external() {
+            External::Function(_) if import.module().starts_with("seal") => (),
+            External::Function(_) => errors.push(format!(
+                "The contract imports the function {} which is not part of the contracts API, \
+                 only functions of the `seal` modules are provided",
+                name
+            )),
+            External::Memory(_) if import.module() == "env" && import.field() == "memory" => (),
+            External::Memory(_) => errors.push(format!(
+                "The contract imports its memory as {}, instead of env::memory",
+                name
+            )),
+            External::Table(_) | External::Global(_) => errors.push(format!(
+                "The contract imports {}, only functions and the memory can be imported",
+                name
+            )),
+        }
+    }
+}
+
+/// The contract must export the `deploy` and `call` functions without parameters and results,
+/// and nothing else.
+fn check_exports(module: &Module, errors: &mut Vec<String>) {
+    let exports = module
+        .export_section()
+        .map_or(&[][..], |exports| exports.entries());
+    for entrypoint in ENTRYPOINTS.iter() {
+        if !exports.iter().any(|export| export.field() == *entrypoint) {
+            errors.push(format!(
+                "The contract does not export the `{}` function, is it an ink! contract?",
+                entrypoint
+            ));
+        }
+    }
+    for export in exports {
+        let field = export.field();
+        let function = match export.internal() {
+            Internal::Function(index) if ENTRYPOINTS.contains(&field) => *index,
+            _ => {
+                errors.push(format!(
+                    "The contract exports `{}`, only the `deploy` and `call` functions can be \
+                     exported",
+                    field
+                ));
+                continue;
+            }
+        };
+        match function_type(module, function) {
+            Some(signature) if signature.params().is_empty() && signature.results().is_empty() => {}
+            _ => errors.push(format!(
+                "The `{}` function must take no parameters and return nothing",
+                field
+            )),
+        }
+    }
+}
+
+/// The magic bytes at the start of a PolkaVM program.
+const POLKAVM_MAGIC: &[u8] = b"PVM\0";
+
+/// Checks the PolkaVM program linked for the RISC-V target, which no chain validates yet.
+///
+/// The exports of a program are stored by their names, which are looked for in it.
+pub(crate) fn validate_polkavm(program: &[u8]) -> Result<()> {
+    if !program.starts_with(POLKAVM_MAGIC) {
+        anyhow::bail!("The linked code is not a PolkaVM program")
+    }
+    let missing = ENTRYPOINTS
+        .iter()
+        .filter(|entrypoint| {
+            !program
+                .windows(entrypoint.len())
+                .any(|window| window == entrypoint.as_bytes())
+        })
+        .map(|entrypoint| format!("`{}`", entrypoint))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "The PolkaVM program does not export {}, is it an ink! contract?",
+            missing.join(" and ")
+        )
+    }
+    Ok(())
+}
+
+/// The signature of the function with the index, which counts the imported functions first.
+fn function_type(module: &Module, index: u32) -> Option<&FunctionType> {
+    let imported = module
+        .import_section()
+        .map_or(&[][..], |imports| imports.entries())
+        .iter()
+        .filter_map(|import| match import.external() {
+            External::Function(type_index) => Some(*type_index),
+            _ => None,
+        });
+    let defined = module
+        .function_section()
+        .map_or(&[][..], |functions| functions.entries())
+        .iter()
+        .map(|function| function.type_ref());
+    let type_index = imported.chain(defined).nth(index as usize)?;
+    match module.type_section()?.types().get(type_index as usize)? {
+        Type::Function(signature) => Some(signature),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parity_wasm::{builder, elements::Instructions};
+
+    fn contract(body: Vec<Instruction>, exports: &[&str]) -> Module {
+        let mut module = builder::module()
+            .import()
+            .module("env")
+            .field("memory")
+            .external()
+            .memory(1, Some(16))
+            .build()
+            .function()
+            .signature()
+            .build()
+            .body()
+            .with_instructions(Instructions::new(body))
+            .build()
+            .build();
+        for export in exports {
+            module = module.export().field(export).internal().func(0).build();
+        }
+        module.build()
+    }
+
+    #[test]
+    fn rejects_what_pallet_contracts_rejects() {
+        let valid = contract(vec![Instruction::End], &["deploy", "call"]);
+        assert!(validate_wasm(&valid).is_ok());
+
+        let floats = vec![
+            Instruction::F32Const(0),
+            Instruction::Drop,
+            Instruction::End,
+        ];
+        let err = validate_wasm(&contract(floats, &["deploy", "call", "memcpy"]))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("floating point instruction F32Const"));
+        assert!(err.contains("exports `memcpy`"));
+        assert!(!err.contains("does not export"));
+
+        let err = validate_wasm(&contract(vec![Instruction::End], &["call"]))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("does not export the `deploy` function"));
+    }
+
+    #[test]
+    fn checks_the_polkavm_program() {
+        let program = [&b"PVM\0\x01"[..], b"\x0adeploy\x04call"].concat();
+        assert!(validate_polkavm(&program).is_ok());
+        assert!(validate_polkavm(b"\0asm\x01deploycall").is_err());
+        let err = validate_polkavm(b"PVM\0\x01deploy").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The PolkaVM program does not export `call`, is it an ink! contract?"
+        );
+    }
+}
@@ -0,0 +1,271 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+use super::cache::cache_dir;
+use crate::util;
+
+/// Releases of binaryen before this one miscompile contracts.
+const MIN_VERSION: u32 = 99;
+
+/// The newest release of binaryen the builds were tested with.
+const MAX_TESTED_VERSION: u32 = 101;
+
+/// The release of binaryen downloaded if no `wasm-opt` is installed.
+const PINNED_VERSION: u32 = 101;
+
+/// Held while downloading, so that the concurrent builds of a workspace download the release
+/// once.
+static DOWNLOAD: Mutex<()> = Mutex::new(());
+
+/// Where the builds find the `wasm-opt` executable of binaryen to optimize with.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct WasmOpt {
+    /// The executable of `--wasm-opt-path`, instead of the one on the `PATH`.
+    pub path: Option<PathBuf>,
+    /// Download the pinned release if no `wasm-opt` is installed, instead of optimizing with the
+    /// bundled binaryen library.
+    pub download: bool,
+}
+
+impl WasmOpt {
+    /// Returns the `wasm-opt` executable to optimize with, checking that its version is
+    /// supported, or `None` to optimize with the bundled binaryen library.
+    ///
+    /// It is the one of `--wasm-opt-path`, else the one on the `PATH`, else a downloaded release,
+    /// which is downloaded if requested with `--download-wasm-opt`.
+    pub fn find(&self) -> Result<Option<PathBuf>> {
+        let wasm_opt = match self.installed() {
+            Some(wasm_opt) => wasm_opt,
+            None if self.download => download()?,
+            None => return Ok(None),
+        };
+        check_version(&wasm_opt)?;
+        Ok(Some(wasm_opt))
+    }
+
+    /// The `wasm-opt` executable found without downloading one.
+    pub fn installed(&self) -> Option<PathBuf> {
+        match self.path {
+            Some(ref path) => Some(path.clone()),
+            None => which::which("wasm-opt")
+                .ok()
+                .or_else(|| downloaded().filter(|path| path.exists())),
+        }
+    }
+}
+
+/// Warns that the Wasm is optimized with the bundled binaryen library, which differs from the
+/// builds with the pinned release.
+///
+/// Printed regardless of the log level, as the Wasm differs silently otherwise.
+pub(crate) fn warn_bundled() {
+    eprintln!(
+        "{} {}",
+        "warning:".yellow().bold(),
+        format!(
+            "wasm-opt is not installed, optimizing with the bundled binaryen. Install binaryen \
+             version {} or pass --download-wasm-opt for the Wasm to match other builds",
+            PINNED_VERSION
+        )
+        .bold()
+    );
+}
+
+/// The `wasm-opt --version` output of the executable.
+pub(crate) fn version_output(wasm_opt: &Path) -> Result<String> {
+    let output = Command::new(wasm_opt)
+        .arg("--version")
+        .output()
+        .context(format!("Failed to execute {}", wasm_opt.display()))?;
+    if !output.status.success() {
+        anyhow::bail!("`{} --version` failed", wasm_opt.display())
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The release number in the output of `wasm-opt --version`, e.g. `wasm-opt version 101
+/// (version_101)`.
+fn parse_version(output: &str) -> Option<u32> {
+    let (_, version) = output.split_once("version ")?;
+    let digits: String = version.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+/// Fails unless the `wasm-opt` is a release of binaryen known not to miscompile contracts.
+fn check_version(wasm_opt: &Path) -> Result<u32> {
+    let output = version_output(wasm_opt)?;
+    let version = parse_version(&output).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Unable to parse the version of {} from `{}`",
+            wasm_opt.display(),
+            output
+        )
+    })?;
+    if version < MIN_VERSION {
+        anyhow::bail!(
+            "{} is binaryen version {}, which miscompiles contracts. Install version {} or newer, \
+             or supply another one with --wasm-opt-path",
+            wasm_opt.display(),
+            version,
+            MIN_VERSION
+        )
+    }
+    if version > MAX_TESTED_VERSION {
+        log::warn!(
+            "{} is binaryen version {}, newer than version {} the builds were tested with",
+            wasm_opt.display(),
+            version,
+            MAX_TESTED_VERSION
+        );
+    }
+    Ok(version)
+}
+
+/// The name and the pinned SHA-256 digest of the release archive of the pinned binaryen version
+/// for this platform.
+fn release_archive() -> Option<(String, &'static str)> {
+    // FIXME: pin the SHA-256 digests of the published archives.
+    let (platform, sha256) = match (env::consts::ARCH, env::consts::OS) {
+        ("x86_64", "linux") => (
+            "x86_64-linux",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+        ),
+        ("x86_64", "macos") => (
+            "x86_64-macos",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+        ),
+        ("aarch64", "macos") => (
+            "arm64-macos",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+        ),
+        ("x86_64", "windows") => (
+            "x86_64-windows",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+        ),
+        _ => return None,
+    };
+    let archive = format!("binaryen-version_{}-{}.tar.gz", PINNED_VERSION, platform);
+    Some((archive, sha256))
+}
+
+/// The directory of the files of cargo-contract in the home directory of the user, e.g. the
+/// state of the node started by `node start`.
+pub(crate) fn download_dir() -> Option<PathBuf> {
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(".cargo-contract"))
+}
+
+/// The `wasm-opt` of the downloaded pinned release.
+fn downloaded() -> Option<PathBuf> {
+    let wasm_opt = format!("wasm-opt{}", env::consts::EXE_SUFFIX);
+    Some(
+        cache_dir()?
+            .join(format!("binaryen-version_{}", PINNED_VERSION))
+            .join("bin")
+            .join(wasm_opt),
+    )
+}
+
+/// Downloads and unpacks the release archive of binaryen, returning its `wasm-opt`.
+fn download() -> Result<PathBuf> {
+    let ((archive, sha256), wasm_opt, dir) = match (release_archive(), downloaded(), cache_dir()) {
+        (Some(archive), Some(wasm_opt), Some(dir)) => (archive, wasm_opt, dir),
+        _ => anyhow::bail!("No release of binaryen can be downloaded for this platform"),
+    };
+    let _downloading = DOWNLOAD
+        .lock()
+        .expect("no build panicked while downloading");
+    if wasm_opt.exists() {
+        return Ok(wasm_opt);
+    }
+    let url = format!(
+        "https://github.com/WebAssembly/binaryen/releases/download/version_{}/{}",
+        PINNED_VERSION, archive
+    );
+    util::download_archive(&url, sha256, &dir, "binaryen")?;
+    if !wasm_opt.exists() {
+        anyhow::bail!("The binaryen release has no {}", wasm_opt.display())
+    }
+    Ok(wasm_opt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn parses_the_version() {
+        assert_eq!(
+            parse_version("wasm-opt version 101 (version_101)"),
+            Some(101)
+        );
+        assert_eq!(parse_version("wasm-opt version 90"), Some(90));
+        assert_eq!(parse_version("wasm-opt"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_miscompiling_versions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        crate::util::tests::with_tmp_dir(|dir| {
+            let wasm_opt = dir.join("wasm-opt");
+            let fake = |version: u32| -> Result<()> {
+                let script = format!("#!/bin/sh\necho 'wasm-opt version {}'\n", version);
+                fs::write(&wasm_opt, script)?;
+                fs::set_permissions(&wasm_opt, fs::Permissions::from_mode(0o755))?;
+                Ok(())
+            };
+            fake(90)?;
+            assert!(check_version(&wasm_opt).is_err());
+            fake(101)?;
+            assert_eq!(check_version(&wasm_opt)?, 101);
+            Ok(())
+        })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finds_the_wasm_opt_of_each_build() {
+        use std::os::unix::fs::PermissionsExt;
+
+        crate::util::tests::with_tmp_dir(|dir| {
+            for name in &["wasm-opt-a", "wasm-opt-b"] {
+                let wasm_opt = dir.join(name);
+                fs::write(&wasm_opt, "#!/bin/sh\necho 'wasm-opt version 101'\n")?;
+                fs::set_permissions(&wasm_opt, fs::Permissions::from_mode(0o755))?;
+                let found = WasmOpt {
+                    path: Some(wasm_opt.clone()),
+                    download: false,
+                }
+                .find()?;
+                assert_eq!(found, Some(wasm_opt));
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,272 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    convert::TryFrom,
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use colored::Colorize;
+use structopt::StructOpt;
+
+use super::build;
+#[cfg(feature = "extrinsics")]
+use super::ExtrinsicSigner;
+use crate::{
+    util, workspace::ManifestPath, BuildArtifacts, BuildResult, OptimizationPasses, Target,
+    UnstableFlags, UnstableOptions, Verbosity, VerbosityFlags,
+};
+#[cfg(feature = "extrinsics")]
+use crate::{ExtrinsicOpts, HexData};
+
+/// Rebuilds the contract whenever its sources change, and optionally redeploys it.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "watch")]
+pub(crate) struct WatchCommand {
+    /// Path to the Cargo.toml of the contract to build
+    #[structopt(long, parse(from_os_str))]
+    manifest_path: Option<PathBuf>,
+    /// Which build artifacts to generate, `all` or `code-only`
+    #[structopt(
+        long = "generate",
+        default_value = "all",
+        value_name = "all | code-only"
+    )]
+    build_artifact: BuildArtifacts,
+    /// The binaryen optimization passes to run on the Wasm, `0` to `4`, `s` or `z`
+    #[structopt(long, default_value = "z", value_name = "0 | 1 | 2 | 3 | 4 | s | z")]
+    optimization_passes: OptimizationPasses,
+    #[structopt(flatten)]
+    verbosity: VerbosityFlags,
+    #[structopt(flatten)]
+    unstable_options: UnstableOptions,
+    /// Seconds to wait between checking the sources for changes
+    #[structopt(long, default_value = "1")]
+    interval: u64,
+    #[cfg(feature = "extrinsics")]
+    #[structopt(flatten)]
+    pub redeploy: RedeployOpts,
+}
+
+/// Options for redeploying the contract after each successful build.
+#[cfg(feature = "extrinsics")]
+#[derive(Debug, StructOpt)]
+pub(crate) struct RedeployOpts {
+    /// Upload and instantiate the contract after each successful build, by default on the node at
+    /// `ws://localhost:9944`
+    #[structopt(long)]
+    redeploy: bool,
+    #[structopt(flatten)]
+    pub extrinsic_opts: ExtrinsicOpts,
+    /// Hex encoded data to call the constructor of the redeployed contract with
+    #[structopt(long, requires = "redeploy", default_value = "")]
+    data: HexData,
+    /// Transfers an initial balance to the redeployed contract
+    #[structopt(long, requires = "redeploy", default_value = "0")]
+    endowment: super::BalanceVariant,
+    /// Maximum amount of gas to be used for instantiating the contract
+    #[structopt(name = "gas", long, requires = "redeploy", default_value = "500000000")]
+    gas_limit: u64,
+    /// Hex encoded data of a message to call the redeployed contract with, e.g. a smoke test
+    /// reverting on failure
+    #[structopt(long, requires = "redeploy")]
+    smoke_call: Option<HexData>,
+}
+
+#[cfg(feature = "extrinsics")]
+impl RedeployOpts {
+    /// The signer of the redeployments, if enabled.
+    fn signer(&self) -> Result<Option<ExtrinsicSigner>> {
+        if !self.redeploy {
+            return Ok(None);
+        }
+        if self.extrinsic_opts.offline_params()?.is_some() {
+            anyhow::bail!("Redeploying requires a connection to the node, remove --offline")
+        }
+        self.extrinsic_opts.signer().map(Some)
+    }
+
+    /// Uploads and instantiates the built contract, unless its code did not change since the
+    /// last redeployment, and calls the smoke test message.
+    fn redeploy(
+        &self,
+        signer: &ExtrinsicSigner,
+        build: &BuildResult,
+        deployed: &mut Option<sp_core::H256>,
+    ) -> Result<()> {
+        let output = self.extrinsic_opts.submit.output;
+        let wasm = build
+            .dest_wasm
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("The build produced no Wasm to redeploy"))?;
+        let uploaded = super::execute_deploy(&self.extrinsic_opts, signer, Some(wasm))?;
+        if *deployed == Some(uploaded.code_hash) {
+            log::info!("The code did not change, skipping the redeployment");
+            return Ok(());
+        }
+        println!("\t{}", output.format(&uploaded, |result| result.display())?);
+
+        let instantiated = super::execute_instantiate(
+            &self.extrinsic_opts,
+            signer,
+            self.endowment.clone(),
+            self.gas_limit,
+            uploaded.code_hash,
+            self.data.clone(),
+        )?;
+        let contract = instantiated.contract.clone().ok_or_else(|| {
+            anyhow::anyhow!("Redeploying requires awaiting the inclusion of the extrinsics")
+        })?;
+        *deployed = Some(uploaded.code_hash);
+        println!(
+            "\t{}",
+            output.format(&instantiated, |result| format!(
+                "Contract account: {}",
+                result.contract.as_ref().unwrap_or(&result.predicted)
+            ))?
+        );
+
+        if let Some(ref data) = self.smoke_call {
+            let contract = super::Address {
+                account: contract,
+                format: Default::default(),
+            };
+            let called = super::execute_call(
+                &self.extrinsic_opts,
+                signer,
+                &contract,
+                "0".parse()?,
+                super::GasLimit::Auto,
+                super::GasMargin::default(),
+                false,
+                data.clone(),
+            )?;
+            println!("\t{}", output.format(&called, |result| result.display())?);
+        }
+        Ok(())
+    }
+}
+
+/// The paths, modification times and sizes of the source files of the project, skipping the
+/// `target` directory and hidden files.
+fn fingerprint(dir: &Path) -> Result<Vec<(PathBuf, SystemTime, u64)>> {
+    let mut files = Vec::new();
+    for path in util::project_files(dir)? {
+        let metadata = fs::metadata(&path)?;
+        files.push((path, metadata.modified()?, metadata.len()));
+    }
+    Ok(files)
+}
+
+/// Prints the error the same way as a failing command, without exiting.
+fn report(err: anyhow::Error) {
+    eprintln!(
+        "{} {}",
+        "ERROR:".bright_red().bold(),
+        format!("{:?}", err).bright_red()
+    );
+}
+
+impl WatchCommand {
+    fn build(&self, manifest_path: &ManifestPath) -> Result<BuildResult> {
+        let unstable_flags = UnstableFlags::try_from(&self.unstable_options)?;
+        let verbosity = Option::<Verbosity>::try_from(&self.verbosity)?;
+        build::execute(
+            manifest_path,
+            verbosity,
+            true,
+            self.build_artifact,
+            Target::Wasm,
+            self.optimization_passes,
+            false,
+            None,
+            false,
+            &Default::default(),
+            &Default::default(),
+            unstable_flags,
+            &Default::default(),
+            &Default::default(),
+        )
+    }
+
+    /// Builds the contract, and rebuilds it whenever its sources change, until interrupted.
+    ///
+    /// Failing builds and redeployments are reported and the watching continues.
+    pub fn exec(&self) -> Result<String> {
+        let manifest_path = ManifestPath::try_from(self.manifest_path.as_ref())?;
+        let dir = manifest_path
+            .directory()
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf();
+        #[cfg(feature = "extrinsics")]
+        let signer = self.redeploy.signer()?;
+        #[cfg(feature = "extrinsics")]
+        let mut deployed = None;
+        let mut last = None;
+        loop {
+            let current = fingerprint(&dir)?;
+            if last.as_ref() != Some(&current) {
+                if last.is_some() {
+                    println!("\t{}", "Sources changed, rebuilding".bold());
+                }
+                match self.build(&manifest_path) {
+                    Ok(result) => {
+                        println!("\t{}", result.display());
+                        #[cfg(feature = "extrinsics")]
+                        if let Some(ref signer) = signer {
+                            if let Err(err) = self.redeploy.redeploy(signer, &result, &mut deployed)
+                            {
+                                report(err)
+                            }
+                        }
+                    }
+                    Err(err) => report(err),
+                }
+                // Taken after the build, so files it touches do not trigger another one.
+                last = Some(fingerprint(&dir)?);
+                println!("\tWatching {} for changes", dir.display());
+            }
+            thread::sleep(Duration::from_secs(self.interval));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprint_ignores_target_and_hidden_files() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            fs::create_dir_all(dir.join("src"))?;
+            fs::create_dir_all(dir.join("target"))?;
+            fs::write(dir.join("src/lib.rs"), "")?;
+            let before = fingerprint(dir)?;
+
+            fs::write(dir.join("target/contract.wasm"), "wasm")?;
+            fs::write(dir.join(".editor.swp"), "")?;
+            assert_eq!(fingerprint(dir)?, before);
+
+            fs::write(dir.join("src/lib.rs"), "// changed")?;
+            assert_ne!(fingerprint(dir)?, before);
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::marker::PhantomData;
+
+use anyhow::Result;
+use codec::{Encode, Output};
+use sp_core::{crypto::AccountId32, storage::StorageKey, H256};
+use subxt::{
+    sudo::SudoCall, Call, DefaultNodeRuntime, Encoded, EventsDecoder, Metadata, MetadataError,
+    Store,
+};
+
+use super::{
+    multisig::{AsMultiCall, CallFile, Multisig, MultisigDetails, Timepoint},
+    sign,
+};
+use crate::ExtrinsicOpts;
+
+/// A call wrapped for dispatching from another origin than the signer, ready to be signed.
+pub(crate) struct WrappedCall {
+    /// The encoded outermost call.
+    pub call: Encoded,
+    sudo: bool,
+    proxy: bool,
+    /// The multisig approving the call, together with the call it wraps and its hash.
+    multisig: Option<(Multisig, Encoded, H256)>,
+}
+
+impl WrappedCall {
+    /// Wraps the encoded call as requested by the extrinsic options: in a `Sudo::sudo` call for
+    /// `--sudo`, then in a `Proxy::proxy` call for `--proxy`, then in a `Multisig::as_multi` call
+    /// for `--multisig-threshold`.
+    ///
+    /// This way a multisig account can act as the proxy of another account, either of which may
+    /// be the sudo key.
+    pub fn new(
+        metadata: &Metadata,
+        call: Encoded,
+        extrinsic_opts: &ExtrinsicOpts,
+        signer: &AccountId32,
+    ) -> Result<Self> {
+        let mut call = call;
+        if extrinsic_opts.sudo {
+            call = sign::encode_call(
+                metadata,
+                SudoCall::<DefaultNodeRuntime> {
+                    _runtime: PhantomData,
+                    call: &call,
+                },
+            )?;
+        }
+        if let Some(ref real) = extrinsic_opts.proxy {
+            call = sign::encode_call(
+                metadata,
+                ProxyCall {
+                    real: real.account.clone(),
+                    call,
+                },
+            )?;
+        }
+        let mut multisig = None;
+        if let Some(wrapper) = extrinsic_opts.multisig.multisig(signer)? {
+            let as_multi = wrapper.as_multi(call.clone());
+            let call_hash = as_multi.call_hash();
+            multisig = Some((wrapper, call, call_hash));
+            call = sign::encode_call(metadata, as_multi)?;
+        }
+        Ok(WrappedCall {
+            call,
+            sudo: extrinsic_opts.sudo,
+            proxy: extrinsic_opts.proxy.is_some(),
+            multisig,
+        })
+    }
+
+    /// Registers the types required for decoding the events of the wrapping calls.
+    pub fn events_decoder(&self, decoder: &mut EventsDecoder<DefaultNodeRuntime>) {
+        if self.sudo {
+            SudoCall::<DefaultNodeRuntime>::events_decoder(decoder);
+        }
+        if self.proxy {
+            ProxyCall::events_decoder(decoder);
+        }
+        if self.multisig.is_some() {
+            AsMultiCall::events_decoder(decoder);
+        }
+    }
+
+    /// Returns the details of the multisig operation, if the call is dispatched from a multisig
+    /// account.
+    pub fn multisig_details(&self, included_at: Option<Timepoint>) -> Option<MultisigDetails> {
+        self.multisig
+            .as_ref()
+            .map(|(multisig, _, call_hash)| multisig.details(*call_hash, included_at))
+    }
+
+    /// Returns the call file of the multisig operation for the other signatories of the
+    /// `signer`, if the call is dispatched from a multisig account and the timepoint of its
+    /// first approval is known.
+    pub fn multisig_call_file(
+        &self,
+        signer: &AccountId32,
+        included_at: Option<Timepoint>,
+    ) -> Option<CallFile> {
+        let (multisig, call, _) = self.multisig.as_ref()?;
+        multisig.call_file(signer, &call.0, included_at)
+    }
+}
+
+/// The `Proxy::proxy` call, dispatching the wrapped call from the `real` account the signer is a
+/// proxy for.
+struct ProxyCall {
+    real: AccountId32,
+    call: Encoded,
+}
+
+impl Encode for ProxyCall {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.real.encode_to(dest);
+        // Let the runtime pick any proxy type the signer has been granted.
+        None::<u8>.encode_to(dest);
+        // The boxed call is encoded as is, without a length prefix.
+        dest.write(&self.call.0);
+    }
+}
+
+impl Call<DefaultNodeRuntime> for ProxyCall {
+    const MODULE: &'static str = "Proxy";
+    const FUNCTION: &'static str = "proxy";
+
+    fn events_decoder(decoder: &mut EventsDecoder<DefaultNodeRuntime>) {
+        decoder.register_type_size::<u8>("ProxyType");
+    }
+}
+
+/// The account of the sudo key, from the `Sudo::Key` storage.
+pub(crate) struct SudoKeyStore;
+
+impl Encode for SudoKeyStore {
+    fn encode_to<W: Output>(&self, _dest: &mut W) {}
+}
+
+impl Store<DefaultNodeRuntime> for SudoKeyStore {
+    const MODULE: &'static str = "Sudo";
+    const FIELD: &'static str = "Key";
+    type Returns = AccountId32;
+
+    fn prefix(metadata: &Metadata) -> Result<StorageKey, MetadataError> {
+        Ok(metadata
+            .module(Self::MODULE)?
+            .storage(Self::FIELD)?
+            .prefix())
+    }
+
+    fn key(&self, metadata: &Metadata) -> Result<StorageKey, MetadataError> {
+        Ok(metadata
+            .module(Self::MODULE)?
+            .storage(Self::FIELD)?
+            .plain()?
+            .key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proxy_call_embeds_call_without_length_prefix() {
+        let call = ProxyCall {
+            real: AccountId32::from([1u8; 32]),
+            call: Encoded(vec![7, 8, 9]),
+        };
+        let mut expected = vec![1u8; 32];
+        expected.extend_from_slice(&[0, 7, 8, 9]);
+        assert_eq!(call.encode(), expected);
+    }
+
+    #[test]
+    fn sudo_call_embeds_call_without_length_prefix() {
+        let inner = Encoded(vec![7, 8, 9]);
+        let call = SudoCall::<DefaultNodeRuntime> {
+            _runtime: PhantomData,
+            call: &inner,
+        };
+        assert_eq!(call.encode(), vec![7, 8, 9]);
+    }
+}
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::ManifestPath;
+use crate::{CargoOptions, ManifestPath, Target};
 use anyhow::{Context, Result};
 use cargo_metadata::{Metadata as CargoMetadata, MetadataCommand, Package};
 use semver::Version;
@@ -30,21 +30,48 @@ pub struct CrateMetadata {
     pub cargo_meta: cargo_metadata::Metadata,
     pub package_name: String,
     pub root_package: Package,
+    /// The code compiled by cargo, an ELF binary for the RISC-V target.
     pub original_wasm: PathBuf,
+    /// The code of the contract, a PolkaVM program for the RISC-V target.
     pub dest_wasm: PathBuf,
+    pub target: Target,
     pub ink_version: Version,
     pub documentation: Option<Url>,
     pub homepage: Option<Url>,
     pub user: Option<Map<String, Value>>,
+    /// The maximum size of the optimized Wasm in kilobytes, from the `max-contract-size` of the
+    /// `[package.metadata.contract]` section.
+    pub max_contract_size: Option<u64>,
+    /// The commands to run after each build, from the `post-build` of the
+    /// `[package.metadata.contract]` section.
+    pub post_build: Vec<String>,
+    /// The custom sections to keep in the Wasm instead of stripping them, from the
+    /// `keep-sections` of the `[package.metadata.contract]` section.
+    pub keep_sections: Vec<String>,
     pub target_directory: PathBuf,
 }
 
 impl CrateMetadata {
-    /// Parses the contract manifest and returns relevant metadata.
+    /// Parses the contract manifest and returns relevant metadata, of the Wasm target.
     pub fn collect(manifest_path: &ManifestPath) -> Result<Self> {
-        let (metadata, root_package) = get_cargo_metadata(manifest_path)?;
+        Self::collect_for(manifest_path, Target::Wasm, &CargoOptions::default())
+    }
+
+    /// Parses the contract manifest and returns relevant metadata, of the `target` and in the
+    /// target directory of the cargo options, if any. `cargo metadata` is run `--offline` if they
+    /// are.
+    pub(crate) fn collect_for(
+        manifest_path: &ManifestPath,
+        target: Target,
+        cargo_options: &CargoOptions,
+    ) -> Result<Self> {
+        let (metadata, root_package) = get_cargo_metadata(manifest_path, cargo_options)?;
 
-        let mut target_directory = metadata.target_directory.clone();
+        let mut target_directory = match cargo_options.target_dir {
+            // Cargo is invoked in other directories, so a relative path is resolved here.
+            Some(ref target_dir) => std::env::current_dir()?.join(target_dir),
+            None => metadata.target_directory.clone(),
+        };
         target_directory.push("ink");
 
         // Normalize the package name.
@@ -52,15 +79,15 @@ impl CrateMetadata {
 
         // {target_dir}/wasm32-unknown-unknown/release/{package_name}.wasm
         let mut original_wasm = target_directory.clone();
-        original_wasm.push("wasm32-unknown-unknown");
+        original_wasm.push(target.llvm_target());
         original_wasm.push("release");
         original_wasm.push(package_name.clone());
-        original_wasm.set_extension("wasm");
+        original_wasm.set_extension(target.source_extension());
 
         // {target_dir}/{package_name}.wasm
         let mut dest_wasm = target_directory.clone();
         dest_wasm.push(package_name.clone());
-        dest_wasm.set_extension("wasm");
+        dest_wasm.set_extension(target.dest_extension());
 
         let ink_version = metadata
             .packages
@@ -77,7 +104,7 @@ impl CrateMetadata {
             })
             .ok_or(anyhow::anyhow!("No 'ink_lang' dependency found"))?;
 
-        let (documentation, homepage, user) = get_cargo_toml_metadata(manifest_path)?;
+        let extra = get_cargo_toml_metadata(manifest_path)?;
 
         let crate_metadata = CrateMetadata {
             manifest_path: manifest_path.clone(),
@@ -86,10 +113,14 @@ impl CrateMetadata {
             package_name,
             original_wasm,
             dest_wasm,
+            target,
             ink_version,
-            documentation,
-            homepage,
-            user,
+            documentation: extra.documentation,
+            homepage: extra.homepage,
+            user: extra.user,
+            max_contract_size: extra.max_contract_size,
+            post_build: extra.post_build,
+            keep_sections: extra.keep_sections,
             target_directory,
         };
         Ok(crate_metadata)
@@ -97,10 +128,14 @@ impl CrateMetadata {
 }
 
 /// Get the result of `cargo metadata`, together with the root package id.
-fn get_cargo_metadata(manifest_path: &ManifestPath) -> Result<(CargoMetadata, Package)> {
+fn get_cargo_metadata(
+    manifest_path: &ManifestPath,
+    cargo_options: &CargoOptions,
+) -> Result<(CargoMetadata, Package)> {
     let mut cmd = MetadataCommand::new();
     let metadata = cmd
         .manifest_path(manifest_path.as_ref())
+        .other_options(cargo_options.network_args())
         .exec()
         .context("Error invoking `cargo metadata`")?;
     let root_package_id = metadata
@@ -120,10 +155,37 @@ fn get_cargo_metadata(manifest_path: &ManifestPath) -> Result<(CargoMetadata, Pa
     Ok((metadata, root_package))
 }
 
+/// Extra metadata not available via `cargo metadata`.
+struct ExtraMetadata {
+    documentation: Option<Url>,
+    homepage: Option<Url>,
+    user: Option<Map<String, Value>>,
+    max_contract_size: Option<u64>,
+    post_build: Vec<String>,
+    keep_sections: Vec<String>,
+}
+
+/// Converts the user defined section from TOML to JSON verbatim, with dates and times as the
+/// strings they are written as.
+fn toml_to_json(value: &value::Value) -> Value {
+    match value {
+        value::Value::String(string) => Value::from(string.clone()),
+        value::Value::Integer(integer) => Value::from(*integer),
+        value::Value::Float(float) => Value::from(*float),
+        value::Value::Boolean(boolean) => Value::from(*boolean),
+        value::Value::Datetime(datetime) => Value::from(datetime.to_string()),
+        value::Value::Array(values) => Value::Array(values.iter().map(toml_to_json).collect()),
+        value::Value::Table(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, value)| (key.clone(), toml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
 /// Read extra metadata not available via `cargo metadata` directly from `Cargo.toml`
-fn get_cargo_toml_metadata(
-    manifest_path: &ManifestPath,
-) -> Result<(Option<Url>, Option<Url>, Option<Map<String, Value>>)> {
+fn get_cargo_toml_metadata(manifest_path: &ManifestPath) -> Result<ExtraMetadata> {
     let toml = fs::read_to_string(manifest_path)?;
     let toml: value::Table = toml::from_str(&toml)?;
 
@@ -141,17 +203,62 @@ fn get_cargo_toml_metadata(
     let documentation = get_url("documentation")?;
     let homepage = get_url("homepage")?;
 
-    let user = toml
+    let contract = toml
         .get("package")
         .and_then(|v| v.get("metadata"))
-        .and_then(|v| v.get("contract"))
+        .and_then(|v| v.get("contract"));
+
+    let user = contract
         .and_then(|v| v.get("user"))
-        .and_then(|v| v.as_table())
+        .map(|v| match toml_to_json(v) {
+            Value::Object(user) => Ok(user),
+            _ => Err(anyhow::anyhow!(
+                "metadata.contract.user should be a table, copied into the metadata"
+            )),
+        })
+        .transpose()?;
+
+    let max_contract_size = contract
+        .and_then(|v| v.get("max-contract-size"))
         .map(|v| {
-            // convert user defined section from toml to json
-            serde_json::to_string(v).and_then(|json| serde_json::from_str(&json))
+            v.as_integer()
+                .filter(|size| *size > 0)
+                .map(|size| size as u64)
+                .ok_or(anyhow::anyhow!(
+                    "max-contract-size should be a positive number of kilobytes"
+                ))
         })
         .transpose()?;
 
-    Ok((documentation, homepage, user))
+    let strings = |field_name, what| -> Result<Vec<String>> {
+        contract
+            .and_then(|v| v.get(field_name))
+            .map(|v| {
+                v.as_array()
+                    .and_then(|values| {
+                        values
+                            .iter()
+                            .map(|value| value.as_str().map(ToString::to_string))
+                            .collect::<Option<Vec<_>>>()
+                    })
+                    .ok_or(anyhow::anyhow!(
+                        "{} should be an array of {}",
+                        field_name,
+                        what
+                    ))
+            })
+            .transpose()
+            .map(Option::unwrap_or_default)
+    };
+    let post_build = strings("post-build", "commands")?;
+    let keep_sections = strings("keep-sections", "section names")?;
+
+    Ok(ExtraMetadata {
+        documentation,
+        homepage,
+        user,
+        max_contract_size,
+        post_build,
+        keep_sections,
+    })
 }
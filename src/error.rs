@@ -0,0 +1,203 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{error::Error as StdError, fmt};
+
+/// Encoding or decoding a value with the types of the metadata of a contract failed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TranscodeError {
+    /// The path of the field the error occurred at, e.g. `owner.balances[2]`, empty for the value
+    /// itself.
+    pub path: String,
+    pub message: String,
+}
+
+#[cfg_attr(not(feature = "extrinsics"), allow(dead_code))]
+impl TranscodeError {
+    pub(crate) fn new(message: impl fmt::Display) -> Self {
+        TranscodeError {
+            path: String::new(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Adds the `segment` to the front of the path, e.g. a field name or `[2]`.
+    pub(crate) fn at(mut self, segment: &str) -> Self {
+        self.path = match self.path.chars().next() {
+            None => segment.to_string(),
+            Some('[') => format!("{}{}", segment, self.path),
+            Some(_) => format!("{}.{}", segment, self.path),
+        };
+        self
+    }
+}
+
+impl fmt::Display for TranscodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.as_str() {
+            "" => write!(f, "{}", self.message),
+            path => write!(f, "{} at `{}`", self.message, path),
+        }
+    }
+}
+
+/// A request to the node failed, or the node did not include the extrinsic.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RpcError {
+    pub message: String,
+}
+
+/// Compiling or post-processing the contract failed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildError {
+    pub message: String,
+}
+
+/// The key of the signer could not be derived, loaded or used for signing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignerError {
+    pub message: String,
+}
+
+macro_rules! message_error {
+    ($($error:ident),*) => {$(
+        #[cfg_attr(not(feature = "extrinsics"), allow(dead_code))]
+        impl $error {
+            pub(crate) fn new(message: impl fmt::Display) -> Self {
+                $error {
+                    message: message.to_string(),
+                }
+            }
+        }
+
+        impl fmt::Display for $error {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.message)
+            }
+        }
+
+        impl StdError for $error {}
+    )*};
+}
+
+message_error!(RpcError, BuildError, SignerError);
+
+impl StdError for TranscodeError {}
+
+/// The errors of the library, by their cause.
+#[derive(Debug)]
+pub enum Error {
+    /// Invalid values for the types of the metadata, e.g. the arguments of a message.
+    Transcode(TranscodeError),
+    /// The node is unreachable or failed a request.
+    Rpc(RpcError),
+    /// The contract does not build.
+    Build(BuildError),
+    /// The secret key of the signer is invalid or could not be loaded.
+    Signer(SignerError),
+    /// Any other error, e.g. failing to read a file.
+    Other(anyhow::Error),
+}
+
+impl Error {
+    /// The exit code of `cargo contract` failing with the error: `2` for invalid input, `3` for
+    /// a failing node, `4` for a failing build and `1` otherwise. Bugs panic with `101`.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Transcode(_) | Error::Signer(_) => 2,
+            Error::Rpc(_) => 3,
+            Error::Build(_) => 4,
+            Error::Other(_) => 1,
+        }
+    }
+}
+
+/// The first error of the type in the chain of causes.
+fn cause<E: StdError + Clone + 'static>(err: &anyhow::Error) -> Option<E> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<E>())
+        .cloned()
+}
+
+/// Classifies the error by the first typed error in its chain of causes, if any.
+fn classify(err: &anyhow::Error) -> Option<Error> {
+    cause(err)
+        .map(Error::Transcode)
+        .or_else(|| cause(err).map(Error::Signer))
+        .or_else(|| cause(err).map(Error::Rpc))
+        .or_else(|| cause(err).map(Error::Build))
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<Error>() {
+            Ok(err) => err,
+            Err(err) => classify(&err).unwrap_or(Error::Other(err)),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Transcode(err) => err.fmt(f),
+            Error::Rpc(err) => err.fmt(f),
+            Error::Build(err) => err.fmt(f),
+            Error::Signer(err) => err.fmt(f),
+            Error::Other(err) => err.fmt(f),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Other(err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+/// The exit code of `cargo contract` failing with the error.
+pub(crate) fn exit_code(err: &anyhow::Error) -> i32 {
+    // E.g. the error of building with the `ContractBuilder`, classified already.
+    if let Some(err) = err.chain().find_map(|cause| cause.downcast_ref::<Error>()) {
+        return err.exit_code();
+    }
+    classify(err).map_or(1, |err| err.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn classifies_errors_by_their_cause() {
+        let transcode = TranscodeError::new("Invalid address").at("owner").at("[1]");
+        assert_eq!(transcode.to_string(), "Invalid address at `[1].owner`");
+        let err = Err::<(), _>(transcode)
+            .context("Invalid argument of `transfer`")
+            .unwrap_err();
+        assert_eq!(exit_code(&err), 2);
+        assert!(matches!(Error::from(err), Error::Transcode(ref err) if err.path == "[1].owner"));
+
+        let err = anyhow::Error::new(RpcError::new("Connection refused")).context("Failed to call");
+        assert_eq!(exit_code(&err), 3);
+        assert_eq!(exit_code(&anyhow::anyhow!("No such file")), 1);
+        assert!(matches!(Error::from(anyhow::anyhow!("")), Error::Other(_)));
+    }
+}
@@ -0,0 +1,1963 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+#[cfg(feature = "extrinsics")]
+pub mod api;
+mod cmd;
+mod crate_metadata;
+mod error;
+mod logging;
+mod progress;
+mod util;
+mod workspace;
+
+use self::workspace::ManifestPath;
+
+pub use crate::cmd::{build::ContractBuilder, extract::ContractBundle};
+use crate::cmd::{BuildCommand, CheckCommand};
+pub use crate::error::{BuildError, Error, RpcError, SignerError, TranscodeError};
+pub use crate::progress::{Progress, ProgressHook};
+
+#[cfg(feature = "extrinsics")]
+use sp_core::H256;
+use std::{convert::TryFrom, path::PathBuf, time::Duration};
+
+use anyhow::Result;
+use colored::Colorize;
+use structopt::{clap, StructOpt};
+
+#[derive(Debug, StructOpt)]
+#[structopt(bin_name = "cargo")]
+pub(crate) enum Opts {
+    /// Utilities to develop Wasm smart contracts.
+    #[structopt(name = "contract")]
+    #[structopt(setting = clap::AppSettings::UnifiedHelpMessage)]
+    #[structopt(setting = clap::AppSettings::DeriveDisplayOrder)]
+    #[structopt(setting = clap::AppSettings::DontCollapseArgsInUsage)]
+    Contract(ContractArgs),
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct ContractArgs {
+    #[structopt(subcommand)]
+    cmd: Command,
+    /// The level of the log written to stderr, overriding the `RUST_LOG` environment variable
+    #[structopt(
+        long,
+        global = true,
+        value_name = "off | error | warn | info | debug | trace"
+    )]
+    log_level: Option<tracing_subscriber::filter::LevelFilter>,
+    /// The format of the log, `json` writing an object per line
+    #[structopt(
+        long,
+        global = true,
+        default_value = "text",
+        value_name = "text | json"
+    )]
+    log_format: logging::LogFormat,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct HexData(pub Vec<u8>);
+
+#[cfg(feature = "extrinsics")]
+impl std::str::FromStr for HexData {
+    type Err = hex::FromHexError;
+
+    fn from_str(input: &str) -> std::result::Result<Self, Self::Err> {
+        hex::decode(input.trim_start_matches("0x")).map(HexData)
+    }
+}
+
+/// Arguments required for creating and sending an extrinsic to a substrate node
+#[cfg(feature = "extrinsics")]
+#[derive(Debug, StructOpt)]
+pub(crate) struct ExtrinsicOpts {
+    #[structopt(flatten)]
+    submit: SubmitOpts,
+    #[structopt(flatten)]
+    signer_opts: SignerOpts,
+    /// Url of a JSON-RPC service holding the key of the `--remote-account`, which the signing
+    /// payloads are forwarded to, instead of signing locally.
+    #[structopt(
+        long,
+        requires = "remote-account",
+        conflicts_with_all = &["suri", "keyfile"]
+    )]
+    remote_signer: Option<url::Url>,
+    /// SS58 address of the account signing via the `--remote-signer`.
+    #[structopt(long, parse(try_from_str = cmd::parse_address))]
+    remote_account: Option<cmd::Address>,
+    /// SS58 address of an account whose key is held by an air-gapped signer, e.g. Polkadot
+    /// Vault, when signing `--offline`.
+    ///
+    /// The signing payload is shown as QR codes in the Universal Offline Signatures format, and
+    /// the signature the signer returns is scanned or typed back.
+    #[structopt(
+        long,
+        parse(try_from_str = cmd::parse_address),
+        requires = "offline",
+        conflicts_with_all = &["suri", "keyfile", "remote-signer"]
+    )]
+    qr_signer: Option<cmd::Address>,
+    /// Nonce to sign the extrinsic with, instead of fetching the next nonce of the account from
+    /// the node.
+    ///
+    /// Allows submitting several extrinsics without waiting for each to be included, or replacing
+    /// a stuck extrinsic.
+    #[structopt(long)]
+    nonce: Option<u32>,
+    /// Number of blocks the extrinsic remains valid for, rounded up to a power of two. Defaults
+    /// to 64.
+    #[structopt(long, conflicts_with = "immortal")]
+    lifetime: Option<u64>,
+    /// Sign an immortal extrinsic, which remains valid forever.
+    #[structopt(long)]
+    immortal: bool,
+    /// Tip for the block author, to increase the priority of the extrinsic.
+    ///
+    /// Either in the smallest unit of the chain's token, or denominated in the token, e.g.
+    /// `0.01UNIT`.
+    #[structopt(long, default_value = "0")]
+    tip: cmd::BalanceVariant,
+    /// Pay the transaction fee in this asset instead of the native token, on chains charging
+    /// fees via `ChargeAssetTxPayment`.
+    #[structopt(long)]
+    fee_asset: Option<u32>,
+    /// Dispatch the call with the root origin via `Sudo::sudo`, which requires the sudo key to
+    /// sign it.
+    #[structopt(long)]
+    sudo: bool,
+    /// Dispatch the call from this account, which the signer is a proxy for, via `Proxy::proxy`.
+    #[structopt(long, parse(try_from_str = cmd::parse_address))]
+    proxy: Option<cmd::Address>,
+    #[structopt(flatten)]
+    multisig: cmd::MultisigOpts,
+    #[structopt(flatten)]
+    offline: cmd::OfflineOpts,
+    /// Submit extrinsics transferring value without asking for confirmation, which is otherwise
+    /// required on chains other than development chains.
+    #[structopt(long, short)]
+    yes: bool,
+}
+
+/// The key pair signing extrinsics or attestations, derived from a secret key URI or loaded from
+/// a keystore file
+#[cfg(feature = "extrinsics")]
+#[derive(Debug, StructOpt)]
+pub(crate) struct SignerOpts {
+    /// Secret key URI of the signer's account.
+    ///
+    /// Read from the `CARGO_CONTRACT_SURI` environment variable if the flag is not supplied.
+    #[structopt(
+        name = "suri",
+        long,
+        short,
+        env = "CARGO_CONTRACT_SURI",
+        hide_env_values = true
+    )]
+    suri: Option<String>,
+    /// JSON keystore file with the key pair of the account, as exported by polkadot.js, to use
+    /// instead of a secret key URI.
+    #[structopt(long, parse(from_os_str), conflicts_with = "suri")]
+    keyfile: Option<PathBuf>,
+    /// Password for the secret key, or for decrypting the keystore file.
+    ///
+    /// Prefer `--password-file` or the interactive prompt, which is shown for an encrypted
+    /// keystore file or a secret key URI ending in `///`, so the password does not appear in
+    /// process listings.
+    #[structopt(name = "password", long, short)]
+    password: Option<String>,
+    /// File containing the password for the secret key or the keystore file.
+    #[structopt(long, parse(from_os_str), conflicts_with = "password")]
+    password_file: Option<PathBuf>,
+    /// Signature scheme of the key pair derived from the secret key URI. The scheme of a
+    /// keystore file is read from the file.
+    #[structopt(
+        long,
+        default_value = "sr25519",
+        value_name = "sr25519 | ed25519 | ecdsa"
+    )]
+    scheme: cmd::Scheme,
+}
+
+#[cfg(feature = "extrinsics")]
+impl SignerOpts {
+    /// The key pair of the keystore file, or else of the secret key URI, `None` if neither is
+    /// supplied.
+    pub fn key_pair(&self) -> Result<Option<cmd::ExtrinsicSigner>> {
+        let password = match self.password_file {
+            Some(ref path) => Some(cmd::read_password_file(path)?),
+            None => self.password.clone(),
+        };
+        let password = password.as_deref();
+        match (&self.keyfile, &self.suri) {
+            (Some(keyfile), _) => cmd::ExtrinsicSigner::from_keyfile(keyfile, password).map(Some),
+            (None, Some(suri)) => {
+                cmd::ExtrinsicSigner::from_suri(self.scheme, suri, password).map(Some)
+            }
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Arguments required for sending an extrinsic to a substrate node and reporting the result
+#[cfg(feature = "extrinsics")]
+#[derive(Debug, StructOpt)]
+pub(crate) struct SubmitOpts {
+    /// Websockets url of a substrate node.
+    ///
+    /// Supply the flag several times, or a comma separated list, for nodes of the same chain to
+    /// fail over to in this order, should a node be unreachable or the connection drop.
+    #[structopt(
+        name = "url",
+        long = "url",
+        parse(try_from_str),
+        require_delimiter = true,
+        default_value = "ws://localhost:9944"
+    )]
+    urls: Vec<url::Url>,
+    /// Name of the network to connect to: `local`, `rococo-contracts`, `shibuya`, or one of the
+    /// `[networks.<name>]` configured in `.cargo-contract.toml`.
+    ///
+    /// Sets the url, unless `--url` is supplied, and the address format and token of the chain.
+    #[structopt(long)]
+    network: Option<String>,
+    /// The network selected with `--network`, resolved once the configuration is loaded.
+    #[structopt(skip)]
+    network_profile: Option<cmd::Network>,
+    /// Format in which the result is printed.
+    ///
+    /// - `human-readable`: Text intended to be read by a human, the default.
+    ///
+    /// - `json`: A single JSON document, intended for consumption by scripts and CI pipelines.
+    ///
+    /// - `json-pjs`: The JSON document following the conventions of polkadot.js: `camelCase`
+    ///   field names, and decoded values as polkadot.js decodes them, e.g. accounts as SS58
+    ///   addresses and options as `null` or their value.
+    #[structopt(
+        long,
+        default_value = "human-readable",
+        value_name = "human-readable | json | json-pjs",
+        verbatim_doc_comment
+    )]
+    output: OutputType,
+    /// Status of the submitted extrinsic to wait for before returning.
+    ///
+    /// - `broadcast`: Return as soon as the extrinsic has been accepted by the node.
+    ///
+    /// - `in-block`: Wait until the extrinsic is included in a block, the default.
+    ///
+    /// - `finalized`: Wait until the block including the extrinsic has been finalized.
+    #[structopt(
+        long,
+        default_value = "in-block",
+        value_name = "finalized | in-block | broadcast",
+        verbatim_doc_comment
+    )]
+    wait_for: WaitFor,
+    /// PEM file with CA certificates to trust for `wss://` urls, in addition to the common root
+    /// certificates, e.g. of a corporate TLS proxy
+    #[structopt(long, parse(from_os_str))]
+    tls_ca_cert: Option<PathBuf>,
+    /// Print balances in the smallest unit of the chain's token, instead of denominated in it,
+    /// e.g. `1500000000000` instead of `1.5 UNIT`.
+    #[structopt(long)]
+    raw: bool,
+    /// Record the uploads and instantiations in the `deployments.json` of the contract project
+    /// in the current directory, which the library only does on request.
+    #[structopt(skip = true)]
+    record_deployments: bool,
+    /// The hook the statuses of the extrinsics are reported to, which only the library sets.
+    #[structopt(skip)]
+    progress: progress::Reporter,
+}
+
+#[cfg(feature = "extrinsics")]
+impl ExtrinsicOpts {
+    pub fn signer(&self) -> Result<cmd::ExtrinsicSigner> {
+        let mut signer = match (&self.qr_signer, &self.remote_signer) {
+            (Some(address), _) => {
+                cmd::ExtrinsicSigner::air_gapped(self.signer_opts.scheme, address.account.clone())
+            }
+            (None, Some(url)) => {
+                let address = self.remote_account.as_ref().ok_or_else(|| {
+                    SignerError::new("--remote-signer requires the --remote-account")
+                })?;
+                cmd::ExtrinsicSigner::remote(url, address.account.clone())
+            }
+            (None, None) => match self.signer_opts.key_pair()? {
+                Some(signer) => signer,
+                None => {
+                    let error =
+                        "Either --suri, --keyfile, --remote-signer or --qr-signer is required";
+                    return Err(SignerError::new(error).into());
+                }
+            },
+        };
+        if let Some(nonce) = self.nonce {
+            signer.set_nonce(nonce);
+        }
+        Ok(signer)
+    }
+
+    /// Returns the account the call is dispatched from: the account the signer is a proxy for,
+    /// else the multisig account, else the signer's account.
+    pub fn origin(
+        &self,
+        signer: &sp_core::crypto::AccountId32,
+    ) -> Result<sp_core::crypto::AccountId32> {
+        if let Some(ref real) = self.proxy {
+            return Ok(real.account.clone());
+        }
+        match self.multisig.multisig(signer)? {
+            Some(multisig) => Ok(multisig.account().clone()),
+            None => Ok(signer.clone()),
+        }
+    }
+
+    /// Returns the settings for the signed extensions of the extrinsic.
+    pub fn signed_extras(&self) -> cmd::SignedExtras {
+        let mortality = if self.immortal {
+            cmd::Mortality::Immortal
+        } else {
+            cmd::Mortality::Mortal(self.lifetime.unwrap_or(64))
+        };
+        cmd::SignedExtras {
+            mortality,
+            tip: self.tip.clone(),
+            fee_asset: self.fee_asset,
+        }
+    }
+
+    /// Warns about the account addresses supplied as options which were encoded for another
+    /// network than the chain's.
+    pub fn check_address_formats(&self) {
+        self.remote_account
+            .iter()
+            .chain(self.qr_signer.iter())
+            .chain(self.proxy.iter())
+            .for_each(cmd::Address::check_format);
+        self.multisig.check_address_formats();
+    }
+
+    /// Returns the parameters for offline signing, if `--offline` was requested.
+    pub fn offline_params(&self) -> Result<Option<cmd::OfflineParams>> {
+        self.offline.params(self.nonce)
+    }
+}
+
+/// Describes the format in which the result of a command is printed
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum OutputType {
+    /// Output intended to be read by a human
+    HumanReadable,
+    /// Output as a JSON document, intended to be consumed by other programs
+    Json,
+    /// Output as a JSON document following the conventions of polkadot.js, e.g. `camelCase`
+    /// field names, to be compared with or piped into its tooling
+    JsonPjs,
+}
+
+impl std::str::FromStr for OutputType {
+    type Err = String;
+
+    fn from_str(output: &str) -> Result<Self, Self::Err> {
+        match output {
+            "human-readable" => Ok(OutputType::HumanReadable),
+            "json" => Ok(OutputType::Json),
+            "json-pjs" => Ok(OutputType::JsonPjs),
+            _ => Err("Could not parse output type".to_string()),
+        }
+    }
+}
+
+impl OutputType {
+    /// Whether the result is printed as a JSON document, to be parsed by other programs.
+    pub fn is_json(&self) -> bool {
+        matches!(self, OutputType::Json | OutputType::JsonPjs)
+    }
+
+    /// Formats the command result according to the output type.
+    ///
+    /// The supplied closure is used to create the human readable output.
+    pub fn format<T, F>(&self, result: &T, human_readable: F) -> Result<String>
+    where
+        T: serde::Serialize,
+        F: FnOnce(&T) -> String,
+    {
+        match self {
+            OutputType::HumanReadable => Ok(human_readable(result)),
+            OutputType::Json => Ok(serde_json::to_string_pretty(result)?),
+            OutputType::JsonPjs => Ok(serde_json::to_string_pretty(&cmd::to_pjs_value(result)?)?),
+        }
+    }
+}
+
+/// Describes the status of a submitted extrinsic to wait for
+#[cfg(feature = "extrinsics")]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WaitFor {
+    /// The extrinsic has been accepted into the transaction pool of the node
+    Broadcast,
+    /// The extrinsic has been included in a block
+    InBlock,
+    /// The block including the extrinsic has been finalized
+    Finalized,
+}
+
+#[cfg(feature = "extrinsics")]
+impl std::str::FromStr for WaitFor {
+    type Err = String;
+
+    fn from_str(wait_for: &str) -> Result<Self, Self::Err> {
+        match wait_for {
+            "broadcast" => Ok(WaitFor::Broadcast),
+            "in-block" => Ok(WaitFor::InBlock),
+            "finalized" => Ok(WaitFor::Finalized),
+            _ => Err("Could not parse extrinsic status to wait for".to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct VerbosityFlags {
+    /// Print neither the steps of the build nor the progress of cargo
+    #[structopt(short, long)]
+    quiet: bool,
+    /// Print the invoked cargo commands and run them with `--verbose`, `-vv` for very verbose
+    /// output including that of build scripts. Applies to the build of the crate generating the
+    /// metadata as well, whose temporary workspace is kept if it fails
+    #[structopt(short, long, parse(from_occurrences))]
+    verbose: u8,
+}
+
+/// How much the build prints, besides its steps printed by default.
+#[derive(Clone, Copy, Debug)]
+pub enum Verbosity {
+    /// Print neither the steps of the build nor the progress of cargo.
+    Quiet,
+    /// Print the invoked cargo commands and run them with `--verbose`.
+    Verbose,
+    /// Additionally print the output of build scripts.
+    VeryVerbose,
+}
+
+impl TryFrom<&VerbosityFlags> for Option<Verbosity> {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &VerbosityFlags) -> Result<Self, Self::Error> {
+        match (value.quiet, value.verbose) {
+            (false, 0) => Ok(None),
+            (true, 0) => Ok(Some(Verbosity::Quiet)),
+            (false, 1) => Ok(Some(Verbosity::Verbose)),
+            (false, _) => Ok(Some(Verbosity::VeryVerbose)),
+            (true, _) => anyhow::bail!("Cannot pass both --quiet and --verbose flags"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+struct UnstableOptions {
+    /// Use the original manifest (Cargo.toml), do not modify for build optimizations, with
+    /// `original-manifest`, or rebuild the standard library for a smaller Wasm on the nightly
+    /// toolchain, with `build-std`
+    #[structopt(long = "unstable-options", short = "Z", number_of_values = 1)]
+    options: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct UnstableFlags {
+    original_manifest: bool,
+    build_std: bool,
+}
+
+impl TryFrom<&UnstableOptions> for UnstableFlags {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &UnstableOptions) -> Result<Self, Self::Error> {
+        let valid_flags = ["original-manifest", "build-std"];
+        let invalid_flags = value
+            .options
+            .iter()
+            .filter(|o| !valid_flags.contains(&o.as_str()))
+            .collect::<Vec<_>>();
+        if !invalid_flags.is_empty() {
+            anyhow::bail!("Unknown unstable-options {:?}", invalid_flags)
+        }
+        Ok(UnstableFlags {
+            original_manifest: value.options.contains(&"original-manifest".to_owned()),
+            build_std: value.options.contains(&"build-std".to_owned()),
+        })
+    }
+}
+
+/// Options passed through to cargo building the contract.
+///
+/// The default features are always disabled, as they usually enable `std`.
+#[derive(Clone, Debug, Default, StructOpt)]
+struct CargoOptions {
+    /// Space or comma separated list of features of the contract to activate
+    #[structopt(long)]
+    features: Vec<String>,
+    /// Activate all features of the contract
+    #[structopt(long, conflicts_with = "features")]
+    all_features: bool,
+    /// Run cargo without accessing the network, e.g. on air-gapped CI runners
+    #[structopt(long)]
+    offline: bool,
+    /// Require the `Cargo.lock` of the contract to be up to date
+    #[structopt(long)]
+    locked: bool,
+    /// The cargo target directory, instead of the one `cargo metadata` reports.
+    #[structopt(skip)]
+    target_dir: Option<PathBuf>,
+}
+
+impl CargoOptions {
+    fn feature_names(&self) -> Vec<&str> {
+        self.features
+            .iter()
+            .flat_map(|features| features.split(|c: char| c == ',' || c.is_whitespace()))
+            .filter(|feature| !feature.is_empty())
+            .collect()
+    }
+
+    fn network_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.offline {
+            args.push("--offline".to_string());
+        }
+        args
+    }
+
+    /// The arguments of `cargo build` building the contract.
+    fn cargo_args(&self) -> Vec<String> {
+        let mut args = self.network_args();
+        if self.locked {
+            args.push("--locked".to_string());
+        }
+        if self.all_features {
+            args.push("--all-features".to_string());
+        }
+        let names = self.feature_names();
+        if !names.is_empty() {
+            args.push("--features".to_string());
+            args.push(names.join(","));
+        }
+        args
+    }
+
+    /// The arguments of `cargo run` for the metadata generation package, depending on the contract
+    /// as the `dependency` which has the `available` features.
+    ///
+    /// `--locked` is not passed, as the package is added to the `Cargo.lock`.
+    fn metadata_gen_args<'a>(
+        &self,
+        dependency: &str,
+        available: impl Iterator<Item = &'a String>,
+    ) -> Vec<String> {
+        let mut args = self.network_args();
+        let names = if self.all_features {
+            available.map(String::as_str).collect()
+        } else {
+            self.feature_names()
+        };
+        if !names.is_empty() {
+            let features = names
+                .iter()
+                .map(|feature| format!("{}/{}", dependency, feature))
+                .collect::<Vec<_>>();
+            args.push("--features".to_string());
+            args.push(features.join(","));
+        }
+        args
+    }
+}
+
+/// Describes which artifacts to generate
+#[derive(Copy, Clone, Eq, PartialEq, Debug, StructOpt)]
+#[structopt(name = "build-artifacts")]
+pub enum BuildArtifacts {
+    /// Generate the Wasm, the metadata and a bundled `<name>.contract` file
+    #[structopt(name = "all")]
+    All,
+    /// Only the Wasm is created, generation of metadata and a bundled `<name>.contract` file is skipped
+    #[structopt(name = "code-only")]
+    CodeOnly,
+    /// Only the metadata is generated, skipping the build of the Wasm
+    #[structopt(name = "metadata-only")]
+    MetadataOnly,
+    CheckOnly,
+}
+
+impl BuildArtifacts {
+    /// Returns the number of steps required to complete a build artifact.
+    /// Used as output on the cli.
+    pub fn steps(&self) -> usize {
+        match self {
+            BuildArtifacts::All => 5,
+            BuildArtifacts::CodeOnly => 3,
+            BuildArtifacts::MetadataOnly => 1,
+            BuildArtifacts::CheckOnly => 2,
+        }
+    }
+}
+
+impl std::str::FromStr for BuildArtifacts {
+    type Err = String;
+    fn from_str(artifact: &str) -> Result<Self, Self::Err> {
+        match artifact {
+            "all" => Ok(BuildArtifacts::All),
+            // `wasm-only` is an alias, as the Wasm is the code.
+            "code-only" | "wasm-only" => Ok(BuildArtifacts::CodeOnly),
+            "metadata-only" => Ok(BuildArtifacts::MetadataOnly),
+            _ => Err("Could not parse build artifact".to_string()),
+        }
+    }
+}
+
+/// The binaryen optimization passes run on the Wasm, trading the time spent optimizing against the
+/// size of the code.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OptimizationPasses {
+    Zero,
+    One,
+    Two,
+    Three,
+    Four,
+    /// Optimize for size, like `-Os`.
+    S,
+    /// Optimize aggressively for size, like `-Oz`, the default.
+    #[default]
+    Z,
+}
+
+impl std::str::FromStr for OptimizationPasses {
+    type Err = String;
+
+    fn from_str(passes: &str) -> Result<Self, Self::Err> {
+        match passes.to_lowercase().as_str() {
+            "0" => Ok(OptimizationPasses::Zero),
+            "1" => Ok(OptimizationPasses::One),
+            "2" => Ok(OptimizationPasses::Two),
+            "3" => Ok(OptimizationPasses::Three),
+            "4" => Ok(OptimizationPasses::Four),
+            "s" => Ok(OptimizationPasses::S),
+            "z" => Ok(OptimizationPasses::Z),
+            _ => Err("Could not parse optimization passes, expected 0-4, s or z".to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for OptimizationPasses {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let passes = match self {
+            OptimizationPasses::Zero => "0",
+            OptimizationPasses::One => "1",
+            OptimizationPasses::Two => "2",
+            OptimizationPasses::Three => "3",
+            OptimizationPasses::Four => "4",
+            OptimizationPasses::S => "s",
+            OptimizationPasses::Z => "z",
+        };
+        write!(f, "{}", passes)
+    }
+}
+
+impl OptimizationPasses {
+    /// Returns the optimization level of binaryen, from 0 to 4.
+    pub fn optimization_level(&self) -> u32 {
+        match self {
+            OptimizationPasses::Zero => 0,
+            OptimizationPasses::One => 1,
+            OptimizationPasses::Two | OptimizationPasses::S | OptimizationPasses::Z => 2,
+            OptimizationPasses::Three => 3,
+            OptimizationPasses::Four => 4,
+        }
+    }
+
+    /// Returns the shrink level of binaryen, 1 for `s` and 2 for `z`.
+    pub fn shrink_level(&self) -> u32 {
+        match self {
+            OptimizationPasses::S => 1,
+            OptimizationPasses::Z => 2,
+            _ => 0,
+        }
+    }
+}
+
+/// The architecture the contract is compiled for.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Target {
+    /// Wasm for `pallet-contracts`, the default.
+    #[default]
+    Wasm,
+    /// RISC-V linked into a program for the experimental PolkaVM based executor.
+    RiscV,
+}
+
+impl std::str::FromStr for Target {
+    type Err = String;
+
+    fn from_str(target: &str) -> Result<Self, Self::Err> {
+        match target.to_lowercase().as_str() {
+            "wasm" => Ok(Target::Wasm),
+            "riscv" => Ok(Target::RiscV),
+            _ => Err("Could not parse the target, expected wasm or riscv".to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Target::Wasm => write!(f, "wasm"),
+            Target::RiscV => write!(f, "riscv"),
+        }
+    }
+}
+
+impl Target {
+    /// The target triple of rustc.
+    pub fn llvm_target(&self) -> &'static str {
+        match self {
+            Target::Wasm => "wasm32-unknown-unknown",
+            Target::RiscV => "riscv32em-unknown-none-elf",
+        }
+    }
+
+    /// The `RUSTFLAGS` of the cargo build.
+    pub fn rustflags(&self) -> &'static str {
+        match self {
+            Target::Wasm => {
+                "-C link-arg=-z -C link-arg=stack-size=65536 -C link-arg=--import-memory"
+            }
+            // The PolkaVM linker relocates the code itself.
+            Target::RiscV => {
+                "-C relocation-model=pie -C link-arg=--emit-relocs -C link-arg=--unique"
+            }
+        }
+    }
+
+    /// The extension of the code compiled by cargo.
+    pub fn source_extension(&self) -> &'static str {
+        match self {
+            Target::Wasm => "wasm",
+            Target::RiscV => "",
+        }
+    }
+
+    /// The extension of the code of the contract in `target/ink`.
+    pub fn dest_extension(&self) -> &'static str {
+        match self {
+            Target::Wasm => "wasm",
+            Target::RiscV => "polkavm",
+        }
+    }
+}
+
+/// Result of the metadata generation process.
+pub struct BuildResult {
+    /// Path to the resulting metadata file.
+    pub dest_metadata: Option<PathBuf>,
+    /// Path to the metadata file without the docs and display names, if requested.
+    pub dest_stripped_metadata: Option<PathBuf>,
+    /// Path to the resulting Wasm file.
+    pub dest_wasm: Option<PathBuf>,
+    /// Path to the bundled file.
+    pub dest_bundle: Option<PathBuf>,
+    /// Path to the directory where output files are written to.
+    pub target_directory: PathBuf,
+    /// If existent the result of the optimization.
+    pub optimization_result: Option<OptimizationResult>,
+    /// Which build artifacts were generated.
+    pub build_artifact: BuildArtifacts,
+    /// The attribution of the code size to functions and crates, if requested.
+    pub size_report: Option<cmd::SizeReport>,
+    /// The names of the custom sections kept in the Wasm.
+    pub custom_sections: Vec<String>,
+    /// The change of the size of the optimized Wasm since the previous build of the contract.
+    pub size_delta: Option<cmd::SizeDelta>,
+    /// The wall-clock time of the build.
+    pub build_time: Duration,
+}
+
+/// Result of the optimization process.
+pub struct OptimizationResult {
+    /// The original Wasm size.
+    pub original_size: f64,
+    /// The Wasm size after optimizations have been applied.
+    pub optimized_size: f64,
+    /// The optimization passes which were run.
+    pub optimization_passes: OptimizationPasses,
+    /// The wall-clock time of the optimization.
+    pub optimization_time: Duration,
+}
+
+impl BuildResult {
+    pub fn display(&self) -> String {
+        if self.build_artifact == BuildArtifacts::MetadataOnly {
+            return format!(
+                "\nYour contract's metadata is ready. You can find it here:\n{}",
+                self.dest_metadata
+                    .as_ref()
+                    .expect("metadata path must exist")
+                    .display()
+                    .to_string()
+                    .bold()
+            );
+        }
+        let size_delta = match self.size_delta {
+            Some(ref size_delta) => format!(", {}", size_delta.display().bold()),
+            None => String::new(),
+        };
+        let size_diff = match self.display_optimization() {
+            Some(optimization) => format!(
+                "\nOriginal wasm size: {}, Optimized: {}{} (optimization passes: {})\n\n",
+                format!("{:.1}K", optimization.0).bold(),
+                format!("{:.1}K", optimization.1).bold(),
+                size_delta,
+                optimization.2.to_string().bold(),
+            ),
+            // The PolkaVM program of the RISC-V target is not optimized.
+            None => "\n".to_string(),
+        };
+
+        let size_diff = match self.custom_sections.as_slice() {
+            [] => size_diff,
+            sections => format!(
+                "{}Custom sections kept in the Wasm: {}\n\n",
+                size_diff,
+                sections.join(", ").bold()
+            ),
+        };
+
+        if self.build_artifact == BuildArtifacts::CodeOnly {
+            let out = format!(
+                "{}Your contract's code is ready. You can find it here:\n{}",
+                size_diff,
+                self.dest_wasm
+                    .as_ref()
+                    .expect("wasm path must exist")
+                    .display()
+                    .to_string()
+                    .bold()
+            );
+            if let Some(size_report) = self.size_report.as_ref() {
+                return format!("{}\n\n{}", out, size_report.display());
+            }
+            return out;
+        };
+
+        let mut out = format!(
+            "{}Your contract artifacts are ready. You can find them in:\n{}\n\n",
+            size_diff,
+            self.target_directory.display().to_string().bold(),
+        );
+        if let Some(dest_bundle) = self.dest_bundle.as_ref() {
+            let bundle = format!(
+                "  - {} (code + metadata)\n",
+                util::base_name(dest_bundle).bold()
+            );
+            out.push_str(&bundle);
+        }
+        if let Some(dest_wasm) = self.dest_wasm.as_ref() {
+            let wasm = format!(
+                "  - {} (the contract's code)\n",
+                util::base_name(dest_wasm).bold()
+            );
+            out.push_str(&wasm);
+        }
+        if let Some(dest_metadata) = self.dest_metadata.as_ref() {
+            let metadata = format!(
+                "  - {} (the contract's metadata)",
+                util::base_name(dest_metadata).bold()
+            );
+            out.push_str(&metadata);
+        }
+        if let Some(dest_stripped_metadata) = self.dest_stripped_metadata.as_ref() {
+            let metadata = format!(
+                "\n  - {} (the metadata without docs)",
+                util::base_name(dest_stripped_metadata).bold()
+            );
+            out.push_str(&metadata);
+        }
+        if let Some(size_report) = self.size_report.as_ref() {
+            out.push_str(&format!("\n\n{}", size_report.display()));
+        }
+        out
+    }
+
+    /// Returns a tuple of `(original_size, optimized_size, optimization_passes)`, if the code was
+    /// optimized.
+    fn display_optimization(&self) -> Option<(f64, f64, OptimizationPasses)> {
+        let optimization = self.optimization_result.as_ref()?;
+        Some((
+            optimization.original_size,
+            optimization.optimized_size,
+            optimization.optimization_passes,
+        ))
+    }
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Setup and create a new smart contract project
+    #[structopt(name = "new")]
+    New(cmd::NewCommand),
+    /// Compiles the contract, generates metadata, bundles both together in a `<name>.contract` file
+    #[structopt(name = "build")]
+    Build(Box<BuildCommand>),
+    /// Command has been deprecated, use `cargo contract build` instead
+    #[structopt(name = "generate-metadata")]
+    GenerateMetadata {},
+    /// Check that the code and its metadata generation compile for Wasm, without codegen or build artifacts
+    #[structopt(name = "check")]
+    Check(CheckCommand),
+    /// Test the smart contract off-chain, and optionally end-to-end against a contracts node
+    #[structopt(name = "test")]
+    Test(cmd::TestCommand),
+    /// Start, stop or inspect a local contracts node to try out and test contracts against
+    #[structopt(name = "node")]
+    Node(cmd::NodeCommand),
+    /// List or remove the cached templates of `new` and downloaded releases of binaryen and the node
+    #[structopt(name = "cache")]
+    Cache(cmd::CacheCommand),
+    /// Rebuild the contract whenever its sources change, optionally redeploying it to a node
+    #[structopt(name = "watch")]
+    Watch(cmd::WatchCommand),
+    /// Remove the artifacts in `target/ink` and the legacy metadata generation package
+    #[structopt(name = "clean")]
+    Clean(cmd::CleanCommand),
+    /// Write the Wasm and the metadata of a `.contract` bundle to separate files
+    #[structopt(name = "extract")]
+    Extract(cmd::ExtractCommand),
+    /// Inspect the Wasm of a contract, e.g. printing it in the WebAssembly text format
+    #[structopt(name = "inspect")]
+    Inspect(cmd::InspectCommand),
+    /// Report the chain extensions, the host functions with side effects and the unchecked
+    /// arithmetic the Wasm of a contract uses
+    #[structopt(name = "audit")]
+    Audit(cmd::AuditCommand),
+    /// Render the reference of a contract from its metadata as markdown or HTML
+    #[structopt(name = "doc")]
+    Doc(cmd::DocCommand),
+    /// Generate typed bindings for a contract from its metadata
+    #[structopt(name = "bindgen")]
+    Bindgen(cmd::BindgenCommand),
+    /// Generate an end-to-end test for the contract from its metadata
+    #[structopt(name = "generate")]
+    Generate(cmd::GenerateCommand),
+    /// Convert, compare, validate or verify the metadata of contracts
+    #[structopt(name = "metadata")]
+    Metadata(cmd::MetadataCommand),
+    /// Check that the hash in the metadata of a `.contract` bundle matches its code, that the
+    /// metadata is well-formed and the code acceptable to pallet-contracts, reporting the
+    /// compiler and the ink! version
+    #[structopt(name = "validate")]
+    Validate(cmd::ValidateCommand),
+    /// Run the ink! specific lints on the contract with `cargo dylint`
+    #[structopt(name = "lint")]
+    Lint(cmd::LintCommand),
+    /// Execute a message of the contract off-chain in an in-memory sandbox, without a node
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "exec")]
+    Exec(cmd::ExecCommand),
+    /// Call a message of the contract in the sandbox with random arguments, reporting panics,
+    /// traps and violated invariants
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "fuzz")]
+    Fuzz(cmd::FuzzCommand),
+    /// Dry run the messages of the contract several times, reporting the minimum, median and
+    /// maximum gas, proof size and storage deposit of each
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "benchmark")]
+    Benchmark(cmd::BenchmarkCommand),
+    /// Upload the smart contract code to the chain
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "deploy", alias = "upload")]
+    Deploy {
+        #[structopt(flatten)]
+        extrinsic_opts: ExtrinsicOpts,
+        /// Path to wasm contract code, defaults to `./target/ink/<name>-pruned.wasm`
+        #[structopt(parse(from_os_str))]
+        wasm_path: Option<PathBuf>,
+        /// Path to a TOML file with a `[[step]]` table for each upload, instantiation or call to
+        /// execute in order, instead of uploading a single contract
+        #[structopt(long, parse(from_os_str), conflicts_with_all = &["wasm-path", "offline"])]
+        plan: Option<PathBuf>,
+        /// Upload the code of each contract of the workspace at the current directory, as built
+        /// by `cargo contract build`, skipping the code already stored on the chain
+        #[structopt(long, conflicts_with_all = &["wasm-path", "plan", "offline"])]
+        all: bool,
+    },
+    /// Instantiate a deployed smart contract
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "instantiate")]
+    Instantiate {
+        #[structopt(flatten)]
+        extrinsic_opts: ExtrinsicOpts,
+        /// Transfers an initial balance to the instantiated contract
+        ///
+        /// Either in the smallest unit of the chain's token, or denominated in the token, e.g.
+        /// `1.5UNIT`.
+        #[structopt(name = "endowment", long, default_value = "0")]
+        endowment: cmd::BalanceVariant,
+        /// Maximum amount of gas to be used for this command
+        #[structopt(name = "gas", long, default_value = "500000000")]
+        gas_limit: u64,
+        /// The hash of the smart contract code already uploaded to the chain
+        #[structopt(long, parse(try_from_str = parse_code_hash))]
+        code_hash: H256,
+        #[structopt(flatten)]
+        constructor: cmd::ConstructorOpts,
+        /// Print the account id the contract would be instantiated at, without submitting the
+        /// extrinsic
+        #[structopt(long, conflicts_with = "offline")]
+        dry_run: bool,
+    },
+    /// Call a smart contract deployed on the chain
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "call")]
+    Call {
+        #[structopt(flatten)]
+        extrinsic_opts: ExtrinsicOpts,
+        /// The address of the contract to call, or its alias in the address book. Defaults to the
+        /// contract instantiated last on the network, as recorded in `deployments.json`
+        #[structopt(long)]
+        contract: Option<cmd::ContractRef>,
+        /// Transfers a balance to the contract
+        ///
+        /// Either in the smallest unit of the chain's token, or denominated in the token, e.g.
+        /// `1.5UNIT`.
+        #[structopt(name = "value", long, default_value = "0")]
+        value: cmd::BalanceVariant,
+        /// Maximum amount of gas to be used for this command, or `auto` to estimate it with a
+        /// dry run
+        #[structopt(name = "gas", long, default_value = "500000000")]
+        gas_limit: cmd::GasLimit,
+        /// Factor the gas estimated for `--gas auto` is increased by, e.g. `1.2` or `20%`
+        #[structopt(long, default_value = "1")]
+        gas_margin: cmd::GasMargin,
+        #[structopt(flatten)]
+        message: cmd::MessageOpts,
+        /// Execute the call via RPC without submitting an extrinsic, printing its result
+        #[structopt(long, conflicts_with = "offline")]
+        dry_run: bool,
+        /// Print the debug messages of the contract with the result of a dry run
+        #[structopt(long)]
+        verbose: bool,
+        /// Dry run the call on the state of this block, a block number or a 0x prefixed hash,
+        /// instead of the best block
+        #[structopt(long, requires = "dry-run")]
+        at: Option<cmd::BlockRef>,
+        /// Print the host functions the dry run calls, e.g. its storage reads and writes and its
+        /// transfers, with their decoded arguments. The call is replayed in the sandbox on a fork
+        /// of the contract, where calls to other contracts trap
+        #[structopt(long, requires = "dry-run")]
+        trace: bool,
+        /// Print the storage cells the dry run writes and removes, decoded with the storage
+        /// layout of the metadata. The call is replayed in the sandbox like with `--trace`
+        #[structopt(long, requires = "dry-run")]
+        storage_diff: bool,
+        /// Estimate the gas again and resubmit the call once, should it fail for running out of
+        /// gas or storage deposit
+        #[structopt(long, conflicts_with_all = &["dry-run", "nonce"])]
+        auto_retry: bool,
+    },
+    /// Dispatch several contract calls atomically, in a single `Utility::batch_all` extrinsic
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "batch")]
+    Batch {
+        #[structopt(flatten)]
+        extrinsic_opts: ExtrinsicOpts,
+        /// Path to a TOML file with a `[[call]]` table for each contract call, specifying the
+        /// `contract` address, hex encoded `data` and optionally `value` and `gas_limit`
+        #[structopt(parse(from_os_str))]
+        calls: PathBuf,
+    },
+    /// Submit an extrinsic which has already been signed, e.g. with `--offline`
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "submit")]
+    Submit {
+        #[structopt(flatten)]
+        submit_opts: SubmitOpts,
+        /// Hex encoded signed extrinsic
+        extrinsic: HexData,
+    },
+    /// Show the code hash, storage and balance of a contract deployed on the chain
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "info")]
+    Info {
+        #[structopt(flatten)]
+        submit_opts: SubmitOpts,
+        /// The address of the contract, or its alias in the address book. Defaults to the contract
+        /// instantiated last on the network, as recorded in `deployments.json`
+        contract: Option<cmd::ContractRef>,
+        /// Query the state of this block, a block number or a 0x prefixed hash, instead of the
+        /// best block
+        #[structopt(long)]
+        at: Option<cmd::BlockRef>,
+    },
+    /// Print the storage of a contract deployed on the chain
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "storage")]
+    Storage {
+        #[structopt(flatten)]
+        submit_opts: SubmitOpts,
+        /// The address of the contract, or its alias in the address book. Defaults to the contract
+        /// instantiated last on the network, as recorded in `deployments.json`
+        contract: Option<cmd::ContractRef>,
+        /// Only print the cells of this storage field, e.g. `owner` or `owner.name`
+        #[structopt(long, alias = "key")]
+        field: Option<String>,
+        /// Path to the metadata of the contract for decoding its storage, defaults to
+        /// `./target/ink/metadata.json`
+        #[structopt(long, parse(from_os_str))]
+        metadata: Option<PathBuf>,
+        /// Read the storage at this block, a block number or a 0x prefixed hash, instead of the
+        /// best block
+        #[structopt(long)]
+        at: Option<cmd::BlockRef>,
+        /// Print the old and the new value of the cells changed by each new best block, until
+        /// interrupted
+        #[structopt(long, conflicts_with = "at")]
+        watch: bool,
+    },
+    /// Stream the events emitted by a contract deployed on the chain
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "events")]
+    Events {
+        #[structopt(flatten)]
+        submit_opts: SubmitOpts,
+        /// The address of the contract
+        #[structopt(long, parse(try_from_str = cmd::parse_address))]
+        contract: cmd::Address,
+        /// Only report the events of finalized blocks
+        #[structopt(long)]
+        finalized: bool,
+        /// Path to the metadata of the contract for decoding its events, defaults to
+        /// `./target/ink/metadata.json`
+        #[structopt(long, parse(from_os_str))]
+        metadata: Option<PathBuf>,
+    },
+    /// Download the Wasm code of a code hash stored on the chain
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "download")]
+    Download {
+        #[structopt(flatten)]
+        submit_opts: SubmitOpts,
+        /// The hash of the code
+        #[structopt(parse(try_from_str = parse_code_hash))]
+        code_hash: H256,
+        /// Path of the file to write the code to, defaults to `<code-hash>.wasm`
+        #[structopt(parse(from_os_str))]
+        path: Option<PathBuf>,
+    },
+    /// Verify that the local Wasm code of the contract matches the code deployed on the chain
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "verify")]
+    Verify {
+        #[structopt(flatten)]
+        submit_opts: SubmitOpts,
+        /// The code hash, or the address of a contract, to verify the code of
+        code: cmd::CodeRef,
+        /// Path to the Wasm file to compare, defaults to the one built for the contract project
+        #[structopt(long, parse(from_os_str), conflicts_with_all = &["build", "manifest-path"])]
+        wasm: Option<PathBuf>,
+        /// Rebuild the contract before comparing its Wasm code
+        #[structopt(long)]
+        build: bool,
+        /// Path to the Cargo.toml of the contract
+        #[structopt(long, parse(from_os_str))]
+        manifest_path: Option<PathBuf>,
+    },
+    /// Remove unused code from the chain, refunding its deposit
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "remove-code")]
+    RemoveCode {
+        #[structopt(flatten)]
+        extrinsic_opts: ExtrinsicOpts,
+        /// The hash of the code to remove
+        #[structopt(parse(try_from_str = parse_code_hash))]
+        code_hash: H256,
+    },
+    /// Terminate a contract by calling its terminating message, confirming its removal
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "terminate")]
+    Terminate {
+        #[structopt(flatten)]
+        extrinsic_opts: ExtrinsicOpts,
+        /// The address of the contract to terminate
+        #[structopt(long, parse(try_from_str = cmd::parse_address))]
+        contract: cmd::Address,
+        /// The label of the terminating message, defaults to the one with `terminate` in its label
+        #[structopt(long)]
+        message: Option<String>,
+        /// Hex encoded arguments of the message
+        #[structopt(long)]
+        args: Option<HexData>,
+        /// Maximum amount of gas to be used for this command, or `auto` to estimate it with a
+        /// dry run
+        #[structopt(name = "gas", long, default_value = "500000000")]
+        gas_limit: cmd::GasLimit,
+        /// Factor the gas estimated for `--gas auto` is increased by, e.g. `1.2` or `20%`
+        #[structopt(long, default_value = "1")]
+        gas_margin: cmd::GasMargin,
+        /// Path to the metadata of the contract, defaults to `./target/ink/metadata.json`
+        #[structopt(long = "contract-metadata", parse(from_os_str))]
+        contract_metadata: Option<PathBuf>,
+    },
+    /// Upgrade a contract to new code by calling its message setting the code hash
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "upgrade")]
+    Upgrade {
+        #[structopt(flatten)]
+        extrinsic_opts: ExtrinsicOpts,
+        /// The address of the contract to upgrade
+        #[structopt(long, parse(try_from_str = cmd::parse_address))]
+        contract: cmd::Address,
+        /// Path to the new Wasm code, defaults to the one built for the contract project
+        #[structopt(parse(from_os_str))]
+        wasm_path: Option<PathBuf>,
+        /// The label of the message setting the code hash, defaults to the one with
+        /// `set_code_hash` in its label
+        #[structopt(long)]
+        message: Option<String>,
+        /// Maximum amount of gas to be used for this command, or `auto` to estimate it with a
+        /// dry run
+        #[structopt(name = "gas", long, default_value = "500000000")]
+        gas_limit: cmd::GasLimit,
+        /// Factor the gas estimated for `--gas auto` is increased by, e.g. `1.2` or `20%`
+        #[structopt(long, default_value = "1")]
+        gas_margin: cmd::GasMargin,
+        /// Path to the metadata of the contract, defaults to `./target/ink/metadata.json`
+        #[structopt(long = "contract-metadata", parse(from_os_str))]
+        contract_metadata: Option<PathBuf>,
+    },
+    /// Re-encode the storage of a contract into the layout of its new code, and encode the calls
+    /// moving the state into the upgraded contract
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "migrate")]
+    Migrate(cmd::MigrateCommand),
+    /// Transfer a balance to a contract, keeping the signer's account alive
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "transfer")]
+    Transfer {
+        #[structopt(flatten)]
+        extrinsic_opts: ExtrinsicOpts,
+        /// The address of the contract to fund
+        #[structopt(parse(try_from_str = cmd::parse_address))]
+        dest: cmd::Address,
+        /// The balance to transfer
+        ///
+        /// Either in the smallest unit of the chain's token, or denominated in the token, e.g.
+        /// `1.5UNIT`.
+        value: cmd::BalanceVariant,
+    },
+    /// Request funds for an account from the faucet of a test network, or transfer them from
+    /// `//Alice` on a development chain
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "faucet")]
+    Faucet(cmd::FaucetCommand),
+    /// Show the address and balance of an account, derived from a secret key URI or given by
+    /// its address, without submitting anything
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "account")]
+    Account {
+        #[structopt(flatten)]
+        submit_opts: SubmitOpts,
+        /// The address of the account, of any address format, instead of deriving it from the
+        /// secret key URI
+        #[structopt(parse(try_from_str = cmd::parse_address), required_unless = "suri")]
+        address: Option<cmd::Address>,
+        /// Secret key URI to derive the account from.
+        ///
+        /// Read from the `CARGO_CONTRACT_SURI` environment variable if the flag is not supplied.
+        #[structopt(
+            name = "suri",
+            long,
+            short,
+            env = "CARGO_CONTRACT_SURI",
+            hide_env_values = true
+        )]
+        suri: Option<String>,
+        /// Password for the secret key
+        #[structopt(long, short)]
+        password: Option<String>,
+        /// Signature scheme of the key pair derived from the secret key URI
+        #[structopt(
+            long,
+            default_value = "sr25519",
+            value_name = "sr25519 | ed25519 | ecdsa"
+        )]
+        scheme: cmd::Scheme,
+        /// Also encode the address with this SS58 prefix, e.g. `0` for Polkadot
+        #[structopt(long)]
+        ss58_prefix: Option<u8>,
+        /// Only derive and encode the address, without connecting to the chain for the balance
+        #[structopt(long)]
+        offline: bool,
+    },
+    /// Approve a call dispatched from a multisig account, exported by its first approval with
+    /// `--multisig-call-file`, and check its approvals
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "multisig")]
+    Multisig(cmd::MultisigCommand),
+    /// Manage aliases for contract addresses, accepted by `--contract` of `call`, `info` and
+    /// `storage`
+    ///
+    /// The aliases are stored in `~/.cargo-contract-addresses.toml`, separately for each network.
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "addressbook")]
+    AddressBook {
+        #[structopt(flatten)]
+        submit_opts: SubmitOpts,
+        #[structopt(subcommand)]
+        cmd: cmd::AddressBookCommand,
+    },
+    /// Check the signature of the attestation of a verifiable build, written by `build
+    /// --verifiable --attest`
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "verify-attestation")]
+    VerifyAttestation {
+        /// Path to the `<name>.attestation.json`
+        #[structopt(parse(from_os_str))]
+        attestation: PathBuf,
+        /// Also check that this Wasm hashes to the attested code hash
+        #[structopt(long, parse(from_os_str))]
+        wasm: Option<PathBuf>,
+    },
+    /// Generate the completion script of a shell, completing the message labels of the local
+    /// metadata
+    #[structopt(name = "completions")]
+    Completions(cmd::CompletionsCommand),
+    /// Print the labels of the messages or constructors of the local metadata, for the
+    /// completion scripts
+    #[cfg(feature = "extrinsics")]
+    #[structopt(name = "__complete-labels", setting = clap::AppSettings::Hidden)]
+    CompleteLabels(cmd::CompleteLabelsCommand),
+    /// Runs the `cargo-contract-<name>` plugin in the `PATH` for another subcommand `name`
+    #[structopt(external_subcommand)]
+    External(Vec<String>),
+}
+
+#[cfg(feature = "extrinsics")]
+impl Command {
+    /// The options for submitting the extrinsic, if the command submits one.
+    fn submit_opts(&self) -> Option<&SubmitOpts> {
+        match self {
+            Command::Deploy { extrinsic_opts, .. }
+            | Command::Instantiate { extrinsic_opts, .. }
+            | Command::Call { extrinsic_opts, .. }
+            | Command::Batch { extrinsic_opts, .. }
+            | Command::RemoveCode { extrinsic_opts, .. }
+            | Command::Terminate { extrinsic_opts, .. }
+            | Command::Upgrade { extrinsic_opts, .. }
+            | Command::Transfer { extrinsic_opts, .. } => Some(&extrinsic_opts.submit),
+            Command::Submit { submit_opts, .. }
+            | Command::Info { submit_opts, .. }
+            | Command::Storage { submit_opts, .. }
+            | Command::Events { submit_opts, .. }
+            | Command::Download { submit_opts, .. }
+            | Command::Verify { submit_opts, .. }
+            | Command::Account { submit_opts, .. }
+            | Command::AddressBook { submit_opts, .. } => Some(submit_opts),
+            Command::Watch(watch) => Some(&watch.redeploy.extrinsic_opts.submit),
+            Command::Benchmark(benchmark) => Some(&benchmark.submit_opts),
+            Command::Migrate(migrate) => Some(&migrate.submit_opts),
+            Command::Faucet(faucet) => Some(&faucet.submit_opts),
+            Command::Inspect(inspect) => Some(&inspect.submit_opts),
+            Command::Metadata(metadata) => metadata.submit_opts(),
+            Command::Multisig(multisig) => Some(multisig.submit_opts()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "extrinsics")]
+fn parse_code_hash(input: &str) -> Result<H256> {
+    let bytes = hex::decode(input)?;
+    if bytes.len() != 32 {
+        anyhow::bail!("Code hash should be 32 bytes in length")
+    }
+    let mut arr = [0u8; 32];
+    arr.copy_from_slice(&bytes);
+    Ok(H256(arr))
+}
+
+/// Runs `cargo contract` with the arguments of the process, exiting with an error code if the
+/// command fails.
+pub fn run() {
+    let matches = Opts::clap().get_matches();
+    let Opts::Contract(mut args) = Opts::from_clap(&matches);
+    if let Some(level) = global_value(&matches, "log-level") {
+        args.log_level = Some(parse_global(level, "log-level"));
+    }
+    if let Some(format) = global_value(&matches, "log-format") {
+        args.log_format = parse_global(format, "log-format");
+    }
+    logging::init(args.log_level, args.log_format);
+    match configure(args.cmd, &matches).and_then(exec) {
+        Ok(msg) => println!("\t{}", msg),
+        Err(err) => {
+            eprintln!(
+                "{} {}",
+                "ERROR:".bright_red().bold(),
+                format!("{:?}", err).bright_red()
+            );
+            std::process::exit(error::exit_code(&err));
+        }
+    }
+}
+
+/// The value of the global option supplied last, which clap records in the matches of the
+/// subcommand it is supplied to.
+fn global_value<'a>(mut matches: &'a clap::ArgMatches<'a>, name: &str) -> Option<&'a str> {
+    let mut value = None;
+    loop {
+        if matches.occurrences_of(name) > 0 {
+            value = matches.value_of(name);
+        }
+        match matches.subcommand() {
+            (_, Some(subcommand)) => matches = subcommand,
+            _ => return value,
+        }
+    }
+}
+
+/// Parses the value of a global argument, exiting with a usage error if it is invalid.
+fn parse_global<T>(value: &str, name: &str) -> T
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value.parse().unwrap_or_else(|err| {
+        let msg = format!("Invalid value {:?} for '--{}': {}", value, name, err);
+        clap::Error::value_validation_auto(msg).exit()
+    })
+}
+
+/// Applies the defaults from the `.cargo-contract.toml` configuration files to the options of
+/// commands submitting extrinsics, which were not supplied on the command line.
+#[cfg(feature = "extrinsics")]
+fn configure(mut cmd: Command, matches: &clap::ArgMatches) -> Result<Command> {
+    let matches = matches
+        .subcommand_matches("contract")
+        .and_then(|contract| contract.subcommand().1);
+    let matches = match matches {
+        Some(matches) if cmd.submit_opts().is_some() => matches,
+        _ => return Ok(cmd),
+    };
+    // The options of `metadata verify` are recorded in the matches of the nested subcommand.
+    let matches = match cmd {
+        Command::Metadata(_) => matches.subcommand().1.unwrap_or(matches),
+        _ => matches,
+    };
+    cmd::Config::load()?.apply(&mut cmd, matches)?;
+    let network = cmd
+        .submit_opts()
+        .and_then(|submit_opts| submit_opts.network_profile.as_ref());
+    if let Some(ss58_prefix) = network.and_then(|network| network.ss58_prefix) {
+        cmd::set_address_format(ss58_prefix);
+    }
+    Ok(cmd)
+}
+
+#[cfg(not(feature = "extrinsics"))]
+fn configure(cmd: Command, _: &clap::ArgMatches) -> Result<Command> {
+    Ok(cmd)
+}
+
+fn exec(cmd: Command) -> Result<String> {
+    match &cmd {
+        Command::New(new) => new.exec(),
+        Command::Build(build) => build.exec(),
+        Command::Watch(watch) => watch.exec(),
+        Command::Clean(clean) => clean.exec(),
+        Command::Extract(extract) => extract.exec(),
+        Command::Inspect(inspect) => inspect.exec(),
+        Command::Audit(audit) => audit.exec(),
+        Command::Metadata(metadata) => metadata.exec(),
+        Command::Validate(validate) => validate.exec(),
+        Command::Bindgen(bindgen) => bindgen.exec(),
+        Command::Generate(generate) => generate.exec(),
+        Command::Doc(doc) => doc.exec(),
+        Command::Lint(lint) => lint.exec(),
+        #[cfg(feature = "extrinsics")]
+        Command::Exec(exec) => exec.exec(),
+        #[cfg(feature = "extrinsics")]
+        Command::Fuzz(fuzz) => fuzz.exec(),
+        #[cfg(feature = "extrinsics")]
+        Command::Benchmark(benchmark) => benchmark.exec(),
+        #[cfg(feature = "extrinsics")]
+        Command::Migrate(migrate) => migrate.exec(),
+        #[cfg(feature = "extrinsics")]
+        Command::Faucet(faucet) => faucet.exec(),
+        Command::Check(check) => {
+            let res = check.exec()?;
+            assert!(
+                res.dest_wasm.is_none(),
+                "no dest_wasm must be on the generation result"
+            );
+            Ok("\nYour contract's code was checked successfully.".to_string())
+        }
+        Command::GenerateMetadata {} => Err(anyhow::anyhow!(
+            "Command deprecated, use `cargo contract build` instead"
+        )),
+        Command::Test(test) => test.exec(),
+        Command::Node(node) => node.exec(),
+        Command::Cache(cache) => cache.exec(),
+        Command::Completions(completions) => {
+            // Printed as is, since shells read e.g. the `#compdef` of zsh from the first line.
+            print!("{}", completions.exec()?);
+            std::process::exit(0)
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::CompleteLabels(labels) => {
+            // Errors would be completed as labels, so a missing metadata prints none.
+            println!("{}", labels.exec().unwrap_or_default());
+            std::process::exit(0)
+        }
+        Command::External(args) => std::process::exit(cmd::execute_plugin(args)?),
+        #[cfg(feature = "extrinsics")]
+        Command::Deploy {
+            extrinsic_opts,
+            wasm_path,
+            plan,
+            all,
+        } => {
+            if *all {
+                let result = cmd::execute_deploy_all(extrinsic_opts)?;
+                return extrinsic_opts
+                    .submit
+                    .output
+                    .format(&result, |result| result.display());
+            }
+            if let Some(plan) = plan {
+                let result = cmd::execute_plan(extrinsic_opts, plan)?;
+                return extrinsic_opts
+                    .submit
+                    .output
+                    .format(&result, |result| result.display());
+            }
+            if let Some(params) = extrinsic_opts.offline_params()? {
+                let signed = cmd::sign_deploy(extrinsic_opts, &params, wasm_path.as_ref())?;
+                return extrinsic_opts
+                    .submit
+                    .output
+                    .format(&signed, |signed| signed.display());
+            }
+            let signer = extrinsic_opts.signer()?;
+            let result = cmd::execute_deploy(extrinsic_opts, &signer, wasm_path.as_ref())?;
+            extrinsic_opts
+                .submit
+                .output
+                .format(&result, |result| result.display())
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::Instantiate {
+            extrinsic_opts,
+            endowment,
+            code_hash,
+            gas_limit,
+            constructor,
+            dry_run,
+        } => {
+            let data = constructor.data(endowment)?;
+            if *dry_run {
+                let signer = extrinsic_opts.signer()?;
+                let predicted =
+                    cmd::predict_contract(extrinsic_opts, &signer, *code_hash, &data.0)?;
+                return extrinsic_opts
+                    .submit
+                    .output
+                    .format(&predicted, |predicted| predicted.display());
+            }
+            if let Some(params) = extrinsic_opts.offline_params()? {
+                let signed = cmd::sign_instantiate(
+                    extrinsic_opts,
+                    &params,
+                    endowment.clone(),
+                    *gas_limit,
+                    *code_hash,
+                    data.clone(),
+                )?;
+                return extrinsic_opts
+                    .submit
+                    .output
+                    .format(&signed, |signed| signed.display());
+            }
+            let signer = extrinsic_opts.signer()?;
+            let result = cmd::execute_instantiate(
+                extrinsic_opts,
+                &signer,
+                endowment.clone(),
+                *gas_limit,
+                *code_hash,
+                data.clone(),
+            )?;
+            extrinsic_opts.submit.output.format(&result, |result| {
+                let mut output = match result.contract {
+                    Some(ref contract) => format!("Contract account: {}", contract),
+                    None => format!(
+                        "Contract account (predicted): {}\n\tExtrinsic hash: {:?}",
+                        result.predicted, result.extrinsic.extrinsic
+                    ),
+                };
+                if let Some(ref explorer) = result.contract_explorer {
+                    output.push_str(&format!("\n\tContract explorer: {}", explorer));
+                }
+                result.extrinsic.display_details(output)
+            })
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::Call {
+            extrinsic_opts,
+            contract,
+            value,
+            gas_limit,
+            gas_margin,
+            message,
+            dry_run,
+            verbose,
+            at,
+            trace,
+            storage_diff,
+            auto_retry,
+        } => {
+            let output = extrinsic_opts.submit.output;
+            let data = message.data()?;
+            let contract = &cmd::resolve_contract(contract.as_ref(), &extrinsic_opts.submit)?;
+            if *dry_run {
+                let metadata = if *storage_diff {
+                    message.metadata()?
+                } else {
+                    None
+                };
+                let replay = cmd::Replay {
+                    trace: *trace,
+                    storage_diff: *storage_diff,
+                    metadata: metadata.as_ref(),
+                };
+                let result = cmd::dry_run_call(
+                    extrinsic_opts,
+                    contract,
+                    value.clone(),
+                    *gas_limit,
+                    data.clone(),
+                    at.as_ref(),
+                    replay,
+                )?;
+                return output.format(&result, |result| result.display(*verbose));
+            }
+            if let Some(params) = extrinsic_opts.offline_params()? {
+                let signed = cmd::sign_call(
+                    extrinsic_opts,
+                    &params,
+                    contract,
+                    value.clone(),
+                    *gas_limit,
+                    data.clone(),
+                )?;
+                return output.format(&signed, |signed| signed.display());
+            }
+            let signer = extrinsic_opts.signer()?;
+            let result = cmd::execute_call(
+                extrinsic_opts,
+                &signer,
+                contract,
+                value.clone(),
+                *gas_limit,
+                *gas_margin,
+                *auto_retry,
+                data,
+            )?;
+            output.format(&result, |result| result.display())
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::Batch {
+            extrinsic_opts,
+            calls,
+        } => {
+            if let Some(params) = extrinsic_opts.offline_params()? {
+                let signed = cmd::sign_batch(extrinsic_opts, &params, calls)?;
+                return extrinsic_opts
+                    .submit
+                    .output
+                    .format(&signed, |signed| signed.display());
+            }
+            let result = cmd::execute_batch(extrinsic_opts, calls)?;
+            extrinsic_opts.submit.output.format(&result, |result| {
+                let output = format!(
+                    "Dispatched {} calls in extrinsic {:?}",
+                    result.calls, result.extrinsic.extrinsic
+                );
+                result.extrinsic.display_details(output)
+            })
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::Submit {
+            submit_opts,
+            extrinsic,
+        } => {
+            let result = cmd::execute_submit(submit_opts, extrinsic.clone())?;
+            submit_opts
+                .output
+                .format(&result, |result| match result.block {
+                    Some(ref block) => format!(
+                        "Extrinsic {:?} included in block {:?}",
+                        result.extrinsic, block
+                    ),
+                    None => format!("Extrinsic hash: {:?}", result.extrinsic),
+                })
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::Info {
+            submit_opts,
+            contract,
+            at,
+        } => {
+            let contract = cmd::resolve_contract(contract.as_ref(), submit_opts)?;
+            let result = cmd::execute_info(submit_opts, &contract, at.as_ref())?;
+            submit_opts
+                .output
+                .format(&result, |result| result.display())
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::Storage {
+            submit_opts,
+            contract,
+            field,
+            metadata,
+            at,
+            watch,
+        } => {
+            let contract = cmd::resolve_contract(contract.as_ref(), submit_opts)?;
+            if *watch {
+                return cmd::watch_storage(
+                    submit_opts,
+                    &contract,
+                    field.as_deref(),
+                    metadata.as_deref(),
+                );
+            }
+            let result = cmd::execute_storage(
+                submit_opts,
+                &contract,
+                field.as_deref(),
+                metadata.as_deref(),
+                at.as_ref(),
+            )?;
+            submit_opts
+                .output
+                .format(&result, |result| result.display())
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::Events {
+            submit_opts,
+            contract,
+            finalized,
+            metadata,
+        } => cmd::execute_events(submit_opts, contract, *finalized, metadata.as_deref()),
+        #[cfg(feature = "extrinsics")]
+        Command::Download {
+            submit_opts,
+            code_hash,
+            path,
+        } => {
+            let result = cmd::execute_download(submit_opts, code_hash, path.as_deref())?;
+            submit_opts
+                .output
+                .format(&result, |result| result.display())
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::Verify {
+            submit_opts,
+            code,
+            wasm,
+            build,
+            manifest_path,
+        } => {
+            let result = cmd::execute_verify(
+                submit_opts,
+                code,
+                wasm.as_deref(),
+                *build,
+                manifest_path.as_ref(),
+            )?;
+            let output = submit_opts
+                .output
+                .format(&result, |result| result.display())?;
+            // A mismatch fails the command, so that scripts can rely on the exit code.
+            match result.verified {
+                true => Ok(output),
+                false => Err(anyhow::anyhow!(output)),
+            }
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::RemoveCode {
+            extrinsic_opts,
+            code_hash,
+        } => {
+            if let Some(params) = extrinsic_opts.offline_params()? {
+                let signed = cmd::sign_remove_code(extrinsic_opts, &params, code_hash)?;
+                return extrinsic_opts
+                    .submit
+                    .output
+                    .format(&signed, |signed| signed.display());
+            }
+            let result = cmd::execute_remove_code(extrinsic_opts, code_hash)?;
+            extrinsic_opts.submit.output.format(&result, |result| {
+                let output = format!("Extrinsic hash: {:?}", result.extrinsic);
+                result.display_details(output)
+            })
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::Terminate {
+            extrinsic_opts,
+            contract,
+            message,
+            args,
+            gas_limit,
+            gas_margin,
+            contract_metadata,
+        } => {
+            if extrinsic_opts.offline_params()?.is_some() {
+                anyhow::bail!(
+                    "Terminating a contract is confirmed on the chain, sign its terminating \
+                     message with `cargo contract call --offline` instead"
+                )
+            }
+            let result = cmd::execute_terminate(
+                extrinsic_opts,
+                contract,
+                message.as_deref(),
+                args.as_ref(),
+                *gas_limit,
+                *gas_margin,
+                contract_metadata.as_deref(),
+            )?;
+            extrinsic_opts
+                .submit
+                .output
+                .format(&result, |result| result.display())
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::Upgrade {
+            extrinsic_opts,
+            contract,
+            wasm_path,
+            message,
+            gas_limit,
+            gas_margin,
+            contract_metadata,
+        } => {
+            if extrinsic_opts.offline_params()?.is_some() {
+                anyhow::bail!(
+                    "Upgrading a contract is confirmed on the chain, sign its message setting the \
+                     code hash with `cargo contract call --offline` instead"
+                )
+            }
+            let result = cmd::execute_upgrade(
+                extrinsic_opts,
+                contract,
+                wasm_path.as_ref(),
+                message.as_deref(),
+                *gas_limit,
+                *gas_margin,
+                contract_metadata.as_deref(),
+            )?;
+            extrinsic_opts
+                .submit
+                .output
+                .format(&result, |result| result.display())
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::Transfer {
+            extrinsic_opts,
+            dest,
+            value,
+        } => {
+            if let Some(params) = extrinsic_opts.offline_params()? {
+                let signed = cmd::sign_transfer(extrinsic_opts, &params, dest, value)?;
+                return extrinsic_opts
+                    .submit
+                    .output
+                    .format(&signed, |signed| signed.display());
+            }
+            let result = cmd::execute_transfer(extrinsic_opts, dest, value)?;
+            extrinsic_opts.submit.output.format(&result, |result| {
+                let output = format!(
+                    "Transferred {} to {}\n\tExtrinsic hash: {:?}",
+                    result.balance_format.display(result.value),
+                    result.dest,
+                    result.extrinsic.extrinsic
+                );
+                result.extrinsic.display_details(output)
+            })
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::Account {
+            submit_opts,
+            address,
+            suri,
+            password,
+            scheme,
+            ss58_prefix,
+            offline,
+        } => {
+            let account = match (address, suri) {
+                (Some(address), _) => {
+                    address.check_format();
+                    address.account.clone()
+                }
+                (None, Some(suri)) => {
+                    cmd::ExtrinsicSigner::from_suri(*scheme, suri, password.as_deref())?
+                        .account_id()
+                        .clone()
+                }
+                (None, None) => anyhow::bail!("Either an address or --suri is required"),
+            };
+            let result = cmd::execute_account(submit_opts, &account, *ss58_prefix, *offline)?;
+            submit_opts
+                .output
+                .format(&result, |result| result.display())
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::Multisig(multisig) => {
+            let result = multisig.exec()?;
+            multisig
+                .submit_opts()
+                .output
+                .format(&result, |result| result.display())
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::AddressBook { submit_opts, cmd } => {
+            let result = cmd.exec(submit_opts)?;
+            submit_opts
+                .output
+                .format(&result, |result| result.display())
+        }
+        #[cfg(feature = "extrinsics")]
+        Command::VerifyAttestation { attestation, wasm } => {
+            let attestation = cmd::execute_verify_attestation(attestation, wasm.as_deref())?;
+            Ok(attestation.display())
+        }
+    }
+}
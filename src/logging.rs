@@ -0,0 +1,176 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    io::{self, IsTerminal},
+    str::FromStr,
+};
+
+use anyhow::Result;
+use tracing::Dispatch;
+use tracing_subscriber::{
+    filter::LevelFilter,
+    fmt::{format::FmtSpan, MakeWriter},
+    EnvFilter,
+};
+
+/// The format of the log written to stderr.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum LogFormat {
+    /// A line per record, with the spans it is logged in.
+    Text,
+    /// A JSON object per line, e.g. for collecting the log of CI runs.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => anyhow::bail!("Log format must be `text` or `json`"),
+        }
+    }
+}
+
+/// Writes the records of the `level`, or else of the `RUST_LOG` environment variable, in the
+/// `format`, together with the end of each span and its duration.
+fn dispatch<W>(level: Option<LevelFilter>, format: LogFormat, writer: W) -> Dispatch
+where
+    W: MakeWriter + Send + Sync + 'static,
+{
+    let filter = match level {
+        Some(level) => EnvFilter::default().add_directive(level.into()),
+        None => EnvFilter::from_default_env(),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(writer);
+    match format {
+        LogFormat::Text => Dispatch::new(builder.with_ansi(io::stderr().is_terminal()).finish()),
+        LogFormat::Json => Dispatch::new(builder.json().finish()),
+    }
+}
+
+/// Logs the records of the `level`, or else of the `RUST_LOG` environment variable, to stderr
+/// in the `format`, including those of the `log` macros.
+pub(crate) fn init(level: Option<LevelFilter>, format: LogFormat) {
+    // Fails if a logger is set already, e.g. by a test.
+    if tracing_log::LogTracer::init().is_ok() {
+        tracing::dispatcher::set_global_default(dispatch(level, format, io::stderr)).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tracing::{debug, info, info_span};
+
+    /// The records written by a dispatch.
+    #[derive(Clone, Default)]
+    struct Log(Arc<Mutex<Vec<u8>>>);
+
+    impl Log {
+        fn lines(&self) -> Vec<serde_json::Value> {
+            let written = self.0.lock().expect("not poisoned");
+            serde_json::Deserializer::from_slice(&written)
+                .into_iter()
+                .collect::<serde_json::Result<_>>()
+                .expect("a JSON object per line")
+        }
+    }
+
+    impl io::Write for Log {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().expect("not poisoned").extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn json(level: LevelFilter) -> (Dispatch, Log) {
+        let log = Log::default();
+        let writer = log.clone();
+        let dispatch = dispatch(Some(level), LogFormat::Json, move || writer.clone());
+        (dispatch, log)
+    }
+
+    #[test]
+    fn writes_the_spans_of_the_records() {
+        let (dispatch, log) = json(LevelFilter::INFO);
+        tracing::dispatcher::with_default(&dispatch, || {
+            let build = info_span!("build", package = "flipper");
+            let _build = build.enter();
+            info!("optimized");
+            debug!("filtered");
+        });
+        let lines = log.lines();
+        assert_eq!(lines[0]["fields"]["message"], "optimized");
+        assert_eq!(lines[0]["spans"][0]["name"], "build");
+        assert_eq!(lines[0]["spans"][0]["package"], "flipper");
+        assert!(lines
+            .iter()
+            .all(|line| line["fields"]["message"] != "filtered"));
+        assert_eq!("json".parse::<LogFormat>().unwrap(), LogFormat::Json);
+    }
+
+    #[cfg(feature = "extrinsics")]
+    #[test]
+    fn writes_the_spans_of_interleaved_futures() {
+        use tracing::Instrument;
+
+        let (dispatch, log) = json(LevelFilter::INFO);
+        let request = |method: &'static str| {
+            async move {
+                info!("sent");
+                async_std::task::yield_now().await;
+                info!("received");
+            }
+            .instrument(info_span!("rpc", method))
+        };
+        tracing::dispatcher::with_default(&dispatch, || {
+            async_std::task::block_on(futures::future::join(
+                request("state_getRuntimeVersion"),
+                request("system_properties"),
+            ))
+        });
+        let methods = log
+            .lines()
+            .iter()
+            .filter(|line| line["fields"]["message"] != "close")
+            .map(|line| {
+                let method = line["spans"][0]["method"].as_str().expect("in a span");
+                format!("{} {}", line["fields"]["message"].as_str().unwrap(), method)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            methods,
+            vec![
+                "sent state_getRuntimeVersion",
+                "sent system_properties",
+                "received state_getRuntimeVersion",
+                "received system_properties",
+            ]
+        );
+    }
+}
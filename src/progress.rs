@@ -0,0 +1,119 @@
+// Copyright 2018-2020 Parity Technologies (UK) Ltd.
+// This file is part of cargo-contract.
+//
+// cargo-contract is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// cargo-contract is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fmt,
+    sync::{mpsc::Sender, Arc, Mutex},
+};
+
+#[cfg(feature = "extrinsics")]
+use sp_core::H256;
+
+/// A step of a build or of the submission of an extrinsic, reported to the [`ProgressHook`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Progress {
+    /// The build of the `package` started its `step` of `steps`, e.g. `Building cargo project`.
+    BuildStep {
+        package: String,
+        step: usize,
+        steps: usize,
+        message: String,
+    },
+    /// wasm-opt optimized the Wasm of the `package`, with its sizes in kilobytes.
+    Optimized {
+        package: String,
+        original_size: f64,
+        optimized_size: f64,
+    },
+    /// The extrinsic was submitted to the node.
+    #[cfg(feature = "extrinsics")]
+    Broadcast { extrinsic: H256 },
+    /// The extrinsic was included in the block.
+    #[cfg(feature = "extrinsics")]
+    InBlock { extrinsic: H256, block: H256 },
+    /// The block including the extrinsic was finalized.
+    #[cfg(feature = "extrinsics")]
+    Finalized { extrinsic: H256, block: H256 },
+}
+
+/// Receives the progress of the builds of a `ContractBuilder`, or of the extrinsics of an
+/// `api::Client`, e.g. to show progress bars.
+///
+/// Closures taking the `&Progress` are hooks, and so are channel senders of `Progress`.
+pub trait ProgressHook: Send + Sync {
+    fn progress(&self, progress: &Progress);
+}
+
+impl<F: Fn(&Progress) + Send + Sync> ProgressHook for F {
+    fn progress(&self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+impl ProgressHook for Mutex<Sender<Progress>> {
+    fn progress(&self, progress: &Progress) {
+        if let Ok(sender) = self.lock() {
+            // The receiver may have stopped listening, which does not affect the build.
+            let _ = sender.send(progress.clone());
+        }
+    }
+}
+
+/// The hook a build or a connection reports its progress to, if any, shared by the builds of
+/// the contracts of a workspace.
+#[derive(Clone, Default)]
+pub(crate) struct Reporter(Option<Arc<dyn ProgressHook>>);
+
+impl Reporter {
+    pub fn new(hook: impl ProgressHook + 'static) -> Self {
+        Reporter(Some(Arc::new(hook)))
+    }
+
+    /// Reports the progress to the hook, if any.
+    pub fn report(&self, progress: Progress) {
+        if let Some(ref hook) = self.0 {
+            hook.progress(&progress)
+        }
+    }
+}
+
+impl fmt::Debug for Reporter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Reporter")
+            .field(&self.0.as_ref().map(|_| "ProgressHook"))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn reports_the_progress_to_the_hook() {
+        let (sender, receiver) = mpsc::channel();
+        let step = Progress::BuildStep {
+            package: "flipper".to_string(),
+            step: 1,
+            steps: 4,
+            message: "Reporting to the hook".to_string(),
+        };
+        Reporter::new(Mutex::new(sender)).report(step.clone());
+        Reporter::default().report(step.clone());
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![step]);
+    }
+}
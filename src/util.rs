@@ -14,28 +14,111 @@
 // You should have received a copy of the GNU General Public License
 // along with cargo-contract.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::Verbosity;
+use crate::{progress::Reporter, Progress, Verbosity};
 use anyhow::{Context, Result};
+use colored::Colorize;
 use rustc_version::Channel;
+use sha2::{Digest as _, Sha256};
 use std::path::PathBuf;
-use std::{ffi::OsStr, path::Path, process::Command};
+use std::{ffi::OsStr, fs, path::Path, process::Command};
+use walkdir::WalkDir;
 
-/// Check whether the current rust channel is valid: `nightly` is recommended.
+/// Check whether the current rust channel is `nightly`, which rebuilding the standard library
+/// requires.
 pub fn assert_channel() -> Result<()> {
     let meta = rustc_version::version_meta()?;
     match meta.channel {
         Channel::Dev | Channel::Nightly => Ok(()),
         Channel::Stable | Channel::Beta => {
             anyhow::bail!(
-                "cargo-contract cannot build using the {:?} channel. \
-                Switch to nightly. \
-                See https://github.com/paritytech/cargo-contract#build-requires-the-nightly-toolchain",
+                "cargo-contract cannot rebuild the standard library with `-Z build-std` using the \
+                {:?} channel. Switch to nightly, or build without `-Z build-std`. \
+                See https://github.com/paritytech/cargo-contract#toolchains",
                 format!("{:?}", meta.channel).to_lowercase(),
             );
         }
     }
 }
 
+/// Check whether the standard library of the target is installed, for building without
+/// rebuilding it.
+pub fn assert_target_installed(target: &str) -> Result<()> {
+    let rustlib = match rustlib()? {
+        Some(rustlib) => rustlib,
+        None => return Ok(()),
+    };
+    if !rustlib.join(target).exists() {
+        anyhow::bail!(
+            "The {} target of the toolchain is not installed, add it with `rustup target add {}`",
+            target,
+            target
+        )
+    }
+    Ok(())
+}
+
+/// Check whether the `rust-src` component, which rebuilding the standard library requires, is
+/// installed in the layout of the nightlies after `2020-07-30`.
+pub fn assert_rust_src() -> Result<()> {
+    let src = match rustlib()? {
+        Some(rustlib) => rustlib.join("src").join("rust"),
+        None => return Ok(()),
+    };
+    if src.join("library").exists() {
+        return Ok(());
+    }
+    if src.join("src").exists() {
+        anyhow::bail!(
+            "The rust-src component of the toolchain predates the nightly of 2020-07-30, which \
+             `-Z build-std` requires. Update it with `rustup update nightly`"
+        )
+    }
+    anyhow::bail!(
+        "Rebuilding the standard library requires the rust-src component, add it with `rustup \
+         component add rust-src --toolchain nightly`"
+    )
+}
+
+/// The `lib/rustlib` directory of the sysroot of the toolchain, `None` if `rustc` does not
+/// report its sysroot.
+fn rustlib() -> Result<Option<PathBuf>> {
+    let output = Command::new("rustc")
+        .args(["--print", "sysroot"])
+        .output()
+        .context("Failed to execute rustc")?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let sysroot = String::from_utf8_lossy(&output.stdout);
+    Ok(Some(Path::new(sysroot.trim()).join("lib").join("rustlib")))
+}
+
+/// Prints the step of the build of the package, `[step/steps] message`, unless quiet, and
+/// reports it to the progress hook.
+pub(crate) fn print_step(
+    verbosity: Option<Verbosity>,
+    progress: &Reporter,
+    package: &str,
+    step: usize,
+    steps: usize,
+    message: &str,
+) {
+    progress.report(Progress::BuildStep {
+        package: package.to_string(),
+        step,
+        steps,
+        message: message.to_string(),
+    });
+    if let Some(Verbosity::Quiet) = verbosity {
+        return;
+    }
+    println!(
+        " {} {}",
+        format!("[{}/{}]", step, steps).bold(),
+        message.bright_green().bold()
+    );
+}
+
 /// Run cargo with the supplied args
 ///
 /// If successful, returns the stdout bytes
@@ -44,6 +127,7 @@ pub(crate) fn invoke_cargo<I, S, P>(
     args: I,
     working_dir: Option<P>,
     verbosity: Option<Verbosity>,
+    env: &[(&str, &str)],
 ) -> Result<Vec<u8>>
 where
     I: IntoIterator<Item = S> + std::fmt::Debug,
@@ -57,15 +141,20 @@ where
         cmd.current_dir(path);
     }
 
+    cmd.envs(env.iter().copied());
     cmd.arg(command);
     cmd.args(args);
     match verbosity {
         Some(Verbosity::Quiet) => cmd.arg("--quiet"),
         Some(Verbosity::Verbose) => cmd.arg("--verbose"),
+        Some(Verbosity::VeryVerbose) => cmd.arg("-vv"),
         None => &mut cmd,
     };
 
     log::info!("invoking cargo: {:?}", cmd);
+    if let Some(Verbosity::Verbose) | Some(Verbosity::VeryVerbose) = verbosity {
+        println!("   {} {:?}", "Running".bold(), cmd);
+    }
 
     let child = cmd
         // capture the stdout to return from this function as bytes
@@ -85,6 +174,78 @@ where
     }
 }
 
+/// The files of the project in the directory, sorted by path, skipping the `target` directory and
+/// hidden files.
+pub(crate) fn project_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = WalkDir::new(dir)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            entry.depth() == 0 || !(name.starts_with('.') || name == "target")
+        });
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            files.push(entry.into_path());
+        }
+    }
+    Ok(files)
+}
+
+/// Downloads the release archive at the url with `curl`, checks its pinned SHA-256 digest and
+/// unpacks it into the directory with `tar`, naming the released software `what` in the errors.
+pub(crate) fn download_archive(url: &str, sha256: &str, dir: &Path, what: &str) -> Result<()> {
+    fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+    let archive = url.rsplit('/').next().expect("the url names the archive");
+    let path = dir.join(archive);
+    let run = |command: &mut Command, action: String| -> Result<()> {
+        let status = command
+            .status()
+            .context(format!("Failed to {}, is it installed?", action))?;
+        if !status.success() {
+            anyhow::bail!("Failed to {}", action)
+        }
+        Ok(())
+    };
+    eprintln!("Downloading {}", url);
+    run(
+        Command::new("curl")
+            .arg("-sSfL")
+            .arg("-o")
+            .arg(&path)
+            .arg(url),
+        format!("download {} with curl", what),
+    )?;
+    let unpacked = verify_sha256(&path, sha256).and_then(|()| {
+        run(
+            Command::new("tar")
+                .arg("-xzf")
+                .arg(&path)
+                .arg("-C")
+                .arg(dir),
+            format!("unpack {} with tar", what),
+        )
+    });
+    fs::remove_file(&path)?;
+    unpacked
+}
+
+/// Fails unless the SHA-256 digest of the file is the pinned one.
+fn verify_sha256(path: &Path, sha256: &str) -> Result<()> {
+    let digest = format!("{:x}", Sha256::digest(&fs::read(path)?));
+    if digest != sha256 {
+        anyhow::bail!(
+            "The SHA-256 digest {} of {} is not the pinned {}",
+            digest,
+            path.display(),
+            sha256
+        )
+    }
+    Ok(())
+}
+
 /// Returns the base name of the path.
 pub(crate) fn base_name(path: &PathBuf) -> &str {
     path.file_name()
@@ -109,4 +270,16 @@ pub mod tests {
         // catch test panics in order to clean up temp dir which will be very large
         f(tmp_dir.path()).expect("Error executing test with tmp dir")
     }
+
+    #[test]
+    fn verifies_the_pinned_sha256_digest() {
+        with_tmp_dir(|path| {
+            let archive = path.join("release.tar.gz");
+            std::fs::write(&archive, "abc")?;
+            let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+            super::verify_sha256(&archive, sha256)?;
+            assert!(super::verify_sha256(&archive, &"0".repeat(64)).is_err());
+            Ok(())
+        })
+    }
 }
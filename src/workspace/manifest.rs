@@ -26,9 +26,12 @@ use std::{
 use toml::value;
 
 const MANIFEST_FILE: &str = "Cargo.toml";
-const LEGACY_METADATA_PACKAGE_PATH: &str = ".ink/abi_gen";
+pub(crate) const LEGACY_METADATA_PACKAGE_PATH: &str = ".ink/abi_gen";
 const METADATA_PACKAGE_PATH: &str = ".ink/metadata_gen";
 
+/// The sections declaring dependencies, which may have paths.
+const DEPENDENCY_SECTIONS: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+
 /// Path to a Cargo.toml file
 #[derive(Clone, Debug)]
 pub struct ManifestPath {
@@ -253,7 +256,8 @@ impl Manifest {
     /// # Rewrites
     ///
     /// - `[lib]/path`
-    /// - `[dependencies]`
+    /// - `[dependencies]`, `[dev-dependencies]` and `[build-dependencies]`, also of `[target.*]`
+    /// - `[patch.*]` and `[replace]`, so that forked dependencies stay pinned
     ///
     /// Dependencies with package names specified in `exclude_deps` will not be rewritten.
     pub(super) fn rewrite_relative_paths<I, S>(&mut self, exclude_deps: I) -> Result<&mut Self>
@@ -326,14 +330,14 @@ impl Manifest {
         }
 
         // Rewrite any dependency relative paths
-        if let Some(dependencies) = self.toml.get_mut("dependencies") {
-            let exclude = exclude_deps
-                .into_iter()
-                .map(|s| s.as_ref().to_string())
-                .collect::<HashSet<_>>();
+        let exclude = exclude_deps
+            .into_iter()
+            .map(|s| s.as_ref().to_string())
+            .collect::<HashSet<_>>();
+        let rewrite_dependencies = |dependencies: &mut value::Value, section: &str| {
             let table = dependencies
                 .as_table_mut()
-                .ok_or(anyhow::anyhow!("dependencies should be a table"))?;
+                .ok_or(anyhow::anyhow!("[{}] should be a table", section))?;
             for (name, value) in table {
                 let package_name = {
                     let package = value.get("package");
@@ -349,8 +353,51 @@ impl Manifest {
                     }
                 }
             }
+            Ok::<_, anyhow::Error>(())
+        };
+        for section in DEPENDENCY_SECTIONS {
+            if let Some(dependencies) = self.toml.get_mut(*section) {
+                rewrite_dependencies(dependencies, section)?;
+            }
+        }
+        if let Some(targets) = self.toml.get_mut("target").and_then(|t| t.as_table_mut()) {
+            for (target, table) in targets {
+                for section in DEPENDENCY_SECTIONS {
+                    if let Some(dependencies) = table.get_mut(*section) {
+                        rewrite_dependencies(
+                            dependencies,
+                            &format!("target.{}.{}", target, section),
+                        )?;
+                    }
+                }
+            }
+        }
+        // Rewrite `[patch.crates-io]`, `[patch.'https://github.com/...']` etc.
+        if let Some(patches) = self.toml.get_mut("patch").and_then(|p| p.as_table_mut()) {
+            for (source, patch) in patches {
+                rewrite_dependencies(patch, &format!("patch.{}", source))?;
+            }
         }
+        if let Some(replace) = self.toml.get_mut("replace") {
+            rewrite_dependencies(replace, "replace")?;
+        }
+
+        Ok(self)
+    }
 
+    /// Copies the `[patch]` and `[replace]` sections of the root manifest of a virtual
+    /// workspace, unless the manifest has its own, since cargo only reads them from the
+    /// workspace root.
+    ///
+    /// The paths of the root manifest should have been rewritten to absolute paths already.
+    pub(super) fn with_workspace_overrides(&mut self, root: &Manifest) -> Result<&mut Self> {
+        for section in &["patch", "replace"] {
+            if let Some(overrides) = root.toml.get(*section) {
+                if !self.toml.contains_key(*section) {
+                    self.toml.insert(section.to_string(), overrides.clone());
+                }
+            }
+        }
         Ok(self)
     }
 
@@ -405,3 +452,48 @@ fn crate_type_exists(crate_type: &str, crate_types: &value::Array) -> bool {
         .iter()
         .any(|v| v.as_str().map_or(false, |s| s == crate_type))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_the_paths_of_patches_and_replacements() {
+        crate::util::tests::with_tmp_dir(|dir| {
+            fs::create_dir_all(dir.join("contract"))?;
+            fs::write(
+                dir.join("Cargo.toml"),
+                "[workspace]\nmembers = [\"contract\"]\n\n\
+                 [patch.crates-io]\nink_env = { path = \"forks/ink_env\" }\n\n\
+                 [replace]\n\"scale-info:0.6.0\" = { path = \"forks/scale-info\" }\n",
+            )?;
+            fs::write(
+                dir.join("contract/Cargo.toml"),
+                "[package]\nname = \"contract\"\nversion = \"0.1.0\"\n\n\
+                 [lib]\npath = \"lib.rs\"\n\n\
+                 [target.'cfg(unix)'.dev-dependencies]\nhelper = { path = \"../helper\" }\n",
+            )?;
+            let mut root = Manifest::new(&dir.join("Cargo.toml"))?;
+            root.rewrite_relative_paths(Vec::<String>::new())?;
+            let mut contract = Manifest::new(&dir.join("contract/Cargo.toml"))?;
+            contract
+                .rewrite_relative_paths(Vec::<String>::new())?
+                .with_workspace_overrides(&root)?;
+
+            let dir = dir.canonicalize()?;
+            let path = |table: &value::Value| PathBuf::from(table["path"].as_str().unwrap());
+            let toml = &contract.toml;
+            assert_eq!(
+                path(&toml["patch"]["crates-io"]["ink_env"]),
+                dir.join("forks/ink_env")
+            );
+            assert_eq!(
+                path(&toml["replace"]["scale-info:0.6.0"]),
+                dir.join("forks/scale-info")
+            );
+            let helper = &toml["target"]["cfg(unix)"]["dev-dependencies"]["helper"];
+            assert_eq!(path(helper), dir.join("contract/../helper"));
+            Ok(())
+        })
+    }
+}
@@ -18,17 +18,19 @@ mod manifest;
 mod metadata;
 mod profile;
 
+pub(crate) use self::manifest::LEGACY_METADATA_PACKAGE_PATH;
 #[doc(inline)]
 pub use self::{
     manifest::{Manifest, ManifestPath},
     profile::Profile,
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use cargo_metadata::{Metadata as CargoMetadata, Package, PackageId};
 
 use std::{
     collections::HashMap,
+    fs,
     path::{Path, PathBuf},
 };
 
@@ -42,6 +44,9 @@ pub struct Workspace {
     workspace_root: PathBuf,
     root_package: PackageId,
     members: HashMap<PackageId, (Package, Manifest)>,
+    /// The manifest of the workspace root, if it is a virtual manifest without a package.
+    virtual_root: Option<Manifest>,
+    keep_temp_on_failure: bool,
 }
 
 impl Workspace {
@@ -70,10 +75,22 @@ impl Workspace {
             anyhow::bail!("The root package should be a workspace member")
         }
 
+        let root_manifest = metadata.workspace_root.join("Cargo.toml");
+        let is_member = members
+            .values()
+            .any(|(package, _)| package.manifest_path == root_manifest);
+        let virtual_root = if !is_member && root_manifest.exists() {
+            Some(Manifest::new(&root_manifest)?)
+        } else {
+            None
+        };
+
         Ok(Workspace {
             workspace_root: metadata.workspace_root.clone(),
             root_package: root_package.clone(),
             members,
+            virtual_root,
+            keep_temp_on_failure: false,
         })
     }
 
@@ -128,11 +145,15 @@ impl Workspace {
     }
 
     /// Writes the amended manifests to the `target` directory, retaining the workspace directory
-    /// structure, but only with the `Cargo.toml` files.
+    /// structure, but only with the `Cargo.toml` files and the `Cargo.lock`, so the dependencies
+    /// resolve to the locked versions.
     ///
     /// Relative paths will be rewritten to absolute paths from the original workspace root, except
     /// intra-workspace relative dependency paths which will be preserved.
     ///
+    /// The copied members are not part of a workspace, so the `[patch]` and `[replace]` sections
+    /// of a virtual workspace root are copied into their manifests.
+    ///
     /// Returns the paths of the new manifests.
     pub fn write<P: AsRef<Path>>(&mut self, target: P) -> Result<Vec<(PackageId, ManifestPath)>> {
         let exclude_member_package_names = self
@@ -140,6 +161,9 @@ impl Workspace {
             .iter()
             .map(|(_, (p, _))| p.name.clone())
             .collect::<Vec<_>>();
+        if let Some(ref mut root) = self.virtual_root {
+            root.rewrite_relative_paths(Vec::<String>::new())?;
+        }
         let mut new_manifest_paths = Vec::new();
         for (package_id, (package, manifest)) in self.members.iter_mut() {
             // replace the original workspace root with the temporary directory
@@ -148,13 +172,27 @@ impl Workspace {
             let new_manifest = ManifestPath::new(new_path)?;
 
             manifest.rewrite_relative_paths(&exclude_member_package_names)?;
+            if let Some(ref root) = self.virtual_root {
+                manifest.with_workspace_overrides(root)?;
+            }
             manifest.write(&new_manifest)?;
 
             new_manifest_paths.push((package_id.clone(), new_manifest));
         }
+        let lock = self.workspace_root.join("Cargo.lock");
+        if lock.exists() {
+            fs::copy(&lock, target.as_ref().join("Cargo.lock"))?;
+        }
         Ok(new_manifest_paths)
     }
 
+    /// Keep the temporary directory of [`Workspace::using_temp`] if the supplied function fails,
+    /// to inspect the amended manifests.
+    pub fn keep_temp_on_failure(&mut self, keep: bool) -> &mut Self {
+        self.keep_temp_on_failure = keep;
+        self
+    }
+
     /// Copy the workspace with amended manifest files to a temporary directory, executing the
     /// supplied function with the root manifest path before the directory is cleaned up.
     pub fn using_temp<F>(&mut self, f: F) -> Result<()>
@@ -176,6 +214,14 @@ impl Workspace {
                 }
             })
             .expect("root package should be a member of the temp workspace");
-        f(root_manifest_path)
+        let result = f(root_manifest_path);
+        if result.is_err() && self.keep_temp_on_failure {
+            let path = tmp_dir.into_path();
+            return result.context(format!(
+                "Kept the temporary workspace at {}",
+                path.display()
+            ));
+        }
+        result
     }
 }